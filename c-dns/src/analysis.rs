@@ -0,0 +1,43 @@
+//! Aggregations and reports computed from the Q/R items of C-DNS files
+//!
+//! The analyses in this module work directly on the [serialization] types.
+//! They resolve the `*_index` fields against the [`BlockTables`] of each [`Block`] and take care of the timestamp arithmetic.
+//!
+//! [serialization]: crate::serialization
+//! [`BlockTables`]: crate::serialization::BlockTables
+//! [`Block`]: crate::serialization::Block
+
+pub mod amplification;
+pub mod bailiwick;
+pub mod buckets;
+pub mod domains;
+pub mod fingerprint;
+#[cfg(feature = "maxminddb")]
+pub mod geoip;
+pub mod k_anonymity;
+pub mod malformed;
+pub mod repeated;
+#[cfg(feature = "reverse-dns")]
+pub mod reverse_dns;
+pub mod stats;
+pub mod transport;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Number of the bucket of `width` length containing `time`, counted since the POSIX epoch.
+pub(crate) fn bucket_number(time: SystemTime, width: Duration) -> u128 {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_epoch.as_nanos() / width.as_nanos()
+}
+
+/// Start time of the bucket with number `bucket_number`.
+///
+/// This is the inverse of [`bucket_number`].
+pub(crate) fn bucket_start(bucket_number: u128, width: Duration) -> SystemTime {
+    let nanos = bucket_number * width.as_nanos();
+    UNIX_EPOCH
+        + Duration::new(
+            (nanos / 1_000_000_000) as u64,
+            (nanos % 1_000_000_000) as u32,
+        )
+}
@@ -0,0 +1,159 @@
+//! Ratio of response size to query size
+//!
+//! See [`AmplificationReport`].
+
+use crate::serialization::*;
+use crate::tables::signature;
+use crate::utils::{name_to_string, to_std_ip};
+use std::collections::{BTreeMap, HashMap};
+
+/// Accumulated query and response sizes of a group of Q/R items.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AmplificationStats {
+    /// Number of Q/R items with both a query and a response size.
+    pub count: usize,
+    /// Sum of the query sizes.
+    pub query_bytes: u64,
+    /// Sum of the response sizes.
+    pub response_bytes: u64,
+    /// Largest amplification factor of a single Q/R item.
+    pub max_factor: f64,
+}
+
+impl AmplificationStats {
+    /// Overall amplification factor, the total response bytes divided by the total query bytes.
+    pub fn factor(&self) -> f64 {
+        if self.query_bytes == 0 {
+            0.
+        } else {
+            self.response_bytes as f64 / self.query_bytes as f64
+        }
+    }
+
+    fn add(&mut self, query_size: u16, response_size: u16) {
+        self.count += 1;
+        self.query_bytes += u64::from(query_size);
+        self.response_bytes += u64::from(response_size);
+        let factor = f64::from(response_size) / f64::from(query_size);
+        if factor > self.max_factor {
+            self.max_factor = factor;
+        }
+    }
+}
+
+/// Amplification factors per query type, server, and query name.
+///
+/// The amplification factor of a Q/R item is its `response_size` divided by its `query_size`.
+/// Only Q/R items recording both sizes, with a non-zero query size, are considered.
+#[derive(Debug, Clone, Default)]
+pub struct AmplificationReport {
+    /// Statistics over all considered Q/R items.
+    pub total: AmplificationStats,
+    /// Statistics per TYPE of the first Question.
+    pub by_qtype: BTreeMap<u16, AmplificationStats>,
+    /// Statistics per server address.
+    pub by_server: HashMap<std::net::IpAddr, AmplificationStats>,
+    /// Statistics per QNAME of the first Question.
+    ///
+    /// Names are in presentation format if they can be decoded, otherwise the raw bytes are shown.
+    pub by_name: HashMap<String, AmplificationStats>,
+}
+
+impl AmplificationReport {
+    /// Compute the report over all blocks of `file`.
+    pub fn from_file(file: &File) -> Self {
+        let mut res = Self::default();
+        for (block, _) in file.iter_blocks() {
+            res.add_block(block);
+        }
+        res
+    }
+
+    /// Add all Q/R items of `block` to the report.
+    pub fn add_block(&mut self, block: &Block) {
+        let block_tables = match &block.block_tables {
+            Some(block_tables) => block_tables,
+            None => return,
+        };
+        for query_response in block.query_responses.as_deref().unwrap_or(&[]) {
+            let (query_size, response_size) =
+                match (query_response.query_size, query_response.response_size) {
+                    (Some(query_size), Some(response_size)) if query_size > 0 => {
+                        (query_size, response_size)
+                    }
+                    _ => continue,
+                };
+            self.total.add(query_size, response_size);
+
+            let sig = signature(block_tables, query_response);
+            let qtype = sig
+                .and_then(|sig| {
+                    block_tables
+                        .classtype
+                        .as_ref()?
+                        .get(sig.query_classtype_index?)
+                })
+                .map(|classtype| u16::from(classtype.type_));
+            if let Some(qtype) = qtype {
+                self.by_qtype
+                    .entry(qtype)
+                    .or_default()
+                    .add(query_size, response_size);
+            }
+
+            let server = sig.and_then(|sig| {
+                let ip = block_tables
+                    .ip_address
+                    .as_ref()?
+                    .get(sig.server_address_index?)?;
+                to_std_ip(
+                    ip,
+                    sig.qr_transport_flags
+                        .as_ref()
+                        .map(|flags| flags.ip_version()),
+                )
+            });
+            if let Some(server) = server {
+                self.by_server
+                    .entry(server)
+                    .or_default()
+                    .add(query_size, response_size);
+            }
+
+            let name = block_tables
+                .name_rdata
+                .as_ref()
+                .zip(query_response.query_name_index)
+                .and_then(|(name_rdata, idx)| name_rdata.get(idx));
+            if let Some(name) = name {
+                self.by_name
+                    .entry(name_to_string(name))
+                    .or_default()
+                    .add(query_size, response_size);
+            }
+        }
+    }
+
+    /// Query names with an amplification factor of at least `threshold`.
+    ///
+    /// Only names with at least `min_count` considered Q/R items are returned, to filter out one-off outliers.
+    /// The result is sorted by descending amplification factor.
+    pub fn high_amplification_names(
+        &self,
+        threshold: f64,
+        min_count: usize,
+    ) -> Vec<(&str, &AmplificationStats)> {
+        let mut names: Vec<_> = self
+            .by_name
+            .iter()
+            .filter(|(_, stats)| stats.count >= min_count && stats.factor() >= threshold)
+            .map(|(name, stats)| (&**name, stats))
+            .collect();
+        names.sort_by(|a, b| {
+            b.1.factor()
+                .total_cmp(&a.1.factor())
+                .then_with(|| a.0.cmp(b.0))
+        });
+        names
+    }
+}
@@ -0,0 +1,148 @@
+//! SERVFAIL and NXDOMAIN rates per response bailiwick
+//!
+//! See [`BailiwickReport`].
+
+use crate::serialization::*;
+use crate::tables::signature;
+use crate::utils::name_to_string;
+use std::collections::HashMap;
+
+/// RCODE of a successful response.
+const RCODE_NOERROR: u16 = 0;
+/// RCODE of a response indicating a server failure.
+const RCODE_SERVFAIL: u16 = 2;
+/// RCODE of a response indicating that the name does not exist.
+const RCODE_NXDOMAIN: u16 = 3;
+
+/// Response codes of all responses within one bailiwick.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BailiwickCounts {
+    /// Number of responses with a recorded RCODE.
+    pub responses: usize,
+    /// Number of NOERROR responses.
+    pub noerror: usize,
+    /// Number of SERVFAIL responses.
+    pub servfail: usize,
+    /// Number of NXDOMAIN responses.
+    pub nxdomain: usize,
+    /// Number of responses with any other RCODE.
+    pub other: usize,
+}
+
+impl BailiwickCounts {
+    /// Fraction of SERVFAIL responses, between 0 and 1.
+    pub fn servfail_rate(&self) -> f64 {
+        self.rate(self.servfail)
+    }
+
+    /// Fraction of NXDOMAIN responses, between 0 and 1.
+    pub fn nxdomain_rate(&self) -> f64 {
+        self.rate(self.nxdomain)
+    }
+
+    fn rate(&self, count: usize) -> f64 {
+        if self.responses == 0 {
+            0.
+        } else {
+            count as f64 / self.responses as f64
+        }
+    }
+
+    fn add(&mut self, rcode: u16) {
+        self.responses += 1;
+        match rcode {
+            RCODE_NOERROR => self.noerror += 1,
+            RCODE_SERVFAIL => self.servfail += 1,
+            RCODE_NXDOMAIN => self.nxdomain += 1,
+            _ => self.other += 1,
+        }
+    }
+}
+
+/// Response failure rates per zone.
+///
+/// The zone of a Q/R item is the owner name of the response bailiwick, referenced by [`ResponseProcessingData.bailiwick_index`].
+/// Recursive resolvers record the bailiwick of responses received from authoritative servers.
+/// A high SERVFAIL rate for a zone therefore hints at a broken delegation.
+///
+/// Only Q/R items with a Response and a recorded `response_rcode` are counted.
+#[derive(Debug, Clone, Default)]
+pub struct BailiwickReport {
+    /// Counts per zone, in presentation format.
+    pub zones: HashMap<String, BailiwickCounts>,
+    /// Counts of responses without a recorded bailiwick.
+    pub without_bailiwick: BailiwickCounts,
+}
+
+impl BailiwickReport {
+    /// Compute the report over all blocks of `file`.
+    pub fn from_file(file: &File) -> Self {
+        let mut res = Self::default();
+        for (block, _) in file.iter_blocks() {
+            res.add_block(block);
+        }
+        res
+    }
+
+    /// Add all Q/R items of `block` to the report.
+    pub fn add_block(&mut self, block: &Block) {
+        let block_tables = match &block.block_tables {
+            Some(block_tables) => block_tables,
+            None => return,
+        };
+        for query_response in block.query_responses.as_deref().unwrap_or(&[]) {
+            let sig = match signature(block_tables, query_response) {
+                Some(sig) => sig,
+                None => continue,
+            };
+            let has_response = sig
+                .qr_sig_flags
+                .is_none_or(|flags| flags.contains(QueryResponseFlags::HasResponse));
+            let rcode = match sig.response_rcode {
+                Some(rcode) if has_response => rcode,
+                _ => continue,
+            };
+
+            let zone = query_response
+                .response_processing_data
+                .as_ref()
+                .and_then(|data| data.bailiwick_index)
+                .and_then(|idx| block_tables.name_rdata.as_ref()?.get(idx));
+            match zone {
+                Some(zone) => self
+                    .zones
+                    .entry(name_to_string(zone))
+                    .or_default()
+                    .add(rcode),
+                None => self.without_bailiwick.add(rcode),
+            }
+        }
+    }
+
+    /// Zones whose SERVFAIL or NXDOMAIN rate is at least `threshold`.
+    ///
+    /// Only zones with at least `min_responses` responses are returned.
+    /// The result is sorted by descending SERVFAIL rate, then by descending NXDOMAIN rate.
+    pub fn failing_zones(
+        &self,
+        threshold: f64,
+        min_responses: usize,
+    ) -> Vec<(&str, &BailiwickCounts)> {
+        let mut zones: Vec<_> = self
+            .zones
+            .iter()
+            .filter(|(_, counts)| {
+                counts.responses >= min_responses
+                    && (counts.servfail_rate() >= threshold || counts.nxdomain_rate() >= threshold)
+            })
+            .map(|(zone, counts)| (&**zone, counts))
+            .collect();
+        zones.sort_by(|a, b| {
+            b.1.servfail_rate()
+                .total_cmp(&a.1.servfail_rate())
+                .then_with(|| b.1.nxdomain_rate().total_cmp(&a.1.nxdomain_rate()))
+                .then_with(|| a.0.cmp(b.0))
+        });
+        zones
+    }
+}
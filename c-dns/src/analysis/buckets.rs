@@ -0,0 +1,192 @@
+//! Group Q/R items into fixed time buckets
+//!
+//! See [`File::time_buckets`] and [`TimeBuckets`].
+
+use crate::analysis::{bucket_number, bucket_start};
+use crate::serialization::*;
+use crate::tables::signature_flags;
+use crate::utils::absolute_time;
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::time::{Duration, SystemTime};
+
+/// Aggregated values of all Q/R items within one time bucket.
+///
+/// A bucket covers the half-open interval `start..start + width`.
+/// Bucket boundaries are aligned to multiples of `width` since the POSIX epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeBucket {
+    /// Start of the bucket (inclusive).
+    pub start: SystemTime,
+    /// Length of the bucket.
+    pub width: Duration,
+    /// Number of Q/R items in the bucket.
+    pub query_responses: usize,
+    /// Number of Q/R items which contain a Query.
+    pub queries: usize,
+    /// Number of Q/R items which contain a Response.
+    pub responses: usize,
+    /// Sum of all recorded Query sizes.
+    pub query_bytes: u64,
+    /// Sum of all recorded Response sizes.
+    pub response_bytes: u64,
+}
+
+impl TimeBucket {
+    fn new(start: SystemTime, width: Duration) -> Self {
+        Self {
+            start,
+            width,
+            query_responses: 0,
+            queries: 0,
+            responses: 0,
+            query_bytes: 0,
+            response_bytes: 0,
+        }
+    }
+
+    /// End of the bucket (exclusive).
+    pub fn end(&self) -> SystemTime {
+        self.start + self.width
+    }
+
+    /// Average number of Queries per second in this bucket.
+    pub fn queries_per_second(&self) -> f64 {
+        self.queries as f64 / self.width.as_secs_f64()
+    }
+
+    /// Average number of Q/R items per second in this bucket.
+    pub fn query_responses_per_second(&self) -> f64 {
+        self.query_responses as f64 / self.width.as_secs_f64()
+    }
+
+    fn add(&mut self, block_tables: Option<&BlockTables>, query_response: &QueryResponse) {
+        self.query_responses += 1;
+        if let Some(block_tables) = block_tables {
+            let flags = signature_flags(block_tables, query_response);
+            if flags.contains(QueryResponseFlags::HasQuery) {
+                self.queries += 1;
+            }
+            if flags.contains(QueryResponseFlags::HasResponse) {
+                self.responses += 1;
+            }
+        }
+        self.query_bytes += u64::from(query_response.query_size.unwrap_or(0));
+        self.response_bytes += u64::from(query_response.response_size.unwrap_or(0));
+    }
+}
+
+impl File {
+    /// Group all Q/R items of the file into buckets of `width` length.
+    ///
+    /// See [`TimeBuckets`] for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is zero.
+    pub fn time_buckets(
+        &self,
+        width: Duration,
+    ) -> TimeBuckets<'_, impl Iterator<Item = (&'_ Block, &'_ BlockParameters)>> {
+        TimeBuckets::new(self.iter_blocks(), width)
+    }
+}
+
+/// Iterator adapter grouping Q/R items into fixed time buckets.
+///
+/// The adapter consumes `(Block, BlockParameters)` pairs, as produced by [`File::iter_blocks`].
+/// The absolute timestamp of each Q/R item is computed from [`BlockPreamble.earliest_time`] and the item's `time_offset`.
+/// Q/R items without an absolute timestamp are skipped.
+///
+/// Q/R items within a [`Block`] are not required to be in chronological order.
+/// Buckets are yielded in chronological order once no later [`Block`] can contribute to them anymore.
+/// This requires the blocks themselves to be ordered by their earliest time.
+/// Otherwise a later block can produce a second bucket with the same start time.
+///
+/// Buckets without any Q/R items are not yielded.
+pub struct TimeBuckets<'a, I>
+where
+    I: Iterator<Item = (&'a Block, &'a BlockParameters)>,
+{
+    blocks: Peekable<I>,
+    width: Duration,
+    /// Buckets which may still receive items, keyed by the bucket number since the epoch.
+    pending: BTreeMap<u128, TimeBucket>,
+    /// All buckets starting before this bucket number are complete.
+    complete_before: u128,
+}
+
+impl<'a, I> TimeBuckets<'a, I>
+where
+    I: Iterator<Item = (&'a Block, &'a BlockParameters)>,
+{
+    /// Create a new adapter with buckets of `width` length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is zero.
+    pub fn new(blocks: I, width: Duration) -> Self {
+        assert!(!width.is_zero(), "Bucket width must be larger than zero");
+        Self {
+            blocks: blocks.peekable(),
+            width,
+            pending: BTreeMap::new(),
+            complete_before: 0,
+        }
+    }
+
+    fn add_block(&mut self, block: &Block, block_parameters: &BlockParameters) {
+        let earliest_time = block.block_preamble.earliest_time;
+        let block_tables = block.block_tables.as_ref();
+        for query_response in block.query_responses.as_deref().unwrap_or(&[]) {
+            let time =
+                match absolute_time(earliest_time, query_response.time_offset, block_parameters) {
+                    Some(time) => time,
+                    None => continue,
+                };
+            let width = self.width;
+            let number = bucket_number(time, width);
+            let start = bucket_start(number, width);
+            self.pending
+                .entry(number)
+                .or_insert_with(|| TimeBucket::new(start, width))
+                .add(block_tables, query_response);
+        }
+    }
+}
+
+impl<'a, I> Iterator for TimeBuckets<'a, I>
+where
+    I: Iterator<Item = (&'a Block, &'a BlockParameters)>,
+{
+    type Item = TimeBucket;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.pending.first_entry() {
+                if *entry.key() < self.complete_before {
+                    return Some(entry.remove());
+                }
+            }
+
+            let (block, block_parameters) = match self.blocks.next() {
+                Some(block) => block,
+                None => return self.pending.pop_first().map(|(_, bucket)| bucket),
+            };
+            self.add_block(block, block_parameters);
+
+            // All items of the next block are at or after its earliest time.
+            // Buckets before the one containing this time are therefore complete.
+            self.complete_before = match self.blocks.peek() {
+                Some((block, block_parameters)) => {
+                    match absolute_time(block.block_preamble.earliest_time, None, block_parameters)
+                    {
+                        Some(time) => bucket_number(time, self.width).max(self.complete_before),
+                        None => self.complete_before,
+                    }
+                }
+                None => u128::MAX,
+            };
+        }
+    }
+}
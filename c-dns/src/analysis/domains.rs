@@ -0,0 +1,199 @@
+//! Roll up statistics by top-level domain and registrable domain
+//!
+//! See [`DomainHierarchy`] and [`DomainAggregation`].
+
+use crate::serialization::*;
+use crate::tables::{signature, signature_flags};
+use std::collections::HashMap;
+
+/// RCODE of a response indicating that the name does not exist.
+const RCODE_NXDOMAIN: u16 = 3;
+
+/// Map domain names onto their top-level domain and registrable domain.
+///
+/// Without a public suffix list the last label of a name is its top-level domain and the last two labels are its registrable domain.
+/// This is wrong for multi-label public suffixes like `co.uk.`, where the registrable domain has three labels.
+///
+/// With the `publicsuffix` feature a public suffix list can be provided using [`DomainHierarchy::with_public_suffix_list`].
+/// The public suffix of a name then takes the place of the top-level domain, and the registrable domain is the public suffix plus one label.
+///
+/// All names are expected in presentation format with a trailing dot, as produced by [`NameOrRdata::to_domain_name`].
+#[derive(Debug, Default)]
+pub struct DomainHierarchy {
+    #[cfg(feature = "publicsuffix")]
+    public_suffix_list: Option<publicsuffix::List>,
+}
+
+impl DomainHierarchy {
+    /// Create a hierarchy using the last one and two labels of a name.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a hierarchy using the rules of a public suffix list.
+    ///
+    /// The list can be parsed from the data published at <https://publicsuffix.org/list/>.
+    #[cfg(feature = "publicsuffix")]
+    pub fn with_public_suffix_list(public_suffix_list: publicsuffix::List) -> Self {
+        Self {
+            public_suffix_list: Some(public_suffix_list),
+        }
+    }
+
+    /// The top-level domain or public suffix of `name`.
+    ///
+    /// Returns [`None`] for the root name.
+    pub fn tld<'a>(&self, name: &'a str) -> Option<&'a str> {
+        #[cfg(feature = "publicsuffix")]
+        if let Some(public_suffix_list) = &self.public_suffix_list {
+            use publicsuffix::Psl;
+            let suffix = public_suffix_list.suffix(name.as_bytes())?;
+            return Some(&name[name.len() - suffix.as_bytes().len()..]);
+        }
+
+        Self::last_labels(name, 1)
+    }
+
+    /// The registrable domain of `name`, one label below the [top-level domain](Self::tld).
+    ///
+    /// Returns [`None`] if `name` has no label below the top-level domain.
+    pub fn registrable_domain<'a>(&self, name: &'a str) -> Option<&'a str> {
+        #[cfg(feature = "publicsuffix")]
+        if let Some(public_suffix_list) = &self.public_suffix_list {
+            use publicsuffix::Psl;
+            let domain = public_suffix_list.domain(name.as_bytes())?;
+            return Some(&name[name.len() - domain.as_bytes().len()..]);
+        }
+
+        Self::last_labels(name, 2)
+    }
+
+    /// The last `count` labels of `name` including the trailing dot.
+    fn last_labels(name: &str, count: usize) -> Option<&str> {
+        let trimmed = name.strip_suffix('.').unwrap_or(name);
+        if trimmed.is_empty() {
+            return None;
+        }
+        let mut start = trimmed.len();
+        for _ in 0..count {
+            if start == 0 {
+                return None;
+            }
+            start = trimmed[..start - 1].rfind('.').map_or(0, |pos| pos + 1);
+        }
+        Some(&name[start..])
+    }
+}
+
+/// Traffic counters for one domain.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DomainCounts {
+    /// Number of Q/R items.
+    pub query_responses: usize,
+    /// Number of Q/R items which contain a Query.
+    pub queries: usize,
+    /// Number of Q/R items with an NXDOMAIN Response.
+    pub nxdomain: usize,
+    /// Sum of all recorded Query sizes.
+    pub query_bytes: u64,
+    /// Sum of all recorded Response sizes.
+    pub response_bytes: u64,
+}
+
+/// Q/R statistics rolled up by top-level domain and registrable domain.
+///
+/// The QNAME of each Q/R item is mapped using a [`DomainHierarchy`].
+/// Names are lowercased before the mapping, so the keys are in lowercase presentation format.
+#[derive(Debug, Clone, Default)]
+pub struct DomainAggregation {
+    /// Counters per top-level domain or public suffix.
+    pub by_tld: HashMap<String, DomainCounts>,
+    /// Counters per registrable domain.
+    pub by_registrable_domain: HashMap<String, DomainCounts>,
+    /// Number of Q/R items without a QNAME or with a QNAME which cannot be decoded.
+    pub unknown_name: usize,
+}
+
+impl DomainAggregation {
+    /// Compute the aggregation over all blocks of `file`.
+    pub fn from_file(file: &File, hierarchy: &DomainHierarchy) -> Self {
+        let mut res = Self::default();
+        for (block, _) in file.iter_blocks() {
+            res.add_block(block, hierarchy);
+        }
+        res
+    }
+
+    /// Add all Q/R items of `block` to the aggregation.
+    pub fn add_block(&mut self, block: &Block, hierarchy: &DomainHierarchy) {
+        let block_tables = block.block_tables.as_ref();
+        for query_response in block.query_responses.as_deref().unwrap_or(&[]) {
+            let name = block_tables
+                .and_then(|tables| {
+                    tables
+                        .name_rdata
+                        .as_ref()?
+                        .get(query_response.query_name_index?)
+                })
+                .and_then(|name| name.to_domain_name().ok())
+                .map(|name| name.to_string());
+            let (name, block_tables) = match (name, block_tables) {
+                (Some(name), Some(block_tables)) => (name.to_ascii_lowercase(), block_tables),
+                _ => {
+                    self.unknown_name += 1;
+                    continue;
+                }
+            };
+
+            let flags = signature_flags(block_tables, query_response);
+            let nxdomain = signature(block_tables, query_response)
+                .and_then(|sig| sig.response_rcode)
+                == Some(RCODE_NXDOMAIN);
+            let add = |counts: &mut DomainCounts| {
+                counts.query_responses += 1;
+                if flags.contains(QueryResponseFlags::HasQuery) {
+                    counts.queries += 1;
+                }
+                if nxdomain {
+                    counts.nxdomain += 1;
+                }
+                counts.query_bytes += u64::from(query_response.query_size.unwrap_or(0));
+                counts.response_bytes += u64::from(query_response.response_size.unwrap_or(0));
+            };
+
+            if let Some(tld) = hierarchy.tld(&name) {
+                add(self.by_tld.entry(tld.to_string()).or_default());
+            }
+            if let Some(domain) = hierarchy.registrable_domain(&name) {
+                add(self
+                    .by_registrable_domain
+                    .entry(domain.to_string())
+                    .or_default());
+            }
+        }
+    }
+
+    /// The `n` top-level domains with the most Q/R items, in descending order.
+    pub fn top_tlds(&self, n: usize) -> Vec<(&str, &DomainCounts)> {
+        Self::top(&self.by_tld, n)
+    }
+
+    /// The `n` registrable domains with the most Q/R items, in descending order.
+    pub fn top_registrable_domains(&self, n: usize) -> Vec<(&str, &DomainCounts)> {
+        Self::top(&self.by_registrable_domain, n)
+    }
+
+    fn top(map: &HashMap<String, DomainCounts>, n: usize) -> Vec<(&str, &DomainCounts)> {
+        let mut entries: Vec<_> = map
+            .iter()
+            .map(|(domain, counts)| (&**domain, counts))
+            .collect();
+        entries.sort_by(|a, b| {
+            b.1.query_responses
+                .cmp(&a.1.query_responses)
+                .then_with(|| a.0.cmp(b.0))
+        });
+        entries.truncate(n);
+        entries
+    }
+}
@@ -0,0 +1,349 @@
+//! Fingerprint clients by the traits of their queries
+//!
+//! See [`ClientFingerprint`] and [`FingerprintReport`].
+
+use crate::serialization::*;
+use crate::tables::signature;
+use crate::utils::to_std_ip;
+use crate::{IpVersion, Transport};
+use enumset::EnumSet;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// The query flags of [`DNSFlags`] with their mnemonic.
+const QUERY_FLAGS: [(DNSFlags, &str); 8] = [
+    (DNSFlags::QueryAa, "AA"),
+    (DNSFlags::QueryTc, "TC"),
+    (DNSFlags::QueryRd, "RD"),
+    (DNSFlags::QueryRa, "RA"),
+    (DNSFlags::QueryZ, "Z"),
+    (DNSFlags::QueryAd, "AD"),
+    (DNSFlags::QueryCd, "CD"),
+    (DNSFlags::QueryDo, "DO"),
+];
+
+/// Query TYPEs with their own entry in the [feature vector](ClientFingerprint::feature_vector).
+const FEATURE_QTYPES: [(u16, &str); 10] = [
+    (1, "A"),
+    (2, "NS"),
+    (12, "PTR"),
+    (15, "MX"),
+    (16, "TXT"),
+    (28, "AAAA"),
+    (33, "SRV"),
+    (43, "DS"),
+    (48, "DNSKEY"),
+    (65, "HTTPS"),
+];
+
+/// Transports with their own entry in the [feature vector](ClientFingerprint::feature_vector).
+const FEATURE_TRANSPORTS: [Transport; 4] = [
+    Transport::Udp,
+    Transport::Tcp,
+    Transport::Tls,
+    Transport::Https,
+];
+
+/// Minimal share of a query TYPE to be included in the [`ClientFingerprint::signature`].
+const SIGNATURE_QTYPE_SHARE: f64 = 0.1;
+
+/// Distribution of query traits of a single client.
+///
+/// Stub resolver implementations differ in their EDNS parameters, the DNS header flags they set, the transports they use, and the mix of query types.
+/// The combination of these traits identifies populations of clients running the same software.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientFingerprint {
+    /// Number of queries of the client.
+    pub queries: usize,
+    /// EDNS version of the queries, [`None`] for queries without EDNS.
+    pub edns_versions: BTreeMap<Option<u8>, usize>,
+    /// EDNS UDP payload size of the queries, [`None`] for queries without EDNS.
+    pub udp_sizes: BTreeMap<Option<u16>, usize>,
+    /// IP version and transport of the queries.
+    pub transports: BTreeMap<(IpVersion, Transport), usize>,
+    /// DNS header flags of the queries, only containing the query flags.
+    pub query_flags: BTreeMap<EnumSet<DNSFlags>, usize>,
+    /// TYPE of the first Question of the queries.
+    pub qtypes: BTreeMap<u16, usize>,
+}
+
+impl ClientFingerprint {
+    /// Names of the entries of [`ClientFingerprint::feature_vector`].
+    pub fn feature_names() -> Vec<String> {
+        let mut names = vec![
+            "edns".to_string(),
+            "udp_size".to_string(),
+            "ipv6".to_string(),
+        ];
+        names.extend(
+            QUERY_FLAGS
+                .iter()
+                .map(|(_, name)| format!("flag_{}", name.to_ascii_lowercase())),
+        );
+        names.extend(
+            FEATURE_TRANSPORTS.iter().map(|transport| {
+                format!("transport_{}", transport.to_string().to_ascii_lowercase())
+            }),
+        );
+        names.extend(
+            FEATURE_QTYPES
+                .iter()
+                .map(|(_, name)| format!("qtype_{}", name.to_ascii_lowercase())),
+        );
+        names.push("qtype_other".to_string());
+        names
+    }
+
+    /// Numerical representation of the fingerprint.
+    ///
+    /// All entries are between 0 and 1.
+    /// Most entries are the fraction of queries with a trait, for example the fraction of queries with EDNS or with the RD flag.
+    /// The `udp_size` entry is the average EDNS UDP payload size of queries with EDNS, divided by 65535.
+    /// The meaning of each entry is given by [`ClientFingerprint::feature_names`].
+    ///
+    /// The vectors of different clients can be compared with any distance metric or clustering algorithm.
+    pub fn feature_vector(&self) -> Vec<f64> {
+        let share = |count: usize| {
+            if self.queries == 0 {
+                0.
+            } else {
+                count as f64 / self.queries as f64
+            }
+        };
+
+        let mut vector = Vec::with_capacity(Self::feature_names().len());
+        let edns_queries: usize = self
+            .udp_sizes
+            .iter()
+            .filter(|(size, _)| size.is_some())
+            .map(|(_, count)| count)
+            .sum();
+        vector.push(share(edns_queries));
+        let udp_size_sum: u64 = self
+            .udp_sizes
+            .iter()
+            .filter_map(|(size, count)| Some(u64::from((*size)?) * *count as u64))
+            .sum();
+        vector.push(if edns_queries == 0 {
+            0.
+        } else {
+            udp_size_sum as f64 / edns_queries as f64 / f64::from(u16::MAX)
+        });
+        vector.push(share(
+            self.transports
+                .iter()
+                .filter(|((ip_version, _), _)| *ip_version == IpVersion::Ipv6)
+                .map(|(_, count)| count)
+                .sum(),
+        ));
+        for (flag, _) in QUERY_FLAGS {
+            vector.push(share(
+                self.query_flags
+                    .iter()
+                    .filter(|(flags, _)| flags.contains(flag))
+                    .map(|(_, count)| count)
+                    .sum(),
+            ));
+        }
+        for transport in FEATURE_TRANSPORTS {
+            vector.push(share(
+                self.transports
+                    .iter()
+                    .filter(|((_, trans), _)| *trans == transport)
+                    .map(|(_, count)| count)
+                    .sum(),
+            ));
+        }
+        let mut other_qtypes = self.qtypes.values().sum();
+        for (qtype, _) in FEATURE_QTYPES {
+            let count = self.qtypes.get(&qtype).copied().unwrap_or(0);
+            other_qtypes -= count;
+            vector.push(share(count));
+        }
+        vector.push(share(other_qtypes));
+        vector
+    }
+
+    /// Compact textual signature of the most common traits.
+    ///
+    /// The signature consists of the most common EDNS version and UDP size, the most common query flags, the most common transport, and all query TYPEs making up at least 10% of the queries.
+    /// Clients running the same software usually share the same signature, for example `edns0/1232 RD|AD IPv4/UDP A,AAAA,HTTPS`.
+    pub fn signature(&self) -> String {
+        let mut signature = String::new();
+
+        match most_common(&self.edns_versions).flatten() {
+            Some(version) => {
+                let _ = write!(signature, "edns{}", version);
+                if let Some(udp_size) = most_common(&self.udp_sizes).flatten() {
+                    let _ = write!(signature, "/{}", udp_size);
+                }
+            }
+            None => signature.push_str("no-edns"),
+        }
+
+        signature.push(' ');
+        let flags = most_common(&self.query_flags).unwrap_or_default();
+        let flags: Vec<_> = QUERY_FLAGS
+            .iter()
+            .filter(|(flag, _)| flags.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect();
+        if flags.is_empty() {
+            signature.push('-');
+        } else {
+            signature.push_str(&flags.join("|"));
+        }
+
+        signature.push(' ');
+        match most_common(&self.transports) {
+            Some((ip_version, transport)) => {
+                let _ = write!(signature, "{}/{}", ip_version, transport);
+            }
+            None => signature.push('-'),
+        }
+
+        signature.push(' ');
+        let total: usize = self.qtypes.values().sum();
+        let qtypes: Vec<_> = self
+            .qtypes
+            .iter()
+            .filter(|(_, count)| **count as f64 >= total as f64 * SIGNATURE_QTYPE_SHARE)
+            .map(|(qtype, _)| {
+                FEATURE_QTYPES
+                    .iter()
+                    .find(|(known, _)| known == qtype)
+                    .map_or_else(|| format!("TYPE{}", qtype), |(_, name)| name.to_string())
+            })
+            .collect();
+        if qtypes.is_empty() {
+            signature.push('-');
+        } else {
+            signature.push_str(&qtypes.join(","));
+        }
+
+        signature
+    }
+
+    fn add(&mut self, sig: &QueryResponseSignature, qtype: Option<u16>) {
+        self.queries += 1;
+        let has_edns = sig
+            .qr_sig_flags
+            .map_or(sig.query_edns_version.is_some(), |flags| {
+                flags.contains(QueryResponseFlags::QueryHasOpt)
+            });
+        *self
+            .edns_versions
+            .entry(sig.query_edns_version.filter(|_| has_edns))
+            .or_default() += 1;
+        *self
+            .udp_sizes
+            .entry(sig.query_udp_size.filter(|_| has_edns))
+            .or_default() += 1;
+        if let Some(transport_flags) = &sig.qr_transport_flags {
+            *self
+                .transports
+                .entry((
+                    transport_flags.ip_version(),
+                    transport_flags.transport_protocol(),
+                ))
+                .or_default() += 1;
+        }
+        if let Some(dns_flags) = sig.qr_dns_flags {
+            let query_flags: EnumSet<DNSFlags> =
+                QUERY_FLAGS.iter().map(|(flag, _)| *flag).collect();
+            *self.query_flags.entry(dns_flags & query_flags).or_default() += 1;
+        }
+        if let Some(qtype) = qtype {
+            *self.qtypes.entry(qtype).or_default() += 1;
+        }
+    }
+}
+
+/// The key with the highest count, preferring the smaller key on ties.
+fn most_common<K: Copy + Ord>(map: &BTreeMap<K, usize>) -> Option<K> {
+    map.iter()
+        .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+        .map(|(key, _)| *key)
+}
+
+/// Fingerprints of all clients in a capture.
+///
+/// Only Q/R items containing a Query and with a client address are considered.
+#[derive(Debug, Clone, Default)]
+pub struct FingerprintReport {
+    /// Fingerprint per client address.
+    pub clients: HashMap<std::net::IpAddr, ClientFingerprint>,
+}
+
+impl FingerprintReport {
+    /// Compute the fingerprints over all blocks of `file`.
+    pub fn from_file(file: &File) -> Self {
+        let mut res = Self::default();
+        for (block, _) in file.iter_blocks() {
+            res.add_block(block);
+        }
+        res
+    }
+
+    /// Add all Q/R items of `block` to the fingerprints.
+    pub fn add_block(&mut self, block: &Block) {
+        let block_tables = match &block.block_tables {
+            Some(block_tables) => block_tables,
+            None => return,
+        };
+        for query_response in block.query_responses.as_deref().unwrap_or(&[]) {
+            let sig = match signature(block_tables, query_response) {
+                Some(sig) => sig,
+                None => continue,
+            };
+            if !sig
+                .qr_sig_flags
+                .is_none_or(|flags| flags.contains(QueryResponseFlags::HasQuery))
+            {
+                continue;
+            }
+            let client = block_tables
+                .ip_address
+                .as_ref()
+                .zip(query_response.client_address_index)
+                .and_then(|(ip_address, idx)| ip_address.get(idx))
+                .and_then(|ip| {
+                    to_std_ip(
+                        ip,
+                        sig.qr_transport_flags
+                            .as_ref()
+                            .map(|flags| flags.ip_version()),
+                    )
+                });
+            let client = match client {
+                Some(client) => client,
+                None => continue,
+            };
+            let qtype = block_tables
+                .classtype
+                .as_ref()
+                .zip(sig.query_classtype_index)
+                .and_then(|(classtype, idx)| classtype.get(idx))
+                .map(|classtype| u16::from(classtype.type_));
+
+            self.clients.entry(client).or_default().add(sig, qtype);
+        }
+    }
+
+    /// Group the clients by their [`ClientFingerprint::signature`].
+    ///
+    /// Each group is sorted by address.
+    pub fn populations(&self) -> BTreeMap<String, Vec<std::net::IpAddr>> {
+        let mut populations: BTreeMap<String, Vec<std::net::IpAddr>> = BTreeMap::new();
+        for (client, fingerprint) in &self.clients {
+            populations
+                .entry(fingerprint.signature())
+                .or_default()
+                .push(*client);
+        }
+        for clients in populations.values_mut() {
+            clients.sort();
+        }
+        populations
+    }
+}
@@ -0,0 +1,152 @@
+//! Enrich client and server addresses with their country and autonomous system
+//!
+//! This module requires the `maxminddb` feature.
+//! It reads databases in the MaxMind DB format, like the GeoLite2 Country and ASN databases.
+//! See [`GeoIpDatabase`].
+
+use crate::error::Context;
+use crate::serialization::IpAddr;
+use crate::utils::to_std_ip;
+use crate::IpVersion;
+use crate::Result;
+use maxminddb::Reader;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Geographic and network information about an address.
+///
+/// All fields are [`None`] if the address is not found in the databases.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct GeoInfo {
+    /// ISO 3166-1 country code, like `DE`.
+    pub country: Option<String>,
+    /// Number of the autonomous system announcing the address.
+    pub asn: Option<u32>,
+    /// Organization operating the autonomous system.
+    pub as_organization: Option<String>,
+}
+
+/// A value together with the [`GeoInfo`] of its address.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Enriched<T> {
+    /// The original value.
+    pub value: T,
+    /// Information about the address of the value.
+    pub geo: GeoInfo,
+}
+
+/// Country and ASN databases in the MaxMind DB format.
+///
+/// Both databases are optional, the corresponding [`GeoInfo`] fields stay empty without them.
+#[derive(Default)]
+pub struct GeoIpDatabase {
+    country: Option<Reader<Vec<u8>>>,
+    asn: Option<Reader<Vec<u8>>>,
+}
+
+impl std::fmt::Debug for GeoIpDatabase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GeoIpDatabase")
+            .field("country", &self.country.is_some())
+            .field("asn", &self.asn.is_some())
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
+struct CountryRecord {
+    country: Option<Country>,
+}
+
+#[derive(Deserialize)]
+struct Country {
+    iso_code: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AsnRecord {
+    autonomous_system_number: Option<u32>,
+    autonomous_system_organization: Option<String>,
+}
+
+impl GeoIpDatabase {
+    /// Create an enrichment without any databases.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the country database stored at `path`.
+    pub fn with_country_database(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        self.country = Some(
+            Reader::open_readfile(path)
+                .with_context(|| format!("Failed to open country database {}", path.display()))?,
+        );
+        Ok(self)
+    }
+
+    /// Use the ASN database stored at `path`.
+    pub fn with_asn_database(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        self.asn = Some(
+            Reader::open_readfile(path)
+                .with_context(|| format!("Failed to open ASN database {}", path.display()))?,
+        );
+        Ok(self)
+    }
+
+    /// Look up the information about `addr`.
+    ///
+    /// Addresses which are not found or have malformed database entries result in empty fields.
+    pub fn lookup(&self, addr: std::net::IpAddr) -> GeoInfo {
+        let mut info = GeoInfo::default();
+        if let Some(country) = &self.country {
+            let record: Option<CountryRecord> = country
+                .lookup(addr)
+                .ok()
+                .and_then(|result| result.decode().ok().flatten());
+            info.country = record
+                .and_then(|record| record.country)
+                .and_then(|country| country.iso_code);
+        }
+        if let Some(asn) = &self.asn {
+            let record: Option<AsnRecord> = asn
+                .lookup(addr)
+                .ok()
+                .and_then(|result| result.decode().ok().flatten());
+            if let Some(record) = record {
+                info.asn = record.autonomous_system_number;
+                info.as_organization = record.autonomous_system_organization;
+            }
+        }
+        info
+    }
+
+    /// Look up the information about an address stored in the [`BlockTables`](crate::serialization::BlockTables).
+    ///
+    /// Addresses truncated to a prefix are looked up with the missing bits set to zero.
+    /// The `ip_version` is guessed from the address length if it is not known.
+    /// Returns [`None`] if the bytes are not a valid address.
+    pub fn lookup_address(&self, addr: &IpAddr, ip_version: Option<IpVersion>) -> Option<GeoInfo> {
+        to_std_ip(addr, ip_version).map(|addr| self.lookup(addr))
+    }
+
+    /// Attach the [`GeoInfo`] to a value with an address, like the entries of a top-N list.
+    pub fn enrich<T>(&self, addr: std::net::IpAddr, value: T) -> Enriched<T> {
+        Enriched {
+            value,
+            geo: self.lookup(addr),
+        }
+    }
+
+    /// Attach the [`GeoInfo`] to all entries of a list of addresses, like [`MalformedReport::top_clients`](crate::analysis::malformed::MalformedReport::top_clients).
+    pub fn enrich_all<T>(
+        &self,
+        entries: impl IntoIterator<Item = (std::net::IpAddr, T)>,
+    ) -> Vec<Enriched<(std::net::IpAddr, T)>> {
+        entries
+            .into_iter()
+            .map(|(addr, value)| self.enrich(addr, (addr, value)))
+            .collect()
+    }
+}
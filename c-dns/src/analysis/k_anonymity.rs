@@ -0,0 +1,235 @@
+//! Aggregate Q/R items by client and name while protecting individual clients
+//!
+//! Statistics about which clients query which names can identify individual users.
+//! [`KAnonymityAggregation`] only emits aggregates containing at least `k` distinct clients.
+//! Combinations with fewer clients are generalized step by step, by truncating the client addresses to shorter prefixes and replacing names with their registrable domain or top-level domain.
+//! Combinations which stay below `k` even after the last step are suppressed and only counted.
+//!
+//! See [`KAnonymityConfig`] for the generalization steps.
+
+use crate::analysis::domains::DomainHierarchy;
+use crate::serialization::*;
+use crate::tables::signature;
+use crate::utils::{name_to_string, to_std_ip};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Level of detail kept from a QNAME.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NameGeneralization {
+    /// The full QNAME.
+    Qname,
+    /// The registrable domain of the QNAME, see [`DomainHierarchy::registrable_domain`].
+    RegistrableDomain,
+    /// The top-level domain of the QNAME, see [`DomainHierarchy::tld`].
+    Tld,
+    /// All names are combined.
+    Any,
+}
+
+/// One generalization step, the level of detail kept from client addresses and names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Generalization {
+    /// Number of leading bits kept from IPv4 client addresses.
+    pub ipv4_prefix: u8,
+    /// Number of leading bits kept from IPv6 client addresses.
+    pub ipv6_prefix: u8,
+    /// Level of detail kept from QNAMEs.
+    pub name: NameGeneralization,
+}
+
+/// Threshold and generalization steps of a [`KAnonymityAggregation`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KAnonymityConfig {
+    /// Minimal number of distinct clients in an emitted aggregate.
+    pub k: usize,
+    /// Generalization steps, from the most to the least detailed.
+    ///
+    /// Combinations not reaching `k` clients in one step are combined in the next step.
+    pub levels: Vec<Generalization>,
+}
+
+impl KAnonymityConfig {
+    /// Require `k` distinct clients per aggregate, with the default generalization steps.
+    ///
+    /// The steps keep:
+    ///
+    /// 1. /24 IPv4 and /48 IPv6 prefixes with the full QNAME,
+    /// 2. /16 IPv4 and /32 IPv6 prefixes with the registrable domain,
+    /// 3. /8 IPv4 and /16 IPv6 prefixes with the top-level domain,
+    /// 4. no client information with the top-level domain.
+    pub fn new(k: usize) -> Self {
+        let level = |ipv4_prefix, ipv6_prefix, name| Generalization {
+            ipv4_prefix,
+            ipv6_prefix,
+            name,
+        };
+        Self {
+            k,
+            levels: vec![
+                level(24, 48, NameGeneralization::Qname),
+                level(16, 32, NameGeneralization::RegistrableDomain),
+                level(8, 16, NameGeneralization::Tld),
+                level(0, 0, NameGeneralization::Tld),
+            ],
+        }
+    }
+}
+
+/// A combination of client network and name with at least `k` distinct clients.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Aggregate {
+    /// Position of the generalization step in [`KAnonymityConfig::levels`].
+    pub level: usize,
+    /// Client addresses truncated to `client_prefix` bits.
+    pub client_network: IpAddr,
+    /// Number of bits kept from the client addresses.
+    pub client_prefix: u8,
+    /// The generalized name in lowercase presentation format, [`None`] for [`NameGeneralization::Any`].
+    pub name: Option<String>,
+    /// Number of distinct clients.
+    pub clients: usize,
+    /// Number of Q/R items.
+    pub query_responses: usize,
+}
+
+/// Result of [`KAnonymityAggregation::finish`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KAnonymityReport {
+    /// All aggregates with at least `k` distinct clients, sorted by level, network, and name.
+    pub aggregates: Vec<Aggregate>,
+    /// Number of Q/R items in combinations which did not reach `k` clients in any step.
+    pub suppressed_query_responses: usize,
+    /// Number of Q/R items without a client address or QNAME, which are never included.
+    pub unattributed_query_responses: usize,
+}
+
+/// Collect Q/R counts per client and QNAME, for a k-anonymous report.
+///
+/// The collected data contains the individual clients and must not be shared, only the result of [`KAnonymityAggregation::finish`].
+#[derive(Debug, Clone, Default)]
+pub struct KAnonymityAggregation {
+    counts: HashMap<(IpAddr, String), usize>,
+    unattributed: usize,
+}
+
+impl KAnonymityAggregation {
+    /// Create an empty aggregation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collect the Q/R items of all blocks of `file`.
+    pub fn from_file(file: &File) -> Self {
+        let mut res = Self::default();
+        for (block, _) in file.iter_blocks() {
+            res.add_block(block);
+        }
+        res
+    }
+
+    /// Collect all Q/R items of `block`.
+    pub fn add_block(&mut self, block: &Block) {
+        let block_tables = block.block_tables.as_ref();
+        for query_response in block.query_responses.as_deref().unwrap_or(&[]) {
+            let key = block_tables.and_then(|block_tables| {
+                let ip_version = signature(block_tables, query_response)
+                    .and_then(|sig| sig.qr_transport_flags.as_ref())
+                    .map(|flags| flags.ip_version());
+                let client = block_tables
+                    .ip_address
+                    .as_ref()?
+                    .get(query_response.client_address_index?)
+                    .and_then(|ip| to_std_ip(ip, ip_version))?;
+                let name = block_tables
+                    .name_rdata
+                    .as_ref()?
+                    .get(query_response.query_name_index?)?;
+                Some((client, name_to_string(name).to_ascii_lowercase()))
+            });
+            match key {
+                Some(key) => *self.counts.entry(key).or_default() += 1,
+                None => self.unattributed += 1,
+            }
+        }
+    }
+
+    /// Compute the aggregates meeting the threshold of `config`.
+    pub fn finish(
+        &self,
+        config: &KAnonymityConfig,
+        hierarchy: &DomainHierarchy,
+    ) -> KAnonymityReport {
+        let mut report = KAnonymityReport {
+            unattributed_query_responses: self.unattributed,
+            ..Default::default()
+        };
+        let mut pending: Vec<(&IpAddr, &str, usize)> = self
+            .counts
+            .iter()
+            .map(|((client, name), count)| (client, &**name, *count))
+            .collect();
+
+        for (level_idx, level) in config.levels.iter().enumerate() {
+            let mut groups: BTreeMap<_, (BTreeSet<&IpAddr>, usize, Vec<_>)> = BTreeMap::new();
+            for entry @ (client, name, count) in pending.drain(..) {
+                let (client_network, client_prefix) = match client {
+                    IpAddr::V4(addr) => {
+                        (truncate_ipv4(*addr, level.ipv4_prefix), level.ipv4_prefix)
+                    }
+                    IpAddr::V6(addr) => {
+                        (truncate_ipv6(*addr, level.ipv6_prefix), level.ipv6_prefix)
+                    }
+                };
+                let name = match level.name {
+                    NameGeneralization::Qname => Some(name),
+                    NameGeneralization::RegistrableDomain => {
+                        Some(hierarchy.registrable_domain(name).unwrap_or(name))
+                    }
+                    NameGeneralization::Tld => Some(hierarchy.tld(name).unwrap_or(name)),
+                    NameGeneralization::Any => None,
+                };
+                let group = groups
+                    .entry((client_network, client_prefix, name))
+                    .or_default();
+                group.0.insert(client);
+                group.1 += count;
+                group.2.push(entry);
+            }
+
+            for ((client_network, client_prefix, name), (clients, query_responses, entries)) in
+                groups
+            {
+                if clients.len() >= config.k {
+                    report.aggregates.push(Aggregate {
+                        level: level_idx,
+                        client_network,
+                        client_prefix,
+                        name: name.map(str::to_string),
+                        clients: clients.len(),
+                        query_responses,
+                    });
+                } else {
+                    pending.extend(entries);
+                }
+            }
+        }
+
+        report.suppressed_query_responses = pending.iter().map(|(_, _, count)| count).sum();
+        report
+    }
+}
+
+fn truncate_ipv4(addr: Ipv4Addr, prefix: u8) -> IpAddr {
+    let mask = u32::MAX
+        .checked_shl(32 - u32::from(prefix.min(32)))
+        .unwrap_or(0);
+    IpAddr::V4(Ipv4Addr::from(u32::from(addr) & mask))
+}
+
+fn truncate_ipv6(addr: Ipv6Addr, prefix: u8) -> IpAddr {
+    let mask = u128::MAX
+        .checked_shl(128 - u32::from(prefix.min(128)))
+        .unwrap_or(0);
+    IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask))
+}
@@ -0,0 +1,166 @@
+//! Volume of malformed DNS messages over time, by client, and by transport
+//!
+//! See [`MalformedReport`].
+
+use crate::analysis::{bucket_number, bucket_start};
+use crate::serialization::*;
+use crate::utils::{absolute_time, to_std_ip};
+use crate::{IpVersion, Transport};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime};
+
+/// Malformed message counts of a single [`Block`].
+///
+/// Compares the [`MalformedMessage`] items stored in the block with the count reported in [`BlockStatistics.malformed_items`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MalformedBlockSummary {
+    /// Position of the block in the file.
+    pub block_index: usize,
+    /// The [`BlockPreamble.earliest_time`] as absolute time.
+    pub earliest_time: Option<SystemTime>,
+    /// Number of [`MalformedMessage`] items stored in the block.
+    pub recorded: usize,
+    /// Value of [`BlockStatistics.malformed_items`], if present.
+    pub reported: Option<usize>,
+}
+
+impl MalformedBlockSummary {
+    /// Number of malformed messages which were counted by the collector but not stored.
+    ///
+    /// Collectors may count malformed messages without storing them, for example if [`OtherDataHints::MalformedMessages`] is not set.
+    pub fn unrecorded(&self) -> usize {
+        self.reported.unwrap_or(0).saturating_sub(self.recorded)
+    }
+}
+
+/// Analysis of malformed DNS messages.
+///
+/// The report counts the [`MalformedMessage`] items of all blocks and splits them by time, client address, and transport.
+/// The transport is taken from the referenced [`MalformedMessageData`].
+/// Per-block summaries allow correlating the stored items with [`BlockStatistics.malformed_items`].
+#[derive(Debug, Clone)]
+pub struct MalformedReport {
+    /// Width of the time buckets in [`MalformedReport::over_time`].
+    pub bucket_width: Duration,
+    /// Number of malformed messages per time bucket, keyed by the bucket start.
+    ///
+    /// Messages without an absolute timestamp are not included.
+    pub over_time: BTreeMap<SystemTime, usize>,
+    /// Number of malformed messages per client address.
+    ///
+    /// Messages without a client address are not included.
+    pub by_client: HashMap<std::net::IpAddr, usize>,
+    /// Number of malformed messages per IP version and transport.
+    ///
+    /// Messages without transport flags are not included.
+    pub by_transport: BTreeMap<(IpVersion, Transport), usize>,
+    /// Summary for each block of the file.
+    pub blocks: Vec<MalformedBlockSummary>,
+}
+
+impl MalformedReport {
+    /// Compute the report over all blocks of `file` grouping the messages into time buckets of `bucket_width` length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bucket_width` is zero.
+    pub fn from_file(file: &File, bucket_width: Duration) -> Self {
+        assert!(
+            !bucket_width.is_zero(),
+            "Bucket width must be larger than zero"
+        );
+        let mut res = Self {
+            bucket_width,
+            over_time: BTreeMap::new(),
+            by_client: HashMap::new(),
+            by_transport: BTreeMap::new(),
+            blocks: Vec::with_capacity(file.file_blocks.len()),
+        };
+        for (block, block_parameters) in file.iter_blocks() {
+            res.add_block(block, block_parameters);
+        }
+        res
+    }
+
+    /// Add all malformed messages of `block` to the report.
+    pub fn add_block(&mut self, block: &Block, block_parameters: &BlockParameters) {
+        let earliest_time = block.block_preamble.earliest_time;
+        let malformed_messages = block.malformed_messages.as_deref().unwrap_or(&[]);
+        self.blocks.push(MalformedBlockSummary {
+            block_index: self.blocks.len(),
+            earliest_time: absolute_time(earliest_time, None, block_parameters),
+            recorded: malformed_messages.len(),
+            reported: block
+                .block_statistics
+                .as_ref()
+                .and_then(|stats| stats.malformed_items),
+        });
+
+        let block_tables = block.block_tables.as_ref();
+        for malformed_message in malformed_messages {
+            if let Some(time) = absolute_time(
+                earliest_time,
+                malformed_message.time_offset,
+                block_parameters,
+            ) {
+                let start = bucket_start(bucket_number(time, self.bucket_width), self.bucket_width);
+                *self.over_time.entry(start).or_default() += 1;
+            }
+
+            let transport_flags = block_tables
+                .and_then(|tables| {
+                    tables
+                        .malformed_message_data
+                        .as_ref()?
+                        .get(malformed_message.message_data_index?)
+                })
+                .and_then(|data| data.mm_transport_flags.as_ref());
+            if let Some(transport_flags) = transport_flags {
+                *self
+                    .by_transport
+                    .entry((
+                        transport_flags.ip_version(),
+                        transport_flags.transport_protocol(),
+                    ))
+                    .or_default() += 1;
+            }
+
+            let client = block_tables
+                .and_then(|tables| {
+                    tables
+                        .ip_address
+                        .as_ref()?
+                        .get(malformed_message.client_address_index?)
+                })
+                .and_then(|ip| to_std_ip(ip, transport_flags.map(|flags| flags.ip_version())));
+            if let Some(client) = client {
+                *self.by_client.entry(client).or_default() += 1;
+            }
+        }
+    }
+
+    /// Total number of malformed messages stored in the file.
+    pub fn recorded(&self) -> usize {
+        self.blocks.iter().map(|block| block.recorded).sum()
+    }
+
+    /// Total number of malformed messages reported in the [`BlockStatistics`].
+    pub fn reported(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|block| block.reported.unwrap_or(0))
+            .sum()
+    }
+
+    /// The `n` clients with the most malformed messages, in descending order.
+    pub fn top_clients(&self, n: usize) -> Vec<(std::net::IpAddr, usize)> {
+        let mut clients: Vec<_> = self
+            .by_client
+            .iter()
+            .map(|(client, count)| (*client, *count))
+            .collect();
+        clients.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        clients.truncate(n);
+        clients
+    }
+}
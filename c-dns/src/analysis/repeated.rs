@@ -0,0 +1,197 @@
+//! Detect clients repeating identical queries at a high rate
+//!
+//! See [`RepeatedQueryDetector`].
+
+use crate::error::bail;
+use crate::serialization::*;
+use crate::tables::signature;
+use crate::utils::{absolute_time, name_to_string, to_std_ip};
+use crate::Result;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// A (QNAME, QTYPE) pair repeatedly queried by one client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepeatedQuery {
+    /// QNAME of the first Question, in presentation format.
+    pub qname: String,
+    /// TYPE of the first Question.
+    pub qtype: u16,
+    /// Largest number of queries within a single window.
+    pub max_in_window: usize,
+    /// Total number of queries for this pair.
+    pub total: usize,
+    /// Time of the first query.
+    pub first_seen: SystemTime,
+    /// Time of the last query.
+    pub last_seen: SystemTime,
+}
+
+/// Clients which repeated identical queries, with the offending queries.
+#[derive(Debug, Clone, Default)]
+pub struct RepeatedQueryReport {
+    /// Offending queries per client, sorted by descending `max_in_window`.
+    pub offenders: HashMap<std::net::IpAddr, Vec<RepeatedQuery>>,
+}
+
+impl RepeatedQueryReport {
+    /// Clients sorted by the largest `max_in_window` of any of their queries, in descending order.
+    pub fn worst_offenders(&self) -> Vec<(std::net::IpAddr, &[RepeatedQuery])> {
+        let mut clients: Vec<_> = self
+            .offenders
+            .iter()
+            .map(|(client, queries)| (*client, &**queries))
+            .collect();
+        clients.sort_by(|a, b| {
+            let max = |queries: &[RepeatedQuery]| queries.first().map_or(0, |q| q.max_in_window);
+            max(b.1).cmp(&max(a.1)).then(a.0.cmp(&b.0))
+        });
+        clients
+    }
+}
+
+/// Find clients issuing identical (QNAME, QTYPE) queries at a high frequency.
+///
+/// Retry storms and misconfigured stub resolvers show up as many identical queries from the same client in a short time.
+/// A (client, QNAME, QTYPE) combination is reported if at least `threshold` queries fall into any time window of `window` length.
+///
+/// Only Q/R items containing a Query and with an absolute timestamp, client address, QNAME, and QTYPE are considered.
+#[derive(Debug, Clone)]
+pub struct RepeatedQueryDetector {
+    window: Duration,
+    threshold: usize,
+    queries: HashMap<(std::net::IpAddr, String, u16), Vec<SystemTime>>,
+}
+
+impl RepeatedQueryDetector {
+    /// Create a detector reporting at least `threshold` queries within `window`.
+    ///
+    /// Fails if `window` is zero.
+    pub fn new(window: Duration, threshold: usize) -> Result<Self> {
+        if window.is_zero() {
+            bail!("The window of the repeated query detection must be larger than zero");
+        }
+        Ok(Self {
+            window,
+            threshold,
+            queries: HashMap::new(),
+        })
+    }
+
+    /// Run the detector over all blocks of `file`.
+    ///
+    /// Fails if `window` is zero.
+    pub fn from_file(
+        file: &File,
+        window: Duration,
+        threshold: usize,
+    ) -> Result<RepeatedQueryReport> {
+        let mut detector = Self::new(window, threshold)?;
+        for (block, block_parameters) in file.iter_blocks() {
+            detector.add_block(block, block_parameters);
+        }
+        Ok(detector.finish())
+    }
+
+    /// Add all Q/R items of `block` to the detector.
+    pub fn add_block(&mut self, block: &Block, block_parameters: &BlockParameters) {
+        let block_tables = match &block.block_tables {
+            Some(block_tables) => block_tables,
+            None => return,
+        };
+        for query_response in block.query_responses.as_deref().unwrap_or(&[]) {
+            let sig = match signature(block_tables, query_response) {
+                Some(sig) => sig,
+                None => continue,
+            };
+            if !sig
+                .qr_sig_flags
+                .is_none_or(|flags| flags.contains(QueryResponseFlags::HasQuery))
+            {
+                continue;
+            }
+
+            let entry = (|| {
+                let time = absolute_time(
+                    block.block_preamble.earliest_time,
+                    query_response.time_offset,
+                    block_parameters,
+                )?;
+                let client = to_std_ip(
+                    block_tables
+                        .ip_address
+                        .as_ref()?
+                        .get(query_response.client_address_index?)?,
+                    sig.qr_transport_flags
+                        .as_ref()
+                        .map(|flags| flags.ip_version()),
+                )?;
+                let qname = block_tables
+                    .name_rdata
+                    .as_ref()?
+                    .get(query_response.query_name_index?)?;
+                let qtype = block_tables
+                    .classtype
+                    .as_ref()?
+                    .get(sig.query_classtype_index?)?
+                    .type_;
+                Some((time, client, name_to_string(qname), u16::from(qtype)))
+            })();
+            if let Some((time, client, qname, qtype)) = entry {
+                self.queries
+                    .entry((client, qname, qtype))
+                    .or_default()
+                    .push(time);
+            }
+        }
+    }
+
+    /// Evaluate all added Q/R items and produce the report.
+    pub fn finish(self) -> RepeatedQueryReport {
+        let mut report = RepeatedQueryReport::default();
+        for ((client, qname, qtype), mut times) in self.queries {
+            if times.len() < self.threshold {
+                continue;
+            }
+            times.sort_unstable();
+
+            // Sliding window over the sorted timestamps
+            let mut max_in_window = 0;
+            let mut start = 0;
+            for (end, time) in times.iter().enumerate() {
+                while time
+                    .duration_since(times[start])
+                    .is_ok_and(|diff| diff >= self.window)
+                {
+                    start += 1;
+                }
+                max_in_window = max_in_window.max(end - start + 1);
+            }
+
+            if max_in_window >= self.threshold {
+                report
+                    .offenders
+                    .entry(client)
+                    .or_default()
+                    .push(RepeatedQuery {
+                        qname,
+                        qtype,
+                        max_in_window,
+                        total: times.len(),
+                        first_seen: times[0],
+                        last_seen: times[times.len() - 1],
+                    });
+            }
+        }
+
+        for queries in report.offenders.values_mut() {
+            queries.sort_by(|a, b| {
+                b.max_in_window
+                    .cmp(&a.max_in_window)
+                    .then_with(|| a.qname.cmp(&b.qname))
+                    .then(a.qtype.cmp(&b.qtype))
+            });
+        }
+        report
+    }
+}
@@ -0,0 +1,279 @@
+//! Enrich addresses with the hostnames from their PTR records
+//!
+//! This module requires the `reverse-dns` feature, since the lookups generate network traffic.
+//! [`ReverseDns`] caches the results and limits the rate of lookups, so reports over many addresses do not flood the resolver.
+//!
+//! ```no_run
+//! # use c_dns::analysis::reverse_dns::{ReverseDns, UdpPtrResolver};
+//! # use std::time::Duration;
+//! let resolver = UdpPtrResolver::new("127.0.0.1:53".parse().unwrap());
+//! let mut reverse_dns = ReverseDns::new(resolver, Duration::from_millis(10));
+//! let hostname = reverse_dns.lookup("192.0.2.53".parse().unwrap());
+//! ```
+
+use crate::error::{bail, Context};
+use crate::wire::Message;
+use crate::Result;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Type of the PTR RR, RFC 1035
+const PTR_TYPE: u16 = 12;
+/// The Internet class, RFC 1035
+const IN_CLASS: u16 = 1;
+
+/// Source of PTR records.
+pub trait PtrResolver {
+    /// Look up the hostname of `addr`.
+    ///
+    /// Returns [`None`] if there is no PTR record.
+    fn resolve_ptr(&mut self, addr: IpAddr) -> Result<Option<String>>;
+}
+
+/// Name queried for the PTR record of `addr`, like `1.2.0.192.in-addr.arpa.` for `192.0.2.1`.
+pub fn reverse_name(addr: IpAddr) -> String {
+    let mut name = String::new();
+    match addr {
+        IpAddr::V4(addr) => {
+            for octet in addr.octets().iter().rev() {
+                let _ = write!(name, "{}.", octet);
+            }
+            name.push_str("in-addr.arpa.");
+        }
+        IpAddr::V6(addr) => {
+            for octet in addr.octets().iter().rev() {
+                let _ = write!(name, "{:x}.{:x}.", octet & 0x0f, octet >> 4);
+            }
+            name.push_str("ip6.arpa.");
+        }
+    }
+    name
+}
+
+/// Send PTR queries over UDP to a recursive resolver.
+///
+/// Every Query uses a random transaction ID and source port.
+/// Only Responses from `server` which match the ID and the Question of the Query are accepted, all other packets are ignored.
+#[derive(Debug, Clone)]
+pub struct UdpPtrResolver {
+    server: SocketAddr,
+    timeout: Duration,
+}
+
+impl UdpPtrResolver {
+    /// Query the resolver listening on `server`, waiting up to two seconds for a Response.
+    pub fn new(server: SocketAddr) -> Self {
+        Self {
+            server,
+            timeout: Duration::from_secs(2),
+        }
+    }
+
+    /// Wait up to `timeout` for a Response.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl PtrResolver for UdpPtrResolver {
+    fn resolve_ptr(&mut self, addr: IpAddr) -> Result<Option<String>> {
+        let id = random_id();
+        let qname = wire_name(&reverse_name(addr));
+        let query = ptr_query(id, &qname);
+
+        let bind_addr: SocketAddr = if self.server.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(bind_addr).context("Failed to bind UDP socket")?;
+        socket.set_read_timeout(Some(self.timeout))?;
+        socket
+            .connect(self.server)
+            .with_context(|| format!("Failed to connect to resolver {}", self.server))?;
+        socket.send(&query)?;
+
+        let mut buffer = [0; 4096];
+        loop {
+            let (len, source) = socket
+                .recv_from(&mut buffer)
+                .with_context(|| format!("No Response from resolver {}", self.server))?;
+            if source != self.server {
+                continue;
+            }
+            let response = &buffer[..len];
+            // Ignore stray and spoofed packets, which do not answer this Query
+            match Message::parse(response) {
+                Ok(message) if answers_query(&message, id, &qname) => {
+                    return parse_ptr_response(&message, response)
+                }
+                _ => continue,
+            }
+        }
+    }
+}
+
+/// A transaction ID which cannot be predicted from earlier Queries.
+///
+/// Every [`RandomState`] hashes with fresh random keys, so the hash of any value is unpredictable.
+fn random_id() -> u16 {
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(now) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(now.as_nanos());
+    }
+    hasher.finish() as u16
+}
+
+/// Encode the presentation format `name` as an uncompressed name in wire format.
+fn wire_name(name: &str) -> Vec<u8> {
+    let mut wire = Vec::with_capacity(name.len() + 1);
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        wire.push(label.len() as u8);
+        wire.extend_from_slice(label.as_bytes());
+    }
+    wire.push(0);
+    wire
+}
+
+/// Build a recursive Query for the PTR record of `qname` in wire format.
+fn ptr_query(id: u16, qname: &[u8]) -> Vec<u8> {
+    let mut query = Vec::with_capacity(16 + qname.len());
+    query.extend_from_slice(&id.to_be_bytes());
+    // RD flag, one Question
+    query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    query.extend_from_slice(qname);
+    query.extend_from_slice(&PTR_TYPE.to_be_bytes());
+    query.extend_from_slice(&IN_CLASS.to_be_bytes());
+    query
+}
+
+/// Whether `message` is the Response to the PTR Query for `qname` with transaction `id`.
+///
+/// The name is compared case-insensitively, since resolvers may randomize the case of names.
+fn answers_query(message: &Message, id: u16, qname: &[u8]) -> bool {
+    message.header.id == id
+        && message.header.is_response()
+        && matches!(
+            &*message.questions,
+            [question] if question.rr_type == PTR_TYPE
+                && question.class == IN_CLASS
+                && question.name.as_bytes().eq_ignore_ascii_case(qname)
+        )
+}
+
+/// Extract the first PTR record from the Answer section of `message`, which was parsed from `response`.
+fn parse_ptr_response(message: &Message, response: &[u8]) -> Result<Option<String>> {
+    match message.rcode() {
+        0 => {}
+        // NXDOMAIN
+        3 => return Ok(None),
+        rcode => bail!("Resolver returned RCODE {}", rcode),
+    }
+    match message
+        .answers
+        .iter()
+        .find(|record| record.rr_type == PTR_TYPE)
+    {
+        Some(record) => record
+            .rdata_name(response)?
+            .to_domain_name()
+            .map(|name| Some(name.to_string()))
+            .context("Invalid name in PTR record"),
+        None => Ok(None),
+    }
+}
+
+/// Cached and rate-limited PTR lookups.
+///
+/// Definitive answers, a PTR record, NXDOMAIN, or a NOERROR Response without a PTR record, are cached and every address is looked up at most once.
+/// Failed lookups, like timeouts or SERVFAIL, are only remembered for the retry interval, after which the address is looked up again.
+#[derive(Debug)]
+pub struct ReverseDns<R> {
+    resolver: R,
+    min_interval: Duration,
+    retry_interval: Duration,
+    last_lookup: Option<Instant>,
+    cache: HashMap<IpAddr, Option<String>>,
+    failures: HashMap<IpAddr, Instant>,
+}
+
+impl<R: PtrResolver> ReverseDns<R> {
+    /// Look up addresses with `resolver`, waiting at least `min_interval` between two lookups.
+    ///
+    /// Failed lookups are retried after one minute.
+    pub fn new(resolver: R, min_interval: Duration) -> Self {
+        Self {
+            resolver,
+            min_interval,
+            retry_interval: Duration::from_secs(60),
+            last_lookup: None,
+            cache: HashMap::new(),
+            failures: HashMap::new(),
+        }
+    }
+
+    /// Retry failed lookups once `retry_interval` has passed.
+    pub fn with_retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    /// The hostname of `addr`, if it has a PTR record.
+    ///
+    /// Blocks until the rate limit allows the next lookup, unless the address is cached or failed within the retry interval.
+    pub fn lookup(&mut self, addr: IpAddr) -> Option<&str> {
+        if !self.cache.contains_key(&addr) {
+            if let Some(failed) = self.failures.get(&addr) {
+                if failed.elapsed() < self.retry_interval {
+                    return None;
+                }
+            }
+            if let Some(last_lookup) = self.last_lookup {
+                let elapsed = last_lookup.elapsed();
+                if elapsed < self.min_interval {
+                    std::thread::sleep(self.min_interval - elapsed);
+                }
+            }
+            let result = self.resolver.resolve_ptr(addr);
+            let now = Instant::now();
+            self.last_lookup = Some(now);
+            match result {
+                Ok(hostname) => {
+                    self.failures.remove(&addr);
+                    self.cache.insert(addr, hostname);
+                }
+                Err(_) => {
+                    self.failures.insert(addr, now);
+                    return None;
+                }
+            }
+        }
+        self.cache[&addr].as_deref()
+    }
+
+    /// Attach the hostname to all entries of a list of addresses, like [`MalformedReport::top_clients`](crate::analysis::malformed::MalformedReport::top_clients).
+    pub fn enrich_all<T>(
+        &mut self,
+        entries: impl IntoIterator<Item = (IpAddr, T)>,
+    ) -> Vec<(IpAddr, T, Option<String>)> {
+        entries
+            .into_iter()
+            .map(|(addr, value)| {
+                let hostname = self.lookup(addr).map(str::to_string);
+                (addr, value, hostname)
+            })
+            .collect()
+    }
+
+    /// Number of addresses with a cached answer.
+    ///
+    /// Addresses whose last lookup failed are not counted.
+    pub fn cached(&self) -> usize {
+        self.cache.len()
+    }
+}
@@ -0,0 +1,216 @@
+//! Aggregate statistics over all Q/R items of a file
+//!
+//! [`Statistics`] collects the numbers most analyses start with: the distribution of RCODEs and QTYPEs, the transports, the message sizes, the query rate, and the most active names and clients.
+//!
+//! ```
+//! # use c_dns::analysis::stats::Statistics;
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let file = File::read_path("./tests/data/dns.cdns")?;
+//! let statistics = Statistics::from_file(&file);
+//! assert_eq!(12, statistics.query_responses);
+//! assert!(statistics.queries_per_second().is_some());
+//! println!("{}", statistics);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::analysis::transport::TransportBreakdown;
+use crate::serialization::*;
+use crate::tables::{signature, signature_flags};
+use crate::utils::{absolute_time, to_std_ip};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::SystemTime;
+
+/// Width of the buckets of a [`SizeHistogram`] in bytes
+pub const SIZE_BUCKET_WIDTH: u16 = 64;
+
+/// Histogram of message sizes in buckets of [`SIZE_BUCKET_WIDTH`] bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeHistogram {
+    /// Number of messages per bucket, keyed by the smallest size in the bucket.
+    pub buckets: BTreeMap<u16, usize>,
+}
+
+impl SizeHistogram {
+    /// Count a message of `size` bytes.
+    pub fn add(&mut self, size: u16) {
+        *self
+            .buckets
+            .entry(size - size % SIZE_BUCKET_WIDTH)
+            .or_default() += 1;
+    }
+
+    /// Number of counted messages.
+    pub fn count(&self) -> usize {
+        self.buckets.values().sum()
+    }
+}
+
+/// Aggregate statistics over Q/R items.
+///
+/// The RCODE and QTYPE are taken from the [`QueryResponseSignature`].
+/// Names are lowercased and in presentation format, names which cannot be decoded are not counted.
+///
+/// The [`Display`](fmt::Display) implementation renders a short overview.
+#[derive(Debug, Clone, Default)]
+pub struct Statistics {
+    /// Number of Q/R items.
+    pub query_responses: usize,
+    /// Number of Q/R items which contain a Query.
+    pub queries: usize,
+    /// Number of Q/R items which contain a Response.
+    pub responses: usize,
+    /// Number of Responses per RCODE.
+    pub rcodes: BTreeMap<u16, usize>,
+    /// Number of Q/R items per QTYPE.
+    pub qtypes: BTreeMap<DnsType, usize>,
+    /// Breakdown by IP version and transport.
+    pub transports: TransportBreakdown,
+    /// Sizes of the Queries.
+    pub query_sizes: SizeHistogram,
+    /// Sizes of the Responses.
+    pub response_sizes: SizeHistogram,
+    /// Time of the earliest Q/R item.
+    pub first_time: Option<SystemTime>,
+    /// Time of the latest Q/R item.
+    pub last_time: Option<SystemTime>,
+    /// Number of Q/R items per QNAME.
+    pub qnames: HashMap<String, usize>,
+    /// Number of Q/R items per client address.
+    pub clients: HashMap<std::net::IpAddr, usize>,
+}
+
+impl Statistics {
+    /// Compute the statistics over all blocks of `file`.
+    pub fn from_file(file: &File) -> Self {
+        let mut res = Self::default();
+        for (block, block_parameters) in file.iter_blocks() {
+            res.add_block(block, block_parameters);
+        }
+        res
+    }
+
+    /// Add all Q/R items of `block` to the statistics.
+    pub fn add_block(&mut self, block: &Block, block_parameters: &BlockParameters) {
+        self.transports.add_block(block);
+        for (query_response, earliest_time, block_parameters, block_tables) in
+            block.iter_query_responses(block_parameters)
+        {
+            self.query_responses += 1;
+            let signature = signature(block_tables, query_response);
+            let flags = signature_flags(block_tables, query_response);
+            if flags.contains(QueryResponseFlags::HasQuery) {
+                self.queries += 1;
+            }
+            if flags.contains(QueryResponseFlags::HasResponse) {
+                self.responses += 1;
+                if let Some(rcode) = signature.and_then(|sig| sig.response_rcode) {
+                    *self.rcodes.entry(rcode).or_default() += 1;
+                }
+            }
+            if let Some(classtype) = signature
+                .and_then(|sig| sig.query_classtype_index)
+                .and_then(|idx| block_tables.classtype(idx).ok())
+            {
+                *self.qtypes.entry(classtype.type_).or_default() += 1;
+            }
+            if let Some(size) = query_response.query_size {
+                self.query_sizes.add(size);
+            }
+            if let Some(size) = query_response.response_size {
+                self.response_sizes.add(size);
+            }
+
+            if let Some(time) =
+                absolute_time(earliest_time, query_response.time_offset, block_parameters)
+            {
+                self.first_time = Some(self.first_time.map_or(time, |first| first.min(time)));
+                self.last_time = Some(self.last_time.map_or(time, |last| last.max(time)));
+            }
+            if let Some(name) = query_response
+                .query_name_index
+                .and_then(|idx| block_tables.name(idx).ok())
+                .and_then(|name| name.to_domain_name().ok())
+            {
+                *self
+                    .qnames
+                    .entry(name.to_string().to_ascii_lowercase())
+                    .or_default() += 1;
+            }
+            let ip_version = signature
+                .and_then(|sig| sig.qr_transport_flags.as_ref())
+                .map(|flags| flags.ip_version());
+            if let Some(client) = query_response
+                .client_address_index
+                .and_then(|idx| block_tables.ip(idx).ok())
+                .and_then(|ip| to_std_ip(ip, ip_version))
+            {
+                *self.clients.entry(client).or_default() += 1;
+            }
+        }
+    }
+
+    /// Average number of Queries per second between the first and the last Q/R item.
+    ///
+    /// Returns [`None`] if the Q/R items do not span a time range.
+    pub fn queries_per_second(&self) -> Option<f64> {
+        let duration = self.last_time?.duration_since(self.first_time?).ok()?;
+        if duration.is_zero() {
+            return None;
+        }
+        Some(self.queries as f64 / duration.as_secs_f64())
+    }
+
+    /// The `n` QNAMEs with the most Q/R items, in descending order.
+    pub fn top_qnames(&self, n: usize) -> Vec<(&str, usize)> {
+        top(self.qnames.iter().map(|(name, count)| (&**name, *count)), n)
+    }
+
+    /// The `n` clients with the most Q/R items, in descending order.
+    pub fn top_clients(&self, n: usize) -> Vec<(std::net::IpAddr, usize)> {
+        top(
+            self.clients.iter().map(|(client, count)| (*client, *count)),
+            n,
+        )
+    }
+}
+
+/// The `n` entries with the highest count, ties are ordered by key.
+fn top<K: Ord>(entries: impl Iterator<Item = (K, usize)>, n: usize) -> Vec<(K, usize)> {
+    let mut entries: Vec<_> = entries.collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    entries.truncate(n);
+    entries
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} Q/R items, {} Queries, {} Responses",
+            self.query_responses, self.queries, self.responses
+        )?;
+        if let Some(queries_per_second) = self.queries_per_second() {
+            writeln!(f, "{:.2} Queries per second", queries_per_second)?;
+        }
+        writeln!(f, "RCODEs:")?;
+        for (rcode, count) in &self.rcodes {
+            writeln!(f, "  {:<10} {:>10}", rcode, count)?;
+        }
+        writeln!(f, "QTYPEs:")?;
+        for (qtype, count) in &self.qtypes {
+            writeln!(f, "  {:<10} {:>10}", qtype, count)?;
+        }
+        writeln!(f, "Top QNAMEs:")?;
+        for (name, count) in self.top_qnames(10) {
+            writeln!(f, "  {:<40} {:>10}", name, count)?;
+        }
+        writeln!(f, "Top clients:")?;
+        for (client, count) in self.top_clients(10) {
+            writeln!(f, "  {:<40} {:>10}", client, count)?;
+        }
+        write!(f, "{}", self.transports)
+    }
+}
@@ -0,0 +1,187 @@
+//! Split traffic by IP version and transport protocol
+//!
+//! See [`TransportBreakdown`].
+
+use crate::serialization::*;
+use crate::tables::{signature, signature_flags};
+use crate::{IpVersion, Transport};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Traffic counters for one combination of IP version and transport.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransportCounts {
+    /// Number of Q/R items.
+    pub query_responses: usize,
+    /// Number of Q/R items which contain a Query.
+    pub queries: usize,
+    /// Number of Q/R items which contain a Response.
+    pub responses: usize,
+    /// Number of Q/R items where the Query packet had trailing bytes.
+    pub trailing_data: usize,
+    /// Sum of all recorded Query sizes.
+    pub query_bytes: u64,
+    /// Sum of all recorded Response sizes.
+    pub response_bytes: u64,
+}
+
+impl TransportCounts {
+    /// Fraction of Q/R items with trailing data in the Query packet, between 0 and 1.
+    pub fn trailing_data_rate(&self) -> f64 {
+        if self.query_responses == 0 {
+            0.
+        } else {
+            self.trailing_data as f64 / self.query_responses as f64
+        }
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.query_responses += other.query_responses;
+        self.queries += other.queries;
+        self.responses += other.responses;
+        self.trailing_data += other.trailing_data;
+        self.query_bytes += other.query_bytes;
+        self.response_bytes += other.response_bytes;
+    }
+}
+
+/// Breakdown of Q/R items by IP version and transport.
+///
+/// The values are taken from the [`TransportFlags`] of the [`QueryResponseSignature`].
+/// Q/R items without transport flags are only counted in [`TransportBreakdown::unknown_transport`].
+///
+/// The [`Display`](fmt::Display) implementation renders the breakdown as a table.
+#[derive(Debug, Clone, Default)]
+pub struct TransportBreakdown {
+    /// Counters per IP version and transport.
+    pub transports: BTreeMap<(IpVersion, Transport), TransportCounts>,
+    /// Number of Q/R items without [`TransportFlags`].
+    pub unknown_transport: usize,
+}
+
+impl TransportBreakdown {
+    /// Compute the breakdown over all blocks of `file`.
+    pub fn from_file(file: &File) -> Self {
+        let mut res = Self::default();
+        for (block, _) in file.iter_blocks() {
+            res.add_block(block);
+        }
+        res
+    }
+
+    /// Add all Q/R items of `block` to the breakdown.
+    pub fn add_block(&mut self, block: &Block) {
+        let (block_tables, query_responses) = match (&block.block_tables, &block.query_responses) {
+            (Some(block_tables), Some(query_responses)) => (block_tables, query_responses),
+            (None, Some(query_responses)) => {
+                self.unknown_transport += query_responses.len();
+                return;
+            }
+            _ => return,
+        };
+
+        for query_response in query_responses {
+            let transport_flags = match signature(block_tables, query_response)
+                .and_then(|sig| sig.qr_transport_flags.as_ref())
+            {
+                Some(transport_flags) => transport_flags,
+                None => {
+                    self.unknown_transport += 1;
+                    continue;
+                }
+            };
+            let counts = self
+                .transports
+                .entry((
+                    transport_flags.ip_version(),
+                    transport_flags.transport_protocol(),
+                ))
+                .or_default();
+
+            counts.query_responses += 1;
+            let flags = signature_flags(block_tables, query_response);
+            if flags.contains(QueryResponseFlags::HasQuery) {
+                counts.queries += 1;
+            }
+            if flags.contains(QueryResponseFlags::HasResponse) {
+                counts.responses += 1;
+            }
+            if transport_flags.has_trailing_data() {
+                counts.trailing_data += 1;
+            }
+            counts.query_bytes += u64::from(query_response.query_size.unwrap_or(0));
+            counts.response_bytes += u64::from(query_response.response_size.unwrap_or(0));
+        }
+    }
+
+    /// Counters summed over all IP versions and transports.
+    pub fn total(&self) -> TransportCounts {
+        let mut total = TransportCounts::default();
+        self.transports
+            .values()
+            .for_each(|counts| total.merge(counts));
+        total
+    }
+
+    /// Counters for one IP version summed over all transports.
+    pub fn by_ip_version(&self, ip_version: IpVersion) -> TransportCounts {
+        let mut total = TransportCounts::default();
+        self.transports
+            .iter()
+            .filter(|((version, _), _)| *version == ip_version)
+            .for_each(|(_, counts)| total.merge(counts));
+        total
+    }
+
+    /// Counters for one transport summed over all IP versions.
+    pub fn by_transport(&self, transport: Transport) -> TransportCounts {
+        let mut total = TransportCounts::default();
+        self.transports
+            .iter()
+            .filter(|((_, trans), _)| *trans == transport)
+            .for_each(|(_, counts)| total.merge(counts));
+        total
+    }
+}
+
+impl fmt::Display for TransportBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total().query_responses + self.unknown_transport;
+        let share = |count: usize| {
+            if total == 0 {
+                0.
+            } else {
+                count as f64 * 100. / total as f64
+            }
+        };
+
+        writeln!(
+            f,
+            "{:<4} {:<12} {:>10} {:>8} {:>10} {:>10} {:>10}",
+            "IP", "Transport", "Q/R items", "Share", "Queries", "Responses", "Trailing"
+        )?;
+        for ((ip_version, transport), counts) in &self.transports {
+            writeln!(
+                f,
+                "{:<4} {:<12} {:>10} {:>7.2}% {:>10} {:>10} {:>9.2}%",
+                ip_version,
+                transport,
+                counts.query_responses,
+                share(counts.query_responses),
+                counts.queries,
+                counts.responses,
+                counts.trailing_data_rate() * 100.,
+            )?;
+        }
+        if self.unknown_transport > 0 {
+            writeln!(
+                f,
+                "{:<17} {:>10} {:>7.2}%",
+                "Unknown",
+                self.unknown_transport,
+                share(self.unknown_transport),
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,635 @@
+//! Pseudonymize IP addresses and names before sharing a capture
+//!
+//! [`File::anonymize_addresses`] replaces every address of a file with the result of an [`AddressAnonymizer`].
+//! This covers the [`BlockTables.ip_address`], which holds the client and server addresses and the addresses of address events, the [`CollectionParameters.server_addresses`], and the addresses of EDNS Client Subnet options in the OPT RDATA.
+//! It records the anonymization in the [`StorageParameters`] by setting [`StorageFlags::AnonymizedData`] and the `anonymization_method`.
+//!
+//! Three anonymizers are available:
+//!
+//! * [`PrefixTruncation`] keeps only a prefix of each address, like the address prefixes of a collector.
+//! * [`CryptoPan`] maps addresses to pseudonyms while preserving common prefixes between addresses. Requires the `ring` feature.
+//! * [`HmacHash`] replaces addresses with a keyed hash, which does not preserve any structure. Requires the `ring` feature.
+//!
+//! [`File::redact_names`] likewise replaces all names in the [`BlockTables.name_rdata`] with the result of a [`NameRedactor`]:
+//!
+//! * [`SuffixTruncation`] keeps only the rightmost labels of each name.
+//! * [`SuffixAllowlist`] keeps only known public suffixes, like `com.` or `co.uk.`, and replaces all labels left of them.
+//! * [`LabelHashing`] replaces each label with a keyed hash, preserving the hierarchy of the names. Requires the `ring` feature.
+//!
+//! ```
+//! # use c_dns::anonymize::PrefixTruncation;
+//! # use c_dns::serialization::{File, StorageFlags};
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let mut file = File::read_path("./tests/data/dns.cdns")?;
+//! file.anonymize_addresses(&PrefixTruncation::new(24, 48));
+//! let storage_parameters = &file.file_preamble.block_parameters[0].storage_parameters;
+//! assert!(storage_parameters.storage_flags.unwrap().contains(StorageFlags::AnonymizedData));
+//! assert!(storage_parameters.anonymization_method.is_some());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::edns::{self, ClientSubnet, OptionData, OPTION_CLIENT_SUBNET};
+use crate::name::DomainName;
+use crate::rdata::Rdata;
+use crate::serialization::*;
+use crate::tables::{visit_ip_address_indices, visit_name_rdata_indices, NameRdataUse};
+use crate::IpVersion;
+use std::collections::{HashMap, HashSet};
+
+/// A transformation of IP addresses for pseudonymization.
+///
+/// The transformation must be deterministic, such that the same address is replaced by the same pseudonym in all blocks.
+pub trait AddressAnonymizer {
+    /// The pseudonym of `addr`, which must have the same IP version.
+    fn anonymize(&self, addr: std::net::IpAddr) -> std::net::IpAddr;
+
+    /// Description of the method, which is stored as the `anonymization_method` of the [`StorageParameters`].
+    fn method(&self) -> String;
+}
+
+/// Keep the first bits of each address and set all others to zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixTruncation {
+    /// Number of kept bits of IPv4 addresses.
+    pub ipv4_prefix_len: u8,
+    /// Number of kept bits of IPv6 addresses.
+    pub ipv6_prefix_len: u8,
+}
+
+impl PrefixTruncation {
+    pub fn new(ipv4_prefix_len: u8, ipv6_prefix_len: u8) -> Self {
+        Self {
+            ipv4_prefix_len,
+            ipv6_prefix_len,
+        }
+    }
+}
+
+impl AddressAnonymizer for PrefixTruncation {
+    fn anonymize(&self, addr: std::net::IpAddr) -> std::net::IpAddr {
+        let prefix_len = match addr {
+            std::net::IpAddr::V4(_) => self.ipv4_prefix_len,
+            std::net::IpAddr::V6(_) => self.ipv6_prefix_len,
+        };
+        let mut octets = ip_octets(&addr);
+        for (idx, octet) in octets.iter_mut().enumerate() {
+            let kept_bits = usize::from(prefix_len).saturating_sub(idx * 8).min(8);
+            *octet &= !(0xffu16 >> kept_bits) as u8;
+        }
+        from_octets(&octets)
+    }
+
+    fn method(&self) -> String {
+        format!(
+            "prefix truncation to /{} (IPv4) and /{} (IPv6)",
+            self.ipv4_prefix_len, self.ipv6_prefix_len
+        )
+    }
+}
+
+/// Prefix-preserving pseudonymization in the style of Crypto-PAn.
+///
+/// Two addresses sharing the first `n` bits are mapped to pseudonyms sharing exactly the first `n` bits, so subnets stay recognizable.
+/// Each bit is flipped depending on a keyed pseudo-random function of all preceding bits.
+/// The pseudo-random function is HMAC-SHA256 instead of the AES of the original Crypto-PAn, so the pseudonyms differ from other implementations.
+#[cfg(feature = "ring")]
+pub struct CryptoPan {
+    key: ring::hmac::Key,
+}
+
+#[cfg(feature = "ring")]
+impl CryptoPan {
+    /// Create the anonymizer with a secret `key`, which should have at least 32 bytes.
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            key: ring::hmac::Key::new(ring::hmac::HMAC_SHA256, key),
+        }
+    }
+}
+
+#[cfg(feature = "ring")]
+impl AddressAnonymizer for CryptoPan {
+    fn anonymize(&self, addr: std::net::IpAddr) -> std::net::IpAddr {
+        let octets = ip_octets(&addr);
+        let mut pseudonym = octets.clone();
+        // The bit position followed by the preceding bits of the address
+        let mut message = vec![0; 1 + octets.len()];
+        for bit in 0..octets.len() * 8 {
+            let (idx, mask) = (bit / 8, 0x80 >> (bit % 8));
+            message[0] = bit as u8;
+            if ring::hmac::sign(&self.key, &message).as_ref()[0] & 0x80 != 0 {
+                pseudonym[idx] ^= mask;
+            }
+            message[1 + idx] |= octets[idx] & mask;
+        }
+        from_octets(&pseudonym)
+    }
+
+    fn method(&self) -> String {
+        "prefix-preserving pseudonymization (Crypto-PAn with HMAC-SHA256)".to_string()
+    }
+}
+
+/// Replace each address with the leading bytes of its HMAC-SHA256.
+///
+/// The pseudonyms do not preserve any relation between the addresses.
+#[cfg(feature = "ring")]
+pub struct HmacHash {
+    key: ring::hmac::Key,
+}
+
+#[cfg(feature = "ring")]
+impl HmacHash {
+    /// Create the anonymizer with a secret `key`, which should have at least 32 bytes.
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            key: ring::hmac::Key::new(ring::hmac::HMAC_SHA256, key),
+        }
+    }
+}
+
+#[cfg(feature = "ring")]
+impl AddressAnonymizer for HmacHash {
+    fn anonymize(&self, addr: std::net::IpAddr) -> std::net::IpAddr {
+        let octets = ip_octets(&addr);
+        let tag = ring::hmac::sign(&self.key, &octets);
+        from_octets(&tag.as_ref()[..octets.len()])
+    }
+
+    fn method(&self) -> String {
+        "HMAC-SHA256 hashing".to_string()
+    }
+}
+
+impl File {
+    /// Replace all IP addresses of the file with their pseudonyms, see [`Block::anonymize_addresses`].
+    ///
+    /// This also replaces the `server_addresses` of the [`CollectionParameters`], sets [`StorageFlags::AnonymizedData`], and appends the [`AddressAnonymizer::method`] to the `anonymization_method` of all [`StorageParameters`].
+    ///
+    /// # Panics
+    ///
+    /// The indices in the blocks must be valid, as checked by [`File::dangling_indices`].
+    pub fn anonymize_addresses(&mut self, anonymizer: &impl AddressAnonymizer) {
+        let method = anonymizer.method();
+        for block_parameters in &mut self.file_preamble.block_parameters {
+            record_anonymization(&mut block_parameters.storage_parameters, &method);
+            let server_addresses = block_parameters
+                .collection_parameters
+                .as_mut()
+                .and_then(|params| params.server_addresses.as_mut());
+            for addr in server_addresses.into_iter().flatten() {
+                *addr = anonymize_stored(anonymizer, addr, None);
+            }
+        }
+        for block in &mut self.file_blocks {
+            block.anonymize_addresses(anonymizer);
+        }
+    }
+}
+
+impl Block {
+    /// Replace all entries of the [`BlockTables.ip_address`] with their pseudonyms.
+    ///
+    /// The IP version of each address is taken from the [`TransportFlags`] of the Q/R item, address event, or malformed message referencing it.
+    /// Without transport flags, addresses of up to 4 bytes are IPv4 and longer ones IPv6.
+    /// Pseudonyms are stored with as many bytes as the original address, so configured address prefixes stay valid.
+    /// Addresses which do not fit their IP version are replaced by an empty address.
+    ///
+    /// The table is rebuilt from the referenced addresses only, such that no unreferenced address stays in the clear.
+    /// Addresses with equal pseudonyms share an entry.
+    ///
+    /// The addresses of EDNS Client Subnet options in the OPT RDATA of Queries and of OPT RRs are replaced by their pseudonyms, truncated to the source prefix length of the option.
+    /// OPT RDATA which cannot be parsed is replaced by empty RDATA, and Client Subnet options which cannot be parsed are removed, since they may hold addresses in the clear.
+    /// The [`BlockTables.name_rdata`] is rebuilt like by [`Block::redact_names`].
+    ///
+    /// This does not change the [`StorageParameters`], use [`File::anonymize_addresses`] to also record the anonymization.
+    ///
+    /// # Panics
+    ///
+    /// The indices in the block must be valid, as checked by [`File::dangling_indices`].
+    pub fn anonymize_addresses(&mut self, anonymizer: &impl AddressAnonymizer) {
+        let addresses = match self
+            .block_tables
+            .as_mut()
+            .and_then(|tables| tables.ip_address.take())
+        {
+            Some(addresses) => addresses,
+            None => return,
+        };
+
+        let mut pseudonyms: Vec<IpAddr> = Vec::new();
+        let mut positions: HashMap<IpAddr, usize> = HashMap::new();
+        let mut mapping: HashMap<(usize, Option<IpVersion>), usize> = HashMap::new();
+        visit_ip_address_indices(self, &mut |index, ip_version| {
+            *index = *mapping.entry((*index, ip_version)).or_insert_with(|| {
+                let pseudonym = anonymize_stored(anonymizer, &addresses[*index], ip_version);
+                *positions.entry(pseudonym.clone()).or_insert_with(|| {
+                    pseudonyms.push(pseudonym);
+                    pseudonyms.len() - 1
+                })
+            });
+        });
+        if let Some(tables) = &mut self.block_tables {
+            tables.ip_address = (!pseudonyms.is_empty()).then_some(pseudonyms);
+        }
+
+        rewrite_name_rdata(self, &mut |entry, usage| match usage {
+            NameRdataUse::Rdata(Some(DnsType::OPT)) => anonymize_opt_rdata(anonymizer, entry),
+            _ => entry.clone(),
+        });
+    }
+}
+
+/// The OPT RDATA with the addresses of all Client Subnet options replaced by their pseudonyms.
+fn anonymize_opt_rdata(anonymizer: &impl AddressAnonymizer, rdata: &NameOrRdata) -> NameOrRdata {
+    let options = match rdata.parse_rdata(DnsType::OPT) {
+        Ok(Rdata::Opt(options)) => options,
+        _ => return NameOrRdata::from(bytes::Bytes::new()),
+    };
+    if options
+        .iter()
+        .all(|option| option.code != OPTION_CLIENT_SUBNET)
+    {
+        return rdata.clone();
+    }
+    let options: Vec<OptionData> = options
+        .into_iter()
+        .filter_map(|option| {
+            if option.code != OPTION_CLIENT_SUBNET {
+                return Some(OptionData::Unknown(option));
+            }
+            match OptionData::parse(&option) {
+                Ok(OptionData::ClientSubnet(ecs)) => Some(OptionData::ClientSubnet(ClientSubnet {
+                    scope_prefix_len: ecs.scope_prefix_len,
+                    ..ClientSubnet::new(anonymizer.anonymize(ecs.address), ecs.source_prefix_len)
+                })),
+                _ => None,
+            }
+        })
+        .collect();
+    // The options are not longer than before
+    edns::options_to_rdata(&options).unwrap()
+}
+
+/// Set [`StorageFlags::AnonymizedData`] and append `method` to the `anonymization_method`.
+fn record_anonymization(storage_parameters: &mut StorageParameters, method: &str) {
+    storage_parameters
+        .storage_flags
+        .get_or_insert_with(Default::default)
+        .insert(StorageFlags::AnonymizedData);
+    storage_parameters.anonymization_method =
+        Some(match storage_parameters.anonymization_method.take() {
+            Some(previous) => format!("{}, {}", previous, method),
+            None => method.to_string(),
+        });
+}
+
+/// The pseudonym of the stored address `addr`, with as many bytes as `addr`.
+fn anonymize_stored(
+    anonymizer: &impl AddressAnonymizer,
+    addr: &IpAddr,
+    ip_version: Option<IpVersion>,
+) -> IpAddr {
+    let len = addr.as_bytes().len();
+    let ip_version = ip_version.unwrap_or(if len <= 4 {
+        IpVersion::Ipv4
+    } else {
+        IpVersion::Ipv6
+    });
+    let full = match ip_version {
+        IpVersion::Ipv4 => addr.as_ipv4().map(std::net::IpAddr::V4),
+        IpVersion::Ipv6 => addr.as_ipv6().map(std::net::IpAddr::V6),
+    };
+    match full {
+        Ok(full) => IpAddr::with_prefix(anonymizer.anonymize(full), (len * 8) as u8),
+        Err(_) => IpAddr::from(bytes::Bytes::new()),
+    }
+}
+
+fn from_octets(octets: &[u8]) -> std::net::IpAddr {
+    match <[u8; 4]>::try_from(octets) {
+        Ok(octets) => std::net::IpAddr::from(octets),
+        Err(_) => std::net::IpAddr::from(<[u8; 16]>::try_from(octets).unwrap()),
+    }
+}
+
+/// A transformation of domain names for redaction.
+///
+/// The transformation must be deterministic, such that the same name is replaced by the same redacted name in all blocks.
+pub trait NameRedactor {
+    /// The redacted form of `name`.
+    fn redact(&self, name: &DomainName) -> DomainName;
+
+    /// Description of the method, which is stored as the `anonymization_method` of the [`StorageParameters`].
+    fn method(&self) -> String;
+}
+
+/// Keep the rightmost labels of each name and remove all others.
+///
+/// With two labels, `www.example.com.` becomes `example.com.`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuffixTruncation {
+    /// Number of kept labels.
+    pub labels: usize,
+}
+
+impl SuffixTruncation {
+    pub fn new(labels: usize) -> Self {
+        Self { labels }
+    }
+}
+
+impl NameRedactor for SuffixTruncation {
+    fn redact(&self, name: &DomainName) -> DomainName {
+        let labels: Vec<_> = name.labels().collect();
+        let skip = labels.len().saturating_sub(self.labels);
+        fit_labels(labels[skip..].iter())
+    }
+
+    fn method(&self) -> String {
+        format!("name truncation to {} labels", self.labels)
+    }
+}
+
+/// Keep the longest allowed suffix of each name and replace all labels left of it with a single placeholder label.
+///
+/// The allowlist usually holds the public suffixes, like `com` and `co.uk`, such that `www.example.co.uk.` becomes `redacted.co.uk.`.
+/// Names without an allowed suffix become the placeholder label alone, and names which are an allowed suffix stay unchanged.
+/// Suffixes are compared case-insensitively.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuffixAllowlist {
+    suffixes: HashSet<Vec<Vec<u8>>>,
+    placeholder: Vec<u8>,
+}
+
+impl SuffixAllowlist {
+    /// Create the redactor from allowed suffixes in presentation format, like `co.uk`.
+    ///
+    /// The placeholder label is `redacted`.
+    pub fn new<'a>(suffixes: impl IntoIterator<Item = &'a str>) -> Self {
+        let suffixes = suffixes
+            .into_iter()
+            .map(|suffix| {
+                suffix
+                    .trim_end_matches('.')
+                    .split('.')
+                    .filter(|label| !label.is_empty())
+                    .map(|label| label.as_bytes().to_ascii_lowercase())
+                    .collect()
+            })
+            .collect();
+        Self {
+            suffixes,
+            placeholder: b"redacted".to_vec(),
+        }
+    }
+
+    /// Replace the redacted labels with `placeholder` instead of `redacted`.
+    ///
+    /// The placeholder is truncated to the maximal label length of 63 bytes, and an empty placeholder removes the labels without replacement.
+    pub fn with_placeholder(mut self, placeholder: &str) -> Self {
+        self.placeholder = placeholder.as_bytes().iter().copied().take(63).collect();
+        self
+    }
+}
+
+impl NameRedactor for SuffixAllowlist {
+    fn redact(&self, name: &DomainName) -> DomainName {
+        let labels: Vec<Vec<u8>> = name
+            .labels()
+            .map(|label| label.to_ascii_lowercase())
+            .collect();
+        let kept = (0..labels.len())
+            .find(|&start| self.suffixes.contains(&labels[start..]))
+            .unwrap_or(labels.len());
+        let placeholder = (kept > 0 && !self.placeholder.is_empty()).then_some(&self.placeholder);
+        let original: Vec<&[u8]> = name.labels().collect();
+        fit_labels(
+            placeholder
+                .map(|label| &label[..])
+                .into_iter()
+                .chain(original[kept..].iter().copied()),
+        )
+    }
+
+    fn method(&self) -> String {
+        "name redaction to allowed suffixes".to_string()
+    }
+}
+
+/// Replace each label with the leading bytes of its HMAC-SHA256 in hexadecimal.
+///
+/// Equal labels map to equal hashes, so the hierarchy of the names is preserved: `www.example.com.` and `mail.example.com.` still share two labels.
+/// Labels are lowercased before hashing.
+/// The rightmost labels can be kept in the clear with [`LabelHashing::keep_labels`].
+#[cfg(feature = "ring")]
+pub struct LabelHashing {
+    key: ring::hmac::Key,
+    keep_labels: usize,
+}
+
+#[cfg(feature = "ring")]
+impl LabelHashing {
+    /// Length of the hashed labels in hexadecimal digits.
+    pub const HASH_LEN: usize = 16;
+
+    /// Create the redactor with a secret `key`, which should have at least 32 bytes.
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            key: ring::hmac::Key::new(ring::hmac::HMAC_SHA256, key),
+            keep_labels: 0,
+        }
+    }
+
+    /// Keep the rightmost `labels` labels unchanged, for example `1` to keep the TLD.
+    pub fn keep_labels(mut self, labels: usize) -> Self {
+        self.keep_labels = labels;
+        self
+    }
+}
+
+#[cfg(feature = "ring")]
+impl NameRedactor for LabelHashing {
+    fn redact(&self, name: &DomainName) -> DomainName {
+        let labels: Vec<_> = name.labels().collect();
+        let hashed = labels.len().saturating_sub(self.keep_labels);
+        let hashes: Vec<Vec<u8>> = labels[..hashed]
+            .iter()
+            .map(|label| {
+                let tag = ring::hmac::sign(&self.key, &label.to_ascii_lowercase());
+                tag.as_ref()[..Self::HASH_LEN / 2]
+                    .iter()
+                    .flat_map(|byte| format!("{:02x}", byte).into_bytes())
+                    .collect()
+            })
+            .collect();
+        fit_labels(
+            hashes
+                .iter()
+                .map(|label| &label[..])
+                .chain(labels[hashed..].iter().copied()),
+        )
+    }
+
+    fn method(&self) -> String {
+        format!(
+            "label hashing (HMAC-SHA256) keeping {} labels",
+            self.keep_labels
+        )
+    }
+}
+
+/// Build a name from `labels`, removing the leftmost labels until the name fits into 255 bytes.
+fn fit_labels<L: AsRef<[u8]>>(labels: impl IntoIterator<Item = L>) -> DomainName {
+    let labels: Vec<L> = labels.into_iter().collect();
+    (0..=labels.len())
+        .find_map(|skip| DomainName::from_labels(&labels[skip..]).ok())
+        .unwrap_or_else(DomainName::root)
+}
+
+impl File {
+    /// Replace all names of the file with their redacted form, see [`Block::redact_names`].
+    ///
+    /// This sets [`StorageFlags::AnonymizedData`] and appends the [`NameRedactor::method`] to the `anonymization_method` of all [`StorageParameters`].
+    ///
+    /// # Panics
+    ///
+    /// The indices in the blocks must be valid, as checked by [`File::dangling_indices`].
+    pub fn redact_names(&mut self, redactor: &impl NameRedactor) {
+        let method = redactor.method();
+        for block_parameters in &mut self.file_preamble.block_parameters {
+            record_anonymization(&mut block_parameters.storage_parameters, &method);
+        }
+        for block in &mut self.file_blocks {
+            block.redact_names(redactor);
+        }
+    }
+}
+
+impl Block {
+    /// Replace all QNAMEs, bailiwicks, RR NAMEs, and names in the RDATA with their redacted form.
+    ///
+    /// All references to a name are rewritten consistently, and names with equal redacted forms share an entry of the [`BlockTables.name_rdata`].
+    /// Names which cannot be decoded are replaced by the root name.
+    ///
+    /// The names in the RDATA of NS, CNAME, PTR, DNAME, MX, SOA, and SRV records are redacted, all other fields of the RDATA are kept.
+    /// RDATA of these TYPEs which cannot be parsed, and RDATA of records without a known TYPE, is replaced by empty RDATA.
+    /// The RDATA of all other TYPEs is not changed, even if it contains names.
+    ///
+    /// The table is rebuilt from the referenced entries only, such that no unreferenced name stays in the clear.
+    ///
+    /// This does not change the [`StorageParameters`], use [`File::redact_names`] to also record the redaction.
+    ///
+    /// # Panics
+    ///
+    /// The indices in the block must be valid, as checked by [`File::dangling_indices`].
+    pub fn redact_names(&mut self, redactor: &impl NameRedactor) {
+        rewrite_name_rdata(self, &mut |entry, usage| match usage {
+            NameRdataUse::Name => NameOrRdata::from(
+                entry
+                    .to_domain_name()
+                    .map(|name| redactor.redact(&name))
+                    .unwrap_or_else(|_| DomainName::root()),
+            ),
+            NameRdataUse::Rdata(Some(type_)) => redact_rdata(redactor, entry, type_),
+            NameRdataUse::Rdata(None) => NameOrRdata::from(bytes::Bytes::new()),
+        });
+    }
+}
+
+/// The RDATA of a record of TYPE `type_` with all names replaced by their redacted form.
+fn redact_rdata(redactor: &impl NameRedactor, rdata: &NameOrRdata, type_: DnsType) -> NameOrRdata {
+    if ![
+        DnsType::NS,
+        DnsType::CNAME,
+        DnsType::PTR,
+        DnsType::DNAME,
+        DnsType::MX,
+        DnsType::SOA,
+        DnsType::SRV,
+    ]
+    .contains(&type_)
+    {
+        return rdata.clone();
+    }
+
+    let mut redacted = Vec::new();
+    let push_name = |redacted: &mut Vec<u8>, name: &DomainName| {
+        redacted.extend_from_slice(redactor.redact(name).as_wire())
+    };
+    match rdata.parse_rdata(type_) {
+        Ok(
+            Rdata::Ns(target) | Rdata::Cname(target) | Rdata::Ptr(target) | Rdata::Dname(target),
+        ) => push_name(&mut redacted, &target),
+        Ok(Rdata::Mx {
+            preference,
+            exchange,
+        }) => {
+            redacted.extend_from_slice(&preference.to_be_bytes());
+            push_name(&mut redacted, &exchange);
+        }
+        Ok(Rdata::Soa {
+            mname,
+            rname,
+            serial,
+            refresh,
+            retry,
+            expire,
+            minimum,
+        }) => {
+            push_name(&mut redacted, &mname);
+            push_name(&mut redacted, &rname);
+            for value in [serial, refresh, retry, expire, minimum] {
+                redacted.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        Ok(Rdata::Srv {
+            priority,
+            weight,
+            port,
+            target,
+        }) => {
+            for value in [priority, weight, port] {
+                redacted.extend_from_slice(&value.to_be_bytes());
+            }
+            push_name(&mut redacted, &target);
+        }
+        _ => {}
+    }
+    NameOrRdata::from(bytes::Bytes::from(redacted))
+}
+
+/// Replace every referenced entry of the [`BlockTables.name_rdata`] with the result of `rewrite`.
+///
+/// Each entry is rewritten once for each way it is used.
+/// The table is rebuilt from the results, such that equal results share an entry and unreferenced entries are removed.
+fn rewrite_name_rdata(
+    block: &mut Block,
+    rewrite: &mut impl FnMut(&NameOrRdata, NameRdataUse) -> NameOrRdata,
+) {
+    let entries = match block
+        .block_tables
+        .as_mut()
+        .and_then(|tables| tables.name_rdata.take())
+    {
+        Some(entries) => entries,
+        None => return,
+    };
+
+    let mut name_rdata: Vec<NameOrRdata> = Vec::new();
+    let mut positions: HashMap<NameOrRdata, usize> = HashMap::new();
+    let mut mapping: HashMap<(usize, NameRdataUse), usize> = HashMap::new();
+    visit_name_rdata_indices(block, &mut |index, usage| {
+        *index = *mapping.entry((*index, usage)).or_insert_with(|| {
+            let entry = rewrite(&entries[*index], usage);
+            *positions.entry(entry.clone()).or_insert_with(|| {
+                name_rdata.push(entry);
+                name_rdata.len() - 1
+            })
+        });
+    });
+    if let Some(tables) = &mut block.block_tables {
+        tables.name_rdata = (!name_rdata.is_empty()).then_some(name_rdata);
+    }
+}
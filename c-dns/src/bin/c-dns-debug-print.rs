@@ -1,8 +1,10 @@
-use c_dns::serialization::File;
-use misc_utils::fs;
+use c_dns::compression::decompress;
+use c_dns::encoding::EncodingMetadata;
+use c_dns::serialization::{File, FileReader};
 use std::env;
 use std::error::Error;
 use std::ffi::OsStr;
+use std::io::{self, BufReader, Read};
 use std::path::Path;
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -26,35 +28,195 @@ fn main() -> Result<(), Box<dyn Error>> {
             dump_serialized = true;
             args.next();
         }
+        Some(x) if x == OsStr::new("explain") => {
+            args.next();
+            for file in args {
+                explain(Path::new(&file))?;
+            }
+            return Ok(());
+        }
         _ => {}
     }
 
     for file in args {
         let file = Path::new(&file);
-        let buffer = fs::read(file)?;
-        match serde_path_to_error::deserialize::<_, File>(&mut serde_cbor::Deserializer::from_reader(
-            buffer.as_slice(),
-        )) {
-            Ok(cdns) => {
+        if dump_serialized {
+            print_and_dump(file)?;
+        } else {
+            print(file)?;
+        }
+    }
+    Ok(())
+}
+
+/// Print the content of the file one block at a time.
+///
+/// The input is streamed, so only a single block is kept in memory.
+fn print(file: &Path) -> Result<(), Box<dyn Error>> {
+    let mut reader = match FileReader::new(open_input(file)?) {
+        Ok(reader) => reader,
+        Err(error) => {
+            print_error(file, &error);
+            return Ok(());
+        }
+    };
+    println!(
+        "====================\nFile: {}\n====================\n",
+        file.display(),
+    );
+    println!("File type id: {:?}", reader.file_type_id());
+    println!("{:#?}", reader.file_preamble());
+    for (idx, block) in (&mut reader).enumerate() {
+        match block {
+            Ok(block) => println!("\nBlock {}:\n{:#?}", idx, block),
+            Err(error) => {
+                print_error(file, &error);
+                return Ok(());
+            }
+        }
+    }
+
+    let trailing = io::copy(&mut reader.into_inner(), &mut io::sink())?;
+    if trailing > 0 {
+        println!("\nTrailing data: {} bytes", trailing);
+    }
+    Ok(())
+}
+
+/// Print the error and all its sources, which contain the position of the failing value.
+fn print_error(file: &Path, error: &dyn Error) {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        message = format!("{}: {}", message, error);
+        source = error.source();
+    }
+    eprintln!(
+        "====================\nFailed to deserialize: {}\n====================\n{}\n",
+        file.display(),
+        message
+    );
+}
+
+/// Print the content of the file and write it re-serialized to a new file.
+///
+/// Keeping the original CBOR encoding needs the complete input, so the input is read into memory.
+fn print_and_dump(file: &Path) -> Result<(), Box<dyn Error>> {
+    let mut buffer = Vec::new();
+    open_input(file)?.read_to_end(&mut buffer)?;
+    let mut deserializer = serde_cbor::Deserializer::from_slice(&buffer);
+    match serde_path_to_error::deserialize::<_, File>(&mut deserializer) {
+        Ok(cdns) => {
+            println!(
+                "====================\nFile: {}\n====================\n",
+                file.display(),
+            );
+            println!("{:#?}", cdns);
+
+            let offset = deserializer.byte_offset();
+            if offset < buffer.len() {
                 println!(
-                    "====================\nFile: {}\n====================\n",
-                    file.display(),
+                    "\nTrailing data: {} bytes at offset {}",
+                    buffer.len() - offset,
+                    offset
                 );
-                println!("{:#?}", cdns);
-
-                if dump_serialized {
-                    let mut reserialized = Vec::new();
-                    serde_cbor::to_writer(&mut reserialized, &cdns).unwrap();
-                    let newfile = file.with_extension("new.cdns");
-                    std::fs::write(newfile, reserialized).unwrap();
-                }
             }
-            Err(error) => eprintln!(
-                "====================\nFailed to deserialize: {}\n====================\n{}\n",
-                error.path(),
-                error.inner()
-            ),
+
+            let mut reserialized = Vec::new();
+            serde_cbor::to_writer(&mut reserialized, &cdns)?;
+            // Keep the original CBOR encoding, such that unchanged files are reproduced exactly
+            let reserialized = EncodingMetadata::from_slice(&buffer)
+                .and_then(|metadata| metadata.apply(&reserialized))
+                .unwrap_or(reserialized);
+            let newfile = if is_remote(file) {
+                // Write next to the current directory instead of a path derived from the URL
+                Path::new(file.file_name().unwrap_or_default()).with_extension("new.cdns")
+            } else {
+                file.with_extension("new.cdns")
+            };
+            std::fs::write(newfile, reserialized)?;
         }
+        Err(error) => eprintln!(
+            "====================\nFailed to deserialize: {}\n====================\n{}\n",
+            error.path(),
+            error.inner()
+        ),
+    }
+    Ok(())
+}
+
+/// Whether the argument is a URL instead of a local path.
+fn is_remote(file: &Path) -> bool {
+    let file = file.to_string_lossy();
+    ["http://", "https://", "s3://"]
+        .iter()
+        .any(|scheme| file.starts_with(scheme))
+}
+
+/// Open a local file or stream a remote object, decompressing it if necessary.
+fn open_input(file: &Path) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    if !is_remote(file) {
+        return Ok(decompress(BufReader::new(std::fs::File::open(file)?))?);
+    }
+
+    #[cfg(feature = "remote")]
+    {
+        remote::open(&file.to_string_lossy())
+    }
+    #[cfg(not(feature = "remote"))]
+    Err(format!("Reading {} requires the `remote` feature", file.display()).into())
+}
+
+#[cfg(feature = "remote")]
+mod remote {
+    use c_dns::compression::decompress;
+    use std::error::Error;
+    use std::io::{BufReader, Read};
+
+    /// Stream the object at `url` through a decompressor.
+    ///
+    /// `s3://bucket/key` URLs are fetched anonymously over HTTPS.
+    /// The endpoint defaults to AWS and can be changed with the `AWS_ENDPOINT_URL` environment variable.
+    pub fn open(url: &str) -> Result<Box<dyn Read>, Box<dyn Error>> {
+        let url = match url.strip_prefix("s3://") {
+            Some(path) => s3_url(path)?,
+            None => url.to_string(),
+        };
+        let response = ureq::get(&url).call()?;
+        Ok(decompress(BufReader::new(
+            response.into_body().into_reader(),
+        ))?)
+    }
+
+    fn s3_url(path: &str) -> Result<String, Box<dyn Error>> {
+        let (bucket, key) = path
+            .split_once('/')
+            .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+            .ok_or("Expected an S3 URL of the form s3://bucket/key")?;
+        Ok(match std::env::var("AWS_ENDPOINT_URL") {
+            Ok(endpoint) => format!("{}/{}/{}", endpoint.trim_end_matches('/'), bucket, key),
+            Err(_) => format!("https://{}.s3.amazonaws.com/{}", bucket, key),
+        })
+    }
+}
+
+/// Print the parameters of the file in plain language.
+fn explain(file: &Path) -> Result<(), Box<dyn Error>> {
+    let mut reader = FileReader::new(open_input(file)?)?;
+    let mut blocks = vec![0; reader.file_preamble().block_parameters.len()];
+    for block in &mut reader {
+        let idx = block?.block_preamble.block_parameters_index.unwrap_or(0);
+        if let Some(count) = blocks.get_mut(idx) {
+            *count += 1;
+        }
+    }
+    println!(
+        "====================\nFile: {}\n====================\n",
+        file.display(),
+    );
+    for (idx, block_parameters) in reader.file_preamble().block_parameters.iter().enumerate() {
+        println!("Block parameters {} (used by {} blocks)", idx, blocks[idx]);
+        println!("{}", block_parameters.explain());
     }
     Ok(())
 }
@@ -64,9 +226,22 @@ fn print_help() {
         r#"Test if a C-DNS file can be parsed.
 Print the content of the file in human readable form.
 
+Usage:
+c-dns-debug-print [--dump-serialized] FILE...
+c-dns-debug-print explain FILE...
+
+Commands:
+explain: Describe the storage parameters, storage hints, and collection parameters in plain language.
+         This shows which data the file can contain and which data was never collected.
+
+Files can be local paths or, with the `remote` feature, http://, https://, and s3:// URLs.
+Compressed files are decompressed transparently.
+Files are read one block at a time, except with --dump-serialized, which needs the complete file.
+
 Arguments:
 --help, -h: Print this help message
 --dump-serialized: Create a new FILE.new.cdns file by re-serializing the content.
-               This is useful to test that round-trip convertion is lossless."#
+               This is useful to test that round-trip convertion is lossless.
+               The original CBOR encoding is kept, so the new file is byte-identical if no value was lost."#
     );
 }
@@ -0,0 +1,753 @@
+//! Builders for the parameters in the [`FilePreamble`] and for [`Block`]s
+//!
+//! The parameter structs have many optional fields with restricted value ranges, which deserialization does not check.
+//! The builders here validate the values when building, such that a writer cannot produce out of range parameters by accident.
+//!
+//! A [`BlockBuilder`] takes Q/R items with their addresses, names, and records instead of table indices.
+//! It deduplicates the values into the [`BlockTables`] and assigns the indices.
+//!
+//! ```
+//! # use c_dns::builder::{CollectionParametersBuilder, StorageParametersBuilder};
+//! # use c_dns::serialization::{BlockParameters, ExtraValues};
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let block_parameters = BlockParameters {
+//!     storage_parameters: StorageParametersBuilder::new(1_000_000, 5000)
+//!         .client_address_prefix_ipv4(24)
+//!         .build()?,
+//!     collection_parameters: Some(
+//!         CollectionParametersBuilder::new()
+//!             .query_timeout(5000)
+//!             .skew_timeout(10)
+//!             .interface("eth0")
+//!             .vlan_id(100)
+//!             .build()?,
+//!     ),
+//!     extra_values: ExtraValues::new(),
+//! };
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{bail, invalid};
+use crate::serialization::*;
+use crate::Result;
+use enumset::EnumSet;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Settings of a packet capture, which are recorded in the [`CollectionParameters`].
+///
+/// Implement this for the configuration type of a collector to fill the parameters with [`CollectionParametersBuilder::capture_config`].
+/// All methods default to not providing a value.
+pub trait CaptureConfig {
+    /// Names of the interfaces packets are captured on.
+    fn interfaces(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Filter for input, in "tcpdump" pcap-filter style.
+    fn filter(&self) -> Option<String> {
+        None
+    }
+
+    /// Maximal number of bytes captured per packet.
+    fn snaplen(&self) -> Option<u32> {
+        None
+    }
+
+    /// Whether the interfaces are in promiscuous mode.
+    fn promisc(&self) -> Option<bool> {
+        None
+    }
+
+    /// Human-readable string identifying the collector.
+    fn generator_id(&self) -> Option<String> {
+        None
+    }
+
+    /// String identifying the collecting host.
+    fn host_id(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Builder for [`CollectionParameters`]
+///
+/// [`CollectionParametersBuilder::build`] checks that
+///
+/// * VLAN IDs are in the range 1 to 4094,
+/// * `query_timeout`, `skew_timeout`, and `snaplen` are not zero, and
+/// * the `skew_timeout` (in microseconds) is shorter than the `query_timeout` (in milliseconds).
+#[derive(Debug, Default)]
+pub struct CollectionParametersBuilder {
+    params: CollectionParameters,
+}
+
+impl CollectionParametersBuilder {
+    /// Create a builder without any parameters set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time in milliseconds within which a Response must arrive to be matched with a Query.
+    pub fn query_timeout(mut self, milliseconds: u32) -> Self {
+        self.params.query_timeout = Some(milliseconds);
+        self
+    }
+
+    /// Time in microseconds a Response may be reported before its Query.
+    pub fn skew_timeout(mut self, microseconds: u32) -> Self {
+        self.params.skew_timeout = Some(microseconds);
+        self
+    }
+
+    /// Collect up to this many bytes per packet.
+    pub fn snaplen(mut self, snaplen: u32) -> Self {
+        self.params.snaplen = Some(snaplen);
+        self
+    }
+
+    /// Whether promiscuous mode was enabled on the interfaces.
+    pub fn promisc(mut self, promisc: bool) -> Self {
+        self.params.promisc = Some(promisc);
+        self
+    }
+
+    /// Add an interface used for collection.
+    pub fn interface(mut self, interface: impl Into<String>) -> Self {
+        self.params
+            .interfaces
+            .get_or_insert_with(Vec::new)
+            .push(interface.into());
+        self
+    }
+
+    /// Add a server address used for collection.
+    pub fn server_address(mut self, address: IpAddr) -> Self {
+        self.params
+            .server_addresses
+            .get_or_insert_with(Vec::new)
+            .push(address);
+        self
+    }
+
+    /// Add a VLAN selected for collection.
+    pub fn vlan_id(mut self, vlan_id: u16) -> Self {
+        self.params
+            .vlan_ids
+            .get_or_insert_with(Vec::new)
+            .push(vlan_id);
+        self
+    }
+
+    /// Filter for input, in "tcpdump" pcap-filter style.
+    pub fn filter(mut self, filter: impl Into<String>) -> Self {
+        self.params.filter = Some(filter.into());
+        self
+    }
+
+    /// Human-readable string identifying the collection method.
+    pub fn generator_id(mut self, generator_id: impl Into<String>) -> Self {
+        self.params.generator_id = Some(generator_id.into());
+        self
+    }
+
+    /// String identifying the collecting host.
+    pub fn host_id(mut self, host_id: impl Into<String>) -> Self {
+        self.params.host_id = Some(host_id.into());
+        self
+    }
+
+    /// Set all values provided by the capture configuration.
+    ///
+    /// The interfaces are added to the already configured ones, all other values are replaced.
+    pub fn capture_config(mut self, config: &impl CaptureConfig) -> Self {
+        for interface in config.interfaces() {
+            self = self.interface(interface);
+        }
+        let params = &mut self.params;
+        params.filter = config.filter().or(params.filter.take());
+        params.snaplen = config.snaplen().or(params.snaplen);
+        params.promisc = config.promisc().or(params.promisc);
+        params.generator_id = config.generator_id().or(params.generator_id.take());
+        params.host_id = config.host_id().or(params.host_id.take());
+        self
+    }
+
+    /// Validate the values and create the [`CollectionParameters`].
+    pub fn build(self) -> Result<CollectionParameters> {
+        let params = self.params;
+        for vlan_id in params.vlan_ids.iter().flatten() {
+            if !(1..=4094).contains(vlan_id) {
+                bail!(
+                    "Invalid VLAN ID {}. Expected a value from 1 to 4094.",
+                    vlan_id
+                );
+            }
+        }
+        for (name, value) in [
+            ("query_timeout", params.query_timeout),
+            ("skew_timeout", params.skew_timeout),
+            ("snaplen", params.snaplen),
+        ] {
+            if value == Some(0) {
+                bail!("The {} must not be zero", name);
+            }
+        }
+        if let (Some(query_timeout), Some(skew_timeout)) =
+            (params.query_timeout, params.skew_timeout)
+        {
+            if u64::from(skew_timeout) >= u64::from(query_timeout) * 1000 {
+                bail!(
+                    "The skew_timeout of {}µs must be shorter than the query_timeout of {}ms",
+                    skew_timeout,
+                    query_timeout
+                );
+            }
+        }
+        Ok(params)
+    }
+}
+
+/// Builder for [`StorageParameters`]
+///
+/// The [`StorageHints`] default to all fields being stored.
+/// [`StorageParametersBuilder::build`] checks that
+///
+/// * `ticks_per_second` is not zero, and
+/// * address prefix lengths are in the range 1 to 32 for IPv4 and 1 to 128 for IPv6.
+#[derive(Debug)]
+pub struct StorageParametersBuilder {
+    params: StorageParameters,
+}
+
+impl StorageParametersBuilder {
+    /// Create a builder with the required parameters.
+    pub fn new(ticks_per_second: u32, max_block_items: usize) -> Self {
+        Self {
+            params: StorageParameters {
+                ticks_per_second: ticks_per_second.into(),
+                max_block_items,
+                storage_hints: StorageHints {
+                    query_response_hints: EnumSet::all(),
+                    query_response_signature_hints: EnumSet::all(),
+                    rr_hints: EnumSet::all(),
+                    other_data_hints: EnumSet::all(),
+                    extra_values: ExtraValues::new(),
+                },
+                opcodes: Vec::new(),
+                rr_types: Vec::new(),
+                storage_flags: None,
+                client_address_prefix_ipv4: None,
+                client_address_prefix_ipv6: None,
+                server_address_prefix_ipv4: None,
+                server_address_prefix_ipv6: None,
+                sampling_method: None,
+                anonymization_method: None,
+                extra_values: ExtraValues::new(),
+            },
+        }
+    }
+
+    /// Hints which fields are stored.
+    pub fn storage_hints(mut self, storage_hints: StorageHints) -> Self {
+        self.params.storage_hints = storage_hints;
+        self
+    }
+
+    /// Add an OPCODE recorded by the collector.
+    pub fn opcode(mut self, opcode: Opcode) -> Self {
+        self.params.opcodes.push(opcode);
+        self
+    }
+
+    /// Add an RR TYPE recorded by the collector.
+    pub fn rr_type(mut self, rr_type: DnsType) -> Self {
+        self.params.rr_types.push(rr_type);
+        self
+    }
+
+    /// Add a flag describing the stored data.
+    pub fn storage_flag(mut self, flag: StorageFlags) -> Self {
+        *self.params.storage_flags.get_or_insert_with(EnumSet::new) |= flag;
+        self
+    }
+
+    /// Only store the first `prefix_len` bits of IPv4 client addresses.
+    pub fn client_address_prefix_ipv4(mut self, prefix_len: u8) -> Self {
+        self.params.client_address_prefix_ipv4 = Some(prefix_len);
+        self
+    }
+
+    /// Only store the first `prefix_len` bits of IPv6 client addresses.
+    pub fn client_address_prefix_ipv6(mut self, prefix_len: u8) -> Self {
+        self.params.client_address_prefix_ipv6 = Some(prefix_len);
+        self
+    }
+
+    /// Only store the first `prefix_len` bits of IPv4 server addresses.
+    pub fn server_address_prefix_ipv4(mut self, prefix_len: u8) -> Self {
+        self.params.server_address_prefix_ipv4 = Some(prefix_len);
+        self
+    }
+
+    /// Only store the first `prefix_len` bits of IPv6 server addresses.
+    pub fn server_address_prefix_ipv6(mut self, prefix_len: u8) -> Self {
+        self.params.server_address_prefix_ipv6 = Some(prefix_len);
+        self
+    }
+
+    /// Information on the sampling method used.
+    ///
+    /// This also sets [`StorageFlags::SampledData`].
+    pub fn sampling_method(mut self, method: impl Into<String>) -> Self {
+        self.params.sampling_method = Some(method.into());
+        self.storage_flag(StorageFlags::SampledData)
+    }
+
+    /// Information on the anonymization method used.
+    ///
+    /// This also sets [`StorageFlags::AnonymizedData`].
+    pub fn anonymization_method(mut self, method: impl Into<String>) -> Self {
+        self.params.anonymization_method = Some(method.into());
+        self.storage_flag(StorageFlags::AnonymizedData)
+    }
+
+    /// Validate the values and create the [`StorageParameters`].
+    pub fn build(self) -> Result<StorageParameters> {
+        let params = self.params;
+        if u32::from(params.ticks_per_second) == 0 {
+            bail!("The ticks_per_second must not be zero");
+        }
+        for (name, prefix_len, max) in [
+            (
+                "client_address_prefix_ipv4",
+                params.client_address_prefix_ipv4,
+                32,
+            ),
+            (
+                "client_address_prefix_ipv6",
+                params.client_address_prefix_ipv6,
+                128,
+            ),
+            (
+                "server_address_prefix_ipv4",
+                params.server_address_prefix_ipv4,
+                32,
+            ),
+            (
+                "server_address_prefix_ipv6",
+                params.server_address_prefix_ipv6,
+                128,
+            ),
+        ] {
+            if let Some(prefix_len) = prefix_len {
+                if !(1..=max).contains(&prefix_len) {
+                    bail!(
+                        "Invalid {} of {}. Expected a value from 1 to {}.",
+                        name,
+                        prefix_len,
+                        max
+                    );
+                }
+            }
+        }
+        Ok(params)
+    }
+}
+
+/// A Q/R item with values instead of indices into the [`BlockTables`], see [`BlockBuilder::push`].
+#[derive(Debug, Default)]
+pub struct QueryResponseRecord {
+    /// Timestamp of the Query, or the Response if there is no Query, in the ticks of the block.
+    pub time: Option<Timestamp>,
+    /// Client IP address, the address prefix of the [`StorageParameters`] is applied.
+    pub client_address: Option<std::net::IpAddr>,
+    /// Client port.
+    pub client_port: Option<u16>,
+    /// DNS transaction identifier.
+    pub transaction_id: Option<u16>,
+    /// The IPv4 TTL or IPv6 Hoplimit from the Query packet.
+    pub client_hoplimit: Option<u8>,
+    /// The time difference between Query and Response, in ticks.
+    pub response_delay: Option<Ticks>,
+    /// Name of the first Question.
+    pub query_name: Option<NameOrRdata>,
+    /// DNS size of the Query.
+    pub query_size: Option<u16>,
+    /// DNS size of the Response.
+    pub response_size: Option<u16>,
+    /// Owner name of the Response bailiwick.
+    pub bailiwick: Option<NameOrRdata>,
+    /// Flags relating to Response processing.
+    pub processing_flags: Option<EnumSet<ResponseProcessingFlags>>,
+    /// Signature of the Q/R item.
+    pub signature: Option<SignatureRecord>,
+    /// Extended information about the Query.
+    pub query_extended: Option<SectionsRecord>,
+    /// Extended information about the Response.
+    pub response_extended: Option<SectionsRecord>,
+}
+
+/// A [`QueryResponseSignature`] with values instead of indices into the [`BlockTables`].
+#[derive(Debug, Default)]
+pub struct SignatureRecord {
+    /// Server IP address, the address prefix of the [`StorageParameters`] is applied.
+    pub server_address: Option<std::net::IpAddr>,
+    /// CLASS and TYPE of the first Question.
+    pub query_classtype: Option<ClassType>,
+    /// RDATA of the OPT RR in the Query.
+    pub query_opt_rdata: Option<NameOrRdata>,
+    /// All other fields of the signature.
+    ///
+    /// The `*_index` fields are ignored and replaced by the indices of the values above.
+    pub signature: QueryResponseSignature,
+}
+
+/// The sections of a [`QueryResponseExtended`] with values instead of indices into the [`BlockTables`].
+///
+/// Empty sections are not stored.
+#[derive(Debug, Default)]
+pub struct SectionsRecord {
+    /// Second and subsequent Questions.
+    pub questions: Vec<QuestionRecord>,
+    /// Answer section.
+    pub answers: Vec<RRRecord>,
+    /// Authority section.
+    pub authorities: Vec<RRRecord>,
+    /// Additional section.
+    pub additionals: Vec<RRRecord>,
+}
+
+/// A [`Question`] with values instead of indices into the [`BlockTables`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QuestionRecord {
+    /// Owner name.
+    pub name: NameOrRdata,
+    /// CLASS and TYPE.
+    pub classtype: ClassType,
+}
+
+/// A [`RR`] with values instead of indices into the [`BlockTables`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RRRecord {
+    /// Owner name.
+    pub name: NameOrRdata,
+    /// CLASS and TYPE.
+    pub classtype: ClassType,
+    /// TTL.
+    pub ttl: Option<u32>,
+    /// RDATA in wire format.
+    pub rdata: Option<NameOrRdata>,
+}
+
+impl Default for QueryResponseSignature {
+    fn default() -> Self {
+        Self {
+            server_address_index: None,
+            server_port: None,
+            qr_transport_flags: None,
+            qr_type: None,
+            qr_sig_flags: None,
+            query_opcode: None,
+            qr_dns_flags: None,
+            query_rcode: None,
+            query_classtype_index: None,
+            query_qdcount: None,
+            query_ancount: None,
+            query_nscount: None,
+            query_arcount: None,
+            query_edns_version: None,
+            query_udp_size: None,
+            query_opt_rdata_index: None,
+            response_rcode: None,
+            extra_values: ExtraValues::new(),
+        }
+    }
+}
+
+/// A table of the [`BlockTables`] together with the positions of its entries.
+#[derive(Debug)]
+struct Table<K, V> {
+    entries: Vec<V>,
+    positions: HashMap<K, usize>,
+}
+
+impl<K: Hash + Eq, V> Default for Table<K, V> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            positions: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> Table<K, V> {
+    /// Index of the entry for `key`, adding the entry created by `entry` if the key is new.
+    fn index(&mut self, key: K, entry: impl FnOnce() -> V) -> usize {
+        let entries = &mut self.entries;
+        *self.positions.entry(key).or_insert_with(|| {
+            entries.push(entry());
+            entries.len() - 1
+        })
+    }
+
+    fn into_entries(self) -> Option<Vec<V>> {
+        (!self.entries.is_empty()).then_some(self.entries)
+    }
+}
+
+/// Builder for a [`Block`] from [`QueryResponseRecord`]s
+///
+/// Identical values share a single entry in the [`BlockTables`].
+/// Signatures are identical if their CBOR encoding is identical.
+/// The `earliest_time` of the block is the earliest time of all items, and the time offsets are relative to it.
+///
+/// ```
+/// # use c_dns::builder::{BlockBuilder, QueryResponseRecord, StorageParametersBuilder};
+/// # use c_dns::serialization::{NameOrRdata, Timestamp};
+/// # fn main() -> color_eyre::eyre::Result<()> {
+/// let storage_parameters = StorageParametersBuilder::new(1_000_000, 5000).build()?;
+/// let mut builder = BlockBuilder::new(&storage_parameters);
+/// for client in ["192.0.2.1", "192.0.2.2", "192.0.2.1"] {
+///     builder.push(QueryResponseRecord {
+///         time: Some(Timestamp {
+///             timestamp_secs: 1_600_000_000,
+///             timestamp_ticks: 0.into(),
+///         }),
+///         client_address: Some(client.parse()?),
+///         query_name: Some(NameOrRdata::from(bytes::Bytes::from_static(b"\x07example\x00"))),
+///         ..Default::default()
+///     });
+/// }
+/// let block = builder.build()?;
+/// let block_tables = block.block_tables.as_ref().unwrap();
+/// assert_eq!(2, block_tables.ip_address.as_ref().unwrap().len());
+/// assert_eq!(1, block_tables.name_rdata.as_ref().unwrap().len());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct BlockBuilder {
+    ticks_per_second: i128,
+    address_prefixes: [Option<u8>; 4],
+    block_parameters_index: Option<usize>,
+    ip_address: Table<IpAddr, IpAddr>,
+    classtype: Table<ClassType, ClassType>,
+    name_rdata: Table<NameOrRdata, NameOrRdata>,
+    qr_sig: Table<Vec<u8>, QueryResponseSignature>,
+    qlist: Table<QuestionList, QuestionList>,
+    qrr: Table<(usize, usize), Question>,
+    rrlist: Table<RRList, RRList>,
+    rr: Table<(usize, usize, Option<u32>, Option<usize>), RR>,
+    query_responses: Vec<(Option<i128>, QueryResponse)>,
+}
+
+impl BlockBuilder {
+    /// Create a builder for a block using the `storage_parameters`.
+    ///
+    /// The `ticks_per_second` and the address prefixes are taken from the `storage_parameters`.
+    pub fn new(storage_parameters: &StorageParameters) -> Self {
+        Self {
+            ticks_per_second: i128::from(u32::from(storage_parameters.ticks_per_second)),
+            address_prefixes: [
+                storage_parameters.client_address_prefix_ipv4,
+                storage_parameters.client_address_prefix_ipv6,
+                storage_parameters.server_address_prefix_ipv4,
+                storage_parameters.server_address_prefix_ipv6,
+            ],
+            block_parameters_index: None,
+            ip_address: Table::default(),
+            classtype: Table::default(),
+            name_rdata: Table::default(),
+            qr_sig: Table::default(),
+            qlist: Table::default(),
+            qrr: Table::default(),
+            rrlist: Table::default(),
+            rr: Table::default(),
+            query_responses: Vec::new(),
+        }
+    }
+
+    /// Index of the [`BlockParameters`] in the [`FilePreamble`] which apply to the block.
+    pub fn block_parameters_index(mut self, index: usize) -> Self {
+        self.block_parameters_index = Some(index);
+        self
+    }
+
+    /// Number of Q/R items added so far.
+    pub fn len(&self) -> usize {
+        self.query_responses.len()
+    }
+
+    /// Whether no Q/R item was added yet.
+    pub fn is_empty(&self) -> bool {
+        self.query_responses.is_empty()
+    }
+
+    /// Add a Q/R item, interning its values into the tables.
+    pub fn push(&mut self, record: QueryResponseRecord) {
+        let time = record.time.map(|time| {
+            i128::from(time.timestamp_secs) * self.ticks_per_second
+                + i128::from(u32::from(time.timestamp_ticks))
+        });
+        let response_processing_data =
+            if record.bailiwick.is_some() || record.processing_flags.is_some() {
+                Some(ResponseProcessingData {
+                    bailiwick_index: record.bailiwick.map(|name| self.name(name)),
+                    processing_flags: record.processing_flags,
+                    extra_values: ExtraValues::new(),
+                })
+            } else {
+                None
+            };
+        let query_response = QueryResponse {
+            time_offset: None,
+            client_address_index: record.client_address.map(|addr| self.address(addr, 0)),
+            client_port: record.client_port,
+            transaction_id: record.transaction_id,
+            qr_signature_index: record.signature.map(|sig| self.signature(sig)),
+            client_hoplimit: record.client_hoplimit,
+            response_delay: record.response_delay,
+            query_name_index: record.query_name.map(|name| self.name(name)),
+            query_size: record.query_size,
+            response_size: record.response_size,
+            response_processing_data,
+            query_extended: record
+                .query_extended
+                .map(|sections| self.sections(sections)),
+            response_extended: record
+                .response_extended
+                .map(|sections| self.sections(sections)),
+            extra_values: ExtraValues::new(),
+        };
+        self.query_responses.push((time, query_response));
+    }
+
+    /// Create the [`Block`] with all added Q/R items.
+    ///
+    /// The [`BlockStatistics`] are derived from the items with [`Block::compute_statistics`].
+    /// Fails if the items span more time than a time offset can store, or if items have a time and the `ticks_per_second` is zero.
+    pub fn build(self) -> Result<Block> {
+        let earliest = self
+            .query_responses
+            .iter()
+            .filter_map(|(time, _)| *time)
+            .min();
+        if earliest.is_some() && self.ticks_per_second == 0 {
+            bail!("The ticks_per_second must not be zero");
+        }
+        let mut query_responses = Vec::with_capacity(self.query_responses.len());
+        for (time, mut query_response) in self.query_responses {
+            if let (Some(time), Some(earliest)) = (time, earliest) {
+                let offset = u32::try_from(time - earliest)
+                    .map_err(|_| invalid!("The Q/R items span too much time for one block"))?;
+                query_response.time_offset = Some(UTicks::from(offset));
+            }
+            query_responses.push(query_response);
+        }
+
+        let earliest_time = earliest.map(|ticks| Timestamp {
+            timestamp_secs: ticks.div_euclid(self.ticks_per_second) as i32,
+            timestamp_ticks: UTicks::from(ticks.rem_euclid(self.ticks_per_second) as u32),
+        });
+        let mut block = Block {
+            block_preamble: BlockPreamble {
+                earliest_time,
+                block_parameters_index: self.block_parameters_index,
+                extra_values: ExtraValues::new(),
+            },
+            block_statistics: None,
+            block_tables: Some(BlockTables {
+                ip_address: self.ip_address.into_entries(),
+                classtype: self.classtype.into_entries(),
+                name_rdata: self.name_rdata.into_entries(),
+                qr_sig: self.qr_sig.into_entries(),
+                qlist: self.qlist.into_entries(),
+                qrr: self.qrr.into_entries(),
+                rrlist: self.rrlist.into_entries(),
+                rr: self.rr.into_entries(),
+                malformed_message_data: None,
+                extra_values: ExtraValues::new(),
+            }),
+            query_responses: (!query_responses.is_empty()).then_some(query_responses),
+            address_event_counts: None,
+            malformed_messages: None,
+            extra_values: ExtraValues::new(),
+        };
+        block.block_statistics = Some(block.compute_statistics());
+        Ok(block)
+    }
+
+    /// Intern an address, `prefix_offset` is 0 for client and 2 for server addresses.
+    fn address(&mut self, addr: std::net::IpAddr, prefix_offset: usize) -> usize {
+        let prefix_len = self.address_prefixes[prefix_offset + usize::from(addr.is_ipv6())];
+        let addr = IpAddr::with_prefix(addr, prefix_len.unwrap_or(u8::MAX));
+        self.ip_address.index(addr.clone(), || addr)
+    }
+
+    fn name(&mut self, name: NameOrRdata) -> usize {
+        self.name_rdata.index(name.clone(), || name)
+    }
+
+    fn classtype(&mut self, classtype: ClassType) -> usize {
+        self.classtype.index(classtype, || classtype)
+    }
+
+    fn signature(&mut self, record: SignatureRecord) -> usize {
+        let mut signature = record.signature;
+        signature.server_address_index = record.server_address.map(|addr| self.address(addr, 2));
+        signature.query_classtype_index = record.query_classtype.map(|ct| self.classtype(ct));
+        signature.query_opt_rdata_index = record.query_opt_rdata.map(|rdata| self.name(rdata));
+        // Serializing plain data into a vector cannot fail
+        let key = serde_cbor::to_vec(&signature).unwrap_or_default();
+        self.qr_sig.index(key, || signature)
+    }
+
+    fn sections(&mut self, sections: SectionsRecord) -> QueryResponseExtended {
+        let questions: QuestionList = sections
+            .questions
+            .into_iter()
+            .map(|question| {
+                let name_index = self.name(question.name);
+                let classtype_index = self.classtype(question.classtype);
+                self.qrr.index((name_index, classtype_index), || Question {
+                    name_index,
+                    classtype_index,
+                    extra_values: ExtraValues::new(),
+                })
+            })
+            .collect();
+        QueryResponseExtended {
+            question_index: (!questions.is_empty())
+                .then(|| self.qlist.index(questions.clone(), || questions)),
+            answer_index: self.rr_section(sections.answers),
+            authority_index: self.rr_section(sections.authorities),
+            additional_index: self.rr_section(sections.additionals),
+            extra_values: ExtraValues::new(),
+        }
+    }
+
+    fn rr_section(&mut self, rrs: Vec<RRRecord>) -> Option<usize> {
+        if rrs.is_empty() {
+            return None;
+        }
+        let list: RRList = rrs
+            .into_iter()
+            .map(|rr| {
+                let name_index = self.name(rr.name);
+                let classtype_index = self.classtype(rr.classtype);
+                let rdata_index = rr.rdata.map(|rdata| self.name(rdata));
+                self.rr
+                    .index((name_index, classtype_index, rr.ttl, rdata_index), || RR {
+                        name_index,
+                        classtype_index,
+                        ttl: rr.ttl,
+                        rdata_index,
+                        extra_values: ExtraValues::new(),
+                    })
+            })
+            .collect();
+        Some(self.rrlist.index(list.clone(), || list))
+    }
+}
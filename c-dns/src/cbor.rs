@@ -0,0 +1,431 @@
+//! Decode the structure of CBOR data items without decoding their values
+//!
+//! The serde based deserialization always decodes complete values.
+//! Splitting a file into blocks, skipping parts of it, or preserving its encoding needs the raw structure instead.
+//! All of these parse the data item heads with the functions in this module.
+//! Nested data items in a slice are only walked through by [`walk_item`], which calls a [`Visitor`] for every data item it passes.
+
+use crate::error::{bail, Context};
+use crate::limits::MAX_DEPTH;
+use crate::Result;
+use std::io::Read;
+
+/// Major type of unsigned integers.
+pub(crate) const MAJOR_UNSIGNED: u8 = 0;
+/// Major type of negative integers.
+pub(crate) const MAJOR_NEGATIVE: u8 = 1;
+/// Major type of byte strings.
+pub(crate) const MAJOR_BYTES: u8 = 2;
+/// Major type of text strings.
+pub(crate) const MAJOR_TEXT: u8 = 3;
+/// Major type of arrays.
+pub(crate) const MAJOR_ARRAY: u8 = 4;
+/// Major type of maps.
+pub(crate) const MAJOR_MAP: u8 = 5;
+/// Major type of tags.
+pub(crate) const MAJOR_TAG: u8 = 6;
+/// Major type of floats and simple values.
+pub(crate) const MAJOR_SIMPLE: u8 = 7;
+/// Terminator of indefinite-length items.
+pub(crate) const BREAK: u8 = 0xff;
+
+/// Encoding of the argument of a data item head.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Width {
+    /// Argument stored in the initial byte.
+    Inline,
+    /// Argument stored in 1 following byte.
+    U8,
+    /// Argument stored in 2 following bytes.
+    U16,
+    /// Argument stored in 4 following bytes.
+    U32,
+    /// Argument stored in 8 following bytes.
+    U64,
+    /// Indefinite-length item terminated by a break, or the break itself.
+    Indefinite,
+}
+
+impl Width {
+    /// The shortest width able to store `value`.
+    pub(crate) fn minimal(value: u64) -> Self {
+        match value {
+            0..=23 => Width::Inline,
+            24..=0xff => Width::U8,
+            0x100..=0xffff => Width::U16,
+            0x1_0000..=0xffff_ffff => Width::U32,
+            _ => Width::U64,
+        }
+    }
+
+    /// Whether `value` can be stored with this width.
+    pub(crate) fn fits(self, value: u64) -> bool {
+        match self {
+            Width::Inline => value < 24,
+            Width::U8 => value <= 0xff,
+            Width::U16 => value <= 0xffff,
+            Width::U32 => value <= 0xffff_ffff,
+            Width::U64 => true,
+            Width::Indefinite => false,
+        }
+    }
+
+    /// The width encoded by the additional information of the initial byte of a data item with `major` type.
+    ///
+    /// Fails for reserved values, and for indefinite lengths of types which have no length.
+    fn from_initial(major: u8, info: u8) -> Option<Self> {
+        Some(match info {
+            0..=23 => Width::Inline,
+            24 => Width::U8,
+            25 => Width::U16,
+            26 => Width::U32,
+            27 => Width::U64,
+            31 if (MAJOR_BYTES..=MAJOR_MAP).contains(&major) || major == MAJOR_SIMPLE => {
+                Width::Indefinite
+            }
+            _ => return None,
+        })
+    }
+
+    /// Number of bytes of the argument following the initial byte.
+    fn following_bytes(self) -> usize {
+        match self {
+            Width::Inline | Width::Indefinite => 0,
+            Width::U8 => 1,
+            Width::U16 => 2,
+            Width::U32 => 4,
+            Width::U64 => 8,
+        }
+    }
+}
+
+/// The head of a data item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Head {
+    pub(crate) major: u8,
+    pub(crate) width: Width,
+    /// Argument of the head.
+    ///
+    /// This is the integer value, the length of definite strings, arrays, and maps, the tag number, or the bits of simple values and floats.
+    /// It is 0 for indefinite-length items and breaks.
+    pub(crate) value: u64,
+}
+
+impl Head {
+    /// Decode the head from the initial byte, its `width`, and the bytes of the argument following it.
+    fn decode(initial: u8, width: Width, argument: &[u8]) -> Self {
+        let value = match width {
+            Width::Inline => u64::from(initial & 0x1f),
+            _ => argument
+                .iter()
+                .fold(0, |value, byte| (value << 8) | u64::from(*byte)),
+        };
+        Head {
+            major: initial >> 5,
+            width,
+            value,
+        }
+    }
+
+    /// The argument, or [`None`] for indefinite-length items and breaks.
+    pub(crate) fn argument(&self) -> Option<u64> {
+        (self.width != Width::Indefinite).then_some(self.value)
+    }
+
+    /// Number of entries of an array or key-value pairs of a map, as the number of contained data items.
+    ///
+    /// Fails if the number overflows, which only happens for hostile map lengths.
+    pub(crate) fn item_count(&self, offset: usize) -> Result<u64> {
+        match self.major {
+            MAJOR_MAP => match self.value.checked_mul(2) {
+                Some(count) => Ok(count),
+                None => bail!(
+                    "Map length {} at offset {} is too large",
+                    self.value,
+                    offset
+                ),
+            },
+            _ => Ok(self.value),
+        }
+    }
+}
+
+/// Check that nesting `depth` levels of arrays, maps, and tags does not exceed [`MAX_DEPTH`].
+fn check_depth(depth: usize, offset: usize) -> Result<()> {
+    if depth > MAX_DEPTH {
+        bail!("Nesting deeper than {} at offset {}", MAX_DEPTH, offset);
+    }
+    Ok(())
+}
+
+/// The width of the argument of a data item starting with the `initial` byte at `offset`.
+fn width(initial: u8, offset: usize) -> Result<Width> {
+    match Width::from_initial(initial >> 5, initial & 0x1f) {
+        Some(width) => Ok(width),
+        None => bail!(
+            "Invalid additional information {} for major type {} at offset {}",
+            initial & 0x1f,
+            initial >> 5,
+            offset
+        ),
+    }
+}
+
+/// Read the head of the data item starting at `pos` and advance `pos` past it.
+pub(crate) fn parse_head(bytes: &[u8], pos: &mut usize) -> Result<Head> {
+    let offset = *pos;
+    let initial = match bytes.get(offset) {
+        Some(initial) => *initial,
+        None => bail!("Unexpected end of input at offset {}", offset),
+    };
+    let width = width(initial, offset)?;
+    let end = offset + 1 + width.following_bytes();
+    let argument = match bytes.get(offset + 1..end) {
+        Some(argument) => argument,
+        None => bail!("Unexpected end of input at offset {}", offset),
+    };
+    *pos = end;
+    Ok(Head::decode(initial, width, argument))
+}
+
+/// Read the head of a data item from `reader`, appending its bytes to `buffer`.
+pub(crate) fn read_head(reader: &mut impl Read, buffer: &mut Vec<u8>) -> Result<Head> {
+    let start = buffer.len();
+    let mut initial = [0];
+    reader
+        .read_exact(&mut initial)
+        .context("Unexpected end of input")?;
+    let width = width(initial[0], start)?;
+    buffer.push(initial[0]);
+    buffer.resize(start + 1 + width.following_bytes(), 0);
+    reader
+        .read_exact(&mut buffer[start + 1..])
+        .context("Unexpected end of input")?;
+    Ok(Head::decode(initial[0], width, &buffer[start + 1..]))
+}
+
+/// Read the head of a data item, returning the major type and argument.
+///
+/// The argument is [`None`] for indefinite-length items and breaks.
+pub(crate) fn head(bytes: &[u8], pos: &mut usize) -> Result<(u8, Option<u64>)> {
+    let head = parse_head(bytes, pos)?;
+    Ok((head.major, head.argument()))
+}
+
+/// Read the head of an array, returning the length or [`None`] for indefinite-length arrays.
+pub(crate) fn array_head(bytes: &[u8], pos: &mut usize) -> Result<Option<u64>> {
+    let offset = *pos;
+    match head(bytes, pos)? {
+        (MAJOR_ARRAY, len) => Ok(len),
+        _ => bail!("Expected an array at offset {}", offset),
+    }
+}
+
+/// Callbacks of [`walk_item`] for the data items it passes.
+///
+/// The default implementations do nothing, so `()` is a visitor which only skips the items.
+pub(crate) trait Visitor {
+    /// Called with the `head` of every data item starting at `offset`, before its content.
+    fn start(&mut self, _head: &Head, _offset: usize) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called with the content of every definite-length string, which includes the chunks of indefinite-length strings.
+    fn content(&mut self, _content: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called with the `head` of every data item after its content.
+    fn end(&mut self, _head: &Head) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Visitor for () {}
+
+/// Advance `pos` past the data item starting at `pos`, without decoding it.
+pub(crate) fn skip_item(bytes: &[u8], pos: &mut usize) -> Result<()> {
+    walk_item(bytes, pos, &mut ())
+}
+
+/// Advance `pos` past the data item starting at `pos`, calling `visitor` for it and all data items nested in it.
+///
+/// This checks the nesting depth, that all strings lie within `bytes`, and that indefinite-length strings only consist of definite-length chunks of the same type.
+pub(crate) fn walk_item(bytes: &[u8], pos: &mut usize, visitor: &mut impl Visitor) -> Result<()> {
+    walk_nested(bytes, pos, visitor, 0)
+}
+
+fn walk_nested(
+    bytes: &[u8],
+    pos: &mut usize,
+    visitor: &mut impl Visitor,
+    depth: usize,
+) -> Result<()> {
+    let offset = *pos;
+    check_depth(depth, offset)?;
+    let head = parse_head(bytes, pos)?;
+    if head.major == MAJOR_SIMPLE && head.width == Width::Indefinite {
+        bail!("Unexpected break at offset {}", offset);
+    }
+    visitor.start(&head, offset)?;
+    match (head.major, head.argument()) {
+        (MAJOR_BYTES | MAJOR_TEXT, Some(len)) => visitor.content(take(bytes, pos, len)?)?,
+        (MAJOR_BYTES | MAJOR_TEXT, None) => {
+            while !at_break(bytes, pos)? {
+                let mut chunk_end = *pos;
+                let chunk = parse_head(bytes, &mut chunk_end)?;
+                if chunk.major != head.major || chunk.width == Width::Indefinite {
+                    bail!(
+                        "Invalid chunk in indefinite-length string at offset {}",
+                        *pos
+                    );
+                }
+                walk_nested(bytes, pos, visitor, depth + 1)?;
+            }
+        }
+        (MAJOR_ARRAY | MAJOR_MAP, Some(_)) => {
+            for _ in 0..head.item_count(offset)? {
+                walk_nested(bytes, pos, visitor, depth + 1)?;
+            }
+        }
+        (MAJOR_ARRAY | MAJOR_MAP, None) => {
+            while !at_break(bytes, pos)? {
+                walk_nested(bytes, pos, visitor, depth + 1)?;
+                if head.major == MAJOR_MAP {
+                    walk_nested(bytes, pos, visitor, depth + 1)?;
+                }
+            }
+        }
+        (MAJOR_TAG, _) => walk_nested(bytes, pos, visitor, depth + 1)?,
+        _ => {}
+    }
+    visitor.end(&head)
+}
+
+/// Take the `len` bytes starting at `pos` and advance `pos` past them.
+fn take<'a>(bytes: &'a [u8], pos: &mut usize, len: u64) -> Result<&'a [u8]> {
+    match usize::try_from(len)
+        .ok()
+        .and_then(|len| pos.checked_add(len))
+        .and_then(|end| Some((end, bytes.get(*pos..end)?)))
+    {
+        Some((end, slice)) => {
+            *pos = end;
+            Ok(slice)
+        }
+        None => bail!("Unexpected end of input at offset {}", *pos),
+    }
+}
+
+/// Check for and consume a break.
+fn at_break(bytes: &[u8], pos: &mut usize) -> Result<bool> {
+    match bytes.get(*pos) {
+        Some(&BREAK) => {
+            *pos += 1;
+            Ok(true)
+        }
+        Some(_) => Ok(false),
+        None => bail!("Unexpected end of input at offset {}", *pos),
+    }
+}
+
+/// Position within the elements of an array or the entries of a map.
+///
+/// Definite-length items end after their number of elements, indefinite-length items at the break.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Elements {
+    len: Option<u64>,
+    count: u64,
+}
+
+impl Elements {
+    /// Elements of an array or map, whose head has the argument `len`.
+    pub(crate) fn new(len: Option<u64>) -> Self {
+        Self { len, count: 0 }
+    }
+
+    /// Whether another element starts at `pos`.
+    ///
+    /// The break ending an indefinite-length item is consumed.
+    /// Missing input counts as another element, so decoding it reports the end of input.
+    pub(crate) fn next(&mut self, bytes: &[u8], pos: &mut usize) -> bool {
+        let more = match self.len {
+            Some(len) => self.count < len,
+            None if bytes.get(*pos) == Some(&BREAK) => {
+                *pos += 1;
+                false
+            }
+            None => true,
+        };
+        if more {
+            self.count += 1;
+        }
+        more
+    }
+
+    /// Number of elements so far.
+    pub(crate) fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+/// Read the head of an array from `reader`, returning the length or [`None`] for indefinite-length arrays.
+pub(crate) fn read_array_head(reader: &mut impl Read, buffer: &mut Vec<u8>) -> Result<Option<u64>> {
+    match read_head(reader, buffer)? {
+        head if head.major == MAJOR_ARRAY => Ok(head.argument()),
+        _ => bail!("Expected an array"),
+    }
+}
+
+/// Copy the content following the `head` of a data item from `reader` to `buffer`.
+///
+/// This reads the same items as [`skip_item`].
+/// Strings are read incrementally, so a hostile length does not allocate more memory than the input contains.
+pub(crate) fn read_content(reader: &mut impl Read, buffer: &mut Vec<u8>, head: Head) -> Result<()> {
+    read_nested(reader, buffer, head, 0)
+}
+
+fn read_nested(
+    reader: &mut impl Read,
+    buffer: &mut Vec<u8>,
+    head: Head,
+    depth: usize,
+) -> Result<()> {
+    check_depth(depth, buffer.len())?;
+    match (head.major, head.argument()) {
+        (MAJOR_UNSIGNED | MAJOR_NEGATIVE | MAJOR_SIMPLE, Some(_)) => {}
+        (MAJOR_BYTES | MAJOR_TEXT, Some(len)) => {
+            let read = reader.by_ref().take(len).read_to_end(buffer)?;
+            if read as u64 != len {
+                bail!("Unexpected end of input");
+            }
+        }
+        (MAJOR_ARRAY, Some(len)) => {
+            for _ in 0..len {
+                let head = read_head(reader, buffer)?;
+                read_nested(reader, buffer, head, depth + 1)?;
+            }
+        }
+        (MAJOR_MAP, Some(len)) => {
+            for _ in 0..len {
+                for _ in 0..2 {
+                    let head = read_head(reader, buffer)?;
+                    read_nested(reader, buffer, head, depth + 1)?;
+                }
+            }
+        }
+        (MAJOR_TAG, Some(_)) => {
+            let head = read_head(reader, buffer)?;
+            read_nested(reader, buffer, head, depth + 1)?;
+        }
+        (MAJOR_BYTES..=MAJOR_MAP, None) => loop {
+            let head = read_head(reader, buffer)?;
+            if head.major == MAJOR_SIMPLE && head.argument().is_none() {
+                break;
+            }
+            read_nested(reader, buffer, head, depth + 1)?;
+        },
+        _ => bail!("Unexpected break"),
+    }
+    Ok(())
+}
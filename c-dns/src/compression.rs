@@ -0,0 +1,244 @@
+//! Read and write compressed C-DNS files
+//!
+//! Collectors like compactor usually compress their output, for example into `.cdns.xz` files.
+//! [`decompress`] detects the compression of its input by the magic bytes and decompresses it transparently.
+//! [`File::read_path`] and [`FileReader::open`] use it, so compressed files can be read like uncompressed ones.
+//! [`CompressedWriter`] compresses the output, for example of a [`FileWriter`](crate::serialization::FileWriter).
+//!
+//! Each compression format is only available with its feature: `gzip`, `xz`, and `zstd`.
+//! Reading or writing a format without its feature fails with [`Error::UnsupportedCompression`].
+//!
+//! ```
+//! # use c_dns::compression::Compression;
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let file = File::read_path("./tests/data/dns.cdns")?;
+//! assert_eq!(Compression::None, Compression::detect(&std::fs::read("./tests/data/dns.cdns")?));
+//! assert_eq!(Compression::Xz, Compression::from_path("capture.cdns.xz"));
+//!
+//! let path = std::env::temp_dir().join("c-dns-doctest-compression.cdns");
+//! file.write_path_compressed(&path, Compression::None)?;
+//! assert_eq!(file.block_count(), File::read_path(&path)?.block_count());
+//! # std::fs::remove_file(&path)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::Context;
+use crate::serialization::{File, FileReader};
+use crate::{Error, Result};
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+/// Magic bytes of gzip compressed data
+const MAGIC_GZIP: &[u8] = &[0x1f, 0x8b];
+/// Magic bytes of xz compressed data
+const MAGIC_XZ: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+/// Magic bytes of zstd compressed data
+const MAGIC_ZSTD: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression format of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// Not compressed
+    None,
+    /// gzip, RFC 1952
+    Gzip,
+    /// xz
+    Xz,
+    /// Zstandard, RFC 8878
+    Zstd,
+}
+
+impl Compression {
+    /// Detect the compression format by the magic bytes at the start of `bytes`.
+    ///
+    /// Unknown magic bytes are treated as uncompressed data.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(MAGIC_GZIP) {
+            Compression::Gzip
+        } else if bytes.starts_with(MAGIC_XZ) {
+            Compression::Xz
+        } else if bytes.starts_with(MAGIC_ZSTD) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Select the compression format by the extension of `path`, like `.gz`, `.xz`, or `.zst`.
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("xz") => Compression::Xz,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// Name of the feature enabling the compression format.
+    pub(crate) fn feature(self) -> &'static str {
+        match self {
+            Compression::None => "default",
+            Compression::Gzip => "gzip",
+            Compression::Xz => "xz",
+            Compression::Zstd => "zstd",
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Compression::None => "uncompressed",
+            Compression::Gzip => "gzip",
+            Compression::Xz => "xz",
+            Compression::Zstd => "zstd",
+        })
+    }
+}
+
+/// Decompress `reader` according to the detected [`Compression`].
+///
+/// Concatenated compressed streams are decompressed as one stream.
+/// Fails if the compression format is not enabled.
+pub fn decompress<'a, R: BufRead + 'a>(mut reader: R) -> Result<Box<dyn Read + 'a>> {
+    let compression = Compression::detect(reader.fill_buf()?);
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+        #[cfg(feature = "xz")]
+        Compression::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader)),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?),
+        #[allow(unreachable_patterns)]
+        _ => return Err(Error::UnsupportedCompression(compression)),
+    })
+}
+
+/// A writer compressing all data written to it.
+///
+/// [`CompressedWriter::finish`] must be called to write the end of the compressed stream.
+pub struct CompressedWriter<W: Write> {
+    inner: Encoder<W>,
+}
+
+enum Encoder<W: Write> {
+    None(W),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "xz")]
+    Xz(xz2::write::XzEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    /// Compress the data written to `writer` with the default level of `compression`.
+    ///
+    /// Fails if the compression format is not enabled.
+    pub fn new(writer: W, compression: Compression) -> Result<Self> {
+        let inner = match compression {
+            Compression::None => Encoder::None(writer),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            )),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Encoder::Xz(xz2::write::XzEncoder::new(writer, 6)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Encoder::Zstd(zstd::stream::write::Encoder::new(writer, 0)?),
+            #[allow(unreachable_patterns)]
+            _ => return Err(Error::UnsupportedCompression(compression)),
+        };
+        Ok(Self { inner })
+    }
+
+    /// Write the end of the compressed stream and return the underlying writer.
+    pub fn finish(self) -> Result<W> {
+        Ok(match self.inner {
+            Encoder::None(writer) => writer,
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            #[cfg(feature = "xz")]
+            Encoder::Xz(encoder) => encoder.finish()?,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.finish()?,
+        })
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match &mut self.inner {
+            Encoder::None(writer) => writer,
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(encoder) => encoder,
+            #[cfg(feature = "xz")]
+            Encoder::Xz(encoder) => encoder,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder,
+        }
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer().flush()
+    }
+}
+
+impl<W: Write> fmt::Debug for CompressedWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let compression = match self.inner {
+            Encoder::None(_) => Compression::None,
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(_) => Compression::Gzip,
+            #[cfg(feature = "xz")]
+            Encoder::Xz(_) => Compression::Xz,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(_) => Compression::Zstd,
+        };
+        f.debug_struct("CompressedWriter")
+            .field("compression", &compression)
+            .finish_non_exhaustive()
+    }
+}
+
+impl File {
+    /// Serialize the file, compress it, and store it at `path`, replacing any existing file.
+    ///
+    /// See [`Compression::from_path`] to select the compression by the file name.
+    pub fn write_path_compressed(
+        &self,
+        path: impl AsRef<Path>,
+        compression: Compression,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let write = || -> Result<()> {
+            let output = std::fs::File::create(path)?;
+            let mut writer = CompressedWriter::new(std::io::BufWriter::new(output), compression)?;
+            self.to_writer(&mut writer)?;
+            writer.finish()?.flush()?;
+            Ok(())
+        };
+        write().with_context(|| format!("Failed to write file {}", path.display()))
+    }
+}
+
+impl FileReader<Box<dyn Read>> {
+    /// Open the file at `path` and read its preamble, decompressing it if necessary.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let open = || -> Result<Self> {
+            let input = std::fs::File::open(path)?;
+            FileReader::new(decompress(BufReader::new(input))?)
+        };
+        open().with_context(|| format!("Failed to read C-DNS file {}", path.display()))
+    }
+}
@@ -0,0 +1,424 @@
+//! Read files written in draft versions of the format
+//!
+//! Before RFC 8618 was published, tools like `compactor` wrote files in draft versions of the format, marked with major format version 0.
+//! These drafts use different field numbers and lack some fields, so they cannot be deserialized into the current structs directly.
+//!
+//! A [`DraftLayout`] describes how the map keys of a draft version correspond to the keys of the current version.
+//! Drafts also stored a single configuration map in the [`FilePreamble`](crate::serialization::FilePreamble) instead of the array of [`BlockParameters`](crate::serialization::BlockParameters), which [`DraftLayout::configuration`] describes.
+//! The crate ships no built-in layouts, since the field numbers of the drafts have not been verified against files written by them.
+//! Users with draft files describe their layout and pass it to [`File::from_slice_versioned`], which inspects the format version of the input and converts matching draft files before deserializing them.
+//! Files of the current major version are read like with [`File::from_slice`].
+//!
+//! ```
+//! # use c_dns::draft::DraftLayout;
+//! # use c_dns::extensions::ExtensionScope;
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! # let bytes = std::fs::read("./tests/data/dns.cdns")?;
+//! // A hypothetical draft 0.1, which stored the `query_name_index` of Q/R items at key 13
+//! let mut layout = DraftLayout::new(0, 1);
+//! layout.remap(ExtensionScope::QueryResponse, 13, Some(7));
+//! let file = File::from_slice_versioned(&bytes, &[layout])?;
+//! assert_eq!((1, 0), file.file_preamble.format_version());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{bail, Context};
+use crate::extensions::ExtensionScope;
+use crate::serialization::{
+    DnsType, ExtraValues, File, Opcode, StorageHints, UncheckedFile, MAJOR_FORMAT_VERSION,
+    MINOR_FORMAT_VERSION,
+};
+use crate::{Error, Result};
+use enumset::EnumSet;
+use serde::de::IgnoredAny;
+use serde_cbor::Value;
+use serde_indexed::DeserializeIndexed;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Correspondence between the map keys of a draft format version and the current version.
+///
+/// Keys without a mapping keep their number.
+/// Negative keys are implementation specific and are never remapped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DraftLayout {
+    major_format_version: u32,
+    minor_format_version: u32,
+    keys: BTreeMap<ExtensionScope, BTreeMap<i128, Option<i128>>>,
+    configuration: Option<Configuration>,
+}
+
+/// The configuration map of a draft [`FilePreamble`](crate::serialization::FilePreamble), which becomes the only [`BlockParameters`](crate::serialization::BlockParameters).
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Configuration {
+    preamble_key: i128,
+    ticks_per_second: u32,
+    /// Keys of the [`StorageParameters`](crate::serialization::StorageParameters) and [`CollectionParameters`](crate::serialization::CollectionParameters) for the configuration keys.
+    keys: BTreeMap<i128, (ExtensionScope, i128)>,
+}
+
+impl DraftLayout {
+    /// Create a layout for the format version, which has the same keys as the current version.
+    pub fn new(major_format_version: u32, minor_format_version: u32) -> Self {
+        Self {
+            major_format_version,
+            minor_format_version,
+            keys: BTreeMap::new(),
+            configuration: None,
+        }
+    }
+
+    /// The format version described by this layout.
+    pub fn format_version(&self) -> (u32, u32) {
+        (self.major_format_version, self.minor_format_version)
+    }
+
+    /// Store the field at `draft_key` of a struct of type `scope` at `key` in the current version.
+    ///
+    /// `None` drops the field, if it has no equivalent in the current version.
+    pub fn remap(
+        &mut self,
+        scope: ExtensionScope,
+        draft_key: i128,
+        key: Option<i128>,
+    ) -> &mut Self {
+        self.keys.entry(scope).or_default().insert(draft_key, key);
+        self
+    }
+
+    /// Read the [`BlockParameters`](crate::serialization::BlockParameters) from the configuration map at `preamble_key` of the draft [`FilePreamble`](crate::serialization::FilePreamble).
+    ///
+    /// The converted file has a single [`BlockParameters`](crate::serialization::BlockParameters), which applies to all blocks.
+    /// Drafts did not record the resolution of the time offsets, so `ticks_per_second` gives it.
+    /// The entries of the configuration are moved to the keys given with [`DraftLayout::storage_key`] and [`DraftLayout::collection_key`], all other entries are dropped.
+    ///
+    /// Required storage parameters missing from the configuration are derived from the blocks:
+    /// `max_block_items` is the largest number of items in any array of a block, the storage hints claim that all fields are recorded, and `opcodes` and `rr_types` list the values found in the Q/R signatures and the class and type table.
+    pub fn configuration(&mut self, preamble_key: i128, ticks_per_second: u32) -> &mut Self {
+        self.configuration = Some(Configuration {
+            preamble_key,
+            ticks_per_second,
+            keys: BTreeMap::new(),
+        });
+        self
+    }
+
+    /// Store the configuration entry at `draft_key` at `key` of the [`StorageParameters`](crate::serialization::StorageParameters).
+    ///
+    /// This has no effect without a [`DraftLayout::configuration`].
+    pub fn storage_key(&mut self, draft_key: i128, key: i128) -> &mut Self {
+        self.configuration_key(draft_key, ExtensionScope::StorageParameters, key)
+    }
+
+    /// Store the configuration entry at `draft_key` at `key` of the [`CollectionParameters`](crate::serialization::CollectionParameters).
+    ///
+    /// This has no effect without a [`DraftLayout::configuration`].
+    pub fn collection_key(&mut self, draft_key: i128, key: i128) -> &mut Self {
+        self.configuration_key(draft_key, ExtensionScope::CollectionParameters, key)
+    }
+
+    fn configuration_key(
+        &mut self,
+        draft_key: i128,
+        scope: ExtensionScope,
+        key: i128,
+    ) -> &mut Self {
+        if let Some(configuration) = &mut self.configuration {
+            configuration.keys.insert(draft_key, (scope, key));
+        }
+        self
+    }
+
+    /// Convert the CBOR value of a draft file into a [`File`] in the current format version.
+    ///
+    /// The converted file reports the current format version.
+    /// Fails if the converted file lacks required fields.
+    /// Without a [`DraftLayout::configuration`] this includes the [`BlockParameters`](crate::serialization::BlockParameters), which no key mapping can add.
+    pub fn convert(&self, mut value: Value) -> Result<File> {
+        let items = match &mut value {
+            Value::Array(items) if items.len() == 3 => items,
+            _ => bail!("The File must be an array with 3 elements"),
+        };
+        let preamble = as_map(&mut items[1], "FilePreamble")?;
+        let version = (
+            integer(preamble.get(&key(0))),
+            integer(preamble.get(&key(1))),
+        );
+        if version
+            != (
+                Some(self.major_format_version.into()),
+                Some(self.minor_format_version.into()),
+            )
+        {
+            bail!(
+                "The file does not have the format version {}.{} of the layout",
+                self.major_format_version,
+                self.minor_format_version
+            );
+        }
+        preamble.insert(key(0), Value::Integer(MAJOR_FORMAT_VERSION.into()));
+        preamble.insert(key(1), Value::Integer(MINOR_FORMAT_VERSION.into()));
+        let configuration = match &self.configuration {
+            Some(configuration) => match preamble.remove(&key(configuration.preamble_key)) {
+                Some(Value::Map(entries)) => Some((configuration, entries)),
+                _ => bail!(
+                    "The draft FilePreamble has no configuration map at key {}",
+                    configuration.preamble_key
+                ),
+            },
+            None => None,
+        };
+        self.rewrite(&mut items[1], ExtensionScope::FilePreamble);
+        if let Value::Array(blocks) = &mut items[2] {
+            for block in blocks {
+                self.rewrite(block, ExtensionScope::Block);
+            }
+        }
+        if let Some((configuration, entries)) = configuration {
+            let block_parameters = configuration.block_parameters(entries, &items[2])?;
+            as_map(&mut items[1], "FilePreamble")?
+                .insert(key(3), Value::Array(vec![block_parameters]));
+        }
+
+        let file: UncheckedFile =
+            serde_cbor::value::from_value(value).context("Failed to read the converted file")?;
+        Ok(file.into_file())
+    }
+
+    /// Remap the keys of the map `value` of type `scope`, and of all nested structs.
+    fn rewrite(&self, value: &mut Value, scope: ExtensionScope) {
+        let map = match value {
+            Value::Map(map) => map,
+            _ => return,
+        };
+        if let Some(keys) = self.keys.get(&scope) {
+            *map = std::mem::take(map)
+                .into_iter()
+                .filter_map(|(k, v)| match integer(Some(&k)) {
+                    Some(draft_key) if draft_key >= 0 => match keys.get(&draft_key) {
+                        Some(Some(new_key)) => Some((key(*new_key), v)),
+                        Some(None) => None,
+                        None => Some((k, v)),
+                    },
+                    _ => Some((k, v)),
+                })
+                .collect();
+        }
+        for (child_key, child_scope, is_array) in children(scope) {
+            match map.get_mut(&key(*child_key)) {
+                Some(Value::Array(items)) if *is_array => {
+                    for item in items {
+                        self.rewrite(item, *child_scope);
+                    }
+                }
+                Some(child) if !is_array => self.rewrite(child, *child_scope),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Configuration {
+    /// Build the [`BlockParameters`](crate::serialization::BlockParameters) from the `entries` of the configuration map and the already converted `blocks`.
+    fn block_parameters(&self, entries: BTreeMap<Value, Value>, blocks: &Value) -> Result<Value> {
+        let mut storage = BTreeMap::new();
+        let mut collection = BTreeMap::new();
+        for (draft_key, value) in entries {
+            match integer(Some(&draft_key)).and_then(|draft_key| self.keys.get(&draft_key)) {
+                Some((ExtensionScope::StorageParameters, key)) => {
+                    storage.insert(self::key(*key), value)
+                }
+                Some((_, key)) => collection.insert(self::key(*key), value),
+                None => None,
+            };
+        }
+
+        let blocks = match blocks {
+            Value::Array(blocks) => &blocks[..],
+            _ => &[],
+        };
+        storage
+            .entry(key(0))
+            .or_insert_with(|| Value::Integer(self.ticks_per_second.into()));
+        if let Entry::Vacant(entry) = storage.entry(key(1)) {
+            let max_block_items = blocks
+                .iter()
+                .flat_map(|block| (3..=5).filter_map(move |item_key| field(block, item_key)))
+                .filter_map(|items| match items {
+                    Value::Array(items) => Some(items.len()),
+                    _ => None,
+                })
+                .max()
+                .unwrap_or(0);
+            entry.insert(Value::Integer(max_block_items as i128));
+        }
+        if let Entry::Vacant(entry) = storage.entry(key(2)) {
+            let all_fields = StorageHints {
+                query_response_hints: EnumSet::all(),
+                query_response_signature_hints: EnumSet::all(),
+                rr_hints: EnumSet::all(),
+                other_data_hints: EnumSet::all(),
+                extra_values: ExtraValues::new(),
+            };
+            entry.insert(serde_cbor::value::to_value(all_fields)?);
+        }
+        if let Entry::Vacant(entry) = storage.entry(key(3)) {
+            // Q/R signatures at key 3 of the block tables, with the query opcode at key 5
+            let opcodes = table_values(blocks, 3, 5)
+                .filter_map(|opcode| u8::try_from(opcode).ok())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .filter_map(|opcode| Opcode::try_from(opcode).ok())
+                .collect::<Vec<_>>();
+            if opcodes.is_empty() {
+                bail!(
+                    "The draft file records no opcodes, and the configuration does not list them"
+                );
+            }
+            entry.insert(serde_cbor::value::to_value(opcodes)?);
+        }
+        if let Entry::Vacant(entry) = storage.entry(key(4)) {
+            // Classes and types at key 1 of the block tables, with the type at key 0
+            let rr_types = table_values(blocks, 1, 0)
+                .filter_map(|rr_type| u16::try_from(rr_type).ok())
+                .map(DnsType::from)
+                .collect::<BTreeSet<_>>();
+            if rr_types.is_empty() {
+                bail!(
+                    "The draft file records no RR types, and the configuration does not list them"
+                );
+            }
+            entry.insert(serde_cbor::value::to_value(rr_types)?);
+        }
+
+        let mut block_parameters = BTreeMap::new();
+        block_parameters.insert(key(0), Value::Map(storage));
+        if !collection.is_empty() {
+            block_parameters.insert(key(1), Value::Map(collection));
+        }
+        Ok(Value::Map(block_parameters))
+    }
+}
+
+/// The preamble of a file of any format version.
+///
+/// Preambles of the current major version are left to [`File::from_slice`], which reports errors with their path.
+#[derive(Debug, DeserializeIndexed)]
+#[serde_indexed(discriminator = 0)]
+enum VersionedPreamble {
+    #[serde_indexed(tag = 1)]
+    Current(IgnoredAny),
+    Draft(DraftPreamble),
+}
+
+/// The format version of a preamble of another major version.
+#[derive(Debug, DeserializeIndexed)]
+struct DraftPreamble {
+    major_format_version: u32,
+    minor_format_version: u32,
+    /// The remaining fields differ between the drafts and are read by [`DraftLayout::convert`]
+    #[serde_indexed(extras)]
+    _fields: ExtraValues,
+}
+
+impl File {
+    /// Deserialize a [`File`] from `bytes`, converting files of draft format versions.
+    ///
+    /// Files of the current major format version are read like with [`File::from_slice`].
+    /// Files of a draft version are converted with the layout in `layouts` matching their version, see [`DraftLayout::convert`].
+    /// Fails for other versions.
+    pub fn from_slice_versioned(bytes: &[u8], layouts: &[DraftLayout]) -> Result<File> {
+        let draft =
+            match serde_cbor::from_slice::<(IgnoredAny, VersionedPreamble, IgnoredAny)>(bytes) {
+                Ok((_, VersionedPreamble::Draft(draft), _)) => draft,
+                // Report errors of the current version with the path of the failing value
+                _ => return File::from_slice(bytes),
+            };
+        let (major, minor) = (draft.major_format_version, draft.minor_format_version);
+        match layouts
+            .iter()
+            .find(|layout| layout.format_version() == (major, minor))
+        {
+            Some(layout) => layout.convert(serde_cbor::from_slice(bytes)?),
+            None => Err(Error::UnsupportedVersion { major, minor }),
+        }
+    }
+}
+
+/// The nested structs of a struct of type `scope`, as their key, type, and whether they are stored in an array.
+fn children(scope: ExtensionScope) -> &'static [(i128, ExtensionScope, bool)] {
+    use ExtensionScope::*;
+    match scope {
+        FilePreamble => &[(3, BlockParameters, true)],
+        BlockParameters => &[
+            (0, StorageParameters, false),
+            (1, CollectionParameters, false),
+        ],
+        StorageParameters => &[(2, StorageHints, false)],
+        Block => &[
+            (0, BlockPreamble, false),
+            (1, BlockStatistics, false),
+            (2, BlockTables, false),
+            (3, QueryResponse, true),
+            (4, AddressEventCount, true),
+            (5, MalformedMessage, true),
+        ],
+        BlockTables => &[
+            (3, QueryResponseSignature, true),
+            (5, Question, true),
+            (7, RR, true),
+            (8, MalformedMessageData, true),
+        ],
+        QueryResponse => &[
+            (10, ResponseProcessingData, false),
+            (11, QueryResponseExtended, false),
+            (12, QueryResponseExtended, false),
+        ],
+        _ => &[],
+    }
+}
+
+fn as_map<'a>(value: &'a mut Value, name: &str) -> Result<&'a mut BTreeMap<Value, Value>> {
+    match value {
+        Value::Map(map) => Ok(map),
+        _ => bail!("The {} must be a map", name),
+    }
+}
+
+/// The value at `field_key` of the map `value`.
+fn field(value: &Value, field_key: i128) -> Option<&Value> {
+    match value {
+        Value::Map(map) => map.get(&key(field_key)),
+        _ => None,
+    }
+}
+
+/// The integers at `field_key` of all entries of the table at `table_key` of the block tables of all `blocks`.
+fn table_values(
+    blocks: &[Value],
+    table_key: i128,
+    field_key: i128,
+) -> impl Iterator<Item = i128> + '_ {
+    blocks
+        .iter()
+        .filter_map(move |block| field(field(block, 2)?, table_key))
+        .filter_map(|table| match table {
+            Value::Array(entries) => Some(entries),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(move |entry| integer(field(entry, field_key)))
+}
+
+fn integer(value: Option<&Value>) -> Option<i128> {
+    match value {
+        Some(Value::Integer(i)) => Some(*i),
+        _ => None,
+    }
+}
+
+fn key(key: i128) -> Value {
+    Value::Integer(key)
+}
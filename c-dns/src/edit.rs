@@ -0,0 +1,195 @@
+//! Add Q/R items to an existing block
+//!
+//! The items of a [`Block`] reference the [`BlockTables`] of the block and store their time relative to the [`BlockPreamble.earliest_time`].
+//! A [`DetachedQueryResponse`] is a Q/R item which carries its own tables and an absolute time instead.
+//! [`Block::push_query_response`] adds it to a block, for example to stitch matched Responses into a capture which only contains Queries.
+//!
+//! ```
+//! # use c_dns::edit::DetachedQueryResponse;
+//! # use c_dns::serialization::{BlockTables, File, NameOrRdata, QueryResponse};
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! # let bytes = std::fs::read("./tests/data/dns.cdns")?;
+//! let mut file: File = serde_cbor::from_slice(&bytes)?;
+//! let item = DetachedQueryResponse {
+//!     time: file.file_blocks[0].block_preamble.earliest_time,
+//!     query_response: QueryResponse {
+//!         query_name_index: Some(0),
+//!         ..Default::default()
+//!     },
+//!     block_tables: BlockTables {
+//!         name_rdata: Some(vec![NameOrRdata::from(bytes::Bytes::from_static(b"\x07example\x00"))]),
+//!         ..Default::default()
+//!     },
+//! };
+//! let block_parameters = &file.file_preamble.block_parameters[0];
+//! file.file_blocks[0].push_query_response(item, block_parameters)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{bail, invalid};
+use crate::serialization::*;
+use crate::Result;
+
+/// A Q/R item together with the table entries it references.
+#[derive(Debug)]
+pub struct DetachedQueryResponse {
+    /// Timestamp of the Query, or the Response if there is no Query, in the ticks of the target block.
+    ///
+    /// This replaces the `time_offset` of the `query_response`, which is ignored.
+    pub time: Option<Timestamp>,
+    /// The Q/R item, its indices reference `block_tables`.
+    ///
+    /// The `response_delay` must be in the ticks of the target block.
+    pub query_response: QueryResponse,
+    /// The entries referenced by `query_response`.
+    pub block_tables: BlockTables,
+}
+
+impl Default for QueryResponse {
+    fn default() -> Self {
+        Self {
+            time_offset: None,
+            client_address_index: None,
+            client_port: None,
+            transaction_id: None,
+            qr_signature_index: None,
+            client_hoplimit: None,
+            response_delay: None,
+            query_name_index: None,
+            query_size: None,
+            response_size: None,
+            response_processing_data: None,
+            query_extended: None,
+            response_extended: None,
+            extra_values: ExtraValues::new(),
+        }
+    }
+}
+
+impl Block {
+    /// Add a Q/R item to the end of the block.
+    ///
+    /// IP addresses, CLASS and TYPE pairs, and names or RDATA of the item are interned into the [`BlockTables`], the remaining entries are appended, like with [`BlockTables::append`].
+    /// If the item is earlier than the [`BlockPreamble.earliest_time`], the `earliest_time` moves and the time offsets of all items are adjusted.
+    /// The counts in the [`BlockStatistics`] are updated, but only the counts which are present.
+    ///
+    /// `block_parameters` must be the [`BlockParameters`] of this block, which define the ticks per second.
+    /// Returns the index of the new item in the `query_responses`.
+    /// Fails without changing the block if a time offset does not fit.
+    ///
+    /// # Panics
+    ///
+    /// The indices in the item must be valid for its `block_tables`, as checked by [`File::dangling_indices`].
+    pub fn push_query_response(
+        &mut self,
+        item: DetachedQueryResponse,
+        block_parameters: &BlockParameters,
+    ) -> Result<usize> {
+        let DetachedQueryResponse {
+            time,
+            mut query_response,
+            block_tables,
+        } = item;
+        let ticks_per_second = i128::from(u32::from(
+            block_parameters.storage_parameters.ticks_per_second,
+        ));
+
+        // Determine the time offset first, since moving the earliest time can fail
+        let mut shift = 0;
+        query_response.time_offset = match (time, self.block_preamble.earliest_time) {
+            (None, _) => None,
+            (Some(time), None) => {
+                self.block_preamble.earliest_time = Some(time);
+                Some(UTicks::from(0))
+            }
+            (Some(time), Some(earliest)) => {
+                let offset = ticks(time, ticks_per_second) - ticks(earliest, ticks_per_second);
+                if offset >= 0 {
+                    Some(UTicks::from(u32::try_from(offset).map_err(|_| {
+                        invalid!("The Q/R item is too late for the block")
+                    })?))
+                } else {
+                    shift = -offset;
+                    let offsets = self
+                        .query_responses
+                        .iter()
+                        .flatten()
+                        .map(|qr| qr.time_offset)
+                        .chain(
+                            self.malformed_messages
+                                .iter()
+                                .flatten()
+                                .map(|mm| mm.time_offset),
+                        );
+                    for offset in offsets.flatten() {
+                        if i128::from(u32::from(offset)) + shift > i128::from(u32::MAX) {
+                            bail!("The Q/R item is too early for the block");
+                        }
+                    }
+                    Some(UTicks::from(0))
+                }
+            }
+        };
+        if shift > 0 {
+            self.block_preamble.earliest_time = time;
+            let offsets = self
+                .query_responses
+                .iter_mut()
+                .flatten()
+                .map(|qr| &mut qr.time_offset)
+                .chain(
+                    self.malformed_messages
+                        .iter_mut()
+                        .flatten()
+                        .map(|mm| &mut mm.time_offset),
+                );
+            for offset in offsets.flatten() {
+                *offset = UTicks::from((i128::from(u32::from(*offset)) + shift) as u32);
+            }
+        }
+
+        let flags = query_response
+            .qr_signature_index
+            .and_then(|idx| block_tables.qr_sig.as_ref()?.get(idx))
+            .and_then(|sig| sig.qr_sig_flags)
+            .unwrap_or_default();
+        if let Some(statistics) = &mut self.block_statistics {
+            let has_query = flags.contains(QueryResponseFlags::HasQuery);
+            let has_response = flags.contains(QueryResponseFlags::HasResponse);
+            for (count, increment) in [
+                (&mut statistics.qr_data_items, 1),
+                (
+                    &mut statistics.processed_messages,
+                    usize::from(has_query) + usize::from(has_response),
+                ),
+                (
+                    &mut statistics.unmatched_queries,
+                    usize::from(has_query && !has_response),
+                ),
+                (
+                    &mut statistics.unmatched_responses,
+                    usize::from(has_response && !has_query),
+                ),
+            ] {
+                if let Some(count) = count {
+                    *count += increment;
+                }
+            }
+        }
+
+        let remapping = self
+            .block_tables
+            .get_or_insert_with(Default::default)
+            .append(block_tables);
+        remapping.remap_query_response(&mut query_response);
+        let query_responses = self.query_responses.get_or_insert_with(Vec::new);
+        query_responses.push(query_response);
+        Ok(query_responses.len() - 1)
+    }
+}
+
+/// Number of ticks since the epoch
+pub(crate) fn ticks(time: Timestamp, ticks_per_second: i128) -> i128 {
+    i128::from(time.timestamp_secs) * ticks_per_second + i128::from(u32::from(time.timestamp_ticks))
+}
@@ -0,0 +1,258 @@
+//! Parse and build the options of the EDNS OPT RR
+//!
+//! The signature of a Q/R item references the RDATA of the OPT RR of the Query with `query_opt_rdata_index`.
+//! The RDATA is a sequence of options, which [`parse_options`] decodes into [`OptionData`].
+//! Well-known options like the EDNS Client Subnet (ECS) are decoded into their fields, all other options are kept as raw [`EdnsOption`]s.
+//! [`options_to_rdata`] serializes the options back into RDATA.
+//!
+//! ```
+//! # use c_dns::edns::{self, ClientSubnet, OptionData};
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let options = vec![OptionData::ClientSubnet(ClientSubnet::new("192.0.2.123".parse()?, 24))];
+//! let rdata = edns::options_to_rdata(&options)?;
+//! assert_eq!(b"\x00\x08\x00\x07\x00\x01\x18\x00\xc0\x00\x02", rdata.as_bytes());
+//!
+//! let parsed = edns::parse_options(&rdata)?;
+//! match &parsed[0] {
+//!     OptionData::ClientSubnet(ecs) => assert_eq!("192.0.2.0".parse::<std::net::IpAddr>()?, ecs.address),
+//!     _ => unreachable!(),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{bail, invalid};
+use crate::rdata::Rdata;
+use crate::resolved::ResolvedQueryResponse;
+use crate::serialization::{DnsType, IpAddr, NameOrRdata};
+use crate::wire::EdnsOption;
+use crate::Result;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Name server identifier, RFC 5001
+pub const OPTION_NSID: u16 = 3;
+/// Client subnet, RFC 7871
+pub const OPTION_CLIENT_SUBNET: u16 = 8;
+/// DNS cookie, RFC 7873
+pub const OPTION_COOKIE: u16 = 10;
+/// Extended DNS error, RFC 8914
+pub const OPTION_EXTENDED_ERROR: u16 = 15;
+
+/// Address family of IPv4 in the client subnet option
+const FAMILY_IPV4: u16 = 1;
+/// Address family of IPv6 in the client subnet option
+const FAMILY_IPV6: u16 = 2;
+
+/// An EDNS option with its data decoded
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OptionData {
+    /// Name server identifier, RFC 5001
+    ///
+    /// The identifier is empty in Queries.
+    Nsid(Vec<u8>),
+    /// Client subnet, RFC 7871
+    ClientSubnet(ClientSubnet),
+    /// DNS cookie, RFC 7873
+    Cookie {
+        /// Client cookie of 8 bytes.
+        client: [u8; 8],
+        /// Server cookie of 8 to 32 bytes, if present.
+        server: Option<Vec<u8>>,
+    },
+    /// Extended DNS error, RFC 8914
+    ExtendedError { info_code: u16, extra_text: String },
+    /// An option without a dedicated variant
+    Unknown(EdnsOption),
+}
+
+impl OptionData {
+    /// Decode the data of `option` according to its option code.
+    ///
+    /// Fails if the data is malformed for a known option code.
+    pub fn parse(option: &EdnsOption) -> Result<Self> {
+        let data = &option.data;
+        Ok(match option.code {
+            OPTION_NSID => OptionData::Nsid(data.clone()),
+            OPTION_CLIENT_SUBNET => OptionData::ClientSubnet(ClientSubnet::parse(data)?),
+            OPTION_COOKIE => {
+                if data.len() != 8 && !(16..=40).contains(&data.len()) {
+                    bail!("Invalid DNS cookie length {}", data.len());
+                }
+                let (client, server) = data.split_at(8);
+                OptionData::Cookie {
+                    // Checked to be 8 bytes long
+                    client: client.try_into().unwrap(),
+                    server: (!server.is_empty()).then(|| server.to_vec()),
+                }
+            }
+            OPTION_EXTENDED_ERROR => {
+                if data.len() < 2 {
+                    bail!("The extended DNS error is truncated");
+                }
+                OptionData::ExtendedError {
+                    info_code: u16::from_be_bytes([data[0], data[1]]),
+                    extra_text: String::from_utf8(data[2..].to_vec()).map_err(|_| {
+                        invalid!("The EXTRA-TEXT of the extended DNS error is not UTF-8")
+                    })?,
+                }
+            }
+            _ => OptionData::Unknown(option.clone()),
+        })
+    }
+
+    /// The option code.
+    pub fn code(&self) -> u16 {
+        match self {
+            OptionData::Nsid(_) => OPTION_NSID,
+            OptionData::ClientSubnet(_) => OPTION_CLIENT_SUBNET,
+            OptionData::Cookie { .. } => OPTION_COOKIE,
+            OptionData::ExtendedError { .. } => OPTION_EXTENDED_ERROR,
+            OptionData::Unknown(option) => option.code,
+        }
+    }
+
+    /// Encode the option data.
+    pub fn to_option(&self) -> EdnsOption {
+        let data = match self {
+            OptionData::Nsid(nsid) => nsid.clone(),
+            OptionData::ClientSubnet(ecs) => ecs.to_bytes(),
+            OptionData::Cookie { client, server } => {
+                let mut data = client.to_vec();
+                data.extend(server.iter().flatten());
+                data
+            }
+            OptionData::ExtendedError {
+                info_code,
+                extra_text,
+            } => {
+                let mut data = info_code.to_be_bytes().to_vec();
+                data.extend_from_slice(extra_text.as_bytes());
+                data
+            }
+            OptionData::Unknown(option) => return option.clone(),
+        };
+        EdnsOption {
+            code: self.code(),
+            data,
+        }
+    }
+}
+
+/// The EDNS Client Subnet option, RFC 7871
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientSubnet {
+    /// The address with all bits after the `source_prefix_len` set to zero.
+    pub address: std::net::IpAddr,
+    /// Number of significant bits of the address sent by the client.
+    pub source_prefix_len: u8,
+    /// Number of bits of the address the Response covers, zero in Queries.
+    pub scope_prefix_len: u8,
+}
+
+impl ClientSubnet {
+    /// Create the option of a Query for the first `source_prefix_len` bits of `address`.
+    pub fn new(address: std::net::IpAddr, source_prefix_len: u8) -> Self {
+        let max_len = match address {
+            std::net::IpAddr::V4(_) => 32,
+            std::net::IpAddr::V6(_) => 128,
+        };
+        let source_prefix_len = source_prefix_len.min(max_len);
+        let prefix = IpAddr::with_prefix(address, source_prefix_len);
+        Self {
+            address: expand(prefix.as_bytes(), address.is_ipv6()),
+            source_prefix_len,
+            scope_prefix_len: 0,
+        }
+    }
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 4 {
+            bail!("The client subnet option is truncated");
+        }
+        let family = u16::from_be_bytes([data[0], data[1]]);
+        let (source_prefix_len, scope_prefix_len) = (data[2], data[3]);
+        let address = &data[4..];
+        let max_len = match family {
+            FAMILY_IPV4 => 32,
+            FAMILY_IPV6 => 128,
+            _ => bail!("Unsupported client subnet address family {}", family),
+        };
+        if source_prefix_len > max_len || scope_prefix_len > max_len {
+            bail!(
+                "Invalid client subnet prefix lengths {}/{}",
+                source_prefix_len,
+                scope_prefix_len
+            );
+        }
+        if address.len() != usize::from(source_prefix_len).div_ceil(8) {
+            bail!(
+                "The client subnet address has {} bytes, but the source prefix length is {}",
+                address.len(),
+                source_prefix_len
+            );
+        }
+        Ok(Self {
+            scope_prefix_len,
+            ..Self::new(expand(address, family == FAMILY_IPV6), source_prefix_len)
+        })
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let family = match self.address {
+            std::net::IpAddr::V4(_) => FAMILY_IPV4,
+            std::net::IpAddr::V6(_) => FAMILY_IPV6,
+        };
+        let mut data = family.to_be_bytes().to_vec();
+        data.push(self.source_prefix_len);
+        data.push(self.scope_prefix_len);
+        data.extend_from_slice(
+            IpAddr::with_prefix(self.address, self.source_prefix_len).as_bytes(),
+        );
+        data
+    }
+}
+
+/// Zero-extend the address bytes, which are at most as long as the address.
+fn expand(bytes: &[u8], is_ipv6: bool) -> std::net::IpAddr {
+    let mut octets = [0; 16];
+    octets[..bytes.len()].copy_from_slice(bytes);
+    if is_ipv6 {
+        std::net::IpAddr::V6(Ipv6Addr::from(octets))
+    } else {
+        std::net::IpAddr::V4(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]))
+    }
+}
+
+/// Parse the RDATA of an OPT RR into its options.
+///
+/// Fails if the RDATA is malformed or an option of a known code is malformed.
+pub fn parse_options(rdata: &NameOrRdata) -> Result<Vec<OptionData>> {
+    match rdata.parse_rdata(DnsType::OPT)? {
+        Rdata::Opt(options) => options.iter().map(OptionData::parse).collect(),
+        _ => unreachable!("OPT RDATA is parsed as Rdata::Opt"),
+    }
+}
+
+/// Serialize options into the RDATA of an OPT RR.
+///
+/// Fails if the data of an option is longer than 65535 bytes.
+pub fn options_to_rdata(options: &[OptionData]) -> Result<NameOrRdata> {
+    let mut rdata = Vec::new();
+    for option in options {
+        let option = option.to_option();
+        let len = u16::try_from(option.data.len())
+            .map_err(|_| invalid!("The data of the EDNS option {} is too long", option.code))?;
+        rdata.extend_from_slice(&option.code.to_be_bytes());
+        rdata.extend_from_slice(&len.to_be_bytes());
+        rdata.extend_from_slice(&option.data);
+    }
+    Ok(NameOrRdata::from(bytes::Bytes::from(rdata)))
+}
+
+impl ResolvedQueryResponse<'_> {
+    /// The EDNS options of the Query, if the signature references the OPT RDATA.
+    pub fn query_edns_options(&self) -> Result<Option<Vec<OptionData>>> {
+        self.query_opt_rdata.map(parse_options).transpose()
+    }
+}
@@ -0,0 +1,433 @@
+//! Preserve the CBOR encoding of a file for byte-exact re-serialization
+//!
+//! CBOR allows multiple encodings of the same value.
+//! Integers and lengths can use wider encodings than necessary, and arrays, maps, and strings can use indefinite lengths.
+//! The serde based serialization always produces the shortest definite-length encoding, so re-serializing a parsed file usually changes its bytes, even if no value changes.
+//!
+//! [`EncodingMetadata`] records the encoding of every data item of the original bytes.
+//! [`EncodingMetadata::apply`] transfers the recorded encoding onto newly serialized bytes.
+//! Data items are matched by their position in arrays and by their key in maps, so the encoding of unmodified parts is preserved even if other parts of the file change.
+//! This also restores the original order of map keys and tags dropped during deserialization.
+//!
+//! The opposite is [`canonicalize`], which re-encodes a document in the deterministic encoding of [RFC 8949 Section 4.2](https://www.rfc-editor.org/rfc/rfc8949#section-4.2).
+//! Two documents with equal values are then equal byte-wise, regardless of the encoder which produced them.
+//!
+//! ```
+//! # use c_dns::encoding::EncodingMetadata;
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let original = std::fs::read("./tests/data/dns.cdns")?;
+//! let metadata = EncodingMetadata::from_slice(&original)?;
+//! let file: File = serde_cbor::from_slice(&original)?;
+//!
+//! let reserialized = metadata.apply(&serde_cbor::to_vec(&file)?)?;
+//! assert_eq!(original, reserialized);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::cbor::{
+    walk_item, Head, Visitor, Width, BREAK, MAJOR_ARRAY, MAJOR_BYTES, MAJOR_MAP, MAJOR_SIMPLE,
+    MAJOR_TAG, MAJOR_TEXT,
+};
+use crate::error::invalid;
+use crate::Result;
+
+/// A single parsed data item with its encoding.
+#[derive(Debug, Clone)]
+struct Item {
+    major: u8,
+    width: Width,
+    /// Argument of the head.
+    ///
+    /// This is the integer value, the length of definite strings, arrays, and maps, the tag number, or the bits of simple values and floats.
+    value: u64,
+    /// Content of byte and text strings, concatenated over all chunks.
+    payload: Vec<u8>,
+    /// Elements of arrays, alternating keys and values of maps, the tagged item, or the chunks of indefinite strings.
+    children: Vec<Item>,
+}
+
+impl Item {
+    fn parse(bytes: &[u8], pos: &mut usize) -> Result<Self> {
+        let mut builder = ItemBuilder::default();
+        walk_item(bytes, pos, &mut builder)?;
+        builder
+            .done
+            .ok_or_else(|| invalid!("No data item at offset {}", *pos))
+    }
+
+    /// Whether both items encode the same value, ignoring the encoding.
+    fn same_value(&self, other: &Item) -> bool {
+        if self.major != other.major {
+            return false;
+        }
+        match self.major {
+            MAJOR_BYTES | MAJOR_TEXT => self.payload == other.payload,
+            MAJOR_ARRAY | MAJOR_MAP => {
+                self.children.len() == other.children.len()
+                    && self
+                        .children
+                        .iter()
+                        .zip(&other.children)
+                        .all(|(a, b)| a.same_value(b))
+            }
+            MAJOR_TAG => {
+                self.value == other.value && self.children[0].same_value(&other.children[0])
+            }
+            MAJOR_SIMPLE => match (self.as_float(), other.as_float()) {
+                (Some(a), Some(b)) => a == b || (a.is_nan() && b.is_nan()),
+                _ => self.value == other.value && self.width == other.width,
+            },
+            _ => self.value == other.value,
+        }
+    }
+
+    /// Decode the item as a floating point number.
+    fn as_float(&self) -> Option<f64> {
+        if self.major != MAJOR_SIMPLE {
+            return None;
+        }
+        match self.width {
+            Width::U16 => Some(f16_to_f64(self.value as u16)),
+            Width::U32 => Some(f64::from(f32::from_bits(self.value as u32))),
+            Width::U64 => Some(f64::from_bits(self.value)),
+            _ => None,
+        }
+    }
+
+    /// Write the item, using the encoding of `original` wherever the values match.
+    fn write(&self, original: Option<&Item>, out: &mut Vec<u8>) {
+        let original = match original {
+            // Tags are dropped during deserialization, so restore them
+            Some(original) if original.major == MAJOR_TAG && self.major != MAJOR_TAG => {
+                write_head(out, MAJOR_TAG, original.width, original.value);
+                return self.write(original.children.first(), out);
+            }
+            Some(original) if original.major == self.major => Some(original),
+            _ => None,
+        };
+
+        match self.major {
+            MAJOR_BYTES | MAJOR_TEXT => match original {
+                Some(original) if original.payload == self.payload => original.write_verbatim(out),
+                Some(original) if original.width.fits(self.payload.len() as u64) => {
+                    write_head(out, self.major, original.width, self.payload.len() as u64);
+                    out.extend_from_slice(&self.payload);
+                }
+                _ => self.write_verbatim(out),
+            },
+            MAJOR_ARRAY => {
+                let width = self.container_width(original, self.children.len() as u64);
+                write_head(out, self.major, width, self.children.len() as u64);
+                for (idx, child) in self.children.iter().enumerate() {
+                    child.write(
+                        original.and_then(|original| original.children.get(idx)),
+                        out,
+                    );
+                }
+                if width == Width::Indefinite {
+                    out.push(BREAK);
+                }
+            }
+            MAJOR_MAP => {
+                let len = self.children.len() as u64 / 2;
+                let width = self.container_width(original, len);
+                write_head(out, self.major, width, len);
+
+                let mut written = vec![false; self.children.len() / 2];
+                // First all keys in their original order, then all new keys
+                if let Some(original) = original {
+                    for orig_entry in original.children.chunks_exact(2) {
+                        let entry =
+                            self.children
+                                .chunks_exact(2)
+                                .enumerate()
+                                .find(|(idx, entry)| {
+                                    !written[*idx] && entry[0].same_value(&orig_entry[0])
+                                });
+                        if let Some((idx, entry)) = entry {
+                            written[idx] = true;
+                            entry[0].write(Some(&orig_entry[0]), out);
+                            entry[1].write(Some(&orig_entry[1]), out);
+                        }
+                    }
+                }
+                for (idx, entry) in self.children.chunks_exact(2).enumerate() {
+                    if !written[idx] {
+                        entry[0].write(None, out);
+                        entry[1].write(None, out);
+                    }
+                }
+                if width == Width::Indefinite {
+                    out.push(BREAK);
+                }
+            }
+            MAJOR_TAG => {
+                let original = original.filter(|original| original.value == self.value);
+                let width = original.map_or(Width::minimal(self.value), |original| original.width);
+                write_head(out, self.major, width, self.value);
+                self.children[0]
+                    .write(original.and_then(|original| original.children.first()), out);
+            }
+            MAJOR_SIMPLE => match original {
+                Some(original) if original.same_value(self) => original.write_verbatim(out),
+                _ => self.write_verbatim(out),
+            },
+            _ => {
+                let width = original
+                    .filter(|original| original.value == self.value)
+                    .map_or(Width::minimal(self.value), |original| original.width);
+                write_head(out, self.major, width, self.value);
+            }
+        }
+    }
+
+    /// Write the item in the deterministic encoding.
+    ///
+    /// All lengths are definite, all arguments use the shortest width, floats use the shortest width representing the same value, and map keys are sorted by their encoded bytes.
+    fn write_canonical(&self, out: &mut Vec<u8>) {
+        match self.major {
+            MAJOR_BYTES | MAJOR_TEXT => {
+                write_head(
+                    out,
+                    self.major,
+                    Width::minimal(self.payload.len() as u64),
+                    self.payload.len() as u64,
+                );
+                out.extend_from_slice(&self.payload);
+            }
+            MAJOR_ARRAY => {
+                let len = self.children.len() as u64;
+                write_head(out, self.major, Width::minimal(len), len);
+                for child in &self.children {
+                    child.write_canonical(out);
+                }
+            }
+            MAJOR_MAP => {
+                let mut entries: Vec<(Vec<u8>, &Item)> = self
+                    .children
+                    .chunks_exact(2)
+                    .map(|entry| {
+                        let mut key = Vec::new();
+                        entry[0].write_canonical(&mut key);
+                        (key, &entry[1])
+                    })
+                    .collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                let len = entries.len() as u64;
+                write_head(out, self.major, Width::minimal(len), len);
+                for (key, value) in entries {
+                    out.extend_from_slice(&key);
+                    value.write_canonical(out);
+                }
+            }
+            MAJOR_TAG => {
+                write_head(out, self.major, Width::minimal(self.value), self.value);
+                self.children[0].write_canonical(out);
+            }
+            MAJOR_SIMPLE => match self.as_float() {
+                Some(value) => write_float(out, value),
+                None => write_head(out, self.major, Width::minimal(self.value), self.value),
+            },
+            _ => write_head(out, self.major, Width::minimal(self.value), self.value),
+        }
+    }
+
+    /// Width of an array or map with `len` entries, reusing the original width if possible.
+    fn container_width(&self, original: Option<&Item>, len: u64) -> Width {
+        match original {
+            Some(original) if original.width == Width::Indefinite || original.width.fits(len) => {
+                original.width
+            }
+            _ => Width::minimal(len),
+        }
+    }
+
+    /// Write the item exactly as it was parsed.
+    fn write_verbatim(&self, out: &mut Vec<u8>) {
+        write_head(out, self.major, self.width, self.value);
+        match (self.major, self.width) {
+            (MAJOR_BYTES | MAJOR_TEXT, Width::Indefinite) => {
+                for chunk in &self.children {
+                    chunk.write_verbatim(out);
+                }
+                out.push(BREAK);
+            }
+            (MAJOR_BYTES | MAJOR_TEXT, _) => out.extend_from_slice(&self.payload),
+            (MAJOR_ARRAY | MAJOR_MAP | MAJOR_TAG, _) => {
+                for child in &self.children {
+                    child.write_verbatim(out);
+                }
+                if self.width == Width::Indefinite {
+                    out.push(BREAK);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Encoding details of all data items in a CBOR document.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug, Clone)]
+pub struct EncodingMetadata {
+    root: Item,
+}
+
+impl EncodingMetadata {
+    /// Record the encoding of the first data item in `bytes`.
+    ///
+    /// Any bytes after the first data item are ignored.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        let mut pos = 0;
+        let root = Item::parse(bytes, &mut pos)?;
+        Ok(Self { root })
+    }
+
+    /// Re-encode the CBOR document in `bytes` using the recorded encoding.
+    ///
+    /// Data items matching a recorded data item keep the recorded encoding.
+    /// All other data items use the shortest definite-length encoding.
+    /// Re-encoding unmodified data reproduces the original bytes.
+    pub fn apply(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let mut pos = 0;
+        let item = Item::parse(bytes, &mut pos)?;
+        let mut out = Vec::with_capacity(bytes.len());
+        item.write(Some(&self.root), &mut out);
+        Ok(out)
+    }
+}
+
+/// Re-encode the CBOR document in `bytes` in the deterministic encoding.
+///
+/// See the [module documentation](self) for details.
+/// Any bytes after the first data item are ignored.
+pub fn canonicalize(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut pos = 0;
+    let item = Item::parse(bytes, &mut pos)?;
+    let mut out = Vec::with_capacity(bytes.len());
+    item.write_canonical(&mut out);
+    Ok(out)
+}
+
+/// Build the tree of [`Item`]s while walking through a data item.
+#[derive(Debug, Default)]
+struct ItemBuilder {
+    /// The items whose content is being parsed.
+    open: Vec<Item>,
+    /// The complete top-level item.
+    done: Option<Item>,
+}
+
+impl Visitor for ItemBuilder {
+    fn start(&mut self, head: &Head, _offset: usize) -> Result<()> {
+        self.open.push(Item {
+            major: head.major,
+            width: head.width,
+            value: head.value,
+            payload: Vec::new(),
+            children: Vec::new(),
+        });
+        Ok(())
+    }
+
+    fn content(&mut self, content: &[u8]) -> Result<()> {
+        if let Some(item) = self.open.last_mut() {
+            item.payload.extend_from_slice(content);
+        }
+        Ok(())
+    }
+
+    fn end(&mut self, _head: &Head) -> Result<()> {
+        let item = self.open.pop();
+        match (self.open.last_mut(), item) {
+            (Some(parent), Some(item)) => {
+                // Indefinite-length strings keep the content of all chunks
+                if matches!(parent.major, MAJOR_BYTES | MAJOR_TEXT) {
+                    parent.payload.extend_from_slice(&item.payload);
+                }
+                parent.children.push(item);
+            }
+            (None, item) => self.done = item,
+            (Some(_), None) => {}
+        }
+        Ok(())
+    }
+}
+
+fn write_head(out: &mut Vec<u8>, major: u8, width: Width, value: u64) {
+    let major = major << 5;
+    match width {
+        Width::Inline => out.push(major | value as u8),
+        Width::U8 => out.extend_from_slice(&[major | 24, value as u8]),
+        Width::U16 => {
+            out.push(major | 25);
+            out.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        Width::U32 => {
+            out.push(major | 26);
+            out.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        Width::U64 => {
+            out.push(major | 27);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+        Width::Indefinite => out.push(major | 31),
+    }
+}
+
+/// Write a float with the shortest width representing the same value.
+fn write_float(out: &mut Vec<u8>, value: f64) {
+    if let Some(bits) = f64_to_f16(value) {
+        write_head(out, MAJOR_SIMPLE, Width::U16, u64::from(bits));
+    } else if f64::from(value as f32) == value {
+        write_head(
+            out,
+            MAJOR_SIMPLE,
+            Width::U32,
+            u64::from((value as f32).to_bits()),
+        );
+    } else {
+        write_head(out, MAJOR_SIMPLE, Width::U64, value.to_bits());
+    }
+}
+
+/// Encode a float as IEEE 754 half-precision float, if this does not lose precision.
+///
+/// All NaNs are encoded as the same quiet NaN.
+fn f64_to_f16(value: f64) -> Option<u16> {
+    let sign = if value.is_sign_negative() { 0x8000 } else { 0 };
+    if value.is_nan() {
+        return Some(0x7e00);
+    }
+    if value.is_infinite() {
+        return Some(sign | 0x7c00);
+    }
+    let abs = value.abs();
+    // Subnormal half-precision floats are multiples of 2^-24
+    let scaled = abs * 2f64.powi(24);
+    if scaled < 1024. {
+        return (scaled.fract() == 0.).then_some(sign | scaled as u16);
+    }
+    let exponent = ((abs.to_bits() >> 52) & 0x7ff) as i32 - 1023;
+    let mantissa = abs.to_bits() & ((1 << 52) - 1);
+    if !(-14..=15).contains(&exponent) || mantissa & ((1 << 42) - 1) != 0 {
+        return None;
+    }
+    Some(sign | (((exponent + 15) as u16) << 10) | (mantissa >> 42) as u16)
+}
+
+/// Decode an IEEE 754 half-precision float.
+fn f16_to_f64(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1. } else { 1. };
+    let exponent = i32::from((bits >> 10) & 0x1f);
+    let mantissa = f64::from(bits & 0x3ff);
+    sign * match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        0x1f if mantissa == 0. => f64::INFINITY,
+        0x1f => f64::NAN,
+        _ => (1. + mantissa / 1024.) * 2f64.powi(exponent - 15),
+    }
+}
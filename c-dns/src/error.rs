@@ -0,0 +1,101 @@
+//! Error type of the library
+
+use crate::compression::Compression;
+use crate::wire::WireError;
+use crate::IpVersion;
+
+/// Errors returned by this library.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The file type id is not [`FILE_TYPE_ID`](crate::serialization::FILE_TYPE_ID).
+    #[error("Invalid file type id {0:?}, expected \"C-DNS\"")]
+    InvalidFileType(String),
+    /// The format version is not supported.
+    #[error("Unsupported format version {major}.{minor}")]
+    UnsupportedVersion { major: u32, minor: u32 },
+    /// A stored IP address has more bytes than an address of the IP version.
+    #[error("Too many bytes to convert into an {ip_version} address. Expected up to {} bytes but got {len}.", max_address_len(*.ip_version))]
+    AddressTooLong { ip_version: IpVersion, len: usize },
+    /// A stored IP address has no bytes.
+    #[error("No bytes to convert into an {0} address")]
+    EmptyAddress(IpVersion),
+    /// An `*_index` field does not reference an entry of its table.
+    #[error("Invalid index {index} into the {table} table")]
+    IndexOutOfBounds { table: &'static str, index: usize },
+    /// A value is out of range or inconsistent with other values.
+    #[error("{0}")]
+    InvalidValue(String),
+    /// The compression format is not enabled by its feature.
+    #[error("Handling {0} compressed data requires the `{}` feature", .0.feature())]
+    UnsupportedCompression(Compression),
+    /// The input is not valid CBOR or does not match the structure of the format.
+    #[error(transparent)]
+    Cbor(#[from] serde_cbor::Error),
+    /// Reading or writing failed.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// A DNS message cannot be parsed.
+    #[error(transparent)]
+    Wire(#[from] WireError),
+    /// A GeoIP database cannot be read.
+    #[cfg(feature = "maxminddb")]
+    #[error(transparent)]
+    MaxMindDb(#[from] maxminddb::MaxMindDbError),
+    /// An error together with a description of the failed operation.
+    #[error("{context}")]
+    Context {
+        context: String,
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+/// Result type of this library.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+fn max_address_len(ip_version: IpVersion) -> usize {
+    match ip_version {
+        IpVersion::Ipv4 => 4,
+        IpVersion::Ipv6 => 16,
+    }
+}
+
+/// Describe the operation which failed.
+pub(crate) trait Context<T> {
+    fn context(self, context: impl Into<String>) -> Result<T>;
+
+    fn with_context(self, context: impl FnOnce() -> String) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for Result<T, E> {
+    fn context(self, context: impl Into<String>) -> Result<T> {
+        self.map_err(|source| Error::Context {
+            context: context.into(),
+            source: Box::new(source.into()),
+        })
+    }
+
+    fn with_context(self, context: impl FnOnce() -> String) -> Result<T> {
+        self.map_err(|source| Error::Context {
+            context: context(),
+            source: Box::new(source.into()),
+        })
+    }
+}
+
+/// Create an [`Error::InvalidValue`] with a formatted message.
+macro_rules! invalid {
+    ($($arg:tt)*) => {
+        $crate::Error::InvalidValue(format!($($arg)*))
+    };
+}
+
+/// Return early with an [`Error::InvalidValue`] with a formatted message.
+macro_rules! bail {
+    ($($arg:tt)*) => {
+        return Err($crate::error::invalid!($($arg)*).into())
+    };
+}
+
+pub(crate) use {bail, invalid};
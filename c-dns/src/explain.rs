@@ -0,0 +1,253 @@
+//! Plain language description of the [`BlockParameters`]
+//!
+//! The [`StorageParameters`], [`StorageHints`], and [`CollectionParameters`] determine which data a file can contain.
+//! [`BlockParameters::explain`] describes them as text, for example which fields were collected, whether addresses were truncated, and what the hints imply is missing.
+//!
+//! ```
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let file = File::read_path("./tests/data/dns.cdns")?;
+//! let explanation = file.file_preamble.block_parameters[0].explain().to_string();
+//! assert!(explanation.contains("Generator: dns-stats-compactor 1.2.0"));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::flags::FlagName;
+use crate::serialization::*;
+use crate::utils::to_std_ip;
+use crate::IpVersion;
+use enumset::EnumSet;
+use std::fmt;
+
+impl BlockParameters {
+    /// Describe the parameters in plain language.
+    pub fn explain(&self) -> Explanation<'_> {
+        Explanation(self)
+    }
+}
+
+/// Plain language description of [`BlockParameters`], created by [`BlockParameters::explain`].
+///
+/// The description spans multiple lines and is produced by the [`Display`](fmt::Display) implementation.
+#[derive(Debug, Clone, Copy)]
+pub struct Explanation<'a>(&'a BlockParameters);
+
+impl fmt::Display for Explanation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        explain_storage_parameters(f, &self.0.storage_parameters)?;
+        explain_storage_hints(f, &self.0.storage_parameters.storage_hints)?;
+        match &self.0.collection_parameters {
+            Some(collection_parameters) => {
+                explain_collection_parameters(f, collection_parameters)
+            }
+            None => writeln!(
+                f,
+                "Collection parameters:\n  Not recorded, nothing is known about the collection setup."
+            ),
+        }
+    }
+}
+
+fn explain_storage_parameters(
+    f: &mut fmt::Formatter<'_>,
+    storage_parameters: &StorageParameters,
+) -> fmt::Result {
+    writeln!(f, "Storage parameters:")?;
+    let ticks_per_second = u32::from(storage_parameters.ticks_per_second);
+    writeln!(
+        f,
+        "  Timestamps have a resolution of {} ticks per second{}.",
+        ticks_per_second,
+        match ticks_per_second {
+            1_000 => " (milliseconds)",
+            1_000_000 => " (microseconds)",
+            1_000_000_000 => " (nanoseconds)",
+            _ => "",
+        }
+    )?;
+    writeln!(
+        f,
+        "  Blocks contain at most {} items of each kind.",
+        storage_parameters.max_block_items
+    )?;
+
+    let storage_flags = storage_parameters.storage_flags.unwrap_or_default();
+    writeln!(
+        f,
+        "  The data is {}anonymized, {}sampled, and names are {}.",
+        if storage_flags.contains(StorageFlags::AnonymizedData) {
+            ""
+        } else {
+            "not "
+        },
+        if storage_flags.contains(StorageFlags::SampledData) {
+            ""
+        } else {
+            "not "
+        },
+        if storage_flags.contains(StorageFlags::NormalizedNames) {
+            "normalized to a uniform case"
+        } else {
+            "stored in their original case"
+        },
+    )?;
+    if let Some(sampling_method) = &storage_parameters.sampling_method {
+        writeln!(f, "  Sampling method: {}", sampling_method)?;
+    }
+    if let Some(anonymization_method) = &storage_parameters.anonymization_method {
+        writeln!(f, "  Anonymization method: {}", anonymization_method)?;
+    }
+
+    for (kind, prefix) in [
+        (
+            "client",
+            StorageParameters::client_address_prefix as fn(_, _) -> _,
+        ),
+        ("server", StorageParameters::server_address_prefix),
+    ] {
+        for ip_version in [IpVersion::Ipv4, IpVersion::Ipv6] {
+            match prefix(storage_parameters, ip_version) {
+                Some(prefix) => writeln!(
+                    f,
+                    "  Only the first {} bits of {} {} addresses are stored.",
+                    prefix, ip_version, kind
+                )?,
+                None => writeln!(f, "  {} {} addresses are stored in full.", ip_version, kind)?,
+            }
+        }
+    }
+
+    if storage_parameters.opcodes.is_empty() {
+        writeln!(f, "  No OPCODEs are recorded.")?;
+    } else {
+        let opcodes: Vec<String> = storage_parameters
+            .opcodes
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        writeln!(
+            f,
+            "  Only messages with these OPCODEs are recorded: {}",
+            opcodes.join(", ")
+        )?;
+    }
+    if storage_parameters.rr_types.is_empty() {
+        writeln!(f, "  No RR types are recorded.")?;
+    } else {
+        let rr_types: Vec<String> = storage_parameters
+            .rr_types
+            .iter()
+            .map(|rr_type| u16::from(*rr_type).to_string())
+            .collect();
+        writeln!(
+            f,
+            "  Only RRs with these TYPEs are recorded: {}",
+            rr_types.join(", ")
+        )?;
+    }
+    Ok(())
+}
+
+fn explain_storage_hints(f: &mut fmt::Formatter<'_>, storage_hints: &StorageHints) -> fmt::Result {
+    writeln!(f, "Storage hints:")?;
+    explain_hint(f, "Q/R item fields", storage_hints.query_response_hints)?;
+    explain_hint(
+        f,
+        "Q/R signature fields",
+        storage_hints.query_response_signature_hints,
+    )?;
+    explain_hint(f, "RR fields", storage_hints.rr_hints)?;
+    explain_hint(f, "Other data", storage_hints.other_data_hints)
+}
+
+/// List the collected and the omitted values of one kind of hints.
+fn explain_hint<T: FlagName>(
+    f: &mut fmt::Formatter<'_>,
+    kind: &str,
+    hints: EnumSet<T>,
+) -> fmt::Result {
+    let names = |set: EnumSet<T>| {
+        if set.is_empty() {
+            "none".to_string()
+        } else {
+            set.iter()
+                .map(FlagName::flag_name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    };
+    writeln!(f, "  {} collected: {}", kind, names(hints))?;
+    let missing = EnumSet::<T>::all() - hints;
+    if !missing.is_empty() {
+        writeln!(f, "  {} never present: {}", kind, names(missing))?;
+    }
+    Ok(())
+}
+
+fn explain_collection_parameters(
+    f: &mut fmt::Formatter<'_>,
+    collection_parameters: &CollectionParameters,
+) -> fmt::Result {
+    writeln!(f, "Collection parameters:")?;
+    if let Some(generator_id) = &collection_parameters.generator_id {
+        writeln!(f, "  Generator: {}", generator_id)?;
+    }
+    if let Some(host_id) = &collection_parameters.host_id {
+        writeln!(f, "  Collected on host: {}", host_id)?;
+    }
+    if let Some(query_timeout) = collection_parameters.query_timeout {
+        writeln!(
+            f,
+            "  Responses arriving more than {} ms after the Query are not matched.",
+            query_timeout
+        )?;
+    }
+    if let Some(skew_timeout) = collection_parameters.skew_timeout {
+        writeln!(
+            f,
+            "  Responses are matched with Queries arriving up to {} µs later.",
+            skew_timeout
+        )?;
+    }
+    if let Some(snaplen) = collection_parameters.snaplen {
+        writeln!(f, "  At most {} bytes are captured per packet.", snaplen)?;
+    }
+    if let Some(promisc) = collection_parameters.promisc {
+        writeln!(
+            f,
+            "  The interfaces were {}in promiscuous mode.",
+            if promisc { "" } else { "not " }
+        )?;
+    }
+    if let Some(interfaces) = &collection_parameters.interfaces {
+        writeln!(f, "  Interfaces: {}", interfaces.join(", "))?;
+    }
+    if let Some(server_addresses) = &collection_parameters.server_addresses {
+        let server_addresses: Vec<String> = server_addresses
+            .iter()
+            .map(|ip| match to_std_ip(ip, None) {
+                Some(ip) => ip.to_string(),
+                None => format!("{:?}", ip),
+            })
+            .collect();
+        writeln!(f, "  Server addresses: {}", server_addresses.join(", "))?;
+    }
+    if let Some(vlan_ids) = &collection_parameters.vlan_ids {
+        let vlan_ids: Vec<String> = vlan_ids.iter().map(ToString::to_string).collect();
+        writeln!(
+            f,
+            "  Only these VLANs are collected: {}",
+            vlan_ids.join(", ")
+        )?;
+    }
+    match &collection_parameters.filter {
+        Some(filter) => writeln!(
+            f,
+            "  Only packets matching the filter {:?} are collected.",
+            filter
+        )?,
+        None => writeln!(f, "  No packet filter is recorded.")?,
+    }
+    Ok(())
+}
@@ -0,0 +1,207 @@
+//! Interpret implementation specific values
+//!
+//! Negative map keys are reserved for implementation specific extensions and are kept in the `extra_values` of each struct.
+//! Different implementations use the same keys for different purposes, so the meaning of a key depends on the producer of the file.
+//! Producers identify their extensions with the [`FilePreamble.private_version`].
+//!
+//! An [`ExtensionRegistry`] holds [`ExtensionDecoder`]s for multiple producers and selects the decoders applying to a file, based on its `private_version`.
+//!
+//! ```
+//! # use c_dns::extensions::{ExtensionDecoder, ExtensionRegistry, ExtensionScope};
+//! # use c_dns::serialization::File;
+//! struct Compactor;
+//!
+//! impl ExtensionDecoder for Compactor {
+//!     fn name(&self) -> &str {
+//!         "compactor"
+//!     }
+//!
+//!     fn field_name(&self, scope: ExtensionScope, key: isize) -> Option<&str> {
+//!         match (scope, key) {
+//!             (ExtensionScope::CollectionParameters, -1) => Some("dns-port"),
+//!             _ => None,
+//!         }
+//!     }
+//! }
+//!
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let mut registry = ExtensionRegistry::new();
+//! registry.register(Some(2), Compactor);
+//!
+//! let file = File::read_path("./tests/data/dns.cdns")?;
+//! let collection_parameters = file.file_preamble.block_parameters[0]
+//!     .collection_parameters
+//!     .as_ref()
+//!     .unwrap();
+//! let fields = registry.fields(
+//!     &file.file_preamble,
+//!     ExtensionScope::CollectionParameters,
+//!     &collection_parameters.extra_values,
+//! );
+//! assert_eq!(Some("dns-port"), fields[0].name);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::serialization::{ExtraValues, FilePreamble};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The struct containing an [`ExtraValues`] map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ExtensionScope {
+    FilePreamble,
+    BlockParameters,
+    StorageParameters,
+    StorageHints,
+    CollectionParameters,
+    Block,
+    BlockPreamble,
+    BlockStatistics,
+    BlockTables,
+    QueryResponseSignature,
+    Question,
+    RR,
+    MalformedMessageData,
+    QueryResponse,
+    ResponseProcessingData,
+    QueryResponseExtended,
+    AddressEventCount,
+    MalformedMessage,
+}
+
+/// Knowledge about the extensions of a single producer.
+pub trait ExtensionDecoder: Send + Sync {
+    /// Name of the producer or extension.
+    fn name(&self) -> &str;
+
+    /// Whether the decoder applies to a file with this preamble.
+    ///
+    /// The [`ExtensionRegistry`] already selects decoders by the `private_version`.
+    /// This allows further restrictions, for example on the `generator_id` of the [`CollectionParameters`](crate::serialization::CollectionParameters).
+    fn applies_to(&self, preamble: &FilePreamble) -> bool {
+        let _ = preamble;
+        true
+    }
+
+    /// Name of the extension field stored at `key` in a struct of type `scope`.
+    ///
+    /// Returns [`None`] if the key is not part of this extension.
+    fn field_name(&self, scope: ExtensionScope, key: isize) -> Option<&str>;
+}
+
+/// A value of the `extra_values`, with the decoder knowing its meaning.
+#[derive(Clone, Copy)]
+pub struct ExtensionField<'a> {
+    /// Key in the `extra_values`.
+    pub key: isize,
+    /// Name of the field, if any applicable decoder knows the key.
+    pub name: Option<&'a str>,
+    /// The decoder which knows the key.
+    pub decoder: Option<&'a dyn ExtensionDecoder>,
+    /// The stored value.
+    pub value: &'a serde_cbor::Value,
+}
+
+impl fmt::Debug for ExtensionField<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtensionField")
+            .field("key", &self.key)
+            .field("name", &self.name)
+            .field("decoder", &self.decoder.map(|decoder| decoder.name()))
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+/// [`ExtensionDecoder`]s registered for their `private_version`.
+#[derive(Default)]
+pub struct ExtensionRegistry {
+    decoders: BTreeMap<Option<u32>, Vec<Box<dyn ExtensionDecoder>>>,
+}
+
+impl fmt::Debug for ExtensionRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.decoders.iter().map(|(private_version, decoders)| {
+                (
+                    private_version,
+                    decoders
+                        .iter()
+                        .map(|decoder| decoder.name())
+                        .collect::<Vec<_>>(),
+                )
+            }))
+            .finish()
+    }
+}
+
+impl ExtensionRegistry {
+    /// Create a registry without any decoders.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `decoder` for files with the `private_version`.
+    ///
+    /// `None` registers the decoder for files without a `private_version`.
+    /// Decoders registered first take precedence if multiple decoders know the same key.
+    pub fn register(
+        &mut self,
+        private_version: Option<u32>,
+        decoder: impl ExtensionDecoder + 'static,
+    ) -> &mut Self {
+        self.decoders
+            .entry(private_version)
+            .or_default()
+            .push(Box::new(decoder));
+        self
+    }
+
+    /// All decoders applying to the file with this preamble.
+    pub fn decoders<'a>(
+        &'a self,
+        preamble: &'a FilePreamble,
+    ) -> impl Iterator<Item = &'a dyn ExtensionDecoder> + 'a {
+        self.decoders
+            .get(&preamble.private_version)
+            .into_iter()
+            .flatten()
+            .map(|decoder| &**decoder)
+            .filter(move |decoder| decoder.applies_to(preamble))
+    }
+
+    /// Find the decoder and name of the field at `key` in a struct of type `scope`.
+    pub fn field_name<'a>(
+        &'a self,
+        preamble: &'a FilePreamble,
+        scope: ExtensionScope,
+        key: isize,
+    ) -> Option<(&'a dyn ExtensionDecoder, &'a str)> {
+        self.decoders(preamble)
+            .find_map(|decoder| decoder.field_name(scope, key).map(|name| (decoder, name)))
+    }
+
+    /// Describe all `extra_values` of a struct of type `scope` in the file with this preamble.
+    ///
+    /// The fields are ordered by their key.
+    pub fn fields<'a>(
+        &'a self,
+        preamble: &'a FilePreamble,
+        scope: ExtensionScope,
+        extra_values: &'a ExtraValues,
+    ) -> Vec<ExtensionField<'a>> {
+        extra_values
+            .iter()
+            .map(|(key, value)| {
+                let known = self.field_name(preamble, scope, *key);
+                ExtensionField {
+                    key: *key,
+                    name: known.map(|(_, name)| name),
+                    decoder: known.map(|(decoder, _)| decoder),
+                    value,
+                }
+            })
+            .collect()
+    }
+}
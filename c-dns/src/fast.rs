@@ -0,0 +1,290 @@
+//! Fast path for decoding blocks
+//!
+//! Most of the decoding time of a [`Block`] is spent in the large `ip_address`, `name_rdata`, and `qr_sig` arrays of the [`BlockTables`].
+//! Going through serde costs a visitor and several calls per value for these, even though their structure is trivial.
+//! The decoder here reads these arrays directly from the CBOR bytes and uses serde for everything else.
+//!
+//! The fast path produces the same structs as serde.
+//! It only handles the common encodings and gives up on anything unusual, like tags, duplicate keys, or out of range values.
+//! In that case the whole block is decoded again with serde, which also produces the proper error message.
+
+use crate::cbor::{
+    head, Elements, MAJOR_ARRAY, MAJOR_BYTES, MAJOR_MAP, MAJOR_NEGATIVE, MAJOR_UNSIGNED,
+};
+use crate::read::deserialize_at;
+use crate::serialization::*;
+use crate::zero_copy::shared_bytes;
+use bytes::Bytes;
+use enumset::EnumSet;
+use serde::Deserialize;
+
+/// Encoding of the simple value `null`.
+const NULL: u8 = 0xf6;
+
+/// Decode a single [`Block`] from `raw`, using the fast path for the [`BlockTables`] arrays.
+///
+/// `raw` must contain exactly one block.
+pub(crate) fn decode_block(raw: &[u8]) -> serde_cbor::Result<Block> {
+    match try_decode_block(raw, None) {
+        Some(block) => Ok(block),
+        None => serde_cbor::from_slice(raw),
+    }
+}
+
+/// Decode a single [`Block`] from `raw` with the fast path only.
+///
+/// The addresses and names reference `input` instead of copying, if `raw` is part of it.
+/// Returns [`None`] if the block needs to be decoded with serde instead.
+pub(crate) fn try_decode_block(raw: &[u8], input: Option<&Bytes>) -> Option<Block> {
+    let mut pos = 0;
+    block(raw, &mut pos, input).filter(|_| pos == raw.len())
+}
+
+fn block(bytes: &[u8], pos: &mut usize, input: Option<&Bytes>) -> Option<Block> {
+    let mut block_preamble = None;
+    let mut block_statistics = None;
+    let mut block_tables = None;
+    let mut query_responses = None;
+    let mut address_event_counts = None;
+    let mut malformed_messages = None;
+    let mut extra_values = ExtraValues::new();
+
+    map(bytes, pos, |bytes, pos, key| {
+        match key {
+            0 => block_preamble = Some(value(bytes, pos)?),
+            1 => block_statistics = value(bytes, pos)?,
+            2 => {
+                block_tables = optional(bytes, pos, |bytes, pos| {
+                    self::block_tables(bytes, pos, input)
+                })?
+            }
+            3 => query_responses = value(bytes, pos)?,
+            4 => address_event_counts = value(bytes, pos)?,
+            5 => malformed_messages = value(bytes, pos)?,
+            key => {
+                extra_values.insert(key, value(bytes, pos)?);
+            }
+        }
+        Some(())
+    })?;
+
+    Some(Block {
+        block_preamble: block_preamble?,
+        block_statistics,
+        block_tables,
+        query_responses,
+        address_event_counts,
+        malformed_messages,
+        extra_values,
+    })
+}
+
+fn block_tables(bytes: &[u8], pos: &mut usize, input: Option<&Bytes>) -> Option<BlockTables> {
+    let mut block_tables = BlockTables {
+        ip_address: None,
+        classtype: None,
+        name_rdata: None,
+        qr_sig: None,
+        qlist: None,
+        qrr: None,
+        rrlist: None,
+        rr: None,
+        malformed_message_data: None,
+        extra_values: ExtraValues::new(),
+    };
+
+    map(bytes, pos, |bytes, pos, key| {
+        match key {
+            0 => {
+                block_tables.ip_address = optional(bytes, pos, |bytes, pos| {
+                    array(bytes, pos, |bytes, pos| {
+                        byte_string(bytes, pos, input).map(IpAddr::from)
+                    })
+                })?
+            }
+            1 => block_tables.classtype = value(bytes, pos)?,
+            2 => {
+                block_tables.name_rdata = optional(bytes, pos, |bytes, pos| {
+                    array(bytes, pos, |bytes, pos| {
+                        byte_string(bytes, pos, input).map(NameOrRdata::from)
+                    })
+                })?
+            }
+            3 => {
+                block_tables.qr_sig = optional(bytes, pos, |bytes, pos| array(bytes, pos, qr_sig))?
+            }
+            4 => block_tables.qlist = value(bytes, pos)?,
+            5 => block_tables.qrr = value(bytes, pos)?,
+            6 => block_tables.rrlist = value(bytes, pos)?,
+            7 => block_tables.rr = value(bytes, pos)?,
+            8 => block_tables.malformed_message_data = value(bytes, pos)?,
+            key => {
+                block_tables.extra_values.insert(key, value(bytes, pos)?);
+            }
+        }
+        Some(())
+    })?;
+
+    Some(block_tables)
+}
+
+fn qr_sig(bytes: &[u8], pos: &mut usize) -> Option<QueryResponseSignature> {
+    let mut sig = QueryResponseSignature {
+        server_address_index: None,
+        server_port: None,
+        qr_transport_flags: None,
+        qr_type: None,
+        qr_sig_flags: None,
+        query_opcode: None,
+        qr_dns_flags: None,
+        query_rcode: None,
+        query_classtype_index: None,
+        query_qdcount: None,
+        query_ancount: None,
+        query_nscount: None,
+        query_arcount: None,
+        query_edns_version: None,
+        query_udp_size: None,
+        query_opt_rdata_index: None,
+        response_rcode: None,
+        extra_values: ExtraValues::new(),
+    };
+
+    map(bytes, pos, |bytes, pos, key| {
+        match key {
+            0 => sig.server_address_index = optional(bytes, pos, uint)?,
+            1 => sig.server_port = optional(bytes, pos, uint)?,
+            2 => {
+                sig.qr_transport_flags = optional(bytes, pos, |bytes, pos| {
+                    uint::<u8>(bytes, pos).map(TransportFlags::from)
+                })?
+            }
+            3 => {
+                sig.qr_type = optional(bytes, pos, |bytes, pos| {
+                    uint::<u8>(bytes, pos).map(QueryResponseType::from)
+                })?
+            }
+            4 => {
+                sig.qr_sig_flags = optional(bytes, pos, |bytes, pos| {
+                    uint(bytes, pos).map(EnumSet::from_u8_truncated)
+                })?
+            }
+            5 => {
+                sig.query_opcode = optional(bytes, pos, |bytes, pos| {
+                    uint::<u8>(bytes, pos).and_then(|opcode| Opcode::try_from(opcode).ok())
+                })?
+            }
+            6 => {
+                sig.qr_dns_flags = optional(bytes, pos, |bytes, pos| {
+                    uint(bytes, pos).map(EnumSet::from_u16_truncated)
+                })?
+            }
+            7 => sig.query_rcode = optional(bytes, pos, uint)?,
+            8 => sig.query_classtype_index = optional(bytes, pos, uint)?,
+            9 => sig.query_qdcount = optional(bytes, pos, uint)?,
+            10 => sig.query_ancount = optional(bytes, pos, uint)?,
+            11 => sig.query_nscount = optional(bytes, pos, uint)?,
+            12 => sig.query_arcount = optional(bytes, pos, uint)?,
+            13 => sig.query_edns_version = optional(bytes, pos, uint)?,
+            14 => sig.query_udp_size = optional(bytes, pos, uint)?,
+            15 => sig.query_opt_rdata_index = optional(bytes, pos, uint)?,
+            16 => sig.response_rcode = optional(bytes, pos, uint)?,
+            key => {
+                sig.extra_values.insert(key, value(bytes, pos)?);
+            }
+        }
+        Some(())
+    })?;
+
+    Some(sig)
+}
+
+/// Decode a value of any type with serde.
+fn value<'de, T: Deserialize<'de>>(bytes: &'de [u8], pos: &mut usize) -> Option<T> {
+    deserialize_at(bytes, pos).ok()
+}
+
+/// Decode a value which might be `null`.
+fn optional<T>(
+    bytes: &[u8],
+    pos: &mut usize,
+    decode: impl FnOnce(&[u8], &mut usize) -> Option<T>,
+) -> Option<Option<T>> {
+    if bytes.get(*pos) == Some(&NULL) {
+        *pos += 1;
+        return Some(None);
+    }
+    decode(bytes, pos).map(Some)
+}
+
+/// Decode an unsigned integer, which must fit into `T`.
+fn uint<T: TryFrom<u64>>(bytes: &[u8], pos: &mut usize) -> Option<T> {
+    match head(bytes, pos).ok()? {
+        (MAJOR_UNSIGNED, Some(value)) => T::try_from(value).ok(),
+        _ => None,
+    }
+}
+
+/// Decode a definite-length byte string, referencing `input` if possible.
+fn byte_string(bytes: &[u8], pos: &mut usize, input: Option<&Bytes>) -> Option<Bytes> {
+    let len = match head(bytes, pos).ok()? {
+        (MAJOR_BYTES, Some(len)) => usize::try_from(len).ok()?,
+        _ => return None,
+    };
+    let value = bytes.get(*pos..pos.checked_add(len)?)?;
+    *pos += len;
+    Some(shared_bytes(input, value))
+}
+
+/// Decode an array, calling `decode` for each element.
+fn array<T>(
+    bytes: &[u8],
+    pos: &mut usize,
+    mut decode: impl FnMut(&[u8], &mut usize) -> Option<T>,
+) -> Option<Vec<T>> {
+    let len = match head(bytes, pos).ok()? {
+        (MAJOR_ARRAY, len) => len,
+        _ => return None,
+    };
+    // Every element takes at least one byte, which bounds the allocation for corrupted lengths
+    let capacity = len.map_or(0, |len| len.min((bytes.len() - *pos) as u64) as usize);
+    let mut items = Vec::with_capacity(capacity);
+    let mut elements = Elements::new(len);
+    while elements.next(bytes, pos) {
+        items.push(decode(bytes, pos)?);
+    }
+    Some(items)
+}
+
+/// Iterate over a map with integer keys, calling `entry` for each key.
+///
+/// `entry` must decode the value belonging to the key.
+/// Duplicate keys are rejected.
+fn map(
+    bytes: &[u8],
+    pos: &mut usize,
+    mut entry: impl FnMut(&[u8], &mut usize, isize) -> Option<()>,
+) -> Option<()> {
+    let len = match head(bytes, pos).ok()? {
+        (MAJOR_MAP, len) => len,
+        _ => return None,
+    };
+    // All positive keys of the C-DNS maps are small, so a bitmask suffices to find duplicates
+    let mut seen = 0u64;
+    let mut entries = Elements::new(len);
+    while entries.next(bytes, pos) {
+        let key = match head(bytes, pos).ok()? {
+            (MAJOR_UNSIGNED, Some(key)) => isize::try_from(key).ok()?,
+            (MAJOR_NEGATIVE, Some(key)) => -1 - isize::try_from(key).ok()?,
+            _ => return None,
+        };
+        if (0..64).contains(&key) {
+            if seen & (1 << key) != 0 {
+                return None;
+            }
+            seen |= 1 << key;
+        }
+        entry(bytes, pos, key)?;
+    }
+    Some(())
+}
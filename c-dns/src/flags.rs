@@ -0,0 +1,343 @@
+//! Compact human readable output for sets of bit flags
+//!
+//! The [`Debug`](fmt::Debug) output of an [`EnumSet`] lists the full variant names, like `EnumSet(QueryRd | ResponseRa)`.
+//! [`FlagSetExt::display`] instead renders the short names of the flags, like `Q:RD|R:RA`.
+//!
+//! ```
+//! # use c_dns::flags::FlagSetExt;
+//! # use c_dns::serialization::DNSFlags;
+//! let flags = DNSFlags::QueryRd | DNSFlags::QueryAd | DNSFlags::ResponseRa;
+//! assert_eq!("Q:AD|Q:RD|R:RA", flags.display().to_string());
+//! ```
+//!
+//! The [`DNSFlags`] combine the flags of the Query and the Response.
+//! [`DnsHeaderFlags`] is the structured view of a single message, as returned by [`QueryResponseSignature::query_flags`] and [`QueryResponseSignature::response_flags`].
+
+use crate::serialization::*;
+use crate::wire::Direction;
+use enumset::{EnumSet, EnumSetType};
+use std::fmt;
+
+/// Short name of a single flag.
+pub trait FlagName: EnumSetType {
+    /// The name used when displaying a set of flags.
+    fn flag_name(self) -> &'static str;
+}
+
+/// Display a set of flags as their names joined by `|`.
+///
+/// The empty set is displayed as `-`.
+/// The [`Debug`](fmt::Debug) output is the same as the [`Display`](fmt::Display) output.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct DisplayFlags<T: EnumSetType>(pub EnumSet<T>);
+
+impl<T: FlagName> fmt::Display for DisplayFlags<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("-");
+        }
+        for (idx, flag) in self.0.iter().enumerate() {
+            if idx > 0 {
+                f.write_str("|")?;
+            }
+            f.write_str(flag.flag_name())?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: FlagName> fmt::Debug for DisplayFlags<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Extension trait to display an [`EnumSet`] of flags.
+pub trait FlagSetExt<T: EnumSetType> {
+    /// Compact human readable representation of the flags.
+    fn display(&self) -> DisplayFlags<T>;
+}
+
+impl<T: FlagName> FlagSetExt<T> for EnumSet<T> {
+    fn display(&self) -> DisplayFlags<T> {
+        DisplayFlags(*self)
+    }
+}
+
+impl FlagName for StorageFlags {
+    fn flag_name(self) -> &'static str {
+        match self {
+            StorageFlags::AnonymizedData => "anonymized",
+            StorageFlags::SampledData => "sampled",
+            StorageFlags::NormalizedNames => "normalized",
+        }
+    }
+}
+
+impl FlagName for QueryResponseHints {
+    fn flag_name(self) -> &'static str {
+        match self {
+            QueryResponseHints::TimeOffset => "time-offset",
+            QueryResponseHints::ClientAddressIndex => "client-address-index",
+            QueryResponseHints::ClientPort => "client-port",
+            QueryResponseHints::TransactionId => "transaction-id",
+            QueryResponseHints::QrSignatureIndex => "qr-signature-index",
+            QueryResponseHints::ClientHoplimit => "client-hoplimit",
+            QueryResponseHints::ResponseDelay => "response-delay",
+            QueryResponseHints::QueryNameIndex => "query-name-index",
+            QueryResponseHints::QuerySize => "query-size",
+            QueryResponseHints::ResponseSize => "response-size",
+            QueryResponseHints::ResponseProcessingData => "response-processing-data",
+            QueryResponseHints::QueryQuestionSections => "query-question-sections",
+            QueryResponseHints::QueryAnswerSections => "query-answer-sections",
+            QueryResponseHints::QueryAuthoritySections => "query-authority-sections",
+            QueryResponseHints::QueryAdditionalSections => "query-additional-sections",
+            QueryResponseHints::ResponseAnswerSections => "response-answer-sections",
+            QueryResponseHints::ResponseAuthoritySections => "response-authority-sections",
+            QueryResponseHints::ResponseAdditionalSections => "response-additional-sections",
+        }
+    }
+}
+
+impl FlagName for QueryResponseSignatureHints {
+    fn flag_name(self) -> &'static str {
+        match self {
+            QueryResponseSignatureHints::ServerAddressIndex => "server-address-index",
+            QueryResponseSignatureHints::ServerPort => "server-port",
+            QueryResponseSignatureHints::QrTransportFlags => "qr-transport-flags",
+            QueryResponseSignatureHints::QrType => "qr-type",
+            QueryResponseSignatureHints::QrSigFlags => "qr-sig-flags",
+            QueryResponseSignatureHints::QueryOpcode => "query-opcode",
+            QueryResponseSignatureHints::QrDnsFlags => "qr-dns-flags",
+            QueryResponseSignatureHints::QueryRcode => "query-rcode",
+            QueryResponseSignatureHints::QueryClasstypeIndex => "query-classtype-index",
+            QueryResponseSignatureHints::QueryQdcount => "query-qdcount",
+            QueryResponseSignatureHints::QueryAncount => "query-ancount",
+            QueryResponseSignatureHints::QueryNscount => "query-nscount",
+            QueryResponseSignatureHints::QueryArcount => "query-arcount",
+            QueryResponseSignatureHints::QueryEdnsVersion => "query-edns-version",
+            QueryResponseSignatureHints::QueryUdpSize => "query-udp-size",
+            QueryResponseSignatureHints::QueryOptRdataIndex => "query-opt-rdata-index",
+            QueryResponseSignatureHints::ResponseRcode => "response-rcode",
+        }
+    }
+}
+
+impl FlagName for RRHint {
+    fn flag_name(self) -> &'static str {
+        match self {
+            RRHint::Ttl => "ttl",
+            RRHint::RdataIndex => "rdata-index",
+        }
+    }
+}
+
+impl FlagName for OtherDataHints {
+    fn flag_name(self) -> &'static str {
+        match self {
+            OtherDataHints::MalformedMessages => "malformed-messages",
+            OtherDataHints::AddressEventCounts => "address-event-counts",
+        }
+    }
+}
+
+impl FlagName for QueryResponseFlags {
+    fn flag_name(self) -> &'static str {
+        match self {
+            QueryResponseFlags::HasQuery => "QUERY",
+            QueryResponseFlags::HasResponse => "RESPONSE",
+            QueryResponseFlags::QueryHasOpt => "Q:OPT",
+            QueryResponseFlags::ResponseHasOpt => "R:OPT",
+            QueryResponseFlags::QueryHasNoQuestion => "Q:NO-QUESTION",
+            QueryResponseFlags::ResponseHasNoQuestion => "R:NO-QUESTION",
+        }
+    }
+}
+
+impl FlagName for DNSFlags {
+    fn flag_name(self) -> &'static str {
+        match self {
+            DNSFlags::QueryCd => "Q:CD",
+            DNSFlags::QueryAd => "Q:AD",
+            DNSFlags::QueryZ => "Q:Z",
+            DNSFlags::QueryRa => "Q:RA",
+            DNSFlags::QueryRd => "Q:RD",
+            DNSFlags::QueryTc => "Q:TC",
+            DNSFlags::QueryAa => "Q:AA",
+            DNSFlags::QueryDo => "Q:DO",
+            DNSFlags::ResponseCd => "R:CD",
+            DNSFlags::ResponseAd => "R:AD",
+            DNSFlags::ResponseZ => "R:Z",
+            DNSFlags::ResponseRa => "R:RA",
+            DNSFlags::ResponseRd => "R:RD",
+            DNSFlags::ResponseRc => "R:TC",
+            DNSFlags::ResponseAa => "R:AA",
+        }
+    }
+}
+
+impl FlagName for ResponseProcessingFlags {
+    fn flag_name(self) -> &'static str {
+        match self {
+            ResponseProcessingFlags::FromCache => "from-cache",
+            ResponseProcessingFlags::Unassigned1 => "bit-1",
+            ResponseProcessingFlags::Unassigned2 => "bit-2",
+            ResponseProcessingFlags::Unassigned3 => "bit-3",
+            ResponseProcessingFlags::Unassigned4 => "bit-4",
+            ResponseProcessingFlags::Unassigned5 => "bit-5",
+            ResponseProcessingFlags::Unassigned6 => "bit-6",
+            ResponseProcessingFlags::Unassigned7 => "bit-7",
+        }
+    }
+}
+
+/// The DNS flags of a single message.
+///
+/// The flags are stored in the header, except the DO bit, which is part of the OPT RR.
+/// The DO bit is only recorded for the Query.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct DnsHeaderFlags {
+    /// Authoritative Answer
+    pub aa: bool,
+    /// TrunCation
+    pub tc: bool,
+    /// Recursion Desired
+    pub rd: bool,
+    /// Recursion Available
+    pub ra: bool,
+    /// Reserved
+    pub z: bool,
+    /// Authenticated Data
+    pub ad: bool,
+    /// Checking Disabled
+    pub cd: bool,
+    /// DNSSEC answer OK (DO)
+    pub dnssec_ok: bool,
+}
+
+impl DnsHeaderFlags {
+    /// Extract the flags of one message from the combined [`DNSFlags`].
+    pub fn from_dns_flags(dns_flags: EnumSet<DNSFlags>, direction: Direction) -> Self {
+        let mut res = Self::default();
+        for (field, _, query, response) in res.fields_mut() {
+            *field = dns_flags.contains(match direction {
+                Direction::Query => query,
+                Direction::Response => response,
+            });
+        }
+        res.dnssec_ok = direction == Direction::Query && dns_flags.contains(DNSFlags::QueryDo);
+        res
+    }
+
+    /// The [`DNSFlags`] of the message, to be combined with the flags of the other message.
+    ///
+    /// The DO bit is dropped for the Response.
+    pub fn to_dns_flags(mut self, direction: Direction) -> EnumSet<DNSFlags> {
+        let mut res = EnumSet::new();
+        if direction == Direction::Query && self.dnssec_ok {
+            res |= DNSFlags::QueryDo;
+        }
+        for (field, _, query, response) in self.fields_mut() {
+            if *field {
+                res |= match direction {
+                    Direction::Query => query,
+                    Direction::Response => response,
+                };
+            }
+        }
+        res
+    }
+
+    /// Extract the flags from the second 16 bits of the DNS header.
+    ///
+    /// The QR bit, OPCODE, and RCODE are ignored, and the DO bit is not set.
+    pub fn from_header_bits(bits: u16) -> Self {
+        let mut res = Self::default();
+        for (field, mask, _, _) in res.fields_mut() {
+            *field = bits & mask != 0;
+        }
+        res
+    }
+
+    /// The flags in the position of the second 16 bits of the DNS header.
+    ///
+    /// The QR bit, OPCODE, and RCODE are 0.
+    pub fn header_bits(mut self) -> u16 {
+        self.fields_mut()
+            .into_iter()
+            .filter(|(field, _, _, _)| **field)
+            .fold(0, |bits, (_, mask, _, _)| bits | mask)
+    }
+
+    /// The header flags with their mask in the header and the [`DNSFlags`] of Query and Response.
+    fn fields_mut(&mut self) -> [(&mut bool, u16, DNSFlags, DNSFlags); 7] {
+        [
+            (
+                &mut self.aa,
+                0x0400,
+                DNSFlags::QueryAa,
+                DNSFlags::ResponseAa,
+            ),
+            (
+                &mut self.tc,
+                0x0200,
+                DNSFlags::QueryTc,
+                DNSFlags::ResponseRc,
+            ),
+            (
+                &mut self.rd,
+                0x0100,
+                DNSFlags::QueryRd,
+                DNSFlags::ResponseRd,
+            ),
+            (
+                &mut self.ra,
+                0x0080,
+                DNSFlags::QueryRa,
+                DNSFlags::ResponseRa,
+            ),
+            (&mut self.z, 0x0040, DNSFlags::QueryZ, DNSFlags::ResponseZ),
+            (
+                &mut self.ad,
+                0x0020,
+                DNSFlags::QueryAd,
+                DNSFlags::ResponseAd,
+            ),
+            (
+                &mut self.cd,
+                0x0010,
+                DNSFlags::QueryCd,
+                DNSFlags::ResponseCd,
+            ),
+        ]
+    }
+}
+
+impl QueryResponseSignature {
+    /// The DNS flags of the Query.
+    ///
+    /// Returns [`None`] if the `qr_dns_flags` are not stored or if the [`QueryResponseFlags`] show that there is no Query.
+    pub fn query_flags(&self) -> Option<DnsHeaderFlags> {
+        self.message_flags(Direction::Query, QueryResponseFlags::HasQuery)
+    }
+
+    /// The DNS flags of the Response, see [`QueryResponseSignature::query_flags`].
+    pub fn response_flags(&self) -> Option<DnsHeaderFlags> {
+        self.message_flags(Direction::Response, QueryResponseFlags::HasResponse)
+    }
+
+    fn message_flags(
+        &self,
+        direction: Direction,
+        has_message: QueryResponseFlags,
+    ) -> Option<DnsHeaderFlags> {
+        if self
+            .qr_sig_flags
+            .is_some_and(|flags| !flags.contains(has_message))
+        {
+            return None;
+        }
+        let dns_flags = self.qr_dns_flags?;
+        Some(DnsHeaderFlags::from_dns_flags(dns_flags, direction))
+    }
+}
@@ -0,0 +1,169 @@
+//! Estimate the memory used by decoded values
+
+use crate::serialization::*;
+use bytes::Bytes;
+use smallvec::{Array, SmallVec};
+use std::mem::size_of;
+
+/// Memory owned by a value outside of its own stack representation.
+pub(crate) trait HeapSize {
+    /// Estimated number of heap bytes owned by `self`.
+    fn heap_size(&self) -> usize;
+}
+
+impl Block {
+    /// Estimated number of bytes of memory used by the decoded block.
+    ///
+    /// This includes the block itself and all data it owns.
+    /// Shared byte buffers, for example from an [`Interner`](crate::intern::Interner), are counted fully for every block using them.
+    pub fn estimated_size(&self) -> usize {
+        size_of::<Self>() + self.heap_size()
+    }
+}
+
+impl HeapSize for Block {
+    fn heap_size(&self) -> usize {
+        self.block_preamble.extra_values.heap_size()
+            + self
+                .block_statistics
+                .as_ref()
+                .map_or(0, |statistics| statistics.extra_values.heap_size())
+            + self.block_tables.heap_size()
+            + self.query_responses.heap_size()
+            + self.address_event_counts.heap_size()
+            + self.malformed_messages.heap_size()
+            + self.extra_values.heap_size()
+    }
+}
+
+impl HeapSize for BlockTables {
+    fn heap_size(&self) -> usize {
+        self.ip_address.heap_size()
+            + self.classtype.heap_size()
+            + self.name_rdata.heap_size()
+            + self.qr_sig.heap_size()
+            + self.qlist.heap_size()
+            + self.qrr.heap_size()
+            + self.rrlist.heap_size()
+            + self.rr.heap_size()
+            + self.malformed_message_data.heap_size()
+            + self.extra_values.heap_size()
+    }
+}
+
+impl HeapSize for QueryResponse {
+    fn heap_size(&self) -> usize {
+        self.response_processing_data
+            .as_ref()
+            .map_or(0, |data| data.extra_values.heap_size())
+            + self
+                .query_extended
+                .as_ref()
+                .map_or(0, |extended| extended.extra_values.heap_size())
+            + self
+                .response_extended
+                .as_ref()
+                .map_or(0, |extended| extended.extra_values.heap_size())
+            + self.extra_values.heap_size()
+    }
+}
+
+impl HeapSize for MalformedMessageData {
+    fn heap_size(&self) -> usize {
+        self.mm_payload.heap_size() + self.extra_values.heap_size()
+    }
+}
+
+/// Implement [`HeapSize`] for types whose only heap data are the [`ExtraValues`].
+macro_rules! heap_size_extra_values {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl HeapSize for $ty {
+                fn heap_size(&self) -> usize {
+                    self.extra_values.heap_size()
+                }
+            }
+        )*
+    };
+}
+
+heap_size_extra_values!(
+    AddressEventCount,
+    MalformedMessage,
+    Question,
+    QueryResponseSignature,
+    RR,
+);
+
+impl HeapSize for IpAddr {
+    fn heap_size(&self) -> usize {
+        self.as_bytes().len()
+    }
+}
+
+impl HeapSize for NameOrRdata {
+    fn heap_size(&self) -> usize {
+        self.as_bytes().len()
+    }
+}
+
+impl HeapSize for Bytes {
+    fn heap_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl HeapSize for ClassType {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for usize {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for ExtraValues {
+    fn heap_size(&self) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        // The map nodes are not accessible, so count each entry with the CBOR size of its value
+        size_of::<std::collections::BTreeMap<isize, serde_cbor::Value>>()
+            + self
+                .iter()
+                .map(|(_, value)| {
+                    size_of::<(isize, serde_cbor::Value)>()
+                        + serde_cbor::to_vec(value).map_or(0, |bytes| bytes.len())
+                })
+                .sum::<usize>()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, HeapSize::heap_size)
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
+impl<A: Array> HeapSize for SmallVec<A>
+where
+    A::Item: HeapSize,
+{
+    fn heap_size(&self) -> usize {
+        let spilled = if self.spilled() {
+            self.capacity() * size_of::<A::Item>()
+        } else {
+            0
+        };
+        spilled + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
@@ -0,0 +1,474 @@
+//! Derive and enforce the [`StorageHints`]
+//!
+//! The [`StorageHints`] tell readers which optional fields the collector stores, so a missing field can be told apart from a field which was never collected.
+//! Nothing checks that the hints match the stored data, and they are easy to get wrong when writing files by hand.
+//! [`StorageHints::from_observed`] derives the hints from the fields a block actually contains.
+//! [`Block::strip_to_hints`] goes the other way and removes all fields the hints do not cover, which [`FileWriter::strip_to_hints`](crate::serialization::FileWriter::strip_to_hints) applies while writing.
+//! When reading, [`QueryResponse::field_presence`] and the similar methods tell whether a missing field was not collected or did not exist.
+//!
+//! ```
+//! # use c_dns::serialization::{File, QueryResponseHints, StorageHints};
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let mut file = File::read_path("./tests/data/dns.cdns")?;
+//! let mut hints = StorageHints::from_observed(&file.file_blocks[0]);
+//! assert!(hints.query_response_hints.contains(QueryResponseHints::QueryNameIndex));
+//!
+//! hints.query_response_hints.remove(QueryResponseHints::QueryNameIndex);
+//! file.file_blocks[0].strip_to_hints(&hints);
+//! assert!(!StorageHints::from_observed(&file.file_blocks[0])
+//!     .query_response_hints
+//!     .contains(QueryResponseHints::QueryNameIndex));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::serialization::*;
+use enumset::{EnumSet, EnumSetType};
+
+/// Whether a field contains data, according to the [`StorageHints`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FieldPresence {
+    /// The field contains a value.
+    ///
+    /// This is also returned if the hints claim the field is not collected.
+    Present,
+    /// The field is collected, but the message had no value for it.
+    Absent,
+    /// The field is not collected, so it is unknown whether the message had a value.
+    NotCollected,
+}
+
+/// Any field with a bit in the [`StorageHints`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HintedField {
+    QueryResponse(QueryResponseHints),
+    QueryResponseSignature(QueryResponseSignatureHints),
+    RR(RRHint),
+    OtherData(OtherDataHints),
+}
+
+impl From<QueryResponseHints> for HintedField {
+    fn from(hint: QueryResponseHints) -> Self {
+        Self::QueryResponse(hint)
+    }
+}
+
+impl From<QueryResponseSignatureHints> for HintedField {
+    fn from(hint: QueryResponseSignatureHints) -> Self {
+        Self::QueryResponseSignature(hint)
+    }
+}
+
+impl From<RRHint> for HintedField {
+    fn from(hint: RRHint) -> Self {
+        Self::RR(hint)
+    }
+}
+
+impl From<OtherDataHints> for HintedField {
+    fn from(hint: OtherDataHints) -> Self {
+        Self::OtherData(hint)
+    }
+}
+
+impl StorageHints {
+    /// Whether the collector stores `field`.
+    ///
+    /// ```
+    /// # use c_dns::serialization::{QueryResponseHints, RRHint, StorageHints};
+    /// let mut hints = StorageHints::none();
+    /// hints.query_response_hints.insert(QueryResponseHints::ClientPort);
+    /// assert!(hints.includes(QueryResponseHints::ClientPort));
+    /// assert!(!hints.includes(RRHint::Ttl));
+    /// ```
+    pub fn includes(&self, field: impl Into<HintedField>) -> bool {
+        match field.into() {
+            HintedField::QueryResponse(hint) => self.query_response_hints.contains(hint),
+            HintedField::QueryResponseSignature(hint) => {
+                self.query_response_signature_hints.contains(hint)
+            }
+            HintedField::RR(hint) => self.rr_hints.contains(hint),
+            HintedField::OtherData(hint) => self.other_data_hints.contains(hint),
+        }
+    }
+
+    /// The [`FieldPresence`] of `field`, given whether it contains a value.
+    pub fn presence(&self, field: impl Into<HintedField>, present: bool) -> FieldPresence {
+        if present {
+            FieldPresence::Present
+        } else if self.includes(field) {
+            FieldPresence::Absent
+        } else {
+            FieldPresence::NotCollected
+        }
+    }
+
+    /// Hints with no field stored.
+    pub fn none() -> Self {
+        Self {
+            query_response_hints: EnumSet::empty(),
+            query_response_signature_hints: EnumSet::empty(),
+            rr_hints: EnumSet::empty(),
+            other_data_hints: EnumSet::empty(),
+            extra_values: ExtraValues::new(),
+        }
+    }
+
+    /// Hints with exactly the fields set which `block` contains.
+    ///
+    /// Use [`StorageHints::add_observed`] to derive the hints of multiple blocks.
+    pub fn from_observed(block: &Block) -> Self {
+        let mut hints = Self::none();
+        hints.add_observed(block);
+        hints
+    }
+
+    /// Add the hints for all fields which `block` contains.
+    ///
+    /// The signature and RR hints are taken from all entries of the [`BlockTables`], even unreferenced ones.
+    pub fn add_observed(&mut self, block: &Block) {
+        for query_response in block.query_responses.iter().flatten() {
+            for (hint, present) in query_response_fields(query_response) {
+                set_if(&mut self.query_response_hints, hint, present);
+            }
+        }
+        if let Some(block_tables) = &block.block_tables {
+            for sig in block_tables.qr_sig.iter().flatten() {
+                for (hint, present) in signature_fields(sig) {
+                    set_if(&mut self.query_response_signature_hints, hint, present);
+                }
+            }
+            for rr in block_tables.rr.iter().flatten() {
+                set_if(&mut self.rr_hints, RRHint::Ttl, rr.ttl.is_some());
+                set_if(
+                    &mut self.rr_hints,
+                    RRHint::RdataIndex,
+                    rr.rdata_index.is_some(),
+                );
+            }
+        }
+        for hint in [
+            OtherDataHints::MalformedMessages,
+            OtherDataHints::AddressEventCounts,
+        ] {
+            set_if(
+                &mut self.other_data_hints,
+                hint,
+                other_data_present(block, hint),
+            );
+        }
+    }
+
+    /// Whether the hints include every field which `block` contains.
+    pub fn covers(&self, block: &Block) -> bool {
+        let observed = Self::from_observed(block);
+        self.query_response_hints
+            .is_superset(observed.query_response_hints)
+            && self
+                .query_response_signature_hints
+                .is_superset(observed.query_response_signature_hints)
+            && self.rr_hints.is_superset(observed.rr_hints)
+            && self.other_data_hints.is_superset(observed.other_data_hints)
+    }
+}
+
+impl File {
+    /// Remove all fields not covered by the [`StorageHints`] of the blocks, see [`Block::strip_to_hints`].
+    ///
+    /// Blocks referencing non-existing [`BlockParameters`] are not changed.
+    pub fn strip_to_hints(&mut self) {
+        for block in &mut self.file_blocks {
+            let index = block.block_preamble.block_parameters_index.unwrap_or(0);
+            if let Some(block_parameters) = self.file_preamble.block_parameters.get(index) {
+                block.strip_to_hints(&block_parameters.storage_parameters.storage_hints);
+            }
+        }
+    }
+}
+
+impl QueryResponse {
+    /// Whether the `field` contains data, or why it does not.
+    ///
+    /// The `hints` are the [`StorageHints`] of the block's [`BlockParameters`].
+    /// The sections fields are present if the extended information contains the list.
+    pub fn field_presence(&self, field: QueryResponseHints, hints: &StorageHints) -> FieldPresence {
+        let present = query_response_fields(self).any(|(hint, present)| hint == field && present);
+        hints.presence(field, present)
+    }
+}
+
+impl QueryResponseSignature {
+    /// Whether the `field` contains data, or why it does not, see [`QueryResponse::field_presence`].
+    pub fn field_presence(
+        &self,
+        field: QueryResponseSignatureHints,
+        hints: &StorageHints,
+    ) -> FieldPresence {
+        let present = signature_fields(self).any(|(hint, present)| hint == field && present);
+        hints.presence(field, present)
+    }
+}
+
+impl RR {
+    /// Whether the `field` contains data, or why it does not, see [`QueryResponse::field_presence`].
+    pub fn field_presence(&self, field: RRHint, hints: &StorageHints) -> FieldPresence {
+        let present = match field {
+            RRHint::Ttl => self.ttl.is_some(),
+            RRHint::RdataIndex => self.rdata_index.is_some(),
+        };
+        hints.presence(field, present)
+    }
+}
+
+impl Block {
+    /// Whether the `field` contains data, or why it does not, see [`QueryResponse::field_presence`].
+    ///
+    /// Empty lists count as absent.
+    pub fn field_presence(&self, field: OtherDataHints, hints: &StorageHints) -> FieldPresence {
+        hints.presence(field, other_data_present(self, field))
+    }
+
+    /// Remove all fields not covered by `hints`.
+    ///
+    /// Afterwards, [`StorageHints::covers`] holds for the block.
+    /// Extended sections without any remaining list are removed.
+    /// Table entries which are no longer referenced are removed with [`Block::compact_tables`], so no stripped data remains in the block.
+    ///
+    /// # Panics
+    ///
+    /// The indices in the block must be valid, as checked by [`File::dangling_indices`].
+    pub fn strip_to_hints(&mut self, hints: &StorageHints) {
+        let qr_hints = hints.query_response_hints;
+        for query_response in self.query_responses.iter_mut().flatten() {
+            use QueryResponseHints::*;
+
+            clear_unless(&mut query_response.time_offset, qr_hints, TimeOffset);
+            clear_unless(
+                &mut query_response.client_address_index,
+                qr_hints,
+                ClientAddressIndex,
+            );
+            clear_unless(&mut query_response.client_port, qr_hints, ClientPort);
+            clear_unless(&mut query_response.transaction_id, qr_hints, TransactionId);
+            clear_unless(
+                &mut query_response.qr_signature_index,
+                qr_hints,
+                QrSignatureIndex,
+            );
+            clear_unless(
+                &mut query_response.client_hoplimit,
+                qr_hints,
+                ClientHoplimit,
+            );
+            clear_unless(&mut query_response.response_delay, qr_hints, ResponseDelay);
+            clear_unless(
+                &mut query_response.query_name_index,
+                qr_hints,
+                QueryNameIndex,
+            );
+            clear_unless(&mut query_response.query_size, qr_hints, QuerySize);
+            clear_unless(&mut query_response.response_size, qr_hints, ResponseSize);
+            clear_unless(
+                &mut query_response.response_processing_data,
+                qr_hints,
+                ResponseProcessingData,
+            );
+            for (extended, question, answer, authority, additional) in [
+                (
+                    &mut query_response.query_extended,
+                    Some(QueryQuestionSections),
+                    QueryAnswerSections,
+                    QueryAuthoritySections,
+                    QueryAdditionalSections,
+                ),
+                (
+                    &mut query_response.response_extended,
+                    None,
+                    ResponseAnswerSections,
+                    ResponseAuthoritySections,
+                    ResponseAdditionalSections,
+                ),
+            ] {
+                if let Some(sections) = extended {
+                    if let Some(question) = question {
+                        clear_unless(&mut sections.question_index, qr_hints, question);
+                    }
+                    clear_unless(&mut sections.answer_index, qr_hints, answer);
+                    clear_unless(&mut sections.authority_index, qr_hints, authority);
+                    clear_unless(&mut sections.additional_index, qr_hints, additional);
+                    if sections.question_index.is_none()
+                        && sections.answer_index.is_none()
+                        && sections.authority_index.is_none()
+                        && sections.additional_index.is_none()
+                        && sections.extra_values.is_empty()
+                    {
+                        *extended = None;
+                    }
+                }
+            }
+        }
+
+        if let Some(block_tables) = &mut self.block_tables {
+            let sig_hints = hints.query_response_signature_hints;
+            for sig in block_tables.qr_sig.iter_mut().flatten() {
+                use QueryResponseSignatureHints::*;
+
+                clear_unless(&mut sig.server_address_index, sig_hints, ServerAddressIndex);
+                clear_unless(&mut sig.server_port, sig_hints, ServerPort);
+                clear_unless(&mut sig.qr_transport_flags, sig_hints, QrTransportFlags);
+                clear_unless(&mut sig.qr_type, sig_hints, QrType);
+                clear_unless(&mut sig.qr_sig_flags, sig_hints, QrSigFlags);
+                clear_unless(&mut sig.query_opcode, sig_hints, QueryOpcode);
+                clear_unless(&mut sig.qr_dns_flags, sig_hints, QrDnsFlags);
+                clear_unless(&mut sig.query_rcode, sig_hints, QueryRcode);
+                clear_unless(
+                    &mut sig.query_classtype_index,
+                    sig_hints,
+                    QueryClasstypeIndex,
+                );
+                clear_unless(&mut sig.query_qdcount, sig_hints, QueryQdcount);
+                clear_unless(&mut sig.query_ancount, sig_hints, QueryAncount);
+                clear_unless(&mut sig.query_nscount, sig_hints, QueryNscount);
+                clear_unless(&mut sig.query_arcount, sig_hints, QueryArcount);
+                clear_unless(&mut sig.query_edns_version, sig_hints, QueryEdnsVersion);
+                clear_unless(&mut sig.query_udp_size, sig_hints, QueryUdpSize);
+                clear_unless(
+                    &mut sig.query_opt_rdata_index,
+                    sig_hints,
+                    QueryOptRdataIndex,
+                );
+                clear_unless(&mut sig.response_rcode, sig_hints, ResponseRcode);
+            }
+            for rr in block_tables.rr.iter_mut().flatten() {
+                clear_unless(&mut rr.ttl, hints.rr_hints, RRHint::Ttl);
+                clear_unless(&mut rr.rdata_index, hints.rr_hints, RRHint::RdataIndex);
+            }
+        }
+        clear_unless(
+            &mut self.malformed_messages,
+            hints.other_data_hints,
+            OtherDataHints::MalformedMessages,
+        );
+        clear_unless(
+            &mut self.address_event_counts,
+            hints.other_data_hints,
+            OtherDataHints::AddressEventCounts,
+        );
+        self.compact_tables();
+    }
+}
+
+/// Whether each hinted field of `query_response` is present.
+fn query_response_fields(
+    query_response: &QueryResponse,
+) -> impl Iterator<Item = (QueryResponseHints, bool)> {
+    use QueryResponseHints::*;
+
+    let query_extended = query_response.query_extended.as_ref();
+    let response_extended = query_response.response_extended.as_ref();
+    [
+        (TimeOffset, query_response.time_offset.is_some()),
+        (
+            ClientAddressIndex,
+            query_response.client_address_index.is_some(),
+        ),
+        (ClientPort, query_response.client_port.is_some()),
+        (TransactionId, query_response.transaction_id.is_some()),
+        (
+            QrSignatureIndex,
+            query_response.qr_signature_index.is_some(),
+        ),
+        (ClientHoplimit, query_response.client_hoplimit.is_some()),
+        (ResponseDelay, query_response.response_delay.is_some()),
+        (QueryNameIndex, query_response.query_name_index.is_some()),
+        (QuerySize, query_response.query_size.is_some()),
+        (ResponseSize, query_response.response_size.is_some()),
+        (
+            ResponseProcessingData,
+            query_response.response_processing_data.is_some(),
+        ),
+        (
+            QueryQuestionSections,
+            query_extended.is_some_and(|ext| ext.question_index.is_some()),
+        ),
+        (
+            QueryAnswerSections,
+            query_extended.is_some_and(|ext| ext.answer_index.is_some()),
+        ),
+        (
+            QueryAuthoritySections,
+            query_extended.is_some_and(|ext| ext.authority_index.is_some()),
+        ),
+        (
+            QueryAdditionalSections,
+            query_extended.is_some_and(|ext| ext.additional_index.is_some()),
+        ),
+        (
+            ResponseAnswerSections,
+            response_extended.is_some_and(|ext| ext.answer_index.is_some()),
+        ),
+        (
+            ResponseAuthoritySections,
+            response_extended.is_some_and(|ext| ext.authority_index.is_some()),
+        ),
+        (
+            ResponseAdditionalSections,
+            response_extended.is_some_and(|ext| ext.additional_index.is_some()),
+        ),
+    ]
+    .into_iter()
+}
+
+/// Whether each hinted field of `sig` is present.
+fn signature_fields(
+    sig: &QueryResponseSignature,
+) -> impl Iterator<Item = (QueryResponseSignatureHints, bool)> {
+    use QueryResponseSignatureHints::*;
+
+    [
+        (ServerAddressIndex, sig.server_address_index.is_some()),
+        (ServerPort, sig.server_port.is_some()),
+        (QrTransportFlags, sig.qr_transport_flags.is_some()),
+        (QrType, sig.qr_type.is_some()),
+        (QrSigFlags, sig.qr_sig_flags.is_some()),
+        (QueryOpcode, sig.query_opcode.is_some()),
+        (QrDnsFlags, sig.qr_dns_flags.is_some()),
+        (QueryRcode, sig.query_rcode.is_some()),
+        (QueryClasstypeIndex, sig.query_classtype_index.is_some()),
+        (QueryQdcount, sig.query_qdcount.is_some()),
+        (QueryAncount, sig.query_ancount.is_some()),
+        (QueryNscount, sig.query_nscount.is_some()),
+        (QueryArcount, sig.query_arcount.is_some()),
+        (QueryEdnsVersion, sig.query_edns_version.is_some()),
+        (QueryUdpSize, sig.query_udp_size.is_some()),
+        (QueryOptRdataIndex, sig.query_opt_rdata_index.is_some()),
+        (ResponseRcode, sig.response_rcode.is_some()),
+    ]
+    .into_iter()
+}
+
+/// Whether `block` contains a non-empty list of the other data `hint`.
+fn other_data_present(block: &Block, hint: OtherDataHints) -> bool {
+    match hint {
+        OtherDataHints::MalformedMessages => block
+            .malformed_messages
+            .as_ref()
+            .is_some_and(|mm| !mm.is_empty()),
+        OtherDataHints::AddressEventCounts => block
+            .address_event_counts
+            .as_ref()
+            .is_some_and(|aec| !aec.is_empty()),
+    }
+}
+
+fn set_if<T: EnumSetType>(set: &mut EnumSet<T>, hint: T, present: bool) {
+    if present {
+        set.insert(hint);
+    }
+}
+
+fn clear_unless<T, H: EnumSetType>(field: &mut Option<T>, hints: EnumSet<H>, hint: H) {
+    if !hints.contains(hint) {
+        *field = None;
+    }
+}
@@ -0,0 +1,117 @@
+//! Deduplicate names and addresses across blocks
+//!
+//! Each [`Block`] has its own [`BlockTables`], so a name or address occurring in many blocks is stored once per block.
+//! For long captures of repetitive traffic most of these byte strings are identical.
+//! An [`Interner`] replaces identical byte strings by a single shared buffer, so only one copy stays in memory.
+//!
+//! Interning a decoded [`File`] only shrinks it after all blocks were decoded.
+//! A [`FileReader`] interns each block directly after decoding it, so the duplicates never accumulate while reading.
+//!
+//! ```
+//! # use c_dns::intern::Interner;
+//! # use c_dns::serialization::FileReader;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let input = std::fs::File::open("./tests/data/dns.cdns")?;
+//! let mut reader = FileReader::new(std::io::BufReader::new(input))?;
+//! reader.set_interner(Some(Interner::new()));
+//! let blocks = reader.collect::<c_dns::Result<Vec<_>>>()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::serialization::*;
+use bytes::Bytes;
+use std::collections::HashSet;
+
+/// Set of shared byte strings used for deduplication.
+///
+/// Interning a block right after it is decoded keeps at most one copy of each distinct name and address in memory.
+/// The same [`Interner`] should be used for all blocks of a file, and can be shared between multiple files.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    strings: HashSet<Bytes>,
+}
+
+impl Interner {
+    /// Create an empty interner.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of distinct byte strings.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Whether no byte string was interned yet.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Estimated number of bytes kept alive by the interner.
+    ///
+    /// This counts the content of all byte strings, even if blocks still share them.
+    pub fn retained_bytes(&self) -> usize {
+        self.strings
+            .iter()
+            .map(|bytes| std::mem::size_of::<Bytes>() + bytes.len())
+            .sum()
+    }
+
+    /// Forget all byte strings.
+    ///
+    /// Byte strings still used by blocks stay alive until these are dropped.
+    /// Later blocks no longer share them.
+    pub fn clear(&mut self) {
+        self.strings.clear();
+    }
+
+    /// Return the shared buffer with the same content as `bytes`.
+    ///
+    /// The first occurrence of a content becomes the shared buffer.
+    pub fn intern(&mut self, bytes: Bytes) -> Bytes {
+        if let Some(shared) = self.strings.get(&bytes) {
+            return shared.clone();
+        }
+        self.strings.insert(bytes.clone());
+        bytes
+    }
+
+    /// Replace the names and addresses in the [`BlockTables`] of `block` by shared buffers.
+    pub fn intern_block(&mut self, block: &mut Block) {
+        let block_tables = match &mut block.block_tables {
+            Some(block_tables) => block_tables,
+            None => return,
+        };
+        for ip in block_tables.ip_address.iter_mut().flatten() {
+            self.intern_ip(ip);
+        }
+        for name in block_tables.name_rdata.iter_mut().flatten() {
+            let bytes = Bytes::from(std::mem::replace(name, NameOrRdata::from(Bytes::new())));
+            *name = NameOrRdata::from(self.intern(bytes));
+        }
+    }
+
+    /// Replace the names and addresses in all blocks of `file` by shared buffers.
+    ///
+    /// This includes the server addresses of the [`CollectionParameters`].
+    pub fn intern_file(&mut self, file: &mut File) {
+        for block_parameters in &mut file.file_preamble.block_parameters {
+            let server_addresses = block_parameters
+                .collection_parameters
+                .as_mut()
+                .and_then(|params| params.server_addresses.as_mut());
+            for ip in server_addresses.into_iter().flatten() {
+                self.intern_ip(ip);
+            }
+        }
+        for block in &mut file.file_blocks {
+            self.intern_block(block);
+        }
+    }
+
+    fn intern_ip(&mut self, ip: &mut IpAddr) {
+        let bytes = Bytes::from(std::mem::replace(ip, IpAddr::from(Bytes::new())));
+        *ip = IpAddr::from(self.intern(bytes));
+    }
+}
@@ -1,16 +1,96 @@
+//! Iterators over the blocks of a file and the Q/R items of a block
+
 use crate::serialization::*;
-use std::slice;
+use crate::tables::signature;
+use crate::tables::EMPTY_TABLES;
+use crate::utils::{absolute_time, to_std_ip};
+use crate::Result;
+use std::time::SystemTime;
+use std::{slice, vec};
 
 impl File {
     /// Iterate over all Blocks with corresponding parameters in the file.
     pub fn iter_blocks(&self) -> impl Iterator<Item = (&Block, &BlockParameters)> {
         BlockIterator {
-            block_parameters: &*self.file_preamble.block_parameters,
+            block_parameters: &self.file_preamble.block_parameters,
             blocks: self.file_blocks.iter(),
         }
     }
+
+    /// Iterate over the Q/R items of all Blocks in the file, as one stream.
+    ///
+    /// Each item comes with its absolute time, computed from the `earliest_time` of the block and the `time_offset` of the item, see [`ResolvedQueryResponse::time`](crate::resolved::ResolvedQueryResponse::time).
+    pub fn iter_all_query_responses(&self) -> AllQueryResponseIterator<'_> {
+        AllQueryResponseIterator {
+            blocks: BlockIterator {
+                block_parameters: &self.file_preamble.block_parameters,
+                blocks: self.file_blocks.iter(),
+            },
+            query_responses: None,
+            filter: Filter::default(),
+        }
+    }
+
+    /// Iterate mutably over all Blocks with corresponding parameters in the file.
+    ///
+    /// This allows editing the blocks in place before serializing the file again.
+    pub fn iter_blocks_mut(&mut self) -> impl Iterator<Item = (&mut Block, &BlockParameters)> {
+        let block_parameters = &self.file_preamble.block_parameters;
+        self.file_blocks.iter_mut().map(move |block| {
+            let parameters =
+                &block_parameters[block.block_preamble.block_parameters_index.unwrap_or(0)];
+            (block, parameters)
+        })
+    }
+
+    /// Move all Blocks out of the file.
+    ///
+    /// The [`FilePreamble`] stays available through the iterator, see [`IntoBlocks::block_parameters`].
+    pub fn into_blocks(self) -> IntoBlocks {
+        IntoBlocks {
+            file_preamble: self.file_preamble,
+            blocks: self.file_blocks.into_iter(),
+        }
+    }
+}
+
+/// Move [`Block`]s out of a [`File`].
+///
+/// See [`File::into_blocks`]
+#[derive(Debug)]
+pub struct IntoBlocks {
+    file_preamble: FilePreamble,
+    blocks: vec::IntoIter<Block>,
 }
 
+impl IntoBlocks {
+    /// The preamble of the file.
+    pub fn file_preamble(&self) -> &FilePreamble {
+        &self.file_preamble
+    }
+
+    /// The [`BlockParameters`] applicable to `block`.
+    pub fn block_parameters(&self, block: &Block) -> Option<&BlockParameters> {
+        self.file_preamble
+            .block_parameters
+            .get(block.block_preamble.block_parameters_index.unwrap_or(0))
+    }
+}
+
+impl Iterator for IntoBlocks {
+    type Item = Block;
+
+    fn next(&mut self) -> Option<Block> {
+        self.blocks.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.blocks.size_hint()
+    }
+}
+
+impl ExactSizeIterator for IntoBlocks {}
+
 /// Iterate over [`Block`]s and their parameters.
 ///
 /// See [`File::iter_blocks`]
@@ -53,30 +133,137 @@ impl<'a> Iterator for BlockIterator<'a> {
 }
 
 impl Block {
-    /// Iterate over all Blocks with corresponding parameters in the file.
+    /// Iterate over all Q/R items of the block together with the block's parameters and tables.
+    ///
+    /// A block without [`BlockTables`] yields empty tables, such that looking up an index fails with [`Error::IndexOutOfBounds`](crate::Error::IndexOutOfBounds).
     pub fn iter_query_responses<'a>(
         &'a self,
         block_parameters: &'a BlockParameters,
-    ) -> impl Iterator<
-        Item = (
-            &'a QueryResponse,
-            Option<Timestamp>,
-            &'a BlockParameters,
-            &'a BlockTables,
-        ),
-    > {
+    ) -> QueryResponseIterator<'a> {
         QueryResponseIterator {
             earliest_time: self.block_preamble.earliest_time,
             block_parameters,
-            block_tables: self
-                .block_tables
-                .as_ref()
-                .expect("Missing BlockTables in Block"),
+            block_tables: self.block_tables.as_ref().unwrap_or(&EMPTY_TABLES),
             query_responses: self.query_responses.as_deref().unwrap_or(&[]).iter(),
         }
     }
 }
 
+impl Block {
+    /// Iterate mutably over all Q/R items of the block.
+    ///
+    /// The referenced table entries can be edited with the `*_mut` accessors of [`BlockTables`], like [`BlockTables::ip_mut`].
+    pub fn iter_query_responses_mut(&mut self) -> slice::IterMut<'_, QueryResponse> {
+        self.query_responses
+            .as_deref_mut()
+            .unwrap_or(&mut [])
+            .iter_mut()
+    }
+
+    /// Iterate over all address events of the block together with the referenced address.
+    ///
+    /// Fails for each item whose `ae_address_index` is not valid in the [`BlockTables`].
+    pub fn iter_address_events(
+        &self,
+    ) -> impl Iterator<Item = Result<(&AddressEventCount, &IpAddr)>> {
+        let block_tables = self.block_tables.as_ref().unwrap_or(&EMPTY_TABLES);
+        self.address_event_counts
+            .as_deref()
+            .unwrap_or(&[])
+            .iter()
+            .map(move |address_event| {
+                Ok((
+                    address_event,
+                    block_tables.ip(address_event.ae_address_index)?,
+                ))
+            })
+    }
+
+    /// Iterate over all malformed messages of the block together with the client address and the message data.
+    ///
+    /// The address and data are [`None`] if the malformed message does not reference them.
+    /// Fails for each item with an index which is not valid in the [`BlockTables`].
+    #[allow(clippy::type_complexity)]
+    pub fn iter_malformed_messages(
+        &self,
+    ) -> impl Iterator<
+        Item = Result<(
+            &MalformedMessage,
+            Option<&IpAddr>,
+            Option<&MalformedMessageData>,
+        )>,
+    > {
+        let block_tables = self.block_tables.as_ref().unwrap_or(&EMPTY_TABLES);
+        self.malformed_messages
+            .as_deref()
+            .unwrap_or(&[])
+            .iter()
+            .map(move |malformed_message| {
+                let client_address = match malformed_message.client_address_index {
+                    Some(idx) => Some(block_tables.ip(idx)?),
+                    None => None,
+                };
+                let data = match malformed_message.message_data_index {
+                    Some(idx) => Some(block_tables.malformed_data(idx)?),
+                    None => None,
+                };
+                Ok((malformed_message, client_address, data))
+            })
+    }
+
+    /// Move all Q/R items out of the block.
+    ///
+    /// The [`BlockPreamble`] and [`BlockTables`] stay available through the iterator, so the items can still be resolved.
+    pub fn into_query_responses(mut self) -> IntoQueryResponses {
+        IntoQueryResponses {
+            query_responses: self.query_responses.take().unwrap_or_default().into_iter(),
+            block_preamble: self.block_preamble,
+            block_tables: self.block_tables,
+        }
+    }
+}
+
+/// Move [`QueryResponse`]s out of a [`Block`].
+///
+/// See [`Block::into_query_responses`]
+#[derive(Debug)]
+pub struct IntoQueryResponses {
+    block_preamble: BlockPreamble,
+    block_tables: Option<BlockTables>,
+    query_responses: vec::IntoIter<QueryResponse>,
+}
+
+impl IntoQueryResponses {
+    /// The preamble of the block.
+    pub fn block_preamble(&self) -> &BlockPreamble {
+        &self.block_preamble
+    }
+
+    /// The tables of the block, which the indices of the Q/R items refer to.
+    pub fn block_tables(&self) -> Option<&BlockTables> {
+        self.block_tables.as_ref()
+    }
+
+    /// Take the tables of the block, for example to keep them together with the Q/R items.
+    pub fn take_block_tables(&mut self) -> Option<BlockTables> {
+        self.block_tables.take()
+    }
+}
+
+impl Iterator for IntoQueryResponses {
+    type Item = QueryResponse;
+
+    fn next(&mut self) -> Option<QueryResponse> {
+        self.query_responses.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.query_responses.size_hint()
+    }
+}
+
+impl ExactSizeIterator for IntoQueryResponses {}
+
 /// Iterate over [`QueryResponse`]s and their parameters.
 ///
 /// See [`Block::iter_query_responses`]
@@ -130,3 +317,152 @@ impl<'a> Iterator for QueryResponseIterator<'a> {
         })
     }
 }
+
+/// Iterate over the [`QueryResponse`]s of all blocks with their absolute time, parameters, and tables.
+///
+/// The adapters [`between`](Self::between), [`for_client`](Self::for_client), and [`for_qname_suffix`](Self::for_qname_suffix) only yield matching items.
+/// Blocks starting after the end of the time range are skipped without looking at their items.
+///
+/// See [`File::iter_all_query_responses`]
+pub struct AllQueryResponseIterator<'a> {
+    blocks: BlockIterator<'a>,
+    query_responses: Option<QueryResponseIterator<'a>>,
+    filter: Filter,
+}
+
+/// Conditions of the filter adapters of [`AllQueryResponseIterator`]
+#[derive(Debug, Default)]
+struct Filter {
+    time_range: Option<(SystemTime, SystemTime)>,
+    client: Option<(std::net::IpAddr, u8)>,
+    qname_suffix: Option<Vec<Vec<u8>>>,
+}
+
+impl<'a> AllQueryResponseIterator<'a> {
+    /// Only yield items with a time in the range from `start` (inclusive) to `end` (exclusive).
+    ///
+    /// Items without a time are skipped.
+    pub fn between(mut self, start: SystemTime, end: SystemTime) -> Self {
+        self.filter.time_range = Some((start, end));
+        self
+    }
+
+    /// Only yield items whose client address lies within the first `prefix_len` bits of `address`.
+    ///
+    /// A `prefix_len` of 32 for IPv4 or 128 for IPv6 matches a single address.
+    /// Items without a client address or with a client address of the other IP version are skipped.
+    pub fn for_client(mut self, address: std::net::IpAddr, prefix_len: u8) -> Self {
+        self.filter.client = Some((address, prefix_len));
+        self
+    }
+
+    /// Only yield items whose query name equals or is below `suffix`, ignoring ASCII case.
+    ///
+    /// `suffix` is a name in presentation format like `example.com.`, the trailing dot is optional.
+    /// Escape sequences are not supported.
+    /// Items without a query name or with an invalid query name are skipped.
+    pub fn for_qname_suffix(mut self, suffix: &str) -> Self {
+        self.filter.qname_suffix = Some(
+            suffix
+                .split('.')
+                .filter(|label| !label.is_empty())
+                .map(|label| label.as_bytes().to_ascii_lowercase())
+                .collect(),
+        );
+        self
+    }
+}
+
+impl Filter {
+    /// Whether the block starting at `earliest_time` may contain matching items.
+    fn matches_block(&self, earliest_time: Option<SystemTime>) -> bool {
+        match (self.time_range, earliest_time) {
+            (Some((_, end)), Some(earliest_time)) => earliest_time < end,
+            _ => true,
+        }
+    }
+
+    fn matches(
+        &self,
+        query_response: &QueryResponse,
+        time: Option<SystemTime>,
+        block_tables: &BlockTables,
+    ) -> bool {
+        if let Some((start, end)) = self.time_range {
+            if !time.is_some_and(|time| start <= time && time < end) {
+                return false;
+            }
+        }
+        if let Some((network, prefix_len)) = &self.client {
+            let ip_version = signature(block_tables, query_response)
+                .and_then(|sig| sig.qr_transport_flags.as_ref())
+                .map(|flags| flags.ip_version());
+            let client = query_response
+                .client_address_index
+                .and_then(|idx| block_tables.ip(idx).ok())
+                .and_then(|ip| to_std_ip(ip, ip_version));
+            let matches = client.is_some_and(|client| {
+                client.is_ipv4() == network.is_ipv4()
+                    && IpAddr::from(client).matches(network, *prefix_len)
+            });
+            if !matches {
+                return false;
+            }
+        }
+        if let Some(suffix) = &self.qname_suffix {
+            let name = query_response
+                .query_name_index
+                .and_then(|idx| block_tables.name(idx).ok())
+                .and_then(|name| name.to_domain_name().ok());
+            let matches = name.is_some_and(|name| {
+                let labels: Vec<_> = name.labels().collect();
+                labels.len() >= suffix.len()
+                    && labels[labels.len() - suffix.len()..]
+                        .iter()
+                        .zip(suffix)
+                        .all(|(label, suffix)| label.eq_ignore_ascii_case(suffix))
+            });
+            if !matches {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl<'a> Iterator for AllQueryResponseIterator<'a> {
+    type Item = (
+        &'a QueryResponse,
+        Option<SystemTime>,
+        &'a BlockParameters,
+        &'a BlockTables,
+    );
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(query_responses) = &mut self.query_responses {
+                for (query_response, earliest_time, block_parameters, block_tables) in
+                    query_responses.by_ref()
+                {
+                    let time =
+                        absolute_time(earliest_time, query_response.time_offset, block_parameters);
+                    if self.filter.matches(query_response, time, block_tables) {
+                        return Some((query_response, time, block_parameters, block_tables));
+                    }
+                }
+            }
+            let (block, block_parameters) = self.blocks.next()?;
+            let earliest_time =
+                absolute_time(block.block_preamble.earliest_time, None, block_parameters);
+            self.query_responses = if self.filter.matches_block(earliest_time) {
+                Some(block.iter_query_responses(block_parameters))
+            } else {
+                None
+            };
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, None)
+    }
+}
@@ -0,0 +1,237 @@
+//! Decode blocks only when they are accessed
+//!
+//! Decoding a large file takes a long time, even if only a few blocks are needed.
+//! A [`LazyFile`] only decodes the [`FilePreamble`] and the [`BlockPreamble`] of each block.
+//! The remaining block content is kept as raw CBOR and decoded on first access.
+//!
+//! ```
+//! # use c_dns::lazy::LazyFile;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let bytes = std::fs::read("./tests/data/dns.cdns")?;
+//! let file = LazyFile::from_slice(&bytes)?;
+//! for block in file.blocks() {
+//!     if block.preamble().earliest_time.is_some() {
+//!         let block = block.block()?;
+//!         println!("{:?}", block.query_responses);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Decoded blocks stay in memory until the [`LazyFile`] is dropped.
+//! Long running services can bound this with a memory budget, see [`LazyFile::set_budget`].
+//! Blocks decoded through [`LazyFile::decode`] then release the earlier decoded blocks whenever the estimated memory use exceeds the budget.
+
+use crate::cbor::{head, skip_item, Elements, MAJOR_MAP, MAJOR_UNSIGNED};
+use crate::error::bail;
+use crate::fast::decode_block;
+use crate::limits::Limits;
+use crate::read::{deserialize_at, split_blocks};
+use crate::serialization::*;
+use crate::Result;
+use std::sync::OnceLock;
+
+/// Map key of [`Block::block_preamble`].
+const BLOCK_PREAMBLE_KEY: u64 = 0;
+
+/// A C-DNS file with lazily decoded blocks.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct LazyFile<'a> {
+    /// String "C-DNS" identifying the file type.
+    pub file_type_id: String,
+    /// Version and parameter information for the whole file.
+    pub file_preamble: FilePreamble,
+    blocks: Vec<LazyBlock<'a>>,
+    budget: Option<usize>,
+}
+
+impl<'a> LazyFile<'a> {
+    /// Split `bytes` into blocks and decode the preambles.
+    ///
+    /// The structure of all blocks is validated, but their content is only decoded on access.
+    pub fn from_slice(bytes: &'a [u8]) -> Result<Self> {
+        let (file_type_id, file_preamble, raw_blocks, _) = split_blocks(bytes)?;
+        let blocks = raw_blocks
+            .into_iter()
+            .map(LazyBlock::new)
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            file_type_id,
+            file_preamble,
+            blocks,
+            budget: None,
+        })
+    }
+
+    /// Split `bytes` into blocks and decode the preambles, after checking it against the [`Limits`].
+    ///
+    /// Nothing is decoded if the input exceeds any of the limits.
+    pub fn from_slice_with_limits(bytes: &'a [u8], limits: &Limits) -> Result<Self> {
+        limits.check(bytes)?;
+        Self::from_slice(bytes)
+    }
+
+    /// All blocks of the file.
+    pub fn blocks(&self) -> &[LazyBlock<'a>] {
+        &self.blocks
+    }
+
+    /// The memory budget for decoded blocks in bytes, if any.
+    pub fn budget(&self) -> Option<usize> {
+        self.budget
+    }
+
+    /// Set the memory budget for decoded blocks in bytes.
+    ///
+    /// The budget is enforced by [`LazyFile::decode`].
+    /// Blocks decoded with [`LazyBlock::block`] are counted, but do not release other blocks.
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+    }
+
+    /// Estimated number of bytes used by all currently decoded blocks.
+    ///
+    /// See [`Block::estimated_size`] for how the size is estimated.
+    pub fn retained_bytes(&self) -> usize {
+        self.blocks.iter().map(LazyBlock::retained_bytes).sum()
+    }
+
+    /// Decode the block at `index`, staying within the memory budget.
+    ///
+    /// If the decoded blocks exceed the [budget](LazyFile::set_budget), the other decoded blocks are released in file order until the budget is met again.
+    /// The block at `index` is always kept, even if it exceeds the budget on its own.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn decode(&mut self, index: usize) -> serde_cbor::Result<&Block> {
+        self.blocks[index].block()?;
+        if let Some(budget) = self.budget {
+            let mut retained = self.retained_bytes();
+            for (other, block) in self.blocks.iter_mut().enumerate() {
+                if retained <= budget {
+                    break;
+                }
+                if other != index {
+                    retained -= block.release();
+                }
+            }
+        }
+        self.blocks[index].block()
+    }
+
+    /// Release the decoded content of all blocks.
+    ///
+    /// Returns the estimated number of released bytes.
+    pub fn release_all(&mut self) -> usize {
+        self.blocks.iter_mut().map(LazyBlock::release).sum()
+    }
+
+    /// The [`BlockParameters`] used by `block`.
+    ///
+    /// Returns [`None`] if the `block_parameters_index` is out of range.
+    pub fn block_parameters(&self, block: &LazyBlock<'_>) -> Option<&BlockParameters> {
+        self.file_preamble
+            .block_parameters
+            .get(block.preamble.block_parameters_index.unwrap_or(0))
+    }
+
+    /// Decode all remaining blocks and convert into a [`File`].
+    pub fn into_file(self) -> Result<File> {
+        let file_blocks = self
+            .blocks
+            .into_iter()
+            .map(LazyBlock::into_block)
+            .collect::<Result<_, _>>()?;
+        Ok(File {
+            file_type_id: self.file_type_id,
+            file_preamble: self.file_preamble,
+            file_blocks,
+        })
+    }
+}
+
+/// A [`Block`] which is decoded on first access.
+#[derive(Debug)]
+pub struct LazyBlock<'a> {
+    raw: &'a [u8],
+    preamble: BlockPreamble,
+    /// The decoded block and its estimated size.
+    block: OnceLock<(Block, usize)>,
+}
+
+impl<'a> LazyBlock<'a> {
+    /// Decode only the [`BlockPreamble`] of the raw block.
+    fn new(raw: &'a [u8]) -> Result<Self> {
+        let mut pos = 0;
+        let mut entries = match head(raw, &mut pos)? {
+            (MAJOR_MAP, entries) => Elements::new(entries),
+            _ => bail!("A Block must be a map"),
+        };
+        let preamble = loop {
+            if !entries.next(raw, &mut pos) {
+                bail!("Block without block_preamble");
+            }
+            let key = head(raw, &mut pos)?;
+            if key == (MAJOR_UNSIGNED, Some(BLOCK_PREAMBLE_KEY)) {
+                break deserialize_at::<BlockPreamble>(raw, &mut pos)?;
+            }
+            skip_item(raw, &mut pos)?;
+        };
+
+        Ok(Self {
+            raw,
+            preamble,
+            block: OnceLock::new(),
+        })
+    }
+
+    /// The CBOR encoding of the block.
+    pub fn raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    /// The preamble of the block, which is always decoded.
+    pub fn preamble(&self) -> &BlockPreamble {
+        &self.preamble
+    }
+
+    /// Whether the block content is already decoded.
+    pub fn is_decoded(&self) -> bool {
+        self.block.get().is_some()
+    }
+
+    /// Estimated number of bytes used by the decoded block, or 0 if it is not decoded.
+    pub fn retained_bytes(&self) -> usize {
+        self.block.get().map_or(0, |(_, size)| *size)
+    }
+
+    /// The decoded block, decoding it on the first call.
+    pub fn block(&self) -> serde_cbor::Result<&Block> {
+        if let Some((block, _)) = self.block.get() {
+            return Ok(block);
+        }
+        let block = decode_block(self.raw)?;
+        let size = block.estimated_size();
+        Ok(&self.block.get_or_init(|| (block, size)).0)
+    }
+
+    /// Drop the decoded block, such that it is decoded again on the next access.
+    ///
+    /// Returns the estimated number of released bytes.
+    pub fn release(&mut self) -> usize {
+        self.block.take().map_or(0, |(_, size)| size)
+    }
+
+    /// The decoded block, decoding it if it was not accessed before.
+    pub fn into_block(self) -> serde_cbor::Result<Block> {
+        match self.block.into_inner() {
+            Some((block, _)) => Ok(block),
+            None => decode_block(self.raw),
+        }
+    }
+}
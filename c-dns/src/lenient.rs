@@ -0,0 +1,223 @@
+//! Read files which violate details of the format
+//!
+//! Older collectors wrote files which do not follow the format in small details, like an opcode outside of the range 0 to 15, or a different file type id.
+//! [`File::from_reader_lenient`] reads such files anyway.
+//! Values which cannot be represented are dropped, all other off-spec values are kept.
+//! Every deviation is reported as a [`Violation`], together with the structural violations found by [`File::validate`].
+//!
+//! ```
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let reader = std::fs::File::open("./tests/data/dns.cdns")?;
+//! let (file, violations) = File::from_reader_lenient(reader)?;
+//! assert!(violations.is_empty());
+//! assert_eq!(1, file.block_count());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::Context;
+use crate::lint::Violation;
+use crate::serialization::*;
+use crate::{IpVersion, Result};
+use serde_cbor::Value;
+use std::io::Read;
+
+/// Key of the `block_parameters` in the [`FilePreamble`]
+const KEY_BLOCK_PARAMETERS: i128 = 3;
+/// Key of the `storage_parameters` in the [`BlockParameters`]
+const KEY_STORAGE_PARAMETERS: i128 = 0;
+/// Key of the `opcodes` in the [`StorageParameters`]
+const KEY_OPCODES: i128 = 3;
+/// Keys of the address prefix lengths in the [`StorageParameters`]
+const KEYS_ADDRESS_PREFIX: [(i128, &str); 4] = [
+    (6, "client_address_prefix_ipv4"),
+    (7, "client_address_prefix_ipv6"),
+    (8, "server_address_prefix_ipv4"),
+    (9, "server_address_prefix_ipv6"),
+];
+
+impl File {
+    /// Read a [`File`] from `reader`, tolerating values which are out of range.
+    ///
+    /// * Any file type id and format version is accepted.
+    /// * Opcodes outside of the range 0 to 15 and address prefix lengths which do not fit into a byte are dropped.
+    /// * Address prefix lengths outside of the valid range are kept.
+    /// * VLAN IDs outside of the range 1 to 4094 are kept.
+    /// * Unknown [`QueryResponseType`]s and [`AddressEventType`]s are kept.
+    ///
+    /// All of these are reported as [`Violation::InvalidValue`], followed by the violations of [`File::validate`].
+    /// Fails if the input is not CBOR or the file cannot be deserialized even after dropping the values.
+    pub fn from_reader_lenient(reader: impl Read) -> Result<(File, Vec<Violation>)> {
+        let mut value: Value = serde_cbor::from_reader(reader)?;
+        let mut violations = Vec::new();
+        repair_storage_parameters(&mut value, &mut violations);
+        let file: UncheckedFile = serde_cbor::value::from_value(value)
+            .context("Failed to deserialize the file after dropping invalid values")?;
+        let file = file.into_file();
+
+        if file.file_type_id != FILE_TYPE_ID {
+            violations.push(invalid_value(
+                "file_type_id".to_string(),
+                format!("invalid file type id {:?}", file.file_type_id),
+            ));
+        }
+        if file.check_header().is_err() && file.file_type_id == FILE_TYPE_ID {
+            let (major, minor) = file.file_preamble.format_version();
+            violations.push(invalid_value(
+                "file_preamble.major_format_version".to_string(),
+                format!("unsupported format version {}.{}", major, minor),
+            ));
+        }
+        check_address_prefixes(&file, &mut violations);
+        check_vlan_ids(&file, &mut violations);
+        check_unknown_types(&file, &mut violations);
+        violations.extend(file.validate());
+        Ok((file, violations))
+    }
+}
+
+fn invalid_value(path: String, message: String) -> Violation {
+    Violation::InvalidValue { path, message }
+}
+
+/// Drop the values of the [`StorageParameters`] which cannot be deserialized.
+fn repair_storage_parameters(value: &mut Value, violations: &mut Vec<Violation>) {
+    let block_parameters = match value {
+        Value::Array(items) => match items.get_mut(1) {
+            Some(Value::Map(preamble)) => preamble.get_mut(&Value::Integer(KEY_BLOCK_PARAMETERS)),
+            _ => None,
+        },
+        _ => None,
+    };
+    let block_parameters = match block_parameters {
+        Some(Value::Array(block_parameters)) => block_parameters,
+        _ => return,
+    };
+    for (idx, block_parameters) in block_parameters.iter_mut().enumerate() {
+        let storage_parameters = match block_parameters {
+            Value::Map(map) => map.get_mut(&Value::Integer(KEY_STORAGE_PARAMETERS)),
+            _ => None,
+        };
+        let storage_parameters = match storage_parameters {
+            Some(Value::Map(map)) => map,
+            _ => continue,
+        };
+        let path = format!("file_preamble.block_parameters[{}].storage_parameters", idx);
+
+        if let Some(Value::Array(opcodes)) =
+            storage_parameters.get_mut(&Value::Integer(KEY_OPCODES))
+        {
+            let mut pos = 0;
+            opcodes.retain(|opcode| {
+                let keep = matches!(opcode, Value::Integer(0..=15));
+                if !keep {
+                    violations.push(invalid_value(
+                        format!("{}.opcodes[{}]", path, pos),
+                        format!("dropped invalid OPCODE {:?}", opcode),
+                    ));
+                }
+                pos += 1;
+                keep
+            });
+        }
+        for (key, field) in KEYS_ADDRESS_PREFIX {
+            let key = Value::Integer(key);
+            match storage_parameters.get(&key) {
+                Some(Value::Integer(0..=255)) | None => {}
+                Some(prefix_len) => {
+                    violations.push(invalid_value(
+                        format!("{}.{}", path, field),
+                        format!("dropped invalid address prefix length {:?}", prefix_len),
+                    ));
+                    storage_parameters.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+/// Report address prefix lengths outside of their valid range.
+fn check_address_prefixes(file: &File, violations: &mut Vec<Violation>) {
+    for (idx, block_parameters) in file.file_preamble.block_parameters.iter().enumerate() {
+        let storage_parameters = &block_parameters.storage_parameters;
+        for (ip_version, max) in [(IpVersion::Ipv4, 32), (IpVersion::Ipv6, 128)] {
+            for (role, prefix_len) in [
+                (
+                    "client",
+                    storage_parameters.client_address_prefix(ip_version),
+                ),
+                (
+                    "server",
+                    storage_parameters.server_address_prefix(ip_version),
+                ),
+            ] {
+                match prefix_len {
+                    Some(prefix_len) if prefix_len == 0 || prefix_len > max => {
+                        violations.push(invalid_value(
+                            format!(
+                                "file_preamble.block_parameters[{}].storage_parameters.{}_address_prefix_{}",
+                                idx,
+                                role,
+                                ip_version.to_string().to_lowercase()
+                            ),
+                            format!(
+                                "address prefix length {} is outside of the range 1 to {}",
+                                prefix_len, max
+                            ),
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Report VLAN IDs outside of their valid range.
+fn check_vlan_ids(file: &File, violations: &mut Vec<Violation>) {
+    for (idx, block_parameters) in file.file_preamble.block_parameters.iter().enumerate() {
+        let vlan_ids = block_parameters
+            .collection_parameters
+            .as_ref()
+            .and_then(|params| params.vlan_ids.as_ref());
+        for (pos, vlan_id) in vlan_ids.into_iter().flatten().enumerate() {
+            if !(1..=4094).contains(vlan_id) {
+                violations.push(invalid_value(
+                    format!(
+                        "file_preamble.block_parameters[{}].collection_parameters.vlan_ids[{}]",
+                        idx, pos
+                    ),
+                    format!("VLAN ID {} is outside of the range 1 to 4094", vlan_id),
+                ));
+            }
+        }
+    }
+}
+
+/// Report values of enums which are not defined.
+fn check_unknown_types(file: &File, violations: &mut Vec<Violation>) {
+    for (block_idx, block) in file.file_blocks.iter().enumerate() {
+        let path = format!("file_blocks[{}]", block_idx);
+        let signatures = block
+            .block_tables
+            .as_ref()
+            .and_then(|tables| tables.qr_sig.as_ref());
+        for (idx, signature) in signatures.into_iter().flatten().enumerate() {
+            if let Some(QueryResponseType::Reserved(qr_type)) = signature.qr_type {
+                violations.push(invalid_value(
+                    format!("{}.block_tables.qr_sig[{}].qr_type", path, idx),
+                    format!("unknown Q/R type {}", qr_type),
+                ));
+            }
+        }
+        for (idx, address_event) in block.address_event_counts.iter().flatten().enumerate() {
+            if let AddressEventType::Reserved(ae_type) = address_event.ae_type {
+                violations.push(invalid_value(
+                    format!("{}.address_event_counts[{}].ae_type", path, idx),
+                    format!("unknown address event type {}", ae_type),
+                ));
+            }
+        }
+    }
+}
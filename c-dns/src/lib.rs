@@ -1,22 +1,196 @@
-mod iterators;
+pub mod analysis;
+pub mod anonymize;
+pub mod builder;
+mod cbor;
+pub mod compression;
+pub mod draft;
+pub mod edit;
+pub mod edns;
+pub mod encoding;
+mod error;
+pub mod explain;
+pub mod extensions;
+mod fast;
+pub mod flags;
+mod heap_size;
+pub mod hints;
+pub mod intern;
+pub mod iterators;
+pub mod lazy;
+pub mod lenient;
+pub mod limits;
+pub mod lint;
+pub mod matcher;
+pub mod merge;
+pub mod name;
+pub mod normalize;
+pub mod rdata;
+pub mod read;
+pub mod resolved;
+pub mod roundtrip;
+pub mod sampling;
 pub mod serialization;
+pub mod split;
+pub mod statistics;
+pub mod summary;
+pub mod tables;
+pub mod ticks;
 mod utils;
+pub mod wire;
+pub mod write;
+mod zero_copy;
+
+pub use error::{Error, Result};
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// IP version of the transport
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IpVersion {
+    Ipv4,
+    Ipv6,
+}
+
+impl fmt::Display for IpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            IpVersion::Ipv4 => "IPv4",
+            IpVersion::Ipv6 => "IPv6",
+        })
+    }
+}
 
 /// DNS transport protocol
+///
+/// This is the 4-bit transport code of the [`TransportFlags`](serialization::TransportFlags).
+/// Codes reserved by RFC 8618 are kept as [`Transport::Reserved`], such that they survive a round-trip.
+///
+/// RFC 8618 predates DNS over QUIC and DNS over HTTP/3 and has no codes for them.
+/// The `non-rfc-transports` feature adds [`Transport::Quic`] and [`Transport::Http3`] on the reserved codes 5 and 6.
+/// This assignment is not part of any standard, so only enable it to exchange files with tools using the same codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
 pub enum Transport {
     /// UDP specified in RFC 1035
-    Udp = 0,
+    Udp,
     /// TCP specified in RFC 1035
-    Tcp = 1,
+    Tcp,
     /// TLS specified in RFC 7858
-    Tls = 2,
+    Tls,
     /// DTLS specified in RFC 8094
-    Dtls = 3,
+    Dtls,
     /// HTTPS specified in RFC 8484
-    Https = 4,
-    /// Reserved Value
-    Reserved = 5,
-    NonStandard = 15,
+    Https,
+    /// QUIC specified in RFC 9250, on the reserved code 5
+    ///
+    /// Not assigned by RFC 8618, see the `non-rfc-transports` feature.
+    #[cfg(feature = "non-rfc-transports")]
+    Quic,
+    /// HTTP/3 specified in RFC 9114 for DNS over HTTPS, on the reserved code 6
+    ///
+    /// Not assigned by RFC 8618, see the `non-rfc-transports` feature.
+    #[cfg(feature = "non-rfc-transports")]
+    Http3,
+    /// Reserved Value, with the raw transport code
+    Reserved(ReservedTransport),
+    NonStandard,
+}
+
+/// The first transport code, which is reserved
+#[cfg(not(feature = "non-rfc-transports"))]
+const FIRST_RESERVED_TRANSPORT: u8 = 5;
+#[cfg(feature = "non-rfc-transports")]
+const FIRST_RESERVED_TRANSPORT: u8 = 7;
+
+/// A transport code reserved by RFC 8618, from 5 to 14
+///
+/// With the `non-rfc-transports` feature the codes 5 and 6 are assigned to [`Transport::Quic`] and [`Transport::Http3`], which leaves the codes 7 to 14.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ReservedTransport(u8);
+
+impl ReservedTransport {
+    /// The reserved transport `code`.
+    ///
+    /// Fails if the code is assigned to a transport or does not fit into 4 bits.
+    pub fn new(code: u8) -> Result<Self> {
+        match code {
+            FIRST_RESERVED_TRANSPORT..=14 => Ok(Self(code)),
+            _ => error::bail!(
+                "Transport code {} is not reserved. Expected a value from {} to 14.",
+                code,
+                FIRST_RESERVED_TRANSPORT
+            ),
+        }
+    }
+
+    /// The raw transport code.
+    pub fn code(self) -> u8 {
+        self.0
+    }
+}
+
+impl From<Transport> for u8 {
+    fn from(transport: Transport) -> Self {
+        match transport {
+            Transport::Udp => 0,
+            Transport::Tcp => 1,
+            Transport::Tls => 2,
+            Transport::Dtls => 3,
+            Transport::Https => 4,
+            #[cfg(feature = "non-rfc-transports")]
+            Transport::Quic => 5,
+            #[cfg(feature = "non-rfc-transports")]
+            Transport::Http3 => 6,
+            Transport::Reserved(reserved) => reserved.code(),
+            Transport::NonStandard => 15,
+        }
+    }
+}
+
+impl TryFrom<u8> for Transport {
+    type Error = Error;
+
+    /// Fails if the transport code does not fit into 4 bits.
+    fn try_from(code: u8) -> Result<Self> {
+        Ok(match code {
+            0 => Transport::Udp,
+            1 => Transport::Tcp,
+            2 => Transport::Tls,
+            3 => Transport::Dtls,
+            4 => Transport::Https,
+            #[cfg(feature = "non-rfc-transports")]
+            5 => Transport::Quic,
+            #[cfg(feature = "non-rfc-transports")]
+            6 => Transport::Http3,
+            FIRST_RESERVED_TRANSPORT..=14 => Transport::Reserved(ReservedTransport(code)),
+            15 => Transport::NonStandard,
+            _ => error::bail!(
+                "Invalid transport code {}. Expected a value from 0 to 15.",
+                code
+            ),
+        })
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Transport::Udp => "UDP",
+            Transport::Tcp => "TCP",
+            Transport::Tls => "TLS",
+            Transport::Dtls => "DTLS",
+            Transport::Https => "HTTPS",
+            #[cfg(feature = "non-rfc-transports")]
+            Transport::Quic => "QUIC",
+            #[cfg(feature = "non-rfc-transports")]
+            Transport::Http3 => "HTTP/3",
+            Transport::Reserved(reserved) => {
+                return f.pad(&format!("Reserved ({})", reserved.code()))
+            }
+            Transport::NonStandard => "Non-Standard",
+        })
+    }
 }
 
 /// Serialization helpers
@@ -24,10 +198,28 @@ pub enum Transport {
 /// These functions are necessary for the derive to produce the correct code.
 #[doc(hidden)]
 mod derive_helpers {
+    use serde::de::{DeserializeOwned, Error, Visitor};
     use serde::{Deserialize, Deserializer};
-    use serde::de::{Error, Visitor};
     use std::marker::PhantomData;
 
+    /// Any value, buffered while an enum selects its variant.
+    pub type Buffered = serde_cbor::Value;
+
+    /// A map value with the integer keys of `entries`.
+    pub fn buffered_map(entries: Vec<(isize, Buffered)>) -> Buffered {
+        Buffered::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (Buffered::Integer(key as i128), value))
+                .collect(),
+        )
+    }
+
+    /// Decode a buffered value.
+    pub fn from_buffered<T: DeserializeOwned, E: Error>(value: Buffered) -> Result<T, E> {
+        serde_cbor::value::from_value(value).map_err(E::custom)
+    }
+
     /// If the missing field is of type `Option<T>` then treat is as `None`,
     /// otherwise it is an error.
     ///
@@ -0,0 +1,238 @@
+//! Bound the resources used while reading untrusted files
+//!
+//! The lengths in a CBOR file are chosen by whoever wrote it.
+//! A corrupted or hostile file can declare huge arrays and strings, or simply contain millions of blocks, and thereby exhaust the memory of a service decoding it.
+//! [`Limits`] restricts these sizes, and [`File::from_slice_with_limits`] checks the whole input against them before anything is decoded.
+//! [`LazyFile::from_slice_with_limits`] does the same for lazily decoded files, and [`FileReader::with_limits`] checks every block before decoding it.
+//!
+//! The other readers, like [`File::from_slice_fast`], [`File::from_slice_parallel`], or [`File::from_bytes`], do not check any limits.
+//! Untrusted input needs to pass [`Limits::check`] before it is given to them.
+//!
+//! ```
+//! # use c_dns::limits::Limits;
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let bytes = std::fs::read("./tests/data/dns.cdns")?;
+//! let limits = Limits {
+//!     max_blocks: 100,
+//!     max_allocation: 64 * 1024 * 1024,
+//!     ..Limits::default()
+//! };
+//! let file = File::from_slice_with_limits(&bytes, &limits)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`LazyFile::from_slice_with_limits`]: crate::lazy::LazyFile::from_slice_with_limits
+//! [`FileReader::with_limits`]: crate::serialization::FileReader::with_limits
+//! [`File::from_slice_parallel`]: crate::serialization::File::from_slice_parallel
+
+use crate::cbor::{
+    array_head, walk_item, Elements, Head, Visitor, Width, MAJOR_ARRAY, MAJOR_BYTES, MAJOR_MAP,
+    MAJOR_TEXT,
+};
+use crate::error::bail;
+use crate::read::check_file_len;
+use crate::serialization::File;
+use crate::Result;
+
+/// Estimated memory used by a decoded data item, excluding the content of strings.
+///
+/// This is roughly the size of the structs and enums the values are decoded into.
+const ITEM_SIZE: usize = 32;
+
+/// Maximal nesting of arrays, maps, and tags.
+///
+/// The C-DNS format nests much less deeply, so this only guards against stack overflows.
+pub(crate) const MAX_DEPTH: usize = 128;
+
+/// Upper bounds for the sizes of data in a C-DNS file.
+///
+/// The [`Default`] does not restrict anything, so the limits of interest need to be set explicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximal number of elements in an array, or entries in a map.
+    pub max_array_len: usize,
+    /// Maximal length of a byte or text string in bytes.
+    pub max_string_len: usize,
+    /// Maximal number of [`Block`](crate::serialization::Block)s in the file.
+    pub max_blocks: usize,
+    /// Maximal number of bytes allocated for the decoded file.
+    ///
+    /// This is an estimate, counting the content of all strings and a fixed size for every other data item.
+    pub max_allocation: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_array_len: usize::MAX,
+            max_string_len: usize::MAX,
+            max_blocks: usize::MAX,
+            max_allocation: usize::MAX,
+        }
+    }
+}
+
+impl Limits {
+    /// Check that the [`File`] at the start of `bytes` stays within the limits.
+    ///
+    /// Only the CBOR structure is checked, so a file passing the check can still fail to decode.
+    /// Any bytes following the [`File`] are ignored.
+    pub fn check(&self, bytes: &[u8]) -> Result<()> {
+        let mut checker = Checker {
+            limits: self,
+            allocation: 0,
+            open: Vec::new(),
+        };
+        let mut pos = 0;
+        let file_len = array_head(bytes, &mut pos)?;
+        check_file_len(file_len)?;
+        walk_item(bytes, &mut pos, &mut checker)?;
+        walk_item(bytes, &mut pos, &mut checker)?;
+
+        let offset = pos;
+        let mut blocks = Elements::new(array_head(bytes, &mut pos)?);
+        checker.allocate(ITEM_SIZE, offset)?;
+        while blocks.next(bytes, &mut pos) {
+            if blocks.count() > self.max_blocks as u64 {
+                bail!(
+                    "More than {} blocks starting at offset {}",
+                    self.max_blocks,
+                    offset
+                );
+            }
+            walk_item(bytes, &mut pos, &mut checker)?;
+        }
+        Ok(())
+    }
+}
+
+impl Limits {
+    /// Check a single data item, like a [`Block`](crate::serialization::Block), which fills all of `bytes`.
+    ///
+    /// The allocation is estimated for this data item alone.
+    pub(crate) fn check_item(&self, bytes: &[u8]) -> Result<()> {
+        let mut checker = Checker {
+            limits: self,
+            allocation: 0,
+            open: Vec::new(),
+        };
+        walk_item(bytes, &mut 0, &mut checker)
+    }
+}
+
+impl File {
+    /// Deserialize a [`File`] from `bytes`, after checking it against the [`Limits`].
+    ///
+    /// Nothing is decoded if the input exceeds any of the limits.
+    /// Otherwise this is the same as [`File::from_slice`].
+    pub fn from_slice_with_limits(bytes: &[u8], limits: &Limits) -> Result<File> {
+        limits.check(bytes)?;
+        File::from_slice(bytes)
+    }
+}
+
+/// State of checking an input against [`Limits`].
+struct Checker<'a> {
+    limits: &'a Limits,
+    /// Estimated allocation so far.
+    allocation: usize,
+    /// The data items containing the current one.
+    open: Vec<OpenItem>,
+}
+
+/// A data item whose content is being checked.
+struct OpenItem {
+    head: Head,
+    offset: usize,
+    /// Number of contained data items, or the total length of the chunks of a string.
+    len: u64,
+}
+
+impl Checker<'_> {
+    /// Account for `size` more allocated bytes.
+    fn allocate(&mut self, size: usize, offset: usize) -> Result<()> {
+        self.allocation = self.allocation.saturating_add(size);
+        if self.allocation > self.limits.max_allocation {
+            bail!(
+                "Decoding needs more than {} bytes at offset {}",
+                self.limits.max_allocation,
+                offset
+            );
+        }
+        Ok(())
+    }
+
+    /// Check the length of an array or map.
+    fn check_len(&self, len: u64, offset: usize) -> Result<()> {
+        if len > self.limits.max_array_len as u64 {
+            bail!(
+                "Length {} at offset {} exceeds the limit of {}",
+                len,
+                offset,
+                self.limits.max_array_len
+            );
+        }
+        Ok(())
+    }
+
+    /// Check the length of a string.
+    fn check_string_len(&self, len: u64, offset: usize) -> Result<()> {
+        if len > self.limits.max_string_len as u64 {
+            bail!(
+                "String of length {} at offset {} exceeds the limit of {}",
+                len,
+                offset,
+                self.limits.max_string_len
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Visitor for Checker<'_> {
+    fn start(&mut self, head: &Head, offset: usize) -> Result<()> {
+        self.allocate(ITEM_SIZE, offset)?;
+        // Indefinite lengths are only known while going through the content, so count them in the containing item
+        if let Some(parent) = self.open.last_mut() {
+            match parent.head.major {
+                MAJOR_BYTES | MAJOR_TEXT => parent.len = parent.len.saturating_add(head.value),
+                _ => parent.len += 1,
+            }
+            if parent.head.width == Width::Indefinite {
+                let (parent_head, parent_offset, len) = (parent.head, parent.offset, parent.len);
+                match parent_head.major {
+                    // Indefinite-length strings are decoded into a single buffer, so the limit applies to the total length
+                    MAJOR_BYTES | MAJOR_TEXT if len > self.limits.max_string_len as u64 => bail!(
+                        "String at offset {} exceeds the limit of {}",
+                        parent_offset,
+                        self.limits.max_string_len
+                    ),
+                    MAJOR_ARRAY => self.check_len(len, parent_offset)?,
+                    MAJOR_MAP => self.check_len(len.div_ceil(2), parent_offset)?,
+                    _ => {}
+                }
+            }
+        }
+        match (head.major, head.argument()) {
+            (MAJOR_BYTES | MAJOR_TEXT, Some(len)) => {
+                self.check_string_len(len, offset)?;
+                self.allocate(len as usize, offset)?;
+            }
+            (MAJOR_ARRAY | MAJOR_MAP, Some(len)) => self.check_len(len, offset)?,
+            _ => {}
+        }
+        self.open.push(OpenItem {
+            head: *head,
+            offset,
+            len: 0,
+        });
+        Ok(())
+    }
+
+    fn end(&mut self, _head: &Head) -> Result<()> {
+        self.open.pop();
+        Ok(())
+    }
+}
@@ -0,0 +1,777 @@
+//! Consistency checks between related fields
+//!
+//! C-DNS stores related information in separate, optional fields.
+//! Nothing in the format prevents a writer from producing contradicting values, for example a `response_size` for a Q/R item whose flags say there is no Response.
+//! [`File::lint`] reports such contradictions.
+//!
+//! The `*_index` fields reference entries in the [`BlockTables`] of the same block.
+//! [`File::dangling_indices`] reports all references pointing outside of their table.
+//! [`File::validate`] and [`Block::validate`] additionally report arrays which must not be empty, and should be run before processing untrusted files.
+
+use crate::serialization::*;
+use std::fmt;
+
+/// A single violated consistency rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintWarning {
+    /// Location of the offending field, like `file_blocks[0].query_responses[3].response_delay`.
+    pub path: String,
+    /// Description of the inconsistency.
+    pub message: String,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// An `*_index` field referencing a non-existing table entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DanglingIndex {
+    /// Location of the index field, like `file_blocks[0].query_responses[3].query_name_index`.
+    pub path: String,
+    /// Value of the index field.
+    pub index: usize,
+    /// Name of the referenced table, like `block_tables.name_rdata`.
+    pub table: &'static str,
+    /// Number of entries in the referenced table, 0 if the table is missing.
+    pub table_len: usize,
+}
+
+impl fmt::Display for DanglingIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: index {} is out of range for {} with {} entries",
+            self.path, self.index, self.table, self.table_len
+        )
+    }
+}
+
+/// A violation of the structure of the format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Violation {
+    /// An `*_index` field references a non-existing entry.
+    DanglingIndex(DanglingIndex),
+    /// An array, which must contain at least one item, is empty.
+    EmptyArray {
+        /// Location of the array, like `file_blocks[0].block_tables.qlist[1]`.
+        path: String,
+    },
+    /// A value is outside of the range allowed by the format, see [`File::from_reader_lenient`].
+    InvalidValue {
+        /// Location of the value, like `file_preamble.block_parameters[0].storage_parameters.opcodes[2]`.
+        path: String,
+        /// Description of the problem.
+        message: String,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::DanglingIndex(dangling) => dangling.fmt(f),
+            Violation::EmptyArray { path } => write!(f, "{}: array must not be empty", path),
+            Violation::InvalidValue { path, message } => write!(f, "{}: {}", path, message),
+        }
+    }
+}
+
+impl File {
+    /// Check the structure of the file.
+    ///
+    /// This reports all [`File::dangling_indices`], and all arrays which must contain at least one item according to the CDDL, but are empty.
+    /// An empty result means the indices of the file can be resolved without failures.
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut violations: Vec<_> = self
+            .dangling_indices()
+            .into_iter()
+            .map(Violation::DanglingIndex)
+            .collect();
+        let mut check = |path: String, is_empty: bool| {
+            if is_empty {
+                violations.push(Violation::EmptyArray { path });
+            }
+        };
+
+        let preamble = &self.file_preamble;
+        check(
+            "file_preamble.block_parameters".to_string(),
+            preamble.block_parameters.is_empty(),
+        );
+        for (idx, block_parameters) in preamble.block_parameters.iter().enumerate() {
+            let storage_parameters = &block_parameters.storage_parameters;
+            let path = format!("file_preamble.block_parameters[{}].storage_parameters", idx);
+            for (field, is_empty) in [
+                ("opcodes", storage_parameters.opcodes.is_empty()),
+                ("rr_types", storage_parameters.rr_types.is_empty()),
+            ] {
+                check(format!("{}.{}", path, field), is_empty);
+            }
+            if let Some(collection_parameters) = &block_parameters.collection_parameters {
+                let path = format!(
+                    "file_preamble.block_parameters[{}].collection_parameters",
+                    idx
+                );
+                for (field, is_empty) in [
+                    ("interfaces", is_empty(&collection_parameters.interfaces)),
+                    (
+                        "server_addresses",
+                        is_empty(&collection_parameters.server_addresses),
+                    ),
+                    ("vlan_ids", is_empty(&collection_parameters.vlan_ids)),
+                ] {
+                    check(format!("{}.{}", path, field), is_empty);
+                }
+            }
+        }
+        for (block_idx, block) in self.file_blocks.iter().enumerate() {
+            check_empty_arrays(&mut check, &format!("file_blocks[{}]", block_idx), block);
+        }
+        violations
+    }
+
+    /// Check the consistency of related fields in all blocks.
+    ///
+    /// The checks cover:
+    ///
+    /// * Query and Response sizes, RCODEs, and OPT information against the [`QueryResponseFlags`] of the signature.
+    /// * `response_delay` only being present if both Query and Response exist.
+    /// * Time offsets only being used if the block has an `earliest_time`.
+    /// * [`BlockStatistics`] counts against the number of stored items.
+    /// * Unknown fields, unless the file uses a newer minor format version.
+    /// * Empty blocks, which can be removed with [`File::prune_empty_blocks`].
+    ///
+    /// Index fields pointing outside of the [`BlockTables`] are skipped.
+    /// An empty result means no inconsistencies were found.
+    pub fn lint(&self) -> Vec<LintWarning> {
+        let mut warnings = Vec::new();
+        for (block_idx, block) in self.file_blocks.iter().enumerate() {
+            lint_block(&mut warnings, &format!("file_blocks[{}]", block_idx), block);
+        }
+        if !self.file_preamble.has_newer_minor_version() {
+            let (major, minor) = self.file_preamble.format_version();
+            visit_extra_values(self, &mut |path, extra_values| {
+                for (key, _) in extra_values.iter().filter(|(key, _)| **key >= 0) {
+                    warnings.push(LintWarning {
+                        path: path.to_string(),
+                        message: format!(
+                            "unknown field {} in format version {}.{}",
+                            key, major, minor
+                        ),
+                    });
+                }
+            });
+        }
+        warnings
+    }
+
+    /// Find all index fields which point outside of the referenced table.
+    ///
+    /// This covers the Q/R items, signatures, Question and RR lists, Questions, RRs, malformed messages, address events, and the `block_parameters_index` of each block.
+    /// All other functions assume valid indices and may panic or skip items otherwise.
+    /// An empty result means all indices are valid.
+    pub fn dangling_indices(&self) -> Vec<DanglingIndex> {
+        let mut dangling = Vec::new();
+        for (block_idx, block) in self.file_blocks.iter().enumerate() {
+            let path = format!("file_blocks[{}]", block_idx);
+            let mut check =
+                |path: String, index: Option<usize>, table: &'static str, table_len: usize| {
+                    if let Some(index) = index {
+                        if index >= table_len {
+                            dangling.push(DanglingIndex {
+                                path,
+                                index,
+                                table,
+                                table_len,
+                            });
+                        }
+                    }
+                };
+
+            check(
+                format!("{}.block_preamble.block_parameters_index", path),
+                block.block_preamble.block_parameters_index,
+                "file_preamble.block_parameters",
+                self.file_preamble.block_parameters.len(),
+            );
+            check_block_indices(&mut check, &path, block);
+        }
+        dangling
+    }
+}
+
+impl Block {
+    /// Check the structure of the block, like [`File::validate`].
+    ///
+    /// The `block_parameters_index` is not checked, since it references the [`FilePreamble`].
+    /// Paths start with `block`.
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        check_block_indices(
+            &mut |path, index, table, table_len| {
+                if let Some(index) = index {
+                    if index >= table_len {
+                        violations.push(Violation::DanglingIndex(DanglingIndex {
+                            path,
+                            index,
+                            table,
+                            table_len,
+                        }));
+                    }
+                }
+            },
+            "block",
+            self,
+        );
+        check_empty_arrays(
+            &mut |path, is_empty| {
+                if is_empty {
+                    violations.push(Violation::EmptyArray { path });
+                }
+            },
+            "block",
+            self,
+        );
+        violations
+    }
+}
+
+fn is_empty<T>(array: &Option<Vec<T>>) -> bool {
+    array.as_ref().is_some_and(Vec::is_empty)
+}
+
+fn check_empty_arrays(check: &mut impl FnMut(String, bool), path: &str, block: &Block) {
+    check(
+        format!("{}.query_responses", path),
+        is_empty(&block.query_responses),
+    );
+    check(
+        format!("{}.address_event_counts", path),
+        is_empty(&block.address_event_counts),
+    );
+    check(
+        format!("{}.malformed_messages", path),
+        is_empty(&block.malformed_messages),
+    );
+
+    let tables = match &block.block_tables {
+        Some(tables) => tables,
+        None => return,
+    };
+    let path = format!("{}.block_tables", path);
+    for (table, is_empty) in [
+        ("ip_address", is_empty(&tables.ip_address)),
+        ("classtype", is_empty(&tables.classtype)),
+        ("name_rdata", is_empty(&tables.name_rdata)),
+        ("qr_sig", is_empty(&tables.qr_sig)),
+        ("qlist", is_empty(&tables.qlist)),
+        ("qrr", is_empty(&tables.qrr)),
+        ("rrlist", is_empty(&tables.rrlist)),
+        ("rr", is_empty(&tables.rr)),
+        (
+            "malformed_message_data",
+            is_empty(&tables.malformed_message_data),
+        ),
+    ] {
+        check(format!("{}.{}", path, table), is_empty);
+    }
+    for (idx, list) in tables.qlist.iter().flatten().enumerate() {
+        check(format!("{}.qlist[{}]", path, idx), list.is_empty());
+    }
+    for (idx, list) in tables.rrlist.iter().flatten().enumerate() {
+        check(format!("{}.rrlist[{}]", path, idx), list.is_empty());
+    }
+}
+
+pub(crate) fn check_block_indices(
+    check: &mut impl FnMut(String, Option<usize>, &'static str, usize),
+    path: &str,
+    block: &Block,
+) {
+    let tables = block.block_tables.as_ref();
+    let ip_address = tables
+        .and_then(|t| t.ip_address.as_ref())
+        .map_or(0, Vec::len);
+    let classtype = tables
+        .and_then(|t| t.classtype.as_ref())
+        .map_or(0, Vec::len);
+    let name_rdata = tables
+        .and_then(|t| t.name_rdata.as_ref())
+        .map_or(0, Vec::len);
+    let qr_sig = tables.and_then(|t| t.qr_sig.as_ref()).map_or(0, Vec::len);
+    let qlist = tables.and_then(|t| t.qlist.as_ref()).map_or(0, Vec::len);
+    let qrr = tables.and_then(|t| t.qrr.as_ref()).map_or(0, Vec::len);
+    let rrlist = tables.and_then(|t| t.rrlist.as_ref()).map_or(0, Vec::len);
+    let rr = tables.and_then(|t| t.rr.as_ref()).map_or(0, Vec::len);
+    let malformed_message_data = tables
+        .and_then(|t| t.malformed_message_data.as_ref())
+        .map_or(0, Vec::len);
+
+    if let Some(tables) = tables {
+        let path = format!("{}.block_tables", path);
+        for (idx, sig) in tables.qr_sig.iter().flatten().enumerate() {
+            let path = format!("{}.qr_sig[{}]", path, idx);
+            check(
+                format!("{}.server_address_index", path),
+                sig.server_address_index,
+                "block_tables.ip_address",
+                ip_address,
+            );
+            check(
+                format!("{}.query_classtype_index", path),
+                sig.query_classtype_index,
+                "block_tables.classtype",
+                classtype,
+            );
+            check(
+                format!("{}.query_opt_rdata_index", path),
+                sig.query_opt_rdata_index,
+                "block_tables.name_rdata",
+                name_rdata,
+            );
+        }
+        for (idx, list) in tables.qlist.iter().flatten().enumerate() {
+            for (pos, question) in list.iter().enumerate() {
+                check(
+                    format!("{}.qlist[{}][{}]", path, idx, pos),
+                    Some(*question),
+                    "block_tables.qrr",
+                    qrr,
+                );
+            }
+        }
+        for (idx, question) in tables.qrr.iter().flatten().enumerate() {
+            let path = format!("{}.qrr[{}]", path, idx);
+            check(
+                format!("{}.name_index", path),
+                Some(question.name_index),
+                "block_tables.name_rdata",
+                name_rdata,
+            );
+            check(
+                format!("{}.classtype_index", path),
+                Some(question.classtype_index),
+                "block_tables.classtype",
+                classtype,
+            );
+        }
+        for (idx, list) in tables.rrlist.iter().flatten().enumerate() {
+            for (pos, rr_idx) in list.iter().enumerate() {
+                check(
+                    format!("{}.rrlist[{}][{}]", path, idx, pos),
+                    Some(*rr_idx),
+                    "block_tables.rr",
+                    rr,
+                );
+            }
+        }
+        for (idx, record) in tables.rr.iter().flatten().enumerate() {
+            let path = format!("{}.rr[{}]", path, idx);
+            check(
+                format!("{}.name_index", path),
+                Some(record.name_index),
+                "block_tables.name_rdata",
+                name_rdata,
+            );
+            check(
+                format!("{}.classtype_index", path),
+                Some(record.classtype_index),
+                "block_tables.classtype",
+                classtype,
+            );
+            check(
+                format!("{}.rdata_index", path),
+                record.rdata_index,
+                "block_tables.name_rdata",
+                name_rdata,
+            );
+        }
+        for (idx, data) in tables.malformed_message_data.iter().flatten().enumerate() {
+            check(
+                format!(
+                    "{}.malformed_message_data[{}].server_address_index",
+                    path, idx
+                ),
+                data.server_address_index,
+                "block_tables.ip_address",
+                ip_address,
+            );
+        }
+    }
+
+    for (idx, query_response) in block.query_responses.iter().flatten().enumerate() {
+        let path = format!("{}.query_responses[{}]", path, idx);
+        check(
+            format!("{}.client_address_index", path),
+            query_response.client_address_index,
+            "block_tables.ip_address",
+            ip_address,
+        );
+        check(
+            format!("{}.qr_signature_index", path),
+            query_response.qr_signature_index,
+            "block_tables.qr_sig",
+            qr_sig,
+        );
+        check(
+            format!("{}.query_name_index", path),
+            query_response.query_name_index,
+            "block_tables.name_rdata",
+            name_rdata,
+        );
+        if let Some(data) = &query_response.response_processing_data {
+            check(
+                format!("{}.response_processing_data.bailiwick_index", path),
+                data.bailiwick_index,
+                "block_tables.name_rdata",
+                name_rdata,
+            );
+        }
+        for (field, extended) in [
+            ("query_extended", &query_response.query_extended),
+            ("response_extended", &query_response.response_extended),
+        ] {
+            let extended = match extended {
+                Some(extended) => extended,
+                None => continue,
+            };
+            check(
+                format!("{}.{}.question_index", path, field),
+                extended.question_index,
+                "block_tables.qlist",
+                qlist,
+            );
+            for (name, index) in [
+                ("answer_index", extended.answer_index),
+                ("authority_index", extended.authority_index),
+                ("additional_index", extended.additional_index),
+            ] {
+                check(
+                    format!("{}.{}.{}", path, field, name),
+                    index,
+                    "block_tables.rrlist",
+                    rrlist,
+                );
+            }
+        }
+    }
+
+    for (idx, address_event) in block.address_event_counts.iter().flatten().enumerate() {
+        check(
+            format!("{}.address_event_counts[{}].ae_address_index", path, idx),
+            Some(address_event.ae_address_index),
+            "block_tables.ip_address",
+            ip_address,
+        );
+    }
+
+    for (idx, malformed_message) in block.malformed_messages.iter().flatten().enumerate() {
+        let path = format!("{}.malformed_messages[{}]", path, idx);
+        check(
+            format!("{}.client_address_index", path),
+            malformed_message.client_address_index,
+            "block_tables.ip_address",
+            ip_address,
+        );
+        check(
+            format!("{}.message_data_index", path),
+            malformed_message.message_data_index,
+            "block_tables.malformed_message_data",
+            malformed_message_data,
+        );
+    }
+}
+
+/// Call `visit` with the path and [`ExtraValues`] of every struct in `file`.
+fn visit_extra_values(file: &File, visit: &mut impl FnMut(&str, &ExtraValues)) {
+    let preamble = &file.file_preamble;
+    visit("file_preamble", &preamble.extra_values);
+    for (idx, block_parameters) in preamble.block_parameters.iter().enumerate() {
+        let path = format!("file_preamble.block_parameters[{}]", idx);
+        visit(&path, &block_parameters.extra_values);
+        let storage_parameters = &block_parameters.storage_parameters;
+        visit(
+            &format!("{}.storage_parameters", path),
+            &storage_parameters.extra_values,
+        );
+        visit(
+            &format!("{}.storage_parameters.storage_hints", path),
+            &storage_parameters.storage_hints.extra_values,
+        );
+        if let Some(collection_parameters) = &block_parameters.collection_parameters {
+            visit(
+                &format!("{}.collection_parameters", path),
+                &collection_parameters.extra_values,
+            );
+        }
+    }
+
+    for (block_idx, block) in file.file_blocks.iter().enumerate() {
+        let path = format!("file_blocks[{}]", block_idx);
+        visit(&path, &block.extra_values);
+        visit(
+            &format!("{}.block_preamble", path),
+            &block.block_preamble.extra_values,
+        );
+        if let Some(statistics) = &block.block_statistics {
+            visit(
+                &format!("{}.block_statistics", path),
+                &statistics.extra_values,
+            );
+        }
+        if let Some(tables) = &block.block_tables {
+            let path = format!("{}.block_tables", path);
+            visit(&path, &tables.extra_values);
+            for (idx, sig) in tables.qr_sig.iter().flatten().enumerate() {
+                visit(&format!("{}.qr_sig[{}]", path, idx), &sig.extra_values);
+            }
+            for (idx, question) in tables.qrr.iter().flatten().enumerate() {
+                visit(&format!("{}.qrr[{}]", path, idx), &question.extra_values);
+            }
+            for (idx, record) in tables.rr.iter().flatten().enumerate() {
+                visit(&format!("{}.rr[{}]", path, idx), &record.extra_values);
+            }
+            for (idx, data) in tables.malformed_message_data.iter().flatten().enumerate() {
+                visit(
+                    &format!("{}.malformed_message_data[{}]", path, idx),
+                    &data.extra_values,
+                );
+            }
+        }
+        for (idx, query_response) in block.query_responses.iter().flatten().enumerate() {
+            let path = format!("{}.query_responses[{}]", path, idx);
+            visit(&path, &query_response.extra_values);
+            if let Some(data) = &query_response.response_processing_data {
+                visit(
+                    &format!("{}.response_processing_data", path),
+                    &data.extra_values,
+                );
+            }
+            for (field, extended) in [
+                ("query_extended", &query_response.query_extended),
+                ("response_extended", &query_response.response_extended),
+            ] {
+                if let Some(extended) = extended {
+                    visit(&format!("{}.{}", path, field), &extended.extra_values);
+                }
+            }
+        }
+        for (idx, address_event) in block.address_event_counts.iter().flatten().enumerate() {
+            visit(
+                &format!("{}.address_event_counts[{}]", path, idx),
+                &address_event.extra_values,
+            );
+        }
+        for (idx, malformed_message) in block.malformed_messages.iter().flatten().enumerate() {
+            visit(
+                &format!("{}.malformed_messages[{}]", path, idx),
+                &malformed_message.extra_values,
+            );
+        }
+    }
+}
+
+fn lint_block(warnings: &mut Vec<LintWarning>, path: &str, block: &Block) {
+    let mut warn = |path: String, message: &str| {
+        warnings.push(LintWarning {
+            path,
+            message: message.to_string(),
+        })
+    };
+    let has_earliest_time = block.block_preamble.earliest_time.is_some();
+
+    if block.is_empty() {
+        warn(
+            path.to_string(),
+            "block contains no Q/R items, address events, or malformed messages",
+        );
+    }
+
+    for (sig_idx, sig) in block
+        .block_tables
+        .as_ref()
+        .and_then(|tables| tables.qr_sig.as_deref())
+        .unwrap_or(&[])
+        .iter()
+        .enumerate()
+    {
+        if let Some(flags) = sig.qr_sig_flags {
+            lint_signature(
+                &mut warn,
+                &format!("{}.block_tables.qr_sig[{}]", path, sig_idx),
+                sig,
+                flags,
+            );
+        }
+    }
+
+    for (qr_idx, query_response) in block
+        .query_responses
+        .as_deref()
+        .unwrap_or(&[])
+        .iter()
+        .enumerate()
+    {
+        let path = format!("{}.query_responses[{}]", path, qr_idx);
+        if query_response.time_offset.is_some() && !has_earliest_time {
+            warn(
+                format!("{}.time_offset", path),
+                "time offset is used, but the block has no earliest_time",
+            );
+        }
+
+        let sig = block.block_tables.as_ref().and_then(|tables| {
+            tables
+                .qr_sig
+                .as_ref()?
+                .get(query_response.qr_signature_index?)
+        });
+        let flags = match sig.and_then(|sig| sig.qr_sig_flags) {
+            Some(flags) => flags,
+            None => continue,
+        };
+        let has_query = flags.contains(QueryResponseFlags::HasQuery);
+        let has_response = flags.contains(QueryResponseFlags::HasResponse);
+
+        if query_response.query_size.is_some() && !has_query {
+            warn(
+                format!("{}.query_size", path),
+                "query size is present, but qr_sig_flags indicate no Query",
+            );
+        }
+        if query_response.response_size.is_some() && !has_response {
+            warn(
+                format!("{}.response_size", path),
+                "response size is present, but qr_sig_flags indicate no Response",
+            );
+        }
+        if query_response.response_delay.is_some() && !(has_query && has_response) {
+            warn(
+                format!("{}.response_delay", path),
+                "response delay is present, but qr_sig_flags indicate not both Query and Response",
+            );
+        }
+        if query_response.response_processing_data.is_some() && !has_response {
+            warn(
+                format!("{}.response_processing_data", path),
+                "response processing data is present, but qr_sig_flags indicate no Response",
+            );
+        }
+        if query_response.query_extended.is_some() && !has_query {
+            warn(
+                format!("{}.query_extended", path),
+                "query sections are present, but qr_sig_flags indicate no Query",
+            );
+        }
+        if query_response.response_extended.is_some() && !has_response {
+            warn(
+                format!("{}.response_extended", path),
+                "response sections are present, but qr_sig_flags indicate no Response",
+            );
+        }
+    }
+
+    for (mm_idx, malformed_message) in block
+        .malformed_messages
+        .as_deref()
+        .unwrap_or(&[])
+        .iter()
+        .enumerate()
+    {
+        if malformed_message.time_offset.is_some() && !has_earliest_time {
+            warn(
+                format!("{}.malformed_messages[{}].time_offset", path, mm_idx),
+                "time offset is used, but the block has no earliest_time",
+            );
+        }
+    }
+
+    if let Some(statistics) = &block.block_statistics {
+        let query_responses = block.query_responses.as_ref().map_or(0, Vec::len);
+        if statistics
+            .qr_data_items
+            .is_some_and(|count| count != query_responses)
+        {
+            warn(
+                format!("{}.block_statistics.qr_data_items", path),
+                "count differs from the number of Q/R data items in the block",
+            );
+        }
+        let malformed_messages = block.malformed_messages.as_ref().map_or(0, Vec::len);
+        if statistics
+            .malformed_items
+            .is_some_and(|count| count < malformed_messages)
+        {
+            warn(
+                format!("{}.block_statistics.malformed_items", path),
+                "count is smaller than the number of malformed messages in the block",
+            );
+        }
+    }
+}
+
+fn lint_signature(
+    warn: &mut impl FnMut(String, &str),
+    path: &str,
+    sig: &QueryResponseSignature,
+    flags: enumset::EnumSet<QueryResponseFlags>,
+) {
+    let has_query = flags.contains(QueryResponseFlags::HasQuery);
+    let has_response = flags.contains(QueryResponseFlags::HasResponse);
+    let query_has_opt = flags.contains(QueryResponseFlags::QueryHasOpt);
+
+    if query_has_opt && !has_query {
+        warn(
+            format!("{}.qr_sig_flags", path),
+            "Query has OPT, but there is no Query",
+        );
+    }
+    if flags.contains(QueryResponseFlags::ResponseHasOpt) && !has_response {
+        warn(
+            format!("{}.qr_sig_flags", path),
+            "Response has OPT, but there is no Response",
+        );
+    }
+    if flags.contains(QueryResponseFlags::QueryHasNoQuestion) && !has_query {
+        warn(
+            format!("{}.qr_sig_flags", path),
+            "Query has no Question, but there is no Query",
+        );
+    }
+    if flags.contains(QueryResponseFlags::ResponseHasNoQuestion) && !has_response {
+        warn(
+            format!("{}.qr_sig_flags", path),
+            "Response has no Question, but there is no Response",
+        );
+    }
+
+    if !query_has_opt {
+        if sig.query_opt_rdata_index.is_some() {
+            warn(
+                format!("{}.query_opt_rdata_index", path),
+                "OPT RDATA is present, but qr_sig_flags indicate no Query OPT",
+            );
+        }
+        if sig.query_edns_version.is_some() {
+            warn(
+                format!("{}.query_edns_version", path),
+                "EDNS version is present, but qr_sig_flags indicate no Query OPT",
+            );
+        }
+        if sig.query_udp_size.is_some() {
+            warn(
+                format!("{}.query_udp_size", path),
+                "UDP payload size is present, but qr_sig_flags indicate no Query OPT",
+            );
+        }
+    }
+    if sig.response_rcode.is_some() && !has_response {
+        warn(
+            format!("{}.response_rcode", path),
+            "response RCODE is present, but qr_sig_flags indicate no Response",
+        );
+    }
+}
@@ -0,0 +1,392 @@
+//! Pair captured DNS messages into Q/R items
+//!
+//! A collector observes Queries and Responses as individual messages.
+//! The [`Matcher`] pairs each Response with its Query, based on the client and server addresses and ports, the transport, the transaction id, and the first Question.
+//! It follows the `query_timeout` and `skew_timeout` of the [`CollectionParameters`]:
+//! A Query without a Response within the `query_timeout` becomes an unmatched Query.
+//! A Response may be seen up to `skew_timeout` before its Query, otherwise it becomes an unmatched Response.
+//!
+//! The matcher produces [`QueryResponseRecord`]s, which a [`BlockBuilder`](crate::builder::BlockBuilder) turns into a [`Block`].
+//!
+//! ```
+//! # use c_dns::matcher::{CapturedMessage, Matcher};
+//! # use c_dns::serialization::{CollectionParameters, Timestamp};
+//! # use c_dns::wire::Message;
+//! # use c_dns::Transport;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! # let query: &[u8] = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x00\x00\x01\x00\x01";
+//! # let response: &[u8] = b"\x12\x34\x81\x80\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x00\x00\x01\x00\x01";
+//! let mut matcher = Matcher::new(1_000_000, &CollectionParameters::default());
+//! for (ticks, message) in [(0, query), (1500, response)] {
+//!     matcher.push(CapturedMessage {
+//!         time: Timestamp {
+//!             timestamp_secs: 1_600_000_000,
+//!             timestamp_ticks: ticks.into(),
+//!         },
+//!         client: "192.0.2.1:40000".parse()?,
+//!         server: "192.0.2.53:53".parse()?,
+//!         transport: Transport::Udp,
+//!         hoplimit: None,
+//!         size: message.len() as u16,
+//!         message: Message::parse(message)?,
+//!     });
+//! }
+//! let record = matcher.pop().unwrap();
+//! assert_eq!(Some(1500), record.response_delay.map(i32::from));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::builder::{
+    QueryResponseRecord, QuestionRecord, RRRecord, SectionsRecord, SignatureRecord,
+};
+use crate::flags::DnsHeaderFlags;
+use crate::serialization::*;
+use crate::wire::{Direction, Message, Record};
+use crate::{IpVersion, Transport};
+use bytes::Bytes;
+use enumset::EnumSet;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+
+/// Default `query_timeout` in milliseconds, if the [`CollectionParameters`] do not specify one.
+pub const DEFAULT_QUERY_TIMEOUT: u32 = 5000;
+/// Default `skew_timeout` in microseconds, if the [`CollectionParameters`] do not specify one.
+pub const DEFAULT_SKEW_TIMEOUT: u32 = 10;
+
+/// A single DNS message as seen by the collector.
+#[derive(Debug, Clone)]
+pub struct CapturedMessage {
+    /// Time the message was captured, in the ticks of the [`Matcher`].
+    pub time: Timestamp,
+    /// Address and port of the client, which sends the Query.
+    pub client: SocketAddr,
+    /// Address and port of the server, which sends the Response.
+    pub server: SocketAddr,
+    /// Transport the message was sent over.
+    pub transport: Transport,
+    /// The IPv4 TTL or IPv6 Hoplimit of the packet.
+    ///
+    /// Only used for Queries.
+    pub hoplimit: Option<u8>,
+    /// Size of the DNS message in bytes.
+    pub size: u16,
+    /// The parsed DNS message.
+    pub message: Message,
+}
+
+/// Fields identifying which Query a Response belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct MatchKey {
+    client: SocketAddr,
+    server: SocketAddr,
+    transport: Transport,
+    transaction_id: u16,
+    question: Option<(NameOrRdata, u16, u16)>,
+}
+
+impl MatchKey {
+    fn new(message: &CapturedMessage) -> Self {
+        Self {
+            client: message.client,
+            server: message.server,
+            transport: message.transport,
+            transaction_id: message.message.header.id,
+            question: message
+                .message
+                .questions
+                .first()
+                .map(|question| (question.name.clone(), question.rr_type, question.class)),
+        }
+    }
+}
+
+/// Pairs Queries and Responses into [`QueryResponseRecord`]s.
+///
+/// Messages must be pushed in the order they were captured.
+/// The time of each message advances the clock of the matcher, which expires Queries and Responses waiting for their partner.
+/// Completed records are returned by [`Matcher::pop`] in the order they were completed.
+#[derive(Debug)]
+pub struct Matcher {
+    ticks_per_second: i128,
+    /// The `query_timeout` in ticks.
+    query_timeout: i128,
+    /// The `skew_timeout` in ticks.
+    skew_timeout: i128,
+    now: i128,
+    queries: HashMap<MatchKey, VecDeque<(i128, CapturedMessage)>>,
+    responses: HashMap<MatchKey, VecDeque<(i128, CapturedMessage)>>,
+    completed: VecDeque<QueryResponseRecord>,
+}
+
+impl Matcher {
+    /// Create a matcher producing times in `ticks_per_second`.
+    ///
+    /// The timeouts are taken from `collection_parameters`, or default to [`DEFAULT_QUERY_TIMEOUT`] and [`DEFAULT_SKEW_TIMEOUT`].
+    pub fn new(ticks_per_second: u32, collection_parameters: &CollectionParameters) -> Self {
+        let ticks_per_second = i128::from(ticks_per_second);
+        let query_timeout = collection_parameters
+            .query_timeout
+            .unwrap_or(DEFAULT_QUERY_TIMEOUT);
+        let skew_timeout = collection_parameters
+            .skew_timeout
+            .unwrap_or(DEFAULT_SKEW_TIMEOUT);
+        Self {
+            ticks_per_second,
+            query_timeout: i128::from(query_timeout) * ticks_per_second / 1000,
+            skew_timeout: i128::from(skew_timeout) * ticks_per_second / 1_000_000,
+            now: i128::MIN,
+            queries: HashMap::new(),
+            responses: HashMap::new(),
+            completed: VecDeque::new(),
+        }
+    }
+
+    /// Number of Queries and Responses waiting for their partner.
+    pub fn pending(&self) -> usize {
+        self.queries.values().map(VecDeque::len).sum::<usize>()
+            + self.responses.values().map(VecDeque::len).sum::<usize>()
+    }
+
+    /// Add a captured message, which advances the clock to its time.
+    pub fn push(&mut self, message: CapturedMessage) {
+        let time = self.ticks(message.time);
+        self.advance(time);
+        let key = MatchKey::new(&message);
+        if message.message.header.is_response() {
+            // The oldest Query still waiting is the partner
+            match take_first(&mut self.queries, &key) {
+                Some((_, query)) => self.complete(Some(query), Some(message)),
+                None => self
+                    .responses
+                    .entry(key)
+                    .or_default()
+                    .push_back((time, message)),
+            }
+        } else {
+            // A Response seen within the skew before the Query
+            match take_first(&mut self.responses, &key) {
+                Some((_, response)) => self.complete(Some(message), Some(response)),
+                None => self
+                    .queries
+                    .entry(key)
+                    .or_default()
+                    .push_back((time, message)),
+            }
+        }
+    }
+
+    /// Advance the clock, expiring Queries older than the `query_timeout` and Responses older than the `skew_timeout`.
+    pub fn advance_to(&mut self, time: Timestamp) {
+        let time = self.ticks(time);
+        self.advance(time);
+    }
+
+    /// Take the next completed record.
+    pub fn pop(&mut self) -> Option<QueryResponseRecord> {
+        self.completed.pop_front()
+    }
+
+    /// Expire all waiting Queries and Responses, and return all remaining records.
+    ///
+    /// Use this at the end of a capture.
+    pub fn finish(mut self) -> Vec<QueryResponseRecord> {
+        self.advance(i128::MAX);
+        self.completed.into()
+    }
+
+    fn advance(&mut self, time: i128) {
+        self.now = self.now.max(time);
+        let now = self.now;
+        let (query_timeout, skew_timeout) = (self.query_timeout, self.skew_timeout);
+        let mut expired = Vec::new();
+        for (map, timeout, is_response) in [
+            (&mut self.queries, query_timeout, false),
+            (&mut self.responses, skew_timeout, true),
+        ] {
+            map.retain(|_, pending| {
+                while let Some((time, _)) = pending.front() {
+                    if now.saturating_sub(*time) <= timeout {
+                        break;
+                    }
+                    if let Some((time, message)) = pending.pop_front() {
+                        expired.push((time, is_response, message));
+                    }
+                }
+                !pending.is_empty()
+            });
+        }
+        expired.sort_by_key(|(time, _, _)| *time);
+        for (_, is_response, message) in expired {
+            if is_response {
+                self.complete(None, Some(message));
+            } else {
+                self.complete(Some(message), None);
+            }
+        }
+    }
+
+    fn ticks(&self, time: Timestamp) -> i128 {
+        i128::from(time.timestamp_secs) * self.ticks_per_second
+            + i128::from(u32::from(time.timestamp_ticks))
+    }
+
+    fn complete(&mut self, query: Option<CapturedMessage>, response: Option<CapturedMessage>) {
+        let first = match query.as_ref().or(response.as_ref()) {
+            Some(first) => first,
+            None => return,
+        };
+        let response_delay = match (&query, &response) {
+            (Some(query), Some(response)) => {
+                let delay = self.ticks(response.time) - self.ticks(query.time);
+                i32::try_from(delay).ok().map(Ticks::from)
+            }
+            _ => None,
+        };
+
+        let mut qr_sig_flags = EnumSet::new();
+        let mut qr_dns_flags = EnumSet::new();
+        if let Some(query) = &query {
+            qr_sig_flags |= QueryResponseFlags::HasQuery;
+            if query.message.opt.is_some() {
+                qr_sig_flags |= QueryResponseFlags::QueryHasOpt;
+            }
+            if query.message.questions.is_empty() {
+                qr_sig_flags |= QueryResponseFlags::QueryHasNoQuestion;
+            }
+            qr_dns_flags |= dns_flags(&query.message, false);
+        }
+        if let Some(response) = &response {
+            qr_sig_flags |= QueryResponseFlags::HasResponse;
+            if response.message.opt.is_some() {
+                qr_sig_flags |= QueryResponseFlags::ResponseHasOpt;
+            }
+            if response.message.questions.is_empty() {
+                qr_sig_flags |= QueryResponseFlags::ResponseHasNoQuestion;
+            }
+            qr_dns_flags |= dns_flags(&response.message, true);
+        }
+
+        let ip_version = if first.client.is_ipv6() {
+            IpVersion::Ipv6
+        } else {
+            IpVersion::Ipv4
+        };
+        let header = &first.message.header;
+        let question = first.message.questions.first();
+        let query_opt = query.as_ref().and_then(|query| query.message.opt.as_ref());
+        let signature = QueryResponseSignature {
+            server_port: Some(first.server.port()),
+            qr_transport_flags: Some(TransportFlags::new(ip_version, first.transport, false)),
+            qr_sig_flags: Some(qr_sig_flags),
+            query_opcode: Opcode::try_from(header.opcode()).ok(),
+            qr_dns_flags: Some(qr_dns_flags),
+            query_rcode: query.as_ref().map(|query| query.message.rcode()),
+            query_qdcount: Some(usize::from(header.qdcount)),
+            query_ancount: Some(usize::from(header.ancount)),
+            query_nscount: Some(usize::from(header.nscount)),
+            query_arcount: Some(usize::from(header.arcount)),
+            query_edns_version: query_opt.map(|opt| opt.version),
+            query_udp_size: query_opt.map(|opt| opt.udp_payload_size),
+            response_rcode: response.as_ref().map(|response| response.message.rcode()),
+            ..Default::default()
+        };
+        let query_opt_rdata = query_opt.filter(|opt| !opt.options.is_empty()).map(|opt| {
+            let mut rdata = Vec::new();
+            for option in &opt.options {
+                rdata.extend_from_slice(&option.code.to_be_bytes());
+                rdata.extend_from_slice(&(option.data.len() as u16).to_be_bytes());
+                rdata.extend_from_slice(&option.data);
+            }
+            NameOrRdata::from(Bytes::from(rdata))
+        });
+
+        self.completed.push_back(QueryResponseRecord {
+            time: Some(first.time),
+            client_address: Some(first.client.ip()),
+            client_port: Some(first.client.port()),
+            transaction_id: Some(header.id),
+            client_hoplimit: query.as_ref().and_then(|query| query.hoplimit),
+            response_delay,
+            query_name: question.map(|question| question.name.clone()),
+            query_size: query.as_ref().map(|query| query.size),
+            response_size: response.as_ref().map(|response| response.size),
+            bailiwick: None,
+            processing_flags: None,
+            signature: Some(SignatureRecord {
+                server_address: Some(first.server.ip()),
+                query_classtype: question.map(|question| ClassType {
+                    type_: question.rr_type.into(),
+                    class: question.class.into(),
+                }),
+                query_opt_rdata,
+                signature,
+            }),
+            query_extended: query.and_then(|query| sections(query.message)),
+            response_extended: response.and_then(|response| sections(response.message)),
+        });
+    }
+}
+
+/// Take the oldest entry waiting with `key`.
+fn take_first(
+    map: &mut HashMap<MatchKey, VecDeque<(i128, CapturedMessage)>>,
+    key: &MatchKey,
+) -> Option<(i128, CapturedMessage)> {
+    let pending = map.get_mut(key)?;
+    let first = pending.pop_front();
+    if pending.is_empty() {
+        map.remove(key);
+    }
+    first
+}
+
+/// The [`DNSFlags`] of the header and OPT RR of `message`.
+fn dns_flags(message: &Message, is_response: bool) -> EnumSet<DNSFlags> {
+    let mut flags = DnsHeaderFlags::from_header_bits(message.header.flags);
+    flags.dnssec_ok = message
+        .opt
+        .as_ref()
+        .is_some_and(|opt| opt.flags & 0x8000 != 0);
+    // The DO bit is only recorded for the Query
+    flags.to_dns_flags(if is_response {
+        Direction::Response
+    } else {
+        Direction::Query
+    })
+}
+
+/// The sections of `message` except the first Question, or [`None`] if they are all empty.
+fn sections(message: Message) -> Option<SectionsRecord> {
+    let rr = |record: Record| RRRecord {
+        name: record.name,
+        classtype: ClassType {
+            type_: record.rr_type.into(),
+            class: record.class.into(),
+        },
+        ttl: Some(record.ttl),
+        rdata: Some(record.rdata),
+    };
+    let sections = SectionsRecord {
+        questions: message
+            .questions
+            .into_iter()
+            .skip(1)
+            .map(|question| QuestionRecord {
+                name: question.name,
+                classtype: ClassType {
+                    type_: question.rr_type.into(),
+                    class: question.class.into(),
+                },
+            })
+            .collect(),
+        answers: message.answers.into_iter().map(rr).collect(),
+        authorities: message.authorities.into_iter().map(rr).collect(),
+        additionals: message.additionals.into_iter().map(rr).collect(),
+    };
+    let is_empty = sections.questions.is_empty()
+        && sections.answers.is_empty()
+        && sections.authorities.is_empty()
+        && sections.additionals.is_empty();
+    (!is_empty).then_some(sections)
+}
@@ -0,0 +1,339 @@
+//! Combine multiple files into one
+//!
+//! Collectors commonly rotate their output files, so a single capture is split into many files.
+//! [`File::concat`] joins such files, keeping a single copy of the [`BlockParameters`] shared between them.
+//!
+//! ```
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let first = File::read_path("./tests/data/dns.cdns")?;
+//! let second = File::read_path("./tests/data/dns.cdns")?;
+//! let file = File::concat([first, second])?;
+//! assert_eq!(2, file.block_count());
+//! assert_eq!(1, file.file_preamble.block_parameters.len());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`File::merge`] additionally combines consecutive blocks into larger ones, for example to consolidate hourly captures into a daily archive.
+//! [`Block::merge`] combines two blocks, which share their [`BlockParameters`], into one.
+//! The [`BlockTables`] are merged with [`BlockTables::append`], such that equal addresses and names are only stored once.
+
+use crate::edit::ticks;
+use crate::error::bail;
+use crate::serialization::*;
+use crate::Result;
+
+impl File {
+    /// Concatenate the blocks of all `files` into a single file.
+    ///
+    /// Identical [`BlockParameters`] are only stored once and the `block_parameters_index` of the blocks is rewritten accordingly.
+    /// Parameters differing in any value, for example the `ticks_per_second` or the address prefixes, are kept as separate entries.
+    /// Converting all files to the same resolution with [`File::convert_ticks`] first avoids separate entries for differing `ticks_per_second`.
+    ///
+    /// The file type id, the `private_version`, and the `extra_values` of the [`FilePreamble`] are taken from the first file and must be identical in all files.
+    /// The format version is the newest minor version of all files.
+    /// Fails if `files` is empty or a block references non-existing [`BlockParameters`].
+    pub fn concat(files: impl IntoIterator<Item = File>) -> Result<File> {
+        let mut files = files.into_iter();
+        let mut result = match files.next() {
+            Some(first) => first,
+            None => bail!("Cannot concatenate zero files"),
+        };
+        let first_parameters = std::mem::take(&mut result.file_preamble.block_parameters);
+        let first_blocks = std::mem::take(&mut result.file_blocks);
+        append_file(&mut result, first_parameters, first_blocks, 0)?;
+
+        for (file_idx, file) in files.enumerate() {
+            let file_idx = file_idx + 1;
+            let preamble = &file.file_preamble;
+            if file.file_type_id != result.file_type_id {
+                bail!(
+                    "File {} has the file type id {:?}, expected {:?}",
+                    file_idx,
+                    file.file_type_id,
+                    result.file_type_id
+                );
+            }
+            if preamble.major_format_version != result.file_preamble.major_format_version {
+                bail!(
+                    "File {} has the major format version {}, expected {}",
+                    file_idx,
+                    preamble.major_format_version,
+                    result.file_preamble.major_format_version
+                );
+            }
+            if preamble.private_version != result.file_preamble.private_version {
+                bail!(
+                    "File {} has the private version {:?}, expected {:?}",
+                    file_idx,
+                    preamble.private_version,
+                    result.file_preamble.private_version
+                );
+            }
+            if preamble.extra_values != result.file_preamble.extra_values {
+                bail!(
+                    "File {} has different extra values in the FilePreamble",
+                    file_idx
+                );
+            }
+            result.file_preamble.minor_format_version = result
+                .file_preamble
+                .minor_format_version
+                .max(preamble.minor_format_version);
+            append_file(
+                &mut result,
+                file.file_preamble.block_parameters,
+                file.file_blocks,
+                file_idx,
+            )?;
+        }
+        Ok(result)
+    }
+}
+
+impl File {
+    /// Concatenate all `files` like [`File::concat`], and merge consecutive blocks with the same [`BlockParameters`].
+    ///
+    /// Blocks are merged with [`Block::merge`] as long as the merged block has at most `max_block_items` Q/R items and all time offsets fit.
+    /// Otherwise, a new block is started.
+    pub fn merge(files: impl IntoIterator<Item = File>) -> Result<File> {
+        let mut file = File::concat(files)?;
+        let mut merged: Vec<Block> = Vec::with_capacity(file.file_blocks.len());
+        for block in std::mem::take(&mut file.file_blocks) {
+            let block_parameters_index = block.block_preamble.block_parameters_index.unwrap_or(0);
+            // The indices were checked by `concat`
+            let block_parameters = &file.file_preamble.block_parameters[block_parameters_index];
+            let last = match merged.last_mut() {
+                Some(last)
+                    if last.block_preamble.block_parameters_index.unwrap_or(0)
+                        == block_parameters_index
+                        && query_response_count(last) + query_response_count(&block)
+                            <= block_parameters.storage_parameters.max_block_items
+                        && merged_time(last, &block, block_parameters).is_ok() =>
+                {
+                    last
+                }
+                _ => {
+                    merged.push(block);
+                    continue;
+                }
+            };
+            let previous = std::mem::replace(last, empty_block());
+            *last = previous.merge(block, block_parameters)?;
+        }
+        file.file_blocks = merged;
+        Ok(file)
+    }
+}
+
+impl Block {
+    /// Move all items of `other` into this block.
+    ///
+    /// Both blocks must use the same `block_parameters`, which define the ticks per second.
+    /// The `earliest_time` becomes the earlier of both blocks and the time offsets are adjusted accordingly.
+    /// The tables of `other` are added with [`BlockTables::append`] and the indices of its items are rewritten.
+    /// The counts in the [`BlockStatistics`] are summed, a count missing in either block is missing in the result.
+    /// The [`BlockPreamble`] is kept from this block apart from the `earliest_time`.
+    ///
+    /// Fails if a time offset does not fit after moving the `earliest_time`.
+    ///
+    /// # Panics
+    ///
+    /// The indices in `other` must be valid, as checked by [`File::dangling_indices`].
+    pub fn merge(mut self, mut other: Block, block_parameters: &BlockParameters) -> Result<Block> {
+        let (earliest_time, shift, other_shift) = merged_time(&self, &other, block_parameters)?;
+        shift_time_offsets(&mut self, shift);
+        shift_time_offsets(&mut other, other_shift);
+        self.block_preamble.earliest_time = earliest_time;
+
+        self.block_statistics = match (self.block_statistics.take(), other.block_statistics) {
+            (Some(statistics), Some(other)) => {
+                let sum = |a: Option<usize>, b: Option<usize>| Some(a? + b?);
+                Some(BlockStatistics {
+                    processed_messages: sum(
+                        statistics.processed_messages,
+                        other.processed_messages,
+                    ),
+                    qr_data_items: sum(statistics.qr_data_items, other.qr_data_items),
+                    unmatched_queries: sum(statistics.unmatched_queries, other.unmatched_queries),
+                    unmatched_responses: sum(
+                        statistics.unmatched_responses,
+                        other.unmatched_responses,
+                    ),
+                    discarded_opcode: sum(statistics.discarded_opcode, other.discarded_opcode),
+                    malformed_items: sum(statistics.malformed_items, other.malformed_items),
+                    extra_values: statistics.extra_values,
+                })
+            }
+            _ => None,
+        };
+
+        let remapping = match other.block_tables {
+            Some(block_tables) => Some(
+                self.block_tables
+                    .get_or_insert_with(Default::default)
+                    .append(block_tables),
+            ),
+            None => None,
+        };
+        for mut query_response in other.query_responses.into_iter().flatten() {
+            if let Some(remapping) = &remapping {
+                remapping.remap_query_response(&mut query_response);
+            }
+            self.query_responses
+                .get_or_insert_with(Vec::new)
+                .push(query_response);
+        }
+        for mut address_event_count in other.address_event_counts.into_iter().flatten() {
+            if let Some(remapping) = &remapping {
+                remapping.remap_address_event_count(&mut address_event_count);
+            }
+            self.address_event_counts
+                .get_or_insert_with(Vec::new)
+                .push(address_event_count);
+        }
+        for mut malformed_message in other.malformed_messages.into_iter().flatten() {
+            if let Some(remapping) = &remapping {
+                remapping.remap_malformed_message(&mut malformed_message);
+            }
+            self.malformed_messages
+                .get_or_insert_with(Vec::new)
+                .push(malformed_message);
+        }
+        Ok(self)
+    }
+}
+
+pub(crate) fn query_response_count(block: &Block) -> usize {
+    block.query_responses.as_ref().map_or(0, Vec::len)
+}
+
+pub(crate) fn empty_block() -> Block {
+    Block {
+        block_preamble: BlockPreamble {
+            earliest_time: None,
+            block_parameters_index: None,
+            extra_values: ExtraValues::new(),
+        },
+        block_statistics: None,
+        block_tables: None,
+        query_responses: None,
+        address_event_counts: None,
+        malformed_messages: None,
+        extra_values: ExtraValues::new(),
+    }
+}
+
+/// The `earliest_time` of the merged block and how many ticks the time offsets of `block` and `other` move.
+///
+/// Fails if a time offset does not fit after moving.
+pub(crate) fn merged_time(
+    block: &Block,
+    other: &Block,
+    block_parameters: &BlockParameters,
+) -> Result<(Option<Timestamp>, i128, i128)> {
+    let ticks_per_second = i128::from(u32::from(
+        block_parameters.storage_parameters.ticks_per_second,
+    ));
+    let earliest_time = [
+        block.block_preamble.earliest_time,
+        other.block_preamble.earliest_time,
+    ]
+    .into_iter()
+    .flatten()
+    .min_by_key(|time| ticks(*time, ticks_per_second));
+    let shift = |block: &Block| -> Result<i128> {
+        let shift = match (block.block_preamble.earliest_time, earliest_time) {
+            (Some(time), Some(earliest_time)) => {
+                ticks(time, ticks_per_second) - ticks(earliest_time, ticks_per_second)
+            }
+            _ => 0,
+        };
+        for offset in time_offsets(block) {
+            if i128::from(u32::from(offset)) + shift > i128::from(u32::MAX) {
+                bail!("The blocks span too much time to be merged");
+            }
+        }
+        Ok(shift)
+    };
+    Ok((earliest_time, shift(block)?, shift(other)?))
+}
+
+pub(crate) fn time_offsets(block: &Block) -> impl Iterator<Item = UTicks> + '_ {
+    block
+        .query_responses
+        .iter()
+        .flatten()
+        .map(|qr| qr.time_offset)
+        .chain(
+            block
+                .malformed_messages
+                .iter()
+                .flatten()
+                .map(|mm| mm.time_offset),
+        )
+        .flatten()
+}
+
+/// Move all time offsets of `block` by `shift` ticks, which must fit as checked by [`merged_time`].
+pub(crate) fn shift_time_offsets(block: &mut Block, shift: i128) {
+    if shift == 0 {
+        return;
+    }
+    let offsets = block
+        .query_responses
+        .iter_mut()
+        .flatten()
+        .map(|qr| &mut qr.time_offset)
+        .chain(
+            block
+                .malformed_messages
+                .iter_mut()
+                .flatten()
+                .map(|mm| &mut mm.time_offset),
+        );
+    for offset in offsets.flatten() {
+        *offset = UTicks::from((i128::from(u32::from(*offset)) + shift) as u32);
+    }
+}
+
+/// Append `blocks` to `result`, merging their `block_parameters` into the existing ones.
+fn append_file(
+    result: &mut File,
+    block_parameters: Vec<BlockParameters>,
+    blocks: Vec<Block>,
+    file_idx: usize,
+) -> Result<()> {
+    let existing = &mut result.file_preamble.block_parameters;
+    let mapping: Vec<usize> = block_parameters
+        .into_iter()
+        .map(
+            |params| match existing.iter().position(|other| *other == params) {
+                Some(idx) => idx,
+                None => {
+                    existing.push(params);
+                    existing.len() - 1
+                }
+            },
+        )
+        .collect();
+
+    for (block_idx, mut block) in blocks.into_iter().enumerate() {
+        let index = block.block_preamble.block_parameters_index;
+        let new_index = match mapping.get(index.unwrap_or(0)) {
+            Some(new_index) => *new_index,
+            None => bail!(
+                "Block {} of file {} references the non-existing BlockParameters {}",
+                block_idx,
+                file_idx,
+                index.unwrap_or(0)
+            ),
+        };
+        // Keep the default index implicit
+        block.block_preamble.block_parameters_index =
+            (index.is_some() || new_index != 0).then_some(new_index);
+        result.file_blocks.push(block);
+    }
+    Ok(())
+}
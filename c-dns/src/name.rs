@@ -0,0 +1,213 @@
+//! Decode domain names from the uncompressed wire format
+//!
+//! Names in the `name_rdata` table are stored in uncompressed wire format, as a sequence of length-prefixed labels ending in the root label.
+//! [`NameOrRdata::to_domain_name`] checks the label structure and returns a [`DomainName`], which renders in presentation format.
+//! Bytes which have a special meaning in presentation format or are not printable ASCII are escaped as described in RFC 4343, so every name has an unambiguous string representation.
+//!
+//! ```
+//! # use c_dns::serialization::NameOrRdata;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let name = NameOrRdata::from(bytes::Bytes::from_static(b"\x04a.b;\x07example\x00"));
+//! let domain = name.to_domain_name()?;
+//! assert_eq!(2, domain.labels().count());
+//! assert_eq!(r"a\.b\;.example.", domain.to_string());
+//!
+//! // Malformed names can still be displayed
+//! let truncated = NameOrRdata::from(bytes::Bytes::from_static(b"\x07exam"));
+//! assert!(truncated.to_domain_name().is_err());
+//! assert_eq!(r"\007exam", truncated.to_string_lossy());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::bail;
+use crate::serialization::NameOrRdata;
+use crate::Result;
+use std::fmt::{self, Write};
+
+/// Maximal length of a name in wire format, RFC 1035
+const MAX_NAME_LEN: usize = 255;
+/// Maximal length of a label, RFC 1035
+const MAX_LABEL_LEN: usize = 63;
+
+/// A domain name with a valid label structure
+///
+/// The [`Display`](fmt::Display) implementation renders the name in presentation format with a trailing dot.
+/// The root name renders as a single dot.
+/// Comparisons are byte-wise and therefore case-sensitive.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DomainName(Vec<u8>);
+
+impl DomainName {
+    /// Decode a name in uncompressed wire format.
+    ///
+    /// Fails if a label is longer than 63 bytes or exceeds the input, if the name is longer than 255 bytes, or if the input does not end with exactly one root label.
+    /// Compression pointers are not allowed.
+    pub fn from_wire(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() > MAX_NAME_LEN {
+            bail!(
+                "The name is {} bytes long, but at most {} bytes are allowed",
+                bytes.len(),
+                MAX_NAME_LEN
+            );
+        }
+        let mut pos = 0;
+        loop {
+            let len = match bytes.get(pos) {
+                Some(&len) => usize::from(len),
+                None => bail!("The name does not end with the root label"),
+            };
+            pos += 1;
+            if len == 0 {
+                if pos != bytes.len() {
+                    bail!(
+                        "The name has {} trailing bytes after the root label",
+                        bytes.len() - pos
+                    );
+                }
+                return Ok(Self(bytes.to_vec()));
+            }
+            if len > MAX_LABEL_LEN {
+                bail!(
+                    "Invalid label length {} at offset {}, expected at most {}",
+                    len,
+                    pos - 1,
+                    MAX_LABEL_LEN
+                );
+            }
+            if pos + len > bytes.len() {
+                bail!("The label at offset {} exceeds the name", pos - 1);
+            }
+            pos += len;
+        }
+    }
+
+    /// Build a name from its labels, from the leftmost to the rightmost.
+    ///
+    /// Fails if a label is empty or longer than 63 bytes, or if the name is longer than 255 bytes.
+    pub fn from_labels<L: AsRef<[u8]>>(labels: impl IntoIterator<Item = L>) -> Result<Self> {
+        let mut wire = Vec::new();
+        for label in labels {
+            let label = label.as_ref();
+            if label.is_empty() || label.len() > MAX_LABEL_LEN {
+                bail!(
+                    "Invalid label length {}, expected 1 to {}",
+                    label.len(),
+                    MAX_LABEL_LEN
+                );
+            }
+            wire.push(label.len() as u8);
+            wire.extend_from_slice(label);
+        }
+        wire.push(0);
+        if wire.len() > MAX_NAME_LEN {
+            bail!(
+                "The name is {} bytes long, but at most {} bytes are allowed",
+                wire.len(),
+                MAX_NAME_LEN
+            );
+        }
+        Ok(Self(wire))
+    }
+
+    /// The root name `.`
+    pub fn root() -> Self {
+        Self(vec![0])
+    }
+
+    /// Whether this is the root name.
+    pub fn is_root(&self) -> bool {
+        self.0 == [0]
+    }
+
+    /// The labels from the leftmost to the rightmost, excluding the root label.
+    pub fn labels(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        let mut rest = &self.0[..];
+        std::iter::from_fn(move || {
+            let (&len, tail) = rest.split_first()?;
+            if len == 0 {
+                return None;
+            }
+            let (label, tail) = tail.split_at(usize::from(len));
+            rest = tail;
+            Some(label)
+        })
+    }
+
+    /// The name in uncompressed wire format.
+    pub fn as_wire(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for DomainName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("DomainName({:?})", self.to_string()))
+    }
+}
+
+impl fmt::Display for DomainName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_root() {
+            return f.write_char('.');
+        }
+        for label in self.labels() {
+            write_escaped(f, label)?;
+            f.write_char('.')?;
+        }
+        Ok(())
+    }
+}
+
+impl From<DomainName> for NameOrRdata {
+    fn from(name: DomainName) -> Self {
+        NameOrRdata::from(bytes::Bytes::from(name.0))
+    }
+}
+
+impl NameOrRdata {
+    /// Decode the bytes as a domain name in uncompressed wire format, see [`DomainName::from_wire`].
+    pub fn to_domain_name(&self) -> Result<DomainName> {
+        DomainName::from_wire(self.as_bytes())
+    }
+
+    /// Render the bytes as a domain name, even if they are malformed.
+    ///
+    /// Valid names are rendered like [`DomainName`].
+    /// Otherwise, the labels are rendered up to the first malformed one, and all remaining bytes are rendered escaped as a final label without a trailing dot.
+    pub fn to_string_lossy(&self) -> String {
+        if let Ok(name) = self.to_domain_name() {
+            return name.to_string();
+        }
+        let bytes = self.as_bytes();
+        let mut res = String::with_capacity(bytes.len() * 2);
+        let mut pos = 0;
+        while let Some(&len) = bytes.get(pos) {
+            let len = usize::from(len);
+            if len == 0 || len > MAX_LABEL_LEN || pos + 1 + len > bytes.len() {
+                break;
+            }
+            // Writing into a String never fails
+            let _ = write_escaped(&mut res, &bytes[pos + 1..][..len]);
+            res.push('.');
+            pos += 1 + len;
+        }
+        let _ = write_escaped(&mut res, &bytes[pos..]);
+        res
+    }
+}
+
+/// Write a label in presentation format, escaping special and non-printable bytes.
+fn write_escaped(w: &mut impl Write, label: &[u8]) -> fmt::Result {
+    for &byte in label {
+        match byte {
+            b'.' | b';' | b'\\' | b'"' | b'(' | b')' | b'@' | b'$' => {
+                w.write_char('\\')?;
+                w.write_char(char::from(byte))?;
+            }
+            0x21..=0x7e => w.write_char(char::from(byte))?,
+            _ => write!(w, "\\{:03}", byte)?,
+        }
+    }
+    Ok(())
+}
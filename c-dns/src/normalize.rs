@@ -0,0 +1,149 @@
+//! Convert names to a uniform case
+//!
+//! DNS names are case-insensitive, but their case is preserved on the wire.
+//! Clients using random capitalization, like DNS 0x20 encoding, lead to many entries in the [`BlockTables.name_rdata`] which only differ in case.
+//! [`File::normalize_names`] converts all names to lowercase and merges entries which become identical.
+//! The RDATA is not changed, even if it contains names.
+//!
+//! ```
+//! # use c_dns::serialization::{File, StorageFlags};
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let mut file = File::read_path("./tests/data/dns.cdns")?;
+//! file.normalize_names();
+//! let storage_flags = file.file_preamble.block_parameters[0].storage_parameters.storage_flags;
+//! assert!(storage_flags.unwrap().contains(StorageFlags::NormalizedNames));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::serialization::*;
+use crate::tables::{visit_name_rdata_indices, NameRdataUse};
+use std::collections::HashMap;
+
+impl NameOrRdata {
+    /// The name with all ASCII letters converted to lowercase.
+    ///
+    /// The length bytes of the labels are smaller than any letter, so they are never changed.
+    pub fn to_lowercase(&self) -> NameOrRdata {
+        NameOrRdata::from(bytes::Bytes::from(self.as_bytes().to_ascii_lowercase()))
+    }
+}
+
+impl File {
+    /// Convert all names in all blocks to lowercase, see [`Block::normalize_names`].
+    ///
+    /// This sets [`StorageFlags::NormalizedNames`] in all [`StorageParameters`].
+    /// Returns the number of removed [`BlockTables.name_rdata`] entries.
+    ///
+    /// # Panics
+    ///
+    /// The indices in all blocks must be valid, as checked by [`File::dangling_indices`].
+    pub fn normalize_names(&mut self) -> usize {
+        for block_parameters in &mut self.file_preamble.block_parameters {
+            block_parameters
+                .storage_parameters
+                .storage_flags
+                .get_or_insert_with(Default::default)
+                .insert(StorageFlags::NormalizedNames);
+        }
+        self.file_blocks
+            .iter_mut()
+            .map(Block::normalize_names)
+            .sum()
+    }
+}
+
+impl Block {
+    /// Convert all QNAMEs, bailiwicks, and RR NAMEs to lowercase.
+    ///
+    /// Entries of the [`BlockTables.name_rdata`] which become identical are merged.
+    /// Entries which are also used as RDATA are kept unchanged for the RDATA, and a lowercase copy is added for the names.
+    /// Returns the number of removed [`BlockTables.name_rdata`] entries.
+    ///
+    /// This does not change the [`StorageFlags`], use [`File::normalize_names`] to also set [`StorageFlags::NormalizedNames`].
+    ///
+    /// # Panics
+    ///
+    /// The indices in the block must be valid, as checked by [`File::dangling_indices`].
+    pub fn normalize_names(&mut self) -> usize {
+        let table_len = match self
+            .block_tables
+            .as_ref()
+            .and_then(|tables| tables.name_rdata.as_ref())
+        {
+            Some(name_rdata) => name_rdata.len(),
+            None => return 0,
+        };
+
+        let mut used_before = vec![false; table_len];
+        let mut used_as_rdata = vec![false; table_len];
+        visit_name_rdata_indices(self, &mut |index, usage| {
+            used_before[*index] = true;
+            if matches!(usage, NameRdataUse::Rdata(_)) {
+                used_as_rdata[*index] = true;
+            }
+        });
+
+        // Find the lowercase entry for each name, replacing entries only used as names
+        let mut name_rdata = self
+            .block_tables
+            .as_mut()
+            .and_then(|tables| tables.name_rdata.take())
+            .unwrap_or_default();
+        let mut positions: HashMap<NameOrRdata, usize> = HashMap::new();
+        for (idx, entry) in name_rdata.iter().enumerate() {
+            positions.entry(entry.clone()).or_insert(idx);
+        }
+        let mut lowercase_index: Vec<Option<usize>> = vec![None; table_len];
+        visit_name_rdata_indices(self, &mut |index, usage| {
+            if usage != NameRdataUse::Name {
+                return;
+            }
+            if let Some(new_index) = lowercase_index[*index] {
+                *index = new_index;
+                return;
+            }
+            let lowercase = name_rdata[*index].to_lowercase();
+            let new_index = match positions.get(&lowercase) {
+                Some(position) => *position,
+                None if !used_as_rdata[*index] => {
+                    name_rdata[*index] = lowercase.clone();
+                    positions.insert(lowercase, *index);
+                    *index
+                }
+                None => {
+                    name_rdata.push(lowercase.clone());
+                    positions.insert(lowercase, name_rdata.len() - 1);
+                    name_rdata.len() - 1
+                }
+            };
+            lowercase_index[*index] = Some(new_index);
+            *index = new_index;
+        });
+
+        // Remove the entries which are no longer used
+        // Entries which were not used before are kept, since they are not names.
+        let mut used_after = vec![false; name_rdata.len()];
+        visit_name_rdata_indices(self, &mut |index, _| used_after[*index] = true);
+        let keep: Vec<bool> = used_after
+            .iter()
+            .enumerate()
+            .map(|(idx, used)| *used || !used_before.get(idx).copied().unwrap_or(false))
+            .collect();
+        let mut new_positions = Vec::with_capacity(keep.len());
+        let mut kept = 0;
+        for keep in &keep {
+            new_positions.push(kept);
+            kept += usize::from(*keep);
+        }
+        let removed = name_rdata.len() - kept;
+        let mut keep = keep.into_iter();
+        name_rdata.retain(|_| keep.next().unwrap_or(true));
+        visit_name_rdata_indices(self, &mut |index, _| *index = new_positions[*index]);
+
+        if let Some(tables) = &mut self.block_tables {
+            tables.name_rdata = Some(name_rdata);
+        }
+        removed
+    }
+}
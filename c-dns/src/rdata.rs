@@ -0,0 +1,250 @@
+//! Parse RDATA into typed values
+//!
+//! The `name_rdata` table stores the RDATA of resource records as raw bytes, with all names uncompressed.
+//! [`NameOrRdata::parse_rdata`] interprets these bytes according to the TYPE of the record and returns an [`Rdata`].
+//! TYPEs without a dedicated variant are returned as [`Rdata::Unknown`].
+//!
+//! ```
+//! # use c_dns::rdata::Rdata;
+//! # use c_dns::serialization::{DnsType, NameOrRdata};
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let rdata = NameOrRdata::from(bytes::Bytes::from_static(b"\x00\x0a\x04mail\x07example\x00"));
+//! match rdata.parse_rdata(DnsType::MX)? {
+//!     Rdata::Mx { preference, exchange } => {
+//!         assert_eq!(10, preference);
+//!         assert_eq!("mail.example.", exchange.to_string());
+//!     }
+//!     _ => unreachable!(),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::bail;
+use crate::name::DomainName;
+use crate::serialization::{DnsType, NameOrRdata};
+use crate::wire::EdnsOption;
+use crate::Result;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// RDATA interpreted according to the TYPE of the record
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Rdata {
+    /// IPv4 host address, RFC 1035
+    A(Ipv4Addr),
+    /// IPv6 host address, RFC 3596
+    Aaaa(Ipv6Addr),
+    /// Authoritative name server, RFC 1035
+    Ns(DomainName),
+    /// Canonical name for an alias, RFC 1035
+    Cname(DomainName),
+    /// Domain name pointer, RFC 1035
+    Ptr(DomainName),
+    /// Delegation name, RFC 6672
+    Dname(DomainName),
+    /// Mail exchange, RFC 1035
+    Mx {
+        preference: u16,
+        exchange: DomainName,
+    },
+    /// Character strings, RFC 1035
+    Txt(Vec<Vec<u8>>),
+    /// Start of a zone of authority, RFC 1035
+    Soa {
+        mname: DomainName,
+        rname: DomainName,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+    },
+    /// Server selection, RFC 2782
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: DomainName,
+    },
+    /// Options of the EDNS pseudo-RR, RFC 6891
+    Opt(Vec<EdnsOption>),
+    /// Delegation signer, RFC 4034
+    Ds {
+        key_tag: u16,
+        algorithm: u8,
+        digest_type: u8,
+        digest: Vec<u8>,
+    },
+    /// DNS key, RFC 4034
+    Dnskey {
+        flags: u16,
+        protocol: u8,
+        algorithm: u8,
+        public_key: Vec<u8>,
+    },
+    /// Certification authority authorization, RFC 8659
+    Caa {
+        flags: u8,
+        tag: Vec<u8>,
+        value: Vec<u8>,
+    },
+    /// RDATA of a TYPE without a dedicated variant
+    Unknown { type_: DnsType, data: Vec<u8> },
+}
+
+impl NameOrRdata {
+    /// Parse the bytes as the RDATA of a record of TYPE `type_`.
+    ///
+    /// Fails if the RDATA is malformed for the TYPE, for example if it is truncated, has trailing bytes, or contains an invalid name.
+    pub fn parse_rdata(&self, type_: DnsType) -> Result<Rdata> {
+        let mut reader = Reader {
+            bytes: self.as_bytes(),
+            pos: 0,
+        };
+        let rdata = match type_ {
+            DnsType::A => Rdata::A(Ipv4Addr::from(reader.array::<4>()?)),
+            DnsType::AAAA => Rdata::Aaaa(Ipv6Addr::from(reader.array::<16>()?)),
+            DnsType::NS => Rdata::Ns(reader.name()?),
+            DnsType::CNAME => Rdata::Cname(reader.name()?),
+            DnsType::PTR => Rdata::Ptr(reader.name()?),
+            DnsType::DNAME => Rdata::Dname(reader.name()?),
+            DnsType::MX => Rdata::Mx {
+                preference: reader.u16()?,
+                exchange: reader.name()?,
+            },
+            DnsType::TXT => {
+                let mut strings = Vec::new();
+                while !reader.is_empty() {
+                    let len = reader.u8()?;
+                    strings.push(reader.take(usize::from(len))?.to_vec());
+                }
+                Rdata::Txt(strings)
+            }
+            DnsType::SOA => Rdata::Soa {
+                mname: reader.name()?,
+                rname: reader.name()?,
+                serial: reader.u32()?,
+                refresh: reader.u32()?,
+                retry: reader.u32()?,
+                expire: reader.u32()?,
+                minimum: reader.u32()?,
+            },
+            DnsType::SRV => Rdata::Srv {
+                priority: reader.u16()?,
+                weight: reader.u16()?,
+                port: reader.u16()?,
+                target: reader.name()?,
+            },
+            DnsType::OPT => {
+                let mut options = Vec::new();
+                while !reader.is_empty() {
+                    let code = reader.u16()?;
+                    let len = reader.u16()?;
+                    options.push(EdnsOption {
+                        code,
+                        data: reader.take(usize::from(len))?.to_vec(),
+                    });
+                }
+                Rdata::Opt(options)
+            }
+            DnsType::DS | DnsType::CDS => Rdata::Ds {
+                key_tag: reader.u16()?,
+                algorithm: reader.u8()?,
+                digest_type: reader.u8()?,
+                digest: reader.rest().to_vec(),
+            },
+            DnsType::DNSKEY | DnsType::CDNSKEY => Rdata::Dnskey {
+                flags: reader.u16()?,
+                protocol: reader.u8()?,
+                algorithm: reader.u8()?,
+                public_key: reader.rest().to_vec(),
+            },
+            DnsType::CAA => {
+                let flags = reader.u8()?;
+                let tag_len = reader.u8()?;
+                Rdata::Caa {
+                    flags,
+                    tag: reader.take(usize::from(tag_len))?.to_vec(),
+                    value: reader.rest().to_vec(),
+                }
+            }
+            _ => Rdata::Unknown {
+                type_,
+                data: reader.rest().to_vec(),
+            },
+        };
+        if !reader.is_empty() {
+            bail!(
+                "The {} RDATA has {} trailing bytes",
+                type_,
+                reader.bytes.len() - reader.pos
+            );
+        }
+        Ok(rdata)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        match self.bytes.get(self.pos..self.pos + len) {
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            }
+            None => bail!(
+                "The RDATA is truncated, expected {} more bytes at offset {}",
+                self.pos + len - self.bytes.len(),
+                self.pos
+            ),
+        }
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.bytes[self.pos..];
+        self.pos = self.bytes.len();
+        rest
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        // `take` returns exactly N bytes
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    /// Read an uncompressed name up to and including the root label.
+    fn name(&mut self) -> Result<DomainName> {
+        let start = self.pos;
+        loop {
+            let len = self.u8()?;
+            if len == 0 {
+                break;
+            }
+            if len > 63 {
+                bail!("Invalid label length {} in a name in the RDATA", len);
+            }
+            self.take(usize::from(len))?;
+        }
+        DomainName::from_wire(&self.bytes[start..self.pos])
+    }
+}
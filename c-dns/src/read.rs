@@ -0,0 +1,550 @@
+//! Read C-DNS files from raw bytes
+//!
+//! The plain serde functions like [`serde_cbor::from_slice`] fail on any bytes following the [`File`].
+//! Appended junk and concatenated files are common in practice, so the functions here report such bytes as [`TrailingData`] instead.
+//! Similarly, files cut off by an interrupted collector can be salvaged up to the last complete block, see [`File::from_slice_salvage`].
+//!
+//! [`File::from_slice`], [`File::from_reader`], and [`File::read_path`] read complete files.
+//! Their errors contain the position of the value which failed to deserialize, like `[2][3]` for the fourth block.
+//! All readers keep the indices as stored, files of producers writing indices starting at 1 can be rewritten with [`File::normalize_index_base`].
+//! Files too large to keep in memory can be read one block at a time with a [`FileReader`].
+//! Only [`File::from_slice_with_limits`], [`LazyFile::from_slice_with_limits`](crate::lazy::LazyFile::from_slice_with_limits), and [`FileReader::with_limits`] bound the resources used for untrusted input, see [`Limits`].
+//!
+//! ```
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let file = File::read_path("./tests/data/dns.cdns")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::cbor::{
+    array_head, read_array_head, read_content, read_head, skip_item, Elements, Head, BREAK,
+};
+use crate::compression::{decompress, Compression};
+use crate::error::{bail, Context};
+use crate::fast::try_decode_block;
+use crate::intern::Interner;
+use crate::limits::Limits;
+use crate::serialization::{check_header, Block, BlockParameters, File, FilePreamble};
+use crate::{Error, Result};
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::io::Read;
+use std::path::Path;
+
+/// Bytes following the [`File`] data item in the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrailingData<'a> {
+    /// Offset of the first trailing byte in the input.
+    pub offset: usize,
+    /// The trailing bytes.
+    pub bytes: &'a [u8],
+}
+
+impl TrailingData<'_> {
+    /// Number of trailing bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Whether there are no trailing bytes.
+    ///
+    /// Trailing data returned by [`File::from_slice_allow_trailing`] is never empty.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+/// Location where a [`File`] is cut off or corrupted.
+#[derive(Debug)]
+pub struct Truncation {
+    /// Offset of the first byte of the partial block.
+    pub offset: usize,
+    /// Index of the partial block, which is also the number of complete blocks.
+    pub block_index: usize,
+    /// The error encountered while reading the partial block.
+    pub error: serde_cbor::Error,
+}
+
+impl File {
+    /// Deserialize a [`File`] from `bytes`.
+    ///
+    /// Fails if any bytes follow the [`File`], see [`File::from_slice_allow_trailing`] to read those.
+    pub fn from_slice(bytes: &[u8]) -> Result<File> {
+        let mut deserializer = serde_cbor::Deserializer::from_slice(bytes);
+        let file = deserialize_with_path(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(file)
+    }
+
+    /// Deserialize a [`File`] from `reader`.
+    ///
+    /// Fails if any bytes follow the [`File`].
+    /// The reader is not buffered, so wrap it in a [`BufReader`](std::io::BufReader) if necessary.
+    pub fn from_reader(reader: impl Read) -> Result<File> {
+        let mut deserializer = serde_cbor::Deserializer::from_reader(reader);
+        let file = deserialize_with_path(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(file)
+    }
+
+    /// Read and deserialize the [`File`] stored at `path`.
+    ///
+    /// Compressed files are decompressed transparently, see [`compression`](crate::compression).
+    /// Fails if any bytes follow the [`File`].
+    pub fn read_path(path: impl AsRef<Path>) -> Result<File> {
+        let path = path.as_ref();
+        let mut bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read file {}", path.display()))?;
+        if Compression::detect(&bytes) != Compression::None {
+            let mut decompressed = Vec::new();
+            decompress(&*bytes)?
+                .read_to_end(&mut decompressed)
+                .with_context(|| format!("Failed to decompress file {}", path.display()))?;
+            bytes = decompressed;
+        }
+        File::from_slice(&bytes)
+            .with_context(|| format!("Failed to read C-DNS file {}", path.display()))
+    }
+
+    /// Deserialize a [`File`] from the start of `bytes`, returning any bytes following it.
+    ///
+    /// The bytes after the [`File`] data item are returned as [`TrailingData`], if there are any.
+    /// They might be another concatenated C-DNS file, which can be read by calling this function again on [`TrailingData::bytes`].
+    pub fn from_slice_allow_trailing(bytes: &[u8]) -> Result<(File, Option<TrailingData<'_>>)> {
+        let mut deserializer = serde_cbor::Deserializer::from_slice(bytes);
+        let file = File::deserialize(&mut deserializer)?;
+        let offset = deserializer.byte_offset();
+        let trailing = if offset < bytes.len() {
+            Some(TrailingData {
+                offset,
+                bytes: &bytes[offset..],
+            })
+        } else {
+            None
+        };
+        Ok((file, trailing))
+    }
+
+    /// Deserialize a [`File`] from `bytes`, keeping all complete blocks if the input is cut off.
+    ///
+    /// Collectors interrupted while writing leave files which end in the middle of a block.
+    /// Instead of failing, this function returns all blocks before the partial block together with a [`Truncation`] describing where the data ends.
+    /// A corrupted block is handled the same way, as it cannot be distinguished from a cut off one.
+    ///
+    /// Inputs without a complete file type id and [`FilePreamble`] cannot be salvaged and return an error.
+    pub fn from_slice_salvage(bytes: &[u8]) -> Result<(File, Option<Truncation>)> {
+        let mut pos = 0;
+        check_file_len(array_head(bytes, &mut pos)?)?;
+        let file_type_id = deserialize_at::<String>(bytes, &mut pos)?;
+        let file_preamble = deserialize_at::<FilePreamble>(bytes, &mut pos)?;
+        check_header(&file_type_id, &file_preamble)?;
+        let mut file = File {
+            file_type_id,
+            file_preamble,
+            file_blocks: Vec::new(),
+        };
+
+        let mut blocks = Elements::new(array_head(bytes, &mut pos)?);
+        while blocks.next(bytes, &mut pos) {
+            let offset = pos;
+            match deserialize_at::<Block>(bytes, &mut pos) {
+                Ok(block) => file.file_blocks.push(block),
+                Err(error) => {
+                    let truncation = Truncation {
+                        offset,
+                        block_index: file.file_blocks.len(),
+                        error,
+                    };
+                    return Ok((file, Some(truncation)));
+                }
+            }
+        }
+        Ok((file, None))
+    }
+
+    /// Deserialize a [`File`] from `bytes`, using a specialized decoder for the large block tables.
+    ///
+    /// This produces the same [`File`] as [`File::from_slice`], but is considerably faster for files with many names, addresses, and Q/R signatures.
+    /// Like [`File::from_slice`], it fails if any bytes follow the [`File`].
+    /// No [`Limits`] are checked, so untrusted input needs to pass [`Limits::check`] first.
+    pub fn from_slice_fast(bytes: &[u8]) -> Result<File> {
+        decode_fast(bytes, None)
+    }
+
+    /// Deserialize a [`File`] from `bytes`, decoding the blocks in parallel.
+    ///
+    /// The blocks are decoded on the global rayon thread pool, using the same decoder as [`File::from_slice_fast`].
+    /// Like [`File::from_slice`], it fails if any bytes follow the [`File`].
+    /// No [`Limits`] are checked, so untrusted input needs to pass [`Limits::check`] first.
+    #[cfg(feature = "rayon")]
+    pub fn from_slice_parallel(bytes: &[u8]) -> Result<File> {
+        use rayon::prelude::*;
+
+        let (file_type_id, file_preamble, raw_blocks, end) = split_blocks(bytes)?;
+        check_end(bytes, end)?;
+        let file_blocks = raw_blocks
+            .into_par_iter()
+            .enumerate()
+            .map(|(block_index, raw)| decode_block_with_path(raw, block_index, None))
+            .collect::<Result<_>>()?;
+        Ok(File {
+            file_type_id,
+            file_preamble,
+            file_blocks,
+        })
+    }
+}
+
+/// Read a [`File`] one [`Block`] at a time.
+///
+/// The file type id and the [`FilePreamble`] are read when creating the reader.
+/// Afterwards, the reader yields the blocks in order, and only the block being decoded is kept in memory.
+/// Reading stops after the first error.
+///
+/// The reader keeps the raw bytes of the largest block so far and the byte strings of its [`Interner`] alive between blocks.
+/// [`FileReader::retained_bytes`] estimates their size, and a budget set with [`FileReader::set_budget`] releases them whenever they exceed it.
+///
+/// ```
+/// # use c_dns::serialization::FileReader;
+/// # fn main() -> color_eyre::eyre::Result<()> {
+/// let input = std::fs::File::open("./tests/data/dns.cdns")?;
+/// let mut reader = FileReader::new(std::io::BufReader::new(input))?;
+/// let mut query_responses = 0;
+/// for block in &mut reader {
+///     query_responses += block?.query_responses.map_or(0, |qrs| qrs.len());
+/// }
+/// assert_eq!(12, query_responses);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FileReader<R> {
+    reader: R,
+    file_type_id: String,
+    file_preamble: FilePreamble,
+    /// Number of remaining blocks, [`None`] for an indefinite-length array
+    remaining: Option<u64>,
+    block_index: usize,
+    finished: bool,
+    /// Raw bytes of the current block, reused between blocks
+    buffer: Vec<u8>,
+    /// Deduplicates the names and addresses of each block right after decoding
+    interner: Option<Interner>,
+    budget: Option<usize>,
+    limits: Limits,
+}
+
+impl<R: Read> FileReader<R> {
+    /// Read the file type id and the [`FilePreamble`] from `reader`.
+    ///
+    /// The reader is not buffered, so wrap it in a [`BufReader`](std::io::BufReader) if necessary.
+    /// No [`Limits`] are checked, use [`FileReader::with_limits`] for untrusted input.
+    pub fn new(reader: R) -> Result<Self> {
+        Self::with_limits(reader, Limits::default())
+    }
+
+    /// Read the file type id and the [`FilePreamble`] from `reader`, checking the file against `limits`.
+    ///
+    /// The preamble and every block are checked before they are decoded.
+    /// The allocation limit applies to each of them separately, and also bounds the raw bytes read for a single block.
+    pub fn with_limits(mut reader: R, limits: Limits) -> Result<Self> {
+        let mut buffer = Vec::new();
+        check_file_len(read_array_head(&mut reader, &mut buffer)?)?;
+
+        buffer.clear();
+        read_limited_item(&mut reader, &mut buffer, &limits)?;
+        let file_type_id: String = deserialize_item_with_path(&buffer, "[0]")?;
+        buffer.clear();
+        read_limited_item(&mut reader, &mut buffer, &limits)?;
+        let file_preamble: FilePreamble = deserialize_item_with_path(&buffer, "[1]")?;
+        check_header(&file_type_id, &file_preamble)?;
+
+        buffer.clear();
+        let remaining = read_array_head(&mut reader, &mut buffer)?;
+        Ok(Self {
+            reader,
+            file_type_id,
+            file_preamble,
+            remaining,
+            block_index: 0,
+            finished: false,
+            buffer,
+            interner: None,
+            budget: None,
+            limits,
+        })
+    }
+
+    /// Deduplicate the names and addresses of all blocks with `interner`.
+    ///
+    /// Each block is interned directly after it is decoded, before it is returned.
+    /// The duplicates of a block are therefore released immediately, and at most one block holds copies of already interned byte strings.
+    /// `None` disables interning.
+    pub fn set_interner(&mut self, interner: Option<Interner>) {
+        self.interner = interner;
+    }
+
+    /// The interner used for the blocks, if any.
+    pub fn interner(&self) -> Option<&Interner> {
+        self.interner.as_ref()
+    }
+
+    /// Remove and return the interner, disabling interning for the remaining blocks.
+    pub fn take_interner(&mut self) -> Option<Interner> {
+        self.interner.take()
+    }
+
+    /// The memory budget of the reader in bytes, if any.
+    pub fn budget(&self) -> Option<usize> {
+        self.budget
+    }
+
+    /// Set the memory budget of the reader in bytes.
+    ///
+    /// After each block, the reader [releases](FileReader::release) its retained memory if it exceeds the budget.
+    /// The blocks returned by the reader are owned by the caller and are not counted.
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+    }
+
+    /// Estimated number of bytes kept alive by the reader between blocks.
+    ///
+    /// This is the buffer for the raw block bytes and the byte strings of the [interner](FileReader::set_interner).
+    pub fn retained_bytes(&self) -> usize {
+        self.buffer.capacity()
+            + self
+                .interner
+                .as_ref()
+                .map_or(0, |interner| interner.retained_bytes())
+    }
+
+    /// Release the raw block buffer and clear the interner.
+    ///
+    /// Returns the estimated number of released bytes.
+    /// Blocks read afterwards no longer share byte strings with the earlier blocks.
+    pub fn release(&mut self) -> usize {
+        let released = self.retained_bytes();
+        self.buffer = Vec::new();
+        if let Some(interner) = &mut self.interner {
+            interner.clear();
+        }
+        released
+    }
+
+    /// The file type id of the file.
+    pub fn file_type_id(&self) -> &str {
+        &self.file_type_id
+    }
+
+    /// The preamble of the file.
+    pub fn file_preamble(&self) -> &FilePreamble {
+        &self.file_preamble
+    }
+
+    /// The [`BlockParameters`] applicable to `block`.
+    pub fn block_parameters(&self, block: &Block) -> Option<&BlockParameters> {
+        self.file_preamble
+            .block_parameters
+            .get(block.block_preamble.block_parameters_index.unwrap_or(0))
+    }
+
+    /// Read the next block, or [`None`] after the last block.
+    pub fn next_block(&mut self) -> Result<Option<Block>> {
+        if self.finished {
+            return Ok(None);
+        }
+        let result = self.read_block();
+        if !matches!(result, Ok(Some(_))) {
+            self.finished = true;
+        }
+        result
+    }
+
+    fn read_block(&mut self) -> Result<Option<Block>> {
+        self.buffer.clear();
+        match &mut self.remaining {
+            Some(0) => return Ok(None),
+            Some(remaining) => *remaining -= 1,
+            None => {}
+        }
+        let block_index = self.block_index;
+        let head = read_head(&mut self.reader, &mut self.buffer)
+            .with_context(|| format!("Failed to read block {}", block_index))?;
+        if self.remaining.is_none() && self.buffer == [BREAK] {
+            return Ok(None);
+        }
+        if block_index >= self.limits.max_blocks {
+            bail!("More than {} blocks", self.limits.max_blocks);
+        }
+        read_limited_content(&mut self.reader, &mut self.buffer, head, &self.limits)
+            .with_context(|| format!("Failed to read block {}", block_index))?;
+        let mut block = decode_block_with_path(&self.buffer, block_index, None)?;
+        if let Some(interner) = &mut self.interner {
+            interner.intern_block(&mut block);
+        }
+        if self
+            .budget
+            .is_some_and(|budget| self.retained_bytes() > budget)
+        {
+            self.release();
+        }
+        self.block_index += 1;
+        Ok(Some(block))
+    }
+
+    /// Return the underlying reader, positioned after the last read block.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for FileReader<R> {
+    type Item = Result<Block>;
+
+    fn next(&mut self) -> Option<Result<Block>> {
+        self.next_block().transpose()
+    }
+}
+
+/// Copy one complete data item from `reader` to `buffer` and check it against `limits`.
+fn read_limited_item(reader: &mut impl Read, buffer: &mut Vec<u8>, limits: &Limits) -> Result<()> {
+    let head = read_head(reader, buffer)?;
+    read_limited_content(reader, buffer, head, limits)
+}
+
+/// Copy the content following the `head` of a data item from `reader` to `buffer` and check it against `limits`.
+///
+/// Every data item takes at least as much memory when decoded as its encoding, so at most [`Limits::max_allocation`] bytes are read.
+fn read_limited_content(
+    reader: &mut impl Read,
+    buffer: &mut Vec<u8>,
+    head: Head,
+    limits: &Limits,
+) -> Result<()> {
+    let max_allocation = limits.max_allocation as u64;
+    let result = read_content(
+        &mut reader.take(max_allocation.saturating_add(1)),
+        buffer,
+        head,
+    );
+    if buffer.len() as u64 > max_allocation {
+        bail!("Decoding needs more than {} bytes", limits.max_allocation);
+    }
+    result?;
+    limits.check_item(buffer)
+}
+
+/// Check the length of the array holding the [`File`], which must have exactly 3 elements.
+///
+/// The length of indefinite-length arrays is only known at their end, so [`None`] is accepted.
+pub(crate) fn check_file_len(len: Option<u64>) -> Result<()> {
+    if len.is_some_and(|len| len != 3) {
+        bail!("The File array must have 3 elements");
+    }
+    Ok(())
+}
+
+/// Deserialize a [`File`], adding the path of the failing field to errors.
+fn deserialize_with_path<'de, R: serde_cbor::de::Read<'de>>(
+    deserializer: &mut serde_cbor::Deserializer<R>,
+) -> Result<File> {
+    serde_path_to_error::deserialize(deserializer).map_err(|error| {
+        let path = error.path().to_string();
+        Error::Context {
+            context: format!("Failed to deserialize {}", path),
+            source: Box::new(error.into_inner().into()),
+        }
+    })
+}
+
+/// Split a [`File`] into the decoded file type id and preamble, the raw bytes of each block, and the offset following the [`File`].
+///
+/// The blocks are only validated to be well-formed CBOR.
+pub(crate) fn split_blocks(bytes: &[u8]) -> Result<(String, FilePreamble, Vec<&[u8]>, usize)> {
+    let mut pos = 0;
+    check_file_len(array_head(bytes, &mut pos)?)?;
+    let file_type_id = deserialize_at::<String>(bytes, &mut pos)?;
+    let file_preamble = deserialize_at::<FilePreamble>(bytes, &mut pos)?;
+    check_header(&file_type_id, &file_preamble)?;
+
+    let mut blocks = Vec::new();
+    let mut elements = Elements::new(array_head(bytes, &mut pos)?);
+    while elements.next(bytes, &mut pos) {
+        let start = pos;
+        skip_item(bytes, &mut pos)?;
+        blocks.push(&bytes[start..pos]);
+    }
+    Ok((file_type_id, file_preamble, blocks, pos))
+}
+
+/// Fail if any bytes follow the [`File`] ending at `end`, like [`File::from_slice`].
+fn check_end(bytes: &[u8], end: usize) -> Result<()> {
+    if end < bytes.len() {
+        bail!("Trailing data at offset {}", end);
+    }
+    Ok(())
+}
+
+/// Deserialize a [`File`] from `bytes` using the fast path for all blocks.
+///
+/// The addresses and names of the blocks reference `input` if `bytes` is part of it.
+pub(crate) fn decode_fast(bytes: &[u8], input: Option<&Bytes>) -> Result<File> {
+    let (file_type_id, file_preamble, raw_blocks, end) = split_blocks(bytes)?;
+    check_end(bytes, end)?;
+    let file_blocks = raw_blocks
+        .into_iter()
+        .enumerate()
+        .map(|(block_index, raw)| decode_block_with_path(raw, block_index, input))
+        .collect::<Result<_>>()?;
+    Ok(File {
+        file_type_id,
+        file_preamble,
+        file_blocks,
+    })
+}
+
+/// Decode the block with index `block_index` from `raw` using the fast path.
+///
+/// The addresses and names reference `input` if `raw` is part of it.
+/// Errors contain the position of the value which failed to deserialize, like the errors of [`File::from_slice`].
+pub(crate) fn decode_block_with_path(
+    raw: &[u8],
+    block_index: usize,
+    input: Option<&Bytes>,
+) -> Result<Block> {
+    match try_decode_block(raw, input) {
+        Some(block) => Ok(block),
+        None => deserialize_item_with_path(raw, &format!("[2][{}]", block_index)),
+    }
+}
+
+/// Deserialize the value at `prefix` in the [`File`] from `raw`, adding the path of the failing field to errors.
+fn deserialize_item_with_path<T: DeserializeOwned>(raw: &[u8], prefix: &str) -> Result<T> {
+    let mut deserializer = serde_cbor::Deserializer::from_slice(raw);
+    let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|error| {
+        let path = match error.path().to_string() {
+            path if path == "." => String::new(),
+            path => format!(".{}", path),
+        };
+        Error::Context {
+            context: format!("Failed to deserialize {}{}", prefix, path),
+            source: Box::new(error.into_inner().into()),
+        }
+    })?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// Deserialize a value starting at `pos` and advance `pos` past it.
+pub(crate) fn deserialize_at<'de, T: Deserialize<'de>>(
+    bytes: &'de [u8],
+    pos: &mut usize,
+) -> serde_cbor::Result<T> {
+    let mut deserializer = serde_cbor::Deserializer::from_slice(&bytes[*pos..]);
+    let value = T::deserialize(&mut deserializer)?;
+    *pos += deserializer.byte_offset();
+    Ok(value)
+}
@@ -0,0 +1,452 @@
+//! Q/R items with all table references resolved
+//!
+//! A [`QueryResponse`] only stores indices into the [`BlockTables`] of its [`Block`].
+//! [`ResolvedQueryResponse`] looks up all of them at once, such that the addresses, names, and record lists can be used directly.
+//! [`QueryResponseIterator::resolved`] resolves all Q/R items of a block.
+//! The sections of a single [`QueryResponseExtended`] are resolved lazily with [`QueryResponseExtended::iter_questions`], [`QueryResponseExtended::iter_answers`], and the like.
+//!
+//! ```
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let file = File::read_path("./tests/data/dns.cdns")?;
+//! for (block, block_parameters) in file.iter_blocks() {
+//!     for query_response in block.iter_query_responses(block_parameters).resolved() {
+//!         let query_response = query_response?;
+//!         println!(
+//!             "{:?} asked for {:?}",
+//!             query_response.client_address,
+//!             query_response.query_name_string(),
+//!         );
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::iterators::QueryResponseIterator;
+use crate::serialization::*;
+use crate::tables::{BasedTables, IndexBase};
+use crate::utils::{absolute_time, name_to_string, to_std_ip};
+use crate::{Error, Result};
+use std::time::SystemTime;
+
+/// A [`QueryResponse`] with all `*_index` fields resolved against the [`BlockTables`].
+///
+/// Fields are [`None`] if the corresponding index or value is not stored in the Q/R item.
+#[derive(Debug, Clone)]
+pub struct ResolvedQueryResponse<'a> {
+    /// The unresolved Q/R item.
+    pub query_response: &'a QueryResponse,
+    /// Absolute time of the Query, or the Response if there is no Query.
+    pub time: Option<SystemTime>,
+    /// Client IP address.
+    ///
+    /// If an address prefix is configured, only the prefix bits are set.
+    pub client_address: Option<std::net::IpAddr>,
+    /// Client port.
+    pub client_port: Option<u16>,
+    /// Server IP address, from the signature.
+    pub server_address: Option<std::net::IpAddr>,
+    /// Server port, from the signature.
+    pub server_port: Option<u16>,
+    /// The [`QueryResponseSignature`] of the Q/R item.
+    pub signature: Option<&'a QueryResponseSignature>,
+    /// Name of the first Question.
+    pub query_name: Option<&'a NameOrRdata>,
+    /// CLASS and TYPE of the first Question.
+    pub query_classtype: Option<ClassType>,
+    /// RDATA of the OPT RR in the Query.
+    pub query_opt_rdata: Option<&'a NameOrRdata>,
+    /// Name of the zone containing the answer, from the response processing data.
+    pub bailiwick: Option<&'a NameOrRdata>,
+    /// Extended information about the Query.
+    pub query_extended: Option<ResolvedSections<'a>>,
+    /// Extended information about the Response.
+    pub response_extended: Option<ResolvedSections<'a>>,
+}
+
+/// The sections of a [`QueryResponseExtended`] with all lists resolved.
+///
+/// Sections which are not stored are empty.
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedSections<'a> {
+    /// Second and subsequent Questions.
+    pub questions: Vec<ResolvedQuestion<'a>>,
+    /// Answer section.
+    pub answers: Vec<ResolvedRR<'a>>,
+    /// Authority section.
+    pub authorities: Vec<ResolvedRR<'a>>,
+    /// Additional section.
+    pub additionals: Vec<ResolvedRR<'a>>,
+}
+
+/// A [`Question`] with its name and CLASS and TYPE resolved.
+#[derive(Debug, Clone, Copy)]
+pub struct ResolvedQuestion<'a> {
+    /// Owner name.
+    pub name: &'a NameOrRdata,
+    /// CLASS and TYPE.
+    pub classtype: ClassType,
+}
+
+/// A [`RR`] with its name, CLASS and TYPE, and RDATA resolved.
+#[derive(Debug, Clone, Copy)]
+pub struct ResolvedRR<'a> {
+    /// Owner name.
+    pub name: &'a NameOrRdata,
+    /// CLASS and TYPE.
+    pub classtype: ClassType,
+    /// TTL.
+    pub ttl: Option<u32>,
+    /// RDATA in wire format.
+    pub rdata: Option<&'a NameOrRdata>,
+}
+
+impl<'a> ResolvedQueryResponse<'a> {
+    /// Resolve all indices of `query_response` against `block_tables`.
+    ///
+    /// `earliest_time` and `block_parameters` belong to the [`Block`] containing the Q/R item, they are used to compute the absolute time.
+    /// The IP version of the addresses is taken from the [`TransportFlags`] if present, otherwise it is guessed from the address length.
+    ///
+    /// Fails if an index is not valid in `block_tables`.
+    pub fn new(
+        query_response: &'a QueryResponse,
+        earliest_time: Option<Timestamp>,
+        block_parameters: &BlockParameters,
+        block_tables: &'a BlockTables,
+    ) -> Result<Self> {
+        Self::with_index_base(
+            query_response,
+            earliest_time,
+            block_parameters,
+            block_tables.with_base(IndexBase::Zero),
+        )
+    }
+
+    /// Resolve all indices of `query_response` like [`ResolvedQueryResponse::new`], with indices starting at the base of `block_tables`.
+    pub fn with_index_base(
+        query_response: &'a QueryResponse,
+        earliest_time: Option<Timestamp>,
+        block_parameters: &BlockParameters,
+        block_tables: BasedTables<'a>,
+    ) -> Result<Self> {
+        let signature = match query_response.qr_signature_index {
+            Some(idx) => Some(block_tables.qr_signature(idx)?),
+            None => None,
+        };
+        let ip_version = signature
+            .and_then(|sig| sig.qr_transport_flags.as_ref())
+            .map(|flags| flags.ip_version());
+        let address = |idx: Option<usize>| -> Result<Option<std::net::IpAddr>> {
+            Ok(match idx {
+                Some(idx) => to_std_ip(block_tables.ip(idx)?, ip_version),
+                None => None,
+            })
+        };
+        let name = |idx: Option<usize>| match idx {
+            Some(idx) => block_tables.name(idx).map(Some),
+            None => Ok(None),
+        };
+
+        Ok(Self {
+            query_response,
+            time: absolute_time(earliest_time, query_response.time_offset, block_parameters),
+            client_address: address(query_response.client_address_index)?,
+            client_port: query_response.client_port,
+            server_address: address(signature.and_then(|sig| sig.server_address_index))?,
+            server_port: signature.and_then(|sig| sig.server_port),
+            signature,
+            query_name: name(query_response.query_name_index)?,
+            query_classtype: match signature.and_then(|sig| sig.query_classtype_index) {
+                Some(idx) => Some(*block_tables.classtype(idx)?),
+                None => None,
+            },
+            query_opt_rdata: name(signature.and_then(|sig| sig.query_opt_rdata_index))?,
+            bailiwick: name(
+                query_response
+                    .response_processing_data
+                    .as_ref()
+                    .and_then(|data| data.bailiwick_index),
+            )?,
+            query_extended: query_response
+                .query_extended
+                .as_ref()
+                .map(|extended| ResolvedSections::new(extended, block_tables))
+                .transpose()?,
+            response_extended: query_response
+                .response_extended
+                .as_ref()
+                .map(|extended| ResolvedSections::new(extended, block_tables))
+                .transpose()?,
+        })
+    }
+
+    /// The name of the first Question in presentation format.
+    ///
+    /// Names which cannot be decoded are rendered as their raw bytes.
+    pub fn query_name_string(&self) -> Option<String> {
+        self.query_name.map(name_to_string)
+    }
+
+    /// All Questions of the Query, starting with the first Question.
+    ///
+    /// The first Question is only included if its name and its CLASS and TYPE are known.
+    pub fn questions(&self) -> impl Iterator<Item = ResolvedQuestion<'a>> + '_ {
+        let first = match (self.query_name, self.query_classtype) {
+            (Some(name), Some(classtype)) => Some(ResolvedQuestion { name, classtype }),
+            _ => None,
+        };
+        first.into_iter().chain(
+            self.query_extended
+                .iter()
+                .flat_map(|extended| extended.questions.iter().copied()),
+        )
+    }
+}
+
+impl<'a> ResolvedSections<'a> {
+    fn new(extended: &QueryResponseExtended, block_tables: BasedTables<'a>) -> Result<Self> {
+        Ok(Self {
+            questions: extended
+                .iter_questions(block_tables)
+                .collect::<Result<_>>()?,
+            answers: extended.iter_answers(block_tables).collect::<Result<_>>()?,
+            authorities: extended
+                .iter_authorities(block_tables)
+                .collect::<Result<_>>()?,
+            additionals: extended
+                .iter_additionals(block_tables)
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl QueryResponseExtended {
+    /// Iterate over the second and subsequent Questions, resolved against `block_tables`.
+    ///
+    /// `block_tables` is either a [`&BlockTables`](BlockTables) or a [`BasedTables`].
+    pub fn iter_questions<'a>(
+        &self,
+        block_tables: impl Into<BasedTables<'a>>,
+    ) -> ResolvedQuestionIterator<'a> {
+        ResolvedQuestionIterator::new(block_tables.into(), self.question_index)
+    }
+
+    /// Iterate over the Answer section, resolved against `block_tables`.
+    pub fn iter_answers<'a>(
+        &self,
+        block_tables: impl Into<BasedTables<'a>>,
+    ) -> ResolvedRRIterator<'a> {
+        ResolvedRRIterator::new(block_tables.into(), self.answer_index)
+    }
+
+    /// Iterate over the Authority section, resolved against `block_tables`.
+    pub fn iter_authorities<'a>(
+        &self,
+        block_tables: impl Into<BasedTables<'a>>,
+    ) -> ResolvedRRIterator<'a> {
+        ResolvedRRIterator::new(block_tables.into(), self.authority_index)
+    }
+
+    /// Iterate over the Additional section, resolved against `block_tables`.
+    pub fn iter_additionals<'a>(
+        &self,
+        block_tables: impl Into<BasedTables<'a>>,
+    ) -> ResolvedRRIterator<'a> {
+        ResolvedRRIterator::new(block_tables.into(), self.additional_index)
+    }
+}
+
+/// Iterate over the [`ResolvedQuestion`]s of a [`QuestionList`].
+///
+/// Yields a single error if the list itself cannot be found.
+///
+/// See [`QueryResponseExtended::iter_questions`]
+#[derive(Debug)]
+pub struct ResolvedQuestionIterator<'a> {
+    block_tables: BasedTables<'a>,
+    indices: std::slice::Iter<'a, usize>,
+    error: Option<Error>,
+}
+
+impl<'a> ResolvedQuestionIterator<'a> {
+    fn new(block_tables: BasedTables<'a>, index: Option<usize>) -> Self {
+        let (indices, error) = match index.map(|idx| block_tables.question_list(idx)) {
+            Some(Ok(list)) => (list.iter(), None),
+            Some(Err(err)) => ([].iter(), Some(err)),
+            None => ([].iter(), None),
+        };
+        Self {
+            block_tables,
+            indices,
+            error,
+        }
+    }
+}
+
+impl<'a> Iterator for ResolvedQuestionIterator<'a> {
+    type Item = Result<ResolvedQuestion<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
+        let block_tables = self.block_tables;
+        self.indices.next().map(|&idx| {
+            let question = block_tables.question(idx)?;
+            Ok(ResolvedQuestion {
+                name: block_tables.name(question.name_index)?,
+                classtype: *block_tables.classtype(question.classtype_index)?,
+            })
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let error = usize::from(self.error.is_some());
+        let (lower, upper) = self.indices.size_hint();
+        (lower + error, upper.map(|upper| upper + error))
+    }
+}
+
+/// Iterate over the [`ResolvedRR`]s of a [`RRList`].
+///
+/// Yields a single error if the list itself cannot be found.
+///
+/// See [`QueryResponseExtended::iter_answers`]
+#[derive(Debug)]
+pub struct ResolvedRRIterator<'a> {
+    block_tables: BasedTables<'a>,
+    indices: std::slice::Iter<'a, usize>,
+    error: Option<Error>,
+}
+
+impl<'a> ResolvedRRIterator<'a> {
+    fn new(block_tables: BasedTables<'a>, index: Option<usize>) -> Self {
+        let (indices, error) = match index.map(|idx| block_tables.rr_list(idx)) {
+            Some(Ok(list)) => (list.iter(), None),
+            Some(Err(err)) => ([].iter(), Some(err)),
+            None => ([].iter(), None),
+        };
+        Self {
+            block_tables,
+            indices,
+            error,
+        }
+    }
+}
+
+impl<'a> Iterator for ResolvedRRIterator<'a> {
+    type Item = Result<ResolvedRR<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
+        let block_tables = self.block_tables;
+        self.indices.next().map(|&idx| {
+            let rr = block_tables.rr(idx)?;
+            Ok(ResolvedRR {
+                name: block_tables.name(rr.name_index)?,
+                classtype: *block_tables.classtype(rr.classtype_index)?,
+                ttl: rr.ttl,
+                rdata: match rr.rdata_index {
+                    Some(idx) => Some(block_tables.name(idx)?),
+                    None => None,
+                },
+            })
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let error = usize::from(self.error.is_some());
+        let (lower, upper) = self.indices.size_hint();
+        (lower + error, upper.map(|upper| upper + error))
+    }
+}
+
+impl File {
+    /// Resolve the Q/R items of all blocks in parallel.
+    ///
+    /// The items are resolved on the global rayon thread pool, see [`ResolvedQueryResponse::new`].
+    /// Blocks without [`BlockTables`] are treated like blocks with empty tables.
+    #[cfg(feature = "rayon")]
+    pub fn par_iter_query_responses(
+        &self,
+    ) -> impl rayon::iter::ParallelIterator<Item = Result<ResolvedQueryResponse<'_>>> {
+        use rayon::prelude::*;
+
+        let block_parameters = &self.file_preamble.block_parameters;
+        self.file_blocks.par_iter().flat_map(move |block| {
+            let parameters =
+                &block_parameters[block.block_preamble.block_parameters_index.unwrap_or(0)];
+            let block_tables = block
+                .block_tables
+                .as_ref()
+                .unwrap_or(&crate::tables::EMPTY_TABLES);
+            block
+                .query_responses
+                .as_deref()
+                .unwrap_or(&[])
+                .par_iter()
+                .map(move |query_response| {
+                    ResolvedQueryResponse::new(
+                        query_response,
+                        block.block_preamble.earliest_time,
+                        parameters,
+                        block_tables,
+                    )
+                })
+        })
+    }
+}
+
+impl<'a> QueryResponseIterator<'a> {
+    /// Resolve the indices of each Q/R item.
+    ///
+    /// See [`ResolvedQueryResponse::new`].
+    pub fn resolved(self) -> ResolvedQueryResponseIterator<'a> {
+        ResolvedQueryResponseIterator {
+            inner: self,
+            base: IndexBase::Zero,
+        }
+    }
+}
+
+/// Iterate over [`ResolvedQueryResponse`]s of a block.
+///
+/// See [`QueryResponseIterator::resolved`]
+pub struct ResolvedQueryResponseIterator<'a> {
+    inner: QueryResponseIterator<'a>,
+    base: IndexBase,
+}
+
+impl ResolvedQueryResponseIterator<'_> {
+    /// Resolve indices starting at `base` instead of 0.
+    ///
+    /// [`IndexBase::detect`] guesses the base of a block.
+    pub fn with_index_base(mut self, base: IndexBase) -> Self {
+        self.base = base;
+        self
+    }
+}
+
+impl<'a> Iterator for ResolvedQueryResponseIterator<'a> {
+    type Item = Result<ResolvedQueryResponse<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(
+            |(query_response, earliest_time, block_parameters, block_tables)| {
+                ResolvedQueryResponse::with_index_base(
+                    query_response,
+                    earliest_time,
+                    block_parameters,
+                    block_tables.with_base(self.base),
+                )
+            },
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
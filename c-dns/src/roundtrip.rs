@@ -0,0 +1,120 @@
+//! Verify that no data is lost by deserializing and re-serializing
+//!
+//! Fields unknown to this library are kept in the `extra_values` of each struct.
+//! A value which does not fit the type of its field, like a text where a number is expected, can still be lost.
+//! [`File::check_lossless_roundtrip`] detects such cases by comparing the CBOR values before and after a round-trip.
+//!
+//! ```
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let bytes = std::fs::read("./tests/data/dns.cdns")?;
+//! assert_eq!(None, File::check_slice_roundtrip(&bytes)?);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::serialization::File;
+use crate::Result;
+use serde_cbor::Value;
+use std::fmt;
+
+/// The first difference between the CBOR values before and after a round-trip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundtripDifference {
+    /// Location of the differing value, like `[2][0].3` for the key `3` of the first [`Block`](crate::serialization::Block).
+    pub path: String,
+    /// The value before the round-trip, [`None`] if the value was added.
+    pub before: Option<Value>,
+    /// The value after the round-trip, [`None`] if the value was lost.
+    pub after: Option<Value>,
+}
+
+impl fmt::Display for RoundtripDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => {
+                write!(f, "{}: {:?} changed to {:?}", self.path, before, after)
+            }
+            (Some(before), None) => write!(f, "{}: {:?} was lost", self.path, before),
+            (None, Some(after)) => write!(f, "{}: {:?} was added", self.path, after),
+            (None, None) => write!(f, "{}: unknown difference", self.path),
+        }
+    }
+}
+
+impl File {
+    /// Check that serializing the file, deserializing it, and serializing it again keeps all values.
+    ///
+    /// This allows producers to verify that their custom extensions survive being read by this library.
+    /// Returns the first difference found, or [`None`] if the round-trip is lossless.
+    pub fn check_lossless_roundtrip(&self) -> Result<Option<RoundtripDifference>> {
+        File::check_slice_roundtrip(&serde_cbor::to_vec(self)?)
+    }
+
+    /// Check that deserializing the [`File`] in `bytes` and serializing it again keeps all values.
+    ///
+    /// Only the values are compared, not their encoding, see [`EncodingMetadata`](crate::encoding::EncodingMetadata) for the latter.
+    /// Returns the first difference found, or [`None`] if the round-trip is lossless.
+    pub fn check_slice_roundtrip(bytes: &[u8]) -> Result<Option<RoundtripDifference>> {
+        let before: Value = serde_cbor::from_slice(bytes)?;
+        let file: File = serde_cbor::from_slice(bytes)?;
+        let after: Value = serde_cbor::from_slice(&serde_cbor::to_vec(&file)?)?;
+        let mut path = String::new();
+        Ok(first_difference(&mut path, &before, &after))
+    }
+}
+
+/// Find the first difference between `before` and `after`, with `path` being their location.
+fn first_difference(
+    path: &mut String,
+    before: &Value,
+    after: &Value,
+) -> Option<RoundtripDifference> {
+    let path_len = path.len();
+    let difference = match (before, after) {
+        (Value::Array(before), Value::Array(after)) => {
+            (0..before.len().max(after.len())).find_map(|idx| {
+                path.truncate(path_len);
+                path.push_str(&format!("[{}]", idx));
+                match (before.get(idx), after.get(idx)) {
+                    (Some(before), Some(after)) => first_difference(path, before, after),
+                    (before, after) => Some(difference(path, before, after)),
+                }
+            })
+        }
+        (Value::Map(before), Value::Map(after)) => {
+            let mut keys: Vec<&Value> = before.keys().chain(after.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            keys.into_iter().find_map(|key| {
+                path.truncate(path_len);
+                path.push('.');
+                push_key(path, key);
+                match (before.get(key), after.get(key)) {
+                    (Some(before), Some(after)) => first_difference(path, before, after),
+                    (before, after) => Some(difference(path, before, after)),
+                }
+            })
+        }
+        (before, after) if before == after => None,
+        (before, after) => Some(difference(path, Some(before), Some(after))),
+    };
+    path.truncate(path_len);
+    difference
+}
+
+fn difference(path: &str, before: Option<&Value>, after: Option<&Value>) -> RoundtripDifference {
+    RoundtripDifference {
+        path: path.to_string(),
+        before: before.cloned(),
+        after: after.cloned(),
+    }
+}
+
+fn push_key(path: &mut String, key: &Value) {
+    match key {
+        Value::Integer(key) => path.push_str(&key.to_string()),
+        Value::Text(key) => path.push_str(key),
+        key => path.push_str(&format!("{:?}", key)),
+    }
+}
@@ -0,0 +1,278 @@
+//! Keep only a sample of the Q/R items
+//!
+//! Large servers cannot store every Q/R item, so collectors often keep only a sample.
+//! A [`Sampler`] decides which items to keep according to a [`Sampling`] method.
+//! It applies to existing files with [`File::sample`] and to new blocks with [`BlockBuilder::build_sampled`].
+//! The sampling is recorded in the [`StorageParameters`] by setting [`StorageFlags::SampledData`] and the `sampling_method`, for new files with [`StorageParametersBuilder::sampling`].
+//!
+//! The random methods use a pseudo-random generator with a fixed seed, so the sample is reproducible.
+//!
+//! ```
+//! # use c_dns::sampling::Sampling;
+//! # use c_dns::serialization::{File, StorageFlags};
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let mut file = File::read_path("./tests/data/dns.cdns")?;
+//! file.sample(Sampling::EveryNth(4))?;
+//! assert_eq!(3, file.query_response_count());
+//! let storage_parameters = &file.file_preamble.block_parameters[0].storage_parameters;
+//! assert!(storage_parameters.storage_flags.unwrap().contains(StorageFlags::SampledData));
+//! assert_eq!(Some("1-in-4"), storage_parameters.sampling_method.as_deref());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::builder::{BlockBuilder, StorageParametersBuilder};
+use crate::error::bail;
+use crate::serialization::*;
+use crate::Result;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Method for choosing the kept Q/R items.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampling {
+    /// Keep every `n`-th Q/R item, starting with the first one.
+    EveryNth(usize),
+    /// Keep each Q/R item independently with the `probability` between 0 and 1.
+    Probability {
+        probability: f64,
+        /// Seed of the pseudo-random generator.
+        seed: u64,
+    },
+    /// Keep at most `per_client` Q/R items per client address, chosen uniformly with reservoir sampling.
+    ///
+    /// Items without a client address count as one client.
+    /// Busy clients are thinned out, while the items of all other clients are kept.
+    PerClientReservoir {
+        per_client: usize,
+        /// Seed of the pseudo-random generator.
+        seed: u64,
+    },
+}
+
+impl Sampling {
+    /// Description of the method, which is stored as the `sampling_method` of the [`StorageParameters`].
+    pub fn method(&self) -> String {
+        match self {
+            Self::EveryNth(n) => format!("1-in-{}", n),
+            Self::Probability { probability, .. } => format!("probabilistic p={}", probability),
+            Self::PerClientReservoir { per_client, .. } => {
+                format!("per-client reservoir of {} items", per_client)
+            }
+        }
+    }
+}
+
+/// Decide which Q/R items to keep.
+///
+/// The state carries over between calls, so a single sampler applied to consecutive blocks samples them like one stream.
+/// Only the reservoir of [`Sampling::PerClientReservoir`] is reset for each call, since it needs all items of a call to choose from.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    sampling: Sampling,
+    /// Number of items seen so far, for [`Sampling::EveryNth`].
+    position: usize,
+    rng: SplitMix64,
+}
+
+impl Sampler {
+    /// Create a sampler for `sampling`.
+    ///
+    /// Fails if `n` or `per_client` is zero, or if the `probability` is not between 0 and 1.
+    pub fn new(sampling: Sampling) -> Result<Self> {
+        let seed = match sampling {
+            Sampling::EveryNth(0) => bail!("Cannot keep every 0th Q/R item"),
+            Sampling::EveryNth(_) => 0,
+            Sampling::Probability { probability, seed } => {
+                if !(0.0..=1.0).contains(&probability) {
+                    bail!(
+                        "The sampling probability must be between 0 and 1, but is {}",
+                        probability
+                    );
+                }
+                seed
+            }
+            Sampling::PerClientReservoir { per_client: 0, .. } => {
+                bail!("Cannot keep zero Q/R items per client")
+            }
+            Sampling::PerClientReservoir { seed, .. } => seed,
+        };
+        Ok(Self {
+            sampling,
+            position: 0,
+            rng: SplitMix64(seed),
+        })
+    }
+
+    /// The sampling method.
+    pub fn sampling(&self) -> &Sampling {
+        &self.sampling
+    }
+
+    /// Decide for each item, identified by its client, whether to keep it.
+    pub fn select<K: Hash + Eq>(&mut self, clients: impl IntoIterator<Item = K>) -> Vec<bool> {
+        match self.sampling {
+            Sampling::EveryNth(n) => clients
+                .into_iter()
+                .map(|_| {
+                    let keep = self.position.is_multiple_of(n);
+                    self.position += 1;
+                    keep
+                })
+                .collect(),
+            Sampling::Probability { probability, .. } => clients
+                .into_iter()
+                .map(|_| self.rng.next_f64() < probability)
+                .collect(),
+            Sampling::PerClientReservoir { per_client, .. } => {
+                // Number of seen items and the kept item indices per client
+                let mut reservoirs: HashMap<K, (usize, Vec<usize>)> = HashMap::new();
+                let mut len = 0;
+                for (idx, client) in clients.into_iter().enumerate() {
+                    len += 1;
+                    let (seen, reservoir) = reservoirs.entry(client).or_default();
+                    *seen += 1;
+                    if reservoir.len() < per_client {
+                        reservoir.push(idx);
+                    } else {
+                        let slot = (self.rng.next() % *seen as u64) as usize;
+                        if slot < per_client {
+                            reservoir[slot] = idx;
+                        }
+                    }
+                }
+                let mut keep = vec![false; len];
+                for (_, reservoir) in reservoirs.into_values() {
+                    for idx in reservoir {
+                        keep[idx] = true;
+                    }
+                }
+                keep
+            }
+        }
+    }
+}
+
+/// The SplitMix64 pseudo-random generator
+#[derive(Debug, Clone)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A uniform value in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl File {
+    /// Keep only a sample of the Q/R items in all blocks.
+    ///
+    /// All Q/R items of the file are sampled as one stream, see [`Block::sample`] for the changes to each block.
+    /// This sets [`StorageFlags::SampledData`] and appends the [`Sampling::method`] to the `sampling_method` of all [`StorageParameters`].
+    /// Returns the number of removed Q/R items.
+    ///
+    /// Fails if the `sampling` is invalid, see [`Sampler::new`].
+    pub fn sample(&mut self, sampling: Sampling) -> Result<usize> {
+        let mut sampler = Sampler::new(sampling)?;
+        let clients: Vec<Option<IpAddr>> = self
+            .iter_all_query_responses()
+            .map(|(query_response, _, _, block_tables)| {
+                let index = query_response.client_address_index?;
+                block_tables.ip(index).ok().cloned()
+            })
+            .collect();
+        let mut keep = sampler.select(clients).into_iter();
+
+        let method = sampling.method();
+        for block_parameters in &mut self.file_preamble.block_parameters {
+            let storage_parameters = &mut block_parameters.storage_parameters;
+            storage_parameters
+                .storage_flags
+                .get_or_insert_with(Default::default)
+                .insert(StorageFlags::SampledData);
+            storage_parameters.sampling_method =
+                Some(match storage_parameters.sampling_method.take() {
+                    Some(previous) => format!("{}, {}", previous, method),
+                    None => method.clone(),
+                });
+        }
+        Ok(self
+            .file_blocks
+            .iter_mut()
+            .map(|block| block.retain_sampled(&mut keep))
+            .sum())
+    }
+}
+
+impl Block {
+    /// Keep only the Q/R items chosen by `sampler`.
+    ///
+    /// Unreferenced table entries are removed with [`Block::compact_tables`].
+    /// The `qr_data_items` and unmatched counts of the [`BlockStatistics`] are updated to the kept items, while the other counts still describe all processed messages.
+    /// Returns the number of removed Q/R items.
+    ///
+    /// This does not change the [`StorageParameters`], use [`File::sample`] or [`StorageParametersBuilder::sampling`] to also record the sampling.
+    ///
+    /// # Panics
+    ///
+    /// The indices in the block must be valid, as checked by [`File::dangling_indices`].
+    pub fn sample(&mut self, sampler: &mut Sampler) -> usize {
+        let block_tables = self.block_tables.as_ref();
+        let keep = sampler.select(self.query_responses.iter().flatten().map(|qr| {
+            let index = qr.client_address_index?;
+            block_tables?.ip(index).ok()
+        }));
+        self.retain_sampled(&mut keep.into_iter())
+    }
+
+    /// Keep the Q/R items for which `keep` yields `true`, see [`Block::sample`].
+    fn retain_sampled(&mut self, keep: &mut impl Iterator<Item = bool>) -> usize {
+        let query_responses = match &mut self.query_responses {
+            Some(query_responses) => query_responses,
+            None => return 0,
+        };
+        let len = query_responses.len();
+        query_responses.retain(|_| keep.next().unwrap_or(true));
+        let removed = len - query_responses.len();
+        if removed == 0 {
+            return 0;
+        }
+        if query_responses.is_empty() {
+            self.query_responses = None;
+        }
+        self.compact_tables();
+        let computed = self.compute_statistics();
+        if let Some(statistics) = &mut self.block_statistics {
+            statistics.qr_data_items = computed.qr_data_items;
+            statistics.unmatched_queries = computed.unmatched_queries;
+            statistics.unmatched_responses = computed.unmatched_responses;
+        }
+        removed
+    }
+}
+
+impl BlockBuilder {
+    /// Create the [`Block`] like [`BlockBuilder::build`], but keep only the Q/R items chosen by `sampler`.
+    ///
+    /// The `processed_messages` of the [`BlockStatistics`] count all added items, see [`Block::sample`].
+    pub fn build_sampled(self, sampler: &mut Sampler) -> Result<Block> {
+        let mut block = self.build()?;
+        block.sample(sampler);
+        Ok(block)
+    }
+}
+
+impl StorageParametersBuilder {
+    /// Record the `sampling` as the sampling method, see [`StorageParametersBuilder::sampling_method`].
+    pub fn sampling(self, sampling: &Sampling) -> Self {
+        self.sampling_method(sampling.method())
+    }
+}
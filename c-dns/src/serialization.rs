@@ -4,20 +4,27 @@
 //! They are intended to provide lossless deserialization and re-serialization of C-DNS data.
 //! They contain references to other parts of the file (`*_index` fields) and some data (like IP addresses) can only be parsed with additional context.
 //!
+//! Files too large to keep in memory can be read block by block with a [`FileReader`] and written block by block with a [`FileWriter`].
+//!
 //! [C-DNS format]: https://tools.ietf.org/html/rfc8618
 
 // Needed to make stuff work between stable and nightly
 #![allow(renamed_and_removed_lints, clippy::unknown_clippy_lints)]
 #![allow(clippy::upper_case_acronyms)]
 
-use color_eyre::eyre::bail;
+pub use crate::read::FileReader;
+pub use crate::write::FileWriter;
+
+use crate::error::bail;
+use crate::flags::FlagSetExt;
+use crate::Error;
+use bytes::Bytes;
 use enumset::{EnumSet, EnumSetType};
 use serde::{Deserialize, Serialize};
-use serde_bytes::ByteBuf;
 use serde_indexed::{DeserializeIndexed, SerializeIndexed};
-use serde_repr::{Deserialize_repr, Serialize_repr};
 use serde_tuple::{Deserialize_tuple, Serialize_tuple};
 use serde_with::skip_serializing_none;
+use smallvec::SmallVec;
 use std::collections::BTreeMap;
 use std::fmt;
 use std::net::{Ipv4Addr, Ipv6Addr};
@@ -32,7 +39,7 @@ use std::net::{Ipv4Addr, Ipv6Addr};
 ///
 /// List of standarized DNS classes:
 /// <https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-2>
-#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[repr(transparent)]
 #[serde(transparent)]
 pub struct DnsClass(u16);
@@ -61,7 +68,7 @@ impl From<u16> for DnsClass {
 ///
 /// List of standarized DNS resource record types:
 /// <https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-4>
-#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[repr(transparent)]
 #[serde(transparent)]
 pub struct DnsType(u16);
@@ -84,6 +91,292 @@ impl From<u16> for DnsType {
     }
 }
 
+/// Define associated constants for the assigned values of a DNS parameter, together with a table of their mnemonics.
+macro_rules! dns_parameters {
+    ($type:ident, $names:ident, $($(#[$meta:meta])* $name:ident = $value:expr,)*) => {
+        impl $type {
+            $(
+                $(#[$meta])*
+                pub const $name: Self = Self($value);
+            )*
+        }
+
+        const $names: &[($type, &str)] = &[$(($type::$name, stringify!($name)),)*];
+    };
+}
+
+dns_parameters!(
+    DnsClass,
+    CLASS_NAMES,
+    /// Internet, RFC 1035
+    IN = 1,
+    /// Chaos, RFC 1035
+    CH = 3,
+    /// Hesiod, RFC 1035
+    HS = 4,
+    /// QCLASS NONE, RFC 2136
+    NONE = 254,
+    /// QCLASS ANY, RFC 1035
+    ANY = 255,
+);
+
+dns_parameters!(
+    DnsType,
+    TYPE_NAMES,
+    /// IPv4 host address, RFC 1035
+    A = 1,
+    /// Authoritative name server, RFC 1035
+    NS = 2,
+    /// Canonical name for an alias, RFC 1035
+    CNAME = 5,
+    /// Start of a zone of authority, RFC 1035
+    SOA = 6,
+    /// Null RR, RFC 1035
+    NULL = 10,
+    /// Domain name pointer, RFC 1035
+    PTR = 12,
+    /// Host information, RFC 1035
+    HINFO = 13,
+    /// Mail exchange, RFC 1035
+    MX = 15,
+    /// Text strings, RFC 1035
+    TXT = 16,
+    /// Responsible person, RFC 1183
+    RP = 17,
+    /// AFS database location, RFC 1183
+    AFSDB = 18,
+    /// Security signature, RFC 2535
+    SIG = 24,
+    /// Security key, RFC 2535
+    KEY = 25,
+    /// IPv6 host address, RFC 3596
+    AAAA = 28,
+    /// Location information, RFC 1876
+    LOC = 29,
+    /// Server selection, RFC 2782
+    SRV = 33,
+    /// Naming authority pointer, RFC 3403
+    NAPTR = 35,
+    /// Key exchanger, RFC 2230
+    KX = 36,
+    /// Certificate, RFC 4398
+    CERT = 37,
+    /// Delegation name, RFC 6672
+    DNAME = 39,
+    /// EDNS pseudo-RR, RFC 6891
+    OPT = 41,
+    /// Address prefix list, RFC 3123
+    APL = 42,
+    /// Delegation signer, RFC 4034
+    DS = 43,
+    /// SSH key fingerprint, RFC 4255
+    SSHFP = 44,
+    /// IPsec key, RFC 4025
+    IPSECKEY = 45,
+    /// DNSSEC signature, RFC 4034
+    RRSIG = 46,
+    /// Next secure record, RFC 4034
+    NSEC = 47,
+    /// DNS key, RFC 4034
+    DNSKEY = 48,
+    /// DHCP identifier, RFC 4701
+    DHCID = 49,
+    /// Hashed next secure record, RFC 5155
+    NSEC3 = 50,
+    /// NSEC3 parameters, RFC 5155
+    NSEC3PARAM = 51,
+    /// TLSA certificate association, RFC 6698
+    TLSA = 52,
+    /// S/MIME certificate association, RFC 8162
+    SMIMEA = 53,
+    /// Host identity protocol, RFC 8005
+    HIP = 55,
+    /// Child DS, RFC 7344
+    CDS = 59,
+    /// Child DNSKEY, RFC 7344
+    CDNSKEY = 60,
+    /// OpenPGP key, RFC 7929
+    OPENPGPKEY = 61,
+    /// Child-to-parent synchronization, RFC 7477
+    CSYNC = 62,
+    /// Message digest for DNS zones, RFC 8976
+    ZONEMD = 63,
+    /// General purpose service binding, RFC 9460
+    SVCB = 64,
+    /// Service binding for HTTPS, RFC 9460
+    HTTPS = 65,
+    /// Sender policy framework, RFC 7208
+    SPF = 99,
+    /// Transaction key, RFC 2930
+    TKEY = 249,
+    /// Transaction signature, RFC 8945
+    TSIG = 250,
+    /// Incremental zone transfer, RFC 1995
+    IXFR = 251,
+    /// Full zone transfer, RFC 1035
+    AXFR = 252,
+    /// QTYPE for all records, RFC 1035
+    ANY = 255,
+    /// Uniform resource identifier, RFC 7553
+    URI = 256,
+    /// Certification authority authorization, RFC 8659
+    CAA = 257,
+);
+
+impl DnsClass {
+    /// The mnemonic of the CLASS, if it is assigned.
+    pub fn name(&self) -> Option<&'static str> {
+        parameter_name(CLASS_NAMES, *self)
+    }
+}
+
+/// Render the mnemonic, or the generic `CLASS` form of RFC 3597 for unassigned values.
+impl fmt::Display for DnsClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.pad(name),
+            None => f.pad(&format!("CLASS{}", self.0)),
+        }
+    }
+}
+
+/// Parse a mnemonic or the generic `CLASS` form of RFC 3597, ignoring case.
+impl std::str::FromStr for DnsClass {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_parameter(CLASS_NAMES, "CLASS", s)
+            .map(Self)
+            .ok_or_else(|| crate::error::invalid!("Unknown DNS CLASS {:?}", s))
+    }
+}
+
+impl DnsType {
+    /// The mnemonic of the TYPE, if it is assigned.
+    pub fn name(&self) -> Option<&'static str> {
+        parameter_name(TYPE_NAMES, *self)
+    }
+}
+
+/// Render the mnemonic, or the generic `TYPE` form of RFC 3597 for unassigned values.
+impl fmt::Display for DnsType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.pad(name),
+            None => f.pad(&format!("TYPE{}", self.0)),
+        }
+    }
+}
+
+/// Parse a mnemonic or the generic `TYPE` form of RFC 3597, ignoring case.
+impl std::str::FromStr for DnsType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_parameter(TYPE_NAMES, "TYPE", s)
+            .map(Self)
+            .ok_or_else(|| crate::error::invalid!("Unknown DNS TYPE {:?}", s))
+    }
+}
+
+fn parameter_name<T: PartialEq>(names: &[(T, &'static str)], value: T) -> Option<&'static str> {
+    names
+        .iter()
+        .find(|(known, _)| *known == value)
+        .map(|&(_, name)| name)
+}
+
+fn parse_parameter<T: Copy + Into<u16>>(
+    names: &[(T, &str)],
+    generic_prefix: &str,
+    s: &str,
+) -> Option<u16> {
+    if let Some(&(value, _)) = names.iter().find(|(_, name)| name.eq_ignore_ascii_case(s)) {
+        return Some(value.into());
+    }
+    let prefix = s.get(..generic_prefix.len())?;
+    if !prefix.eq_ignore_ascii_case(generic_prefix) {
+        return None;
+    }
+    let number = &s[generic_prefix.len()..];
+    if !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+/// DNS OPCODE
+///
+/// 4-bit value identifying the kind of a DNS message.
+/// Deserialization fails for values outside of the range 0 to 15.
+///
+/// List of standarized DNS opcodes:
+/// <https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-5>
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(transparent)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct Opcode(u8);
+
+impl Opcode {
+    /// Standard query, RFC 1035
+    pub const QUERY: Self = Self(0);
+    /// Inverse query, obsoleted by RFC 3425
+    pub const IQUERY: Self = Self(1);
+    /// Server status request, RFC 1035
+    pub const STATUS: Self = Self(2);
+    /// Zone change notification, RFC 1996
+    pub const NOTIFY: Self = Self(4);
+    /// Dynamic update, RFC 2136
+    pub const UPDATE: Self = Self(5);
+    /// DNS Stateful Operations, RFC 8490
+    pub const DSO: Self = Self(6);
+
+    /// The name of the opcode, if it is assigned.
+    pub fn name(&self) -> Option<&'static str> {
+        Some(match *self {
+            Self::QUERY => "QUERY",
+            Self::IQUERY => "IQUERY",
+            Self::STATUS => "STATUS",
+            Self::NOTIFY => "NOTIFY",
+            Self::UPDATE => "UPDATE",
+            Self::DSO => "DSO",
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Debug for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("Opcode({})", self.0))
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.pad(name),
+            None => f.pad(&format!("OPCODE{}", self.0)),
+        }
+    }
+}
+
+impl From<Opcode> for u8 {
+    fn from(value: Opcode) -> Self {
+        value.0
+    }
+}
+
+impl TryFrom<u8> for Opcode {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if value > 15 {
+            bail!("Invalid OPCODE {}. Expected a value from 0 to 15.", value);
+        }
+        Ok(Self(value))
+    }
+}
+
 /// IPv4 or IPv6 address
 ///
 /// Type representing an IPv4 or IPv6 address.
@@ -91,91 +384,259 @@ impl From<u16> for DnsType {
 ///
 /// If client or server address prefixes are set, only the address prefix bits are stored.
 /// Each string is therefore up to 4 bytes long for an IPv4 address, or up to 16 bytes long for an IPv6 address.
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+///
+/// The bytes are reference counted, so cloning an [`IpAddr`] does not copy them.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[repr(transparent)]
 #[serde(transparent)]
-pub struct IpAddr(ByteBuf);
+pub struct IpAddr(#[serde(deserialize_with = "crate::zero_copy::deserialize_bytes")] Bytes);
 
 impl fmt::Debug for IpAddr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_fmt(format_args!("IpAddr({:?})", self.0))
+        f.write_fmt(format_args!("IpAddr({:?})", &*self.0))
+    }
+}
+
+impl From<Bytes> for IpAddr {
+    fn from(bytes: Bytes) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<IpAddr> for Bytes {
+    fn from(ip: IpAddr) -> Self {
+        ip.0
     }
 }
 
 impl IpAddr {
-    pub fn as_ipv4(&self) -> color_eyre::eyre::Result<Ipv4Addr> {
-        Ok(match self.0.as_slice() {
-            &[] => bail!("No bytes to convert into Ipv4Addr"),
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn as_ipv4(&self) -> crate::Result<Ipv4Addr> {
+        Ok(match &*self.0 {
+            &[] => return Err(Error::EmptyAddress(crate::IpVersion::Ipv4)),
             &[a] => Ipv4Addr::new(a, 0, 0, 0),
             &[a, b] => Ipv4Addr::new(a, b, 0, 0),
             &[a, b, c] => Ipv4Addr::new(a, b, c, 0),
             &[a, b, c, d] => Ipv4Addr::new(a, b, c, d),
-            bytes => bail!(
-                "Too many bytes to convert into Ipv4Addr. Expected up to 4 bytes but got {}.",
-                bytes.len()
-            ),
+            bytes => {
+                return Err(Error::AddressTooLong {
+                    ip_version: crate::IpVersion::Ipv4,
+                    len: bytes.len(),
+                })
+            }
         })
     }
 
-    pub fn as_ipv6(&self) -> color_eyre::eyre::Result<Ipv6Addr> {
-        Ok(match self.0.as_slice() {
-            &[] => bail!("No bytes to convert into Ipv6Addr"),
+    pub fn as_ipv6(&self) -> crate::Result<Ipv6Addr> {
+        Ok(match &*self.0 {
+            &[] => return Err(Error::EmptyAddress(crate::IpVersion::Ipv6)),
             bytes if bytes.len() <= 16 => {
                 let mut vec = bytes.to_vec();
-                vec.extend(std::iter::repeat(0).take(16 - vec.len()));
+                vec.resize(16, 0);
                 Ipv6Addr::from(<[u8; 16]>::try_from(&*vec).unwrap())
             }
-            bytes => bail!(
-                "Too many bytes to convert into Ipv6Addr. Expected up to 16 bytes but got {}.",
-                bytes.len()
-            ),
+            bytes => {
+                return Err(Error::AddressTooLong {
+                    ip_version: crate::IpVersion::Ipv6,
+                    len: bytes.len(),
+                })
+            }
         })
     }
+
+    /// Store only the first `prefix_len` bits of `addr`, like a collector with address prefixes set.
+    ///
+    /// The address is shortened to the bytes containing the prefix, and the remaining bits in the last byte are set to zero.
+    /// A `prefix_len` longer than the address stores the full address.
+    pub fn with_prefix(addr: std::net::IpAddr, prefix_len: u8) -> Self {
+        let mut bytes = ip_octets(&addr);
+        let prefix_len = usize::from(prefix_len).min(bytes.len() * 8);
+        bytes.truncate(prefix_len.div_ceil(8));
+        if prefix_len % 8 != 0 {
+            if let Some(last) = bytes.last_mut() {
+                *last &= 0xff << (8 - prefix_len % 8);
+            }
+        }
+        Self(bytes.into())
+    }
+
+    /// Expand the stored address into a full address of the IP version given by `is_ipv6`.
+    ///
+    /// The bytes are zero-extended, and bits beyond the address prefix of the [`StorageParameters`] are set to zero.
+    /// The address table is shared by client and server addresses, so the longer of the client and server address prefix applies.
+    /// `is_ipv6` is usually taken from the [`TransportFlags`] of the Q/R item.
+    ///
+    /// Fails if the address has more bytes than the prefix allows, or if it is empty although the prefix is not.
+    ///
+    /// ```
+    /// # use c_dns::builder::StorageParametersBuilder;
+    /// # use c_dns::serialization::IpAddr;
+    /// # fn main() -> color_eyre::eyre::Result<()> {
+    /// let storage_parameters = StorageParametersBuilder::new(1_000_000, 5000)
+    ///     .client_address_prefix_ipv4(24)
+    ///     .server_address_prefix_ipv4(24)
+    ///     .build()?;
+    /// let stored = IpAddr::with_prefix("192.0.2.123".parse()?, 24);
+    /// assert_eq!(
+    ///     "192.0.2.0".parse::<std::net::IpAddr>()?,
+    ///     stored.to_ip_addr(&storage_parameters, false)?
+    /// );
+    /// assert!(IpAddr::from("192.0.2.123".parse::<std::net::IpAddr>()?)
+    ///     .to_ip_addr(&storage_parameters, false)
+    ///     .is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_ip_addr(
+        &self,
+        storage_parameters: &StorageParameters,
+        is_ipv6: bool,
+    ) -> crate::Result<std::net::IpAddr> {
+        let ip_version = if is_ipv6 {
+            crate::IpVersion::Ipv6
+        } else {
+            crate::IpVersion::Ipv4
+        };
+        let address_bits = if is_ipv6 { 128 } else { 32 };
+        let prefix_bits = |prefix: Option<u8>| prefix.map_or(address_bits, usize::from);
+        let prefix_len = prefix_bits(storage_parameters.client_address_prefix(ip_version))
+            .max(prefix_bits(
+                storage_parameters.server_address_prefix(ip_version),
+            ))
+            .min(address_bits);
+
+        let len = self.0.len();
+        if len > address_bits / 8 {
+            return Err(Error::AddressTooLong { ip_version, len });
+        }
+        if len > prefix_len.div_ceil(8) {
+            bail!(
+                "The {} address has {} bytes, but the address prefix is only {} bits",
+                ip_version,
+                len,
+                prefix_len
+            );
+        }
+        if len == 0 && prefix_len > 0 {
+            return Err(Error::EmptyAddress(ip_version));
+        }
+
+        let mut octets = [0; 16];
+        octets[..len].copy_from_slice(&self.0);
+        for (i, octet) in octets.iter_mut().enumerate() {
+            let kept_bits = prefix_len.saturating_sub(i * 8).min(8);
+            *octet &= !(0xff_u16 >> kept_bits) as u8;
+        }
+        Ok(if is_ipv6 {
+            std::net::IpAddr::V6(Ipv6Addr::from(octets))
+        } else {
+            std::net::IpAddr::V4(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]))
+        })
+    }
+
+    /// Number of address bits stored.
+    ///
+    /// This is an upper bound for the prefix length configured during collection.
+    pub fn stored_bits(&self) -> usize {
+        self.0.len() * 8
+    }
+
+    /// Whether the stored address lies in the subnet `addr/prefix_len`.
+    ///
+    /// If the stored address is truncated to fewer than `prefix_len` bits, only the stored bits are compared, as the others are unknown.
+    /// Such an address matches if the subnet overlaps with the stored prefix.
+    /// An IPv4 `addr` never matches stored addresses longer than 4 bytes, and an empty stored address never matches.
+    ///
+    /// The number of stored bits is only known to a full byte.
+    /// For prefixes which do not end on a byte boundary use [`IpAddr::matches_with_stored_prefix`].
+    ///
+    /// ```
+    /// # use c_dns::serialization::IpAddr;
+    /// let stored = IpAddr::with_prefix("192.0.2.123".parse().unwrap(), 24);
+    /// assert!(stored.matches(&"192.0.2.0".parse().unwrap(), 24));
+    /// assert!(stored.matches(&"192.0.2.42".parse().unwrap(), 32));
+    /// assert!(!stored.matches(&"198.51.100.0".parse().unwrap(), 24));
+    /// ```
+    pub fn matches(&self, addr: &std::net::IpAddr, prefix_len: u8) -> bool {
+        self.matches_with_stored_prefix(addr, prefix_len, None)
+    }
+
+    /// Like [`IpAddr::matches`], but only compare the first `stored_prefix_len` stored bits.
+    ///
+    /// The `stored_prefix_len` is the address prefix of the [`StorageParameters`], see [`StorageParameters::client_address_prefix`] and [`StorageParameters::server_address_prefix`].
+    /// `None` means the full address is stored.
+    pub fn matches_with_stored_prefix(
+        &self,
+        addr: &std::net::IpAddr,
+        prefix_len: u8,
+        stored_prefix_len: Option<u8>,
+    ) -> bool {
+        let octets = ip_octets(addr);
+        if self.0.is_empty() || self.0.len() > octets.len() {
+            return false;
+        }
+        let bits = usize::from(prefix_len)
+            .min(self.stored_bits())
+            .min(stored_prefix_len.map_or(usize::MAX, usize::from));
+        let (full, rest) = (bits / 8, bits % 8);
+        if self.0[..full] != octets[..full] {
+            return false;
+        }
+        let mask = !(0xff_u8 >> rest);
+        rest == 0 || self.0[full] & mask == octets[full] & mask
+    }
+}
+
+impl From<std::net::IpAddr> for IpAddr {
+    fn from(addr: std::net::IpAddr) -> Self {
+        Self(ip_octets(&addr).into())
+    }
+}
+
+impl From<Ipv4Addr> for IpAddr {
+    fn from(addr: Ipv4Addr) -> Self {
+        Self(addr.octets().to_vec().into())
+    }
+}
+
+impl From<Ipv6Addr> for IpAddr {
+    fn from(addr: Ipv6Addr) -> Self {
+        Self(addr.octets().to_vec().into())
+    }
+}
+
+/// Bytes of `addr` in network byte order.
+pub(crate) fn ip_octets(addr: &std::net::IpAddr) -> Vec<u8> {
+    match addr {
+        std::net::IpAddr::V4(addr) => addr.octets().to_vec(),
+        std::net::IpAddr::V6(addr) => addr.octets().to_vec(),
+    }
 }
 
 /// Holds a Name or RDATA
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+///
+/// The bytes are reference counted, so cloning a [`NameOrRdata`] does not copy them.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[repr(transparent)]
 #[serde(transparent)]
-pub struct NameOrRdata(ByteBuf);
+pub struct NameOrRdata(#[serde(deserialize_with = "crate::zero_copy::deserialize_bytes")] Bytes);
 
-impl NameOrRdata {
-    #[allow(clippy::result_unit_err)]
-    pub fn to_string_domain(&self) -> Result<String, ()> {
-        if self.0.len() > 255 {
-            // A valid domain name is at most 255 bytes long.
-            return Err(());
-        } else if self.0 == [0] {
-            // Special case for empty domain name, since otherwise an empty string is returned, instead of a single dot.
-            return Ok(".".to_string());
-        }
-        let mut res = Vec::with_capacity(self.0.len());
-        let mut pos = 0;
-        loop {
-            let len = self.0[pos];
-            pos += 1;
-            if len == 0 && usize::from(len) + pos == self.0.len() {
-                // This conversion fails is the bytes are not valid UTF-8, but a domain MUST be ASCII.
-                let res = String::from_utf8(res).map_err(|_| ());
-                return res;
-            } else if len == 0 || len > 63 || usize::from(len) + pos > self.0.len() {
-                // len == 0
-                // There are trailing bytes after the last label.
-                //
-                // len > 63
-                // Label too long
-                // A valid label is at most 63 bytes long.
-                //
-                // usize::from(len) + pos > self.0.len()
-                // Current position is past the end of the buffer.
-                return Err(());
-            }
-            res.extend(&self.0[pos as usize..][..len as usize]);
-            res.push(b'.');
-            pos += len as usize;
-        }
+impl From<Bytes> for NameOrRdata {
+    fn from(bytes: Bytes) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<NameOrRdata> for Bytes {
+    fn from(name_or_rdata: NameOrRdata) -> Self {
+        name_or_rdata.0
     }
+}
 
+impl NameOrRdata {
     pub fn as_bytes(&self) -> &[u8] {
         &self.0
     }
@@ -183,10 +644,10 @@ impl NameOrRdata {
 
 impl fmt::Debug for NameOrRdata {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if let Ok(domain) = self.to_string_domain() {
-            f.write_fmt(format_args!("NameOrRdata({:?})", domain))
+        if let Ok(domain) = self.to_domain_name() {
+            f.write_fmt(format_args!("NameOrRdata({:?})", domain.to_string()))
         } else {
-            f.write_fmt(format_args!("NameOrRdata({:?})", self.0))
+            f.write_fmt(format_args!("NameOrRdata({:?})", &*self.0))
         }
     }
 }
@@ -263,22 +724,139 @@ impl From<u32> for UTicks {
 }
 
 /// A [`RRList`] is an array of unsigned integers, indexes to [`RR`] items in the `rr` array.
-pub type RRList = Vec<usize>;
+///
+/// Most lists only contain a few entries, which are stored inline without a separate allocation.
+pub type RRList = SmallVec<[usize; 4]>;
 
 /// A [`QuestionList`] is an array of unsigned integers, indexes to [`Question`] items in the `qrr` array.
-pub type QuestionList = Vec<usize>;
+///
+/// Most lists only contain a single entry, which is stored inline without a separate allocation.
+pub type QuestionList = SmallVec<[usize; 2]>;
+
+/// Custom values with negative or unknown map keys
+///
+/// All map based types keep unknown entries with negative keys, which are reserved for implementation specific extensions.
+/// Entries with unknown positive keys are kept as well, these are fields added by newer minor format versions.
+/// Almost all of these maps are empty, so the map is only allocated once the first value is inserted.
+/// An empty [`ExtraValues`] has the size of a single pointer.
+#[derive(Clone, Default, PartialEq)]
+// The Box shrinks the inline size from three pointers to one, which matters for millions of Q/R items
+#[allow(clippy::box_collection)]
+pub struct ExtraValues(Option<Box<BTreeMap<isize, serde_cbor::Value>>>);
+
+impl ExtraValues {
+    /// Create an empty map without allocating.
+    pub const fn new() -> Self {
+        Self(None)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.as_ref().is_none_or(|map| map.is_empty())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.as_ref().map_or(0, |map| map.len())
+    }
+
+    pub fn get(&self, key: isize) -> Option<&serde_cbor::Value> {
+        self.0.as_ref()?.get(&key)
+    }
+
+    /// Insert a value, returning the previous value with the same key.
+    pub fn insert(&mut self, key: isize, value: serde_cbor::Value) -> Option<serde_cbor::Value> {
+        self.0
+            .get_or_insert_with(Default::default)
+            .insert(key, value)
+    }
+
+    pub fn remove(&mut self, key: isize) -> Option<serde_cbor::Value> {
+        self.0.as_mut()?.remove(&key)
+    }
+
+    /// Iterate over all entries in ascending key order.
+    pub fn iter(&self) -> ExtraValuesIter<'_> {
+        ExtraValuesIter(self.0.as_ref().map(|map| map.iter()))
+    }
+}
+
+impl<'a> IntoIterator for &'a ExtraValues {
+    type Item = (&'a isize, &'a serde_cbor::Value);
+    type IntoIter = ExtraValuesIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the entries of [`ExtraValues`].
+#[derive(Clone, Debug)]
+pub struct ExtraValuesIter<'a>(
+    Option<std::collections::btree_map::Iter<'a, isize, serde_cbor::Value>>,
+);
+
+impl<'a> Iterator for ExtraValuesIter<'a> {
+    type Item = (&'a isize, &'a serde_cbor::Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.as_mut()?.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0
+            .as_ref()
+            .map_or((0, Some(0)), |iter| iter.size_hint())
+    }
+}
+
+impl DoubleEndedIterator for ExtraValuesIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.as_mut()?.next_back()
+    }
+}
+
+impl ExactSizeIterator for ExtraValuesIter<'_> {}
+
+impl From<BTreeMap<isize, serde_cbor::Value>> for ExtraValues {
+    fn from(map: BTreeMap<isize, serde_cbor::Value>) -> Self {
+        if map.is_empty() {
+            Self(None)
+        } else {
+            Self(Some(Box::new(map)))
+        }
+    }
+}
+
+impl fmt::Debug for ExtraValues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
 
 // /////////////////////////////////////////////////////////////////////////////
 // This section contains the main file structure and preamble
 // /////////////////////////////////////////////////////////////////////////////
 
+/// The [`File::file_type_id`] of all C-DNS files.
+pub const FILE_TYPE_ID: &str = "C-DNS";
+/// The supported [`FilePreamble::major_format_version`].
+pub const MAJOR_FORMAT_VERSION: u32 = 1;
+/// The supported [`FilePreamble::minor_format_version`].
+pub const MINOR_FORMAT_VERSION: u32 = 0;
+
 /// A C-DNS file
 ///
 /// Original format descriptoin in [Section 7.3](https://tools.ietf.org/html/rfc8618#section-7.3)
-#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+///
+/// Deserialization fails if the file type id is not [`FILE_TYPE_ID`] or the format version is not supported.
+/// Experimental files can still be read using [`UncheckedFile`].
+///
+/// Files with a newer minor format version are supported.
+/// The fields added in the newer version are kept in the `extra_values` of the respective struct, so they are preserved when re-serializing.
+/// Use [`FilePreamble::has_newer_minor_version`] to detect such files, for example to warn that some data is not understood.
+#[derive(Debug, Serialize_tuple, Deserialize)]
+#[serde(try_from = "UncheckedFile")]
 pub struct File {
     /// String "C-DNS" identifying the file type.
-    // TODO assert that deserialization has value "C-DNS"
     pub file_type_id: String,
     /// Version and parameter information for the whole file.
     pub file_preamble: FilePreamble,
@@ -286,6 +864,123 @@ pub struct File {
     pub file_blocks: Vec<Block>,
 }
 
+impl File {
+    /// Check the file type id and the format version.
+    ///
+    /// This is done automatically during deserialization.
+    pub fn check_header(&self) -> crate::Result<()> {
+        check_header(&self.file_type_id, &self.file_preamble)
+    }
+
+    /// Identifier of the collection method, from the first [`CollectionParameters`] specifying it.
+    pub fn generator_id(&self) -> Option<&str> {
+        self.collection_parameters()
+            .find_map(|params| params.generator_id.as_deref())
+    }
+
+    /// Identifier of the collecting host, from the first [`CollectionParameters`] specifying it.
+    pub fn host_id(&self) -> Option<&str> {
+        self.collection_parameters()
+            .find_map(|params| params.host_id.as_deref())
+    }
+
+    /// Filter for input, from the first [`CollectionParameters`] specifying it.
+    pub fn collection_filter(&self) -> Option<&str> {
+        self.collection_parameters()
+            .find_map(|params| params.filter.as_deref())
+    }
+
+    /// Number of [`Block`]s in the file.
+    pub fn block_count(&self) -> usize {
+        self.file_blocks.len()
+    }
+
+    /// Total number of [`QueryResponse`] items in all [`Block`]s.
+    pub fn query_response_count(&self) -> usize {
+        self.file_blocks
+            .iter()
+            .map(|block| block.query_responses.as_ref().map_or(0, Vec::len))
+            .sum()
+    }
+
+    /// Remove all blocks without any Q/R items, address events, or malformed messages.
+    ///
+    /// Some collectors write such blocks for intervals without any traffic.
+    /// Returns the number of removed blocks.
+    pub fn prune_empty_blocks(&mut self) -> usize {
+        let block_count = self.file_blocks.len();
+        self.file_blocks.retain(|block| !block.is_empty());
+        block_count - self.file_blocks.len()
+    }
+
+    /// All [`CollectionParameters`] in the order of the [`FilePreamble.block_parameters`].
+    fn collection_parameters(&self) -> impl Iterator<Item = &CollectionParameters> {
+        self.file_preamble
+            .block_parameters
+            .iter()
+            .filter_map(|params| params.collection_parameters.as_ref())
+    }
+}
+
+/// Check that the file type id is [`FILE_TYPE_ID`] and the format version is supported.
+pub(crate) fn check_header(file_type_id: &str, file_preamble: &FilePreamble) -> crate::Result<()> {
+    if file_type_id != FILE_TYPE_ID {
+        return Err(Error::InvalidFileType(file_type_id.to_string()));
+    }
+    let (major, minor) = (
+        file_preamble.major_format_version,
+        file_preamble.minor_format_version,
+    );
+    // Newer minor versions only add fields, which are kept in the `extra_values`
+    if major != MAJOR_FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion { major, minor });
+    }
+    Ok(())
+}
+
+/// A C-DNS file with any file type id and format version
+///
+/// The fields are identical to [`File`], but deserialization does not check the file type id and the format version.
+/// This allows reading experimental files, which use a different file type id or an unreleased version of the format.
+///
+/// ```
+/// # use c_dns::serialization::{File, UncheckedFile};
+/// # fn main() -> color_eyre::eyre::Result<()> {
+/// # let bytes = std::fs::read("./tests/data/dns.cdns")?;
+/// let file: File = serde_cbor::from_slice::<UncheckedFile>(&bytes)?.into_file();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct UncheckedFile {
+    /// String identifying the file type, normally "C-DNS".
+    pub file_type_id: String,
+    /// Version and parameter information for the whole file.
+    pub file_preamble: FilePreamble,
+    /// Array of items of type [`Block`].
+    pub file_blocks: Vec<Block>,
+}
+
+impl UncheckedFile {
+    /// Convert into a [`File`] without checking the file type id and the format version.
+    pub fn into_file(self) -> File {
+        File {
+            file_type_id: self.file_type_id,
+            file_preamble: self.file_preamble,
+            file_blocks: self.file_blocks,
+        }
+    }
+}
+
+impl TryFrom<UncheckedFile> for File {
+    type Error = Error;
+
+    fn try_from(file: UncheckedFile) -> Result<Self, Self::Error> {
+        check_header(&file.file_type_id, &file.file_preamble)?;
+        Ok(file.into_file())
+    }
+}
+
 /// Information about data in the file.
 ///
 /// Original format description in [Section 7.3.1](https://tools.ietf.org/html/rfc8618#section-7.3.1).
@@ -295,12 +990,10 @@ pub struct FilePreamble {
     /// Integer with value `1`.
     ///
     /// The major version of the format used in the file.
-    // TODO Assert that deserialization has value 1
     pub major_format_version: u32,
     /// Integer with value `0`.
     ///
     /// The minor version of the format used in the file.
-    // TODO Assert that deserialization has value 0
     pub minor_format_version: u32,
     /// Version indicator available for private use by implementations.
     pub private_version: Option<u32>,
@@ -310,9 +1003,24 @@ pub struct FilePreamble {
     /// (The [`BlockPreamble.block_parameters_index`] item in each [`BlockPreamble`] indicates which array entry applies to that [`Block`].)
     pub block_parameters: Vec<BlockParameters>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
+}
+
+impl FilePreamble {
+    /// The major and minor format version of the file.
+    pub fn format_version(&self) -> (u32, u32) {
+        (self.major_format_version, self.minor_format_version)
+    }
+
+    /// Whether the file uses a newer minor format version than [`MINOR_FORMAT_VERSION`].
+    ///
+    /// Such files can contain fields unknown to this library, which are collected in the `extra_values`.
+    pub fn has_newer_minor_version(&self) -> bool {
+        self.major_format_version == MAJOR_FORMAT_VERSION
+            && self.minor_format_version > MINOR_FORMAT_VERSION
+    }
 }
 
 impl fmt::Debug for FilePreamble {
@@ -331,16 +1039,16 @@ impl fmt::Debug for FilePreamble {
 ///
 /// Original format description in [Section 7.3.1.1](https://tools.ietf.org/html/rfc8618#section-7.3.1.1).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(PartialEq, SerializeIndexed, DeserializeIndexed)]
 pub struct BlockParameters {
     /// Parameters relating to data storage in a [`Block`] item.
     pub storage_parameters: StorageParameters,
     /// Parameters relating to collection of the data in a [`Block`] item.
     pub collection_parameters: Option<CollectionParameters>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
 impl fmt::Debug for BlockParameters {
@@ -357,7 +1065,7 @@ impl fmt::Debug for BlockParameters {
 ///
 /// Original format description in [Section 7.3.1.1.1](https://tools.ietf.org/html/rfc8618#section-7.3.1.1.1).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(PartialEq, SerializeIndexed, DeserializeIndexed)]
 #[serde_indexed(emit_length = false)]
 pub struct StorageParameters {
     /// Sub-second timing is recorded in ticks.
@@ -371,8 +1079,7 @@ pub struct StorageParameters {
     /// Collection of hints as to which fields are omitted in the arrays that have optional fields.
     pub storage_hints: StorageHints,
     /// Array of OPCODES (unsigned integers, each in the range 0 to 15 inclusive) recorded by the collecting implementation.
-    // TODO assert values 0..15
-    pub opcodes: Vec<u8>,
+    pub opcodes: Vec<Opcode>,
     /// Array of RR TYPEs (unsigned integers, each in the range 0 to 65535 inclusive) recorded by the collecting implementation.
     pub rr_types: Vec<DnsType>,
     /// Bit flags indicating attributes of stored data.
@@ -380,31 +1087,45 @@ pub struct StorageParameters {
     /// IPv4 client address prefix length, in the range 1 to 32 inclusive.
     ///
     /// If specified, only the address prefix bits are stored.
-    // TODO assert value is 1..32
     pub client_address_prefix_ipv4: Option<u8>,
     /// IPv6 client address prefix length, in the range 1 to 128 inclusive.
     ///
     /// If specified, only the address prefix bits are stored.
-    // TODO assert value is 1..128
     pub client_address_prefix_ipv6: Option<u8>,
     /// IPv4 server address prefix length, in the range 1 to 32 inclusive.
     ///
     /// If specified, only the address prefix bits are stored.
-    // TODO assert value is 1..32
     pub server_address_prefix_ipv4: Option<u8>,
     /// IPv6 server address prefix length, in the range 1 to 128 inclusive.
     ///
     /// If specified, only the address prefix bits are stored.
-    // TODO assert value is 1..128
     pub server_address_prefix_ipv6: Option<u8>,
     /// Information on the sampling method used.
     pub sampling_method: Option<String>,
     /// Information on the anonymization method used.
     pub anonymization_method: Option<String>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
+}
+
+impl StorageParameters {
+    /// The client address prefix length for addresses of `ip_version`, if only a prefix is stored.
+    pub fn client_address_prefix(&self, ip_version: crate::IpVersion) -> Option<u8> {
+        match ip_version {
+            crate::IpVersion::Ipv4 => self.client_address_prefix_ipv4,
+            crate::IpVersion::Ipv6 => self.client_address_prefix_ipv6,
+        }
+    }
+
+    /// The server address prefix length for addresses of `ip_version`, if only a prefix is stored.
+    pub fn server_address_prefix(&self, ip_version: crate::IpVersion) -> Option<u8> {
+        match ip_version {
+            crate::IpVersion::Ipv4 => self.server_address_prefix_ipv4,
+            crate::IpVersion::Ipv6 => self.server_address_prefix_ipv6,
+        }
+    }
 }
 
 impl fmt::Debug for StorageParameters {
@@ -415,10 +1136,10 @@ impl fmt::Debug for StorageParameters {
             .field("storage_hints", &self.storage_hints)
             .field("opcodes", &self.opcodes)
             .field("rr_types", &self.rr_types);
+        crate::debug_unwrap_option_flags_field!(self, ds, storage_flags,);
         crate::debug_unwrap_option_single_field!(
             self,
             ds,
-            storage_flags,
             client_address_prefix_ipv4,
             client_address_prefix_ipv6,
             server_address_prefix_ipv4,
@@ -449,7 +1170,7 @@ pub enum StorageFlags {
 /// In other words, where a map contains another map, the hint on the containing map overrides any hints in the contained map and the contained map is omitted.
 ///
 /// Original format description in [Section 7.3.1.1.1.1](https://tools.ietf.org/html/rfc8618#section-7.3.1.1.1.1).
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Clone, PartialEq, SerializeIndexed, DeserializeIndexed)]
 pub struct StorageHints {
     /// Hints indicating which [`QueryResponse`] fields are omitted.
     pub query_response_hints: EnumSet<QueryResponseHints>,
@@ -460,21 +1181,21 @@ pub struct StorageHints {
     /// Hints indicating which other datatypes are omitted.
     pub other_data_hints: EnumSet<OtherDataHints>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
 impl fmt::Debug for StorageHints {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut ds = f.debug_struct("StorageHints");
-        ds.field("query_response_hints", &self.query_response_hints);
+        ds.field("query_response_hints", &self.query_response_hints.display());
         ds.field(
             "query_response_signature_hints",
-            &self.query_response_signature_hints,
+            &self.query_response_signature_hints.display(),
         );
-        ds.field("rr_hints", &self.rr_hints);
-        ds.field("other_data_hints", &self.other_data_hints);
+        ds.field("rr_hints", &self.rr_hints.display());
+        ds.field("other_data_hints", &self.other_data_hints.display());
         crate::debug_extra_values!(self, ds, extra_values);
         ds.finish()
     }
@@ -604,7 +1325,7 @@ pub enum OtherDataHints {
 ///
 /// Original format description in [Section 7.3.1.1.2](https://tools.ietf.org/html/rfc8618#section-7.3.1.1.2).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Default, PartialEq, SerializeIndexed, DeserializeIndexed)]
 #[serde_indexed(emit_length = false)]
 pub struct CollectionParameters {
     /// To be matched with a Query, a Response must arrive within this number of milliseconds.
@@ -626,8 +1347,8 @@ pub struct CollectionParameters {
     /// Array of identifiers (of type unsigned integer, each in the range 1 to 4094 inclusive) of VLANs IEEE802.1Q selected for collection.
     ///
     /// VLAN IDs are unique only within an administrative domain.
-    // TODO assert values 1..4094
-    pub vlan_ids: Option<u16>,
+    /// [`CollectionParametersBuilder::build`](crate::builder::CollectionParametersBuilder::build) rejects IDs outside of the range, and [`File::from_reader_lenient`] reports them.
+    pub vlan_ids: Option<Vec<u16>>,
     /// Filter for input, in "tcpdump" pcap-filter style.
     pub filter: Option<String>,
     /// Implementation-specific human-readable string identifying the collection method.
@@ -635,9 +1356,9 @@ pub struct CollectionParameters {
     /// String identifying the collecting host.
     pub host_id: Option<String>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
 crate::debug_unwrap_option_fields!(
@@ -658,7 +1379,7 @@ crate::debug_unwrap_option_fields!(
 ///
 /// Original format description in [Section 7.3.2](https://tools.ietf.org/html/rfc8618#section-7.3.2).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Clone, SerializeIndexed, DeserializeIndexed)]
 #[serde_indexed(emit_length = false)]
 pub struct Block {
     /// Overall information for the [`Block`] item.
@@ -674,9 +1395,20 @@ pub struct Block {
     /// Details of malformed DNS messages.
     pub malformed_messages: Option<Vec<MalformedMessage>>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
+}
+
+impl Block {
+    /// Whether the block contains no Q/R items, address events, or malformed messages.
+    ///
+    /// The [`BlockTables`] and [`BlockStatistics`] are not considered.
+    pub fn is_empty(&self) -> bool {
+        self.query_responses.as_ref().is_none_or(Vec::is_empty)
+            && self.address_event_counts.as_ref().is_none_or(Vec::is_empty)
+            && self.malformed_messages.as_ref().is_none_or(Vec::is_empty)
+    }
 }
 
 impl fmt::Debug for Block {
@@ -701,7 +1433,7 @@ impl fmt::Debug for Block {
 ///
 /// Original format description in [Section 7.3.2.1](https://tools.ietf.org/html/rfc8618#section-7.3.2.1).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Clone, SerializeIndexed, DeserializeIndexed)]
 pub struct BlockPreamble {
     /// A timestamp for the earliest record in the [`Block`] item.
     ///
@@ -712,9 +1444,9 @@ pub struct BlockPreamble {
     /// If not present, index 0 is used.
     pub block_parameters_index: Option<usize>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
 crate::debug_unwrap_option_fields!(BlockPreamble, earliest_time, block_parameters_index,);
@@ -723,7 +1455,7 @@ crate::debug_unwrap_option_fields!(BlockPreamble, earliest_time, block_parameter
 ///
 /// Original format description in [Section 7.3.2.2](https://tools.ietf.org/html/rfc8618#section-7.3.2.2).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Clone, SerializeIndexed, DeserializeIndexed)]
 #[serde_indexed(emit_length = false)]
 pub struct BlockStatistics {
     /// Total number of well-formed DNS messages processed from the input traffic stream during collection of data in this [`Block`] item.
@@ -735,13 +1467,13 @@ pub struct BlockStatistics {
     /// Number of unmatched Responses in this [`Block`] item.
     pub unmatched_responses: Option<usize>,
     /// Number of DNS messages processed from the input traffic stream during collection of data in this [`Block`] item but not recorded because their OPCODE is not in the list to be collected.
-    pub discarded_opcode: Option<u8>,
+    pub discarded_opcode: Option<usize>,
     /// Number of malformed messages processed from the input traffic stream during collection of data in this [`Block`] item.
     pub malformed_items: Option<usize>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
 crate::debug_unwrap_option_fields!(
@@ -764,7 +1496,7 @@ crate::debug_unwrap_option_fields!(
 ///
 /// Original format description in [Section 7.3.2.3](https://tools.ietf.org/html/rfc8618#section-7.3.2.3).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Clone, SerializeIndexed, DeserializeIndexed)]
 #[serde_indexed(emit_length = false)]
 pub struct BlockTables {
     /// Array of IP addresses, in network byte order (of type byte string).
@@ -806,9 +1538,9 @@ pub struct BlockTables {
     /// Array of the contents of malformed messages.
     pub malformed_message_data: Option<Vec<MalformedMessageData>>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
 crate::debug_unwrap_option_fields!(
@@ -827,7 +1559,9 @@ crate::debug_unwrap_option_fields!(
 /// RR CLASS and TYPE information.
 ///
 /// Original format description in [Section 7.3.2.3.1](https://tools.ietf.org/html/rfc8618#section-7.3.2.3.1).
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, SerializeIndexed, DeserializeIndexed,
+)]
 pub struct ClassType {
     /// TYPE value.
     pub type_: DnsType,
@@ -837,8 +1571,7 @@ pub struct ClassType {
 
 impl fmt::Debug for ClassType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        /* OPT */
-        if self.type_ == DnsType(41) {
+        if self.type_ == DnsType::OPT {
             f.write_fmt(format_args!("OPT (UDP Size: {})", u16::from(self.class)))
         } else {
             f.write_fmt(format_args!("{:?} {:?}", self.type_, self.class))
@@ -846,26 +1579,14 @@ impl fmt::Debug for ClassType {
     }
 }
 
-// TODO some fields serialize in a different order than compactor
-//
-// This is the order of some of the fields
-// 2: 1
-// 6: 129
-// 4: f
-// 9: 1
-// 8: 0
-// 7: 0
-// 5: 0
-// a: 0
-// c: 1
-// b: 0
-// d: 0
+// Compactor writes some fields of the signature in a different order than they are serialized here.
+// `EncodingMetadata` in the `encoding` module restores the original key order for byte-exact round-trips.
 
 /// Elements of a Q/R data item that are often common between multiple individual Q/R data items.
 ///
 /// Original format description in [Section 7.3.2.3.2](https://tools.ietf.org/html/rfc8618#section-7.3.2.3.2).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Clone, SerializeIndexed, DeserializeIndexed)]
 pub struct QueryResponseSignature {
     /// The index in the [`BlockTables.ip_address`] array of the server IP address.
     pub server_address_index: Option<usize>,
@@ -878,7 +1599,7 @@ pub struct QueryResponseSignature {
     /// Bit flags explicitly indicating attributes of the message pair represented by this Q/R data item (not all attributes may be recorded or deducible).
     pub qr_sig_flags: Option<EnumSet<QueryResponseFlags>>,
     /// Query OPCODE.
-    pub query_opcode: Option<u8>,
+    pub query_opcode: Option<Opcode>,
     /// Bit flags with values from the Query and Response DNS flags.
     ///
     /// Flag values are 0 if the Query or Response is not present.
@@ -908,31 +1629,43 @@ pub struct QueryResponseSignature {
     /// If the Response contains an OPT RR, this value incorporates any EXTENDED-RCODE value.
     pub response_rcode: Option<u16>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
-crate::debug_unwrap_option_fields!(
-    QueryResponseSignature,
-    server_address_index,
-    server_port,
-    qr_transport_flags,
-    qr_type,
-    qr_sig_flags,
-    query_opcode,
-    qr_dns_flags,
-    query_rcode,
-    query_classtype_index,
-    query_qdcount,
-    query_ancount,
-    query_nscount,
-    query_arcount,
-    query_edns_version,
-    query_udp_size,
-    query_opt_rdata_index,
-    response_rcode,
-);
+impl fmt::Debug for QueryResponseSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ds = f.debug_struct("QueryResponseSignature");
+        crate::debug_unwrap_option_single_field!(
+            self,
+            ds,
+            server_address_index,
+            server_port,
+            qr_transport_flags,
+            qr_type,
+        );
+        crate::debug_unwrap_option_flags_field!(self, ds, qr_sig_flags,);
+        crate::debug_unwrap_option_single_field!(self, ds, query_opcode,);
+        crate::debug_unwrap_option_flags_field!(self, ds, qr_dns_flags,);
+        crate::debug_unwrap_option_single_field!(
+            self,
+            ds,
+            query_rcode,
+            query_classtype_index,
+            query_qdcount,
+            query_ancount,
+            query_nscount,
+            query_arcount,
+            query_edns_version,
+            query_udp_size,
+            query_opt_rdata_index,
+            response_rcode,
+        );
+        crate::debug_extra_values!(self, ds, extra_values);
+        ds.finish()
+    }
+}
 
 /// Bit flags describing the transport used to service the Query.
 ///
@@ -946,60 +1679,165 @@ crate::debug_unwrap_option_fields!(
 ///     * 15 = Non-standard transport (see below)
 ///     * Values 5-14 are reserved for future use.
 /// * Bit 5. `1` if trailing bytes in Query packet.
-#[derive(Serialize, Deserialize)]
+///
+/// The bits 6 and 7 are not assigned at the time of writing, but are kept as [`TransportFlags::extension_bits`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct TransportFlags(u8);
 
+impl From<u8> for TransportFlags {
+    fn from(bits: u8) -> Self {
+        Self(bits)
+    }
+}
+
+impl From<TransportFlags> for u8 {
+    fn from(flags: TransportFlags) -> Self {
+        flags.0
+    }
+}
+
 impl TransportFlags {
+    const IPV6: u8 = 0b0000_0001;
+    const TRANSPORT: u8 = 0b0001_1110;
+    const TRAILING_DATA: u8 = 0b0010_0000;
+    const EXTENSION: u8 = 0b1100_0000;
+
+    /// Create the flags for a transport.
+    pub fn new(
+        ip_version: crate::IpVersion,
+        transport: crate::Transport,
+        has_trailing_data: bool,
+    ) -> Self {
+        Self::from_parts(ip_version, u8::from(transport), has_trailing_data)
+    }
+
+    /// Create the flags from the raw 4-bit transport code.
+    ///
+    /// This allows using transport codes which are reserved at the time of writing.
+    /// Fails if the code does not fit into 4 bits.
+    pub fn with_transport_code(
+        ip_version: crate::IpVersion,
+        transport_code: u8,
+        has_trailing_data: bool,
+    ) -> crate::Result<Self> {
+        crate::Transport::try_from(transport_code)?;
+        Ok(Self::from_parts(
+            ip_version,
+            transport_code,
+            has_trailing_data,
+        ))
+    }
+
+    fn from_parts(
+        ip_version: crate::IpVersion,
+        transport_code: u8,
+        has_trailing_data: bool,
+    ) -> Self {
+        let mut flags = Self(0);
+        flags.set_ip_version(ip_version);
+        flags.set_transport_bits(transport_code);
+        flags.set_trailing_data(has_trailing_data);
+        flags
+    }
+
+    /// The raw bit pattern, including bits not assigned at the time of writing.
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// The bits 6 and 7, which are not assigned at the time of writing.
+    ///
+    /// The bits keep their position, so the other bits are always 0.
+    pub fn extension_bits(&self) -> u8 {
+        self.0 & Self::EXTENSION
+    }
+
+    /// Replace the bits 6 and 7, which are not assigned at the time of writing.
+    ///
+    /// Only the bits 6 and 7 of `bits` are used, see [`TransportFlags::extension_bits`].
+    pub fn set_extension_bits(&mut self, bits: u8) {
+        self.0 = (self.0 & !Self::EXTENSION) | (bits & Self::EXTENSION);
+    }
+
+    pub fn set_ip_version(&mut self, ip_version: crate::IpVersion) {
+        self.0 &= !Self::IPV6;
+        if ip_version == crate::IpVersion::Ipv6 {
+            self.0 |= Self::IPV6;
+        }
+    }
+
+    /// Set the transport, see [`TransportFlags::new`].
+    pub fn set_transport(&mut self, transport: crate::Transport) {
+        self.set_transport_bits(u8::from(transport));
+    }
+
+    /// Set the raw 4-bit transport code.
+    ///
+    /// Fails if the code does not fit into 4 bits, see [`TransportFlags::with_transport_code`].
+    pub fn set_transport_code(&mut self, transport_code: u8) -> crate::Result<()> {
+        crate::Transport::try_from(transport_code)?;
+        self.set_transport_bits(transport_code);
+        Ok(())
+    }
+
+    /// Set the transport code, using only its lowest 4 bits.
+    fn set_transport_bits(&mut self, transport_code: u8) {
+        self.0 = (self.0 & !Self::TRANSPORT) | ((transport_code << 1) & Self::TRANSPORT);
+    }
+
+    pub fn set_trailing_data(&mut self, has_trailing_data: bool) {
+        self.0 &= !Self::TRAILING_DATA;
+        if has_trailing_data {
+            self.0 |= Self::TRAILING_DATA;
+        }
+    }
+
     pub fn is_ipv4(&self) -> bool {
-        self.0 & 0b0000_0001 == 0
+        self.0 & Self::IPV6 == 0
     }
 
     pub fn is_ipv6(&self) -> bool {
         !self.is_ipv4()
     }
 
-    pub fn transport_protocol(&self) -> crate::Transport {
-        // Bit 1..=4 are for Transport
-        let transport = (self.0 & 0b0001_1110) >> 1;
-        match transport {
-            0 => crate::Transport::Udp,
-            1 => crate::Transport::Tcp,
-            2 => crate::Transport::Tls,
-            3 => crate::Transport::Dtls,
-            4 => crate::Transport::Https,
-            15 => crate::Transport::NonStandard,
-            _ => crate::Transport::Reserved,
+    pub fn ip_version(&self) -> crate::IpVersion {
+        if self.is_ipv4() {
+            crate::IpVersion::Ipv4
+        } else {
+            crate::IpVersion::Ipv6
         }
     }
 
+    /// The raw 4-bit transport code.
+    ///
+    /// Unlike [`TransportFlags::transport_protocol`] this keeps the value of reserved transport codes.
+    pub fn transport_code(&self) -> u8 {
+        // Bit 1..=4 are for Transport
+        (self.0 & Self::TRANSPORT) >> 1
+    }
+
+    pub fn transport_protocol(&self) -> crate::Transport {
+        crate::Transport::try_from(self.transport_code())
+            .expect("The transport code has only 4 bits")
+    }
+
     pub fn has_trailing_data(&self) -> bool {
-        self.0 & 0b0010_0000 != 0
+        self.0 & Self::TRAILING_DATA != 0
     }
 }
 
 impl fmt::Debug for TransportFlags {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // First bit of TransportFlagValues is ip-version
-        if self.is_ipv4() {
-            f.write_str("IPv4")?;
-        } else {
-            f.write_str("IPv6")?;
-        }
-
-        f.write_str(match self.transport_protocol() {
-            crate::Transport::Udp => " | UDP",
-            crate::Transport::Tcp => " | TCP",
-            crate::Transport::Tls => " | TLS",
-            crate::Transport::Dtls => " | DTLS",
-            crate::Transport::Https => " | HTTPS",
-            crate::Transport::Reserved => " | Reserved",
-            crate::Transport::NonStandard => " | Non-Standard",
-        })?;
+        write!(f, "{} | {}", self.ip_version(), self.transport_protocol())?;
 
         if self.has_trailing_data() {
             f.write_str(" | Query has trailing data")?;
         }
+        if self.extension_bits() != 0 {
+            write!(f, " | Extension bits {:#010b}", self.extension_bits())?;
+        }
         Ok(())
     }
 }
@@ -1008,22 +1846,53 @@ impl fmt::Debug for TransportFlags {
 ///
 /// The dnstap schema is hosted in this repository:
 /// <https://github.com/dnstap/dnstap.pb/blob/master/dnstap.proto>
-#[derive(Debug, Serialize_repr, Deserialize_repr)]
-#[serde(deny_unknown_fields)]
-#[repr(u8)]
+///
+/// Values not defined at the time of writing are kept as [`QueryResponseType::Reserved`], such that files written by newer implementations can still be parsed and re-serialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "u8", into = "u8")]
 pub enum QueryResponseType {
     /// A transaction between a stub resolver and a DNS server from the perspective of the stub resolver.
-    Stub = 0,
+    Stub,
     /// A transaction between a client and a DNS server (a proxy or full recursive resolver) from the perspective of the DNS server.
-    Client = 1,
+    Client,
     /// A transaction between a recursive resolver and an authoritative server from the perspective of the recursive resolver.
-    Resolver = 2,
+    Resolver,
     /// A transaction between a recursive resolver and an authoritative server from the perspective of the authoritative server.
-    Authoritative = 3,
+    Authoritative,
     /// A transaction between a downstream forwarder and an upstream DNS server (a recursive resolver) from the perspective of the downstream forwarder.
-    Forwarder = 4,
+    Forwarder,
     /// A transaction between a DNS software tool and a DNS server, from the perspective of the tool.
-    Tool = 5,
+    Tool,
+    /// Reserved value, with the raw transaction type.
+    Reserved(u8),
+}
+
+impl From<QueryResponseType> for u8 {
+    fn from(qr_type: QueryResponseType) -> Self {
+        match qr_type {
+            QueryResponseType::Stub => 0,
+            QueryResponseType::Client => 1,
+            QueryResponseType::Resolver => 2,
+            QueryResponseType::Authoritative => 3,
+            QueryResponseType::Forwarder => 4,
+            QueryResponseType::Tool => 5,
+            QueryResponseType::Reserved(value) => value,
+        }
+    }
+}
+
+impl From<u8> for QueryResponseType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Stub,
+            1 => Self::Client,
+            2 => Self::Resolver,
+            3 => Self::Authoritative,
+            4 => Self::Forwarder,
+            5 => Self::Tool,
+            value => Self::Reserved(value),
+        }
+    }
 }
 
 /// Bit flags explicitly indicating attributes of the message pair represented by this Q/R data item (not all attributes may be recorded or deducible).
@@ -1085,7 +1954,7 @@ pub enum DNSFlags {
 /// Details on individual Questions in a Question section.
 ///
 /// Original format description in [Section 7.3.2.3.3](https://tools.ietf.org/html/rfc8618#section-7.3.2.3.3).
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Clone, SerializeIndexed, DeserializeIndexed)]
 #[serde_indexed(emit_length = false)]
 pub struct Question {
     /// The index in the [`BlockTables.name_rdata`] array of the QNAME.
@@ -1093,9 +1962,9 @@ pub struct Question {
     /// The index in the [`BlockTables.classtype`] array of the CLASS and TYPE of the Question.
     pub classtype_index: usize,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
 impl fmt::Debug for Question {
@@ -1112,7 +1981,7 @@ impl fmt::Debug for Question {
 ///
 /// Original format description in [Section 7.3.2.3.4](https://tools.ietf.org/html/rfc8618#section-7.3.2.3.4).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Clone, SerializeIndexed, DeserializeIndexed)]
 pub struct RR {
     /// The index in the [`BlockTables.name_rdata`] array of the NAME.
     pub name_index: usize,
@@ -1123,9 +1992,9 @@ pub struct RR {
     /// The index in the [`BlockTables.name_rdata`] array of the RR RDATA.
     pub rdata_index: Option<usize>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
 impl fmt::Debug for RR {
@@ -1143,7 +2012,7 @@ impl fmt::Debug for RR {
 ///
 /// Original format description in [Section 7.3.2.3.5](https://tools.ietf.org/html/rfc8618#section-7.3.2.3.5).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Clone, SerializeIndexed, DeserializeIndexed)]
 #[serde_indexed(emit_length = false)]
 pub struct MalformedMessageData {
     /// The index in the [`BlockTables.ip_address`] array of the server IP address.
@@ -1153,11 +2022,11 @@ pub struct MalformedMessageData {
     /// Bit flags describing the transport used to service the Query.
     pub mm_transport_flags: Option<TransportFlags>,
     /// The payload (raw bytes) of the DNS message.
-    pub mm_payload: Option<ByteBuf>,
+    pub mm_payload: Option<Bytes>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
 crate::debug_unwrap_option_fields!(
@@ -1179,7 +2048,7 @@ crate::debug_unwrap_option_fields!(
 ///
 /// Original format description in [Section 7.3.2.4](https://tools.ietf.org/html/rfc8618#section-7.3.2.4).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Clone, SerializeIndexed, DeserializeIndexed)]
 #[serde_indexed(emit_length = false)]
 pub struct QueryResponse {
     /// Q/R timestamp as an offset in ticks from [`BlockPreamble.earliest_time`].
@@ -1214,9 +2083,9 @@ pub struct QueryResponse {
     /// Extended Response data.
     pub response_extended: Option<QueryResponseExtended>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
 crate::debug_unwrap_option_fields!(
@@ -1240,28 +2109,45 @@ crate::debug_unwrap_option_fields!(
 ///
 /// Original format description in [Section 7.3.2.4.1](https://tools.ietf.org/html/rfc8618#section-7.3.2.4.1).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Clone, SerializeIndexed, DeserializeIndexed)]
 #[serde_indexed(emit_length = false)]
 pub struct ResponseProcessingData {
     /// The index in the [`BlockTables.name_rdata`] array of the owner name for the Response bailiwick.
     pub bailiwick_index: Option<usize>,
     /// Flags relating to Response processing.
-    pub processing_flags: Option<ResponseProcessingFlags>,
+    pub processing_flags: Option<EnumSet<ResponseProcessingFlags>>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
-crate::debug_unwrap_option_fields!(ResponseProcessingData, bailiwick_index, processing_flags,);
+impl fmt::Debug for ResponseProcessingData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ds = f.debug_struct("ResponseProcessingData");
+        crate::debug_unwrap_option_single_field!(self, ds, bailiwick_index,);
+        crate::debug_unwrap_option_flags_field!(self, ds, processing_flags,);
+        crate::debug_extra_values!(self, ds, extra_values);
+        ds.finish()
+    }
+}
 
 /// Flags relating to Response processing.
 ///
 /// * Bit 0. 1 if the Response came from cache.
-#[derive(Debug, Serialize_repr, Deserialize_repr)]
-#[repr(u8)]
+///
+/// The bits 1 to 7 are not assigned at the time of writing.
+/// They have their own variants, such that flags written by newer implementations survive a round-trip.
+#[derive(Debug, EnumSetType)]
 pub enum ResponseProcessingFlags {
     FromCache = 0,
+    Unassigned1 = 1,
+    Unassigned2 = 2,
+    Unassigned3 = 3,
+    Unassigned4 = 4,
+    Unassigned5 = 5,
+    Unassigned6 = 6,
+    Unassigned7 = 7,
 }
 
 /// Extended data on the Q/R data item.
@@ -1271,7 +2157,7 @@ pub enum ResponseProcessingFlags {
 ///
 /// Original format description in [Section 7.3.2.4.2](https://tools.ietf.org/html/rfc8618#section-7.3.2.4.2).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Clone, SerializeIndexed, DeserializeIndexed)]
 #[serde_indexed(emit_length = false)]
 pub struct QueryResponseExtended {
     /// The index in the [`BlockTables.qlist`] array of the entry listing any second and subsequent Questions in the Question section for the Query or Response.
@@ -1285,9 +2171,9 @@ pub struct QueryResponseExtended {
     ///  Note that Query OPT RR data can optionally be stored in the QuerySignature.
     pub additional_index: Option<usize>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
 crate::debug_unwrap_option_fields!(
@@ -1302,7 +2188,7 @@ crate::debug_unwrap_option_fields!(
 ///
 /// Original format description in [Section 7.3.2.5](https://tools.ietf.org/html/rfc8618#section-7.3.2.5).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Clone, SerializeIndexed, DeserializeIndexed)]
 pub struct AddressEventCount {
     /// The type of event.
     pub ae_type: AddressEventType,
@@ -1318,9 +2204,9 @@ pub struct AddressEventCount {
     /// The number of occurrences of this event during the [`Block`] collection period.
     pub ae_count: usize,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
 impl fmt::Debug for AddressEventCount {
@@ -1344,22 +2230,54 @@ impl fmt::Debug for AddressEventCount {
 /// * `3`: ICMPv6 time exceeded.
 /// * `4`: ICMPv6 destination unreachable.
 /// * `5`: ICMPv6 packet too big.
-#[derive(Debug, Serialize_repr, Deserialize_repr)]
-#[repr(u8)]
+///
+/// Any other value is kept as [`AddressEventType::Reserved`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "u8", into = "u8")]
 pub enum AddressEventType {
-    TcpReset = 0,
-    IcmpTimeExceeded = 1,
-    IcmpDestinationUnreachable = 2,
-    Icmpv6TimeExceeded = 3,
-    Icmpv6DestinationUnreachable = 4,
-    Icmpv6PacketTooBig = 5,
+    TcpReset,
+    IcmpTimeExceeded,
+    IcmpDestinationUnreachable,
+    Icmpv6TimeExceeded,
+    Icmpv6DestinationUnreachable,
+    Icmpv6PacketTooBig,
+    /// Reserved value, with the raw address event type.
+    Reserved(u8),
+}
+
+impl From<AddressEventType> for u8 {
+    fn from(ae_type: AddressEventType) -> Self {
+        match ae_type {
+            AddressEventType::TcpReset => 0,
+            AddressEventType::IcmpTimeExceeded => 1,
+            AddressEventType::IcmpDestinationUnreachable => 2,
+            AddressEventType::Icmpv6TimeExceeded => 3,
+            AddressEventType::Icmpv6DestinationUnreachable => 4,
+            AddressEventType::Icmpv6PacketTooBig => 5,
+            AddressEventType::Reserved(value) => value,
+        }
+    }
+}
+
+impl From<u8> for AddressEventType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::TcpReset,
+            1 => Self::IcmpTimeExceeded,
+            2 => Self::IcmpDestinationUnreachable,
+            3 => Self::Icmpv6TimeExceeded,
+            4 => Self::Icmpv6DestinationUnreachable,
+            5 => Self::Icmpv6PacketTooBig,
+            value => Self::Reserved(value),
+        }
+    }
 }
 
 /// Details on Malformed Message data items.
 ///
 /// Original format description in [Section 7.3.2.6](https://tools.ietf.org/html/rfc8618#section-7.3.2.6).
 #[skip_serializing_none]
-#[derive(SerializeIndexed, DeserializeIndexed)]
+#[derive(Clone, SerializeIndexed, DeserializeIndexed)]
 #[serde_indexed(emit_length = false)]
 pub struct MalformedMessage {
     /// Message timestamp as an offset in ticks from [`BlockPreamble.earliest_time`].
@@ -1371,9 +2289,9 @@ pub struct MalformedMessage {
     /// The index in the [`BlockTables.malformed_message_data`] array of the message data for this message.
     pub message_data_index: Option<usize>,
 
-    /// Collect additional custom values with negative index values.
+    /// Collect additional custom values with negative index values, and unknown fields added in newer minor format versions.
     #[serde_indexed(extras)]
-    pub extra_values: BTreeMap<isize, serde_cbor::Value>,
+    pub extra_values: ExtraValues,
 }
 
 crate::debug_unwrap_option_fields!(
@@ -0,0 +1,332 @@
+//! Re-partition the Q/R items of blocks
+//!
+//! [`Block::split`] cuts a block into blocks of at most a given number of Q/R items.
+//! [`File::reblock`] re-partitions all blocks of a file, either by the number of Q/R items or by aligned time windows, for example to turn minute blocks into hourly ones or to cap the block size of a long capture.
+//! Both honor the `max_block_items` of the [`BlockParameters`].
+//!
+//! Each new block gets its own [`BlockTables`] with only the entries its items reference, and its `earliest_time` is the time of its earliest item.
+//!
+//! ```
+//! # use c_dns::serialization::File;
+//! # use c_dns::split::BlockLimit;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let file = File::read_path("./tests/data/dns.cdns")?;
+//! let file = file.reblock(BlockLimit::Items(5))?;
+//! assert_eq!(3, file.block_count());
+//! assert_eq!(12, file.iter_all_query_responses().count());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::edit::ticks;
+use crate::error::bail;
+use crate::merge::{
+    empty_block, merged_time, query_response_count, shift_time_offsets, time_offsets,
+};
+use crate::serialization::*;
+use crate::tables::extract_tables;
+use crate::Result;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// How [`File::reblock`] partitions the Q/R items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockLimit {
+    /// At most this many Q/R items per block.
+    Items(usize),
+    /// All Q/R items of a block lie in the same time window of this length.
+    ///
+    /// The windows are aligned to the POSIX epoch, so a window of one hour starts at each full hour.
+    Duration(Duration),
+}
+
+impl From<usize> for BlockLimit {
+    fn from(items: usize) -> Self {
+        Self::Items(items)
+    }
+}
+
+impl From<Duration> for BlockLimit {
+    fn from(duration: Duration) -> Self {
+        Self::Duration(duration)
+    }
+}
+
+impl Block {
+    /// Split the block into blocks of at most `max_items` Q/R items each, keeping the order of the items.
+    ///
+    /// The address event counts and malformed messages stay in the first block.
+    /// Each block gets the [`BlockTables`] entries its items reference and the `earliest_time` of its earliest item.
+    /// If the block has [`BlockStatistics`], they are recomputed with [`Block::compute_statistics`] for each new block.
+    ///
+    /// Fails if `max_items` is zero.
+    ///
+    /// # Panics
+    ///
+    /// The indices in the block must be valid, as checked by [`File::dangling_indices`].
+    pub fn split(
+        mut self,
+        max_items: usize,
+        block_parameters: &BlockParameters,
+    ) -> Result<Vec<Block>> {
+        if max_items == 0 {
+            bail!("Cannot split a block into blocks of zero Q/R items");
+        }
+        let query_responses = match self.query_responses.take() {
+            Some(query_responses) if !query_responses.is_empty() => query_responses,
+            query_responses => {
+                self.query_responses = query_responses;
+                return Ok(vec![self]);
+            }
+        };
+        let groups = chunks(query_responses, max_items, max_items);
+        Ok(self.partition(groups, block_parameters))
+    }
+
+    /// Move each group of Q/R items into a new block.
+    ///
+    /// `groups` must not be empty.
+    fn partition(
+        mut self,
+        groups: Vec<Vec<QueryResponse>>,
+        block_parameters: &BlockParameters,
+    ) -> Vec<Block> {
+        let ticks_per_second = i128::from(u32::from(
+            block_parameters.storage_parameters.ticks_per_second,
+        ));
+        let block_tables = self.block_tables.take();
+        let mut address_event_counts = self.address_event_counts.take();
+        let mut malformed_messages = self.malformed_messages.take();
+
+        groups
+            .into_iter()
+            .map(|query_responses| {
+                let mut block = Block {
+                    block_preamble: BlockPreamble {
+                        earliest_time: self.block_preamble.earliest_time,
+                        block_parameters_index: self.block_preamble.block_parameters_index,
+                        extra_values: self.block_preamble.extra_values.clone(),
+                    },
+                    block_statistics: None,
+                    block_tables: None,
+                    query_responses: Some(query_responses),
+                    address_event_counts: address_event_counts.take(),
+                    malformed_messages: malformed_messages.take(),
+                    extra_values: self.extra_values.clone(),
+                };
+                if let Some(block_tables) = &block_tables {
+                    block.block_tables = Some(extract_tables(
+                        block_tables,
+                        block.query_responses.as_deref_mut().unwrap_or(&mut []),
+                        block.address_event_counts.as_deref_mut().unwrap_or(&mut []),
+                        block.malformed_messages.as_deref_mut().unwrap_or(&mut []),
+                    ));
+                }
+                let earliest_offset = time_offsets(&block).min();
+                if let (Some(earliest_time), Some(offset), true) = (
+                    block.block_preamble.earliest_time,
+                    earliest_offset,
+                    ticks_per_second > 0,
+                ) {
+                    let offset = i128::from(u32::from(offset));
+                    shift_time_offsets(&mut block, -offset);
+                    block.block_preamble.earliest_time = Some(timestamp(
+                        ticks(earliest_time, ticks_per_second) + offset,
+                        ticks_per_second,
+                    ));
+                }
+                if self.block_statistics.is_some() {
+                    block.block_statistics = Some(block.compute_statistics());
+                }
+                block
+            })
+            .collect()
+    }
+}
+
+impl File {
+    /// Re-partition the Q/R items of all blocks according to `limit`.
+    ///
+    /// Blocks are cut at the limit and at the `max_block_items` of their [`BlockParameters`].
+    /// Consecutive blocks with the same [`BlockParameters`] are filled up with the Q/R items of the following blocks, so small blocks are combined as with [`File::merge`].
+    /// With [`BlockLimit::Duration`], the Q/R items of a block are grouped by window, and items of the same window in different blocks are only combined if the blocks are consecutive.
+    /// Otherwise, the Q/R items keep their order.
+    ///
+    /// See [`Block::split`] for the content of the new blocks.
+    /// Fails if the limit allows no items or a block references non-existing [`BlockParameters`].
+    pub fn reblock(mut self, limit: impl Into<BlockLimit>) -> Result<File> {
+        let limit = limit.into();
+        let mut reblocked: Vec<(Block, i128)> = Vec::with_capacity(self.file_blocks.len());
+        for (block_idx, mut block) in std::mem::take(&mut self.file_blocks)
+            .into_iter()
+            .enumerate()
+        {
+            let block_parameters_index = block.block_preamble.block_parameters_index.unwrap_or(0);
+            let block_parameters = match self
+                .file_preamble
+                .block_parameters
+                .get(block_parameters_index)
+            {
+                Some(block_parameters) => block_parameters,
+                None => bail!(
+                    "Block {} references the non-existing BlockParameters {}",
+                    block_idx,
+                    block_parameters_index
+                ),
+            };
+            let max_block_items = block_parameters.storage_parameters.max_block_items;
+            let max_items = match limit {
+                BlockLimit::Items(items) => items.min(max_block_items),
+                BlockLimit::Duration(_) => max_block_items,
+            };
+            if max_items == 0 {
+                bail!("Cannot split a block into blocks of zero Q/R items");
+            }
+            let windows = match limit {
+                BlockLimit::Items(_) => Windows::Single,
+                BlockLimit::Duration(duration) => Windows::new(duration, &block, block_parameters)?,
+            };
+
+            let query_responses = block.query_responses.take().unwrap_or_default();
+            let parts = if query_responses.is_empty() {
+                let window = windows.of(None);
+                block.query_responses = Some(query_responses);
+                vec![(window, block)]
+            } else {
+                // Leave room for the items already in the last block
+                let mut last = reblocked
+                    .last()
+                    .filter(|(last, _)| {
+                        last.block_preamble.block_parameters_index.unwrap_or(0)
+                            == block_parameters_index
+                    })
+                    .map(|(last, window)| (*window, query_response_count(last)));
+                let mut keys = Vec::new();
+                let mut groups = Vec::new();
+                for (window, query_responses) in windows.group(query_responses) {
+                    let first = match last {
+                        Some((last_window, count)) if last_window == window => {
+                            max_items.saturating_sub(count)
+                        }
+                        _ => max_items,
+                    };
+                    for group in chunks(query_responses, first, max_items) {
+                        last = Some((window, group.len()));
+                        keys.push(window);
+                        groups.push(group);
+                    }
+                }
+                keys.into_iter()
+                    .zip(block.partition(groups, block_parameters))
+                    .collect()
+            };
+
+            for (window, part) in parts {
+                let last = match reblocked.last_mut() {
+                    Some((last, last_window))
+                        if *last_window == window
+                            && last.block_preamble.block_parameters_index.unwrap_or(0)
+                                == block_parameters_index
+                            && query_response_count(last) + query_response_count(&part)
+                                <= max_items
+                            && merged_time(last, &part, block_parameters).is_ok() =>
+                    {
+                        last
+                    }
+                    _ => {
+                        reblocked.push((part, window));
+                        continue;
+                    }
+                };
+                let previous = std::mem::replace(last, empty_block());
+                *last = previous.merge(part, block_parameters)?;
+            }
+        }
+        self.file_blocks = reblocked.into_iter().map(|(block, _)| block).collect();
+        Ok(self)
+    }
+}
+
+/// Assignment of Q/R items to time windows.
+enum Windows {
+    /// All items are in the same window.
+    Single,
+    /// Windows of `ticks` length, starting at multiples of it.
+    Aligned {
+        /// The `earliest_time` of the block in ticks since the epoch.
+        earliest_time: i128,
+        ticks: i128,
+    },
+}
+
+impl Windows {
+    fn new(duration: Duration, block: &Block, block_parameters: &BlockParameters) -> Result<Self> {
+        let ticks_per_second = i128::from(u32::from(
+            block_parameters.storage_parameters.ticks_per_second,
+        ));
+        let window_ticks = duration.as_nanos() as i128 * ticks_per_second / 1_000_000_000;
+        if window_ticks == 0 {
+            bail!(
+                "The window of {:?} is shorter than a tick of {} ticks per second",
+                duration,
+                ticks_per_second
+            );
+        }
+        Ok(Self::Aligned {
+            earliest_time: block
+                .block_preamble
+                .earliest_time
+                .map_or(0, |time| ticks(time, ticks_per_second)),
+            ticks: window_ticks,
+        })
+    }
+
+    /// The window of an item with the time offset `offset`.
+    fn of(&self, offset: Option<UTicks>) -> i128 {
+        match *self {
+            Self::Single => 0,
+            Self::Aligned {
+                earliest_time,
+                ticks,
+            } => (earliest_time + offset.map_or(0, |offset| i128::from(u32::from(offset))))
+                .div_euclid(ticks),
+        }
+    }
+
+    /// Group the Q/R items by window, keeping their order within each window.
+    fn group(&self, query_responses: Vec<QueryResponse>) -> BTreeMap<i128, Vec<QueryResponse>> {
+        let mut groups: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for query_response in query_responses {
+            groups
+                .entry(self.of(query_response.time_offset))
+                .or_default()
+                .push(query_response);
+        }
+        groups
+    }
+}
+
+/// Cut `items` into chunks, the first of at most `first` items and all others of `size` items.
+///
+/// An empty first chunk is skipped.
+fn chunks<T>(mut items: Vec<T>, first: usize, size: usize) -> Vec<Vec<T>> {
+    let mut chunks = Vec::new();
+    let mut len = first;
+    while !items.is_empty() {
+        let rest = items.split_off(len.min(items.len()));
+        if !items.is_empty() {
+            chunks.push(items);
+        }
+        items = rest;
+        len = size;
+    }
+    chunks
+}
+
+/// The [`Timestamp`] `ticks` after the epoch.
+fn timestamp(ticks: i128, ticks_per_second: i128) -> Timestamp {
+    Timestamp {
+        timestamp_secs: ticks.div_euclid(ticks_per_second) as i32,
+        timestamp_ticks: UTicks::from(ticks.rem_euclid(ticks_per_second) as u32),
+    }
+}
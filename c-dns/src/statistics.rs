@@ -0,0 +1,140 @@
+//! Recompute and verify the statistics of a block
+//!
+//! The [`BlockStatistics`] are written by the collector and not checked when reading a file.
+//! [`Block::compute_statistics`] derives them from the items stored in the block, for example after editing a block or when writing a block by hand.
+//! [`Block::verify_statistics`] compares the stored statistics with the derived ones, which helps auditing files from other producers.
+//!
+//! ```
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let mut file = File::read_path("./tests/data/dns.cdns")?;
+//! let block = &mut file.file_blocks[0];
+//! assert_eq!(Vec::<c_dns::statistics::StatisticsMismatch>::new(), block.verify_statistics());
+//!
+//! block.query_responses.as_mut().unwrap().pop();
+//! assert!(!block.verify_statistics().is_empty());
+//! block.block_statistics = Some(block.compute_statistics());
+//! assert!(block.verify_statistics().is_empty());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::serialization::*;
+use crate::tables::signature_flags;
+use crate::tables::EMPTY_TABLES;
+use std::fmt;
+
+/// A stored value of the [`BlockStatistics`] which contradicts the content of the block.
+///
+/// See [`Block::verify_statistics`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatisticsMismatch {
+    /// Name of the field in [`BlockStatistics`].
+    pub field: &'static str,
+    /// The stored value.
+    pub stored: usize,
+    /// The value derived from the block content.
+    pub computed: usize,
+}
+
+impl fmt::Display for StatisticsMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is {}, but the block content yields {}",
+            self.field, self.stored, self.computed
+        )
+    }
+}
+
+impl Block {
+    /// Derive the [`BlockStatistics`] from the items stored in the block.
+    ///
+    /// Each Query and each Response of a Q/R item counts as one processed message.
+    /// A Q/R item with only a Query or only a Response counts as unmatched.
+    /// The `discarded_opcode` count cannot be derived and is left empty.
+    pub fn compute_statistics(&self) -> BlockStatistics {
+        let block_tables = self.block_tables.as_ref().unwrap_or(&EMPTY_TABLES);
+        let query_responses = self.query_responses.as_deref().unwrap_or(&[]);
+        let mut processed_messages = 0;
+        let mut unmatched_queries = 0;
+        let mut unmatched_responses = 0;
+        for query_response in query_responses {
+            let flags = signature_flags(block_tables, query_response);
+            let has_query = flags.contains(QueryResponseFlags::HasQuery);
+            let has_response = flags.contains(QueryResponseFlags::HasResponse);
+            processed_messages += usize::from(has_query) + usize::from(has_response);
+            unmatched_queries += usize::from(has_query && !has_response);
+            unmatched_responses += usize::from(has_response && !has_query);
+        }
+        BlockStatistics {
+            processed_messages: Some(processed_messages),
+            qr_data_items: Some(query_responses.len()),
+            unmatched_queries: Some(unmatched_queries),
+            unmatched_responses: Some(unmatched_responses),
+            discarded_opcode: None,
+            malformed_items: Some(self.malformed_messages.as_ref().map_or(0, Vec::len)),
+            extra_values: ExtraValues::new(),
+        }
+    }
+
+    /// Compare the stored [`BlockStatistics`] with the ones derived by [`Block::compute_statistics`].
+    ///
+    /// Missing values are not checked.
+    /// The collector may have processed messages which are not stored in the block, so `processed_messages` and `malformed_items` only mismatch if they are smaller than the derived value.
+    /// All other values must be equal.
+    pub fn verify_statistics(&self) -> Vec<StatisticsMismatch> {
+        let stored = match &self.block_statistics {
+            Some(stored) => stored,
+            None => return Vec::new(),
+        };
+        let computed = self.compute_statistics();
+        [
+            (
+                "processed_messages",
+                stored.processed_messages,
+                computed.processed_messages,
+                true,
+            ),
+            (
+                "qr_data_items",
+                stored.qr_data_items,
+                computed.qr_data_items,
+                false,
+            ),
+            (
+                "unmatched_queries",
+                stored.unmatched_queries,
+                computed.unmatched_queries,
+                false,
+            ),
+            (
+                "unmatched_responses",
+                stored.unmatched_responses,
+                computed.unmatched_responses,
+                false,
+            ),
+            (
+                "malformed_items",
+                stored.malformed_items,
+                computed.malformed_items,
+                true,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(field, stored, computed, at_least)| {
+            let (stored, computed) = (stored?, computed?);
+            let matches = if at_least {
+                stored >= computed
+            } else {
+                stored == computed
+            };
+            (!matches).then_some(StatisticsMismatch {
+                field,
+                stored,
+                computed,
+            })
+        })
+        .collect()
+    }
+}
@@ -0,0 +1,163 @@
+//! Summarize blocks without decoding their content
+//!
+//! Questions like "how many Q/R items does this file hold" or "which time range does it cover" only need the [`BlockPreamble`] and [`BlockStatistics`] of each block.
+//! [`FileSummary::from_slice`] decodes only these and skips the block tables and all items at the CBOR level.
+//! The items are counted while skipping them, so the counts are available even if a producer omits the statistics.
+//!
+//! ```
+//! # use c_dns::summary::FileSummary;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let bytes = std::fs::read("./tests/data/dns.cdns")?;
+//! let summary = FileSummary::from_slice(&bytes)?;
+//! assert_eq!(1, summary.blocks.len());
+//! assert_eq!(12, summary.query_response_count());
+//! assert!(summary.earliest_time().is_some());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::cbor::{
+    head, skip_item, Elements, MAJOR_ARRAY, MAJOR_MAP, MAJOR_NEGATIVE, MAJOR_SIMPLE, MAJOR_UNSIGNED,
+};
+use crate::error::bail;
+use crate::read::{deserialize_at, split_blocks};
+use crate::serialization::*;
+use crate::Result;
+
+/// Map keys of the [`Block`] fields
+const KEY_BLOCK_PREAMBLE: u64 = 0;
+const KEY_BLOCK_STATISTICS: u64 = 1;
+const KEY_QUERY_RESPONSES: u64 = 3;
+const KEY_ADDRESS_EVENT_COUNTS: u64 = 4;
+const KEY_MALFORMED_MESSAGES: u64 = 5;
+
+/// Argument of the simple value `null`.
+const NULL: u64 = 22;
+
+/// The preambles of a file and the summaries of all its blocks.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct FileSummary {
+    /// String "C-DNS" identifying the file type.
+    pub file_type_id: String,
+    /// Version and parameter information for the whole file.
+    pub file_preamble: FilePreamble,
+    /// Summary of each block in file order.
+    pub blocks: Vec<BlockSummary>,
+}
+
+/// The preamble and statistics of a [`Block`] together with the number of its items.
+#[derive(Debug)]
+pub struct BlockSummary {
+    /// The decoded [`Block::block_preamble`].
+    pub block_preamble: BlockPreamble,
+    /// The decoded [`Block::block_statistics`].
+    pub block_statistics: Option<BlockStatistics>,
+    /// Number of entries of [`Block::query_responses`].
+    pub query_response_count: usize,
+    /// Number of entries of [`Block::address_event_counts`].
+    pub address_event_count: usize,
+    /// Number of entries of [`Block::malformed_messages`].
+    pub malformed_message_count: usize,
+}
+
+impl FileSummary {
+    /// Decode the preambles and statistics in `bytes`, skipping all other block content.
+    ///
+    /// The skipped content is only validated to be well-formed CBOR.
+    /// Any bytes following the [`File`] are ignored.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        let (file_type_id, file_preamble, raw_blocks, _) = split_blocks(bytes)?;
+        let blocks = raw_blocks
+            .into_iter()
+            .map(BlockSummary::from_slice)
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            file_type_id,
+            file_preamble,
+            blocks,
+        })
+    }
+
+    /// The earliest `earliest_time` of all blocks, if any block has one.
+    pub fn earliest_time(&self) -> Option<Timestamp> {
+        self.blocks
+            .iter()
+            .filter_map(|block| block.block_preamble.earliest_time)
+            .min()
+    }
+
+    /// Total number of Q/R items in all blocks.
+    pub fn query_response_count(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|block| block.query_response_count)
+            .sum()
+    }
+}
+
+impl BlockSummary {
+    /// Summarize the single block in `raw`.
+    fn from_slice(raw: &[u8]) -> Result<Self> {
+        let mut pos = 0;
+        let mut entries = match head(raw, &mut pos)? {
+            (MAJOR_MAP, entries) => Elements::new(entries),
+            _ => bail!("A Block must be a map"),
+        };
+        let mut block_preamble = None;
+        let mut block_statistics = None;
+        let mut query_response_count = 0;
+        let mut address_event_count = 0;
+        let mut malformed_message_count = 0;
+        while entries.next(raw, &mut pos) {
+            match head(raw, &mut pos)? {
+                (MAJOR_UNSIGNED, Some(KEY_BLOCK_PREAMBLE)) => {
+                    block_preamble = Some(deserialize_at::<BlockPreamble>(raw, &mut pos)?)
+                }
+                (MAJOR_UNSIGNED, Some(KEY_BLOCK_STATISTICS)) => {
+                    block_statistics = deserialize_at::<Option<BlockStatistics>>(raw, &mut pos)?
+                }
+                (MAJOR_UNSIGNED, Some(KEY_QUERY_RESPONSES)) => {
+                    query_response_count = count_items(raw, &mut pos)?
+                }
+                (MAJOR_UNSIGNED, Some(KEY_ADDRESS_EVENT_COUNTS)) => {
+                    address_event_count = count_items(raw, &mut pos)?
+                }
+                (MAJOR_UNSIGNED, Some(KEY_MALFORMED_MESSAGES)) => {
+                    malformed_message_count = count_items(raw, &mut pos)?
+                }
+                (MAJOR_UNSIGNED | MAJOR_NEGATIVE, Some(_)) => skip_item(raw, &mut pos)?,
+                _ => bail!("Invalid key in Block at offset {}", pos),
+            }
+        }
+        let block_preamble = match block_preamble {
+            Some(block_preamble) => block_preamble,
+            None => bail!("Block without block_preamble"),
+        };
+        Ok(Self {
+            block_preamble,
+            block_statistics,
+            query_response_count,
+            address_event_count,
+            malformed_message_count,
+        })
+    }
+}
+
+/// Skip an array and return the number of its elements.
+///
+/// `null` counts as an empty array.
+fn count_items(bytes: &[u8], pos: &mut usize) -> Result<usize> {
+    let start = *pos;
+    let len = match head(bytes, pos)? {
+        (MAJOR_ARRAY, len) => len,
+        (MAJOR_SIMPLE, Some(NULL)) => return Ok(0),
+        _ => bail!("Expected an array at offset {}", start),
+    };
+    let mut elements = Elements::new(len);
+    while elements.next(bytes, pos) {
+        skip_item(bytes, pos)?;
+    }
+    Ok(elements.count() as usize)
+}
@@ -0,0 +1,975 @@
+//! Combine the [`BlockTables`] of multiple blocks
+//!
+//! All `*_index` fields of a [`Block`] reference entries in the tables of the same block.
+//! Moving items between blocks therefore requires moving the referenced table entries and rewriting the indices.
+//! [`BlockTables::append`] moves all entries of one set of tables into another and returns an [`IndexRemapping`], which rewrites the indices of the items referencing the moved entries.
+//!
+//! ```
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! # let bytes = std::fs::read("./tests/data/dns.cdns")?;
+//! let mut first: File = serde_cbor::from_slice(&bytes)?;
+//! let mut second: File = serde_cbor::from_slice(&bytes)?;
+//! let (target, source) = (&mut first.file_blocks[0], &mut second.file_blocks[0]);
+//!
+//! let remapping = target
+//!     .block_tables
+//!     .get_or_insert_with(Default::default)
+//!     .append(source.block_tables.take().unwrap_or_default());
+//! for mut query_response in source.query_responses.take().into_iter().flatten() {
+//!     remapping.remap_query_response(&mut query_response);
+//!     target.query_responses.get_or_insert_with(Vec::new).push(query_response);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Single entries are looked up with accessors like [`BlockTables::name`] or [`BlockTables::qr_signature`].
+//! They check that the table exists and the index is in bounds, and fail with [`Error::IndexOutOfBounds`] otherwise.
+//! The `*_mut` variants, like [`BlockTables::ip_mut`], allow rewriting single entries in place.
+//! [`Block::compact_tables`] removes the entries no longer referenced by any item, for example after filtering the Q/R items of a block.
+//! Some producers write indices starting at 1, while the rest of the crate expects indices starting at 0.
+//! Reading a [`File`] keeps the indices as stored.
+//! [`File::normalize_index_base`] rewrites the blocks which [`IndexBase::detect`] guesses to start at 1, and [`BlockTables::with_base`] resolves single indices with a known base.
+//!
+//! ```
+//! # use c_dns::serialization::File;
+//! # use c_dns::tables::IndexBase;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! # let file = File::read_path("./tests/data/dns.cdns")?;
+//! let block_tables = file.file_blocks[0].block_tables.as_ref().unwrap();
+//! let name = block_tables.name(0)?;
+//! assert_eq!(name, block_tables.with_base(IndexBase::One).name(1)?);
+//! assert!(block_tables.with_base(IndexBase::One).name(0).is_err());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::serialization::*;
+use crate::{Error, Result};
+use std::collections::hash_map::{Entry, HashMap};
+use std::hash::Hash;
+
+/// New positions of the entries of appended [`BlockTables`].
+///
+/// Each field contains one element per entry in the appended table, the new index of that entry.
+/// The index `i` of the appended table is now index `remapping.table[i]`.
+///
+/// See [`BlockTables::append`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexRemapping {
+    /// New indices in [`BlockTables.ip_address`].
+    pub ip_address: Vec<usize>,
+    /// New indices in [`BlockTables.classtype`].
+    pub classtype: Vec<usize>,
+    /// New indices in [`BlockTables.name_rdata`].
+    pub name_rdata: Vec<usize>,
+    /// New indices in [`BlockTables.qr_sig`].
+    pub qr_sig: Vec<usize>,
+    /// New indices in [`BlockTables.qlist`].
+    pub qlist: Vec<usize>,
+    /// New indices in [`BlockTables.qrr`].
+    pub qrr: Vec<usize>,
+    /// New indices in [`BlockTables.rrlist`].
+    pub rrlist: Vec<usize>,
+    /// New indices in [`BlockTables.rr`].
+    pub rr: Vec<usize>,
+    /// New indices in [`BlockTables.malformed_message_data`].
+    pub malformed_message_data: Vec<usize>,
+}
+
+/// Tables of a block without [`Block::block_tables`].
+pub(crate) static EMPTY_TABLES: BlockTables = BlockTables::empty();
+
+impl Default for BlockTables {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl BlockTables {
+    const fn empty() -> Self {
+        Self {
+            ip_address: None,
+            classtype: None,
+            name_rdata: None,
+            qr_sig: None,
+            qlist: None,
+            qrr: None,
+            rrlist: None,
+            rr: None,
+            malformed_message_data: None,
+            extra_values: ExtraValues::new(),
+        }
+    }
+
+    /// Move all entries of `other` into `self`.
+    ///
+    /// IP addresses, CLASS and TYPE pairs, and names or RDATA already existing in `self` are reused instead of being added a second time.
+    /// All other entries are added to the end of their table.
+    /// The indices between entries of `other` are rewritten to the new positions.
+    /// Values of `other.extra_values` are added if `self` does not have a value with the same key.
+    ///
+    /// The returned [`IndexRemapping`] rewrites the indices of items which referenced `other`.
+    ///
+    /// # Panics
+    ///
+    /// The indices in `other` must be valid, as checked by [`File::dangling_indices`].
+    pub fn append(&mut self, other: BlockTables) -> IndexRemapping {
+        let mut remapping = IndexRemapping::default();
+
+        remapping.ip_address = append_deduplicated(&mut self.ip_address, other.ip_address);
+        remapping.classtype = append_deduplicated(&mut self.classtype, other.classtype);
+        remapping.name_rdata = append_deduplicated(&mut self.name_rdata, other.name_rdata);
+
+        remapping.qr_sig = append_remapped(&mut self.qr_sig, other.qr_sig, |sig| {
+            remap_option(&mut sig.server_address_index, &remapping.ip_address);
+            remap_option(&mut sig.query_classtype_index, &remapping.classtype);
+            remap_option(&mut sig.query_opt_rdata_index, &remapping.name_rdata);
+        });
+        remapping.qrr = append_remapped(&mut self.qrr, other.qrr, |question| {
+            remap(&mut question.name_index, &remapping.name_rdata);
+            remap(&mut question.classtype_index, &remapping.classtype);
+        });
+        remapping.qlist = append_remapped(&mut self.qlist, other.qlist, |list| {
+            for question in list.iter_mut() {
+                remap(question, &remapping.qrr);
+            }
+        });
+        remapping.rr = append_remapped(&mut self.rr, other.rr, |rr| {
+            remap(&mut rr.name_index, &remapping.name_rdata);
+            remap(&mut rr.classtype_index, &remapping.classtype);
+            remap_option(&mut rr.rdata_index, &remapping.name_rdata);
+        });
+        remapping.rrlist = append_remapped(&mut self.rrlist, other.rrlist, |list| {
+            for rr in list.iter_mut() {
+                remap(rr, &remapping.rr);
+            }
+        });
+        remapping.malformed_message_data = append_remapped(
+            &mut self.malformed_message_data,
+            other.malformed_message_data,
+            |data| remap_option(&mut data.server_address_index, &remapping.ip_address),
+        );
+
+        for (key, value) in &other.extra_values {
+            if self.extra_values.get(*key).is_none() {
+                self.extra_values.insert(*key, value.clone());
+            }
+        }
+
+        remapping
+    }
+}
+
+/// The index of the first entry of a table
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum IndexBase {
+    /// The first entry has index 0, as specified by RFC 8618.
+    #[default]
+    Zero,
+    /// The first entry has index 1, as written by some producers.
+    One,
+}
+
+impl IndexBase {
+    /// Guess the index base used by the producer of `block`.
+    ///
+    /// Indices starting at 1 are detected if no index is 0 and at least one index equals the length of its table, which would be out of bounds for indices starting at 0.
+    /// All other blocks, including blocks without any index, are assumed to start at 0.
+    ///
+    /// A block with indices starting at 0 and a dangling index equal to the length of its table looks the same, if no index references the first entry.
+    pub fn detect(block: &Block) -> Self {
+        let mut has_zero = false;
+        let mut has_table_len = false;
+        let mut has_out_of_bounds = false;
+        crate::lint::check_block_indices(
+            &mut |_path, index, _table, table_len| match index {
+                Some(0) => has_zero = true,
+                Some(index) if index == table_len => has_table_len = true,
+                Some(index) if index > table_len => has_out_of_bounds = true,
+                _ => {}
+            },
+            "block",
+            block,
+        );
+        if has_table_len && !has_zero && !has_out_of_bounds {
+            IndexBase::One
+        } else {
+            IndexBase::Zero
+        }
+    }
+
+    /// Position of the entry with index `idx` in the table, [`None`] if `idx` is below the base.
+    pub fn position(self, idx: usize) -> Option<usize> {
+        match self {
+            IndexBase::Zero => Some(idx),
+            IndexBase::One => idx.checked_sub(1),
+        }
+    }
+}
+
+/// [`BlockTables`] with indices starting at an [`IndexBase`].
+///
+/// See [`BlockTables::with_base`].
+#[derive(Debug, Clone, Copy)]
+pub struct BasedTables<'a> {
+    tables: &'a BlockTables,
+    base: IndexBase,
+}
+
+impl BlockTables {
+    /// Look up entries with indices starting at `base`.
+    pub fn with_base(&self, base: IndexBase) -> BasedTables<'_> {
+        BasedTables { tables: self, base }
+    }
+
+    /// The IP address at `idx`.
+    pub fn ip(&self, idx: usize) -> Result<&IpAddr> {
+        self.with_base(IndexBase::Zero).ip(idx)
+    }
+
+    /// The CLASS and TYPE at `idx`.
+    pub fn classtype(&self, idx: usize) -> Result<&ClassType> {
+        self.with_base(IndexBase::Zero).classtype(idx)
+    }
+
+    /// The name or RDATA at `idx`.
+    pub fn name(&self, idx: usize) -> Result<&NameOrRdata> {
+        self.with_base(IndexBase::Zero).name(idx)
+    }
+
+    /// The Q/R signature at `idx`.
+    pub fn qr_signature(&self, idx: usize) -> Result<&QueryResponseSignature> {
+        self.with_base(IndexBase::Zero).qr_signature(idx)
+    }
+
+    /// The list of Questions at `idx`.
+    pub fn question_list(&self, idx: usize) -> Result<&QuestionList> {
+        self.with_base(IndexBase::Zero).question_list(idx)
+    }
+
+    /// The Question at `idx`.
+    pub fn question(&self, idx: usize) -> Result<&Question> {
+        self.with_base(IndexBase::Zero).question(idx)
+    }
+
+    /// The list of RRs at `idx`.
+    pub fn rr_list(&self, idx: usize) -> Result<&RRList> {
+        self.with_base(IndexBase::Zero).rr_list(idx)
+    }
+
+    /// The RR at `idx`.
+    pub fn rr(&self, idx: usize) -> Result<&RR> {
+        self.with_base(IndexBase::Zero).rr(idx)
+    }
+
+    /// The malformed message data at `idx`.
+    pub fn malformed_data(&self, idx: usize) -> Result<&MalformedMessageData> {
+        self.with_base(IndexBase::Zero).malformed_data(idx)
+    }
+
+    /// Mutable access to the IP address at `idx`.
+    pub fn ip_mut(&mut self, idx: usize) -> Result<&mut IpAddr> {
+        lookup_mut(&mut self.ip_address, idx, "ip_address")
+    }
+
+    /// Mutable access to the CLASS and TYPE at `idx`.
+    pub fn classtype_mut(&mut self, idx: usize) -> Result<&mut ClassType> {
+        lookup_mut(&mut self.classtype, idx, "classtype")
+    }
+
+    /// Mutable access to the name or RDATA at `idx`.
+    pub fn name_mut(&mut self, idx: usize) -> Result<&mut NameOrRdata> {
+        lookup_mut(&mut self.name_rdata, idx, "name_rdata")
+    }
+
+    /// Mutable access to the Q/R signature at `idx`.
+    pub fn qr_signature_mut(&mut self, idx: usize) -> Result<&mut QueryResponseSignature> {
+        lookup_mut(&mut self.qr_sig, idx, "qr_sig")
+    }
+
+    /// Mutable access to the list of Questions at `idx`.
+    pub fn question_list_mut(&mut self, idx: usize) -> Result<&mut QuestionList> {
+        lookup_mut(&mut self.qlist, idx, "qlist")
+    }
+
+    /// Mutable access to the Question at `idx`.
+    pub fn question_mut(&mut self, idx: usize) -> Result<&mut Question> {
+        lookup_mut(&mut self.qrr, idx, "qrr")
+    }
+
+    /// Mutable access to the list of RRs at `idx`.
+    pub fn rr_list_mut(&mut self, idx: usize) -> Result<&mut RRList> {
+        lookup_mut(&mut self.rrlist, idx, "rrlist")
+    }
+
+    /// Mutable access to the RR at `idx`.
+    pub fn rr_mut(&mut self, idx: usize) -> Result<&mut RR> {
+        lookup_mut(&mut self.rr, idx, "rr")
+    }
+
+    /// Mutable access to the malformed message data at `idx`.
+    pub fn malformed_data_mut(&mut self, idx: usize) -> Result<&mut MalformedMessageData> {
+        lookup_mut(
+            &mut self.malformed_message_data,
+            idx,
+            "malformed_message_data",
+        )
+    }
+}
+
+/// Look up the entry at the 0-based `idx` in `values`.
+fn lookup_mut<'a, T>(
+    values: &'a mut Option<Vec<T>>,
+    idx: usize,
+    table: &'static str,
+) -> Result<&'a mut T> {
+    values
+        .as_mut()
+        .and_then(|values| values.get_mut(idx))
+        .ok_or(Error::IndexOutOfBounds { table, index: idx })
+}
+
+impl<'a> From<&'a BlockTables> for BasedTables<'a> {
+    fn from(tables: &'a BlockTables) -> Self {
+        tables.with_base(IndexBase::Zero)
+    }
+}
+
+impl<'a> BasedTables<'a> {
+    /// The index base of all lookups.
+    pub fn base(&self) -> IndexBase {
+        self.base
+    }
+
+    /// The IP address at `idx`.
+    pub fn ip(&self, idx: usize) -> Result<&'a IpAddr> {
+        self.lookup(&self.tables.ip_address, idx, "ip_address")
+    }
+
+    /// The CLASS and TYPE at `idx`.
+    pub fn classtype(&self, idx: usize) -> Result<&'a ClassType> {
+        self.lookup(&self.tables.classtype, idx, "classtype")
+    }
+
+    /// The name or RDATA at `idx`.
+    pub fn name(&self, idx: usize) -> Result<&'a NameOrRdata> {
+        self.lookup(&self.tables.name_rdata, idx, "name_rdata")
+    }
+
+    /// The Q/R signature at `idx`.
+    pub fn qr_signature(&self, idx: usize) -> Result<&'a QueryResponseSignature> {
+        self.lookup(&self.tables.qr_sig, idx, "qr_sig")
+    }
+
+    /// The list of Questions at `idx`.
+    pub fn question_list(&self, idx: usize) -> Result<&'a QuestionList> {
+        self.lookup(&self.tables.qlist, idx, "qlist")
+    }
+
+    /// The Question at `idx`.
+    pub fn question(&self, idx: usize) -> Result<&'a Question> {
+        self.lookup(&self.tables.qrr, idx, "qrr")
+    }
+
+    /// The list of RRs at `idx`.
+    pub fn rr_list(&self, idx: usize) -> Result<&'a RRList> {
+        self.lookup(&self.tables.rrlist, idx, "rrlist")
+    }
+
+    /// The RR at `idx`.
+    pub fn rr(&self, idx: usize) -> Result<&'a RR> {
+        self.lookup(&self.tables.rr, idx, "rr")
+    }
+
+    /// The malformed message data at `idx`.
+    pub fn malformed_data(&self, idx: usize) -> Result<&'a MalformedMessageData> {
+        self.lookup(
+            &self.tables.malformed_message_data,
+            idx,
+            "malformed_message_data",
+        )
+    }
+
+    fn lookup<T>(
+        &self,
+        values: &'a Option<Vec<T>>,
+        idx: usize,
+        table: &'static str,
+    ) -> Result<&'a T> {
+        self.base
+            .position(idx)
+            .and_then(|pos| values.as_ref()?.get(pos))
+            .ok_or(Error::IndexOutOfBounds { table, index: idx })
+    }
+}
+
+impl IndexRemapping {
+    /// Rewrite all indices of `query_response` into the [`BlockTables`].
+    pub fn remap_query_response(&self, query_response: &mut QueryResponse) {
+        remap_option(&mut query_response.client_address_index, &self.ip_address);
+        remap_option(&mut query_response.qr_signature_index, &self.qr_sig);
+        remap_option(&mut query_response.query_name_index, &self.name_rdata);
+        if let Some(data) = &mut query_response.response_processing_data {
+            remap_option(&mut data.bailiwick_index, &self.name_rdata);
+        }
+        for extended in [
+            &mut query_response.query_extended,
+            &mut query_response.response_extended,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            remap_option(&mut extended.question_index, &self.qlist);
+            remap_option(&mut extended.answer_index, &self.rrlist);
+            remap_option(&mut extended.authority_index, &self.rrlist);
+            remap_option(&mut extended.additional_index, &self.rrlist);
+        }
+    }
+
+    /// Rewrite all indices of `address_event_count` into the [`BlockTables`].
+    pub fn remap_address_event_count(&self, address_event_count: &mut AddressEventCount) {
+        remap(&mut address_event_count.ae_address_index, &self.ip_address);
+    }
+
+    /// Rewrite all indices of `malformed_message` into the [`BlockTables`].
+    pub fn remap_malformed_message(&self, malformed_message: &mut MalformedMessage) {
+        remap_option(
+            &mut malformed_message.client_address_index,
+            &self.ip_address,
+        );
+        remap_option(
+            &mut malformed_message.message_data_index,
+            &self.malformed_message_data,
+        );
+    }
+}
+
+impl Block {
+    /// Remove all entries of the [`BlockTables`] which are not referenced by any item of the block.
+    ///
+    /// Entries are kept if a Q/R item, a malformed message, or an address event references them, directly or through another kept entry.
+    /// All indices are rewritten to the new positions, and tables without any remaining entry are removed.
+    /// This drops names and addresses which are left over after removing items from the block.
+    ///
+    /// # Panics
+    ///
+    /// The indices in the block must be valid, as checked by [`File::dangling_indices`].
+    pub fn compact_tables(&mut self) {
+        let tables = match &mut self.block_tables {
+            Some(tables) => tables,
+            None => return,
+        };
+        let mut used = UsedEntries::new(tables);
+        used.mark_items(
+            self.query_responses.as_deref().unwrap_or(&[]),
+            self.address_event_counts.as_deref().unwrap_or(&[]),
+            self.malformed_messages.as_deref().unwrap_or(&[]),
+        );
+        used.mark_referenced(tables);
+
+        let remapping = IndexRemapping {
+            ip_address: retain_used(&mut tables.ip_address, &used.ip_address),
+            classtype: retain_used(&mut tables.classtype, &used.classtype),
+            name_rdata: retain_used(&mut tables.name_rdata, &used.name_rdata),
+            qr_sig: retain_used(&mut tables.qr_sig, &used.qr_sig),
+            qlist: retain_used(&mut tables.qlist, &used.qlist),
+            qrr: retain_used(&mut tables.qrr, &used.qrr),
+            rrlist: retain_used(&mut tables.rrlist, &used.rrlist),
+            rr: retain_used(&mut tables.rr, &used.rr),
+            malformed_message_data: retain_used(
+                &mut tables.malformed_message_data,
+                &used.malformed_message_data,
+            ),
+        };
+        remapping.remap_tables(tables);
+        remapping.remap_items(
+            self.query_responses.as_deref_mut().unwrap_or(&mut []),
+            self.address_event_counts.as_deref_mut().unwrap_or(&mut []),
+            self.malformed_messages.as_deref_mut().unwrap_or(&mut []),
+        );
+    }
+
+    /// Rewrite the indices of the block to start at 0, if [`IndexBase::detect`] finds them starting at 1.
+    ///
+    /// Returns the detected base.
+    /// Only call this for files of producers known to write indices starting at 1.
+    /// The detection is a guess, and a corrupt block with indices starting at 0, which never references the first entry of a table but references one past the last, is rewritten as well.
+    pub fn normalize_index_base(&mut self) -> IndexBase {
+        let base = IndexBase::detect(self);
+        if base == IndexBase::Zero {
+            return base;
+        }
+        fn shift<T>(table: &Option<Vec<T>>) -> Vec<usize> {
+            let len = table.as_ref().map_or(0, Vec::len);
+            std::iter::once(usize::MAX).chain(0..len).collect()
+        }
+
+        // Detection guarantees every index is between 1 and the length of its table
+        let mut tables = self.block_tables.take().unwrap_or_default();
+        let remapping = IndexRemapping {
+            ip_address: shift(&tables.ip_address),
+            classtype: shift(&tables.classtype),
+            name_rdata: shift(&tables.name_rdata),
+            qr_sig: shift(&tables.qr_sig),
+            qlist: shift(&tables.qlist),
+            qrr: shift(&tables.qrr),
+            rrlist: shift(&tables.rrlist),
+            rr: shift(&tables.rr),
+            malformed_message_data: shift(&tables.malformed_message_data),
+        };
+        remapping.remap_tables(&mut tables);
+        remapping.remap_items(
+            self.query_responses.as_deref_mut().unwrap_or(&mut []),
+            self.address_event_counts.as_deref_mut().unwrap_or(&mut []),
+            self.malformed_messages.as_deref_mut().unwrap_or(&mut []),
+        );
+        self.block_tables = Some(tables);
+        base
+    }
+}
+
+impl File {
+    /// Rewrite the indices of all blocks to start at 0, see [`Block::normalize_index_base`].
+    ///
+    /// Returns the number of rewritten blocks.
+    pub fn normalize_index_base(&mut self) -> usize {
+        self.file_blocks
+            .iter_mut()
+            .map(Block::normalize_index_base)
+            .filter(|base| *base == IndexBase::One)
+            .count()
+    }
+}
+
+/// Lookup the [`QueryResponseSignature`] of a [`QueryResponse`].
+pub(crate) fn signature<'a>(
+    block_tables: &'a BlockTables,
+    query_response: &QueryResponse,
+) -> Option<&'a QueryResponseSignature> {
+    block_tables
+        .qr_sig
+        .as_ref()?
+        .get(query_response.qr_signature_index?)
+}
+
+/// Lookup the [`QueryResponseFlags`] of a [`QueryResponse`].
+///
+/// Returns an empty set if no flags are recorded.
+pub(crate) fn signature_flags(
+    block_tables: &BlockTables,
+    query_response: &QueryResponse,
+) -> enumset::EnumSet<QueryResponseFlags> {
+    signature(block_tables, query_response)
+        .and_then(|sig| sig.qr_sig_flags)
+        .unwrap_or_default()
+}
+
+/// Copy the entries of `tables` referenced by the items into new tables, and rewrite the indices of the items accordingly.
+///
+/// This is [`Block::compact_tables`] for items moving into a new block, while `tables` stays unchanged.
+pub(crate) fn extract_tables(
+    tables: &BlockTables,
+    query_responses: &mut [QueryResponse],
+    address_event_counts: &mut [AddressEventCount],
+    malformed_messages: &mut [MalformedMessage],
+) -> BlockTables {
+    let mut used = UsedEntries::new(tables);
+    used.mark_items(query_responses, address_event_counts, malformed_messages);
+    used.mark_referenced(tables);
+
+    let mut extracted = BlockTables {
+        extra_values: tables.extra_values.clone(),
+        ..BlockTables::default()
+    };
+    let remapping = IndexRemapping {
+        ip_address: copy_used(
+            &mut extracted.ip_address,
+            &tables.ip_address,
+            &used.ip_address,
+        ),
+        classtype: copy_used(&mut extracted.classtype, &tables.classtype, &used.classtype),
+        name_rdata: copy_used(
+            &mut extracted.name_rdata,
+            &tables.name_rdata,
+            &used.name_rdata,
+        ),
+        qr_sig: copy_used(&mut extracted.qr_sig, &tables.qr_sig, &used.qr_sig),
+        qlist: copy_used(&mut extracted.qlist, &tables.qlist, &used.qlist),
+        qrr: copy_used(&mut extracted.qrr, &tables.qrr, &used.qrr),
+        rrlist: copy_used(&mut extracted.rrlist, &tables.rrlist, &used.rrlist),
+        rr: copy_used(&mut extracted.rr, &tables.rr, &used.rr),
+        malformed_message_data: copy_used(
+            &mut extracted.malformed_message_data,
+            &tables.malformed_message_data,
+            &used.malformed_message_data,
+        ),
+    };
+    remapping.remap_tables(&mut extracted);
+    remapping.remap_items(query_responses, address_event_counts, malformed_messages);
+    extracted
+}
+
+impl IndexRemapping {
+    /// Rewrite the indices between the entries of `tables`.
+    fn remap_tables(&self, tables: &mut BlockTables) {
+        for sig in tables.qr_sig.iter_mut().flatten() {
+            remap_option(&mut sig.server_address_index, &self.ip_address);
+            remap_option(&mut sig.query_classtype_index, &self.classtype);
+            remap_option(&mut sig.query_opt_rdata_index, &self.name_rdata);
+        }
+        for list in tables.qlist.iter_mut().flatten() {
+            list.iter_mut().for_each(|idx| remap(idx, &self.qrr));
+        }
+        for question in tables.qrr.iter_mut().flatten() {
+            remap(&mut question.name_index, &self.name_rdata);
+            remap(&mut question.classtype_index, &self.classtype);
+        }
+        for list in tables.rrlist.iter_mut().flatten() {
+            list.iter_mut().for_each(|idx| remap(idx, &self.rr));
+        }
+        for rr in tables.rr.iter_mut().flatten() {
+            remap(&mut rr.name_index, &self.name_rdata);
+            remap(&mut rr.classtype_index, &self.classtype);
+            remap_option(&mut rr.rdata_index, &self.name_rdata);
+        }
+        for data in tables.malformed_message_data.iter_mut().flatten() {
+            remap_option(&mut data.server_address_index, &self.ip_address);
+        }
+    }
+
+    fn remap_items(
+        &self,
+        query_responses: &mut [QueryResponse],
+        address_event_counts: &mut [AddressEventCount],
+        malformed_messages: &mut [MalformedMessage],
+    ) {
+        for query_response in query_responses {
+            self.remap_query_response(query_response);
+        }
+        for address_event_count in address_event_counts {
+            self.remap_address_event_count(address_event_count);
+        }
+        for malformed_message in malformed_messages {
+            self.remap_malformed_message(malformed_message);
+        }
+    }
+}
+
+/// Whether each entry of the [`BlockTables`] is referenced.
+struct UsedEntries {
+    ip_address: Vec<bool>,
+    classtype: Vec<bool>,
+    name_rdata: Vec<bool>,
+    qr_sig: Vec<bool>,
+    qlist: Vec<bool>,
+    qrr: Vec<bool>,
+    rrlist: Vec<bool>,
+    rr: Vec<bool>,
+    malformed_message_data: Vec<bool>,
+}
+
+impl UsedEntries {
+    fn new(tables: &BlockTables) -> Self {
+        fn unused<T>(table: &Option<Vec<T>>) -> Vec<bool> {
+            vec![false; table.as_ref().map_or(0, Vec::len)]
+        }
+        Self {
+            ip_address: unused(&tables.ip_address),
+            classtype: unused(&tables.classtype),
+            name_rdata: unused(&tables.name_rdata),
+            qr_sig: unused(&tables.qr_sig),
+            qlist: unused(&tables.qlist),
+            qrr: unused(&tables.qrr),
+            rrlist: unused(&tables.rrlist),
+            rr: unused(&tables.rr),
+            malformed_message_data: unused(&tables.malformed_message_data),
+        }
+    }
+
+    /// Mark the entries referenced by the items directly.
+    fn mark_items(
+        &mut self,
+        query_responses: &[QueryResponse],
+        address_event_counts: &[AddressEventCount],
+        malformed_messages: &[MalformedMessage],
+    ) {
+        for query_response in query_responses {
+            mark_option(&mut self.ip_address, query_response.client_address_index);
+            mark_option(&mut self.qr_sig, query_response.qr_signature_index);
+            mark_option(&mut self.name_rdata, query_response.query_name_index);
+            if let Some(data) = &query_response.response_processing_data {
+                mark_option(&mut self.name_rdata, data.bailiwick_index);
+            }
+            for extended in [
+                &query_response.query_extended,
+                &query_response.response_extended,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                mark_option(&mut self.qlist, extended.question_index);
+                mark_option(&mut self.rrlist, extended.answer_index);
+                mark_option(&mut self.rrlist, extended.authority_index);
+                mark_option(&mut self.rrlist, extended.additional_index);
+            }
+        }
+        for address_event_count in address_event_counts {
+            mark(&mut self.ip_address, address_event_count.ae_address_index);
+        }
+        for malformed_message in malformed_messages {
+            mark_option(&mut self.ip_address, malformed_message.client_address_index);
+            mark_option(
+                &mut self.malformed_message_data,
+                malformed_message.message_data_index,
+            );
+        }
+    }
+
+    /// Mark the entries referenced by marked entries of `tables`.
+    fn mark_referenced(&mut self, tables: &BlockTables) {
+        for sig in used_entries(&tables.qr_sig, &self.qr_sig) {
+            mark_option(&mut self.ip_address, sig.server_address_index);
+            mark_option(&mut self.classtype, sig.query_classtype_index);
+            mark_option(&mut self.name_rdata, sig.query_opt_rdata_index);
+        }
+        for list in used_entries(&tables.qlist, &self.qlist) {
+            list.iter().for_each(|&idx| mark(&mut self.qrr, idx));
+        }
+        for question in used_entries(&tables.qrr, &self.qrr) {
+            mark(&mut self.name_rdata, question.name_index);
+            mark(&mut self.classtype, question.classtype_index);
+        }
+        for list in used_entries(&tables.rrlist, &self.rrlist) {
+            list.iter().for_each(|&idx| mark(&mut self.rr, idx));
+        }
+        for rr in used_entries(&tables.rr, &self.rr) {
+            mark(&mut self.name_rdata, rr.name_index);
+            mark(&mut self.classtype, rr.classtype_index);
+            mark_option(&mut self.name_rdata, rr.rdata_index);
+        }
+        for data in used_entries(&tables.malformed_message_data, &self.malformed_message_data) {
+            mark_option(&mut self.ip_address, data.server_address_index);
+        }
+    }
+}
+
+fn mark(used: &mut [bool], index: usize) {
+    used[index] = true;
+}
+
+fn mark_option(used: &mut [bool], index: Option<usize>) {
+    if let Some(index) = index {
+        mark(used, index);
+    }
+}
+
+/// The entries of `table` marked in `used`.
+fn used_entries<'a, T>(
+    table: &'a Option<Vec<T>>,
+    used: &'a [bool],
+) -> impl Iterator<Item = &'a T> + 'a {
+    table
+        .iter()
+        .flatten()
+        .zip(used)
+        .filter_map(|(entry, &used)| used.then_some(entry))
+}
+
+/// The new index of each entry when only keeping the entries marked in `used`.
+///
+/// The values for removed entries are never read.
+fn used_positions(used: &[bool]) -> Vec<usize> {
+    let mut next = 0;
+    used.iter()
+        .map(|&used| {
+            let idx = next;
+            next += usize::from(used);
+            idx
+        })
+        .collect()
+}
+
+/// Remove the entries of `table` not marked in `used`, and remove the table if it is empty afterwards.
+///
+/// Returns the new index of each entry.
+fn retain_used<T>(table: &mut Option<Vec<T>>, used: &[bool]) -> Vec<usize> {
+    if let Some(entries) = table {
+        let mut used = used.iter();
+        entries.retain(|_| *used.next().unwrap());
+    }
+    if table.as_ref().is_some_and(Vec::is_empty) {
+        *table = None;
+    }
+    used_positions(used)
+}
+
+/// Copy the entries of `table` marked in `used` into `target`, which stays [`None`] if no entry is marked.
+///
+/// Returns the new index of each entry.
+fn copy_used<T: Clone>(
+    target: &mut Option<Vec<T>>,
+    table: &Option<Vec<T>>,
+    used: &[bool],
+) -> Vec<usize> {
+    let entries: Vec<T> = used_entries(table, used).cloned().collect();
+    *target = (!entries.is_empty()).then_some(entries);
+    used_positions(used)
+}
+
+/// Append the entries of `other` to `table`, reusing equal entries.
+///
+/// Returns the new index of each entry of `other`.
+fn append_deduplicated<T: Clone + Hash + Eq>(
+    table: &mut Option<Vec<T>>,
+    other: Option<Vec<T>>,
+) -> Vec<usize> {
+    let other = match other {
+        Some(other) if !other.is_empty() => other,
+        _ => return Vec::new(),
+    };
+    let table = table.get_or_insert_with(Vec::new);
+    let mut positions = HashMap::with_capacity(table.len() + other.len());
+    for (idx, entry) in table.iter().enumerate() {
+        positions.entry(entry.clone()).or_insert(idx);
+    }
+    other
+        .into_iter()
+        .map(|entry| match positions.entry(entry.clone()) {
+            Entry::Occupied(position) => *position.get(),
+            Entry::Vacant(position) => {
+                position.insert(table.len());
+                table.push(entry);
+                table.len() - 1
+            }
+        })
+        .collect()
+}
+
+/// Append the entries of `other` to `table`, after rewriting their indices with `remap`.
+///
+/// Returns the new index of each entry of `other`.
+fn append_remapped<T>(
+    table: &mut Option<Vec<T>>,
+    other: Option<Vec<T>>,
+    mut remap: impl FnMut(&mut T),
+) -> Vec<usize> {
+    let other = match other {
+        Some(other) if !other.is_empty() => other,
+        _ => return Vec::new(),
+    };
+    let table = table.get_or_insert_with(Vec::new);
+    let offset = table.len();
+    table.extend(other.into_iter().map(|mut entry| {
+        remap(&mut entry);
+        entry
+    }));
+    (offset..table.len()).collect()
+}
+
+fn remap(index: &mut usize, remapping: &[usize]) {
+    *index = remapping[*index];
+}
+
+fn remap_option(index: &mut Option<usize>, remapping: &[usize]) {
+    if let Some(index) = index {
+        remap(index, remapping);
+    }
+}
+
+/// How an entry of [`BlockTables.name_rdata`] is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum NameRdataUse {
+    /// A domain name, like a QNAME or the NAME of an RR.
+    Name,
+    /// The RDATA of an RR, including the OPT RR, with the TYPE of the RR if it is known.
+    Rdata(Option<DnsType>),
+}
+
+/// Call `visit` with every index into [`BlockTables.name_rdata`] within `block`.
+pub(crate) fn visit_name_rdata_indices(
+    block: &mut Block,
+    visit: &mut impl FnMut(&mut usize, NameRdataUse),
+) {
+    if let Some(tables) = &mut block.block_tables {
+        for sig in tables.qr_sig.iter_mut().flatten() {
+            if let Some(index) = &mut sig.query_opt_rdata_index {
+                visit(index, NameRdataUse::Rdata(Some(DnsType::OPT)));
+            }
+        }
+        for question in tables.qrr.iter_mut().flatten() {
+            visit(&mut question.name_index, NameRdataUse::Name);
+        }
+        let classtype = &tables.classtype;
+        for rr in tables.rr.iter_mut().flatten() {
+            visit(&mut rr.name_index, NameRdataUse::Name);
+            if let Some(index) = &mut rr.rdata_index {
+                let type_ = classtype
+                    .as_ref()
+                    .and_then(|classtype| classtype.get(rr.classtype_index))
+                    .map(|classtype| classtype.type_);
+                visit(index, NameRdataUse::Rdata(type_));
+            }
+        }
+    }
+    for query_response in block.query_responses.iter_mut().flatten() {
+        if let Some(index) = &mut query_response.query_name_index {
+            visit(index, NameRdataUse::Name);
+        }
+        let bailiwick_index = query_response
+            .response_processing_data
+            .as_mut()
+            .and_then(|data| data.bailiwick_index.as_mut());
+        if let Some(index) = bailiwick_index {
+            visit(index, NameRdataUse::Name);
+        }
+    }
+}
+
+/// Call `visit` with every index into [`BlockTables.ip_address`] within `block`.
+///
+/// The IP version is taken from the [`TransportFlags`] belonging to the address, if there are any.
+pub(crate) fn visit_ip_address_indices(
+    block: &mut Block,
+    visit: &mut impl FnMut(&mut usize, Option<crate::IpVersion>),
+) {
+    let ip_version = |flags: Option<&TransportFlags>| flags.map(TransportFlags::ip_version);
+    if let Some(tables) = &mut block.block_tables {
+        for sig in tables.qr_sig.iter_mut().flatten() {
+            let version = ip_version(sig.qr_transport_flags.as_ref());
+            if let Some(index) = &mut sig.server_address_index {
+                visit(index, version);
+            }
+        }
+        for data in tables.malformed_message_data.iter_mut().flatten() {
+            let version = ip_version(data.mm_transport_flags.as_ref());
+            if let Some(index) = &mut data.server_address_index {
+                visit(index, version);
+            }
+        }
+    }
+    let tables = block.block_tables.as_ref().unwrap_or(&EMPTY_TABLES);
+    for query_response in block.query_responses.iter_mut().flatten() {
+        let version = query_response
+            .qr_signature_index
+            .and_then(|index| tables.qr_sig.as_ref()?.get(index))
+            .and_then(|sig| ip_version(sig.qr_transport_flags.as_ref()));
+        if let Some(index) = &mut query_response.client_address_index {
+            visit(index, version);
+        }
+    }
+    for address_event_count in block.address_event_counts.iter_mut().flatten() {
+        let version = ip_version(address_event_count.ae_transport_flags.as_ref());
+        visit(&mut address_event_count.ae_address_index, version);
+    }
+    for malformed_message in block.malformed_messages.iter_mut().flatten() {
+        let version = malformed_message
+            .message_data_index
+            .and_then(|index| tables.malformed_message_data.as_ref()?.get(index))
+            .and_then(|data| ip_version(data.mm_transport_flags.as_ref()));
+        if let Some(index) = &mut malformed_message.client_address_index {
+            visit(index, version);
+        }
+    }
+}
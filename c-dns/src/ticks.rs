@@ -0,0 +1,357 @@
+//! Convert timestamps to a different tick rate or to [`SystemTime`] and [`Duration`]
+//!
+//! Sub-second times are stored in ticks and every [`StorageParameters`] defines its own `ticks_per_second`.
+//! Files from collectors with different tick rates can only be compared or merged after converting them to a common resolution.
+//! [`File::convert_ticks`] rewrites the `earliest_time`, all time offsets, and all response delays, and updates the `ticks_per_second` accordingly.
+//!
+//! [`Timestamp`], [`UTicks`], and [`Ticks`] convert into [`SystemTime`] and [`Duration`] given the `ticks_per_second`.
+//! [`QueryResponse::absolute_time`] and [`MalformedMessage::absolute_time`] combine the `earliest_time` of the block with the time offset of the item.
+//!
+//! ```
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let mut file = File::read_path("./tests/data/dns.cdns")?;
+//! // Convert to milliseconds
+//! file.convert_ticks(1_000)?;
+//! let ticks_per_second = file.file_preamble.block_parameters[0].storage_parameters.ticks_per_second;
+//! assert_eq!(1_000, u32::from(ticks_per_second));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{bail, invalid};
+use crate::serialization::*;
+use crate::Result;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+impl File {
+    /// Convert all times of all blocks to `ticks_per_second`, see [`Block::convert_ticks`].
+    ///
+    /// This sets the `ticks_per_second` of all [`StorageParameters`].
+    /// Fails without changing the file if a block references non-existing [`BlockParameters`], has zero `ticks_per_second`, or a converted value does not fit.
+    pub fn convert_ticks(&mut self, ticks_per_second: u32) -> Result<()> {
+        let mut rates = Vec::with_capacity(self.file_blocks.len());
+        for (idx, block) in self.file_blocks.iter().enumerate() {
+            let parameters_index = block.block_preamble.block_parameters_index.unwrap_or(0);
+            let parameters = self
+                .file_preamble
+                .block_parameters
+                .get(parameters_index)
+                .ok_or_else(|| {
+                    invalid!(
+                        "Block {} references the non-existing block parameters {}",
+                        idx,
+                        parameters_index
+                    )
+                })?;
+            let from = u32::from(parameters.storage_parameters.ticks_per_second);
+            Conversion::new(from, ticks_per_second)?.check(block)?;
+            rates.push(from);
+        }
+
+        for (block, from) in self.file_blocks.iter_mut().zip(rates) {
+            Conversion::new(from, ticks_per_second)?.apply(block);
+        }
+        for block_parameters in &mut self.file_preamble.block_parameters {
+            block_parameters.storage_parameters.ticks_per_second = ticks_per_second.into();
+        }
+        Ok(())
+    }
+}
+
+impl Block {
+    /// Convert the `earliest_time`, the time offsets, and the response delays from `from` to `to` ticks per second.
+    ///
+    /// Converting to a lower resolution rounds down the absolute times, so the order of the items is preserved.
+    /// Fails without changing the block if either rate is zero or a converted value does not fit.
+    ///
+    /// This does not change the [`StorageParameters`], use [`File::convert_ticks`] to also update the `ticks_per_second`.
+    pub fn convert_ticks(&mut self, from: u32, to: u32) -> Result<()> {
+        let conversion = Conversion::new(from, to)?;
+        conversion.check(self)?;
+        conversion.apply(self);
+        Ok(())
+    }
+}
+
+impl Timestamp {
+    /// Convert into a [`SystemTime`] with `ticks_per_second`.
+    ///
+    /// Fails if `ticks_per_second` is zero or the timestamp lies before the POSIX epoch.
+    pub fn to_system_time(self, ticks_per_second: u32) -> Result<SystemTime> {
+        time_at(self, 0, ticks_per_second)
+    }
+
+    /// Convert a [`SystemTime`] into a timestamp with `ticks_per_second`, rounding down to full ticks.
+    ///
+    /// Fails if `ticks_per_second` is zero or the time does not fit into the seconds of a timestamp.
+    pub fn from_system_time(time: SystemTime, ticks_per_second: u32) -> Result<Self> {
+        check_ticks_per_second(ticks_per_second)?;
+        let since_epoch = time
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| invalid!("The time {:?} lies before the POSIX epoch", time))?;
+        let timestamp_secs = i32::try_from(since_epoch.as_secs())
+            .map_err(|_| invalid!("The time {:?} does not fit into a Timestamp", time))?;
+        let ticks =
+            u64::from(since_epoch.subsec_nanos()) * u64::from(ticks_per_second) / 1_000_000_000;
+        Ok(Self {
+            timestamp_secs,
+            // Always smaller than ticks_per_second, so it fits
+            timestamp_ticks: (ticks as u32).into(),
+        })
+    }
+}
+
+impl UTicks {
+    /// Convert into a [`Duration`] with `ticks_per_second`.
+    ///
+    /// Fails if `ticks_per_second` is zero.
+    pub fn to_duration(self, ticks_per_second: u32) -> Result<Duration> {
+        check_ticks_per_second(ticks_per_second)?;
+        Ok(ticks_to_duration(u32::from(self).into(), ticks_per_second))
+    }
+
+    /// Convert a [`Duration`] into ticks with `ticks_per_second`, rounding down to full ticks.
+    ///
+    /// Fails if `ticks_per_second` is zero or the number of ticks does not fit.
+    pub fn from_duration(duration: Duration, ticks_per_second: u32) -> Result<Self> {
+        check_ticks_per_second(ticks_per_second)?;
+        let ticks = duration.as_nanos() * u128::from(ticks_per_second) / 1_000_000_000;
+        u32::try_from(ticks).map(Self::from).map_err(|_| {
+            invalid!(
+                "The duration {:?} does not fit into ticks with {} ticks per second",
+                duration,
+                ticks_per_second
+            )
+        })
+    }
+}
+
+impl Ticks {
+    /// Convert into a [`Duration`] with `ticks_per_second`.
+    ///
+    /// Fails if `ticks_per_second` is zero or the ticks are negative, see [`Ticks::unsigned_abs`].
+    pub fn to_duration(self, ticks_per_second: u32) -> Result<Duration> {
+        check_ticks_per_second(ticks_per_second)?;
+        let ticks = u64::try_from(i32::from(self)).map_err(|_| {
+            invalid!(
+                "Cannot convert the negative ticks {:?} into a Duration",
+                self
+            )
+        })?;
+        Ok(ticks_to_duration(ticks, ticks_per_second))
+    }
+
+    /// The absolute value of the ticks.
+    ///
+    /// Response delays are negative if the Response was seen before the Query.
+    pub fn unsigned_abs(self) -> UTicks {
+        i32::from(self).unsigned_abs().into()
+    }
+}
+
+impl QueryResponse {
+    /// The time of the Q/R item, which is the `earliest_time` of the block plus the `time_offset`.
+    ///
+    /// A missing `time_offset` is treated as an offset of zero.
+    /// Fails if the block has no `earliest_time`, `ticks_per_second` is zero, or the time lies before the POSIX epoch.
+    pub fn absolute_time(
+        &self,
+        block_preamble: &BlockPreamble,
+        storage_parameters: &StorageParameters,
+    ) -> Result<SystemTime> {
+        item_time(block_preamble, storage_parameters, self.time_offset, 0)
+    }
+
+    /// The time of the Response, which is the [`QueryResponse::absolute_time`] plus the `response_delay`.
+    ///
+    /// Returns [`None`] if the Q/R item has no `response_delay`.
+    pub fn response_time(
+        &self,
+        block_preamble: &BlockPreamble,
+        storage_parameters: &StorageParameters,
+    ) -> Result<Option<SystemTime>> {
+        self.response_delay
+            .map(|delay| {
+                item_time(
+                    block_preamble,
+                    storage_parameters,
+                    self.time_offset,
+                    i32::from(delay).into(),
+                )
+            })
+            .transpose()
+    }
+}
+
+impl MalformedMessage {
+    /// The time of the malformed message, which is the `earliest_time` of the block plus the `time_offset`.
+    ///
+    /// Behaves like [`QueryResponse::absolute_time`].
+    pub fn absolute_time(
+        &self,
+        block_preamble: &BlockPreamble,
+        storage_parameters: &StorageParameters,
+    ) -> Result<SystemTime> {
+        item_time(block_preamble, storage_parameters, self.time_offset, 0)
+    }
+}
+
+fn item_time(
+    block_preamble: &BlockPreamble,
+    storage_parameters: &StorageParameters,
+    time_offset: Option<UTicks>,
+    delay: i64,
+) -> Result<SystemTime> {
+    let earliest_time = block_preamble
+        .earliest_time
+        .ok_or_else(|| invalid!("The block has no earliest_time"))?;
+    let offset = i64::from(time_offset.map_or(0, u32::from));
+    time_at(
+        earliest_time,
+        offset + delay,
+        storage_parameters.ticks_per_second.into(),
+    )
+}
+
+/// The time `ticks` after the `timestamp`.
+pub(crate) fn time_at(
+    timestamp: Timestamp,
+    ticks: i64,
+    ticks_per_second: u32,
+) -> Result<SystemTime> {
+    check_ticks_per_second(ticks_per_second)?;
+    let ticks_per_second = i128::from(ticks_per_second);
+    let ticks = i128::from(timestamp.timestamp_secs) * ticks_per_second
+        + i128::from(u32::from(timestamp.timestamp_ticks))
+        + i128::from(ticks);
+    let ticks = u64::try_from(ticks)
+        .map_err(|_| invalid!("The time {:?} lies before the POSIX epoch", timestamp))?;
+    UNIX_EPOCH
+        .checked_add(ticks_to_duration(ticks, ticks_per_second as u32))
+        .ok_or_else(|| invalid!("The time {:?} cannot be represented", timestamp))
+}
+
+fn ticks_to_duration(ticks: u64, ticks_per_second: u32) -> Duration {
+    let ticks_per_second = u64::from(ticks_per_second);
+    let nanos = (ticks % ticks_per_second) * 1_000_000_000 / ticks_per_second;
+    Duration::new(ticks / ticks_per_second, nanos as u32)
+}
+
+fn check_ticks_per_second(ticks_per_second: u32) -> Result<()> {
+    if ticks_per_second == 0 {
+        bail!("Invalid ticks_per_second of 0");
+    }
+    Ok(())
+}
+
+/// Conversion between two tick rates, working on the absolute times since the second of the `earliest_time`
+#[derive(Debug, Clone, Copy)]
+struct Conversion {
+    from: i128,
+    to: i128,
+}
+
+impl Conversion {
+    fn new(from: u32, to: u32) -> Result<Self> {
+        if from == 0 || to == 0 {
+            bail!(
+                "Cannot convert between {} and {} ticks per second",
+                from,
+                to
+            );
+        }
+        Ok(Self {
+            from: from.into(),
+            to: to.into(),
+        })
+    }
+
+    /// Ticks since the second of the `earliest_time`, rounded down to the new rate
+    fn ticks(&self, ticks: i128) -> i128 {
+        (ticks * self.to).div_euclid(self.from)
+    }
+
+    fn earliest_ticks(block: &Block) -> i128 {
+        block
+            .block_preamble
+            .earliest_time
+            .map_or(0, |time| u32::from(time.timestamp_ticks).into())
+    }
+
+    fn time_offset(&self, earliest: i128, time_offset: UTicks) -> Option<UTicks> {
+        let offset =
+            self.ticks(earliest + i128::from(u32::from(time_offset))) - self.ticks(earliest);
+        u32::try_from(offset).ok().map(UTicks::from)
+    }
+
+    fn response_delay(
+        &self,
+        earliest: i128,
+        time_offset: Option<UTicks>,
+        delay: Ticks,
+    ) -> Option<Ticks> {
+        let query_time = earliest + time_offset.map_or(0, |offset| i128::from(u32::from(offset)));
+        let delay = self.ticks(query_time + i128::from(i32::from(delay))) - self.ticks(query_time);
+        i32::try_from(delay).ok().map(Ticks::from)
+    }
+
+    /// Ensure all converted values fit
+    fn check(&self, block: &Block) -> Result<()> {
+        let earliest = Self::earliest_ticks(block);
+        for qr in block.query_responses.iter().flatten() {
+            if let Some(time_offset) = qr.time_offset {
+                if self.time_offset(earliest, time_offset).is_none() {
+                    bail!(
+                        "The time offset {:?} does not fit after conversion",
+                        time_offset
+                    );
+                }
+            }
+            if let Some(delay) = qr.response_delay {
+                if self
+                    .response_delay(earliest, qr.time_offset, delay)
+                    .is_none()
+                {
+                    bail!(
+                        "The response delay {:?} does not fit after conversion",
+                        delay
+                    );
+                }
+            }
+        }
+        for mm in block.malformed_messages.iter().flatten() {
+            if let Some(time_offset) = mm.time_offset {
+                if self.time_offset(earliest, time_offset).is_none() {
+                    bail!(
+                        "The time offset {:?} does not fit after conversion",
+                        time_offset
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert all values, which must have been checked before
+    fn apply(&self, block: &mut Block) {
+        let earliest = Self::earliest_ticks(block);
+        for qr in block.query_responses.iter_mut().flatten() {
+            if let Some(delay) = qr.response_delay {
+                qr.response_delay = self.response_delay(earliest, qr.time_offset, delay);
+            }
+            if let Some(time_offset) = qr.time_offset {
+                qr.time_offset = self.time_offset(earliest, time_offset);
+            }
+        }
+        for mm in block.malformed_messages.iter_mut().flatten() {
+            if let Some(time_offset) = mm.time_offset {
+                mm.time_offset = self.time_offset(earliest, time_offset);
+            }
+        }
+        if let Some(earliest_time) = &mut block.block_preamble.earliest_time {
+            // Always smaller than the new rate, so it fits
+            earliest_time.timestamp_ticks = (self.ticks(earliest) as u32).into();
+        }
+    }
+}
@@ -1,6 +1,10 @@
+use crate::serialization::*;
+use crate::IpVersion;
+use std::time::SystemTime;
+
 /// Implement [`Debug`] and skip [`None`] fields
 ///
-/// Implement [`Debug`] for a struct which has only [`Option`] fields.
+/// Implement [`Debug`] for a struct which has only [`Option`] fields and an `extra_values` map.
 ///
 /// # Example
 ///
@@ -9,6 +13,7 @@
 ///     field_a: Option<u8>,
 ///     field_b: Option<String>,
 ///     field_c: Option<bool>,
+///     extra_values: c_dns::serialization::ExtraValues,
 /// }
 /// c_dns::debug_unwrap_option_fields!(Abc, field_a, field_b, field_c,);
 /// ```
@@ -52,3 +57,57 @@ macro_rules! debug_extra_values {
         }
     };
 }
+
+/// Print optional flag sets in the compact form of [`FlagSetExt::display`](crate::flags::FlagSetExt::display)
+#[macro_export]
+macro_rules! debug_unwrap_option_flags_field {
+    ($self:ident, $ds:ident, $($field:ident,)+) => {
+        $(
+        if let Some($field) = &$self.$field {
+            $ds.field(stringify!($field), &$crate::flags::FlagSetExt::display($field));
+        }
+        )+
+    }
+}
+
+/// Compute the absolute time of an item in a [`Block`].
+///
+/// The time is the [`BlockPreamble.earliest_time`] plus the `time_offset` of the item, converted with the `ticks_per_second` of the [`StorageParameters`].
+/// A missing `time_offset` is treated as an offset of zero.
+///
+/// Returns [`None`] if the block has no earliest time, the timestamp lies before the POSIX epoch, or `ticks_per_second` is zero.
+pub(crate) fn absolute_time(
+    earliest_time: Option<Timestamp>,
+    time_offset: Option<UTicks>,
+    block_parameters: &BlockParameters,
+) -> Option<SystemTime> {
+    crate::ticks::time_at(
+        earliest_time?,
+        time_offset.map_or(0, u32::from).into(),
+        block_parameters.storage_parameters.ticks_per_second.into(),
+    )
+    .ok()
+}
+
+/// Convert a stored [`IpAddr`] into a [`std::net::IpAddr`].
+///
+/// The IP version is taken from `ip_version` if known.
+/// Otherwise addresses of up to 4 bytes are treated as IPv4 and longer ones as IPv6.
+pub(crate) fn to_std_ip(ip: &IpAddr, ip_version: Option<IpVersion>) -> Option<std::net::IpAddr> {
+    let ip_version = ip_version.unwrap_or(if ip.as_ipv4().is_ok() {
+        IpVersion::Ipv4
+    } else {
+        IpVersion::Ipv6
+    });
+    match ip_version {
+        IpVersion::Ipv4 => ip.as_ipv4().ok().map(std::net::IpAddr::V4),
+        IpVersion::Ipv6 => ip.as_ipv6().ok().map(std::net::IpAddr::V6),
+    }
+}
+
+/// Render a name in presentation format, falling back to the raw bytes if it cannot be decoded.
+pub(crate) fn name_to_string(name: &NameOrRdata) -> String {
+    name.to_domain_name()
+        .map(|name| name.to_string())
+        .unwrap_or_else(|_| format!("{:?}", name.as_bytes()))
+}
@@ -0,0 +1,710 @@
+//! Rebuild DNS messages in wire format from Q/R items
+//!
+//! A [`QueryResponse`] stores the Query and the Response of a transaction split over the [`BlockTables`].
+//! [`QueryResponse::to_wire`] reassembles either message as a complete DNS message, which can be parsed by any DNS library or sent again.
+//!
+//! The rebuilt message is only as complete as the stored data.
+//! Sections which were not collected, as indicated by the [`StorageHints`], are empty and names are never compressed.
+//! The header counts describe the rebuilt message, not the original one.
+//!
+//! In the other direction, [`Message::parse`] parses a DNS message without depending on an external DNS library.
+//! [`check_message`] reports where and why parsing fails, and [`MalformedMessageData::diagnose`] applies it to the payload of a malformed message.
+//!
+//! ```
+//! # use c_dns::serialization::File;
+//! # use c_dns::wire::Direction;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! let file = File::read_path("./tests/data/dns.cdns")?;
+//! let block = &file.file_blocks[0];
+//! let block_tables = block.block_tables.as_ref().unwrap();
+//! let query_response = &block.query_responses.as_ref().unwrap()[0];
+//!
+//! let message = query_response.to_wire(block_tables, Direction::Query)?;
+//! // The QR bit is not set for a Query
+//! assert_eq!(0, message[2] & 0x80);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{bail, invalid};
+use crate::flags::DnsHeaderFlags;
+use crate::serialization::*;
+use crate::Result;
+use enumset::EnumSet;
+use std::fmt;
+
+/// Type of the OPT pseudo RR, RFC 6891
+const OPT_TYPE: u16 = 41;
+
+/// Which message of a [`QueryResponse`] to rebuild.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Direction {
+    Query,
+    Response,
+}
+
+impl QueryResponse {
+    /// Rebuild the Query or Response of this item as a DNS message in wire format.
+    ///
+    /// The header is built from the [`QueryResponseSignature`] and the `transaction_id`.
+    /// The first Question uses the `query_name_index` and the `query_classtype_index`, further Questions and the RR sections come from the [`QueryResponseExtended`] data.
+    /// A Query with [`QueryResponseFlags::QueryHasOpt`] gets an OPT RR built from the EDNS fields of the signature.
+    /// A Response only stores whether it had an OPT RR, so the OPT RR of a Response reuses the EDNS version and UDP size of the Query and has no options.
+    ///
+    /// Fails if the message was not present or an index is not valid in `block_tables`.
+    pub fn to_wire(&self, block_tables: &BlockTables, direction: Direction) -> Result<Vec<u8>> {
+        let signature = match self.qr_signature_index {
+            Some(idx) => Some(block_tables.qr_signature(idx)?),
+            None => None,
+        };
+        let qr_sig_flags = signature.and_then(|sig| sig.qr_sig_flags);
+        let dns_flags = signature
+            .and_then(|sig| sig.qr_dns_flags)
+            .unwrap_or_default();
+
+        let (has_message, has_opt, has_no_question, extended) = match direction {
+            Direction::Query => (
+                QueryResponseFlags::HasQuery,
+                QueryResponseFlags::QueryHasOpt,
+                QueryResponseFlags::QueryHasNoQuestion,
+                &self.query_extended,
+            ),
+            Direction::Response => (
+                QueryResponseFlags::HasResponse,
+                QueryResponseFlags::ResponseHasOpt,
+                QueryResponseFlags::ResponseHasNoQuestion,
+                &self.response_extended,
+            ),
+        };
+        let qr_sig_flags: EnumSet<QueryResponseFlags> = match qr_sig_flags {
+            Some(flags) if !flags.contains(has_message) => {
+                bail!("The Q/R item does not contain a {:?}", direction)
+            }
+            Some(flags) => flags,
+            None => EnumSet::empty(),
+        };
+        let rcode = match direction {
+            Direction::Query => signature.and_then(|sig| sig.query_rcode),
+            Direction::Response => signature.and_then(|sig| sig.response_rcode),
+        }
+        .unwrap_or(0);
+
+        // Questions
+        let mut questions = Vec::new();
+        if !qr_sig_flags.contains(has_no_question) {
+            if let Some(name_index) = self.query_name_index {
+                let classtype_index = signature
+                    .and_then(|sig| sig.query_classtype_index)
+                    .ok_or_else(|| {
+                        invalid!("Missing query_classtype_index for the first Question")
+                    })?;
+                questions.push((name_index, classtype_index));
+            }
+        }
+        let extended = extended.as_ref();
+        if let Some(idx) = extended.and_then(|extended| extended.question_index) {
+            let qlist = block_tables.question_list(idx)?;
+            for &idx in qlist {
+                let question = block_tables.question(idx)?;
+                questions.push((question.name_index, question.classtype_index));
+            }
+        }
+
+        // RR sections
+        let answers = rr_section(block_tables, extended.and_then(|e| e.answer_index))?;
+        let authorities = rr_section(block_tables, extended.and_then(|e| e.authority_index))?;
+        let additionals = rr_section(block_tables, extended.and_then(|e| e.additional_index))?;
+        let stores_opt = additionals
+            .iter()
+            .map(|rr| block_tables.classtype(rr.classtype_index))
+            .collect::<Result<Vec<_>>>()?
+            .iter()
+            .any(|classtype| u16::from(classtype.type_) == OPT_TYPE);
+        let opt = if qr_sig_flags.contains(has_opt) && !stores_opt {
+            Some(opt_rr(signature, direction, rcode, dns_flags))
+        } else {
+            None
+        };
+
+        let additional_count = additionals.len() + usize::from(opt.is_some());
+        let mut message = Vec::with_capacity(512);
+        message.extend_from_slice(&self.transaction_id.unwrap_or(0).to_be_bytes());
+        message.extend_from_slice(&header_flags(signature, direction, rcode, dns_flags));
+        for count in [
+            questions.len(),
+            answers.len(),
+            authorities.len(),
+            additional_count,
+        ] {
+            let count =
+                u16::try_from(count).map_err(|_| invalid!("Too many entries in a section"))?;
+            message.extend_from_slice(&count.to_be_bytes());
+        }
+
+        for (name_index, classtype_index) in questions {
+            message.extend_from_slice(block_tables.name(name_index)?.as_bytes());
+            let classtype = block_tables.classtype(classtype_index)?;
+            message.extend_from_slice(&u16::from(classtype.type_).to_be_bytes());
+            message.extend_from_slice(&u16::from(classtype.class).to_be_bytes());
+        }
+        for rr in answers.iter().chain(&authorities).chain(&additionals) {
+            write_rr(&mut message, block_tables, rr)?;
+        }
+        if let Some((class, ttl, rdata_index)) = opt {
+            // The OPT RR is owned by the root domain
+            message.push(0);
+            message.extend_from_slice(&OPT_TYPE.to_be_bytes());
+            message.extend_from_slice(&class.to_be_bytes());
+            message.extend_from_slice(&ttl.to_be_bytes());
+            write_rdata(&mut message, block_tables, rdata_index)?;
+        }
+        Ok(message)
+    }
+}
+
+/// The second 16 bits of the header, containing the QR bit, the OPCODE, the flags, and the RCODE.
+fn header_flags(
+    signature: Option<&QueryResponseSignature>,
+    direction: Direction,
+    rcode: u16,
+    dns_flags: EnumSet<DNSFlags>,
+) -> [u8; 2] {
+    let opcode = signature
+        .and_then(|sig| sig.query_opcode)
+        .map(u8::from)
+        .unwrap_or(0);
+    let mut bits = DnsHeaderFlags::from_dns_flags(dns_flags, direction).header_bits()
+        | u16::from(opcode & 0x0f) << 11
+        | (rcode & 0x0f);
+    if direction == Direction::Response {
+        bits |= 0x8000;
+    }
+    bits.to_be_bytes()
+}
+
+/// CLASS, TTL, and RDATA index of the OPT RR.
+///
+/// The upper 8 bits of the 12-bit RCODE are stored in the TTL.
+fn opt_rr(
+    signature: Option<&QueryResponseSignature>,
+    direction: Direction,
+    rcode: u16,
+    dns_flags: EnumSet<DNSFlags>,
+) -> (u16, u32, Option<usize>) {
+    let udp_size = signature.and_then(|sig| sig.query_udp_size).unwrap_or(512);
+    let version = signature
+        .and_then(|sig| sig.query_edns_version)
+        .unwrap_or(0);
+    let mut ttl = (u32::from(rcode >> 4) & 0xff) << 24 | u32::from(version) << 16;
+    let rdata_index = match direction {
+        Direction::Query => {
+            if dns_flags.contains(DNSFlags::QueryDo) {
+                ttl |= 0x8000;
+            }
+            signature.and_then(|sig| sig.query_opt_rdata_index)
+        }
+        Direction::Response => None,
+    };
+    (udp_size, ttl, rdata_index)
+}
+
+fn rr_section(block_tables: &BlockTables, index: Option<usize>) -> Result<Vec<&RR>> {
+    let idx = match index {
+        Some(idx) => idx,
+        None => return Ok(Vec::new()),
+    };
+    block_tables
+        .rr_list(idx)?
+        .iter()
+        .map(|&idx| block_tables.rr(idx))
+        .collect()
+}
+
+fn write_rr(message: &mut Vec<u8>, block_tables: &BlockTables, rr: &RR) -> Result<()> {
+    message.extend_from_slice(block_tables.name(rr.name_index)?.as_bytes());
+    let classtype = block_tables.classtype(rr.classtype_index)?;
+    message.extend_from_slice(&u16::from(classtype.type_).to_be_bytes());
+    message.extend_from_slice(&u16::from(classtype.class).to_be_bytes());
+    message.extend_from_slice(&rr.ttl.unwrap_or(0).to_be_bytes());
+    write_rdata(message, block_tables, rr.rdata_index)
+}
+
+/// Write RDLENGTH and RDATA.
+fn write_rdata(
+    message: &mut Vec<u8>,
+    block_tables: &BlockTables,
+    rdata_index: Option<usize>,
+) -> Result<()> {
+    let rdata = match rdata_index {
+        Some(idx) => block_tables.name(idx)?.as_bytes(),
+        None => &[],
+    };
+    let rdlength = u16::try_from(rdata.len()).map_err(|_| invalid!("RDATA is too long"))?;
+    message.extend_from_slice(&rdlength.to_be_bytes());
+    message.extend_from_slice(rdata);
+    Ok(())
+}
+
+/// Part of a DNS message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Section {
+    Header,
+    Question,
+    Answer,
+    Authority,
+    Additional,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Section::Header => "header",
+            Section::Question => "question",
+            Section::Answer => "answer",
+            Section::Authority => "authority",
+            Section::Additional => "additional",
+        })
+    }
+}
+
+/// Reason why a DNS message could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireErrorKind {
+    /// The message ended, but `needed` more bytes were expected.
+    ///
+    /// This happens for truncated messages and if the header counts are larger than the number of records.
+    Truncated { needed: usize },
+    /// The label type bits `0b01` and `0b10` are not assigned.
+    ///
+    /// Label lengths are stored in the remaining 6 bits, so a length above 63 shows up as such a label type.
+    BadLabelType { byte: u8 },
+    /// A compression pointer does not point to an earlier name in the message.
+    BadPointer { target: usize },
+    /// The name is longer than 255 bytes.
+    NameTooLong,
+    /// Bytes after the last record, like if the header counts are smaller than the number of records.
+    TrailingBytes { count: usize },
+}
+
+impl fmt::Display for WireErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireErrorKind::Truncated { needed } => {
+                write!(f, "message is truncated, {} more bytes expected", needed)
+            }
+            WireErrorKind::BadLabelType { byte } => write!(f, "unknown label type {:#04x}", byte),
+            WireErrorKind::BadPointer { target } => {
+                write!(
+                    f,
+                    "compression pointer to offset {} is not backwards",
+                    target
+                )
+            }
+            WireErrorKind::NameTooLong => f.write_str("name exceeds 255 bytes"),
+            WireErrorKind::TrailingBytes { count } => {
+                write!(f, "{} trailing bytes after the last record", count)
+            }
+        }
+    }
+}
+
+/// Location and reason of a failure to parse a DNS message.
+///
+/// See [`check_message`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireError {
+    /// Byte offset into the message where the failing element starts.
+    pub offset: usize,
+    /// Section containing the failing element.
+    pub section: Section,
+    /// Position of the failing record within the section, 0 for the header.
+    pub record: usize,
+    /// Reason of the failure.
+    pub kind: WireErrorKind,
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.section {
+            Section::Header => write!(f, "header at offset {}: {}", self.offset, self.kind),
+            section => write!(
+                f,
+                "{} record {} at offset {}: {}",
+                section, self.record, self.offset, self.kind
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+impl MalformedMessageData {
+    /// Find the reason why the `mm_payload` is malformed, see [`check_message`].
+    ///
+    /// Returns [`None`] if there is no payload or the payload can be parsed.
+    pub fn diagnose(&self) -> Option<WireError> {
+        check_message(self.mm_payload.as_ref()?).err()
+    }
+}
+
+/// Parse the structure of the DNS message and report where and why parsing fails.
+///
+/// The header counts, all names including compression pointers, and the lengths of all records are checked.
+/// The content of the RDATA is not checked.
+pub fn check_message(message: &[u8]) -> Result<(), WireError> {
+    Message::parse(message).map(|_| ())
+}
+
+/// Header of a DNS message, RFC 1035 Section 4.1.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Header {
+    /// Transaction ID
+    pub id: u16,
+    /// The second 16 bits of the header, containing the QR bit, the OPCODE, the flags, and the RCODE.
+    pub flags: u16,
+    pub qdcount: u16,
+    pub ancount: u16,
+    pub nscount: u16,
+    pub arcount: u16,
+}
+
+impl Header {
+    /// Whether the QR bit marks the message as a Response.
+    pub fn is_response(&self) -> bool {
+        self.flags & 0x8000 != 0
+    }
+
+    /// The 4 bit OPCODE.
+    pub fn opcode(&self) -> u8 {
+        ((self.flags >> 11) & 0x0f) as u8
+    }
+
+    /// The 4 bit RCODE of the header, without the extension bits from the OPT RR.
+    pub fn rcode(&self) -> u8 {
+        (self.flags & 0x0f) as u8
+    }
+}
+
+/// Entry of the Question section.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Question {
+    /// The uncompressed QNAME in wire format.
+    pub name: NameOrRdata,
+    pub rr_type: u16,
+    pub class: u16,
+}
+
+/// Resource record of the Answer, Authority, or Additional section.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Record {
+    /// The uncompressed owner name in wire format.
+    pub name: NameOrRdata,
+    pub rr_type: u16,
+    pub class: u16,
+    pub ttl: u32,
+    /// The RDATA as contained in the message.
+    ///
+    /// Names in the RDATA can be compressed, use [`Record::rdata_name`] to read them.
+    pub rdata: NameOrRdata,
+    /// Byte offset of the RDATA in the message.
+    pub rdata_offset: usize,
+}
+
+impl Record {
+    /// Read the name at the start of the RDATA, like for CNAME, NS, and PTR records.
+    ///
+    /// `message` must be the message this record was parsed from, since the name can point to earlier names in the message.
+    pub fn rdata_name(&self, message: &[u8]) -> Result<NameOrRdata, WireError> {
+        read_name(message, self.rdata_offset).map(|(name, _)| name)
+    }
+}
+
+/// Option contained in the OPT RR, RFC 6891 Section 6.1.2
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EdnsOption {
+    pub code: u16,
+    pub data: Vec<u8>,
+}
+
+/// The fields of the OPT pseudo RR, RFC 6891 Section 6.1.3
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Opt {
+    /// Requestor's UDP payload size, stored in the CLASS field.
+    pub udp_payload_size: u16,
+    /// Upper 8 bits of the extended RCODE.
+    pub extended_rcode: u8,
+    /// EDNS version.
+    pub version: u8,
+    /// Flags, containing the DO bit as the most significant bit.
+    pub flags: u16,
+    pub options: Vec<EdnsOption>,
+}
+
+/// A parsed DNS message, RFC 1035 Section 4.1
+///
+/// The parser only depends on the standard library.
+/// It reads the header, all sections, and the OPT RR, but does not interpret the RDATA of other records.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Message {
+    pub header: Header,
+    pub questions: Vec<Question>,
+    pub answers: Vec<Record>,
+    pub authorities: Vec<Record>,
+    /// The Additional section without the OPT RR.
+    pub additionals: Vec<Record>,
+    /// The OPT RR from the Additional section.
+    pub opt: Option<Opt>,
+}
+
+impl Message {
+    /// Parse a complete DNS message.
+    ///
+    /// Fails with the location of the first problem, like [`check_message`].
+    /// Only the first OPT RR is stored in [`Message::opt`], further OPT RRs are kept in the Additional section.
+    pub fn parse(message: &[u8]) -> Result<Message, WireError> {
+        let mut parser = Parser {
+            message,
+            pos: 0,
+            section: Section::Header,
+            record: 0,
+        };
+        let record_start = parser.pos;
+        let header = parser.take(12, record_start)?;
+        let field = |idx: usize| u16::from_be_bytes([header[idx], header[idx + 1]]);
+        let header = Header {
+            id: field(0),
+            flags: field(2),
+            qdcount: field(4),
+            ancount: field(6),
+            nscount: field(8),
+            arcount: field(10),
+        };
+
+        parser.section = Section::Question;
+        let mut questions = Vec::with_capacity(usize::from(header.qdcount).min(16));
+        for record in 0..usize::from(header.qdcount) {
+            parser.record = record;
+            let record_start = parser.pos;
+            let name = parser.name()?;
+            // TYPE and CLASS
+            let fixed = parser.take(4, record_start)?;
+            questions.push(Question {
+                name: NameOrRdata::from(bytes::Bytes::from(name)),
+                rr_type: u16::from_be_bytes([fixed[0], fixed[1]]),
+                class: u16::from_be_bytes([fixed[2], fixed[3]]),
+            });
+        }
+
+        let answers = parser.records(Section::Answer, header.ancount)?;
+        let authorities = parser.records(Section::Authority, header.nscount)?;
+        let mut additionals = parser.records(Section::Additional, header.arcount)?;
+        if parser.pos < message.len() {
+            return Err(parser.error(
+                parser.pos,
+                WireErrorKind::TrailingBytes {
+                    count: message.len() - parser.pos,
+                },
+            ));
+        }
+
+        let opt = match additionals
+            .iter()
+            .position(|record| record.rr_type == OPT_TYPE)
+        {
+            Some(idx) => {
+                parser.section = Section::Additional;
+                parser.record = idx;
+                let record = additionals.remove(idx);
+                Some(parser.opt(&record)?)
+            }
+            None => None,
+        };
+
+        Ok(Message {
+            header,
+            questions,
+            answers,
+            authorities,
+            additionals,
+            opt,
+        })
+    }
+
+    /// The 12 bit RCODE, combining the header RCODE with the extension bits from the OPT RR.
+    pub fn rcode(&self) -> u16 {
+        let extended = self.opt.as_ref().map_or(0, |opt| opt.extended_rcode);
+        u16::from(extended) << 4 | u16::from(self.header.rcode())
+    }
+}
+
+/// Read the possibly compressed name starting at `pos` in `message`.
+///
+/// Returns the uncompressed name and the position after the name.
+pub(crate) fn read_name(message: &[u8], pos: usize) -> Result<(NameOrRdata, usize), WireError> {
+    let mut parser = Parser {
+        message,
+        pos,
+        section: Section::Answer,
+        record: 0,
+    };
+    let name = parser.name()?;
+    Ok((NameOrRdata::from(bytes::Bytes::from(name)), parser.pos))
+}
+
+struct Parser<'a> {
+    message: &'a [u8],
+    pos: usize,
+    section: Section,
+    record: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, offset: usize, kind: WireErrorKind) -> WireError {
+        WireError {
+            offset,
+            section: self.section,
+            record: self.record,
+            kind,
+        }
+    }
+
+    /// Consume `len` bytes of the element starting at `start`.
+    fn take(&mut self, len: usize, start: usize) -> Result<&'a [u8], WireError> {
+        let bytes = self.message.get(self.pos..self.pos + len).ok_or_else(|| {
+            self.error(
+                start,
+                WireErrorKind::Truncated {
+                    needed: self.pos + len - self.message.len(),
+                },
+            )
+        })?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Consume `count` resource records of `section`.
+    fn records(&mut self, section: Section, count: u16) -> Result<Vec<Record>, WireError> {
+        self.section = section;
+        let mut records = Vec::with_capacity(usize::from(count).min(16));
+        for record in 0..usize::from(count) {
+            self.record = record;
+            let record_start = self.pos;
+            let name = self.name()?;
+            // TYPE, CLASS, TTL, and RDLENGTH
+            let fixed = self.take(10, record_start)?;
+            let rdlength = usize::from(u16::from_be_bytes([fixed[8], fixed[9]]));
+            let rdata_offset = self.pos;
+            let rdata = self.take(rdlength, record_start)?;
+            records.push(Record {
+                name: NameOrRdata::from(bytes::Bytes::from(name)),
+                rr_type: u16::from_be_bytes([fixed[0], fixed[1]]),
+                class: u16::from_be_bytes([fixed[2], fixed[3]]),
+                ttl: u32::from_be_bytes([fixed[4], fixed[5], fixed[6], fixed[7]]),
+                rdata: NameOrRdata::from(bytes::Bytes::copy_from_slice(rdata)),
+                rdata_offset,
+            });
+        }
+        Ok(records)
+    }
+
+    /// Split the fields of an OPT RR, including the options in its RDATA.
+    fn opt(&self, record: &Record) -> Result<Opt, WireError> {
+        let rdata = record.rdata.as_bytes();
+        let mut options = Vec::new();
+        let mut pos = 0;
+        while pos < rdata.len() {
+            let offset = record.rdata_offset + pos;
+            let header = rdata.get(pos..pos + 4).ok_or_else(|| {
+                self.error(
+                    offset,
+                    WireErrorKind::Truncated {
+                        needed: pos + 4 - rdata.len(),
+                    },
+                )
+            })?;
+            let code = u16::from_be_bytes([header[0], header[1]]);
+            let len = usize::from(u16::from_be_bytes([header[2], header[3]]));
+            let data = rdata.get(pos + 4..pos + 4 + len).ok_or_else(|| {
+                self.error(
+                    offset,
+                    WireErrorKind::Truncated {
+                        needed: pos + 4 + len - rdata.len(),
+                    },
+                )
+            })?;
+            options.push(EdnsOption {
+                code,
+                data: data.to_vec(),
+            });
+            pos += 4 + len;
+        }
+        let ttl = record.ttl.to_be_bytes();
+        Ok(Opt {
+            udp_payload_size: record.class,
+            extended_rcode: ttl[0],
+            version: ttl[1],
+            flags: u16::from_be_bytes([ttl[2], ttl[3]]),
+            options,
+        })
+    }
+
+    /// Consume a possibly compressed name and return it uncompressed.
+    fn name(&mut self) -> Result<Vec<u8>, WireError> {
+        let start = self.pos;
+        let mut name = Vec::new();
+        // Position to read the next label from, which differs from `self.pos` after following a pointer
+        let mut pos = self.pos;
+        // Pointers must point before the start of the current name, which also prevents loops
+        let mut limit = start;
+        let mut followed_pointer = false;
+        let mut name_len = 0;
+        loop {
+            let byte = *self
+                .message
+                .get(pos)
+                .ok_or_else(|| self.error(start, WireErrorKind::Truncated { needed: 1 }))?;
+            match byte & 0xc0 {
+                0x00 => {
+                    let len = usize::from(byte);
+                    if pos + 1 + len > self.message.len() {
+                        return Err(self.error(
+                            start,
+                            WireErrorKind::Truncated {
+                                needed: pos + 1 + len - self.message.len(),
+                            },
+                        ));
+                    }
+                    name_len += 1 + len;
+                    if name_len > 255 {
+                        return Err(self.error(start, WireErrorKind::NameTooLong));
+                    }
+                    name.extend_from_slice(&self.message[pos..pos + 1 + len]);
+                    pos += 1 + len;
+                    if !followed_pointer {
+                        self.pos = pos;
+                    }
+                    if len == 0 {
+                        return Ok(name);
+                    }
+                }
+                0xc0 => {
+                    let low = *self
+                        .message
+                        .get(pos + 1)
+                        .ok_or_else(|| self.error(start, WireErrorKind::Truncated { needed: 1 }))?;
+                    let target = usize::from(u16::from_be_bytes([byte & 0x3f, low]));
+                    if target >= limit {
+                        return Err(self.error(start, WireErrorKind::BadPointer { target }));
+                    }
+                    if !followed_pointer {
+                        self.pos = pos + 2;
+                        followed_pointer = true;
+                    }
+                    limit = target;
+                    pos = target;
+                }
+                _ => return Err(self.error(start, WireErrorKind::BadLabelType { byte })),
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Write C-DNS files into raw bytes
+//!
+//! [`serde_cbor::to_vec`] allocates a new vector on each call.
+//! When writing many blocks, the functions here allow reusing a single buffer instead.
+//! For writing a whole [`File`] at once, see [`File::to_vec`], [`File::to_writer`], and [`File::write_path`].
+//! Long-running captures can write their blocks as they are completed with a [`FileWriter`].
+//!
+//! ```
+//! # use c_dns::serialization::File;
+//! # fn main() -> color_eyre::eyre::Result<()> {
+//! # let file: File = serde_cbor::from_slice(&std::fs::read("./tests/data/dns.cdns")?)?;
+//! let mut buffer = Vec::new();
+//! for block in &file.file_blocks {
+//!     buffer.clear();
+//!     block.serialize_into(&mut buffer)?;
+//!     // Write the buffer to the output
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::Context;
+use crate::serialization::{Block, File, FilePreamble, StorageHints, FILE_TYPE_ID};
+use crate::Result;
+use std::io::Write;
+use std::path::Path;
+
+impl File {
+    /// Serialize the file into a new vector.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        Ok(serde_cbor::to_vec(self)?)
+    }
+
+    /// Serialize the file into a new vector in the deterministic CBOR encoding.
+    ///
+    /// Files with equal content serialize into equal bytes, see [`canonicalize`](crate::encoding::canonicalize).
+    pub fn to_canonical_vec(&self) -> Result<Vec<u8>> {
+        crate::encoding::canonicalize(&self.to_vec()?)
+    }
+
+    /// Serialize the file into `writer`.
+    ///
+    /// The writer is not buffered, so wrap it in a [`BufWriter`](std::io::BufWriter) if necessary.
+    pub fn to_writer(&self, writer: impl Write) -> Result<()> {
+        Ok(serde_cbor::to_writer(writer, self)?)
+    }
+
+    /// Serialize the file and store it at `path`, replacing any existing file.
+    pub fn write_path(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_vec()?)
+            .with_context(|| format!("Failed to write file {}", path.display()))
+    }
+
+    /// Append the CBOR encoding of the file to `buffer`.
+    ///
+    /// The existing content of `buffer` is kept, so the buffer must be cleared before being reused.
+    pub fn serialize_into(&self, buffer: &mut Vec<u8>) -> serde_cbor::Result<()> {
+        serde_cbor::to_writer(buffer, self)
+    }
+}
+
+impl Block {
+    /// Append the CBOR encoding of the block to `buffer`.
+    ///
+    /// The existing content of `buffer` is kept, so the buffer must be cleared before being reused.
+    pub fn serialize_into(&self, buffer: &mut Vec<u8>) -> serde_cbor::Result<()> {
+        serde_cbor::to_writer(buffer, self)
+    }
+}
+
+/// Write a [`File`] one [`Block`] at a time.
+///
+/// The file type id and the [`FilePreamble`] are written when creating the writer.
+/// Each block is written and flushed as soon as it is added, so only the current block needs to be kept in memory.
+/// The blocks are written as an indefinite-length array, which [`FileWriter::finish`] terminates.
+/// A writer dropped without calling [`FileWriter::finish`] leaves an incomplete file, which can still be read with [`File::from_slice_salvage`].
+///
+/// ```
+/// # use c_dns::serialization::{File, FileWriter};
+/// # fn main() -> color_eyre::eyre::Result<()> {
+/// # let file = File::read_path("./tests/data/dns.cdns")?;
+/// let mut writer = FileWriter::new(Vec::new(), &file.file_preamble)?;
+/// for block in &file.file_blocks {
+///     writer.write_block(block)?;
+/// }
+/// let bytes = writer.finish()?;
+/// assert_eq!(file.block_count(), File::from_slice(&bytes)?.block_count());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FileWriter<W: Write> {
+    writer: W,
+    block_count: usize,
+    /// Encoding of the current block, reused between blocks
+    buffer: Vec<u8>,
+    /// Hints of each [`BlockParameters`](crate::serialization::BlockParameters), if blocks are stripped to them
+    storage_hints: Option<Vec<StorageHints>>,
+}
+
+impl<W: Write> FileWriter<W> {
+    /// Write the start of a file with `file_preamble` to `writer`.
+    ///
+    /// The writer is not buffered, so wrap it in a [`BufWriter`](std::io::BufWriter) if necessary.
+    pub fn new(mut writer: W, file_preamble: &FilePreamble) -> Result<Self> {
+        let mut buffer = Vec::new();
+        // Array of the file type id, the preamble, and the blocks
+        buffer.push(0x83);
+        serde_cbor::to_writer(&mut buffer, &FILE_TYPE_ID)?;
+        serde_cbor::to_writer(&mut buffer, file_preamble)?;
+        // Indefinite-length array of blocks
+        buffer.push(0x9f);
+        writer
+            .write_all(&buffer)
+            .context("Failed to write the file preamble")?;
+        writer.flush()?;
+        buffer.clear();
+        Ok(Self {
+            writer,
+            block_count: 0,
+            buffer,
+            storage_hints: None,
+        })
+    }
+
+    /// Remove all fields not covered by the [`StorageHints`] from each block before writing it, see [`Block::strip_to_hints`].
+    ///
+    /// The hints are taken from the [`BlockParameters`](crate::serialization::BlockParameters) of `file_preamble` referenced by the block.
+    /// Blocks referencing non-existing parameters are written unchanged.
+    pub fn strip_to_hints(mut self, file_preamble: &FilePreamble) -> Self {
+        self.storage_hints = Some(
+            file_preamble
+                .block_parameters
+                .iter()
+                .map(|params| params.storage_parameters.storage_hints.clone())
+                .collect(),
+        );
+        self
+    }
+
+    /// Serialize `block`, write it, and flush the writer.
+    pub fn write_block(&mut self, block: &Block) -> Result<()> {
+        self.buffer.clear();
+        let index = block.block_preamble.block_parameters_index.unwrap_or(0);
+        match self
+            .storage_hints
+            .as_ref()
+            .and_then(|hints| hints.get(index))
+        {
+            Some(hints) => {
+                let mut block = block.clone();
+                block.strip_to_hints(hints);
+                block.serialize_into(&mut self.buffer)?;
+            }
+            None => block.serialize_into(&mut self.buffer)?,
+        }
+        self.writer
+            .write_all(&self.buffer)
+            .with_context(|| format!("Failed to write block {}", self.block_count))?;
+        self.writer.flush()?;
+        self.block_count += 1;
+        Ok(())
+    }
+
+    /// Number of blocks written so far.
+    pub fn block_count(&self) -> usize {
+        self.block_count
+    }
+
+    /// Terminate the array of blocks and return the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.writer
+            .write_all(&[0xff])
+            .context("Failed to finish the file")?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
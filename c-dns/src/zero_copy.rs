@@ -0,0 +1,87 @@
+//! Deserialize without copying byte strings
+//!
+//! The `ip_address` and `name_rdata` tables make up most of a file.
+//! Deserializing from a slice copies each of their byte strings into a new allocation.
+//! [`File::from_bytes`] instead keeps the input alive in a reference counted [`Bytes`] and lets each [`IpAddr`] and [`NameOrRdata`] point into it.
+//! The input is passed to the decoder of the block tables, which references it with [`shared_bytes`] for every address and name.
+
+use crate::read::decode_fast;
+use crate::serialization::*;
+use crate::Result;
+use bytes::Bytes;
+use serde::de::{Deserializer, SeqAccess, Visitor};
+use std::fmt;
+
+impl File {
+    /// Deserialize a [`File`] from `bytes`, sharing the byte strings with the input.
+    ///
+    /// This uses the same decoder as [`File::from_slice_fast`], but each [`IpAddr`] and [`NameOrRdata`] references `bytes` instead of copying.
+    /// Like [`File::from_slice`], it fails if any bytes follow the [`File`].
+    /// No [`Limits`](crate::limits::Limits) are checked, so untrusted input needs to pass [`Limits::check`](crate::limits::Limits::check) first.
+    ///
+    /// Combined with a memory map this reads large captures without copying them, since [`Bytes::from_owner`] accepts any owner of bytes, like a `memmap2::Mmap`.
+    /// The input stays in memory as long as any of the addresses or names point into it.
+    /// Blocks with encodings the fast decoder does not handle are decoded with serde, which copies their byte strings.
+    ///
+    /// ```
+    /// # use c_dns::serialization::File;
+    /// # fn main() -> color_eyre::eyre::Result<()> {
+    /// let bytes = bytes::Bytes::from(std::fs::read("./tests/data/dns.cdns")?);
+    /// let file = File::from_bytes(bytes.clone())?;
+    /// let name = &file.file_blocks[0].block_tables.as_ref().unwrap().name_rdata.as_ref().unwrap()[0];
+    /// // The name points into the input
+    /// assert!(bytes.as_ptr_range().contains(&name.as_bytes().as_ptr()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_bytes(bytes: Bytes) -> Result<File> {
+        decode_fast(&bytes, Some(&bytes))
+    }
+}
+
+/// Reference `value` in `input`, or copy it if it is not part of the input.
+pub(crate) fn shared_bytes(input: Option<&Bytes>, value: &[u8]) -> Bytes {
+    match input {
+        Some(input) if contains(input, value) => input.slice_ref(value),
+        _ => Bytes::copy_from_slice(value),
+    }
+}
+
+fn contains(input: &[u8], value: &[u8]) -> bool {
+    let input = input.as_ptr_range();
+    let value = value.as_ptr_range();
+    input.start <= value.start && value.end <= input.end
+}
+
+/// Deserialize a byte string into [`Bytes`].
+pub(crate) fn deserialize_bytes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Bytes, D::Error> {
+    deserializer.deserialize_bytes(BytesVisitor)
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Bytes;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a byte string")
+    }
+
+    fn visit_bytes<E>(self, value: &[u8]) -> std::result::Result<Bytes, E> {
+        Ok(Bytes::copy_from_slice(value))
+    }
+
+    fn visit_byte_buf<E>(self, value: Vec<u8>) -> std::result::Result<Bytes, E> {
+        Ok(Bytes::from(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Bytes, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(Bytes::from(bytes))
+    }
+}
@@ -0,0 +1,521 @@
+use c_dns::analysis::amplification::AmplificationReport;
+use c_dns::analysis::bailiwick::BailiwickReport;
+use c_dns::analysis::domains::{DomainAggregation, DomainHierarchy};
+use c_dns::analysis::fingerprint::{ClientFingerprint, FingerprintReport};
+use c_dns::analysis::k_anonymity::{KAnonymityAggregation, KAnonymityConfig};
+use c_dns::analysis::malformed::MalformedReport;
+use c_dns::analysis::repeated::RepeatedQueryDetector;
+use c_dns::analysis::stats::Statistics;
+use c_dns::analysis::transport::TransportBreakdown;
+use c_dns::serialization::File;
+use c_dns::{IpVersion, Transport};
+use color_eyre::eyre::Result;
+use std::time::Duration;
+
+fn read_test_file() -> Result<File> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    Ok(serde_cbor::from_slice(&c_dns_content)?)
+}
+
+#[test]
+fn time_buckets_cover_all_query_responses() -> Result<()> {
+    let file = read_test_file()?;
+
+    let buckets: Vec<_> = file.time_buckets(Duration::from_millis(100)).collect();
+    assert!(buckets.len() > 1);
+    assert!(buckets.windows(2).all(|w| w[0].start < w[1].start));
+    assert_eq!(12, buckets.iter().map(|b| b.query_responses).sum::<usize>());
+    assert_eq!(12, buckets.iter().map(|b| b.queries).sum::<usize>());
+
+    let buckets: Vec<_> = file.time_buckets(Duration::from_secs(3600)).collect();
+    assert_eq!(1, buckets.len());
+    assert_eq!(12, buckets[0].responses);
+    Ok(())
+}
+
+#[test]
+fn transport_breakdown() -> Result<()> {
+    let file = read_test_file()?;
+
+    let breakdown = TransportBreakdown::from_file(&file);
+    assert_eq!(0, breakdown.unknown_transport);
+    assert_eq!(12, breakdown.total().query_responses);
+    let ipv6 = breakdown.by_ip_version(IpVersion::Ipv6);
+    let ipv4 = breakdown.by_ip_version(IpVersion::Ipv4);
+    assert!(ipv6.query_responses > 0);
+    assert_eq!(12, ipv4.query_responses + ipv6.query_responses);
+    assert_eq!(12, breakdown.by_transport(Transport::Udp).query_responses);
+    assert_eq!(0., breakdown.total().trailing_data_rate());
+    assert!(breakdown.to_string().contains("IPv6 UDP"));
+    Ok(())
+}
+
+#[cfg(feature = "non-rfc-transports")]
+#[test]
+fn transport_breakdown_quic() -> Result<()> {
+    use c_dns::serialization::TransportFlags;
+
+    let mut file = read_test_file()?;
+    for block in &mut file.file_blocks {
+        let qr_sig = block
+            .block_tables
+            .as_mut()
+            .unwrap()
+            .qr_sig
+            .as_mut()
+            .unwrap();
+        for signature in qr_sig {
+            let flags = signature.qr_transport_flags.unwrap();
+            signature.qr_transport_flags = Some(TransportFlags::new(
+                flags.ip_version(),
+                Transport::Quic,
+                false,
+            ));
+        }
+    }
+
+    let breakdown = TransportBreakdown::from_file(&file);
+    assert_eq!(12, breakdown.by_transport(Transport::Quic).query_responses);
+    assert_eq!(0, breakdown.by_transport(Transport::Udp).query_responses);
+    assert!(breakdown.to_string().contains("IPv6 QUIC"));
+    Ok(())
+}
+
+#[test]
+fn malformed_report_without_malformed_messages() -> Result<()> {
+    let file = read_test_file()?;
+
+    let report = MalformedReport::from_file(&file, Duration::from_secs(60));
+    assert_eq!(1, report.blocks.len());
+    assert_eq!(0, report.recorded());
+    assert_eq!(0, report.reported());
+    assert!(report.over_time.is_empty());
+    assert!(report.top_clients(10).is_empty());
+    Ok(())
+}
+
+#[test]
+fn malformed_report_with_malformed_messages() -> Result<()> {
+    use c_dns::serialization::{
+        ExtraValues, MalformedMessage, MalformedMessageData, TransportFlags, UTicks,
+    };
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let mut file = read_test_file()?;
+    let ticks_per_second = u32::from(
+        file.file_preamble.block_parameters[0]
+            .storage_parameters
+            .ticks_per_second,
+    );
+    let block = &mut file.file_blocks[0];
+    let block_tables = block.block_tables.as_mut().unwrap();
+    let ip_address = block_tables.ip_address.get_or_insert_with(Vec::new);
+    let ipv4_client = ip_address.len();
+    ip_address.push(Ipv4Addr::new(192, 0, 2, 1).into());
+    let ipv6_client = ip_address.len();
+    ip_address.push("2001:db8::1".parse::<Ipv6Addr>()?.into());
+    let data = |ip_version, transport| MalformedMessageData {
+        server_address_index: None,
+        server_port: None,
+        mm_transport_flags: Some(TransportFlags::new(ip_version, transport, false)),
+        mm_payload: Some(bytes::Bytes::from_static(b"\x00")),
+        extra_values: ExtraValues::new(),
+    };
+    block_tables.malformed_message_data = Some(vec![
+        data(IpVersion::Ipv4, Transport::Udp),
+        data(IpVersion::Ipv6, Transport::Tcp),
+    ]);
+    let message = |seconds: u32, client_address_index, message_data_index| MalformedMessage {
+        time_offset: Some(UTicks::from(seconds * ticks_per_second)),
+        client_address_index: Some(client_address_index),
+        client_port: Some(53000),
+        message_data_index: Some(message_data_index),
+        extra_values: ExtraValues::new(),
+    };
+    block.malformed_messages = Some(vec![
+        message(0, ipv4_client, 0),
+        message(1, ipv4_client, 0),
+        message(120, ipv6_client, 1),
+    ]);
+    block.block_statistics.as_mut().unwrap().malformed_items = Some(5);
+
+    let report = MalformedReport::from_file(&file, Duration::from_secs(60));
+    assert_eq!(3, report.recorded());
+    assert_eq!(5, report.reported());
+    assert_eq!(1, report.blocks.len());
+    assert_eq!(3, report.blocks[0].recorded);
+    assert_eq!(Some(5), report.blocks[0].reported);
+    assert_eq!(2, report.blocks[0].unrecorded());
+
+    // The first two messages are within one minute, the third is two minutes later
+    assert_eq!(3, report.over_time.values().sum::<usize>());
+    assert!(report.over_time.len() >= 2);
+    assert_eq!(Some(&1), report.over_time.values().last());
+
+    assert_eq!(2, report.by_transport[&(IpVersion::Ipv4, Transport::Udp)]);
+    assert_eq!(1, report.by_transport[&(IpVersion::Ipv6, Transport::Tcp)]);
+    assert_eq!(
+        vec![
+            (std::net::IpAddr::from(Ipv4Addr::new(192, 0, 2, 1)), 2),
+            ("2001:db8::1".parse()?, 1),
+        ],
+        report.top_clients(10)
+    );
+    Ok(())
+}
+
+#[test]
+fn amplification_report() -> Result<()> {
+    let file = read_test_file()?;
+
+    let report = AmplificationReport::from_file(&file);
+    assert_eq!(12, report.total.count);
+    assert!(report.total.factor() > 1.);
+    // The first query for "." with type NS is 64 bytes and the response 533 bytes
+    assert!(report.by_qtype[&2].max_factor > 8.);
+    let names = report.high_amplification_names(8., 1);
+    assert_eq!(".", names[0].0);
+    Ok(())
+}
+
+#[test]
+fn bailiwick_report() -> Result<()> {
+    let file = read_test_file()?;
+
+    let report = BailiwickReport::from_file(&file);
+    assert!(report.zones.is_empty());
+    assert_eq!(12, report.without_bailiwick.responses);
+    assert_eq!(12, report.without_bailiwick.noerror);
+    assert_eq!(0., report.without_bailiwick.servfail_rate());
+    assert!(report.failing_zones(0., 0).is_empty());
+    Ok(())
+}
+
+#[test]
+fn repeated_queries() -> Result<()> {
+    let file = read_test_file()?;
+
+    // The recursive resolver in the capture asks several servers for the same names
+    let report = RepeatedQueryDetector::from_file(&file, Duration::from_secs(1), 2)?;
+    let client: std::net::IpAddr = "192.168.0.18".parse()?;
+    let offenders = report.worst_offenders();
+    assert!(offenders.iter().any(|(ip, _)| *ip == client));
+    assert!(offenders
+        .iter()
+        .flat_map(|(_, queries)| queries.iter())
+        .all(|query| query.max_in_window >= 2));
+
+    let report = RepeatedQueryDetector::from_file(&file, Duration::from_secs(1), 100)?;
+    assert!(report.offenders.is_empty());
+
+    assert!(RepeatedQueryDetector::from_file(&file, Duration::ZERO, 2).is_err());
+    assert!(RepeatedQueryDetector::new(Duration::ZERO, 2).is_err());
+    Ok(())
+}
+
+#[test]
+fn domain_hierarchy() {
+    let hierarchy = DomainHierarchy::new();
+    assert_eq!(Some("com."), hierarchy.tld("www.google.com."));
+    assert_eq!(
+        Some("google.com."),
+        hierarchy.registrable_domain("www.google.com.")
+    );
+    assert_eq!(
+        Some("co.uk."),
+        hierarchy.registrable_domain("www.bbc.co.uk.")
+    );
+    assert_eq!(None, hierarchy.registrable_domain("com."));
+    assert_eq!(None, hierarchy.tld("."));
+}
+
+#[cfg(feature = "publicsuffix")]
+#[test]
+fn domain_hierarchy_public_suffix_list() -> Result<()> {
+    let list = "// ===BEGIN ICANN DOMAINS===\ncom\nuk\nco.uk\n"
+        .parse()
+        .expect("List must be a valid public suffix list");
+    let hierarchy = DomainHierarchy::with_public_suffix_list(list);
+    assert_eq!(Some("co.uk."), hierarchy.tld("www.bbc.co.uk."));
+    assert_eq!(
+        Some("bbc.co.uk."),
+        hierarchy.registrable_domain("www.bbc.co.uk.")
+    );
+    assert_eq!(
+        Some("google.com."),
+        hierarchy.registrable_domain("www.google.com.")
+    );
+    Ok(())
+}
+
+#[test]
+fn domain_aggregation() -> Result<()> {
+    let file = read_test_file()?;
+
+    let aggregation = DomainAggregation::from_file(&file, &DomainHierarchy::new());
+    let total: usize = aggregation
+        .by_tld
+        .values()
+        .map(|counts| counts.query_responses)
+        .sum();
+    // The queries for the root name have no TLD
+    assert_eq!(0, aggregation.unknown_name);
+    assert_eq!(9, total);
+    assert_eq!("com.", aggregation.top_tlds(1)[0].0);
+    assert!(aggregation.by_registrable_domain.contains_key("isc.org."));
+    Ok(())
+}
+
+#[test]
+fn client_fingerprints() -> Result<()> {
+    let file = read_test_file()?;
+
+    let report = FingerprintReport::from_file(&file);
+    let client = report
+        .clients
+        .get(&"192.168.0.18".parse()?)
+        .expect("Client is part of the capture");
+    assert!(client.queries > 0);
+    assert_eq!(
+        vec![(IpVersion::Ipv4, Transport::Udp)],
+        client.transports.keys().copied().collect::<Vec<_>>()
+    );
+
+    let vector = client.feature_vector();
+    assert_eq!(ClientFingerprint::feature_names().len(), vector.len());
+    assert!(vector.iter().all(|value| (0. ..=1.).contains(value)));
+
+    let populations = report.populations();
+    let clients: usize = populations.values().map(Vec::len).sum();
+    assert_eq!(report.clients.len(), clients);
+    assert!(populations
+        .keys()
+        .any(|signature| signature.contains("IPv4/UDP")));
+    Ok(())
+}
+
+#[test]
+fn k_anonymous_aggregates() -> Result<()> {
+    let file = read_test_file()?;
+    let aggregation = KAnonymityAggregation::from_file(&file);
+    let hierarchy = DomainHierarchy::new();
+    let total = file.query_response_count();
+
+    // Every client is anonymous enough on its own
+    let report = aggregation.finish(&KAnonymityConfig::new(1), &hierarchy);
+    assert!(report
+        .aggregates
+        .iter()
+        .all(|aggregate| aggregate.level == 0));
+    assert_eq!(0, report.suppressed_query_responses);
+    let emitted: usize = report
+        .aggregates
+        .iter()
+        .map(|aggregate| aggregate.query_responses)
+        .sum();
+    assert_eq!(total, emitted + report.unattributed_query_responses);
+
+    // Generalized aggregates always have at least k clients
+    let report = aggregation.finish(&KAnonymityConfig::new(2), &hierarchy);
+    assert!(report
+        .aggregates
+        .iter()
+        .all(|aggregate| aggregate.clients >= 2));
+    let emitted: usize = report
+        .aggregates
+        .iter()
+        .map(|aggregate| aggregate.query_responses)
+        .sum();
+    assert_eq!(
+        total,
+        emitted + report.suppressed_query_responses + report.unattributed_query_responses
+    );
+
+    // There are not enough clients for k = 1000
+    let report = aggregation.finish(&KAnonymityConfig::new(1000), &hierarchy);
+    assert!(report.aggregates.is_empty());
+    assert_eq!(
+        total,
+        report.suppressed_query_responses + report.unattributed_query_responses
+    );
+    Ok(())
+}
+
+#[cfg(feature = "maxminddb")]
+#[test]
+fn geoip_without_databases() {
+    use c_dns::analysis::geoip::{GeoInfo, GeoIpDatabase};
+
+    let geoip = GeoIpDatabase::new();
+    let addr = "192.0.2.1".parse().unwrap();
+    assert_eq!(GeoInfo::default(), geoip.lookup(addr));
+    let enriched = geoip.enrich_all(vec![(addr, 3)]);
+    assert_eq!((addr, 3), enriched[0].value);
+    assert_eq!(None, enriched[0].geo.country);
+
+    assert!(GeoIpDatabase::new()
+        .with_country_database("./tests/data/missing.mmdb")
+        .is_err());
+}
+
+#[cfg(feature = "reverse-dns")]
+#[test]
+fn reverse_dns_cache() {
+    use c_dns::analysis::reverse_dns::{reverse_name, PtrResolver, ReverseDns};
+    use std::net::IpAddr;
+
+    struct Counting(usize);
+
+    impl PtrResolver for Counting {
+        fn resolve_ptr(&mut self, addr: IpAddr) -> c_dns::Result<Option<String>> {
+            self.0 += 1;
+            Ok(match addr {
+                IpAddr::V4(_) => Some(format!("host{}.example.", self.0)),
+                IpAddr::V6(_) => None,
+            })
+        }
+    }
+
+    assert_eq!(
+        "1.2.0.192.in-addr.arpa.",
+        reverse_name("192.0.2.1".parse().unwrap())
+    );
+    assert_eq!(
+        "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa.",
+        reverse_name("2001:db8::1".parse().unwrap())
+    );
+
+    let mut reverse_dns = ReverseDns::new(Counting(0), Duration::from_millis(20));
+    let v4: IpAddr = "192.0.2.1".parse().unwrap();
+    let v6: IpAddr = "2001:db8::1".parse().unwrap();
+    let start = std::time::Instant::now();
+    let enriched = reverse_dns.enrich_all(vec![(v4, 1), (v6, 2), (v4, 3)]);
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    assert_eq!(
+        vec![
+            (v4, 1, Some("host1.example.".to_string())),
+            (v6, 2, None),
+            (v4, 3, Some("host1.example.".to_string())),
+        ],
+        enriched
+    );
+    assert_eq!(2, reverse_dns.cached());
+}
+
+/// Failed lookups are retried, while definitive answers are cached.
+#[cfg(feature = "reverse-dns")]
+#[test]
+fn reverse_dns_retry_failures() {
+    use c_dns::analysis::reverse_dns::{PtrResolver, ReverseDns};
+    use std::net::IpAddr;
+
+    /// Time out on the first lookup of every address, answer NXDOMAIN afterwards
+    struct Flaky(Vec<IpAddr>);
+
+    impl PtrResolver for Flaky {
+        fn resolve_ptr(&mut self, addr: IpAddr) -> c_dns::Result<Option<String>> {
+            if self.0.contains(&addr) {
+                Ok(None)
+            } else {
+                self.0.push(addr);
+                Err(c_dns::Error::Io(std::io::ErrorKind::TimedOut.into()))
+            }
+        }
+    }
+
+    let addr: IpAddr = "192.0.2.1".parse().unwrap();
+    let mut reverse_dns = ReverseDns::new(Flaky(Vec::new()), Duration::ZERO)
+        .with_retry_interval(Duration::from_secs(3600));
+    assert_eq!(None, reverse_dns.lookup(addr));
+    assert_eq!(None, reverse_dns.lookup(addr));
+    assert_eq!(0, reverse_dns.cached());
+
+    let mut reverse_dns =
+        ReverseDns::new(Flaky(Vec::new()), Duration::ZERO).with_retry_interval(Duration::ZERO);
+    assert_eq!(None, reverse_dns.lookup(addr));
+    assert_eq!(0, reverse_dns.cached());
+    assert_eq!(None, reverse_dns.lookup(addr));
+    assert_eq!(1, reverse_dns.cached());
+}
+
+#[cfg(feature = "reverse-dns")]
+#[test]
+fn reverse_dns_udp() -> Result<()> {
+    use c_dns::analysis::reverse_dns::{PtrResolver, UdpPtrResolver};
+    use std::net::UdpSocket;
+
+    let server = UdpSocket::bind("127.0.0.1:0")?;
+    let server_addr = server.local_addr()?;
+    let handle = std::thread::spawn(move || -> std::io::Result<()> {
+        let mut buffer = [0; 512];
+        let (len, client) = server.recv_from(&mut buffer)?;
+        let response = |query: &[u8], hostname: &[u8]| {
+            let mut response = query.to_vec();
+            // QR, RD, RA, one Answer
+            response[2] = 0x81;
+            response[3] = 0x80;
+            response[7] = 1;
+            // Compressed owner name, PTR, IN, TTL, RDLENGTH, RDATA
+            response.extend_from_slice(b"\xc0\x0c\x00\x0c\x00\x01\x00\x00\x0e\x10\x00");
+            response.push(hostname.len() as u8);
+            response.extend_from_slice(hostname);
+            response
+        };
+        let query = &buffer[..len];
+
+        // A different transaction ID
+        let mut spoofed = response(query, b"\x07spoofed\x00");
+        spoofed[1] ^= 0x01;
+        server.send_to(&spoofed, client)?;
+        // A different Question
+        let mut spoofed = response(query, b"\x07spoofed\x00");
+        spoofed[13] = b'9';
+        server.send_to(&spoofed, client)?;
+        // A Query instead of a Response
+        let mut spoofed = response(query, b"\x07spoofed\x00");
+        spoofed[2] = 0x01;
+        server.send_to(&spoofed, client)?;
+        // The case of the name may differ
+        let mut valid = response(query, b"\x03dns\x07example\x00");
+        valid[24] = b'I';
+        server.send_to(&valid, client)?;
+        Ok(())
+    });
+
+    let mut resolver = UdpPtrResolver::new(server_addr).with_timeout(Duration::from_secs(5));
+    let hostname = resolver.resolve_ptr("192.0.2.53".parse().unwrap())?;
+    handle.join().unwrap()?;
+    assert_eq!(Some("dns.example.".to_string()), hostname);
+    Ok(())
+}
+
+#[test]
+fn aggregate_statistics() -> Result<()> {
+    let file = read_test_file()?;
+
+    let statistics = Statistics::from_file(&file);
+    assert_eq!(12, statistics.query_responses);
+    assert_eq!(12, statistics.queries);
+    assert_eq!(12, statistics.responses);
+    assert_eq!(12, statistics.rcodes.values().sum::<usize>());
+    assert_eq!(12, statistics.qtypes.values().sum::<usize>());
+    assert_eq!(12, statistics.transports.total().query_responses);
+    assert_eq!(12, statistics.query_sizes.count());
+    assert!(statistics.first_time <= statistics.last_time);
+    assert!(statistics.queries_per_second().unwrap() > 0.);
+
+    let top_qnames = statistics.top_qnames(3);
+    assert!(!top_qnames.is_empty() && top_qnames.len() <= 3);
+    assert!(top_qnames.windows(2).all(|w| w[0].1 >= w[1].1));
+    assert_eq!(12, statistics.qnames.values().sum::<usize>());
+    assert_eq!(
+        12,
+        statistics
+            .top_clients(usize::MAX)
+            .iter()
+            .map(|c| c.1)
+            .sum::<usize>()
+    );
+    assert!(statistics.to_string().starts_with("12 Q/R items"));
+    Ok(())
+}
@@ -0,0 +1,343 @@
+use c_dns::anonymize::{
+    AddressAnonymizer, NameRedactor, PrefixTruncation, SuffixAllowlist, SuffixTruncation,
+};
+use c_dns::name::DomainName;
+use c_dns::resolved::ResolvedQueryResponse;
+use c_dns::serialization::{File, StorageFlags};
+use color_eyre::eyre::Result;
+use std::net::IpAddr;
+
+fn read_test_file() -> Result<File> {
+    Ok(File::read_path("./tests/data/dns.cdns")?)
+}
+
+/// Client and server address of all Q/R items.
+fn addresses(file: &File) -> Result<Vec<(Option<IpAddr>, Option<IpAddr>)>> {
+    let mut res = Vec::new();
+    for (block, block_parameters) in file.iter_blocks() {
+        let block_tables = block.block_tables.as_ref().unwrap();
+        for qr in block.query_responses.iter().flatten() {
+            let qr = ResolvedQueryResponse::new(
+                qr,
+                block.block_preamble.earliest_time,
+                block_parameters,
+                block_tables,
+            )?;
+            res.push((qr.client_address, qr.server_address));
+        }
+    }
+    Ok(res)
+}
+
+/// QNAMEs of all Q/R items in presentation format.
+fn qnames(file: &File) -> Vec<Option<String>> {
+    file.iter_all_query_responses()
+        .map(|(qr, _, _, block_tables)| {
+            let name = block_tables.name(qr.query_name_index?).ok()?;
+            Some(name.to_domain_name().ok()?.to_string())
+        })
+        .collect()
+}
+
+fn name(name: &str) -> DomainName {
+    DomainName::from_labels(name.split('.').filter(|label| !label.is_empty())).unwrap()
+}
+
+#[test]
+fn prefix_truncation() -> Result<()> {
+    let anonymizer = PrefixTruncation::new(24, 48);
+    assert_eq!(
+        "192.0.2.0".parse::<IpAddr>()?,
+        anonymizer.anonymize("192.0.2.123".parse()?)
+    );
+    assert_eq!(
+        "2001:db8:1::".parse::<IpAddr>()?,
+        anonymizer.anonymize("2001:db8:1:2::53".parse()?)
+    );
+    assert_eq!(
+        "10.0.0.0".parse::<IpAddr>()?,
+        PrefixTruncation::new(12, 0).anonymize("10.15.1.1".parse()?)
+    );
+
+    let original = read_test_file()?;
+    let mut file = read_test_file()?;
+    file.anonymize_addresses(&anonymizer);
+    let expected: Vec<_> = addresses(&original)?
+        .into_iter()
+        .map(|(client, server)| {
+            (
+                client.map(|addr| anonymizer.anonymize(addr)),
+                server.map(|addr| anonymizer.anonymize(addr)),
+            )
+        })
+        .collect();
+    assert_eq!(expected, addresses(&file)?);
+    assert!(file.dangling_indices().is_empty());
+
+    let storage_parameters = &file.file_preamble.block_parameters[0].storage_parameters;
+    assert!(storage_parameters
+        .storage_flags
+        .unwrap()
+        .contains(StorageFlags::AnonymizedData));
+    assert_eq!(
+        Some(anonymizer.method()),
+        storage_parameters.anonymization_method
+    );
+
+    // Anonymizing twice records both methods
+    file.anonymize_addresses(&PrefixTruncation::new(16, 32));
+    let storage_parameters = &file.file_preamble.block_parameters[0].storage_parameters;
+    assert_eq!(
+        Some(
+            "prefix truncation to /24 (IPv4) and /48 (IPv6), prefix truncation to /16 (IPv4) and /32 (IPv6)"
+        ),
+        storage_parameters.anonymization_method.as_deref()
+    );
+    Ok(())
+}
+
+#[test]
+fn anonymize_client_subnet() -> Result<()> {
+    use c_dns::edns::{self, ClientSubnet, OptionData};
+
+    let options = vec![
+        OptionData::ClientSubnet(ClientSubnet::new("192.0.2.123".parse()?, 24)),
+        OptionData::Cookie {
+            client: [1; 8],
+            server: None,
+        },
+    ];
+    let mut file = read_test_file()?;
+    let tables = file.file_blocks[0].block_tables.as_mut().unwrap();
+    let name_rdata = tables.name_rdata.get_or_insert_with(Vec::new);
+    name_rdata.push(edns::options_to_rdata(&options)?);
+    // A truncated Client Subnet option
+    name_rdata.push(bytes::Bytes::from_static(b"\x00\x08\x00\x02\x00\x01").into());
+    let len = name_rdata.len();
+    let qr_sig = tables.qr_sig.as_mut().unwrap();
+    assert!(qr_sig.len() >= 2);
+    qr_sig[0].query_opt_rdata_index = Some(len - 2);
+    qr_sig[1].query_opt_rdata_index = Some(len - 1);
+
+    file.anonymize_addresses(&PrefixTruncation::new(16, 48));
+    let tables = file.file_blocks[0].block_tables.as_ref().unwrap();
+    let qr_sig = tables.qr_sig.as_ref().unwrap();
+    let rdata = tables.name(qr_sig[0].query_opt_rdata_index.unwrap())?;
+    assert_eq!(
+        vec![
+            OptionData::ClientSubnet(ClientSubnet::new("192.0.0.0".parse()?, 24)),
+            options[1].clone(),
+        ],
+        edns::parse_options(rdata)?
+    );
+    let rdata = tables.name(qr_sig[1].query_opt_rdata_index.unwrap())?;
+    assert!(rdata.as_bytes().is_empty());
+    assert!(file.dangling_indices().is_empty());
+    Ok(())
+}
+
+#[cfg(feature = "ring")]
+#[test]
+fn crypto_pan() -> Result<()> {
+    use c_dns::anonymize::CryptoPan;
+
+    let anonymizer = CryptoPan::new(b"0123456789abcdef0123456789abcdef");
+    let common_prefix = |a: IpAddr, b: IpAddr| -> u32 {
+        match (a, b) {
+            (IpAddr::V4(a), IpAddr::V4(b)) => (u32::from(a) ^ u32::from(b)).leading_zeros(),
+            (IpAddr::V6(a), IpAddr::V6(b)) => (u128::from(a) ^ u128::from(b)).leading_zeros(),
+            _ => panic!("IP versions differ"),
+        }
+    };
+    for (a, b) in [
+        ("192.0.2.1", "192.0.2.2"),
+        ("192.0.2.1", "192.0.3.1"),
+        ("192.0.2.1", "10.0.0.1"),
+        ("2001:db8::1", "2001:db8:0:1::1"),
+    ] {
+        let (a, b) = (a.parse()?, b.parse()?);
+        let (anonymized_a, anonymized_b) = (anonymizer.anonymize(a), anonymizer.anonymize(b));
+        assert_ne!(a, anonymized_a);
+        assert_eq!(
+            common_prefix(a, b),
+            common_prefix(anonymized_a, anonymized_b)
+        );
+    }
+    // The pseudonyms are deterministic and depend on the key
+    let addr = "192.0.2.1".parse()?;
+    assert_eq!(anonymizer.anonymize(addr), anonymizer.anonymize(addr));
+    assert_ne!(
+        anonymizer.anonymize(addr),
+        CryptoPan::new(b"another key").anonymize(addr)
+    );
+    Ok(())
+}
+
+#[cfg(feature = "ring")]
+#[test]
+fn hmac_hash() -> Result<()> {
+    use c_dns::anonymize::HmacHash;
+
+    let anonymizer = HmacHash::new(b"0123456789abcdef0123456789abcdef");
+    let mut file = read_test_file()?;
+    file.anonymize_addresses(&anonymizer);
+    let expected: Vec<_> = addresses(&read_test_file()?)?
+        .into_iter()
+        .map(|(client, server)| {
+            (
+                client.map(|addr| anonymizer.anonymize(addr)),
+                server.map(|addr| anonymizer.anonymize(addr)),
+            )
+        })
+        .collect();
+    assert_eq!(expected, addresses(&file)?);
+    assert!(matches!(
+        anonymizer.anonymize("2001:db8::1".parse()?),
+        IpAddr::V6(_)
+    ));
+    Ok(())
+}
+
+#[test]
+fn suffix_truncation() -> Result<()> {
+    let redactor = SuffixTruncation::new(2);
+    assert_eq!(
+        name("example.com."),
+        redactor.redact(&name("www.example.com."))
+    );
+    assert_eq!(name("com."), redactor.redact(&name("com.")));
+    assert_eq!(DomainName::root(), redactor.redact(&DomainName::root()));
+
+    let original = read_test_file()?;
+    let mut file = read_test_file()?;
+    file.redact_names(&redactor);
+    let expected: Vec<_> = qnames(&original)
+        .into_iter()
+        .map(|qname| Some(redactor.redact(&name(&qname?)).to_string()))
+        .collect();
+    assert_eq!(expected, qnames(&file));
+    assert!(file.dangling_indices().is_empty());
+    // Names with equal redacted forms share an entry
+    let name_rdata = |file: &File| {
+        file.file_blocks[0]
+            .block_tables
+            .as_ref()
+            .unwrap()
+            .name_rdata
+            .as_ref()
+            .map_or(0, Vec::len)
+    };
+    assert!(name_rdata(&file) <= name_rdata(&original));
+    assert_eq!(
+        Some("name truncation to 2 labels"),
+        file.file_preamble.block_parameters[0]
+            .storage_parameters
+            .anonymization_method
+            .as_deref()
+    );
+    Ok(())
+}
+
+#[test]
+fn redact_rdata_names() -> Result<()> {
+    use c_dns::serialization::{ClassType, DnsClass, DnsType, NameOrRdata, RR};
+
+    let rdata = |bytes: &'static [u8]| NameOrRdata::from(bytes::Bytes::from_static(bytes));
+    let records = [
+        (DnsType::CNAME, rdata(b"\x03www\x07example\x03com\x00")),
+        (
+            DnsType::MX,
+            rdata(b"\x00\x0a\x04mail\x07example\x03com\x00"),
+        ),
+        (DnsType::TXT, rdata(b"\x0fwww.example.com")),
+        // Truncated name
+        (DnsType::PTR, rdata(b"\x03www")),
+    ];
+    let mut file = read_test_file()?;
+    let tables = file.file_blocks[0].block_tables.as_mut().unwrap();
+    let first_rr = tables.rr.get_or_insert_with(Vec::new).len();
+    for (type_, rdata) in records {
+        let classtype = tables.classtype.get_or_insert_with(Vec::new);
+        classtype.push(ClassType {
+            type_,
+            class: DnsClass::IN,
+        });
+        let classtype_index = classtype.len() - 1;
+        let name_rdata = tables.name_rdata.get_or_insert_with(Vec::new);
+        name_rdata.push(rdata);
+        let rdata_index = name_rdata.len() - 1;
+        tables.rr.as_mut().unwrap().push(RR {
+            name_index: 0,
+            classtype_index,
+            ttl: Some(3600),
+            rdata_index: Some(rdata_index),
+            extra_values: Default::default(),
+        });
+    }
+
+    file.redact_names(&SuffixTruncation::new(2));
+    assert!(file.dangling_indices().is_empty());
+    let tables = file.file_blocks[0].block_tables.as_ref().unwrap();
+    let redacted: Vec<&[u8]> = tables.rr.as_ref().unwrap()[first_rr..]
+        .iter()
+        .map(|rr| tables.name(rr.rdata_index.unwrap()).unwrap().as_bytes())
+        .collect();
+    assert_eq!(
+        vec![
+            &b"\x07example\x03com\x00"[..],
+            &b"\x00\x0a\x07example\x03com\x00"[..],
+            &b"\x0fwww.example.com"[..],
+            &b""[..],
+        ],
+        redacted
+    );
+    Ok(())
+}
+
+#[test]
+fn suffix_allowlist() {
+    let redactor = SuffixAllowlist::new(["com", "uk", "co.uk."]);
+    assert_eq!(
+        name("redacted.CO.uk."),
+        redactor.redact(&name("www.Example.CO.uk."))
+    );
+    assert_eq!(
+        name("redacted.com."),
+        redactor.redact(&name("example.com."))
+    );
+    assert_eq!(name("co.uk."), redactor.redact(&name("co.uk.")));
+    assert_eq!(name("redacted."), redactor.redact(&name("example.org.")));
+    assert_eq!(DomainName::root(), redactor.redact(&DomainName::root()));
+    assert_eq!(
+        name("com."),
+        redactor
+            .with_placeholder("")
+            .redact(&name("www.example.com."))
+    );
+}
+
+#[cfg(feature = "ring")]
+#[test]
+fn label_hashing() -> Result<()> {
+    use c_dns::anonymize::LabelHashing;
+
+    let redactor = LabelHashing::new(b"0123456789abcdef0123456789abcdef").keep_labels(1);
+    let www = redactor.redact(&name("www.example.com."));
+    let mail = redactor.redact(&name("mail.EXAMPLE.com."));
+    let www_labels: Vec<_> = www.labels().collect();
+    let mail_labels: Vec<_> = mail.labels().collect();
+    assert_eq!(3, www_labels.len());
+    assert_eq!(LabelHashing::HASH_LEN, www_labels[0].len());
+    assert_ne!(www_labels[0], mail_labels[0]);
+    assert_eq!(www_labels[1..], mail_labels[1..]);
+    assert_eq!(b"com", www_labels[2]);
+
+    // Hashed labels are longer, so long names lose their leftmost labels
+    let long = name(&"a.".repeat(100));
+    assert!(redactor.redact(&long).as_wire().len() <= 255);
+
+    let mut file = read_test_file()?;
+    file.redact_names(&redactor);
+    assert!(file.dangling_indices().is_empty());
+    Ok(())
+}
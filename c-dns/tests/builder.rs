@@ -0,0 +1,255 @@
+use c_dns::builder::{
+    BlockBuilder, CaptureConfig, CollectionParametersBuilder, QueryResponseRecord, RRRecord,
+    SectionsRecord, SignatureRecord, StorageParametersBuilder,
+};
+use c_dns::resolved::ResolvedQueryResponse;
+use c_dns::serialization::{
+    Block, ClassType, CollectionParameters, NameOrRdata, Opcode, QueryResponseFlags,
+    QueryResponseSignature, StorageFlags, StorageParameters, Timestamp,
+};
+use color_eyre::eyre::Result;
+
+struct Capture;
+
+impl CaptureConfig for Capture {
+    fn interfaces(&self) -> Vec<String> {
+        vec!["eth0".to_string(), "eth1".to_string()]
+    }
+
+    fn filter(&self) -> Option<String> {
+        Some("port 53".to_string())
+    }
+
+    fn snaplen(&self) -> Option<u32> {
+        Some(65535)
+    }
+}
+
+#[test]
+fn collection_parameters() -> Result<()> {
+    let params = CollectionParametersBuilder::new()
+        .query_timeout(5000)
+        .skew_timeout(10_000)
+        .interface("lo")
+        .vlan_id(1)
+        .vlan_id(4094)
+        .generator_id("collector 1.0")
+        .capture_config(&Capture)
+        .build()?;
+    assert_eq!(
+        Some(vec![
+            "lo".to_string(),
+            "eth0".to_string(),
+            "eth1".to_string()
+        ]),
+        params.interfaces
+    );
+    assert_eq!(Some("port 53"), params.filter.as_deref());
+    assert_eq!(Some(65535), params.snaplen);
+    assert_eq!(Some("collector 1.0"), params.generator_id.as_deref());
+    assert_eq!(Some(vec![1, 4094]), params.vlan_ids);
+
+    let reserialized: CollectionParameters = serde_cbor::from_slice(&serde_cbor::to_vec(&params)?)?;
+    assert_eq!(params.vlan_ids, reserialized.vlan_ids);
+    Ok(())
+}
+
+#[test]
+fn invalid_collection_parameters() {
+    assert!(CollectionParametersBuilder::new()
+        .vlan_id(0)
+        .build()
+        .is_err());
+    assert!(CollectionParametersBuilder::new()
+        .vlan_id(4095)
+        .build()
+        .is_err());
+    assert!(CollectionParametersBuilder::new()
+        .query_timeout(0)
+        .build()
+        .is_err());
+    // A skew of 2 seconds with a query timeout of 1 second
+    assert!(CollectionParametersBuilder::new()
+        .query_timeout(1000)
+        .skew_timeout(2_000_000)
+        .build()
+        .is_err());
+}
+
+#[test]
+fn storage_parameters() -> Result<()> {
+    let params = StorageParametersBuilder::new(1_000_000, 5000)
+        .opcode(Opcode::QUERY)
+        .client_address_prefix_ipv4(24)
+        .client_address_prefix_ipv6(48)
+        .sampling_method("1 in 10")
+        .build()?;
+    assert_eq!(vec![Opcode::QUERY], params.opcodes);
+    assert_eq!(Some(StorageFlags::SampledData.into()), params.storage_flags);
+
+    let reserialized: StorageParameters = serde_cbor::from_slice(&serde_cbor::to_vec(&params)?)?;
+    assert_eq!(Some(24), reserialized.client_address_prefix_ipv4);
+    Ok(())
+}
+
+#[test]
+fn invalid_storage_parameters() {
+    assert!(StorageParametersBuilder::new(0, 5000).build().is_err());
+    assert!(StorageParametersBuilder::new(1_000_000, 5000)
+        .client_address_prefix_ipv4(33)
+        .build()
+        .is_err());
+    assert!(StorageParametersBuilder::new(1_000_000, 5000)
+        .server_address_prefix_ipv6(0)
+        .build()
+        .is_err());
+}
+
+fn name(name: &'static [u8]) -> NameOrRdata {
+    NameOrRdata::from(bytes::Bytes::from_static(name))
+}
+
+fn record(secs: i32, ticks: u32, client: &str) -> QueryResponseRecord {
+    let classtype = ClassType {
+        type_: 1.into(),
+        class: 1.into(),
+    };
+    QueryResponseRecord {
+        time: Some(Timestamp {
+            timestamp_secs: secs,
+            timestamp_ticks: ticks.into(),
+        }),
+        client_address: Some(client.parse().unwrap()),
+        query_name: Some(name(b"\x07example\x00")),
+        signature: Some(SignatureRecord {
+            server_address: Some("2001:db8::53".parse().unwrap()),
+            query_classtype: Some(classtype),
+            signature: QueryResponseSignature {
+                qr_sig_flags: Some(QueryResponseFlags::HasQuery.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        response_extended: Some(SectionsRecord {
+            answers: vec![RRRecord {
+                name: name(b"\x07example\x00"),
+                classtype,
+                ttl: Some(300),
+                rdata: Some(name(b"\xc0\x00\x02\x01")),
+            }],
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn block_builder_deduplicates() -> Result<()> {
+    let storage_parameters = StorageParametersBuilder::new(1_000_000, 5000)
+        .client_address_prefix_ipv4(24)
+        .build()?;
+    let mut builder = BlockBuilder::new(&storage_parameters);
+    builder.push(record(10, 500, "192.0.2.1"));
+    builder.push(record(10, 100, "192.0.2.2"));
+    builder.push(record(11, 0, "198.51.100.1"));
+    assert_eq!(3, builder.len());
+    let block = builder.build()?;
+
+    // The block survives a round-trip
+    let block: Block = serde_cbor::from_slice(&serde_cbor::to_vec(&block)?)?;
+    let tables = block.block_tables.as_ref().unwrap();
+    // Two client networks and one server
+    assert_eq!(3, tables.ip_address.as_ref().unwrap().len());
+    // The name is shared between Questions and RRs
+    assert_eq!(2, tables.name_rdata.as_ref().unwrap().len());
+    assert_eq!(1, tables.classtype.as_ref().unwrap().len());
+    assert_eq!(1, tables.qr_sig.as_ref().unwrap().len());
+    assert_eq!(1, tables.rr.as_ref().unwrap().len());
+    assert_eq!(1, tables.rrlist.as_ref().unwrap().len());
+    assert!(tables.qlist.is_none());
+
+    let earliest_time = block.block_preamble.earliest_time;
+    assert_eq!(
+        Some(Timestamp {
+            timestamp_secs: 10,
+            timestamp_ticks: 100.into(),
+        }),
+        earliest_time
+    );
+    let statistics = block.block_statistics.as_ref().unwrap();
+    assert_eq!(Some(3), statistics.unmatched_queries);
+
+    let block_parameters = c_dns::serialization::BlockParameters {
+        storage_parameters,
+        collection_parameters: None,
+        extra_values: Default::default(),
+    };
+    let resolved = block
+        .query_responses
+        .iter()
+        .flatten()
+        .map(|qr| ResolvedQueryResponse::new(qr, earliest_time, &block_parameters, tables))
+        .collect::<c_dns::Result<Vec<_>>>()?;
+    assert_eq!(
+        vec![400, 0, 999_900],
+        resolved
+            .iter()
+            .map(|qr| u32::from(qr.query_response.time_offset.unwrap()))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        Some("192.0.2.0".parse()?),
+        resolved[1].client_address,
+        "The prefix is applied"
+    );
+    assert_eq!(Some("2001:db8::53".parse()?), resolved[2].server_address);
+    let answers = &resolved[0].response_extended.as_ref().unwrap().answers;
+    assert_eq!(Some(300), answers[0].ttl);
+    Ok(())
+}
+
+#[test]
+fn block_builder_time_span() -> Result<()> {
+    let storage_parameters = StorageParametersBuilder::new(1_000_000_000, 5000).build()?;
+    let mut builder = BlockBuilder::new(&storage_parameters);
+    builder.push(record(0, 0, "192.0.2.1"));
+    builder.push(record(10, 0, "192.0.2.1"));
+    assert!(builder.build().is_err());
+    Ok(())
+}
+
+#[test]
+fn iterate_sections() -> Result<()> {
+    let storage_parameters = StorageParametersBuilder::new(1_000_000, 5000).build()?;
+    let mut builder = BlockBuilder::new(&storage_parameters);
+    builder.push(record(10, 0, "192.0.2.1"));
+    let block = builder.build()?;
+    let tables = block.block_tables.as_ref().unwrap();
+    let extended = block.query_responses.as_ref().unwrap()[0]
+        .response_extended
+        .as_ref()
+        .unwrap();
+
+    let answers = extended
+        .iter_answers(tables)
+        .collect::<c_dns::Result<Vec<_>>>()?;
+    assert_eq!(1, answers.len());
+    assert_eq!(&name(b"\x07example\x00"), answers[0].name);
+    assert_eq!(Some(&name(b"\xc0\x00\x02\x01")), answers[0].rdata);
+    assert_eq!(Some(300), answers[0].ttl);
+    assert_eq!(0, extended.iter_questions(tables).count());
+    assert_eq!(0, extended.iter_authorities(tables).count());
+
+    // A missing list is reported once
+    let empty = Default::default();
+    let mut answers = extended.iter_answers(&empty);
+    assert!(matches!(
+        answers.next(),
+        Some(Err(c_dns::Error::IndexOutOfBounds {
+            table: "rrlist",
+            ..
+        }))
+    ));
+    assert!(answers.next().is_none());
+    Ok(())
+}
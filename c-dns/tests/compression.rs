@@ -0,0 +1,98 @@
+use c_dns::compression::{decompress, CompressedWriter, Compression};
+use c_dns::serialization::{File, FileReader};
+use color_eyre::eyre::Result;
+
+fn read_test_file() -> Result<File> {
+    Ok(File::read_path("./tests/data/dns.cdns")?)
+}
+
+#[test]
+fn detect_compression() {
+    assert_eq!(Compression::Gzip, Compression::detect(b"\x1f\x8b\x08\x00"));
+    assert_eq!(Compression::Xz, Compression::detect(b"\xfd7zXZ\x00\x00"));
+    assert_eq!(
+        Compression::Zstd,
+        Compression::detect(b"\x28\xb5\x2f\xfd\x00")
+    );
+    assert_eq!(Compression::None, Compression::detect(b"\x83\x65C-DNS"));
+    assert_eq!(Compression::None, Compression::detect(b""));
+
+    assert_eq!(Compression::Gzip, Compression::from_path("a.cdns.gz"));
+    assert_eq!(Compression::Zstd, Compression::from_path("a.cdns.zst"));
+    assert_eq!(Compression::None, Compression::from_path("a.cdns"));
+}
+
+/// Write the test file with `compression` and read it back with all entry points.
+fn roundtrip(compression: Compression) -> Result<()> {
+    let file = read_test_file()?;
+    let path = std::env::temp_dir().join(format!(
+        "c-dns-test-compression-{}-{}.cdns",
+        compression,
+        std::process::id()
+    ));
+    file.write_path_compressed(&path, compression)?;
+    let bytes = std::fs::read(&path)?;
+    assert_eq!(compression, Compression::detect(&bytes));
+
+    assert_eq!(file.to_vec()?, File::read_path(&path)?.to_vec()?);
+    let reader = FileReader::open(&path)?;
+    assert_eq!(file.block_count(), reader.count());
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn uncompressed_roundtrip() -> Result<()> {
+    roundtrip(Compression::None)
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn gzip_roundtrip() -> Result<()> {
+    roundtrip(Compression::Gzip)
+}
+
+#[cfg(feature = "xz")]
+#[test]
+fn xz_roundtrip() -> Result<()> {
+    roundtrip(Compression::Xz)
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_roundtrip() -> Result<()> {
+    roundtrip(Compression::Zstd)
+}
+
+/// Concatenated compressed streams decompress into the concatenated data.
+#[cfg(feature = "gzip")]
+#[test]
+fn concatenated_streams() -> Result<()> {
+    use std::io::{Read, Write};
+
+    let mut compressed = Vec::new();
+    for part in [&b"C-"[..], b"DNS"] {
+        let mut writer = CompressedWriter::new(Vec::new(), Compression::Gzip)?;
+        writer.write_all(part)?;
+        compressed.extend(writer.finish()?);
+    }
+    let mut decompressed = Vec::new();
+    decompress(&*compressed)?.read_to_end(&mut decompressed)?;
+    assert_eq!(b"C-DNS".to_vec(), decompressed);
+    Ok(())
+}
+
+#[cfg(not(feature = "zstd"))]
+#[test]
+fn unsupported_compression() {
+    let err = decompress(&b"\x28\xb5\x2f\xfd\x00"[..]).err().unwrap();
+    assert!(matches!(
+        err,
+        c_dns::Error::UnsupportedCompression(Compression::Zstd)
+    ));
+    assert_eq!(
+        "Handling zstd compressed data requires the `zstd` feature",
+        err.to_string()
+    );
+    assert!(CompressedWriter::new(Vec::new(), Compression::Zstd).is_err());
+}
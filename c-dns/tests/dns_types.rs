@@ -0,0 +1,29 @@
+use c_dns::serialization::{DnsClass, DnsType};
+use color_eyre::eyre::Result;
+
+#[test]
+fn display_mnemonics() {
+    assert_eq!("AAAA", DnsType::AAAA.to_string());
+    assert_eq!("TYPE65280", DnsType::from(65280).to_string());
+    assert_eq!(Some("OPT"), DnsType::from(41).name());
+    assert_eq!("IN", DnsClass::IN.to_string());
+    assert_eq!("CLASS42", DnsClass::from(42).to_string());
+    assert_eq!(None, DnsClass::from(42).name());
+    assert_eq!("CH   ", format!("{:5}", DnsClass::CH));
+}
+
+#[test]
+fn parse_mnemonics() -> Result<()> {
+    assert_eq!(DnsType::AAAA, "AAAA".parse()?);
+    assert_eq!(DnsType::NSEC3PARAM, "nsec3param".parse()?);
+    assert_eq!(DnsType::from(65280), "TYPE65280".parse()?);
+    assert_eq!(DnsType::A, "type1".parse()?);
+    assert!("TYPE65536".parse::<DnsType>().is_err());
+    assert!("TYPE+1".parse::<DnsType>().is_err());
+    assert!("FOO".parse::<DnsType>().is_err());
+
+    assert_eq!(DnsClass::CH, "ch".parse()?);
+    assert_eq!(DnsClass::from(42), "CLASS42".parse()?);
+    assert!("TYPE1".parse::<DnsClass>().is_err());
+    Ok(())
+}
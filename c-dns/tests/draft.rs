@@ -0,0 +1,193 @@
+use c_dns::draft::DraftLayout;
+use c_dns::extensions::ExtensionScope;
+use c_dns::serialization::File;
+use color_eyre::eyre::Result;
+use serde_cbor::Value;
+use std::collections::BTreeMap;
+
+fn read_test_file() -> Result<Vec<u8>> {
+    Ok(std::fs::read("./tests/data/dns.cdns")?)
+}
+
+fn map(value: &mut Value) -> &mut BTreeMap<Value, Value> {
+    match value {
+        Value::Map(map) => map,
+        _ => panic!("Expected a map"),
+    }
+}
+
+fn array(value: &mut Value) -> &mut Vec<Value> {
+    match value {
+        Value::Array(array) => array,
+        _ => panic!("Expected an array"),
+    }
+}
+
+/// Mark the test file with the format version `major.minor` and move the `query_name_index` of all Q/R items from key 7 to `key`.
+fn with_version(bytes: &[u8], major: i128, minor: i128, key: i128) -> Result<Vec<u8>> {
+    let mut value: Value = serde_cbor::from_slice(bytes)?;
+    let items = array(&mut value);
+    let preamble = map(&mut items[1]);
+    preamble.insert(Value::Integer(0), Value::Integer(major));
+    preamble.insert(Value::Integer(1), Value::Integer(minor));
+    for block in array(&mut items[2]) {
+        if let Some(query_responses) = map(block).get_mut(&Value::Integer(3)) {
+            for query_response in array(query_responses) {
+                let query_response = map(query_response);
+                if let Some(name) = query_response.remove(&Value::Integer(7)) {
+                    query_response.insert(Value::Integer(key), name);
+                }
+            }
+        }
+    }
+    Ok(serde_cbor::to_vec(&value)?)
+}
+
+#[test]
+fn current_version_is_read_directly() -> Result<()> {
+    let bytes = read_test_file()?;
+    let file = File::from_slice_versioned(&bytes, &[])?;
+    assert_eq!(
+        File::from_slice(&bytes)?.file_blocks.len(),
+        file.file_blocks.len()
+    );
+    Ok(())
+}
+
+#[test]
+fn remap_keys_of_draft() -> Result<()> {
+    let bytes = read_test_file()?;
+    let original = File::from_slice(&bytes)?;
+    let draft = with_version(&bytes, 0, 1, 13)?;
+    assert!(File::from_slice(&draft).is_err());
+
+    let mut layout = DraftLayout::new(0, 1);
+    layout.remap(ExtensionScope::QueryResponse, 13, Some(7));
+    let file = File::from_slice_versioned(&draft, &[DraftLayout::new(0, 2), layout])?;
+    assert_eq!((1, 0), file.file_preamble.format_version());
+    assert_eq!(original.query_response_count(), file.query_response_count());
+    let names = |file: &File| {
+        file.iter_all_query_responses()
+            .map(|(query_response, ..)| query_response.query_name_index)
+            .collect::<Vec<_>>()
+    };
+    assert!(names(&original).iter().all(Option::is_some));
+    assert_eq!(names(&original), names(&file));
+    Ok(())
+}
+
+#[test]
+fn drop_keys_of_draft() -> Result<()> {
+    let bytes = read_test_file()?;
+    let draft = with_version(&bytes, 0, 1, 13)?;
+
+    let mut layout = DraftLayout::new(0, 1);
+    layout.remap(ExtensionScope::QueryResponse, 13, None);
+    let file = File::from_slice_versioned(&draft, &[layout])?;
+    assert!(file
+        .iter_all_query_responses()
+        .all(
+            |(query_response, ..)| query_response.query_name_index.is_none()
+                && query_response.extra_values.is_empty()
+        ));
+    Ok(())
+}
+
+#[test]
+fn unknown_draft_version() -> Result<()> {
+    let bytes = read_test_file()?;
+    let draft = with_version(&bytes, 0, 0, 7)?;
+    let error = File::from_slice_versioned(&draft, &[DraftLayout::new(0, 1)]).unwrap_err();
+    assert!(error.to_string().contains("0.0"), "{}", error);
+
+    let value: Value = serde_cbor::from_slice(&draft)?;
+    assert!(DraftLayout::new(0, 1).convert(value.clone()).is_err());
+    assert!(DraftLayout::new(0, 0).convert(value).is_ok());
+    Ok(())
+}
+
+/// Move the [`BlockParameters`](c_dns::serialization::BlockParameters) of the test file into a draft configuration map at key 3 of the preamble.
+///
+/// The collection parameters are stored at their key plus 10 and `max_block_items` at key 20.
+fn with_configuration(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut value: Value = serde_cbor::from_slice(bytes)?;
+    let items = array(&mut value);
+    let preamble = map(&mut items[1]);
+    preamble.insert(Value::Integer(0), Value::Integer(0));
+    preamble.insert(Value::Integer(1), Value::Integer(5));
+    let mut block_parameters = preamble
+        .remove(&Value::Integer(3))
+        .expect("The test file has block parameters");
+    let block_parameters = map(&mut array(&mut block_parameters)[0]);
+    let mut configuration = BTreeMap::new();
+    if let Some(mut collection) = block_parameters.remove(&Value::Integer(1)) {
+        for (key, value) in std::mem::take(map(&mut collection)) {
+            if let Value::Integer(key) = key {
+                configuration.insert(Value::Integer(key + 10), value);
+            }
+        }
+    }
+    let storage = map(block_parameters.get_mut(&Value::Integer(0)).unwrap());
+    configuration.insert(
+        Value::Integer(20),
+        storage.remove(&Value::Integer(1)).unwrap(),
+    );
+    preamble.insert(Value::Integer(3), Value::Map(configuration));
+    Ok(serde_cbor::to_vec(&value)?)
+}
+
+fn configuration_layout() -> DraftLayout {
+    let mut layout = DraftLayout::new(0, 5);
+    layout.configuration(3, 1_000_000).storage_key(20, 1);
+    for key in -1..10 {
+        layout.collection_key(key + 10, key);
+    }
+    layout
+}
+
+#[test]
+fn configuration_of_draft() -> Result<()> {
+    let bytes = read_test_file()?;
+    let original = File::from_slice(&bytes)?;
+    let draft = with_configuration(&bytes)?;
+    assert!(File::from_slice(&draft).is_err());
+
+    let file = File::from_slice_versioned(&draft, &[configuration_layout()])?;
+    assert_eq!(original.query_response_count(), file.query_response_count());
+    let (original, converted) = (
+        &original.file_preamble.block_parameters[0],
+        &file.file_preamble.block_parameters,
+    );
+    assert_eq!(1, converted.len());
+    let converted = &converted[0];
+    assert_eq!(
+        format!("{:?}", original.collection_parameters),
+        format!("{:?}", converted.collection_parameters)
+    );
+    let storage = &converted.storage_parameters;
+    assert_eq!(1_000_000, u32::from(storage.ticks_per_second));
+    assert_eq!(
+        original.storage_parameters.max_block_items,
+        storage.max_block_items
+    );
+    assert!(!storage.opcodes.is_empty());
+    assert!(storage
+        .opcodes
+        .iter()
+        .all(|opcode| original.storage_parameters.opcodes.contains(opcode)));
+    assert!(!storage.rr_types.is_empty());
+    assert!(storage
+        .rr_types
+        .iter()
+        .all(|rr_type| original.storage_parameters.rr_types.contains(rr_type)));
+    Ok(())
+}
+
+#[test]
+fn missing_configuration_of_draft() -> Result<()> {
+    let bytes = read_test_file()?;
+    let draft = with_version(&bytes, 0, 5, 7)?;
+    let error = File::from_slice_versioned(&draft, &[configuration_layout()]).unwrap_err();
+    assert!(error.to_string().contains("configuration"), "{}", error);
+    Ok(())
+}
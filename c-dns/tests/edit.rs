@@ -0,0 +1,118 @@
+use c_dns::edit::DetachedQueryResponse;
+use c_dns::serialization::{BlockTables, File, NameOrRdata, QueryResponse, Timestamp, UTicks};
+use color_eyre::eyre::Result;
+
+fn read_test_file() -> Result<File> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    Ok(serde_cbor::from_slice(&c_dns_content)?)
+}
+
+fn item(time: Option<Timestamp>, name: &'static [u8]) -> DetachedQueryResponse {
+    DetachedQueryResponse {
+        time,
+        query_response: QueryResponse {
+            query_name_index: Some(0),
+            transaction_id: Some(0x1234),
+            ..Default::default()
+        },
+        block_tables: BlockTables {
+            name_rdata: Some(vec![NameOrRdata::from(bytes::Bytes::from_static(name))]),
+            ..Default::default()
+        },
+    }
+}
+
+#[test]
+fn push_interns_names() -> Result<()> {
+    let mut file = read_test_file()?;
+    let block_parameters = &file.file_preamble.block_parameters[0];
+    let block = &mut file.file_blocks[0];
+    let names = block
+        .block_tables
+        .as_ref()
+        .unwrap()
+        .name_rdata
+        .as_ref()
+        .unwrap();
+    let (name_count, existing) = (names.len(), names[0].clone());
+    let qr_count = block.query_responses.as_ref().unwrap().len();
+    let qr_data_items = block.block_statistics.as_ref().unwrap().qr_data_items;
+
+    let idx = block.push_query_response(item(None, b"\x03new\x00"), block_parameters)?;
+    assert_eq!(qr_count, idx);
+    let tables = block.block_tables.as_ref().unwrap();
+    assert_eq!(name_count + 1, tables.name_rdata.as_ref().unwrap().len());
+    let pushed = &block.query_responses.as_ref().unwrap()[idx];
+    assert_eq!(Some(name_count), pushed.query_name_index);
+    assert_eq!(None, pushed.time_offset);
+    assert_eq!(
+        qr_data_items.map(|count| count + 1),
+        block.block_statistics.as_ref().unwrap().qr_data_items
+    );
+
+    // An existing name is reused
+    let mut existing_item = item(None, b"\x00");
+    existing_item.block_tables.name_rdata = Some(vec![existing]);
+    let idx = block.push_query_response(existing_item, block_parameters)?;
+    let tables = block.block_tables.as_ref().unwrap();
+    assert_eq!(name_count + 1, tables.name_rdata.as_ref().unwrap().len());
+    assert_eq!(
+        Some(0),
+        block.query_responses.as_ref().unwrap()[idx].query_name_index
+    );
+    Ok(())
+}
+
+#[test]
+fn push_maintains_time_offsets() -> Result<()> {
+    let mut file = read_test_file()?;
+    let block_parameters = &file.file_preamble.block_parameters[0];
+    let ticks_per_second = u32::from(block_parameters.storage_parameters.ticks_per_second);
+    let block = &mut file.file_blocks[0];
+    let earliest = block.block_preamble.earliest_time.unwrap();
+    let offsets: Vec<_> = block
+        .query_responses
+        .iter()
+        .flatten()
+        .map(|qr| qr.time_offset)
+        .collect();
+
+    // One second later than the earliest time
+    let later = Timestamp {
+        timestamp_secs: earliest.timestamp_secs + 1,
+        ..earliest
+    };
+    let idx = block.push_query_response(item(Some(later), b"\x00"), block_parameters)?;
+    assert_eq!(
+        Some(UTicks::from(ticks_per_second)),
+        block.query_responses.as_ref().unwrap()[idx].time_offset
+    );
+
+    // One second before the earliest time moves all items
+    let earlier = Timestamp {
+        timestamp_secs: earliest.timestamp_secs - 1,
+        ..earliest
+    };
+    let idx = block.push_query_response(item(Some(earlier), b"\x00"), block_parameters)?;
+    assert_eq!(Some(earlier), block.block_preamble.earliest_time);
+    let query_responses = block.query_responses.as_ref().unwrap();
+    assert_eq!(Some(UTicks::from(0)), query_responses[idx].time_offset);
+    for (qr, offset) in query_responses.iter().zip(&offsets) {
+        assert_eq!(
+            offset.map(|offset| UTicks::from(u32::from(offset) + ticks_per_second)),
+            qr.time_offset
+        );
+    }
+
+    // Far too late for the block
+    let too_late = Timestamp {
+        timestamp_secs: earliest.timestamp_secs + 1_000_000,
+        ..earliest
+    };
+    let qr_count = query_responses.len();
+    assert!(block
+        .push_query_response(item(Some(too_late), b"\x00"), block_parameters)
+        .is_err());
+    assert_eq!(qr_count, block.query_responses.as_ref().unwrap().len());
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use c_dns::edns::{self, ClientSubnet, OptionData};
+use c_dns::serialization::{File, NameOrRdata};
+use c_dns::wire::EdnsOption;
+use color_eyre::eyre::Result;
+
+fn rdata(bytes: &'static [u8]) -> NameOrRdata {
+    NameOrRdata::from(bytes::Bytes::from_static(bytes))
+}
+
+#[test]
+fn parse_known_options() -> Result<()> {
+    let options = edns::parse_options(&rdata(
+        b"\x00\x03\x00\x00\x00\x08\x00\x0a\x00\x02\x30\x00\x20\x01\x0d\xb8\x00\x01\x00\x0a\x00\x08\x01\x02\x03\x04\x05\x06\x07\x08\x00\x0f\x00\x04\x00\x12hi\xff\x00\x00\x01\x00",
+    ))?;
+    assert_eq!(
+        vec![
+            OptionData::Nsid(Vec::new()),
+            OptionData::ClientSubnet(ClientSubnet {
+                address: "2001:db8:1::".parse()?,
+                source_prefix_len: 48,
+                scope_prefix_len: 0,
+            }),
+            OptionData::Cookie {
+                client: [1, 2, 3, 4, 5, 6, 7, 8],
+                server: None,
+            },
+            OptionData::ExtendedError {
+                info_code: 18,
+                extra_text: "hi".to_string(),
+            },
+            OptionData::Unknown(EdnsOption {
+                code: 0xff00,
+                data: vec![0],
+            }),
+        ],
+        options
+    );
+    assert_eq!(
+        rdata(b"\x00\x03\x00\x00\x00\x08\x00\x0a\x00\x02\x30\x00\x20\x01\x0d\xb8\x00\x01\x00\x0a\x00\x08\x01\x02\x03\x04\x05\x06\x07\x08\x00\x0f\x00\x04\x00\x12hi\xff\x00\x00\x01\x00"),
+        edns::options_to_rdata(&options)?
+    );
+    Ok(())
+}
+
+#[test]
+fn client_subnet() -> Result<()> {
+    let ecs = ClientSubnet::new("192.0.2.255".parse()?, 20);
+    assert_eq!("192.0.0.0".parse::<std::net::IpAddr>()?, ecs.address);
+    // Bits beyond the source prefix are cleared
+    let options = edns::parse_options(&rdata(b"\x00\x08\x00\x07\x00\x01\x14\x00\xc0\x00\x0f"))?;
+    assert_eq!(vec![OptionData::ClientSubnet(ecs)], options);
+
+    // The address must have as many bytes as the source prefix
+    assert!(
+        edns::parse_options(&rdata(b"\x00\x08\x00\x08\x00\x01\x14\x00\xc0\x00\x02\x00")).is_err()
+    );
+    assert!(edns::parse_options(&rdata(b"\x00\x08\x00\x04\x00\x03\x00\x00")).is_err());
+    Ok(())
+}
+
+#[test]
+fn query_options_of_test_file() -> Result<()> {
+    let file = File::read_path("./tests/data/dns.cdns")?;
+    let mut with_opt = 0;
+    for (block, block_parameters) in file.iter_blocks() {
+        for qr in block.iter_query_responses(block_parameters).resolved() {
+            if qr?.query_edns_options()?.is_some() {
+                with_opt += 1;
+            }
+        }
+    }
+    assert!(with_opt > 0);
+    Ok(())
+}
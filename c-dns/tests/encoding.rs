@@ -0,0 +1,66 @@
+use c_dns::encoding::{canonicalize, EncodingMetadata};
+use color_eyre::eyre::Result;
+use pretty_assertions::assert_eq;
+use serde_cbor::Value;
+
+/// Re-encoding with the original encoding keeps the bytes of an unmodified file and the values of a modified file.
+#[test]
+fn reserialize_with_encoding_metadata() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let metadata = EncodingMetadata::from_slice(&c_dns_content)?;
+    let mut c_dns_file: c_dns::serialization::File = serde_cbor::from_slice(&c_dns_content)?;
+    assert_eq!(
+        c_dns_content,
+        metadata.apply(&serde_cbor::to_vec(&c_dns_file)?)?
+    );
+
+    c_dns_file.file_blocks[0]
+        .query_responses
+        .as_mut()
+        .expect("Test file has Q/R items")[0]
+        .client_port = Some(65535);
+    let canonical = serde_cbor::to_vec(&c_dns_file)?;
+    let reencoded = metadata.apply(&canonical)?;
+    assert_eq!(
+        serde_cbor::from_slice::<Value>(&canonical)?,
+        serde_cbor::from_slice::<Value>(&reencoded)?
+    );
+    Ok(())
+}
+
+/// Hostile lengths and nesting are rejected instead of overflowing.
+#[test]
+fn reject_hostile_encoding() {
+    // Map with 2^64 - 1 entries
+    let huge_map = b"\xbb\xff\xff\xff\xff\xff\xff\xff\xff";
+    assert!(EncodingMetadata::from_slice(huge_map).is_err());
+    assert!(canonicalize(huge_map).is_err());
+
+    // Arrays nested 100000 levels deep
+    let mut deep = vec![0x81; 100_000];
+    deep.push(0x00);
+    assert!(EncodingMetadata::from_slice(&deep).is_err());
+    assert!(canonicalize(&deep).is_err());
+}
+
+/// Different encodings of the same values have the same deterministic encoding.
+#[test]
+fn canonical_encoding() -> Result<()> {
+    // {-1: 1.5, 1: [_ "a", "b"], 0: 24} with a wide integer and indefinite lengths
+    let unusual = b"\xbf\x20\xfb\x3f\xf8\x00\x00\x00\x00\x00\x00\x01\x9f\x7f\x61a\xff\x61b\xff\x00\x19\x00\x18\xff";
+    assert_eq!(
+        b"\xa3\x00\x18\x18\x01\x82\x61a\x61b\x20\xf9\x3e\x00".to_vec(),
+        canonicalize(unusual)?
+    );
+
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let c_dns_file: c_dns::serialization::File = serde_cbor::from_slice(&c_dns_content)?;
+    let canonical = c_dns_file.to_canonical_vec()?;
+    assert_eq!(canonicalize(&c_dns_content)?, canonical);
+    assert_eq!(canonical, canonicalize(&canonical)?);
+    assert_eq!(
+        serde_cbor::from_slice::<Value>(&c_dns_content)?,
+        serde_cbor::from_slice::<Value>(&canonical)?
+    );
+    Ok(())
+}
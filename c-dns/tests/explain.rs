@@ -0,0 +1,39 @@
+use c_dns::serialization::{File, OtherDataHints, StorageFlags};
+use color_eyre::eyre::Result;
+
+fn read_test_file() -> Result<File> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    Ok(serde_cbor::from_slice(&c_dns_content)?)
+}
+
+#[test]
+fn explain_parameters() -> Result<()> {
+    let file = read_test_file()?;
+    let explanation = file.file_preamble.block_parameters[0].explain().to_string();
+    assert!(explanation.contains("resolution of 1000000 ticks per second (microseconds)"));
+    assert!(explanation.contains("The data is not anonymized, not sampled"));
+    assert!(explanation.contains("IPv4 client addresses are stored in full."));
+    assert!(explanation.contains("Other data never present: malformed-messages\n"));
+    assert!(explanation.contains("Generator: dns-stats-compactor 1.2.0\n"));
+    Ok(())
+}
+
+#[test]
+fn explain_modified_parameters() -> Result<()> {
+    let mut file = read_test_file()?;
+    let block_parameters = &mut file.file_preamble.block_parameters[0];
+    let storage_parameters = &mut block_parameters.storage_parameters;
+    storage_parameters.storage_flags = Some(StorageFlags::AnonymizedData.into());
+    storage_parameters.anonymization_method = Some("truncation".to_string());
+    storage_parameters.client_address_prefix_ipv6 = Some(48);
+    storage_parameters.storage_hints.other_data_hints |= OtherDataHints::MalformedMessages;
+    block_parameters.collection_parameters = None;
+
+    let explanation = block_parameters.explain().to_string();
+    assert!(explanation.contains("The data is anonymized, not sampled"));
+    assert!(explanation.contains("Anonymization method: truncation\n"));
+    assert!(explanation.contains("Only the first 48 bits of IPv6 client addresses are stored."));
+    assert!(!explanation.contains("Other data never present"));
+    assert!(explanation.contains("Collection parameters:\n  Not recorded"));
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use c_dns::extensions::{ExtensionDecoder, ExtensionRegistry, ExtensionScope};
+use c_dns::serialization::{File, FilePreamble};
+use color_eyre::eyre::Result;
+
+/// Decoder naming the key -1 of the CollectionParameters.
+struct Vendor {
+    name: &'static str,
+    field: &'static str,
+    generator_prefix: Option<&'static str>,
+}
+
+impl ExtensionDecoder for Vendor {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn applies_to(&self, preamble: &FilePreamble) -> bool {
+        let prefix = match self.generator_prefix {
+            Some(prefix) => prefix,
+            None => return true,
+        };
+        preamble.block_parameters.iter().any(|params| {
+            params
+                .collection_parameters
+                .as_ref()
+                .and_then(|params| params.generator_id.as_deref())
+                .is_some_and(|generator_id| generator_id.starts_with(prefix))
+        })
+    }
+
+    fn field_name(&self, scope: ExtensionScope, key: isize) -> Option<&str> {
+        (scope == ExtensionScope::CollectionParameters && key == -1).then_some(self.field)
+    }
+}
+
+fn collection_field(registry: &ExtensionRegistry, file: &File) -> Option<String> {
+    let params = file.file_preamble.block_parameters[0]
+        .collection_parameters
+        .as_ref()
+        .unwrap();
+    let fields = registry.fields(
+        &file.file_preamble,
+        ExtensionScope::CollectionParameters,
+        &params.extra_values,
+    );
+    assert_eq!(1, fields.len());
+    assert_eq!(-1, fields[0].key);
+    fields[0].name.map(String::from)
+}
+
+#[test]
+fn select_decoder_by_private_version() -> Result<()> {
+    let mut registry = ExtensionRegistry::new();
+    registry
+        .register(
+            Some(2),
+            Vendor {
+                name: "first",
+                field: "dns-port",
+                generator_prefix: None,
+            },
+        )
+        .register(
+            Some(3),
+            Vendor {
+                name: "second",
+                field: "sampling-rate",
+                generator_prefix: None,
+            },
+        );
+
+    let mut file = File::read_path("./tests/data/dns.cdns")?;
+    assert_eq!(Some(2), file.file_preamble.private_version);
+    assert_eq!(Some("dns-port".into()), collection_field(&registry, &file));
+    let (decoder, _) = registry
+        .field_name(
+            &file.file_preamble,
+            ExtensionScope::CollectionParameters,
+            -1,
+        )
+        .unwrap();
+    assert_eq!("first", decoder.name());
+
+    file.file_preamble.private_version = Some(3);
+    assert_eq!(
+        Some("sampling-rate".into()),
+        collection_field(&registry, &file)
+    );
+
+    file.file_preamble.private_version = None;
+    assert_eq!(None, collection_field(&registry, &file));
+    assert_eq!(0, registry.decoders(&file.file_preamble).count());
+    Ok(())
+}
+
+#[test]
+fn restrict_decoder_by_generator() -> Result<()> {
+    let mut registry = ExtensionRegistry::new();
+    registry
+        .register(
+            Some(2),
+            Vendor {
+                name: "other",
+                field: "other-field",
+                generator_prefix: Some("other-collector"),
+            },
+        )
+        .register(
+            Some(2),
+            Vendor {
+                name: "compactor",
+                field: "dns-port",
+                generator_prefix: Some("dns-stats-compactor"),
+            },
+        );
+
+    let file = File::read_path("./tests/data/dns.cdns")?;
+    assert_eq!(Some("dns-port".into()), collection_field(&registry, &file));
+    Ok(())
+}
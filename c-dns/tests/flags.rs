@@ -0,0 +1,142 @@
+use c_dns::flags::{DnsHeaderFlags, FlagSetExt};
+use c_dns::serialization::{
+    DNSFlags, File, QueryResponseFlags, ResponseProcessingData, ResponseProcessingFlags,
+    StorageFlags,
+};
+use c_dns::wire::Direction;
+use color_eyre::eyre::Result;
+use enumset::EnumSet;
+use serde_cbor::Value;
+use std::collections::BTreeMap;
+
+#[test]
+fn header_flags() {
+    let dns_flags =
+        DNSFlags::QueryRd | DNSFlags::QueryDo | DNSFlags::ResponseRa | DNSFlags::ResponseRc;
+    let query = DnsHeaderFlags::from_dns_flags(dns_flags, Direction::Query);
+    assert_eq!(
+        DnsHeaderFlags {
+            rd: true,
+            dnssec_ok: true,
+            ..Default::default()
+        },
+        query
+    );
+    let response = DnsHeaderFlags::from_dns_flags(dns_flags, Direction::Response);
+    assert_eq!(
+        DnsHeaderFlags {
+            ra: true,
+            tc: true,
+            ..Default::default()
+        },
+        response
+    );
+    assert_eq!(
+        dns_flags,
+        query.to_dns_flags(Direction::Query) | response.to_dns_flags(Direction::Response)
+    );
+    // The DO bit is only recorded for the Query
+    assert_eq!(
+        DNSFlags::ResponseRd,
+        query.to_dns_flags(Direction::Response)
+    );
+    assert_eq!(
+        DNSFlags::QueryRd | DNSFlags::QueryDo,
+        query.to_dns_flags(Direction::Query)
+    );
+
+    // RD and RA, with a QR bit and RCODE which are ignored
+    let header = DnsHeaderFlags::from_header_bits(0x8183);
+    assert!(header.rd && header.ra && !header.aa && !header.dnssec_ok);
+    assert_eq!(0x0180, header.header_bits());
+}
+
+#[test]
+fn signature_flags() -> Result<()> {
+    let file = File::read_path("./tests/data/dns.cdns")?;
+    let block_tables = file.file_blocks[0].block_tables.as_ref().unwrap();
+    for sig in block_tables.qr_sig.iter().flatten() {
+        let dns_flags = sig.qr_dns_flags.unwrap();
+        let qr_sig_flags = sig.qr_sig_flags.unwrap();
+        assert_eq!(
+            qr_sig_flags.contains(QueryResponseFlags::HasQuery),
+            sig.query_flags().is_some()
+        );
+        assert_eq!(
+            qr_sig_flags.contains(QueryResponseFlags::HasResponse),
+            sig.response_flags().is_some()
+        );
+        let combined = sig
+            .query_flags()
+            .map(|flags| flags.to_dns_flags(Direction::Query))
+            .unwrap_or_default()
+            | sig
+                .response_flags()
+                .map(|flags| flags.to_dns_flags(Direction::Response))
+                .unwrap_or_default();
+        assert_eq!(dns_flags, combined);
+    }
+
+    let mut sig = block_tables.qr_sig.as_ref().unwrap()[0].clone();
+    sig.qr_dns_flags = None;
+    assert_eq!(None, sig.query_flags());
+    Ok(())
+}
+
+/// The processing flags are a bit field, so any combination of bits must round-trip.
+#[test]
+fn response_processing_flags() -> Result<()> {
+    let mut map = BTreeMap::new();
+    map.insert(Value::Integer(0), Value::Integer(3));
+    map.insert(Value::Integer(1), Value::Integer(1));
+    let before = Value::Map(map);
+
+    let data: ResponseProcessingData = serde_cbor::value::from_value(before.clone())?;
+    assert_eq!(
+        Some(ResponseProcessingFlags::FromCache.into()),
+        data.processing_flags
+    );
+    let after = serde_cbor::value::to_value(&data)?;
+    assert_eq!(before, after);
+
+    let mut map = BTreeMap::new();
+    map.insert(Value::Integer(1), Value::Integer(0));
+    let data: ResponseProcessingData = serde_cbor::value::from_value(Value::Map(map))?;
+    assert_eq!(Some(enumset::EnumSet::empty()), data.processing_flags);
+    Ok(())
+}
+
+#[test]
+fn flag_display() -> Result<()> {
+    assert_eq!("-", EnumSet::<StorageFlags>::new().display().to_string());
+    assert_eq!(
+        "QUERY|Q:OPT",
+        (QueryResponseFlags::HasQuery | QueryResponseFlags::QueryHasOpt)
+            .display()
+            .to_string()
+    );
+
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let file: File = serde_cbor::from_slice(&c_dns_content)?;
+    let hints = &file.file_preamble.block_parameters[0]
+        .storage_parameters
+        .storage_hints;
+    assert_eq!("ttl|rdata-index", hints.rr_hints.display().to_string());
+    assert!(format!("{:?}", hints).contains("other_data_hints: address-event-counts"));
+    Ok(())
+}
+
+/// Bits not assigned at the time of writing are kept.
+#[test]
+fn unassigned_response_processing_flags() -> Result<()> {
+    let mut map = BTreeMap::new();
+    map.insert(Value::Integer(1), Value::Integer(0b1000_0101));
+    let before = Value::Map(map);
+    let data: ResponseProcessingData = serde_cbor::value::from_value(before.clone())?;
+    let flags = data.processing_flags.unwrap();
+    assert!(flags.contains(ResponseProcessingFlags::FromCache));
+    assert_eq!(0b1000_0101, flags.as_u8());
+    assert_eq!("from-cache|bit-2|bit-7", flags.display().to_string());
+    assert_eq!(before, serde_cbor::value::to_value(&data)?);
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use c_dns::hints::FieldPresence;
+use c_dns::serialization::{
+    File, FileWriter, OtherDataHints, QueryResponseHints, QueryResponseSignatureHints, StorageHints,
+};
+use color_eyre::eyre::Result;
+
+fn read_test_file() -> Result<File> {
+    Ok(File::read_path("./tests/data/dns.cdns")?)
+}
+
+#[test]
+fn observed_hints() -> Result<()> {
+    let file = read_test_file()?;
+    let block = &file.file_blocks[0];
+    let observed = StorageHints::from_observed(block);
+    assert!(observed.covers(block));
+    assert!(observed
+        .query_response_hints
+        .contains(QueryResponseHints::ClientAddressIndex));
+    assert!(observed
+        .query_response_signature_hints
+        .contains(QueryResponseSignatureHints::QrSigFlags));
+    // The test data has no extended sections
+    assert!(!observed
+        .query_response_hints
+        .contains(QueryResponseHints::QueryAnswerSections));
+    assert!(observed.other_data_hints.is_empty());
+    assert!(!StorageHints::none().covers(block));
+
+    // The hints of the collector include everything it stored
+    let stored = &file.file_preamble.block_parameters[0]
+        .storage_parameters
+        .storage_hints;
+    assert!(stored.covers(block));
+    Ok(())
+}
+
+#[test]
+fn strip_to_hints() -> Result<()> {
+    let mut file = read_test_file()?;
+    let mut hints = StorageHints::from_observed(&file.file_blocks[0]);
+    hints
+        .query_response_hints
+        .remove(QueryResponseHints::QueryNameIndex);
+    hints
+        .query_response_signature_hints
+        .remove(QueryResponseSignatureHints::QueryClasstypeIndex);
+    file.file_preamble.block_parameters[0]
+        .storage_parameters
+        .storage_hints = hints.clone();
+    let original = file.to_vec()?;
+
+    // Writing strips the fields without changing the file
+    let mut writer =
+        FileWriter::new(Vec::new(), &file.file_preamble)?.strip_to_hints(&file.file_preamble);
+    writer.write_block(&file.file_blocks[0])?;
+    let written = File::from_slice(&writer.finish()?)?;
+    assert_eq!(original, file.to_vec()?);
+
+    file.strip_to_hints();
+    let block = &file.file_blocks[0];
+    assert!(hints.covers(block));
+    assert!(block
+        .query_responses
+        .iter()
+        .flatten()
+        .all(|qr| qr.query_name_index.is_none()));
+    // The classtypes are no longer referenced
+    let block_tables = block.block_tables.as_ref().unwrap();
+    assert!(block_tables.classtype.is_none());
+    assert!(file.dangling_indices().is_empty());
+    assert_eq!(file.to_vec()?, written.to_vec()?);
+
+    // Without any hints only the bare items remain
+    let block = &mut file.file_blocks[0];
+    block.strip_to_hints(&StorageHints::none());
+    assert_eq!(12, block.query_responses.as_ref().unwrap().len());
+    assert!(StorageHints::none().covers(block));
+    Ok(())
+}
+
+#[test]
+fn field_presence() -> Result<()> {
+    let file = read_test_file()?;
+    let block = &file.file_blocks[0];
+    let mut hints = StorageHints::from_observed(block);
+    let qr = &block.query_responses.as_ref().unwrap()[0];
+    let sig = &block
+        .block_tables
+        .as_ref()
+        .unwrap()
+        .qr_sig
+        .as_ref()
+        .unwrap()[0];
+
+    assert!(hints.includes(QueryResponseHints::ClientAddressIndex));
+    assert_eq!(
+        FieldPresence::Present,
+        qr.field_presence(QueryResponseHints::ClientAddressIndex, &hints)
+    );
+    assert_eq!(
+        FieldPresence::Present,
+        sig.field_presence(QueryResponseSignatureHints::QrSigFlags, &hints)
+    );
+    // Present data is reported even if the hints deny it
+    assert_eq!(
+        FieldPresence::Present,
+        qr.field_presence(
+            QueryResponseHints::ClientAddressIndex,
+            &StorageHints::none()
+        )
+    );
+
+    assert!(!hints.includes(QueryResponseHints::QueryAnswerSections));
+    assert_eq!(
+        FieldPresence::NotCollected,
+        qr.field_presence(QueryResponseHints::QueryAnswerSections, &hints)
+    );
+    hints
+        .query_response_hints
+        .insert(QueryResponseHints::QueryAnswerSections);
+    assert_eq!(
+        FieldPresence::Absent,
+        qr.field_presence(QueryResponseHints::QueryAnswerSections, &hints)
+    );
+
+    assert_eq!(
+        FieldPresence::NotCollected,
+        block.field_presence(OtherDataHints::MalformedMessages, &hints)
+    );
+    hints
+        .other_data_hints
+        .insert(OtherDataHints::MalformedMessages);
+    assert!(hints.includes(OtherDataHints::MalformedMessages));
+    assert_eq!(
+        FieldPresence::Absent,
+        block.field_presence(OtherDataHints::MalformedMessages, &hints)
+    );
+    Ok(())
+}
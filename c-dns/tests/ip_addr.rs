@@ -0,0 +1,97 @@
+use c_dns::builder::StorageParametersBuilder;
+use c_dns::serialization::IpAddr;
+use color_eyre::eyre::Result;
+
+#[test]
+fn with_prefix() -> Result<()> {
+    let ip = IpAddr::with_prefix("192.0.2.255".parse()?, 20);
+    assert_eq!(&[192, 0, 0], ip.as_bytes());
+    assert_eq!(24, ip.stored_bits());
+
+    let ip = IpAddr::with_prefix("2001:db8::1".parse()?, 48);
+    assert_eq!(&[0x20, 0x01, 0x0d, 0xb8, 0, 0], ip.as_bytes());
+
+    let ip = IpAddr::with_prefix("192.0.2.1".parse()?, 64);
+    assert_eq!(&[192, 0, 2, 1], ip.as_bytes());
+    assert_eq!(IpAddr::from("192.0.2.1".parse::<std::net::IpAddr>()?), ip);
+    Ok(())
+}
+
+#[test]
+fn match_full_addresses() -> Result<()> {
+    let ip = IpAddr::from("192.0.2.1".parse::<std::net::IpAddr>()?);
+    assert!(ip.matches(&"192.0.2.1".parse()?, 32));
+    assert!(!ip.matches(&"192.0.2.2".parse()?, 32));
+    assert!(ip.matches(&"192.0.2.0".parse()?, 24));
+    assert!(ip.matches(&"192.0.3.0".parse()?, 23));
+    assert!(!ip.matches(&"192.0.3.0".parse()?, 24));
+    assert!(ip.matches(&"10.0.0.0".parse()?, 0));
+
+    let ip = IpAddr::from("2001:db8::1".parse::<std::net::IpAddr>()?);
+    assert!(ip.matches(&"2001:db8::".parse()?, 32));
+    assert!(!ip.matches(&"2001:db9::".parse()?, 32));
+    assert!(!ip.matches(&"32.1.13.184".parse()?, 32));
+    Ok(())
+}
+
+#[test]
+fn match_truncated_addresses() -> Result<()> {
+    let ip = IpAddr::with_prefix("192.0.2.1".parse()?, 22);
+    assert!(ip.matches(&"192.0.0.0".parse()?, 16));
+    assert!(ip.matches(&"192.0.0.0".parse()?, 22));
+    assert!(!ip.matches(&"192.0.4.0".parse()?, 24));
+    // The stored bits are unknown beyond the prefix
+    assert!(!ip.matches(&"192.0.3.77".parse()?, 32));
+    assert!(ip.matches_with_stored_prefix(&"192.0.3.77".parse()?, 32, Some(22)));
+    assert!(!ip.matches_with_stored_prefix(&"192.0.4.0".parse()?, 32, Some(22)));
+
+    // An IPv4 prefix fits into an IPv6 address
+    let ip = IpAddr::with_prefix("2001:db8::1".parse()?, 32);
+    assert!(ip.matches(&"2001:db8:1::".parse()?, 48));
+    assert!(ip.matches(&"32.1.13.184".parse()?, 32));
+
+    assert!(!IpAddr::from(bytes::Bytes::new()).matches(&"192.0.2.1".parse()?, 0));
+    Ok(())
+}
+
+#[test]
+fn expand_with_storage_parameters() -> Result<()> {
+    let full = StorageParametersBuilder::new(1_000_000, 5000).build()?;
+    let ip = IpAddr::from("2001:db8::1".parse::<std::net::Ipv6Addr>()?);
+    assert_eq!(
+        "2001:db8::1".parse::<std::net::IpAddr>()?,
+        ip.to_ip_addr(&full, true)?
+    );
+    assert!(ip.to_ip_addr(&full, false).is_err());
+    // Short addresses are zero-extended
+    let ip = IpAddr::from(bytes::Bytes::from_static(&[192, 0]));
+    assert_eq!(
+        "192.0.0.0".parse::<std::net::IpAddr>()?,
+        ip.to_ip_addr(&full, false)?
+    );
+    assert_eq!(
+        "c000::".parse::<std::net::IpAddr>()?,
+        ip.to_ip_addr(&full, true)?
+    );
+    assert!(IpAddr::from(bytes::Bytes::new())
+        .to_ip_addr(&full, false)
+        .is_err());
+
+    let prefixed = StorageParametersBuilder::new(1_000_000, 5000)
+        .client_address_prefix_ipv4(20)
+        .server_address_prefix_ipv4(16)
+        .client_address_prefix_ipv6(32)
+        .build()?;
+    // Bits beyond the longer prefix are cleared
+    let ip = IpAddr::from(bytes::Bytes::from_static(&[192, 0, 255]));
+    assert_eq!(
+        "192.0.240.0".parse::<std::net::IpAddr>()?,
+        ip.to_ip_addr(&prefixed, false)?
+    );
+    let ip = IpAddr::from("192.0.2.1".parse::<std::net::Ipv4Addr>()?);
+    assert!(ip.to_ip_addr(&prefixed, false).is_err());
+    // Without a server prefix the full address may be stored
+    let ip = IpAddr::from("2001:db8::1".parse::<std::net::Ipv6Addr>()?);
+    assert!(ip.to_ip_addr(&prefixed, true).is_ok());
+    Ok(())
+}
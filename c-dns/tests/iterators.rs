@@ -0,0 +1,287 @@
+use c_dns::serialization::File;
+use color_eyre::eyre::Result;
+use std::net::{IpAddr, Ipv6Addr};
+
+fn read_test_file() -> Result<File> {
+    Ok(File::read_path("./tests/data/dns.cdns")?)
+}
+
+#[test]
+fn owning_iterators() -> Result<()> {
+    let file = read_test_file()?;
+    let expected = file.to_vec()?;
+    let query_response_count = file.file_blocks[0].query_responses.as_ref().unwrap().len();
+
+    let mut blocks = file.into_blocks();
+    assert_eq!(1, blocks.len());
+    assert_eq!(1, blocks.file_preamble().major_format_version);
+    let block = blocks.next().unwrap();
+    assert!(blocks.block_parameters(&block).is_some());
+    assert!(blocks.next().is_none());
+
+    let mut query_responses = block.into_query_responses();
+    assert!(query_responses.block_preamble().earliest_time.is_some());
+    let block_tables = query_responses.take_block_tables().unwrap();
+    let query_responses: Vec<_> = query_responses.collect();
+    assert_eq!(query_response_count, query_responses.len());
+
+    // The moved values are the same as in the file
+    let mut file = File::from_slice(&expected)?;
+    file.file_blocks[0].block_tables = Some(block_tables);
+    file.file_blocks[0].query_responses = Some(query_responses);
+    assert_eq!(expected, file.to_vec()?);
+    Ok(())
+}
+
+#[test]
+fn mutable_iterators() -> Result<()> {
+    let mut file = read_test_file()?;
+    for (block, _block_parameters) in file.iter_blocks_mut() {
+        for query_response in block.iter_query_responses_mut() {
+            query_response.client_port = None;
+        }
+        let block_tables = block.block_tables.as_mut().unwrap();
+        for idx in 0..block_tables.ip_address.as_ref().map_or(0, Vec::len) {
+            let ip = block_tables.ip_mut(idx)?;
+            *ip = bytes::Bytes::copy_from_slice(&ip.as_bytes()[..2]).into();
+        }
+        assert!(block_tables.ip_mut(usize::MAX).is_err());
+    }
+
+    let file = File::from_slice(&file.to_vec()?)?;
+    let block = &file.file_blocks[0];
+    assert!(block
+        .query_responses
+        .iter()
+        .flatten()
+        .all(|query_response| query_response.client_port.is_none()));
+    assert!(block
+        .block_tables
+        .as_ref()
+        .unwrap()
+        .ip_address
+        .iter()
+        .flatten()
+        .all(|ip| ip.as_bytes().len() == 2));
+    Ok(())
+}
+
+#[test]
+fn missing_block_tables() -> Result<()> {
+    let mut file = read_test_file()?;
+    let block_parameters = &file.file_preamble.block_parameters[0];
+    let block = &mut file.file_blocks[0];
+    block.block_tables = None;
+
+    // Q/R items referencing the missing tables fail to resolve
+    let mut resolved = block.iter_query_responses(block_parameters).resolved();
+    assert!(matches!(
+        resolved.next(),
+        Some(Err(c_dns::Error::IndexOutOfBounds { .. }))
+    ));
+
+    // A block without Q/R items does not need tables
+    block.query_responses = None;
+    assert_eq!(0, block.iter_query_responses(block_parameters).count());
+    Ok(())
+}
+
+#[test]
+fn all_query_responses() -> Result<()> {
+    let file = read_test_file()?;
+    let expected: usize = file
+        .iter_blocks()
+        .map(|(block, block_parameters)| block.iter_query_responses(block_parameters).count())
+        .sum();
+    assert_eq!(expected, file.iter_all_query_responses().count());
+
+    let earliest_time = file.file_blocks[0]
+        .block_preamble
+        .earliest_time
+        .unwrap()
+        .to_system_time(
+            file.file_preamble.block_parameters[0]
+                .storage_parameters
+                .ticks_per_second
+                .into(),
+        )?;
+    for (query_response, time, _block_parameters, _block_tables) in file.iter_all_query_responses()
+    {
+        let time = time.unwrap();
+        assert!(time >= earliest_time);
+        if query_response.time_offset.is_none() {
+            assert_eq!(earliest_time, time);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn address_events_and_malformed_messages() -> Result<()> {
+    use c_dns::serialization::{
+        AddressEventCount, AddressEventType, ExtraValues, MalformedMessage, MalformedMessageData,
+    };
+
+    let mut file = read_test_file()?;
+    let block = &mut file.file_blocks[0];
+    block.address_event_counts = Some(vec![AddressEventCount {
+        ae_type: AddressEventType::TcpReset,
+        ae_code: None,
+        ae_address_index: 1,
+        ae_transport_flags: None,
+        ae_count: 3,
+        extra_values: ExtraValues::new(),
+    }]);
+    let block_tables = block.block_tables.as_mut().unwrap();
+    block_tables.malformed_message_data = Some(vec![MalformedMessageData {
+        server_address_index: None,
+        server_port: None,
+        mm_transport_flags: None,
+        mm_payload: Some(bytes::Bytes::from_static(b"\x00")),
+        extra_values: ExtraValues::new(),
+    }]);
+    block.malformed_messages = Some(vec![MalformedMessage {
+        time_offset: None,
+        client_address_index: None,
+        client_port: None,
+        message_data_index: Some(0),
+        extra_values: ExtraValues::new(),
+    }]);
+
+    let block_tables = block.block_tables.as_ref().unwrap();
+    let address_events = block
+        .iter_address_events()
+        .collect::<c_dns::Result<Vec<_>>>()?;
+    assert_eq!(1, address_events.len());
+    assert_eq!(3, address_events[0].0.ae_count);
+    assert_eq!(block_tables.ip(1)?, address_events[0].1);
+
+    let malformed_messages = block
+        .iter_malformed_messages()
+        .collect::<c_dns::Result<Vec<_>>>()?;
+    assert_eq!(1, malformed_messages.len());
+    let (_, client_address, data) = malformed_messages[0];
+    assert!(client_address.is_none());
+    assert_eq!(Some(&b"\x00"[..]), data.unwrap().mm_payload.as_deref());
+
+    // Dangling indices are reported per item
+    block.block_tables = None;
+    assert!(block.iter_address_events().all(|item| item.is_err()));
+    assert!(block.iter_malformed_messages().all(|item| item.is_err()));
+    Ok(())
+}
+
+#[test]
+fn filter_all_query_responses() -> Result<()> {
+    use std::time::Duration;
+
+    let file = read_test_file()?;
+    let items: Vec<_> = file.iter_all_query_responses().collect();
+    let times: Vec<_> = items.iter().filter_map(|item| item.1).collect();
+    let start = *times.iter().min().unwrap();
+    let end = *times.iter().max().unwrap();
+
+    assert_eq!(
+        times.len(),
+        file.iter_all_query_responses()
+            .between(start, end + Duration::from_secs(1))
+            .count()
+    );
+    assert_eq!(
+        times.iter().filter(|&&time| time < end).count(),
+        file.iter_all_query_responses().between(start, end).count()
+    );
+    // The block starts after the range
+    assert_eq!(
+        0,
+        file.iter_all_query_responses()
+            .between(start - Duration::from_secs(10), start)
+            .count()
+    );
+
+    let resolved: Vec<_> = file
+        .iter_blocks()
+        .flat_map(|(block, block_parameters)| {
+            block.iter_query_responses(block_parameters).resolved()
+        })
+        .collect::<c_dns::Result<_>>()?;
+    let client = resolved.iter().find_map(|qr| qr.client_address).unwrap();
+    assert_eq!(
+        resolved
+            .iter()
+            .filter(|qr| qr.client_address == Some(client))
+            .count(),
+        file.iter_all_query_responses()
+            .for_client(client, 128)
+            .count()
+    );
+    assert_eq!(
+        resolved
+            .iter()
+            .filter(|qr| qr
+                .client_address
+                .is_some_and(|addr| addr.is_ipv4() == client.is_ipv4()))
+            .count(),
+        file.iter_all_query_responses()
+            .for_client(client, 0)
+            .count()
+    );
+
+    // An IPv6 network never matches IPv4 clients, even if the leading bytes are equal
+    let ipv4_client = resolved
+        .iter()
+        .find_map(|qr| match qr.client_address {
+            Some(IpAddr::V4(addr)) => Some(addr),
+            _ => None,
+        })
+        .unwrap();
+    let mut octets = [0; 16];
+    octets[..4].copy_from_slice(&ipv4_client.octets());
+    let ipv6_network = IpAddr::V6(Ipv6Addr::from(octets));
+    assert!(file
+        .iter_all_query_responses()
+        .for_client(IpAddr::V4(ipv4_client), 32)
+        .next()
+        .is_some());
+    assert_eq!(
+        0,
+        file.iter_all_query_responses()
+            .for_client(ipv6_network, 32)
+            .count()
+    );
+
+    let name = resolved
+        .iter()
+        .filter_map(|qr| qr.query_name?.to_domain_name().ok())
+        .find(|name| !name.is_root())
+        .unwrap();
+    let suffix = name.labels().last().unwrap();
+    let suffix = String::from_utf8(suffix.to_ascii_uppercase())?;
+    let expected = resolved
+        .iter()
+        .filter(|qr| {
+            qr.query_name
+                .and_then(|name| name.to_domain_name().ok())
+                .and_then(|name| {
+                    name.labels()
+                        .last()
+                        .map(|label| label.eq_ignore_ascii_case(suffix.as_bytes()))
+                })
+                .unwrap_or(false)
+        })
+        .count();
+    assert!(expected > 0);
+    assert_eq!(
+        expected,
+        file.iter_all_query_responses()
+            .for_qname_suffix(&format!("{}.", suffix))
+            .count()
+    );
+    assert_eq!(
+        0,
+        file.iter_all_query_responses()
+            .for_qname_suffix("invalid.")
+            .count()
+    );
+    Ok(())
+}
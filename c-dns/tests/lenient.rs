@@ -0,0 +1,104 @@
+use c_dns::lint::Violation;
+use c_dns::serialization::File;
+use color_eyre::eyre::Result;
+use serde_cbor::Value;
+
+/// The map of the first storage parameters in the CBOR value of a file.
+fn storage_parameters(file: &mut Value) -> &mut std::collections::BTreeMap<Value, Value> {
+    let preamble = match file {
+        Value::Array(items) => &mut items[1],
+        _ => unreachable!(),
+    };
+    let block_parameters = match preamble {
+        Value::Map(map) => map.get_mut(&Value::Integer(3)).unwrap(),
+        _ => unreachable!(),
+    };
+    let block_parameters = match block_parameters {
+        Value::Array(items) => &mut items[0],
+        _ => unreachable!(),
+    };
+    match block_parameters {
+        Value::Map(map) => match map.get_mut(&Value::Integer(0)).unwrap() {
+            Value::Map(map) => map,
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn lenient_valid_file() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let (file, violations) = File::from_reader_lenient(&*c_dns_content)?;
+    assert_eq!(Vec::<Violation>::new(), violations);
+    assert_eq!(
+        serde_cbor::to_vec(&serde_cbor::from_slice::<File>(&c_dns_content)?)?,
+        serde_cbor::to_vec(&file)?
+    );
+    Ok(())
+}
+
+#[test]
+fn lenient_off_spec_file() -> Result<()> {
+    let mut value: Value = serde_cbor::from_slice(&std::fs::read("./tests/data/dns.cdns")?)?;
+    if let Value::Array(items) = &mut value {
+        items[0] = Value::Text("C-DNS-OLD".to_string());
+    }
+    let storage_parameters = storage_parameters(&mut value);
+    storage_parameters.insert(
+        Value::Integer(3),
+        Value::Array(vec![Value::Integer(0), Value::Integer(20)]),
+    );
+    storage_parameters.insert(Value::Integer(6), Value::Integer(40));
+    storage_parameters.insert(Value::Integer(7), Value::Integer(300));
+    let c_dns_content = serde_cbor::to_vec(&value)?;
+
+    assert!(serde_cbor::from_slice::<File>(&c_dns_content).is_err());
+    let (file, violations) = File::from_reader_lenient(&*c_dns_content)?;
+    let storage_parameters = &file.file_preamble.block_parameters[0].storage_parameters;
+    assert_eq!(Some(40), storage_parameters.client_address_prefix_ipv4);
+    assert_eq!(None, storage_parameters.client_address_prefix_ipv6);
+    assert_eq!(
+        vec![
+            "file_preamble.block_parameters[0].storage_parameters.opcodes[1]: dropped invalid OPCODE Integer(20)",
+            "file_preamble.block_parameters[0].storage_parameters.client_address_prefix_ipv6: dropped invalid address prefix length Integer(300)",
+            "file_type_id: invalid file type id \"C-DNS-OLD\"",
+            "file_preamble.block_parameters[0].storage_parameters.client_address_prefix_ipv4: address prefix length 40 is outside of the range 1 to 32",
+        ],
+        violations
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+#[test]
+fn lenient_invalid_vlan_ids() -> Result<()> {
+    let mut file: File = serde_cbor::from_slice(&std::fs::read("./tests/data/dns.cdns")?)?;
+    file.file_preamble.block_parameters[0]
+        .collection_parameters
+        .get_or_insert_with(Default::default)
+        .vlan_ids = Some(vec![0, 100, 4095]);
+    let c_dns_content = serde_cbor::to_vec(&file)?;
+
+    let (file, violations) = File::from_reader_lenient(&*c_dns_content)?;
+    assert_eq!(
+        Some(&vec![0, 100, 4095]),
+        file.file_preamble.block_parameters[0]
+            .collection_parameters
+            .as_ref()
+            .and_then(|params| params.vlan_ids.as_ref())
+    );
+    assert_eq!(
+        vec![
+            "file_preamble.block_parameters[0].collection_parameters.vlan_ids[0]: VLAN ID 0 is outside of the range 1 to 4094",
+            "file_preamble.block_parameters[0].collection_parameters.vlan_ids[2]: VLAN ID 4095 is outside of the range 1 to 4094",
+        ],
+        violations
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use c_dns::lazy::LazyFile;
+use c_dns::limits::Limits;
+use c_dns::serialization::{File, FileReader};
+use color_eyre::eyre::Result;
+
+fn read_test_file() -> Result<Vec<u8>> {
+    Ok(std::fs::read("./tests/data/dns.cdns")?)
+}
+
+#[test]
+fn within_limits() -> Result<()> {
+    let c_dns_content = read_test_file()?;
+    Limits::default().check(&c_dns_content)?;
+    let limits = Limits {
+        max_array_len: 100,
+        max_string_len: 100,
+        max_blocks: 1,
+        max_allocation: 64 * 1024,
+    };
+    let file = File::from_slice_with_limits(&c_dns_content, &limits)?;
+    assert_eq!(1, file.file_blocks.len());
+    let lazy = LazyFile::from_slice_with_limits(&c_dns_content, &limits)?;
+    assert_eq!(1, lazy.blocks().len());
+    let blocks =
+        FileReader::with_limits(&*c_dns_content, limits)?.collect::<c_dns::Result<Vec<_>>>()?;
+    assert_eq!(file.file_blocks.len(), blocks.len());
+    Ok(())
+}
+
+#[test]
+fn exceeding_limits() -> Result<()> {
+    let c_dns_content = read_test_file()?;
+    for limits in [
+        Limits {
+            max_array_len: 5,
+            ..Limits::default()
+        },
+        Limits {
+            max_string_len: 4,
+            ..Limits::default()
+        },
+        Limits {
+            max_blocks: 0,
+            ..Limits::default()
+        },
+        Limits {
+            max_allocation: 1024,
+            ..Limits::default()
+        },
+    ] {
+        assert!(limits.check(&c_dns_content).is_err(), "{:?}", limits);
+        assert!(File::from_slice_with_limits(&c_dns_content, &limits).is_err());
+        assert!(LazyFile::from_slice_with_limits(&c_dns_content, &limits).is_err());
+        let blocks = FileReader::with_limits(&*c_dns_content, limits)
+            .and_then(|reader| reader.collect::<c_dns::Result<Vec<_>>>());
+        assert!(blocks.is_err(), "{:?}", limits);
+    }
+    Ok(())
+}
+
+/// Files within the limits fail like [`File::from_slice`], with the path of the failing value.
+#[test]
+fn error_path() -> Result<()> {
+    let c_dns_content = read_test_file()?;
+    let mut file = serde_cbor::value::to_value(File::from_slice(&c_dns_content)?)?;
+    if let serde_cbor::Value::Array(file) = &mut file {
+        if let serde_cbor::Value::Array(blocks) = &mut file[2] {
+            if let serde_cbor::Value::Map(block) = &mut blocks[0] {
+                block.insert(
+                    serde_cbor::Value::Integer(3),
+                    serde_cbor::Value::Text("not a list".into()),
+                );
+            }
+        }
+    }
+    let file = serde_cbor::to_vec(&file)?;
+    let error = File::from_slice_with_limits(&file, &Limits::default()).unwrap_err();
+    assert_eq!(
+        File::from_slice(&file).unwrap_err().to_string(),
+        error.to_string()
+    );
+    assert!(error
+        .to_string()
+        .starts_with("Failed to deserialize [2][0]"));
+    Ok(())
+}
+
+#[test]
+fn hostile_lengths() -> Result<()> {
+    let limits = Limits {
+        max_array_len: 1000,
+        max_string_len: 1000,
+        ..Limits::default()
+    };
+    // A File whose block array claims 2^32 blocks
+    let huge_array = b"\x83\x65C-DNS\xa0\x9a\xff\xff\xff\xff";
+    assert!(limits.check(huge_array).is_err());
+    // A byte string claiming 2^64 - 1 bytes
+    let huge_string = b"\x83\x65C-DNS\xa0\x81\xa1\x00\x5b\xff\xff\xff\xff\xff\xff\xff\xff";
+    assert!(limits.check(huge_string).is_err());
+
+    // A block of deeply nested arrays
+    let file: File = serde_cbor::from_slice(&read_test_file()?)?;
+    let mut deep = b"\x83\x65C-DNS".to_vec();
+    deep.extend(serde_cbor::to_vec(&file.file_preamble)?);
+    deep.push(0x81);
+    deep.extend(std::iter::repeat_n(0x81, 10_000));
+    deep.push(0x00);
+    assert!(Limits::default().check(&deep).is_err());
+    assert!(c_dns::lazy::LazyFile::from_slice(&deep).is_err());
+    Ok(())
+}
+
+#[test]
+fn indefinite_lengths() {
+    let limits = Limits {
+        max_array_len: 2,
+        max_string_len: 5,
+        ..Limits::default()
+    };
+    // A block with an indefinite-length array of 3 elements
+    let long_array = b"\x83\x65C-DNS\xa0\x81\xa1\x00\x9f\x01\x02\x03\xff";
+    let error = limits.check(long_array).unwrap_err().to_string();
+    assert!(error.contains("exceeds the limit of 2"), "{}", error);
+    assert!(Limits::default().check(long_array).is_ok());
+
+    // A block with an indefinite-length byte string of two chunks with 3 bytes each
+    let long_string = b"\x83\x65C-DNS\xa0\x81\xa1\x00\x5f\x43abc\x43def\xff";
+    let error = limits.check(long_string).unwrap_err().to_string();
+    assert!(error.contains("exceeds the limit of 5"), "{}", error);
+    assert!(Limits::default().check(long_string).is_ok());
+
+    // Chunks of a different type than the string
+    let mixed_chunks = b"\x83\x65C-DNS\xa0\x81\xa1\x00\x5f\x63abc\xff";
+    assert!(Limits::default().check(mixed_chunks).is_err());
+}
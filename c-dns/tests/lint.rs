@@ -0,0 +1,143 @@
+use c_dns::lint::Violation;
+use c_dns::serialization::File;
+use color_eyre::eyre::Result;
+
+fn read_test_file() -> Result<File> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    Ok(serde_cbor::from_slice(&c_dns_content)?)
+}
+
+#[test]
+fn lint_consistent_file() -> Result<()> {
+    let file = read_test_file()?;
+    assert_eq!(
+        Vec::<String>::new(),
+        file.lint()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+#[test]
+fn lint_inconsistent_file() -> Result<()> {
+    let mut file = read_test_file()?;
+    let block = &mut file.file_blocks[0];
+    block.block_preamble.earliest_time = None;
+    block
+        .block_statistics
+        .as_mut()
+        .expect("Test file has block statistics")
+        .qr_data_items = Some(1000);
+
+    let warnings = file.lint();
+    assert!(warnings
+        .iter()
+        .any(|warning| warning.path == "file_blocks[0].query_responses[0].time_offset"));
+    assert!(warnings
+        .iter()
+        .any(|warning| warning.path == "file_blocks[0].block_statistics.qr_data_items"));
+    Ok(())
+}
+
+#[test]
+fn no_dangling_indices() -> Result<()> {
+    let file = read_test_file()?;
+    assert_eq!(
+        Vec::<String>::new(),
+        file.dangling_indices()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+#[test]
+fn dangling_indices() -> Result<()> {
+    let mut file = read_test_file()?;
+    let block = &mut file.file_blocks[0];
+    let name_rdata = block
+        .block_tables
+        .as_ref()
+        .and_then(|tables| tables.name_rdata.as_ref())
+        .map_or(0, Vec::len);
+    block
+        .query_responses
+        .as_mut()
+        .expect("Test file has Q/R items")[2]
+        .query_name_index = Some(name_rdata + 5);
+
+    let dangling = file.dangling_indices();
+    assert_eq!(1, dangling.len());
+    assert_eq!(
+        "file_blocks[0].query_responses[2].query_name_index",
+        dangling[0].path
+    );
+    assert_eq!(name_rdata + 5, dangling[0].index);
+    assert_eq!("block_tables.name_rdata", dangling[0].table);
+    assert_eq!(name_rdata, dangling[0].table_len);
+    Ok(())
+}
+
+#[test]
+fn prune_empty_blocks() -> Result<()> {
+    let mut file = read_test_file()?;
+    let mut empty = read_test_file()?.file_blocks.remove(0);
+    empty.query_responses = None;
+    empty.address_event_counts = Some(Vec::new());
+    assert!(empty.is_empty());
+    file.file_blocks.push(empty);
+
+    assert!(file
+        .lint()
+        .iter()
+        .any(|warning| warning.path == "file_blocks[1]"));
+    assert_eq!(1, file.prune_empty_blocks());
+    assert_eq!(1, file.block_count());
+    assert!(!file.file_blocks[0].is_empty());
+    assert_eq!(0, file.prune_empty_blocks());
+    Ok(())
+}
+
+#[test]
+fn validate_file() -> Result<()> {
+    let mut file = read_test_file()?;
+    assert_eq!(
+        Vec::<String>::new(),
+        file.validate()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    );
+
+    let storage_parameters = &mut file.file_preamble.block_parameters[0].storage_parameters;
+    storage_parameters.opcodes.clear();
+    storage_parameters.rr_types.clear();
+    let block = &mut file.file_blocks[0];
+    block.block_preamble.block_parameters_index = Some(3);
+    let tables = block.block_tables.as_mut().unwrap();
+    tables.rrlist = Some(vec![Default::default()]);
+    tables.malformed_message_data = Some(Vec::new());
+    assert!(block.validate().contains(&Violation::EmptyArray {
+        path: "block.block_tables.rrlist[0]".to_string()
+    }));
+
+    let violations = file.validate();
+    assert_eq!(
+        vec![
+            "file_blocks[0].block_preamble.block_parameters_index: index 3 is out of range for file_preamble.block_parameters with 1 entries",
+            "file_preamble.block_parameters[0].storage_parameters.opcodes: array must not be empty",
+            "file_preamble.block_parameters[0].storage_parameters.rr_types: array must not be empty",
+            "file_blocks[0].block_tables.malformed_message_data: array must not be empty",
+            "file_blocks[0].block_tables.rrlist[0]: array must not be empty",
+        ],
+        violations
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    );
+    assert!(matches!(violations[0], Violation::DanglingIndex(_)));
+    Ok(())
+}
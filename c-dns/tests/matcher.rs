@@ -0,0 +1,121 @@
+use c_dns::builder::{BlockBuilder, StorageParametersBuilder};
+use c_dns::matcher::{CapturedMessage, Matcher};
+use c_dns::serialization::{
+    CollectionParameters, DNSFlags, QueryResponseFlags, QueryResponseSignature, Timestamp,
+};
+use c_dns::wire::Message;
+use c_dns::Transport;
+use color_eyre::eyre::Result;
+
+const QUERY: &[u8] =
+    b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x00\x00\x01\x00\x01";
+const RESPONSE: &[u8] = b"\x12\x34\x81\x80\x00\x01\x00\x01\x00\x00\x00\x00\x07example\x00\x00\x01\x00\x01\xc0\x0c\x00\x01\x00\x01\x00\x00\x01\x2c\x00\x04\xc0\x00\x02\x01";
+
+/// A message captured `millis` after the start, with 1000 ticks per second.
+fn captured(millis: u32, client_port: u16, message: &[u8]) -> Result<CapturedMessage> {
+    Ok(CapturedMessage {
+        time: Timestamp {
+            timestamp_secs: 1_600_000_000 + (millis / 1000) as i32,
+            timestamp_ticks: (millis % 1000).into(),
+        },
+        client: format!("192.0.2.1:{}", client_port).parse()?,
+        server: "192.0.2.53:53".parse()?,
+        transport: Transport::Udp,
+        hoplimit: Some(64),
+        size: message.len() as u16,
+        message: Message::parse(message)?,
+    })
+}
+
+fn flags(signature: &QueryResponseSignature) -> enumset::EnumSet<QueryResponseFlags> {
+    signature.qr_sig_flags.unwrap()
+}
+
+fn matcher() -> Matcher {
+    let collection_parameters = CollectionParameters {
+        query_timeout: Some(1000),
+        skew_timeout: Some(10_000),
+        ..Default::default()
+    };
+    Matcher::new(1000, &collection_parameters)
+}
+
+#[test]
+fn match_query_and_response() -> Result<()> {
+    let mut matcher = matcher();
+    matcher.push(captured(0, 40000, QUERY)?);
+    matcher.push(captured(20, 40000, RESPONSE)?);
+    assert_eq!(0, matcher.pending());
+
+    let record = matcher.pop().unwrap();
+    assert_eq!(Some(20), record.response_delay.map(i32::from));
+    assert_eq!(Some(64), record.client_hoplimit);
+    assert_eq!(Some(RESPONSE.len() as u16), record.response_size);
+    let signature = record.signature.as_ref().unwrap();
+    assert_eq!(
+        QueryResponseFlags::HasQuery | QueryResponseFlags::HasResponse,
+        flags(&signature.signature)
+    );
+    let dns_flags = signature.signature.qr_dns_flags.unwrap();
+    assert!(dns_flags.contains(DNSFlags::QueryRd));
+    assert!(dns_flags.contains(DNSFlags::ResponseRa));
+    assert!(!dns_flags.contains(DNSFlags::QueryRa));
+    assert!(record.query_extended.is_none());
+    assert_eq!(1, record.response_extended.as_ref().unwrap().answers.len());
+
+    // The records can be stored in a block
+    let mut builder = BlockBuilder::new(&StorageParametersBuilder::new(1000, 5000).build()?);
+    builder.push(record);
+    let block = builder.build()?;
+    assert_eq!(1, block.query_responses.unwrap().len());
+    Ok(())
+}
+
+#[test]
+fn unmatched_after_timeout() -> Result<()> {
+    let mut matcher = matcher();
+    matcher.push(captured(0, 40000, QUERY)?);
+    // A Response from a different client port does not match
+    matcher.push(captured(500, 40001, RESPONSE)?);
+    assert!(matcher.pop().is_none());
+    assert_eq!(2, matcher.pending());
+
+    // The Response expires after the skew, the Query after the query timeout
+    matcher.advance_to(Timestamp {
+        timestamp_secs: 1_600_000_000,
+        timestamp_ticks: 600.into(),
+    });
+    let response = matcher.pop().unwrap();
+    assert_eq!(
+        enumset::EnumSet::from(QueryResponseFlags::HasResponse),
+        flags(&response.signature.unwrap().signature)
+    );
+    assert!(matcher.pop().is_none());
+
+    let records = matcher.finish();
+    assert_eq!(1, records.len());
+    assert_eq!(
+        enumset::EnumSet::from(QueryResponseFlags::HasQuery),
+        flags(&records[0].signature.as_ref().unwrap().signature)
+    );
+    assert_eq!(None, records[0].response_delay);
+    Ok(())
+}
+
+#[test]
+fn response_within_skew() -> Result<()> {
+    let mut matcher = matcher();
+    matcher.push(captured(100, 40000, RESPONSE)?);
+    matcher.push(captured(105, 40000, QUERY)?);
+    let record = matcher.pop().unwrap();
+    assert_eq!(Some(-5), record.response_delay.map(i32::from));
+    assert_eq!(Some(QUERY.len() as u16), record.query_size);
+
+    // Outside of the skew both messages are unmatched
+    matcher.push(captured(200, 40000, RESPONSE)?);
+    matcher.push(captured(220, 40000, QUERY)?);
+    assert!(matcher.pop().is_some());
+    assert!(matcher.pop().is_none());
+    assert_eq!(1, matcher.finish().len());
+    Ok(())
+}
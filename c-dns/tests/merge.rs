@@ -0,0 +1,124 @@
+use c_dns::serialization::File;
+use color_eyre::eyre::Result;
+
+fn read_test_file() -> Result<File> {
+    Ok(File::read_path("./tests/data/dns.cdns")?)
+}
+
+#[test]
+fn concat_identical_parameters() -> Result<()> {
+    let file = File::concat([read_test_file()?, read_test_file()?, read_test_file()?])?;
+    assert_eq!(3, file.block_count());
+    assert_eq!(36, file.query_response_count());
+    assert_eq!(1, file.file_preamble.block_parameters.len());
+    for block in &file.file_blocks {
+        assert_eq!(None, block.block_preamble.block_parameters_index);
+    }
+    assert!(file.dangling_indices().is_empty());
+    Ok(())
+}
+
+#[test]
+fn concat_different_parameters() -> Result<()> {
+    let mut second = read_test_file()?;
+    second.file_preamble.block_parameters[0]
+        .storage_parameters
+        .ticks_per_second = 1000.into();
+    second.file_preamble.minor_format_version = 1;
+
+    let file = File::concat([read_test_file()?, second, read_test_file()?])?;
+    assert_eq!(2, file.file_preamble.block_parameters.len());
+    let indices: Vec<_> = file
+        .file_blocks
+        .iter()
+        .map(|block| block.block_preamble.block_parameters_index)
+        .collect();
+    assert_eq!(vec![None, Some(1), None], indices);
+    assert_eq!(1, file.file_preamble.minor_format_version);
+    Ok(())
+}
+
+#[test]
+fn concat_incompatible_files() -> Result<()> {
+    assert!(File::concat(Vec::new()).is_err());
+
+    let mut second = read_test_file()?;
+    second.file_preamble.private_version = Some(42);
+    assert!(File::concat([read_test_file()?, second]).is_err());
+
+    let mut second = read_test_file()?;
+    second.file_blocks[0].block_preamble.block_parameters_index = Some(1);
+    assert!(File::concat([read_test_file()?, second]).is_err());
+    Ok(())
+}
+
+#[test]
+fn merge_blocks() -> Result<()> {
+    let file = read_test_file()?;
+    let block_parameters = &file.file_preamble.block_parameters[0];
+    let ticks_per_second = u32::from(block_parameters.storage_parameters.ticks_per_second);
+    let first = read_test_file()?.file_blocks.remove(0);
+    let mut second = read_test_file()?.file_blocks.remove(0);
+    let names = first
+        .block_tables
+        .as_ref()
+        .unwrap()
+        .name_rdata
+        .as_ref()
+        .unwrap()
+        .len();
+    // The second block starts one second earlier
+    let earliest_time = second.block_preamble.earliest_time.as_mut().unwrap();
+    earliest_time.timestamp_secs -= 1;
+    let expected_earliest_time = *earliest_time;
+
+    let merged = first.merge(second, block_parameters)?;
+    assert_eq!(
+        Some(expected_earliest_time),
+        merged.block_preamble.earliest_time
+    );
+    let query_responses = merged.query_responses.as_ref().unwrap();
+    assert_eq!(24, query_responses.len());
+    // The items of the first block moved by one second
+    let original = &file.file_blocks[0].query_responses.as_ref().unwrap()[0];
+    assert_eq!(
+        u32::from(original.time_offset.unwrap()) + ticks_per_second,
+        u32::from(query_responses[0].time_offset.unwrap())
+    );
+    assert_eq!(original.time_offset, query_responses[12].time_offset);
+    // Equal names are stored once
+    let block_tables = merged.block_tables.as_ref().unwrap();
+    assert_eq!(names, block_tables.name_rdata.as_ref().unwrap().len());
+    assert_eq!(
+        Some(24),
+        merged.block_statistics.as_ref().unwrap().qr_data_items
+    );
+    assert!(merged.verify_statistics().is_empty());
+    Ok(())
+}
+
+#[test]
+fn merge_files() -> Result<()> {
+    let file = File::merge([read_test_file()?, read_test_file()?, read_test_file()?])?;
+    assert_eq!(1, file.block_count());
+    assert_eq!(36, file.query_response_count());
+    assert!(file.dangling_indices().is_empty());
+
+    // Blocks are split at max_block_items
+    let limited = || -> Result<File> {
+        let mut file = read_test_file()?;
+        file.file_preamble.block_parameters[0]
+            .storage_parameters
+            .max_block_items = 30;
+        Ok(file)
+    };
+    let file = File::merge([limited()?, limited()?, limited()?])?;
+    assert_eq!(1, file.file_preamble.block_parameters.len());
+    assert_eq!(2, file.block_count());
+    assert_eq!(
+        Some(24),
+        file.file_blocks[0].query_responses.as_ref().map(Vec::len)
+    );
+    assert_eq!(36, file.query_response_count());
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use c_dns::name::DomainName;
+use c_dns::serialization::NameOrRdata;
+use color_eyre::eyre::Result;
+
+fn name(bytes: &'static [u8]) -> NameOrRdata {
+    NameOrRdata::from(bytes::Bytes::from_static(bytes))
+}
+
+#[test]
+fn decode_names() -> Result<()> {
+    let domain = name(b"\x07example\x03com\x00").to_domain_name()?;
+    assert_eq!("example.com.", domain.to_string());
+    assert_eq!(
+        vec![&b"example"[..], &b"com"[..]],
+        domain.labels().collect::<Vec<_>>()
+    );
+    assert!(!domain.is_root());
+
+    let root = name(b"\x00").to_domain_name()?;
+    assert!(root.is_root());
+    assert_eq!(DomainName::root(), root);
+    assert_eq!(".", root.to_string());
+    assert_eq!(0, root.labels().count());
+    Ok(())
+}
+
+#[test]
+fn escape_special_bytes() -> Result<()> {
+    let domain = name(b"\x06a b\\\"\xff\x01@\x00").to_domain_name()?;
+    assert_eq!(r#"a\032b\\\"\255.\@."#, domain.to_string());
+    Ok(())
+}
+
+#[test]
+fn reject_malformed_names() {
+    for bytes in [
+        &b""[..],
+        b"\x07example",
+        b"\x07exam",
+        b"\x07example\x00\x00",
+        b"\x40aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\x00",
+        b"\xc0\x0c",
+    ] {
+        assert!(DomainName::from_wire(bytes).is_err(), "{:?}", bytes);
+    }
+    assert!(DomainName::from_wire(&[1; 256]).is_err());
+}
+
+#[test]
+fn lossy_display() {
+    assert_eq!("example.", name(b"\x07example\x00").to_string_lossy());
+    assert_eq!(
+        "example.\\003co",
+        name(b"\x07example\x03co").to_string_lossy()
+    );
+    assert_eq!("", name(b"").to_string_lossy());
+    assert_eq!("\\192\\012", name(b"\xc0\x0c").to_string_lossy());
+}
@@ -0,0 +1,104 @@
+use c_dns::serialization::{File, NameOrRdata, StorageFlags};
+use color_eyre::eyre::Result;
+
+fn read_test_file() -> Result<File> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    Ok(serde_cbor::from_slice(&c_dns_content)?)
+}
+
+fn uppercase(name: &NameOrRdata) -> NameOrRdata {
+    NameOrRdata::from(bytes::Bytes::from(name.as_bytes().to_ascii_uppercase()))
+}
+
+#[test]
+fn lowercase_name() {
+    let name = NameOrRdata::from(bytes::Bytes::from_static(b"\x07ExAmPlE\x03CoM\x00"));
+    assert_eq!(
+        "example.com.",
+        name.to_lowercase().to_domain_name().unwrap().to_string()
+    );
+}
+
+#[test]
+fn normalize_merges_entries() -> Result<()> {
+    let mut file = read_test_file()?;
+    let block = &mut file.file_blocks[0];
+    let tables = block.block_tables.as_mut().unwrap();
+    let name_rdata = tables.name_rdata.as_mut().unwrap();
+    let entries = name_rdata.len();
+
+    // Point the first Q/R with a QNAME to an uppercase copy of its name
+    let query_response = block
+        .query_responses
+        .iter_mut()
+        .flatten()
+        .find(|qr| qr.query_name_index.is_some())
+        .unwrap();
+    let index = query_response.query_name_index.unwrap();
+    let name = name_rdata[index].clone();
+    name_rdata.push(uppercase(&name));
+    query_response.query_name_index = Some(entries);
+
+    assert_eq!(1, file.normalize_names());
+    let block = &file.file_blocks[0];
+    let name_rdata = block
+        .block_tables
+        .as_ref()
+        .unwrap()
+        .name_rdata
+        .as_ref()
+        .unwrap();
+    assert_eq!(entries, name_rdata.len());
+    for query_response in block.query_responses.iter().flatten() {
+        if let Some(index) = query_response.query_name_index {
+            let name = &name_rdata[index];
+            assert_eq!(&name.to_lowercase(), name);
+        }
+    }
+    assert!(file.file_preamble.block_parameters[0]
+        .storage_parameters
+        .storage_flags
+        .unwrap()
+        .contains(StorageFlags::NormalizedNames));
+    assert!(file.dangling_indices().is_empty());
+    Ok(())
+}
+
+#[test]
+fn normalize_keeps_rdata() -> Result<()> {
+    let mut file = read_test_file()?;
+    let block = &mut file.file_blocks[0];
+    let query_response = block
+        .query_responses
+        .iter_mut()
+        .flatten()
+        .find(|qr| qr.query_name_index.is_some() && qr.qr_signature_index.is_some())
+        .unwrap();
+    let index = query_response.query_name_index.unwrap();
+    let sig_index = query_response.qr_signature_index.unwrap();
+    let tables = block.block_tables.as_mut().unwrap();
+    // Use the uppercase QNAME also as RDATA of the OPT RR
+    tables.qr_sig.as_mut().unwrap()[sig_index].query_opt_rdata_index = Some(index);
+    let name_rdata = tables.name_rdata.as_mut().unwrap();
+    let rdata = uppercase(&name_rdata[index]);
+    name_rdata[index] = rdata.clone();
+
+    file.normalize_names();
+    let block = &file.file_blocks[0];
+    let tables = block.block_tables.as_ref().unwrap();
+    let name_rdata = tables.name_rdata.as_ref().unwrap();
+    let rdata_index = tables.qr_sig.as_ref().unwrap()[sig_index]
+        .query_opt_rdata_index
+        .unwrap();
+    assert_eq!(rdata, name_rdata[rdata_index]);
+    let query_name_index = block
+        .query_responses
+        .iter()
+        .flatten()
+        .find(|qr| qr.qr_signature_index == Some(sig_index))
+        .and_then(|qr| qr.query_name_index)
+        .unwrap();
+    assert_eq!(rdata.to_lowercase(), name_rdata[query_name_index]);
+    assert!(file.dangling_indices().is_empty());
+    Ok(())
+}
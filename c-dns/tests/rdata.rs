@@ -0,0 +1,84 @@
+use c_dns::rdata::Rdata;
+use c_dns::serialization::{DnsType, File, NameOrRdata};
+use color_eyre::eyre::Result;
+
+fn rdata(bytes: &'static [u8]) -> NameOrRdata {
+    NameOrRdata::from(bytes::Bytes::from_static(bytes))
+}
+
+#[test]
+fn parse_common_types() -> Result<()> {
+    assert_eq!(
+        Rdata::A("192.0.2.1".parse()?),
+        rdata(b"\xc0\x00\x02\x01").parse_rdata(DnsType::A)?
+    );
+    assert_eq!(
+        Rdata::Txt(vec![b"v=spf1".to_vec(), Vec::new()]),
+        rdata(b"\x06v=spf1\x00").parse_rdata(DnsType::TXT)?
+    );
+    match rdata(b"\x02ns\x00\x05admin\x00\x00\x00\x00\x01\x00\x00\x0e\x10\x00\x00\x03\x84\x00\x09\x3a\x80\x00\x00\x01\x2c")
+        .parse_rdata(DnsType::SOA)?
+    {
+        Rdata::Soa {
+            mname,
+            rname,
+            serial,
+            minimum,
+            ..
+        } => {
+            assert_eq!("ns.", mname.to_string());
+            assert_eq!("admin.", rname.to_string());
+            assert_eq!(1, serial);
+            assert_eq!(300, minimum);
+        }
+        other => panic!("Unexpected {:?}", other),
+    }
+    match rdata(b"\x00\x0a\x00\x04\x01\x02\x03\x04").parse_rdata(DnsType::OPT)? {
+        Rdata::Opt(options) => {
+            assert_eq!(1, options.len());
+            assert_eq!(10, options[0].code);
+            assert_eq!(4, options[0].data.len());
+        }
+        other => panic!("Unexpected {:?}", other),
+    }
+    assert_eq!(
+        Rdata::Unknown {
+            type_: DnsType::from(65280),
+            data: b"\x01\x02".to_vec(),
+        },
+        rdata(b"\x01\x02").parse_rdata(DnsType::from(65280))?
+    );
+    Ok(())
+}
+
+#[test]
+fn reject_malformed_rdata() {
+    assert!(rdata(b"\xc0\x00\x02").parse_rdata(DnsType::A).is_err());
+    assert!(rdata(b"\xc0\x00\x02\x01\x00")
+        .parse_rdata(DnsType::A)
+        .is_err());
+    assert!(rdata(b"\x07example").parse_rdata(DnsType::CNAME).is_err());
+    assert!(rdata(b"\x00\x0a\xc0\x0c").parse_rdata(DnsType::MX).is_err());
+    assert!(rdata(b"\x05abc").parse_rdata(DnsType::TXT).is_err());
+}
+
+#[test]
+fn parse_test_file() -> Result<()> {
+    let file = File::read_path("./tests/data/dns.cdns")?;
+    let mut parsed = 0;
+    for block in &file.file_blocks {
+        let tables = block.block_tables.as_ref().unwrap();
+        let name_rdata = tables.name_rdata.as_ref().unwrap();
+        for signature in tables.qr_sig.iter().flatten() {
+            if let Some(idx) = signature.query_opt_rdata_index {
+                assert!(matches!(
+                    name_rdata[idx].parse_rdata(DnsType::OPT)?,
+                    Rdata::Opt(_)
+                ));
+                parsed += 1;
+            }
+        }
+    }
+    assert!(parsed > 0);
+    Ok(())
+}
@@ -0,0 +1,661 @@
+use c_dns::serialization::File;
+use color_eyre::eyre::Result;
+
+#[test]
+fn no_trailing_data() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let (file, trailing) = File::from_slice_allow_trailing(&c_dns_content)?;
+    assert_eq!(1, file.file_blocks.len());
+    assert_eq!(None, trailing);
+    Ok(())
+}
+
+#[test]
+fn trailing_data() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let mut input = c_dns_content.clone();
+    input.extend_from_slice(b"junk");
+    let (_, trailing) = File::from_slice_allow_trailing(&input)?;
+    let trailing = trailing.expect("Input has trailing data");
+    assert_eq!(c_dns_content.len(), trailing.offset);
+    assert_eq!(b"junk", trailing.bytes);
+
+    // Concatenated files can be read one after another
+    let mut input = c_dns_content.clone();
+    input.extend_from_slice(&c_dns_content);
+    let (_, trailing) = File::from_slice_allow_trailing(&input)?;
+    let trailing = trailing.expect("Input has trailing data");
+    assert_eq!(c_dns_content.len(), trailing.len());
+    let (_, trailing) = File::from_slice_allow_trailing(trailing.bytes)?;
+    assert_eq!(None, trailing);
+    Ok(())
+}
+
+#[test]
+fn salvage_complete_file() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let (file, truncation) = File::from_slice_salvage(&c_dns_content)?;
+    assert_eq!(1, file.file_blocks.len());
+    assert!(truncation.is_none());
+    Ok(())
+}
+
+#[test]
+fn salvage_truncated_file() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let complete: File = serde_cbor::from_slice(&c_dns_content)?;
+
+    // Two blocks, with the second one cut off in the middle
+    let mut blocks = serde_cbor::to_vec(&complete.file_blocks[0])?;
+    let block_len = blocks.len();
+    blocks.extend_from_slice(&blocks.clone()[..block_len / 2]);
+    let mut input = serde_cbor::to_vec(&(&complete.file_type_id, &complete.file_preamble))?;
+    input[0] = 0x83;
+    let blocks_start = input.len() + 1;
+    input.push(0x82);
+    input.extend_from_slice(&blocks);
+
+    let (file, truncation) = File::from_slice_salvage(&input)?;
+    assert_eq!(1, file.file_blocks.len());
+    let truncation = truncation.expect("File is truncated");
+    assert_eq!(1, truncation.block_index);
+    assert_eq!(blocks_start + block_len, truncation.offset);
+    assert!(truncation.error.is_eof());
+
+    // Without the preamble there is nothing to salvage
+    assert!(File::from_slice_salvage(&input[..10]).is_err());
+    Ok(())
+}
+
+#[test]
+fn intern_names_and_addresses() -> Result<()> {
+    use c_dns::intern::Interner;
+
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let original: File = serde_cbor::from_slice(&c_dns_content)?;
+    let mut first: File = serde_cbor::from_slice(&c_dns_content)?;
+    let mut second: File = serde_cbor::from_slice(&c_dns_content)?;
+
+    let mut interner = Interner::new();
+    interner.intern_file(&mut first);
+    let distinct = interner.len();
+    interner.intern_file(&mut second);
+    assert_eq!(distinct, interner.len());
+
+    // Interning changes no values, but shares the buffers between both files
+    assert_eq!(serde_cbor::to_vec(&original)?, serde_cbor::to_vec(&second)?);
+    let names = |file: &File| {
+        file.file_blocks[0]
+            .block_tables
+            .as_ref()
+            .and_then(|tables| tables.name_rdata.clone())
+            .expect("Test file has names")
+    };
+    for (a, b) in names(&first).iter().zip(&names(&second)) {
+        assert_eq!(a.as_bytes().as_ptr(), b.as_bytes().as_ptr());
+    }
+
+    // The FileReader interns each block while reading
+    let mut file: File = serde_cbor::from_slice(&c_dns_content)?;
+    file.file_blocks.push(file.file_blocks[0].clone());
+    let bytes = serde_cbor::to_vec(&file)?;
+    let mut reader = c_dns::serialization::FileReader::new(&bytes[..])?;
+    reader.set_interner(Some(Interner::new()));
+    let blocks = reader.by_ref().collect::<c_dns::Result<Vec<_>>>()?;
+    assert_eq!(distinct, reader.interner().unwrap().len());
+    let names = |block: &c_dns::serialization::Block| {
+        block
+            .block_tables
+            .as_ref()
+            .and_then(|tables| tables.name_rdata.clone())
+            .expect("Test file has names")
+    };
+    for (a, b) in names(&blocks[0]).iter().zip(&names(&blocks[1])) {
+        assert_eq!(a.as_bytes().as_ptr(), b.as_bytes().as_ptr());
+    }
+    Ok(())
+}
+
+#[test]
+fn lazy_block_decoding() -> Result<()> {
+    use c_dns::lazy::LazyFile;
+
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let eager: File = serde_cbor::from_slice(&c_dns_content)?;
+    let lazy = LazyFile::from_slice(&c_dns_content)?;
+
+    assert_eq!(eager.file_blocks.len(), lazy.blocks().len());
+    let block = &lazy.blocks()[0];
+    assert!(!block.is_decoded());
+    assert_eq!(
+        serde_cbor::to_vec(&eager.file_blocks[0].block_preamble)?,
+        serde_cbor::to_vec(block.preamble())?
+    );
+    assert!(lazy.block_parameters(block).is_some());
+
+    assert_eq!(
+        serde_cbor::to_vec(&eager.file_blocks[0])?,
+        serde_cbor::to_vec(block.block()?)?
+    );
+    assert!(block.is_decoded());
+
+    assert_eq!(
+        serde_cbor::to_vec(&eager)?,
+        serde_cbor::to_vec(&lazy.into_file()?)?
+    );
+    Ok(())
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel_deserialization() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let sequential: File = serde_cbor::from_slice(&c_dns_content)?;
+    let parallel = File::from_slice_parallel(&c_dns_content)?;
+    assert_eq!(
+        serde_cbor::to_vec(&sequential)?,
+        serde_cbor::to_vec(&parallel)?
+    );
+
+    let mut input = c_dns_content.clone();
+    input.extend_from_slice(b"junk");
+    assert!(File::from_slice_parallel(&input).is_err());
+
+    // Errors contain the path like with from_slice
+    let mut file = serde_cbor::value::to_value(&sequential)?;
+    if let serde_cbor::Value::Array(file) = &mut file {
+        if let serde_cbor::Value::Array(blocks) = &mut file[2] {
+            if let serde_cbor::Value::Map(block) = &mut blocks[0] {
+                block.insert(
+                    serde_cbor::Value::Integer(3),
+                    serde_cbor::Value::Text("not a list".into()),
+                );
+            }
+        }
+    }
+    let file = serde_cbor::to_vec(&file)?;
+    assert_eq!(
+        File::from_slice(&file).unwrap_err().to_string(),
+        File::from_slice_parallel(&file).unwrap_err().to_string()
+    );
+    Ok(())
+}
+
+#[test]
+fn fast_deserialization() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let serde: File = serde_cbor::from_slice(&c_dns_content)?;
+    let fast = File::from_slice_fast(&c_dns_content)?;
+    assert_eq!(serde_cbor::to_vec(&serde)?, serde_cbor::to_vec(&fast)?);
+
+    // Extra values are kept and unknown bits in the flags are dropped, just like with serde
+    let mut file = serde;
+    let block_tables = file.file_blocks[0]
+        .block_tables
+        .as_mut()
+        .expect("Test file has block tables");
+    block_tables
+        .extra_values
+        .insert(-1, serde_cbor::Value::Bool(true));
+    let qr_sig = &mut block_tables
+        .qr_sig
+        .as_mut()
+        .expect("Test file has signatures")[0];
+    qr_sig
+        .extra_values
+        .insert(-3, serde_cbor::Value::Text("custom".into()));
+    let mut modified = serde_cbor::value::to_value(&file)?;
+    if let serde_cbor::Value::Array(file) = &mut modified {
+        if let serde_cbor::Value::Array(blocks) = &mut file[2] {
+            if let serde_cbor::Value::Map(block) = &mut blocks[0] {
+                if let Some(serde_cbor::Value::Map(block_tables)) =
+                    block.get_mut(&serde_cbor::Value::Integer(2))
+                {
+                    if let Some(serde_cbor::Value::Array(qr_sig)) =
+                        block_tables.get_mut(&serde_cbor::Value::Integer(3))
+                    {
+                        if let serde_cbor::Value::Map(qr_sig) = &mut qr_sig[0] {
+                            qr_sig.insert(
+                                serde_cbor::Value::Integer(4),
+                                serde_cbor::Value::Integer(0xff),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let modified = serde_cbor::to_vec(&modified)?;
+    let serde: File = serde_cbor::from_slice(&modified)?;
+    let fast = File::from_slice_fast(&modified)?;
+    assert_eq!(serde_cbor::to_vec(&serde)?, serde_cbor::to_vec(&fast)?);
+    assert_eq!(
+        1,
+        fast.file_blocks[0]
+            .block_tables
+            .as_ref()
+            .unwrap()
+            .extra_values
+            .len()
+    );
+    Ok(())
+}
+
+#[test]
+fn fast_deserialization_errors() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let mut file = serde_cbor::value::to_value(&serde_cbor::from_slice::<File>(&c_dns_content)?)?;
+    // Values of the wrong type are an error in both decoders
+    if let serde_cbor::Value::Array(file) = &mut file {
+        if let serde_cbor::Value::Array(blocks) = &mut file[2] {
+            if let serde_cbor::Value::Map(block) = &mut blocks[0] {
+                block.insert(
+                    serde_cbor::Value::Integer(3),
+                    serde_cbor::Value::Text("not a list".into()),
+                );
+            }
+        }
+    }
+    let file = serde_cbor::to_vec(&file)?;
+    assert!(serde_cbor::from_slice::<File>(&file).is_err());
+    // The error contains the same path as the error of from_slice
+    let error = File::from_slice_fast(&file).unwrap_err();
+    assert_eq!(
+        File::from_slice(&file).unwrap_err().to_string(),
+        error.to_string()
+    );
+    assert!(error
+        .to_string()
+        .starts_with("Failed to deserialize [2][0]"));
+
+    // Trailing bytes are an error, like with from_slice
+    let mut input = c_dns_content.clone();
+    input.extend_from_slice(b"junk");
+    assert!(File::from_slice(&input).is_err());
+    let error = File::from_slice_fast(&input).unwrap_err();
+    assert_eq!(
+        format!("Trailing data at offset {}", c_dns_content.len()),
+        error.to_string()
+    );
+    Ok(())
+}
+
+#[test]
+fn lazy_memory_budget() -> Result<()> {
+    use c_dns::lazy::LazyFile;
+
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let block = serde_cbor::from_slice::<File>(&c_dns_content)?
+        .file_blocks
+        .remove(0);
+    let block_size = block.estimated_size();
+    assert!(block_size > std::mem::size_of_val(&block));
+
+    // Three copies of the same block
+    let mut file: File = serde_cbor::from_slice(&c_dns_content)?;
+    let block = serde_cbor::to_vec(&file.file_blocks[0])?;
+    file.file_blocks.push(serde_cbor::from_slice(&block)?);
+    file.file_blocks.push(serde_cbor::from_slice(&block)?);
+    let bytes = serde_cbor::to_vec(&file)?;
+
+    let mut lazy = LazyFile::from_slice(&bytes)?;
+    assert_eq!(0, lazy.retained_bytes());
+    lazy.blocks()[0].block()?;
+    assert_eq!(block_size, lazy.retained_bytes());
+
+    // Without a budget all blocks are kept
+    lazy.decode(1)?;
+    assert_eq!(2 * block_size, lazy.retained_bytes());
+
+    lazy.set_budget(Some(block_size * 3 / 2));
+    lazy.decode(2)?;
+    assert_eq!(block_size, lazy.retained_bytes());
+    assert!(!lazy.blocks()[0].is_decoded());
+    assert!(!lazy.blocks()[1].is_decoded());
+    assert!(lazy.blocks()[2].is_decoded());
+
+    // A budget smaller than a single block keeps the requested one
+    lazy.set_budget(Some(0));
+    lazy.decode(0)?;
+    assert!(lazy.blocks()[0].is_decoded());
+    assert!(!lazy.blocks()[2].is_decoded());
+
+    assert_eq!(block_size, lazy.release_all());
+    assert_eq!(0, lazy.retained_bytes());
+    Ok(())
+}
+
+#[test]
+fn stream_memory_budget() -> Result<()> {
+    use c_dns::intern::Interner;
+    use c_dns::serialization::FileReader;
+
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let mut file: File = serde_cbor::from_slice(&c_dns_content)?;
+    file.file_blocks.push(file.file_blocks[0].clone());
+    let bytes = serde_cbor::to_vec(&file)?;
+
+    // Without a budget the block buffer and the interned strings are kept
+    let mut reader = FileReader::new(&bytes[..])?;
+    reader.set_interner(Some(Interner::new()));
+    reader.next_block()?;
+    let retained = reader.retained_bytes();
+    let interned = reader.interner().unwrap().retained_bytes();
+    assert!(interned > 0);
+    assert!(retained > interned);
+    reader.next_block()?;
+    assert_eq!(retained, reader.retained_bytes());
+    assert_eq!(retained, reader.release());
+    assert_eq!(0, reader.retained_bytes());
+
+    // With a budget the reader releases its memory after each block
+    let mut reader = FileReader::new(&bytes[..])?;
+    reader.set_interner(Some(Interner::new()));
+    reader.set_budget(Some(retained / 2));
+    let first = reader.next_block()?.unwrap();
+    assert_eq!(0, reader.retained_bytes());
+    assert!(reader.interner().unwrap().is_empty());
+    let second = reader.next_block()?.unwrap();
+    assert_eq!(serde_cbor::to_vec(&first)?, serde_cbor::to_vec(&second)?);
+    assert!(reader.next_block()?.is_none());
+    Ok(())
+}
+
+#[test]
+fn check_file_header() -> Result<()> {
+    use c_dns::serialization::UncheckedFile;
+
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let file: File = serde_cbor::from_slice(&c_dns_content)?;
+    file.check_header()?;
+
+    let mut experimental = serde_cbor::from_slice::<UncheckedFile>(&c_dns_content)?;
+    experimental.file_type_id = "C-DNS-EXPERIMENTAL".to_string();
+    let bytes = serde_cbor::to_vec(&experimental)?;
+    let error = serde_cbor::from_slice::<File>(&bytes).unwrap_err();
+    assert!(
+        error.to_string().contains("C-DNS-EXPERIMENTAL"),
+        "{}",
+        error
+    );
+    assert!(File::from_slice_salvage(&bytes).is_err());
+    assert!(c_dns::lazy::LazyFile::from_slice(&bytes).is_err());
+    let file = serde_cbor::from_slice::<UncheckedFile>(&bytes)?.into_file();
+    assert_eq!("C-DNS-EXPERIMENTAL", file.file_type_id);
+    assert!(matches!(
+        file.check_header(),
+        Err(c_dns::Error::InvalidFileType(id)) if id == "C-DNS-EXPERIMENTAL"
+    ));
+
+    let mut experimental = serde_cbor::from_slice::<UncheckedFile>(&c_dns_content)?;
+    experimental.file_preamble.major_format_version = 2;
+    let bytes = serde_cbor::to_vec(&experimental)?;
+    let error = serde_cbor::from_slice::<File>(&bytes).unwrap_err();
+    assert!(error.to_string().contains("2.0"), "{}", error);
+    assert!(serde_cbor::from_slice::<UncheckedFile>(&bytes).is_ok());
+    Ok(())
+}
+
+#[test]
+fn newer_minor_version() -> Result<()> {
+    use c_dns::serialization::UncheckedFile;
+
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let file: File = serde_cbor::from_slice(&c_dns_content)?;
+    assert_eq!((1, 0), file.file_preamble.format_version());
+    assert!(!file.file_preamble.has_newer_minor_version());
+
+    // A file of version 1.1 with a new field in the Block and the signatures
+    let mut newer = serde_cbor::from_slice::<UncheckedFile>(&c_dns_content)?;
+    newer.file_preamble.minor_format_version = 1;
+    let block = &mut newer.file_blocks[0];
+    block
+        .extra_values
+        .insert(6, serde_cbor::Value::Text("new field".into()));
+    let signatures = block
+        .block_tables
+        .as_mut()
+        .and_then(|tables| tables.qr_sig.as_mut())
+        .expect("Test file has signatures");
+    let signature_count = signatures.len();
+    for sig in signatures {
+        sig.extra_values.insert(17, serde_cbor::Value::Integer(42));
+    }
+    let bytes = serde_cbor::to_vec(&newer)?;
+
+    for file in [
+        serde_cbor::from_slice::<File>(&bytes)?,
+        File::from_slice_fast(&bytes)?,
+        c_dns::lazy::LazyFile::from_slice(&bytes)?.into_file()?,
+    ] {
+        assert!(file.file_preamble.has_newer_minor_version());
+        let block = &file.file_blocks[0];
+        assert_eq!(
+            Some(&serde_cbor::Value::Text("new field".into())),
+            block.extra_values.get(6)
+        );
+        let sig = &block
+            .block_tables
+            .as_ref()
+            .unwrap()
+            .qr_sig
+            .as_ref()
+            .unwrap()[0];
+        assert_eq!(
+            Some(&serde_cbor::Value::Integer(42)),
+            sig.extra_values.get(17)
+        );
+        assert!(file.lint().is_empty());
+        // The unknown fields are kept losslessly
+        assert_eq!(bytes, serde_cbor::to_vec(&file)?);
+    }
+
+    // The same fields in a file of the supported version are reported
+    newer.file_preamble.minor_format_version = 0;
+    let file: File = serde_cbor::from_slice(&serde_cbor::to_vec(&newer)?)?;
+    let warnings = file.lint();
+    assert_eq!(1 + signature_count, warnings.len(), "{:#?}", warnings);
+    assert_eq!(
+        "file_blocks[0]: unknown field 6 in format version 1.0",
+        warnings[0].to_string()
+    );
+
+    // Other major versions are still rejected
+    newer.file_preamble.major_format_version = 2;
+    assert!(serde_cbor::from_slice::<File>(&serde_cbor::to_vec(&newer)?).is_err());
+    Ok(())
+}
+
+#[test]
+fn metadata_accessors() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let file: File = serde_cbor::from_slice(&c_dns_content)?;
+    assert_eq!(Some("dns-stats-compactor 1.2.0"), file.generator_id());
+    assert_eq!(None, file.host_id());
+    assert_eq!(None, file.collection_filter());
+    assert_eq!(1, file.block_count());
+    assert_eq!(12, file.query_response_count());
+    Ok(())
+}
+
+#[test]
+fn read_entry_points() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let expected = serde_cbor::to_vec(&File::from_slice(&c_dns_content)?)?;
+    let file = File::read_path("./tests/data/dns.cdns")?;
+    assert_eq!(expected, file.to_vec()?);
+    let file = File::from_reader(&*c_dns_content)?;
+    assert_eq!(expected, file.to_vec()?);
+
+    let mut input = c_dns_content.clone();
+    input.push(0);
+    assert!(File::from_slice(&input).is_err());
+    assert!(File::from_reader(&*input).is_err());
+
+    let error = File::read_path("./tests/data/missing.cdns").unwrap_err();
+    assert!(format!("{}", error).contains("missing.cdns"));
+    Ok(())
+}
+
+#[test]
+fn read_error_path() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let mut file = serde_cbor::value::to_value(File::from_slice(&c_dns_content)?)?;
+    if let serde_cbor::Value::Array(file) = &mut file {
+        if let serde_cbor::Value::Array(blocks) = &mut file[2] {
+            if let serde_cbor::Value::Map(block) = &mut blocks[0] {
+                block.insert(
+                    serde_cbor::Value::Integer(3),
+                    serde_cbor::Value::Text("not a list".into()),
+                );
+            }
+        }
+    }
+    // The File and Blocks are arrays and integer keyed maps, so only the indices are known
+    let error = File::from_slice(&serde_cbor::to_vec(&file)?).unwrap_err();
+    assert_eq!("Failed to deserialize [2][0].?", error.to_string());
+    Ok(())
+}
+
+#[test]
+fn stream_blocks() -> Result<()> {
+    use c_dns::serialization::FileReader;
+
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let file = File::from_slice(&c_dns_content)?;
+    let mut reader = FileReader::new(&c_dns_content[..])?;
+    assert_eq!("C-DNS", reader.file_type_id());
+    let blocks = reader.by_ref().collect::<c_dns::Result<Vec<_>>>()?;
+    assert_eq!(file.file_blocks.len(), blocks.len());
+    for (expected, block) in file.file_blocks.iter().zip(&blocks) {
+        assert_eq!(serde_cbor::to_vec(expected)?, serde_cbor::to_vec(block)?);
+    }
+    assert!(reader.next().is_none());
+
+    // Indefinite-length arrays of blocks
+    let mut input = vec![0x83];
+    input.extend(serde_cbor::to_vec(&file.file_type_id)?);
+    input.extend(serde_cbor::to_vec(&file.file_preamble)?);
+    input.push(0x9f);
+    for _ in 0..3 {
+        input.extend(serde_cbor::to_vec(&file.file_blocks[0])?);
+    }
+    input.push(0xff);
+    let reader = FileReader::new(&input[..])?;
+    assert_eq!(3, reader.collect::<c_dns::Result<Vec<_>>>()?.len());
+
+    // The File array must have exactly 3 elements
+    for len in [0x82, 0x84] {
+        input[0] = len;
+        assert!(FileReader::new(&input[..]).is_err());
+        assert!(File::from_slice_salvage(&input).is_err());
+    }
+
+    // A truncated block ends the iteration with an error
+    let mut reader = FileReader::new(&c_dns_content[..c_dns_content.len() - 10])?;
+    assert!(reader.next().unwrap().is_err());
+    assert!(reader.next().is_none());
+
+    // Invalid blocks report the path of the failing value, like from_slice
+    let mut block = serde_cbor::value::to_value(&file.file_blocks[0])?;
+    if let serde_cbor::Value::Map(block) = &mut block {
+        block.insert(
+            serde_cbor::Value::Integer(3),
+            serde_cbor::Value::Text("not a list".into()),
+        );
+    }
+    let mut input = vec![0x83];
+    input.extend(serde_cbor::to_vec(&file.file_type_id)?);
+    input.extend(serde_cbor::to_vec(&file.file_preamble)?);
+    input.push(0x81);
+    input.extend(serde_cbor::to_vec(&block)?);
+    let error = FileReader::new(&input[..])?.next().unwrap().unwrap_err();
+    assert_eq!(
+        File::from_slice(&input).unwrap_err().to_string(),
+        error.to_string()
+    );
+    Ok(())
+}
+
+#[test]
+fn structured_errors() -> Result<()> {
+    use c_dns::serialization::IpAddr;
+    use c_dns::{Error, IpVersion};
+
+    let address = IpAddr::from(bytes::Bytes::from_static(&[192, 0, 2, 1, 0]));
+    assert!(matches!(
+        address.as_ipv4(),
+        Err(Error::AddressTooLong {
+            ip_version: IpVersion::Ipv4,
+            len: 5
+        })
+    ));
+    assert!(address.as_ipv6().is_ok());
+    assert!(matches!(
+        IpAddr::from(bytes::Bytes::new()).as_ipv6(),
+        Err(Error::EmptyAddress(IpVersion::Ipv6))
+    ));
+
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let mut file = File::from_slice(&c_dns_content)?;
+    file.file_preamble.major_format_version = 2;
+    assert!(matches!(
+        file.check_header(),
+        Err(Error::UnsupportedVersion { major: 2, minor: 0 })
+    ));
+    Ok(())
+}
+
+#[test]
+fn zero_copy_deserialization() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let bytes = bytes::Bytes::from(c_dns_content.clone());
+    let file = File::from_bytes(bytes.clone())?;
+    assert_eq!(
+        serde_cbor::to_vec(&File::from_slice(&c_dns_content)?)?,
+        serde_cbor::to_vec(&file)?
+    );
+
+    // All addresses and names point into the input
+    let input = bytes.as_ptr_range();
+    let block_tables = file.file_blocks[0].block_tables.as_ref().unwrap();
+    let addresses = block_tables
+        .ip_address
+        .iter()
+        .flatten()
+        .map(|ip| ip.as_bytes());
+    let names = block_tables
+        .name_rdata
+        .iter()
+        .flatten()
+        .map(|name| name.as_bytes());
+    let mut count = 0;
+    for value in addresses.chain(names).filter(|value| !value.is_empty()) {
+        assert!(input.contains(&value.as_ptr()));
+        count += 1;
+    }
+    assert!(count > 0);
+
+    // Like File::from_slice, trailing bytes are rejected
+    let mut trailing = c_dns_content.clone();
+    trailing.push(0);
+    let error = File::from_bytes(trailing.into()).unwrap_err();
+    assert_eq!(
+        format!("Trailing data at offset {}", c_dns_content.len()),
+        error.to_string()
+    );
+
+    // Outside of File::from_bytes the bytes are copied
+    let file = File::from_slice_fast(&c_dns_content)?;
+    let name = &file.file_blocks[0]
+        .block_tables
+        .as_ref()
+        .unwrap()
+        .name_rdata
+        .as_ref()
+        .unwrap()[0];
+    assert!(!c_dns_content
+        .as_ptr_range()
+        .contains(&name.as_bytes().as_ptr()));
+    Ok(())
+}
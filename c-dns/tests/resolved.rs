@@ -0,0 +1,198 @@
+use c_dns::resolved::ResolvedQueryResponse;
+use c_dns::serialization::{Block, File, FileReader};
+use c_dns::tables::{IndexBase, IndexRemapping};
+use color_eyre::eyre::Result;
+
+fn read_test_file() -> Result<File> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    Ok(serde_cbor::from_slice(&c_dns_content)?)
+}
+
+#[test]
+fn resolve_all_query_responses() -> Result<()> {
+    let file = read_test_file()?;
+    let mut count = 0;
+    for (block, block_parameters) in file.iter_blocks() {
+        let block_tables = block.block_tables.as_ref().unwrap();
+        for query_response in block.iter_query_responses(block_parameters).resolved() {
+            let query_response = query_response?;
+            let raw = query_response.query_response;
+            assert_eq!(
+                raw.query_name_index
+                    .map(|idx| &block_tables.name_rdata.as_ref().unwrap()[idx]),
+                query_response.query_name,
+            );
+            assert_eq!(
+                raw.client_address_index.is_some(),
+                query_response.client_address.is_some()
+            );
+            assert!(query_response.time.is_some());
+            count += 1;
+        }
+    }
+    assert_eq!(file.query_response_count(), count);
+    Ok(())
+}
+
+#[test]
+fn questions_start_with_first_question() -> Result<()> {
+    let file = read_test_file()?;
+    let block = &file.file_blocks[0];
+    let block_parameters = &file.file_preamble.block_parameters[0];
+    let query_response = block
+        .iter_query_responses(block_parameters)
+        .resolved()
+        .find_map(|qr| qr.ok().filter(|qr| qr.query_name.is_some()))
+        .unwrap();
+    let first = query_response.questions().next().unwrap();
+    assert_eq!(query_response.query_name, Some(first.name));
+    assert_eq!(query_response.query_classtype, Some(first.classtype));
+    assert!(query_response.query_name_string().unwrap().ends_with('.'));
+    Ok(())
+}
+
+#[test]
+fn dangling_index_is_an_error() -> Result<()> {
+    let mut file = read_test_file()?;
+    let block = &mut file.file_blocks[0];
+    let query_response = &mut block.query_responses.as_mut().unwrap()[0];
+    query_response.query_name_index = Some(usize::MAX);
+
+    let block_tables = block.block_tables.as_ref().unwrap();
+    let query_response = &block.query_responses.as_ref().unwrap()[0];
+    assert!(ResolvedQueryResponse::new(
+        query_response,
+        block.block_preamble.earliest_time,
+        &file.file_preamble.block_parameters[0],
+        block_tables,
+    )
+    .is_err());
+    Ok(())
+}
+
+/// Rewrite all indices of `block` to start at 1.
+fn shift_to_one_based(block: &mut Block) {
+    let tables = block.block_tables.as_mut().unwrap();
+    let shift = |len: usize| (1..=len).collect::<Vec<_>>();
+    let remapping = IndexRemapping {
+        ip_address: shift(tables.ip_address.as_ref().map_or(0, Vec::len)),
+        classtype: shift(tables.classtype.as_ref().map_or(0, Vec::len)),
+        name_rdata: shift(tables.name_rdata.as_ref().map_or(0, Vec::len)),
+        qr_sig: shift(tables.qr_sig.as_ref().map_or(0, Vec::len)),
+        ..Default::default()
+    };
+    for sig in tables.qr_sig.iter_mut().flatten() {
+        for idx in [
+            &mut sig.server_address_index,
+            &mut sig.query_classtype_index,
+            &mut sig.query_opt_rdata_index,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *idx += 1;
+        }
+    }
+    for query_response in block.query_responses.iter_mut().flatten() {
+        remapping.remap_query_response(query_response);
+    }
+}
+
+#[test]
+fn one_based_indices() -> Result<()> {
+    let file = read_test_file()?;
+    let (block, block_parameters) = file.iter_blocks().next().unwrap();
+    assert_eq!(IndexBase::Zero, IndexBase::detect(block));
+
+    let mut shifted = read_test_file()?;
+    shift_to_one_based(&mut shifted.file_blocks[0]);
+    let shifted_block = &shifted.file_blocks[0];
+    assert_eq!(IndexBase::One, IndexBase::detect(shifted_block));
+
+    let expected = block
+        .iter_query_responses(block_parameters)
+        .resolved()
+        .collect::<c_dns::Result<Vec<_>>>()?;
+    let resolved = shifted_block
+        .iter_query_responses(block_parameters)
+        .resolved()
+        .with_index_base(IndexBase::One)
+        .collect::<c_dns::Result<Vec<_>>>()?;
+    assert_eq!(expected.len(), resolved.len());
+    for (expected, resolved) in expected.iter().zip(&resolved) {
+        assert_eq!(expected.client_address, resolved.client_address);
+        assert_eq!(expected.server_address, resolved.server_address);
+        assert_eq!(expected.query_name, resolved.query_name);
+        assert_eq!(expected.query_classtype, resolved.query_classtype);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel_resolution() -> Result<()> {
+    use rayon::prelude::*;
+
+    let file = read_test_file()?;
+    let sequential = file
+        .iter_blocks()
+        .flat_map(|(block, block_parameters)| {
+            block.iter_query_responses(block_parameters).resolved()
+        })
+        .collect::<c_dns::Result<Vec<_>>>()?;
+    let parallel = file
+        .par_iter_query_responses()
+        .collect::<c_dns::Result<Vec<_>>>()?;
+    assert_eq!(sequential.len(), parallel.len());
+    for (sequential, parallel) in sequential.iter().zip(&parallel) {
+        assert!(std::ptr::eq(
+            sequential.query_response,
+            parallel.query_response
+        ));
+        assert_eq!(sequential.client_address, parallel.client_address);
+        assert_eq!(sequential.time, parallel.time);
+    }
+    Ok(())
+}
+
+#[test]
+fn normalize_one_based_indices() -> Result<()> {
+    let file = read_test_file()?;
+    let expected = serde_cbor::to_vec(&file.file_blocks)?;
+    let mut shifted = read_test_file()?;
+    for block in &mut shifted.file_blocks {
+        shift_to_one_based(block);
+    }
+    let stored = serde_cbor::to_vec(&shifted.file_blocks)?;
+    let bytes = serde_cbor::to_vec(&shifted)?;
+    assert_ne!(expected, stored);
+
+    // Reading keeps the indices as stored
+    let readers: [(&str, Vec<Block>); 5] = [
+        ("serde", serde_cbor::from_slice::<File>(&bytes)?.file_blocks),
+        ("from_slice", File::from_slice(&bytes)?.file_blocks),
+        (
+            "from_slice_fast",
+            File::from_slice_fast(&bytes)?.file_blocks,
+        ),
+        (
+            "from_slice_salvage",
+            File::from_slice_salvage(&bytes)?.0.file_blocks,
+        ),
+        (
+            "FileReader",
+            FileReader::new(&*bytes)?.collect::<c_dns::Result<_>>()?,
+        ),
+    ];
+    for (reader, blocks) in readers {
+        assert_eq!(stored, serde_cbor::to_vec(&blocks)?, "{}", reader);
+    }
+
+    let mut file = File::from_slice(&bytes)?;
+    assert!(!file.dangling_indices().is_empty());
+    assert_eq!(1, file.normalize_index_base());
+    assert_eq!(expected, serde_cbor::to_vec(&file.file_blocks)?);
+    assert!(file.dangling_indices().is_empty());
+    assert_eq!(0, file.normalize_index_base());
+    Ok(())
+}
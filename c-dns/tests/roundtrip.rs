@@ -0,0 +1,36 @@
+use c_dns::serialization::File;
+use color_eyre::eyre::Result;
+use pretty_assertions::assert_eq;
+use serde_cbor::Value;
+
+#[test]
+fn lossless_roundtrip_check() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    assert_eq!(None, File::check_slice_roundtrip(&c_dns_content)?);
+    assert_eq!(
+        None,
+        File::from_slice(&c_dns_content)?.check_lossless_roundtrip()?
+    );
+
+    // Unknown storage flags do not fit into the EnumSet and are lost
+    let mut file: Value = serde_cbor::from_slice(&c_dns_content)?;
+    if let Value::Array(file) = &mut file {
+        if let Value::Map(preamble) = &mut file[1] {
+            if let Some(Value::Array(block_parameters)) = preamble.get_mut(&Value::Integer(3)) {
+                if let Value::Map(block_parameters) = &mut block_parameters[0] {
+                    if let Some(Value::Map(storage_parameters)) =
+                        block_parameters.get_mut(&Value::Integer(0))
+                    {
+                        storage_parameters.insert(Value::Integer(5), Value::Integer(0x81));
+                    }
+                }
+            }
+        }
+    }
+    let difference = File::check_slice_roundtrip(&serde_cbor::to_vec(&file)?)?
+        .expect("Unknown flags are not preserved");
+    assert_eq!("[1].3[0].0.5", difference.path);
+    assert_eq!(Some(Value::Integer(0x81)), difference.before);
+    assert_eq!(Some(Value::Integer(0x01)), difference.after);
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use c_dns::builder::{BlockBuilder, QueryResponseRecord, StorageParametersBuilder};
+use c_dns::sampling::{Sampler, Sampling};
+use c_dns::serialization::{File, StorageFlags, Timestamp};
+use color_eyre::eyre::Result;
+use std::collections::HashSet;
+
+fn read_test_file() -> Result<File> {
+    Ok(File::read_path("./tests/data/dns.cdns")?)
+}
+
+#[test]
+fn every_nth() -> Result<()> {
+    let mut sampler = Sampler::new(Sampling::EveryNth(3))?;
+    assert_eq!(vec![true, false, false, true], sampler.select(0..4));
+    // The position carries over
+    assert_eq!(vec![false, false, true], sampler.select(0..3));
+
+    let mut file = read_test_file()?;
+    assert_eq!(8, file.sample(Sampling::EveryNth(3))?);
+    assert_eq!(4, file.query_response_count());
+    assert!(file.dangling_indices().is_empty());
+    let block = &file.file_blocks[0];
+    assert!(block.verify_statistics().is_empty());
+    assert_eq!(
+        Some(4),
+        block.block_statistics.as_ref().unwrap().qr_data_items
+    );
+    Ok(())
+}
+
+#[test]
+fn probability() -> Result<()> {
+    let mut file = read_test_file()?;
+    file.sample(Sampling::Probability {
+        probability: 1.0,
+        seed: 1,
+    })?;
+    assert_eq!(12, file.query_response_count());
+    let storage_parameters = &file.file_preamble.block_parameters[0].storage_parameters;
+    assert!(storage_parameters
+        .storage_flags
+        .unwrap()
+        .contains(StorageFlags::SampledData));
+
+    let mut file = read_test_file()?;
+    file.sample(Sampling::Probability {
+        probability: 0.0,
+        seed: 1,
+    })?;
+    assert_eq!(0, file.query_response_count());
+
+    // The same seed gives the same sample
+    let sampling = Sampling::Probability {
+        probability: 0.5,
+        seed: 42,
+    };
+    let first = Sampler::new(sampling)?.select(0..1000);
+    assert_eq!(first, Sampler::new(sampling)?.select(0..1000));
+    let kept = first.iter().filter(|keep| **keep).count();
+    assert!((400..600).contains(&kept), "{}", kept);
+
+    assert!(Sampler::new(Sampling::Probability {
+        probability: 1.5,
+        seed: 0
+    })
+    .is_err());
+    assert!(Sampler::new(Sampling::EveryNth(0)).is_err());
+    Ok(())
+}
+
+#[test]
+fn per_client_reservoir() -> Result<()> {
+    let mut sampler = Sampler::new(Sampling::PerClientReservoir {
+        per_client: 2,
+        seed: 7,
+    })?;
+    let clients = ["a", "a", "a", "a", "b", "a", "c", "c"];
+    let keep = sampler.select(clients);
+    for (client, expected) in [("a", 2), ("b", 1), ("c", 2)] {
+        let kept = clients
+            .iter()
+            .zip(&keep)
+            .filter(|(other, keep)| **other == client && **keep)
+            .count();
+        assert_eq!(expected, kept, "{}", client);
+    }
+
+    let file = read_test_file()?;
+    let clients: HashSet<_> = file
+        .iter_all_query_responses()
+        .map(|(qr, _, _, block_tables)| {
+            qr.client_address_index
+                .and_then(|idx| block_tables.ip(idx).ok().cloned())
+        })
+        .collect();
+    let mut file = read_test_file()?;
+    file.sample(Sampling::PerClientReservoir {
+        per_client: 1,
+        seed: 0,
+    })?;
+    assert_eq!(clients.len(), file.query_response_count());
+    assert_eq!(
+        Some("per-client reservoir of 1 items"),
+        file.file_preamble.block_parameters[0]
+            .storage_parameters
+            .sampling_method
+            .as_deref()
+    );
+    Ok(())
+}
+
+#[test]
+fn build_sampled() -> Result<()> {
+    let sampling = Sampling::EveryNth(2);
+    let storage_parameters = StorageParametersBuilder::new(1_000_000, 5000)
+        .sampling(&sampling)
+        .build()?;
+    assert!(storage_parameters
+        .storage_flags
+        .unwrap()
+        .contains(StorageFlags::SampledData));
+    assert_eq!(
+        Some("1-in-2"),
+        storage_parameters.sampling_method.as_deref()
+    );
+
+    let mut sampler = Sampler::new(sampling)?;
+    let mut builder = BlockBuilder::new(&storage_parameters);
+    for client in ["192.0.2.1", "192.0.2.2", "192.0.2.3", "192.0.2.4"] {
+        builder.push(QueryResponseRecord {
+            time: Some(Timestamp {
+                timestamp_secs: 1_600_000_000,
+                timestamp_ticks: 0.into(),
+            }),
+            client_address: Some(client.parse()?),
+            ..Default::default()
+        });
+    }
+    let block = builder.build_sampled(&mut sampler)?;
+    assert_eq!(2, block.query_responses.as_ref().unwrap().len());
+    // The addresses of the dropped items are removed
+    let block_tables = block.block_tables.as_ref().unwrap();
+    assert_eq!(2, block_tables.ip_address.as_ref().unwrap().len());
+    assert!(block.verify_statistics().is_empty());
+    Ok(())
+}
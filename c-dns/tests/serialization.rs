@@ -0,0 +1,59 @@
+use c_dns::serialization::{AddressEventType, ExtraValues, File, Opcode, QueryResponseType};
+use color_eyre::eyre::Result;
+use serde_cbor::Value;
+
+/// Transaction types added to dnstap after RFC 8618 must be preserved.
+#[test]
+fn unknown_query_response_type() -> Result<()> {
+    let qr_type: QueryResponseType = serde_cbor::from_slice(&serde_cbor::to_vec(&3u8)?)?;
+    assert_eq!(QueryResponseType::Authoritative, qr_type);
+
+    let qr_type: QueryResponseType = serde_cbor::from_slice(&serde_cbor::to_vec(&7u8)?)?;
+    assert_eq!(QueryResponseType::Reserved(7), qr_type);
+    assert_eq!(Value::Integer(7), serde_cbor::value::to_value(qr_type)?);
+    Ok(())
+}
+
+/// Address event types of newer implementations must be preserved.
+#[test]
+fn unknown_address_event_type() -> Result<()> {
+    let ae_type: AddressEventType = serde_cbor::from_slice(&serde_cbor::to_vec(&5u8)?)?;
+    assert_eq!(AddressEventType::Icmpv6PacketTooBig, ae_type);
+
+    let ae_type: AddressEventType = serde_cbor::from_slice(&serde_cbor::to_vec(&42u8)?)?;
+    assert_eq!(AddressEventType::Reserved(42), ae_type);
+    assert_eq!(Value::Integer(42), serde_cbor::value::to_value(ae_type)?);
+    Ok(())
+}
+
+/// Empty extra values must not take more space than a pointer.
+#[test]
+fn extra_values_size() {
+    assert_eq!(
+        std::mem::size_of::<usize>(),
+        std::mem::size_of::<ExtraValues>()
+    );
+    let mut extra_values = ExtraValues::new();
+    assert!(extra_values.is_empty());
+    extra_values.insert(-1, Value::Bool(true));
+    assert_eq!(Some(&Value::Bool(true)), extra_values.get(-1));
+    assert_eq!(1, extra_values.len());
+}
+
+#[test]
+fn opcodes() -> Result<()> {
+    let opcode: Opcode = serde_cbor::from_slice(&serde_cbor::to_vec(&5u8)?)?;
+    assert_eq!(Opcode::UPDATE, opcode);
+    assert_eq!("UPDATE", opcode.to_string());
+    assert_eq!("OPCODE15", Opcode::try_from(15)?.to_string());
+    assert!(Opcode::try_from(16).is_err());
+    assert!(serde_cbor::from_slice::<Opcode>(&serde_cbor::to_vec(&16u8)?).is_err());
+
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let file: File = serde_cbor::from_slice(&c_dns_content)?;
+    let opcodes = &file.file_preamble.block_parameters[0]
+        .storage_parameters
+        .opcodes;
+    assert!(opcodes.contains(&Opcode::QUERY));
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use c_dns::resolved::ResolvedQueryResponse;
+use c_dns::serialization::{Block, BlockParameters, File};
+use c_dns::split::BlockLimit;
+use color_eyre::eyre::Result;
+use std::time::Duration;
+
+fn read_test_file() -> Result<File> {
+    Ok(File::read_path("./tests/data/dns.cdns")?)
+}
+
+/// The resolved content of the Q/R items of `block`, independent of the block layout.
+fn describe(block: &Block, block_parameters: &BlockParameters) -> Result<Vec<String>> {
+    let block_tables = block.block_tables.as_ref().unwrap();
+    Ok(block
+        .query_responses
+        .iter()
+        .flatten()
+        .map(|qr| {
+            let qr = ResolvedQueryResponse::new(
+                qr,
+                block.block_preamble.earliest_time,
+                block_parameters,
+                block_tables,
+            )?;
+            Ok(format!(
+                "{:?} {:?} {:?} {:?} {:?} {:?}",
+                qr.time,
+                qr.client_address,
+                qr.server_address,
+                qr.signature.map(|sig| sig.response_rcode),
+                qr.query_name,
+                qr.query_classtype
+            ))
+        })
+        .collect::<c_dns::Result<_>>()?)
+}
+
+fn describe_file(file: &File) -> Result<Vec<String>> {
+    let mut res = Vec::new();
+    for (block, block_parameters) in file.iter_blocks() {
+        res.extend(describe(block, block_parameters)?);
+    }
+    Ok(res)
+}
+
+#[test]
+fn split_block() -> Result<()> {
+    let mut file = read_test_file()?;
+    let block_parameters = &file.file_preamble.block_parameters[0];
+    let block = file.file_blocks.remove(0);
+    let expected = describe(&block, block_parameters)?;
+    let names = block
+        .block_tables
+        .as_ref()
+        .unwrap()
+        .name_rdata
+        .as_ref()
+        .unwrap()
+        .len();
+
+    let blocks = block.split(5, block_parameters)?;
+    let lengths: Vec<_> = blocks
+        .iter()
+        .map(|block| block.query_responses.as_ref().unwrap().len())
+        .collect();
+    assert_eq!(vec![5, 5, 2], lengths);
+    let mut described = Vec::new();
+    for block in &blocks {
+        described.extend(describe(block, block_parameters)?);
+        // The earliest item starts at the earliest time of the block
+        assert_eq!(
+            Some(0),
+            block
+                .query_responses
+                .iter()
+                .flatten()
+                .filter_map(|qr| qr.time_offset)
+                .map(u32::from)
+                .min()
+        );
+        assert!(block.verify_statistics().is_empty());
+    }
+    assert_eq!(expected, described);
+    // Each block only keeps the names it references
+    let last_names = blocks[2]
+        .block_tables
+        .as_ref()
+        .unwrap()
+        .name_rdata
+        .as_ref()
+        .unwrap()
+        .len();
+    assert!(last_names < names);
+
+    file.file_blocks = blocks;
+    assert!(file.dangling_indices().is_empty());
+
+    let block = read_test_file()?.file_blocks.remove(0);
+    assert!(block.split(0, block_parameters).is_err());
+    Ok(())
+}
+
+#[test]
+fn reblock_by_items() -> Result<()> {
+    let expected = describe_file(&File::concat([read_test_file()?, read_test_file()?])?)?;
+
+    // The remaining items of the first file fill up the block with the items of the second file
+    let file = File::concat([read_test_file()?, read_test_file()?])?.reblock(5)?;
+    let lengths: Vec<_> = file
+        .file_blocks
+        .iter()
+        .map(|block| block.query_responses.as_ref().unwrap().len())
+        .collect();
+    assert_eq!(vec![5, 5, 5, 5, 4], lengths);
+    assert_eq!(expected, describe_file(&file)?);
+    assert!(file.dangling_indices().is_empty());
+
+    // The max_block_items is a limit as well
+    let mut file = read_test_file()?;
+    file.file_preamble.block_parameters[0]
+        .storage_parameters
+        .max_block_items = 4;
+    let file = file.reblock(BlockLimit::Items(10))?;
+    assert_eq!(3, file.block_count());
+    assert!(read_test_file()?.reblock(0).is_err());
+    Ok(())
+}
+
+#[test]
+fn reblock_by_duration() -> Result<()> {
+    let file = read_test_file()?;
+    let expected = describe_file(&file)?;
+
+    // Everything happens within one hour
+    let hourly = read_test_file()?.reblock(Duration::from_secs(3600))?;
+    assert_eq!(1, hourly.block_count());
+    assert_eq!(expected, describe_file(&hourly)?);
+
+    // Each block only spans a single aligned window
+    let window = Duration::from_millis(100);
+    let file = read_test_file()?.reblock(window)?;
+    assert!(file.block_count() > 1);
+    let mut described = Vec::new();
+    for (block, block_parameters) in file.iter_blocks() {
+        let windows: std::collections::BTreeSet<_> = block
+            .iter_query_responses(block_parameters)
+            .filter_map(|(qr, earliest_time, block_parameters, _)| {
+                let ticks_per_second =
+                    u32::from(block_parameters.storage_parameters.ticks_per_second);
+                let time = earliest_time?
+                    .to_system_time(ticks_per_second)
+                    .ok()?
+                    .checked_add(qr.time_offset?.to_duration(ticks_per_second).ok()?)?;
+                let since_epoch = time.duration_since(std::time::UNIX_EPOCH).ok()?;
+                Some(since_epoch.as_millis() / window.as_millis())
+            })
+            .collect();
+        assert_eq!(1, windows.len());
+        described.extend(describe(block, block_parameters)?);
+    }
+    described.sort();
+    let mut expected = expected;
+    expected.sort();
+    assert_eq!(expected, described);
+    assert!(read_test_file()?.reblock(Duration::ZERO).is_err());
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use c_dns::serialization::File;
+use c_dns::statistics::StatisticsMismatch;
+use color_eyre::eyre::Result;
+
+fn read_test_file() -> Result<File> {
+    Ok(File::read_path("./tests/data/dns.cdns")?)
+}
+
+#[test]
+fn compute_statistics() -> Result<()> {
+    let file = read_test_file()?;
+    let block = &file.file_blocks[0];
+    let computed = block.compute_statistics();
+    assert_eq!(Some(12), computed.qr_data_items);
+    assert_eq!(Some(0), computed.malformed_items);
+    assert_eq!(None, computed.discarded_opcode);
+    let stored = block.block_statistics.as_ref().unwrap();
+    assert_eq!(stored.qr_data_items, computed.qr_data_items);
+    assert_eq!(stored.unmatched_queries, computed.unmatched_queries);
+    assert_eq!(stored.unmatched_responses, computed.unmatched_responses);
+    Ok(())
+}
+
+#[test]
+fn verify_statistics() -> Result<()> {
+    let mut file = read_test_file()?;
+    let block = &mut file.file_blocks[0];
+    assert_eq!(Vec::<StatisticsMismatch>::new(), block.verify_statistics());
+
+    let statistics = block.block_statistics.as_mut().unwrap();
+    statistics.qr_data_items = Some(13);
+    statistics.processed_messages = Some(1_000_000);
+    statistics.malformed_items = None;
+    assert_eq!(
+        vec![StatisticsMismatch {
+            field: "qr_data_items",
+            stored: 13,
+            computed: 12,
+        }],
+        block.verify_statistics()
+    );
+    assert_eq!(
+        "qr_data_items is 13, but the block content yields 12",
+        block.verify_statistics()[0].to_string()
+    );
+
+    block.block_statistics = None;
+    assert!(block.verify_statistics().is_empty());
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use c_dns::serialization::File;
+use c_dns::summary::FileSummary;
+use color_eyre::eyre::Result;
+
+#[test]
+fn summary_matches_file() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let file = File::from_slice(&c_dns_content)?;
+    let summary = FileSummary::from_slice(&c_dns_content)?;
+
+    assert_eq!(file.file_type_id, summary.file_type_id);
+    assert_eq!(file.block_count(), summary.blocks.len());
+    for (block, block_summary) in file.file_blocks.iter().zip(&summary.blocks) {
+        assert_eq!(
+            block.block_preamble.earliest_time,
+            block_summary.block_preamble.earliest_time
+        );
+        assert_eq!(
+            block
+                .block_statistics
+                .as_ref()
+                .and_then(|statistics| statistics.qr_data_items),
+            block_summary
+                .block_statistics
+                .as_ref()
+                .and_then(|statistics| statistics.qr_data_items)
+        );
+        assert_eq!(
+            block.query_responses.as_ref().map_or(0, Vec::len),
+            block_summary.query_response_count
+        );
+        assert_eq!(
+            block.address_event_counts.as_ref().map_or(0, Vec::len),
+            block_summary.address_event_count
+        );
+        assert_eq!(
+            block.malformed_messages.as_ref().map_or(0, Vec::len),
+            block_summary.malformed_message_count
+        );
+    }
+    assert_eq!(
+        file.file_blocks[0].block_preamble.earliest_time,
+        summary.earliest_time()
+    );
+    Ok(())
+}
+
+/// Items in indefinite-length arrays are counted as well.
+#[test]
+fn summary_of_streamed_file() -> Result<()> {
+    use c_dns::serialization::FileWriter;
+
+    let file = File::read_path("./tests/data/dns.cdns")?;
+    let mut writer = FileWriter::new(Vec::new(), &file.file_preamble)?;
+    writer.write_block(&file.file_blocks[0])?;
+    writer.write_block(&file.file_blocks[0])?;
+    let summary = FileSummary::from_slice(&writer.finish()?)?;
+    assert_eq!(2, summary.blocks.len());
+    assert_eq!(24, summary.query_response_count());
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use c_dns::serialization::{BlockTables, DnsType, File, NameOrRdata};
+use color_eyre::eyre::Result;
+use std::collections::{BTreeSet, HashMap};
+
+fn read_test_file() -> Result<File> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    Ok(serde_cbor::from_slice(&c_dns_content)?)
+}
+
+fn table_len<T>(table: &Option<Vec<T>>) -> usize {
+    table.as_ref().map_or(0, Vec::len)
+}
+
+#[test]
+fn append_into_empty_tables() -> Result<()> {
+    let mut file = read_test_file()?;
+    let tables = file.file_blocks[0].block_tables.take().unwrap();
+    let (ip_address, qr_sig) = (table_len(&tables.ip_address), table_len(&tables.qr_sig));
+
+    let mut merged = BlockTables::default();
+    let remapping = merged.append(tables);
+    assert_eq!((0..ip_address).collect::<Vec<_>>(), remapping.ip_address);
+    assert_eq!((0..qr_sig).collect::<Vec<_>>(), remapping.qr_sig);
+    assert_eq!(ip_address, table_len(&merged.ip_address));
+    assert!(merged.qlist.is_none());
+    Ok(())
+}
+
+#[test]
+fn append_deduplicates_values() -> Result<()> {
+    let mut file = read_test_file()?;
+    let mut other = read_test_file()?;
+    let block = &mut file.file_blocks[0];
+    let tables = block.block_tables.as_mut().unwrap();
+    let (ip_address, name_rdata, classtype, qr_sig) = (
+        table_len(&tables.ip_address),
+        table_len(&tables.name_rdata),
+        table_len(&tables.classtype),
+        table_len(&tables.qr_sig),
+    );
+
+    let remapping = tables.append(other.file_blocks[0].block_tables.take().unwrap());
+    assert_eq!(ip_address, table_len(&tables.ip_address));
+    assert_eq!(name_rdata, table_len(&tables.name_rdata));
+    assert_eq!(classtype, table_len(&tables.classtype));
+    assert_eq!(2 * qr_sig, table_len(&tables.qr_sig));
+    assert_eq!((0..name_rdata).collect::<Vec<_>>(), remapping.name_rdata);
+    assert_eq!((qr_sig..2 * qr_sig).collect::<Vec<_>>(), remapping.qr_sig);
+
+    // The remapped Q/R items reference the same data as the originals
+    let originals = block.query_responses.as_ref().unwrap();
+    let tables = block.block_tables.as_ref().unwrap();
+    for (original, mut query_response) in originals
+        .iter()
+        .zip(other.file_blocks[0].query_responses.take().unwrap())
+    {
+        remapping.remap_query_response(&mut query_response);
+        assert_eq!(original.query_name_index, query_response.query_name_index);
+        assert_eq!(
+            original.client_address_index,
+            query_response.client_address_index
+        );
+        let signature = |qr: &c_dns::serialization::QueryResponse| {
+            let sig = &tables.qr_sig.as_ref().unwrap()[qr.qr_signature_index.unwrap()];
+            format!("{:?}", sig)
+        };
+        assert_ne!(
+            original.qr_signature_index,
+            query_response.qr_signature_index
+        );
+        assert_eq!(signature(original), signature(&query_response));
+    }
+    Ok(())
+}
+
+#[test]
+fn table_entries_as_keys() -> Result<()> {
+    let file = read_test_file()?;
+    let mut names: HashMap<&NameOrRdata, usize> = HashMap::new();
+    let mut classtypes = BTreeSet::new();
+    for (block, block_parameters) in file.iter_blocks() {
+        for (query_response, _, _, tables) in block.iter_query_responses(block_parameters) {
+            let name_rdata = tables.name_rdata.as_ref().unwrap();
+            if let Some(idx) = query_response.query_name_index {
+                *names.entry(&name_rdata[idx]).or_default() += 1;
+            }
+            let sig = &tables.qr_sig.as_ref().unwrap()[query_response.qr_signature_index.unwrap()];
+            if let Some(idx) = sig.query_classtype_index {
+                classtypes.insert(tables.classtype.as_ref().unwrap()[idx]);
+            }
+        }
+    }
+    assert_eq!(12, names.values().sum::<usize>());
+    assert!(!classtypes.is_empty());
+    let types: Vec<DnsType> = classtypes.iter().map(|ct| ct.type_).collect();
+    assert!(types.windows(2).all(|pair| pair[0] <= pair[1]));
+    Ok(())
+}
+
+#[test]
+fn checked_accessors() -> Result<()> {
+    use c_dns::tables::IndexBase;
+
+    let file = read_test_file()?;
+    let block_tables = file.file_blocks[0].block_tables.as_ref().unwrap();
+    let names = block_tables.name_rdata.as_ref().unwrap();
+    let signatures = block_tables.qr_sig.as_ref().unwrap();
+    assert_eq!(&names[0], block_tables.name(0)?);
+    assert_eq!(
+        block_tables.ip_address.as_ref().unwrap().last().unwrap(),
+        block_tables.ip(block_tables.ip_address.as_ref().unwrap().len() - 1)?
+    );
+    assert_eq!(
+        signatures[0].query_classtype_index,
+        block_tables.qr_signature(0)?.query_classtype_index
+    );
+    assert!(matches!(
+        block_tables.name(names.len()),
+        Err(c_dns::Error::IndexOutOfBounds {
+            table: "name_rdata",
+            ..
+        })
+    ));
+    // Missing tables behave like empty tables
+    assert!(block_tables.rr(0).is_err());
+    assert!(block_tables.question_list(0).is_err());
+
+    let one_based = block_tables.with_base(IndexBase::One);
+    assert_eq!(&names[0], one_based.name(1)?);
+    assert_eq!(&names[names.len() - 1], one_based.name(names.len())?);
+    assert!(one_based.name(0).is_err());
+    Ok(())
+}
+
+#[test]
+fn compact_tables() -> Result<()> {
+    use c_dns::resolved::ResolvedQueryResponse;
+
+    let mut file = read_test_file()?;
+    let block_parameters = &file.file_preamble.block_parameters[0];
+    let block = &mut file.file_blocks[0];
+    let describe = |block: &c_dns::serialization::Block| -> Result<Vec<String>> {
+        let block_tables = block.block_tables.as_ref().unwrap();
+        Ok(block
+            .query_responses
+            .iter()
+            .flatten()
+            .map(|qr| {
+                let qr = ResolvedQueryResponse::new(
+                    qr,
+                    block.block_preamble.earliest_time,
+                    block_parameters,
+                    block_tables,
+                )?;
+                Ok(format!(
+                    "{:?} {:?} {:?} {:?}",
+                    qr.client_address, qr.server_address, qr.query_name, qr.query_classtype
+                ))
+            })
+            .collect::<c_dns::Result<_>>()?)
+    };
+
+    // Nothing is referenced anymore, except by the kept Q/R item
+    block.query_responses.as_mut().unwrap().truncate(1);
+    let expected = describe(block)?;
+    let names_before = table_len(&block.block_tables.as_ref().unwrap().name_rdata);
+    block.compact_tables();
+    assert_eq!(expected, describe(block)?);
+    let block_tables = block.block_tables.as_ref().unwrap();
+    assert!(table_len(&block_tables.name_rdata) < names_before);
+    assert_eq!(1, table_len(&block_tables.qr_sig));
+    assert!(table_len(&block_tables.ip_address) <= 2);
+    assert!(file.dangling_indices().is_empty());
+
+    // Compacting again changes nothing
+    let bytes = file.to_vec()?;
+    file.file_blocks[0].compact_tables();
+    assert_eq!(bytes, file.to_vec()?);
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use c_dns::serialization::{File, Ticks, Timestamp, UTicks};
+use color_eyre::eyre::Result;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn read_test_file() -> Result<File> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    Ok(serde_cbor::from_slice(&c_dns_content)?)
+}
+
+fn ticks_per_second(file: &File) -> u32 {
+    file.file_preamble.block_parameters[0]
+        .storage_parameters
+        .ticks_per_second
+        .into()
+}
+
+#[test]
+fn convert_roundtrip() -> Result<()> {
+    let original = read_test_file()?;
+    let from = ticks_per_second(&original);
+    let mut file = read_test_file()?;
+
+    // A higher resolution is exact, so converting back restores all values
+    file.convert_ticks(from * 10)?;
+    assert_eq!(from * 10, ticks_per_second(&file));
+    file.convert_ticks(from)?;
+    assert_eq!(serde_cbor::to_vec(&original)?, serde_cbor::to_vec(&file)?);
+    Ok(())
+}
+
+#[test]
+fn convert_to_lower_resolution() -> Result<()> {
+    let mut file = read_test_file()?;
+    let from = ticks_per_second(&file);
+    let block = &mut file.file_blocks[0];
+    block
+        .block_preamble
+        .earliest_time
+        .as_mut()
+        .unwrap()
+        .timestamp_ticks = UTicks::from(from - 1);
+    let qr = &mut block.query_responses.as_mut().unwrap()[0];
+    qr.time_offset = Some(UTicks::from(1));
+    qr.response_delay = Some(Ticks::from(-1));
+
+    block.convert_ticks(from, 1)?;
+    let qr = &block.query_responses.as_ref().unwrap()[0];
+    // The Query crosses into the next second, the Response does not
+    assert_eq!(
+        0,
+        u32::from(block.block_preamble.earliest_time.unwrap().timestamp_ticks)
+    );
+    assert_eq!(Some(UTicks::from(1)), qr.time_offset);
+    assert_eq!(Some(Ticks::from(-1)), qr.response_delay);
+    Ok(())
+}
+
+#[test]
+fn convert_fails_without_changes() -> Result<()> {
+    let mut file = read_test_file()?;
+    let from = ticks_per_second(&file);
+    let original = serde_cbor::to_vec(&file)?;
+    assert!(file.convert_ticks(0).is_err());
+    assert_eq!(original, serde_cbor::to_vec(&file)?);
+
+    file.file_blocks[0].query_responses.as_mut().unwrap()[0].time_offset =
+        Some(UTicks::from(u32::MAX));
+    let modified = serde_cbor::to_vec(&file)?;
+    assert!(file.convert_ticks(from * 2).is_err());
+    assert_eq!(modified, serde_cbor::to_vec(&file)?);
+    Ok(())
+}
+
+#[test]
+fn system_time_conversion() -> Result<()> {
+    let timestamp = Timestamp {
+        timestamp_secs: 1_600_000_000,
+        timestamp_ticks: 250.into(),
+    };
+    let time = UNIX_EPOCH + Duration::new(1_600_000_000, 250_000_000);
+    assert_eq!(time, timestamp.to_system_time(1_000)?);
+    assert_eq!(timestamp, Timestamp::from_system_time(time, 1_000)?);
+    assert!(timestamp.to_system_time(0).is_err());
+
+    assert_eq!(
+        Duration::from_millis(1_500),
+        UTicks::from(1_500_000).to_duration(1_000_000)?
+    );
+    assert_eq!(
+        UTicks::from(15),
+        UTicks::from_duration(Duration::from_micros(1_599), 10_000)?
+    );
+    assert_eq!(Duration::from_millis(5), Ticks::from(5).to_duration(1_000)?);
+    assert!(Ticks::from(-5).to_duration(1_000).is_err());
+    assert_eq!(UTicks::from(5), Ticks::from(-5).unsigned_abs());
+    Ok(())
+}
+
+#[test]
+fn query_response_times() -> Result<()> {
+    let file = read_test_file()?;
+    let block = &file.file_blocks[0];
+    let storage_parameters = &file.file_preamble.block_parameters[0].storage_parameters;
+    let tps = ticks_per_second(&file);
+    let earliest_time = block
+        .block_preamble
+        .earliest_time
+        .unwrap()
+        .to_system_time(tps)?;
+    for qr in block.query_responses.iter().flatten() {
+        let time = qr.absolute_time(&block.block_preamble, storage_parameters)?;
+        let offset = qr
+            .time_offset
+            .map_or(Ok(Duration::ZERO), |offset| offset.to_duration(tps))?;
+        assert_eq!(earliest_time + offset, time);
+        match qr.response_delay {
+            Some(delay) if i32::from(delay) >= 0 => assert_eq!(
+                Some(time + delay.to_duration(tps)?),
+                qr.response_time(&block.block_preamble, storage_parameters)?
+            ),
+            Some(_) => {}
+            None => assert_eq!(
+                None,
+                qr.response_time(&block.block_preamble, storage_parameters)?
+            ),
+        }
+    }
+    Ok(())
+}
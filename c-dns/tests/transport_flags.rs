@@ -0,0 +1,122 @@
+use c_dns::serialization::TransportFlags;
+use c_dns::{IpVersion, ReservedTransport, Transport};
+use color_eyre::eyre::Result;
+
+#[test]
+fn known_transport() {
+    let flags = TransportFlags::new(IpVersion::Ipv6, Transport::Tls, true);
+    assert_eq!(0b0010_0101, flags.bits());
+    assert_eq!(IpVersion::Ipv6, flags.ip_version());
+    assert_eq!(Transport::Tls, flags.transport_protocol());
+    assert_eq!(2, flags.transport_code());
+    assert!(flags.has_trailing_data());
+    assert_eq!(
+        "IPv6 | TLS | Query has trailing data",
+        format!("{:?}", flags)
+    );
+}
+
+#[test]
+fn reserved_transport_code() -> Result<()> {
+    let flags = TransportFlags::with_transport_code(IpVersion::Ipv4, 7, false)?;
+    assert_eq!(
+        Transport::Reserved(ReservedTransport::new(7)?),
+        flags.transport_protocol()
+    );
+    assert_eq!(7, flags.transport_code());
+    assert_eq!("IPv4 | Reserved (7)", format!("{:?}", flags));
+
+    // The raw value survives a round-trip
+    let bytes = serde_cbor::to_vec(&flags)?;
+    let flags: TransportFlags = serde_cbor::from_slice(&bytes)?;
+    assert_eq!(7, flags.transport_code());
+
+    assert!(TransportFlags::with_transport_code(IpVersion::Ipv4, 16, false).is_err());
+    Ok(())
+}
+
+#[test]
+fn transport_codes() -> Result<()> {
+    for code in 0..=15 {
+        let transport = Transport::try_from(code)?;
+        assert_eq!(code, u8::from(transport));
+        // The value survives a round-trip
+        let bytes = serde_cbor::to_vec(&transport)?;
+        assert_eq!(serde_cbor::to_vec(&code)?, bytes);
+        assert_eq!(transport, serde_cbor::from_slice(&bytes)?);
+    }
+    #[cfg(not(feature = "non-rfc-transports"))]
+    assert_eq!(
+        Transport::Reserved(ReservedTransport::new(5)?),
+        Transport::try_from(5)?
+    );
+    assert_eq!("Reserved (9)", Transport::try_from(9)?.to_string());
+    assert!(Transport::try_from(16).is_err());
+    assert!(serde_cbor::from_slice::<Transport>(&serde_cbor::to_vec(&16u8)?).is_err());
+
+    // Only reserved codes can be stored as reserved
+    for code in [0, 1, 4, 15, 16, 20] {
+        assert!(ReservedTransport::new(code).is_err());
+    }
+    #[cfg(not(feature = "non-rfc-transports"))]
+    {
+        let flags = TransportFlags::new(
+            IpVersion::Ipv4,
+            Transport::Reserved(ReservedTransport::new(6)?),
+            false,
+        );
+        assert_eq!(6, flags.transport_code());
+        assert_eq!("IPv4 | Reserved (6)", format!("{:?}", flags));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "non-rfc-transports")]
+#[test]
+fn non_rfc_transports() -> Result<()> {
+    assert_eq!(Transport::Quic, Transport::try_from(5)?);
+    assert_eq!(Transport::Http3, Transport::try_from(6)?);
+    assert!(ReservedTransport::new(5).is_err());
+    assert!(ReservedTransport::new(6).is_err());
+    assert_eq!(
+        Transport::Reserved(ReservedTransport::new(7)?),
+        Transport::try_from(7)?
+    );
+
+    let flags = TransportFlags::new(IpVersion::Ipv6, Transport::Quic, false);
+    assert_eq!(5, flags.transport_code());
+    assert_eq!(Transport::Quic, flags.transport_protocol());
+    assert_eq!("IPv6 | QUIC", format!("{:?}", flags));
+    assert_eq!("HTTP/3", Transport::Http3.to_string());
+    Ok(())
+}
+
+#[test]
+fn mutate_flags() -> Result<()> {
+    let mut flags = TransportFlags::from(0b1000_0000);
+    assert_eq!(0b1000_0000, flags.extension_bits());
+    flags.set_ip_version(IpVersion::Ipv6);
+    flags.set_transport(Transport::Https);
+    flags.set_trailing_data(true);
+    assert_eq!(
+        TransportFlags::new(IpVersion::Ipv6, Transport::Https, true).bits() | 0b1000_0000,
+        u8::from(flags)
+    );
+    assert_eq!(
+        "IPv6 | HTTPS | Query has trailing data | Extension bits 0b10000000",
+        format!("{:?}", flags)
+    );
+
+    flags.set_ip_version(IpVersion::Ipv4);
+    flags.set_trailing_data(false);
+    flags.set_transport_code(12)?;
+    assert!(flags.set_transport_code(16).is_err());
+    flags.set_extension_bits(0b0100_0001);
+    assert_eq!(0b0101_1000, flags.bits());
+    assert_eq!(
+        Transport::Reserved(ReservedTransport::new(12)?),
+        flags.transport_protocol()
+    );
+    assert_eq!(flags, TransportFlags::from(flags.bits()));
+    Ok(())
+}
@@ -0,0 +1,243 @@
+use c_dns::serialization::{File, MalformedMessageData, QueryResponseFlags};
+use c_dns::wire::{
+    check_message, Direction, EdnsOption, Message, Section, WireError, WireErrorKind,
+};
+use color_eyre::eyre::Result;
+
+fn read_test_file() -> Result<File> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    Ok(serde_cbor::from_slice(&c_dns_content)?)
+}
+
+#[test]
+fn rebuild_messages() -> Result<()> {
+    let file = read_test_file()?;
+    let block = &file.file_blocks[0];
+    let block_tables = block.block_tables.as_ref().unwrap();
+    let mut rebuilt = 0;
+    for query_response in block.query_responses.iter().flatten() {
+        let sig =
+            &block_tables.qr_sig.as_ref().unwrap()[query_response.qr_signature_index.unwrap()];
+        let flags = sig.qr_sig_flags.unwrap_or_default();
+        for (direction, present, qr_bit) in [
+            (Direction::Query, QueryResponseFlags::HasQuery, 0),
+            (Direction::Response, QueryResponseFlags::HasResponse, 0x80),
+        ] {
+            let message = query_response.to_wire(block_tables, direction);
+            if !flags.contains(present) {
+                assert!(message.is_err());
+                continue;
+            }
+            let message = message?;
+            rebuilt += 1;
+            assert_eq!(
+                query_response.transaction_id.unwrap().to_be_bytes(),
+                message[0..2]
+            );
+            assert_eq!(qr_bit, message[2] & 0x80);
+            let qdcount = u16::from_be_bytes([message[4], message[5]]);
+            if let Some(name_index) = query_response.query_name_index {
+                assert!(qdcount >= 1);
+                let name = block_tables.name_rdata.as_ref().unwrap()[name_index].as_bytes();
+                assert_eq!(name, &message[12..12 + name.len()]);
+            }
+        }
+    }
+    assert!(rebuilt > 0);
+    Ok(())
+}
+
+#[test]
+fn rebuild_query_with_opt() -> Result<()> {
+    let mut file = read_test_file()?;
+    let block = &mut file.file_blocks[0];
+    let query_response = &block.query_responses.as_ref().unwrap()[0];
+    let block_tables = block.block_tables.as_mut().unwrap();
+    let sig =
+        &mut block_tables.qr_sig.as_mut().unwrap()[query_response.qr_signature_index.unwrap()];
+    sig.qr_sig_flags = Some(QueryResponseFlags::HasQuery | QueryResponseFlags::QueryHasOpt);
+    sig.query_udp_size = Some(1232);
+    sig.query_rcode = Some(0);
+    sig.query_opt_rdata_index = None;
+
+    let message = query_response.to_wire(block_tables, Direction::Query)?;
+    let arcount = u16::from_be_bytes([message[10], message[11]]);
+    assert!(arcount >= 1);
+    // Root name, TYPE 41, CLASS 1232, TTL, RDLENGTH 0
+    let opt = &message[message.len() - 11..];
+    assert_eq!(0, opt[0]);
+    assert_eq!(41u16.to_be_bytes(), opt[1..3]);
+    assert_eq!(1232u16.to_be_bytes(), opt[3..5]);
+    assert_eq!([0, 0], opt[9..11]);
+    Ok(())
+}
+
+#[test]
+fn check_rebuilt_messages() -> Result<()> {
+    let file = read_test_file()?;
+    let block = &file.file_blocks[0];
+    let block_tables = block.block_tables.as_ref().unwrap();
+    for query_response in block.query_responses.iter().flatten() {
+        for direction in [Direction::Query, Direction::Response] {
+            if let Ok(message) = query_response.to_wire(block_tables, direction) {
+                assert_eq!(Ok(()), check_message(&message));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Query for `example.com. IN A` with one Question
+const QUERY: &[u8] =
+    b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+
+#[test]
+fn diagnose_malformed_messages() {
+    assert_eq!(Ok(()), check_message(QUERY));
+
+    let err = check_message(&QUERY[..6]).unwrap_err();
+    assert_eq!(Section::Header, err.section);
+    assert_eq!(WireErrorKind::Truncated { needed: 6 }, err.kind);
+
+    // Truncated TYPE and CLASS
+    let err = check_message(&QUERY[..QUERY.len() - 2]).unwrap_err();
+    assert_eq!(
+        WireError {
+            offset: 12,
+            section: Section::Question,
+            record: 0,
+            kind: WireErrorKind::Truncated { needed: 2 },
+        },
+        err
+    );
+
+    // Too large QDCOUNT
+    let mut message = QUERY.to_vec();
+    message[5] = 2;
+    let err = check_message(&message).unwrap_err();
+    assert_eq!((Section::Question, 1), (err.section, err.record));
+
+    // Too small QDCOUNT
+    message[5] = 0;
+    let err = check_message(&message).unwrap_err();
+    assert_eq!(WireErrorKind::TrailingBytes { count: 17 }, err.kind);
+
+    // Label length of 0x47
+    let mut message = QUERY.to_vec();
+    message[12] = 0x47;
+    assert_eq!(
+        WireErrorKind::BadLabelType { byte: 0x47 },
+        check_message(&message).unwrap_err().kind
+    );
+    message[12] = 0x3f;
+    assert!(matches!(
+        check_message(&message).unwrap_err().kind,
+        WireErrorKind::Truncated { .. }
+    ));
+
+    // Compression pointer to itself
+    let mut message = QUERY[..12].to_vec();
+    message.extend_from_slice(b"\xc0\x0c\x00\x01\x00\x01");
+    assert_eq!(
+        "question record 0 at offset 12: compression pointer to offset 12 is not backwards",
+        check_message(&message).unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn diagnose_payload() {
+    let mut data = MalformedMessageData {
+        server_address_index: None,
+        server_port: None,
+        mm_transport_flags: None,
+        mm_payload: Some(bytes::Bytes::from_static(QUERY)),
+        extra_values: Default::default(),
+    };
+    assert_eq!(None, data.diagnose());
+    data.mm_payload = Some(bytes::Bytes::from_static(&QUERY[..20]));
+    assert_eq!(Section::Question, data.diagnose().unwrap().section);
+}
+
+#[test]
+fn parse_query() -> Result<()> {
+    let message = Message::parse(QUERY)?;
+    assert_eq!(0x1234, message.header.id);
+    assert!(!message.header.is_response());
+    assert_eq!(0, message.header.opcode());
+    assert_eq!(1, message.questions.len());
+    assert_eq!(
+        b"\x07example\x03com\x00",
+        message.questions[0].name.as_bytes()
+    );
+    assert_eq!(
+        (1, 1),
+        (message.questions[0].rr_type, message.questions[0].class)
+    );
+    assert!(message.answers.is_empty());
+    assert_eq!(None, message.opt);
+    Ok(())
+}
+
+#[test]
+fn parse_response_with_opt() -> Result<()> {
+    let mut response = QUERY.to_vec();
+    // QR and RD bits, NXDOMAIN, one Answer and one Additional
+    response[2..12].copy_from_slice(b"\x81\x03\x00\x01\x00\x01\x00\x00\x00\x01");
+    // CNAME pointing to www.example.com, compressed against the QNAME
+    response.extend_from_slice(b"\xc0\x0c\x00\x05\x00\x01\x00\x00\x0e\x10\x00\x06\x03www\xc0\x0c");
+    // OPT with extended RCODE 1, version 0, DO bit, and one option
+    response
+        .extend_from_slice(b"\x00\x00\x29\x04\xd0\x01\x00\x80\x00\x00\x06\x00\x0a\x00\x02\xab\xcd");
+
+    let message = Message::parse(&response)?;
+    assert!(message.header.is_response());
+    assert_eq!(3, message.header.rcode());
+    // The extended RCODE 1 forms the upper bits
+    assert_eq!(0x13, message.rcode());
+
+    let answer = &message.answers[0];
+    assert_eq!(b"\x07example\x03com\x00", answer.name.as_bytes());
+    assert_eq!((5, 3600), (answer.rr_type, answer.ttl));
+    assert_eq!(
+        b"\x03www\x07example\x03com\x00",
+        answer.rdata_name(&response)?.as_bytes()
+    );
+
+    assert!(message.additionals.is_empty());
+    let opt = message.opt.unwrap();
+    assert_eq!(1232, opt.udp_payload_size);
+    assert_eq!((1, 0, 0x8000), (opt.extended_rcode, opt.version, opt.flags));
+    assert_eq!(
+        vec![EdnsOption {
+            code: 10,
+            data: vec![0xab, 0xcd]
+        }],
+        opt.options
+    );
+
+    // The option is longer than the RDATA
+    let len = response.len();
+    response[len - 3] = 3;
+    let err = Message::parse(&response).unwrap_err();
+    assert_eq!((Section::Additional, 0), (err.section, err.record));
+    assert_eq!(WireErrorKind::Truncated { needed: 1 }, err.kind);
+    Ok(())
+}
+
+#[test]
+fn parse_rebuilt_messages() -> Result<()> {
+    let file = read_test_file()?;
+    let block = &file.file_blocks[0];
+    let block_tables = block.block_tables.as_ref().unwrap();
+    for query_response in block.query_responses.iter().flatten() {
+        if let Ok(wire) = query_response.to_wire(block_tables, Direction::Query) {
+            let message = Message::parse(&wire)?;
+            assert_eq!(query_response.transaction_id, Some(message.header.id));
+            if let Some(name_index) = query_response.query_name_index {
+                let name = &block_tables.name_rdata.as_ref().unwrap()[name_index];
+                assert_eq!(name, &message.questions[0].name);
+            }
+        }
+    }
+    Ok(())
+}
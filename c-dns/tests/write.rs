@@ -0,0 +1,72 @@
+use c_dns::serialization::File;
+use color_eyre::eyre::Result;
+
+#[test]
+fn serialize_into_reuses_buffer() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let file: File = serde_cbor::from_slice(&c_dns_content)?;
+
+    let mut buffer = Vec::new();
+    file.serialize_into(&mut buffer)?;
+    assert_eq!(serde_cbor::to_vec(&file)?, buffer);
+
+    let capacity = buffer.capacity();
+    let ptr = buffer.as_ptr();
+    buffer.clear();
+    file.file_blocks[0].serialize_into(&mut buffer)?;
+    assert_eq!(serde_cbor::to_vec(&file.file_blocks[0])?, buffer);
+    assert_eq!(capacity, buffer.capacity());
+    assert_eq!(ptr, buffer.as_ptr());
+    Ok(())
+}
+
+#[test]
+fn write_entry_points() -> Result<()> {
+    let c_dns_content = std::fs::read("./tests/data/dns.cdns")?;
+    let file = File::from_slice(&c_dns_content)?;
+    let expected = file.to_vec()?;
+    assert_eq!(serde_cbor::to_vec(&file)?, expected);
+
+    let mut buffer = Vec::new();
+    file.to_writer(&mut buffer)?;
+    assert_eq!(expected, buffer);
+
+    let path = std::env::temp_dir().join(format!("c-dns-write-{}.cdns", std::process::id()));
+    file.write_path(&path)?;
+    let written = std::fs::read(&path);
+    std::fs::remove_file(&path)?;
+    assert_eq!(expected, written?);
+    Ok(())
+}
+
+#[test]
+fn stream_blocks() -> Result<()> {
+    use c_dns::serialization::{FileReader, FileWriter};
+
+    let file = File::read_path("./tests/data/dns.cdns")?;
+    let mut writer = FileWriter::new(Vec::new(), &file.file_preamble)?;
+    for _ in 0..3 {
+        writer.write_block(&file.file_blocks[0])?;
+    }
+    assert_eq!(3, writer.block_count());
+    let bytes = writer.finish()?;
+
+    let written = File::from_slice(&bytes)?;
+    assert_eq!(3, written.block_count());
+    assert_eq!(
+        serde_cbor::to_vec(&file.file_preamble)?,
+        serde_cbor::to_vec(&written.file_preamble)?
+    );
+    let blocks = FileReader::new(&bytes[..])?.collect::<c_dns::Result<Vec<_>>>()?;
+    assert_eq!(3, blocks.len());
+
+    // Without finishing, the complete blocks can be salvaged
+    let mut unfinished = Vec::new();
+    let mut partial = FileWriter::new(&mut unfinished, &file.file_preamble)?;
+    partial.write_block(&file.file_blocks[0])?;
+    drop(partial);
+    let (salvaged, truncation) = File::from_slice_salvage(&unfinished)?;
+    assert_eq!(1, salvaged.block_count());
+    assert!(truncation.is_some());
+    Ok(())
+}
@@ -17,6 +17,37 @@ pub struct SomeKeys {
 }
 ```
 
+### Extra values
+A single field can be annotated with `#[serde_indexed(extras)]` to collect all entries with negative keys.
+Entries with unknown positive keys are collected as well, which keeps fields added by newer versions of a format.
+Without such a field, negative keys are ignored and unknown positive keys are an error.
+The field type is not fixed, it only needs to implement [`Default`], provide `insert(isize, V)` and `len()` methods, and iterate over `(key, value)` pairs by reference.
+This allows using a `BTreeMap<isize, V>` or a type which only allocates once the first value is inserted.
+
+### Enums
+An enum whose variants each wrap one indexed struct decodes whichever struct matches the map.
+This allows decoding different versions of a structure into one type.
+The first variant matching all of its conditions is selected, in declaration order:
+
+* `#[serde_indexed(requires(1, 3))]` on a variant requires the keys 1 and 3 to be present.
+* `#[serde_indexed(discriminator = 0)]` on the enum names a key whose integer value selects the variant, and `#[serde_indexed(tag = 2)]` on a variant requires this value to be 2.
+* A variant without conditions always matches and can serve as the fallback.
+
+```ignore
+#[derive(Clone, Debug, PartialEq, SerializeIndexed, DeserializeIndexed)]
+pub enum Versioned {
+    #[serde_indexed(requires(2))]
+    V2(SomeKeysV2),
+    V1(SomeKeys),
+}
+```
+
+Serialization writes the wrapped struct.
+Deserialization buffers all entries first, so the wrapped structs cannot borrow from the input.
+Like the missing fields handling, the buffering is provided by the crate using the macro.
+Its `derive_helpers` module must contain a `Buffered` type holding any value, `buffered_map(Vec<(isize, Buffered)>) -> Buffered` creating a map value, and `from_buffered<T: DeserializeOwned, E: de::Error>(Buffered) -> Result<T, E>` decoding a value.
+With [`serde_cbor`][serde-cbor] these are `serde_cbor::Value`, `Value::Map`, and `serde_cbor::value::from_value`.
+
 ### Generated code example
 `cargo expand --test basics` exercises the macros using [`serde_cbor`][serde-cbor].
 
@@ -28,7 +59,7 @@ pub struct SomeKeys {
 
 mod parse;
 
-use crate::parse::{Field, Input};
+use crate::parse::{EnumInput, Field, Input, StructInput};
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
 use syn::{parse_macro_input, Error};
@@ -78,7 +109,13 @@ fn count_serialized_fields(fields: &[parse::Field]) -> Vec<proc_macro2::TokenStr
 
 #[proc_macro_derive(SerializeIndexed, attributes(serde, serde_indexed))]
 pub fn derive_serialize(input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as Input);
+    match parse_macro_input!(input as Input) {
+        Input::Struct(input) => serialize_struct(input),
+        Input::Enum(input) => serialize_enum(input),
+    }
+}
+
+fn serialize_struct(input: StructInput) -> TokenStream {
     let ident = input.ident;
     let num_fields = count_serialized_fields(&input.fields);
     let serialize_fields = serialize_fields(&input.fields, input.attrs.offset);
@@ -106,6 +143,25 @@ pub fn derive_serialize(input: TokenStream) -> TokenStream {
     })
 }
 
+fn serialize_enum(input: EnumInput) -> TokenStream {
+    let ident = input.ident;
+    let variants = input.variants.iter().map(|variant| &variant.ident);
+
+    TokenStream::from(quote! {
+        #[automatically_derived]
+        impl serde::Serialize for #ident {
+            fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer
+            {
+                match self {
+                    #(#ident::#variants(__inner) => serde::Serialize::serialize(__inner, serializer),)*
+                }
+            }
+        }
+    })
+}
+
 fn none_fields(fields: &[parse::Field]) -> Vec<proc_macro2::TokenStream> {
     fields
         .iter()
@@ -174,7 +230,13 @@ fn all_fields(fields: &[parse::Field]) -> Vec<proc_macro2::TokenStream> {
 
 #[proc_macro_derive(DeserializeIndexed, attributes(serde, serde_indexed))]
 pub fn derive_deserialize(input: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(input as Input);
+    match parse_macro_input!(input as Input) {
+        Input::Struct(input) => deserialize_struct(input),
+        Input::Enum(input) => deserialize_enum(input),
+    }
+}
+
+fn deserialize_struct(input: StructInput) -> TokenStream {
     let ident = input.ident;
     let mut none_fields = none_fields(&input.fields);
     let mut unwrap_expected_fields = unwrap_expected_fields(&input.fields);
@@ -198,7 +260,7 @@ pub fn derive_deserialize(input: TokenStream) -> TokenStream {
         .into_compile_error()
         .into();
     }
-    let extra_field = extra_fields.get(0);
+    let extra_field = extra_fields.first();
     let handle_extra_fields = if let Some(extra_field) = extra_field {
         none_fields.remove(extra_field.index);
         unwrap_expected_fields.remove(extra_field.index);
@@ -210,18 +272,21 @@ pub fn derive_deserialize(input: TokenStream) -> TokenStream {
             let mut #ident: #ty = ::std::default::Default::default();
         });
 
-        // Add negative fields to the extras map
+        // Add negative and unknown fields to the extras map
         quote! {
-            x if x < 0 => {
+            x => {
                 #ident.insert(x, map.next_value()?);
             }
         }
     } else {
-        // Consume negative fields and throw them away
+        // Consume negative fields and throw them away, unknown fields are an error
         quote! {
             x if x < 0 => {
                 let _: ::serde::de::IgnoredAny = map.next_value()?;
             }
+            _ => {
+                return Err(serde::de::Error::duplicate_field("inexistent field index"));
+            }
         }
     };
 
@@ -235,9 +300,6 @@ pub fn derive_deserialize(input: TokenStream) -> TokenStream {
                 match __serde_indexed_internal_key {
                     #(#match_fields)*
                     #handle_extra_fields
-                    _ => {
-                        return Err(serde::de::Error::duplicate_field("inexistent field index"));
-                    }
                 }
             }
         }
@@ -280,3 +342,81 @@ pub fn derive_deserialize(input: TokenStream) -> TokenStream {
         }
     })
 }
+
+fn deserialize_enum(input: EnumInput) -> TokenStream {
+    let ident = input.ident;
+
+    // Decode the discriminator first, since it selects the variant
+    let read_tag = input.discriminator.map(|discriminator| {
+        quote! {
+            let __serde_indexed_internal_tag: ::std::option::Option<i128> =
+                match __serde_indexed_internal_entries.iter().find(|(key, _)| *key == #discriminator) {
+                    ::std::option::Option::Some((_, value)) => ::std::option::Option::Some(
+                        crate::derive_helpers::from_buffered(::std::clone::Clone::clone(value))?,
+                    ),
+                    ::std::option::Option::None => ::std::option::Option::None,
+                };
+        }
+    });
+
+    let select_variants = input.variants.iter().map(|variant| {
+        let variant_ident = &variant.ident;
+        let ty = &variant.ty;
+        let tag = variant.tag.map(|tag| {
+            let tag = proc_macro2::Literal::i128_unsuffixed(tag);
+            quote! { && __serde_indexed_internal_tag == ::std::option::Option::Some(#tag) }
+        });
+        let required_keys = &variant.required_keys;
+        quote! {
+            if true #tag #(&& __serde_indexed_internal_entries.iter().any(|(key, _)| *key == #required_keys))* {
+                let value: #ty = crate::derive_helpers::from_buffered(
+                    crate::derive_helpers::buffered_map(__serde_indexed_internal_entries),
+                )?;
+                return ::std::result::Result::Ok(#ident::#variant_ident(value));
+            }
+        }
+    });
+
+    TokenStream::from(quote! {
+        #[automatically_derived]
+        impl<'de> serde::Deserialize<'de> for #ident {
+            fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                struct IndexedVisitor;
+
+                impl<'de> serde::de::Visitor<'de> for IndexedVisitor {
+                    type Value = #ident;
+
+                    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+                        formatter.write_str(stringify!(#ident))
+                    }
+
+                    fn visit_map<V>(self, mut map: V) -> core::result::Result<#ident, V::Error>
+                    where
+                        V: serde::de::MapAccess<'de>,
+                    {
+                        let mut __serde_indexed_internal_entries: ::std::vec::Vec<(isize, crate::derive_helpers::Buffered)> =
+                            ::std::vec::Vec::new();
+                        while let Some(__serde_indexed_internal_key) = map.next_key::<isize>()? {
+                            __serde_indexed_internal_entries.push((__serde_indexed_internal_key, map.next_value()?));
+                        }
+
+                        #read_tag
+
+                        #(#select_variants)*
+
+                        Err(serde::de::Error::custom(concat!(
+                            "no variant of ",
+                            stringify!(#ident),
+                            " matches the keys"
+                        )))
+                    }
+                }
+
+                deserializer.deserialize_map(IndexedVisitor {})
+            }
+        }
+    })
+}
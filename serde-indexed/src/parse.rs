@@ -2,15 +2,27 @@ use proc_macro2::Span;
 use syn::parse::{Error, Parse, ParseStream, Result};
 use syn::{Data, DeriveInput, Fields, Ident, Token};
 
-pub struct Input {
+pub enum Input {
+    Struct(StructInput),
+    Enum(EnumInput),
+}
+
+pub struct StructInput {
     pub ident: Ident,
     pub attrs: StructAttrs,
     pub fields: Vec<Field>,
 }
 
+pub struct EnumInput {
+    pub ident: Ident,
+    pub discriminator: Option<isize>,
+    pub variants: Vec<Variant>,
+}
+
 pub struct StructAttrs {
     pub offset: isize,
     pub emit_length: bool,
+    pub discriminator: Option<isize>,
 }
 
 impl Default for StructAttrs {
@@ -18,10 +30,21 @@ impl Default for StructAttrs {
         Self {
             offset: 0,
             emit_length: true,
+            discriminator: None,
         }
     }
 }
 
+/// A newtype variant of an enum, wrapping an indexed struct
+pub struct Variant {
+    pub ident: Ident,
+    pub ty: syn::Type,
+    /// Value of the discriminator key selecting this variant
+    pub tag: Option<i128>,
+    /// Keys which must be present to select this variant
+    pub required_keys: Vec<isize>,
+}
+
 pub struct Field {
     pub label: String,
     pub ident: syn::Ident,
@@ -29,7 +52,6 @@ pub struct Field {
     pub skip_serializing_if: Option<syn::ExprPath>,
     pub collect_extras: bool,
     pub ty: syn::Type,
-    pub original: syn::Field,
 }
 
 #[allow(clippy::single_match)]
@@ -46,6 +68,10 @@ fn parse_meta(attrs: &mut StructAttrs, meta: &syn::Meta) -> Result<()> {
                         if let syn::Lit::Bool(emit_length) = &name_value.lit {
                             attrs.emit_length = emit_length.value;
                         }
+                    } else if name_value.path.is_ident("discriminator") {
+                        if let syn::Lit::Int(discriminator) = &name_value.lit {
+                            attrs.discriminator = Some(discriminator.base10_parse()?);
+                        }
                     }
                 }
                 _ => {}
@@ -78,14 +104,27 @@ impl Parse for Input {
         let call_site = Span::call_site();
         let derive_input = DeriveInput::parse(input)?;
 
+        let attrs: StructAttrs = parse_attrs(&derive_input.attrs)?;
+
         let data: syn::DataStruct = match derive_input.data {
             Data::Struct(data) => data,
+            Data::Enum(data) => {
+                return Ok(Input::Enum(EnumInput {
+                    ident: derive_input.ident,
+                    discriminator: attrs.discriminator,
+                    variants: variants_from_ast(&data.variants, attrs.discriminator)?,
+                }));
+            }
             _ => {
-                return Err(Error::new(call_site, "input must be a struct"));
+                return Err(Error::new(call_site, "input must be a struct or an enum"));
             }
         };
-
-        let attrs: StructAttrs = parse_attrs(&derive_input.attrs)?;
+        if attrs.discriminator.is_some() {
+            return Err(Error::new(
+                call_site,
+                "the discriminator attribute is only supported on enums",
+            ));
+        }
 
         let syn_fields: syn::FieldsNamed = match data.fields {
             Fields::Named(named_fields) => named_fields,
@@ -96,14 +135,97 @@ impl Parse for Input {
 
         let fields = fields_from_ast(&syn_fields.named);
 
-        Ok(Input {
+        Ok(Input::Struct(StructInput {
             ident: derive_input.ident,
             attrs,
             fields,
-        })
+        }))
     }
 }
 
+fn variants_from_ast(
+    variants: &syn::punctuated::Punctuated<syn::Variant, Token![,]>,
+    discriminator: Option<isize>,
+) -> Result<Vec<Variant>> {
+    variants
+        .iter()
+        .map(|variant| {
+            let ty = match &variant.fields {
+                Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                    fields.unnamed[0].ty.clone()
+                }
+                _ => {
+                    return Err(Error::new(
+                        variant.ident.span(),
+                        "enum variants must wrap exactly one indexed struct",
+                    ));
+                }
+            };
+
+            // parse: #[serde_indexed(tag = 1)] and #[serde_indexed(requires(1, 2))]
+            let mut tag = None;
+            let mut required_keys = Vec::new();
+            for attr in &variant.attrs {
+                if !attr.path.is_ident("serde_indexed") {
+                    continue;
+                }
+                if let syn::Meta::List(value) = attr.parse_meta()? {
+                    for meta in &value.nested {
+                        match meta {
+                            syn::NestedMeta::Meta(syn::Meta::NameValue(name_value))
+                                if name_value.path.is_ident("tag") =>
+                            {
+                                if discriminator.is_none() {
+                                    return Err(Error::new_spanned(
+                                        name_value,
+                                        "a tag requires a discriminator attribute on the enum",
+                                    ));
+                                }
+                                match &name_value.lit {
+                                    syn::Lit::Int(lit) => tag = Some(lit.base10_parse()?),
+                                    lit => {
+                                        return Err(Error::new_spanned(
+                                            lit,
+                                            "the tag must be an integer",
+                                        ))
+                                    }
+                                }
+                            }
+                            syn::NestedMeta::Meta(syn::Meta::List(list))
+                                if list.path.is_ident("requires") =>
+                            {
+                                for key in &list.nested {
+                                    match key {
+                                        syn::NestedMeta::Lit(syn::Lit::Int(lit)) => {
+                                            required_keys.push(lit.base10_parse()?)
+                                        }
+                                        key => {
+                                            return Err(Error::new_spanned(
+                                                key,
+                                                "required keys must be integers",
+                                            ))
+                                        }
+                                    }
+                                }
+                            }
+                            meta => {
+                                return Err(Error::new_spanned(meta, "unknown variant attribute"));
+                            }
+                        }
+                    }
+                }
+            }
+
+            Ok(Variant {
+                ident: variant.ident.clone(),
+                ty,
+                tag,
+                required_keys,
+            })
+        })
+        .collect()
+}
+
 fn fields_from_ast(fields: &syn::punctuated::Punctuated<syn::Field, Token![,]>) -> Vec<Field> {
     // serde::internals::ast.rs:L183
     fields
@@ -162,9 +284,7 @@ fn fields_from_ast(fields: &syn::punctuated::Punctuated<syn::Field, Token![,]>)
                     if attr.path.is_ident("serde_indexed") {
                         if let Ok(syn::Meta::List(value)) = attr.parse_meta() {
                             for meta in &value.nested {
-                                if let syn::NestedMeta::Meta(syn::Meta::Path(path)) =
-                                    meta
-                                {
+                                if let syn::NestedMeta::Meta(syn::Meta::Path(path)) = meta {
                                     if path.is_ident("extras") {
                                         collect_extras = true;
                                     } else {
@@ -179,7 +299,6 @@ fn fields_from_ast(fields: &syn::punctuated::Punctuated<syn::Field, Token![,]>)
                 collect_extras
             },
             ty: field.ty.clone(),
-            original: field.clone(),
         })
         .collect()
 }
@@ -48,6 +48,7 @@ mod some_keys {
         pub vector: heapless::Vec<u8, 16>,
     }
 
+    #[allow(dead_code)]
     #[derive(Clone, Debug, PartialEq, SerializeIndexed, DeserializeIndexed)]
     // #[serde_indexed(offset = 1)]
     pub struct NakedOption {
@@ -56,6 +57,7 @@ mod some_keys {
         pub key: bool,
     }
 
+    #[allow(dead_code)]
     #[derive(Clone, Debug, PartialEq, SerializeIndexed, DeserializeIndexed)]
     // #[serde_indexed(offset = 1)]
     pub struct EmptyStruct {}
@@ -132,11 +134,109 @@ mod some_keys {
     }
 }
 
+mod versioned {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, SerializeIndexed, DeserializeIndexed)]
+    pub struct V1 {
+        pub version: u8,
+        pub name: heapless::String<10>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, SerializeIndexed, DeserializeIndexed)]
+    pub struct V2 {
+        pub version: u8,
+        pub name: heapless::String<10>,
+        pub count: u32,
+    }
+
+    #[derive(Clone, Debug, PartialEq, SerializeIndexed, DeserializeIndexed)]
+    pub enum ByKeys {
+        #[serde_indexed(requires(2))]
+        V2(V2),
+        V1(V1),
+    }
+
+    #[derive(Clone, Debug, PartialEq, SerializeIndexed, DeserializeIndexed)]
+    #[serde_indexed(discriminator = 0)]
+    pub enum ByTag {
+        #[serde_indexed(tag = 1)]
+        V1(V1),
+        #[serde_indexed(tag = 2)]
+        V2(V2),
+    }
+
+    fn v1() -> V1 {
+        V1 {
+            version: 1,
+            name: heapless::String::from("one"),
+        }
+    }
+
+    fn v2() -> V2 {
+        V2 {
+            version: 2,
+            name: heapless::String::from("two"),
+            count: 42,
+        }
+    }
+
+    #[test]
+    fn select_by_keys() {
+        let mut buffer = [0u8; 64];
+        let size = cbor_serialize(&v2(), &mut buffer).unwrap();
+        let decoded: ByKeys = cbor_deserialize(&mut buffer[..size]).unwrap();
+        assert_eq!(decoded, ByKeys::V2(v2()));
+
+        let size = cbor_serialize(&v1(), &mut buffer).unwrap();
+        let decoded: ByKeys = cbor_deserialize(&mut buffer[..size]).unwrap();
+        assert_eq!(decoded, ByKeys::V1(v1()));
+    }
+
+    #[test]
+    fn select_by_tag() {
+        let mut buffer = [0u8; 64];
+        let size = cbor_serialize(&v2(), &mut buffer).unwrap();
+        let decoded: ByTag = cbor_deserialize(&mut buffer[..size]).unwrap();
+        assert_eq!(decoded, ByTag::V2(v2()));
+
+        // A version 1 value with an unknown tag matches no variant
+        let mut unknown = v1();
+        unknown.version = 3;
+        let size = cbor_serialize(&unknown, &mut buffer).unwrap();
+        assert!(cbor_deserialize::<ByTag>(&mut buffer[..size]).is_err());
+    }
+
+    #[test]
+    fn serialize_inner() {
+        let mut expected = [0u8; 64];
+        let expected_size = cbor_serialize(&v1(), &mut expected).unwrap();
+        let mut buffer = [0u8; 64];
+        let size = cbor_serialize(&ByTag::V1(v1()), &mut buffer).unwrap();
+        assert_eq!(&buffer[..size], &expected[..expected_size]);
+    }
+}
+
 mod derive_helpers {
+    use serde::de::{DeserializeOwned, Error, Visitor};
     use serde::{Deserialize, Deserializer};
-    use serde::de::{Error, Visitor};
     use std::marker::PhantomData;
 
+    pub type Buffered = serde_cbor::Value;
+
+    pub fn buffered_map(entries: Vec<(isize, Buffered)>) -> Buffered {
+        Buffered::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (Buffered::Integer(key as i128), value))
+                .collect(),
+        )
+    }
+
+    pub fn from_buffered<T: DeserializeOwned, E: Error>(value: Buffered) -> Result<T, E> {
+        serde_cbor::value::from_value(value).map_err(E::custom)
+    }
+
     /// If the missing field is of type `Option<T>` then treat is as `None`,
     /// otherwise it is an error.
     ///